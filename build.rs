@@ -0,0 +1,12 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=proto");
+
+    tonic_build::configure()
+        .build_server(true)
+        .build_client(true)
+        .compile(
+            &["proto/vae/v1/vision.proto", "proto/vae/v1/ingest.proto"],
+            &["proto"],
+        )?;
+    Ok(())
+}
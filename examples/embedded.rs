@@ -0,0 +1,90 @@
+// Runs the engine and the configured LLM inside a host application, with no
+// HTTP server. Build with `--no-default-features --features vision,llm-openai`
+// (add llm-anthropic or llm-local for the other providers).
+//
+//     cargo run --example embedded -- rtsp://camera.local/stream
+
+use anyhow::{Context, Result};
+use vae::core::engine::Engine;
+use vae::core::llm::provider;
+use vae::core::llm::types::Message;
+use vae::utils::{config, doctor, logger};
+use vae::utils::egress::EgressConfig;
+use vae::vision::processor::{
+    ColorSpace, Processor, ProcessingDevice, ProcessorConfig,
+};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let source = std::env::args().nth(1)
+        .context("usage: embedded <video file or stream url>")?;
+
+    doctor::check_startup("config/vae.yaml")?;
+    let config = config::load_config("config/vae.yaml")?;
+    let logger = logger::setup_logger();
+
+    let mut engine = Engine::builder()
+        .gpu(false)
+        .threads(2)
+        .build()
+        .await?;
+    engine.start().await?;
+
+    let mut processor = Processor::new(ProcessorConfig {
+        input_size: (640, 640),
+        normalize: true,
+        color_space: ColorSpace::BGR,
+        preprocessing: Vec::new(),
+        batch_size: 1,
+        device: ProcessingDevice::CPU,
+        transcode: Default::default(),
+    })?;
+    processor.start_capture(&source).await?;
+
+    // Whichever provider `llm_provider` names, with its fallbacks and the
+    // context guard in front
+    let cache = provider::build_llm_cache(&config)?;
+    let llm = provider::create_llm(&config, logger.clone(), &EgressConfig::default(), cache)?;
+
+    // The host owns the loop: feed frames in, pull results out, and decide
+    // when the model gets involved
+    let mut frames = 0u64;
+    while let Some(frame) = processor.read_frame().await? {
+        engine.process_frame(frame).await?;
+        frames += 1;
+
+        let result = match engine.get_result().await {
+            Some(result) => result,
+            None => break,
+        };
+        if result.detections.is_empty() {
+            continue;
+        }
+
+        let classes: Vec<&str> = result.detections.iter()
+            .map(|d| d.class_name.as_str())
+            .collect();
+        let prompt = format!(
+            "Frame {} contains: {}. Anything unusual worth flagging?",
+            result.frame_id,
+            classes.join(", ")
+        );
+        let reply = llm.complete(vec![
+            Message::new("system", "You watch camera feeds and flag anything unusual in one sentence."),
+            Message::new("user", &prompt),
+        ]).await?;
+        println!("[frame {}] {}", result.frame_id, reply.content);
+
+        if frames >= 300 {
+            break;
+        }
+    }
+
+    let metrics = engine.get_metrics()?;
+    println!(
+        "processed {} frames, {} errors",
+        metrics.frames_processed, metrics.error_count
+    );
+    engine.stop().await?;
+    Ok(())
+}
@@ -0,0 +1,84 @@
+#![cfg(all(feature = "api", feature = "vision"))]
+
+use std::pin::Pin;
+use std::sync::Arc;
+use futures::{Stream, StreamExt};
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::api::proto::v1::{
+    frame_ingest_server::{FrameIngest, FrameIngestServer},
+    FrameEncoding as ProtoEncoding, PushFrameRequest, PushFrameResponse,
+};
+use crate::vision::sources::{decode_frame, FrameEncoding, PushRegistry};
+
+type PushStream = Pin<Box<dyn Stream<Item = Result<PushFrameResponse, Status>> + Send>>;
+
+// gRPC counterpart of POST /v1/frames. Per-frame failures are reported in
+// the response instead of closing the stream, so one bad frame doesn't
+// cost the producer its connection.
+pub struct FrameIngestService {
+    registry: Arc<PushRegistry>,
+}
+
+impl FrameIngestService {
+    pub fn new(registry: Arc<PushRegistry>) -> Self {
+        Self { registry }
+    }
+
+    pub fn into_server(self) -> FrameIngestServer<Self> {
+        FrameIngestServer::new(self)
+    }
+}
+
+async fn ingest(registry: &PushRegistry, request: PushFrameRequest) -> PushFrameResponse {
+    let mut response = PushFrameResponse {
+        stream_id: request.stream_id.clone(),
+        frame_id: 0,
+        client_ref: request.client_ref.clone(),
+        error: String::new(),
+    };
+
+    let encoding = match ProtoEncoding::try_from(request.encoding) {
+        Ok(ProtoEncoding::Jpeg) => FrameEncoding::Jpeg,
+        Ok(ProtoEncoding::Png) => FrameEncoding::Png,
+        Ok(ProtoEncoding::RawBgr) => FrameEncoding::RawBgr {
+            width: request.width,
+            height: request.height,
+        },
+        _ => {
+            response.error = "Frame encoding must be specified".to_string();
+            return response;
+        }
+    };
+
+    let result = match decode_frame(encoding, &request.data) {
+        Ok(frame) => registry.push(&request.stream_id, frame).await.map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    match result {
+        Ok(frame_id) => response.frame_id = frame_id,
+        Err(error) => response.error = error,
+    }
+    response
+}
+
+#[tonic::async_trait]
+impl FrameIngest for FrameIngestService {
+    type PushFramesStream = PushStream;
+
+    async fn push_frames(
+        &self,
+        request: Request<Streaming<PushFrameRequest>>,
+    ) -> Result<Response<Self::PushFramesStream>, Status> {
+        let registry = self.registry.clone();
+        let responses = request.into_inner().then(move |frame| {
+            let registry = registry.clone();
+            async move {
+                let frame = frame?;
+                Ok(ingest(&registry, frame).await)
+            }
+        });
+
+        Ok(Response::new(Box::pin(responses)))
+    }
+}
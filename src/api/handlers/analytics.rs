@@ -0,0 +1,71 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_web::{get, post, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::core::agent::analytics::ConversationAnalytics;
+use crate::core::export::{ExportDataset, ExportRange, ParquetExporter};
+
+#[derive(Debug, Deserialize)]
+pub struct ExportRequest {
+    pub start: chrono::DateTime<chrono::Utc>,
+    pub end: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub datasets: Option<Vec<ExportDataset>>,
+}
+
+#[post("/v1/analytics/export")]
+pub async fn export(
+    exporter: web::Data<Arc<ParquetExporter>>,
+    request: web::Json<ExportRequest>,
+) -> HttpResponse {
+    let request = request.into_inner();
+    let range = ExportRange { start: request.start, end: request.end };
+    let datasets = request.datasets.unwrap_or_else(|| vec![
+        #[cfg(feature = "vision")]
+        ExportDataset::Detections,
+        ExportDataset::Tracks,
+        ExportDataset::Metrics,
+    ]);
+
+    if range.end <= range.start {
+        return HttpResponse::BadRequest().json(json!({
+            "error": "end must be after start"
+        }));
+    }
+
+    match exporter.export(range, &datasets).await {
+        Ok(manifest) => HttpResponse::Ok().json(manifest),
+        Err(e) => {
+            log::error!("Analytics export failed: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Export failed: {}", e)
+            }))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConversationQuery {
+    pub start: Option<chrono::DateTime<chrono::Utc>>,
+    pub end: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[get("/v1/analytics/conversations")]
+pub async fn conversations(
+    analytics: web::Data<Arc<ConversationAnalytics>>,
+    query: web::Query<ConversationQuery>,
+) -> HttpResponse {
+    let end = query.end.unwrap_or_else(chrono::Utc::now);
+    let start = query.start.unwrap_or(end - chrono::Duration::days(7));
+
+    if end <= start {
+        return HttpResponse::BadRequest().json(json!({
+            "error": "end must be after start"
+        }));
+    }
+
+    HttpResponse::Ok().json(analytics.aggregates(start, end).await)
+}
@@ -0,0 +1,75 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_web::{get, post, put, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::core::agent::canary::{CanaryConfig, CanaryRouter};
+
+#[derive(Debug, Deserialize)]
+pub struct PercentRequest {
+    pub percent: u8,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RollbackRequest {
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[get("/v1/admin/canary")]
+pub async fn get_canary(router: web::Data<Arc<CanaryRouter>>) -> HttpResponse {
+    match router.report().await {
+        Some(report) => HttpResponse::Ok().json(report),
+        None => HttpResponse::Ok().json(json!({
+            "status": "none",
+            "stable": router.stable().await,
+        })),
+    }
+}
+
+#[post("/v1/admin/canary")]
+pub async fn start_canary(
+    router: web::Data<Arc<CanaryRouter>>,
+    config: web::Json<CanaryConfig>,
+) -> HttpResponse {
+    match router.start(config.into_inner()).await {
+        Ok(()) => HttpResponse::Created().json(router.report().await),
+        Err(e) => HttpResponse::Conflict().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[put("/v1/admin/canary/percent")]
+pub async fn set_canary_percent(
+    router: web::Data<Arc<CanaryRouter>>,
+    request: web::Json<PercentRequest>,
+) -> HttpResponse {
+    match router.set_percent(request.percent).await {
+        Ok(()) => HttpResponse::Ok().json(router.report().await),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[post("/v1/admin/canary/rollback")]
+pub async fn rollback_canary(
+    router: web::Data<Arc<CanaryRouter>>,
+    request: web::Json<RollbackRequest>,
+) -> HttpResponse {
+    let reason = request.into_inner().reason.unwrap_or_else(|| "manual rollback".to_string());
+    match router.rollback(&reason).await {
+        Ok(()) => HttpResponse::Ok().json(router.report().await),
+        Err(e) => HttpResponse::Conflict().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[post("/v1/admin/canary/promote")]
+pub async fn promote_canary(router: web::Data<Arc<CanaryRouter>>) -> HttpResponse {
+    match router.promote().await {
+        Ok(stable) => HttpResponse::Ok().json(json!({
+            "stable": stable,
+            "report": router.report().await,
+        })),
+        Err(e) => HttpResponse::Conflict().json(json!({ "error": e.to_string() })),
+    }
+}
@@ -0,0 +1,42 @@
+#![cfg(all(feature = "api", feature = "vision"))]
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use actix_web::{get, post, web, HttpResponse};
+use serde_json::json;
+
+use crate::core::bundle::{ConfigBundle, ConfigBundler, ImportOptions};
+
+#[get("/v1/config/export")]
+pub async fn export_config(bundler: web::Data<Arc<ConfigBundler>>) -> HttpResponse {
+    match bundler.export().await.and_then(|bundle| bundle.to_yaml()) {
+        Ok(yaml) => HttpResponse::Ok().content_type("application/yaml").body(yaml),
+        Err(e) => HttpResponse::InternalServerError().json(json!({ "error": e.to_string() })),
+    }
+}
+
+// The body is a bundle as returned by the export. Query parameters:
+//   dry_run=true   validate only
+//   prune=true     also remove what the bundle doesn't mention
+//   var.<name>=x   sets a template variable
+#[post("/v1/config/import")]
+pub async fn import_config(
+    bundler: web::Data<Arc<ConfigBundler>>,
+    query: web::Query<HashMap<String, String>>,
+    body: String,
+) -> HttpResponse {
+    let flag = |name: &str| query.get(name).is_some_and(|v| v == "true" || v == "1");
+    let options = ImportOptions { dry_run: flag("dry_run"), prune: flag("prune") };
+    let variables: HashMap<String, String> = query.iter()
+        .filter_map(|(k, v)| k.strip_prefix("var.").map(|name| (name.to_string(), v.clone())))
+        .collect();
+
+    let bundle = match ConfigBundle::parse(&body, &variables) {
+        Ok(bundle) => bundle,
+        Err(e) => return HttpResponse::BadRequest().json(json!({ "error": format!("{:#}", e) })),
+    };
+    match bundler.import(bundle, &options).await {
+        Ok(summary) => HttpResponse::Ok().json(summary),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": format!("{:#}", e) })),
+    }
+}
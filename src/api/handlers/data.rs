@@ -0,0 +1,93 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_web::{delete, get, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::api::principal::Principal;
+use crate::core::lifecycle::{DataSubject, LifecycleError, LifecycleManager};
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeletionRequest {
+    pub reason: Option<String>,
+}
+
+// Admins act on any subject; anyone else only on their own identity. The
+// audit trail records the principal, never a name the client supplied.
+fn subject_for(principal: &Principal, kind: &str, id: &str) -> Result<DataSubject, HttpResponse> {
+    let subject = DataSubject::parse(kind, id)
+        .map_err(|e| HttpResponse::BadRequest().json(json!({ "error": e.to_string() })))?;
+    let own = matches!(&subject, DataSubject::Identity(identity) if *identity == principal.subject);
+    if !principal.admin && !own {
+        return Err(HttpResponse::Forbidden().json(json!({
+            "error": "Only admins can act on another subject's data"
+        })));
+    }
+    Ok(subject)
+}
+
+// 501 while a required store is unregistered, so nothing is acknowledged
+// that wasn't done
+fn failure(e: anyhow::Error) -> HttpResponse {
+    if let Some(LifecycleError::MissingStores(missing)) = e.downcast_ref::<LifecycleError>() {
+        return HttpResponse::NotImplemented().json(json!({
+            "error": e.to_string(),
+            "missing": missing,
+        }));
+    }
+    HttpResponse::InternalServerError().json(json!({ "error": e.to_string() }))
+}
+
+#[delete("/v1/data/{kind}/{id}")]
+pub async fn delete_subject(
+    lifecycle: web::Data<Arc<LifecycleManager>>,
+    principal: Principal,
+    path: web::Path<(String, String)>,
+    request: Option<web::Json<DeletionRequest>>,
+) -> HttpResponse {
+    let (kind, id) = path.into_inner();
+    let subject = match subject_for(&principal, &kind, &id) {
+        Ok(subject) => subject,
+        Err(response) => return response,
+    };
+
+    let request = request.map(web::Json::into_inner).unwrap_or_default();
+    match lifecycle.delete_subject(&subject, &principal.subject, request.reason).await {
+        Ok(entry) if entry.is_complete() => HttpResponse::Ok().json(entry),
+        // Partial deletions are reported, not hidden; the operator must retry
+        Ok(entry) => HttpResponse::MultiStatus().json(entry),
+        Err(e) => failure(e),
+    }
+}
+
+#[get("/v1/data/{kind}/{id}/export")]
+pub async fn export_subject(
+    lifecycle: web::Data<Arc<LifecycleManager>>,
+    principal: Principal,
+    path: web::Path<(String, String)>,
+) -> HttpResponse {
+    let (kind, id) = path.into_inner();
+    let subject = match subject_for(&principal, &kind, &id) {
+        Ok(subject) => subject,
+        Err(response) => return response,
+    };
+
+    match lifecycle.export_subject(&subject, &principal.subject).await {
+        Ok(bundle) => HttpResponse::Ok().json(bundle),
+        Err(e) => failure(e),
+    }
+}
+
+#[get("/v1/data/{kind}/{id}/audit")]
+pub async fn subject_audit(
+    lifecycle: web::Data<Arc<LifecycleManager>>,
+    principal: Principal,
+    path: web::Path<(String, String)>,
+) -> HttpResponse {
+    let (kind, id) = path.into_inner();
+    match subject_for(&principal, &kind, &id) {
+        Ok(subject) => HttpResponse::Ok().json(lifecycle.audit_trail(Some(&subject)).await),
+        Err(response) => response,
+    }
+}
@@ -0,0 +1,121 @@
+#![cfg(all(feature = "api", feature = "vision"))]
+
+use std::sync::Arc;
+use actix_web::{delete, get, post, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::core::dead_letter::{DeadLetterFilter, DeadLetterQueue};
+
+#[derive(Debug, Deserialize)]
+pub struct ListQuery {
+    pub stream: Option<String>,
+    pub stage: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl ListQuery {
+    fn filter(&self) -> DeadLetterFilter {
+        DeadLetterFilter { stream: self.stream.clone(), stage: self.stage.clone() }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReinjectRequest {
+    // Explicit ids win over the filter
+    #[serde(default)]
+    pub ids: Vec<String>,
+    #[serde(flatten)]
+    pub filter: DeadLetterFilter,
+    pub limit: Option<usize>,
+}
+
+#[get("/v1/dead-letters")]
+pub async fn list_dead_letters(
+    queue: web::Data<Arc<DeadLetterQueue>>,
+    query: web::Query<ListQuery>,
+) -> HttpResponse {
+    let entries = queue.list(&query.filter(), query.limit.unwrap_or(100)).await;
+    HttpResponse::Ok().json(json!({
+        "entries": entries,
+        "stats": queue.stats().await,
+    }))
+}
+
+#[get("/v1/dead-letters/{id}")]
+pub async fn get_dead_letter(
+    queue: web::Data<Arc<DeadLetterQueue>>,
+    id: web::Path<String>,
+) -> HttpResponse {
+    match queue.get(&id).await {
+        Some(entry) => HttpResponse::Ok().json(entry),
+        None => HttpResponse::NotFound().json(json!({
+            "error": format!("Dead letter not found: {}", id)
+        })),
+    }
+}
+
+#[post("/v1/dead-letters/{id}/reinject")]
+pub async fn reinject_dead_letter(
+    queue: web::Data<Arc<DeadLetterQueue>>,
+    id: web::Path<String>,
+) -> HttpResponse {
+    if queue.get(&id).await.is_none() {
+        return HttpResponse::NotFound().json(json!({
+            "error": format!("Dead letter not found: {}", id)
+        }));
+    }
+    match queue.reinject(&id).await {
+        Ok(()) => HttpResponse::Accepted().json(json!({ "reinjected": [id.as_str()] })),
+        Err(e) => HttpResponse::ServiceUnavailable().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[post("/v1/dead-letters/reinject")]
+pub async fn reinject_dead_letters(
+    queue: web::Data<Arc<DeadLetterQueue>>,
+    request: web::Json<ReinjectRequest>,
+) -> HttpResponse {
+    let request = request.into_inner();
+    let ids = if request.ids.is_empty() {
+        queue.list(&request.filter, request.limit.unwrap_or(100)).await.into_iter().map(|e| e.id).collect()
+    } else {
+        request.ids
+    };
+
+    // Stops at the first failure; what was sent by then stays sent
+    let mut sent = Vec::with_capacity(ids.len());
+    for id in ids {
+        if let Err(e) = queue.reinject(&id).await {
+            let body = json!({ "reinjected": sent, "error": e.to_string() });
+            return if sent.is_empty() {
+                HttpResponse::BadRequest().json(body)
+            } else {
+                HttpResponse::Accepted().json(body)
+            };
+        }
+        sent.push(id);
+    }
+    HttpResponse::Accepted().json(json!({ "reinjected": sent }))
+}
+
+#[delete("/v1/dead-letters/{id}")]
+pub async fn delete_dead_letter(
+    queue: web::Data<Arc<DeadLetterQueue>>,
+    id: web::Path<String>,
+) -> HttpResponse {
+    match queue.remove(&id).await {
+        Some(_) => HttpResponse::NoContent().finish(),
+        None => HttpResponse::NotFound().json(json!({
+            "error": format!("Dead letter not found: {}", id)
+        })),
+    }
+}
+
+#[delete("/v1/dead-letters")]
+pub async fn purge_dead_letters(
+    queue: web::Data<Arc<DeadLetterQueue>>,
+    query: web::Query<ListQuery>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(json!({ "purged": queue.purge(&query.filter()).await }))
+}
@@ -0,0 +1,23 @@
+#![cfg(all(feature = "api", feature = "vision"))]
+
+use std::sync::Arc;
+use actix_web::{get, web, HttpResponse};
+use serde_json::json;
+
+use crate::vision::lpr::{PlateIndex, PlateQuery};
+
+// `GET /v1/detections/plates?plate=AB12CDE&source=gate-1&since=...`
+#[get("/v1/detections/plates")]
+pub async fn plate_hits(
+    index: web::Data<Arc<PlateIndex>>,
+    query: web::Query<PlateQuery>,
+) -> HttpResponse {
+    if let (Some(since), Some(until)) = (query.since, query.until) {
+        if since > until {
+            return HttpResponse::BadRequest().json(json!({ "error": "since must be before until" }));
+        }
+    }
+
+    let hits = index.query(&query).await;
+    HttpResponse::Ok().json(json!({ "count": hits.len(), "plates": hits }))
+}
@@ -0,0 +1,123 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_multipart::Multipart;
+use actix_web::{delete, get, post, web, HttpResponse};
+use actix_web::http::header::{self, ContentDisposition, DispositionParam, DispositionType};
+use futures::{StreamExt, TryStreamExt};
+use serde_json::json;
+
+use crate::api::principal::Principal;
+use crate::core::storage::{FileStore, StoredFile};
+
+#[post("/v1/files")]
+pub async fn upload_file(
+    store: web::Data<Arc<FileStore>>,
+    principal: Principal,
+    mut payload: Multipart,
+) -> HttpResponse {
+    let max_size = store.max_size() as usize;
+
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        if field.name() != "file" {
+            continue;
+        }
+
+        let filename = field.content_disposition()
+            .get_filename()
+            .unwrap_or("upload")
+            .to_string();
+        let content_type = field.content_type()
+            .map(|m| m.to_string())
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+
+        let mut data = Vec::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => return HttpResponse::BadRequest().json(json!({
+                    "error": format!("Failed to read upload: {}", e)
+                })),
+            };
+            // Stop reading early instead of buffering an oversized body
+            if data.len() + chunk.len() > max_size {
+                return HttpResponse::PayloadTooLarge().json(json!({
+                    "error": format!("File exceeds {} bytes", max_size)
+                }));
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        return match store.save(&filename, &content_type, &data, &principal.subject, principal.tenant.as_deref()).await {
+            Ok(file) => HttpResponse::Created().json(file),
+            Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+        };
+    }
+
+    HttpResponse::BadRequest().json(json!({
+        "error": "Missing multipart field 'file'"
+    }))
+}
+
+// Someone else's file answers exactly like a missing one, so ids can't be
+// probed. Files stored before uploads were attributed are admin-only.
+async fn owned(store: &FileStore, principal: &Principal, id: &str) -> Option<StoredFile> {
+    store.get(id).await.filter(|file| principal.can_access(file.owner.as_deref().unwrap_or("")))
+}
+
+fn not_found(id: &str) -> HttpResponse {
+    HttpResponse::NotFound().json(json!({
+        "error": format!("File not found: {}", id)
+    }))
+}
+
+#[get("/v1/files/{id}")]
+pub async fn get_file(
+    store: web::Data<Arc<FileStore>>,
+    principal: Principal,
+    id: web::Path<String>,
+) -> HttpResponse {
+    match owned(&store, &principal, &id).await {
+        Some(file) => HttpResponse::Ok().json(file),
+        None => not_found(&id),
+    }
+}
+
+// Always a download: an uploaded text/html or SVG served inline would run
+// in the API's origin
+#[get("/v1/files/{id}/content")]
+pub async fn get_file_content(
+    store: web::Data<Arc<FileStore>>,
+    principal: Principal,
+    id: web::Path<String>,
+) -> HttpResponse {
+    if owned(&store, &principal, &id).await.is_none() {
+        return not_found(&id);
+    }
+    match store.read(&id).await {
+        Ok((file, data)) => HttpResponse::Ok()
+            .content_type(file.content_type)
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(file.filename)],
+            })
+            .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+            .body(data),
+        Err(e) => HttpResponse::NotFound().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[delete("/v1/files/{id}")]
+pub async fn delete_file(
+    store: web::Data<Arc<FileStore>>,
+    principal: Principal,
+    id: web::Path<String>,
+) -> HttpResponse {
+    if owned(&store, &principal, &id).await.is_none() {
+        return not_found(&id);
+    }
+    match store.delete(&id).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => HttpResponse::NotFound().json(json!({ "error": e.to_string() })),
+    }
+}
@@ -0,0 +1,42 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_web::{get, post, web, HttpResponse};
+use serde_json::json;
+
+use crate::api::principal::Principal;
+use crate::core::llm::finetune::{FineTuneManager, FineTuneRequest};
+
+// Admin only: the sessions' transcripts are uploaded to the provider
+#[post("/v1/finetunes")]
+pub async fn create_finetune(
+    manager: web::Data<Arc<FineTuneManager>>,
+    principal: Principal,
+    request: web::Json<FineTuneRequest>,
+) -> HttpResponse {
+    if !principal.admin {
+        return HttpResponse::Forbidden().json(json!({ "error": "Fine-tuning requires an admin" }));
+    }
+    match manager.submit(request.into_inner(), principal.owner_filter()).await {
+        Ok(job) => HttpResponse::Accepted().json(job),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[get("/v1/finetunes")]
+pub async fn list_finetunes(manager: web::Data<Arc<FineTuneManager>>) -> HttpResponse {
+    HttpResponse::Ok().json(manager.list().await)
+}
+
+#[get("/v1/finetunes/{id}")]
+pub async fn get_finetune(
+    manager: web::Data<Arc<FineTuneManager>>,
+    id: web::Path<String>,
+) -> HttpResponse {
+    match manager.get(&id).await {
+        Some(job) => HttpResponse::Ok().json(job),
+        None => HttpResponse::NotFound().json(json!({
+            "error": format!("Fine-tune job not found: {}", id)
+        })),
+    }
+}
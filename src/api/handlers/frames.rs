@@ -0,0 +1,56 @@
+#![cfg(all(feature = "api", feature = "vision"))]
+
+use std::sync::Arc;
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::api::middleware::quota::MeteredUsage;
+use crate::vision::sources::{decode_frame, FrameEncoding, PushError, PushRegistry};
+
+#[derive(Debug, Deserialize)]
+pub struct PushFrameQuery {
+    pub stream: String,
+    // Required for raw BGR bodies only
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+}
+
+// Body is the encoded frame: image/jpeg, image/png, or
+// application/octet-stream for packed BGR with width/height in the query
+#[post("/v1/frames")]
+pub async fn push_frame(
+    registry: web::Data<Arc<PushRegistry>>,
+    query: web::Query<PushFrameQuery>,
+    request: HttpRequest,
+    body: web::Bytes,
+) -> HttpResponse {
+    let content_type = request.headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+
+    let encoding = match FrameEncoding::from_content_type(content_type, query.width, query.height) {
+        Ok(encoding) => encoding,
+        Err(e) => return HttpResponse::UnsupportedMediaType().json(json!({ "error": e.to_string() })),
+    };
+    let frame = match decode_frame(encoding, &body) {
+        Ok(frame) => frame,
+        Err(e) => return HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    };
+
+    match registry.push(&query.stream, frame).await {
+        Ok(frame_id) => MeteredUsage { tokens: 0, frames: 1 }.attach(HttpResponse::Accepted().json(json!({
+            "stream_id": query.stream,
+            "frame_id": frame_id,
+        }))),
+        Err(PushError::UnknownStream) => HttpResponse::NotFound().json(json!({
+            "error": format!("Stream {} has no push source", query.stream)
+        })),
+        Err(PushError::Backlogged) => HttpResponse::TooManyRequests()
+            .insert_header(("Retry-After", "1"))
+            .json(json!({ "error": PushError::Backlogged.to_string() })),
+    }
+}
@@ -0,0 +1,31 @@
+#![cfg(all(feature = "api", feature = "vision"))]
+
+use std::sync::Arc;
+use actix_web::{get, web, HttpResponse};
+use serde_json::json;
+
+use crate::core::geo::{to_geojson, GeoEventIndex, GeoQuery};
+
+#[get("/v1/events/geo")]
+pub async fn events_within_radius(
+    index: web::Data<Arc<GeoEventIndex>>,
+    query: web::Query<GeoQuery>,
+) -> HttpResponse {
+    match index.within_radius(&query).await {
+        Ok(events) => HttpResponse::Ok().json(json!({ "events": events })),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[get("/v1/events/geo.geojson")]
+pub async fn events_geojson(
+    index: web::Data<Arc<GeoEventIndex>>,
+    query: web::Query<GeoQuery>,
+) -> HttpResponse {
+    match index.within_radius(&query).await {
+        Ok(events) => HttpResponse::Ok()
+            .content_type("application/geo+json")
+            .json(to_geojson(&events)),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
@@ -0,0 +1,183 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_web::{get, post, web, HttpResponse};
+use futures::stream;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::core::incidents::{EvidenceClip, IncidentFilter, IncidentManager};
+use crate::core::rag::history::{incident_record, HistoryIndexer, HistoryKind};
+
+#[derive(Debug, Deserialize)]
+pub struct ActorRequest {
+    pub by: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssignRequest {
+    pub assignee: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NoteRequest {
+    pub author: String,
+    pub text: String,
+}
+
+fn incident_error(e: anyhow::Error) -> HttpResponse {
+    let message = e.to_string();
+    if message.starts_with("Incident not found") {
+        HttpResponse::NotFound().json(json!({ "error": message }))
+    } else {
+        HttpResponse::BadRequest().json(json!({ "error": message }))
+    }
+}
+
+#[get("/v1/incidents")]
+pub async fn list_incidents(
+    incidents: web::Data<Arc<IncidentManager>>,
+    filter: web::Query<IncidentFilter>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(incidents.list(&filter).await)
+}
+
+// Server-sent events for on-call UIs. Registered before /v1/incidents/{id}
+// so "events" isn't taken for an incident id.
+#[get("/v1/incidents/events")]
+pub async fn incident_events(incidents: web::Data<Arc<IncidentManager>>) -> HttpResponse {
+    let receiver = incidents.subscribe();
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let payload = serde_json::to_string(&event).unwrap_or_default();
+                    let chunk = web::Bytes::from(format!("data: {}\n\n", payload));
+                    return Some((Ok::<_, actix_web::Error>(chunk), receiver));
+                }
+                // The UI refetches the list on reconnect, so skipped events
+                // are only a missed animation
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!("Incident event subscriber lagged by {} events", skipped);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events)
+}
+
+#[get("/v1/incidents/{id}")]
+pub async fn get_incident(
+    incidents: web::Data<Arc<IncidentManager>>,
+    id: web::Path<String>,
+) -> HttpResponse {
+    match incidents.get(&id).await {
+        Some(incident) => HttpResponse::Ok().json(incident),
+        None => HttpResponse::NotFound().json(json!({
+            "error": format!("Incident not found: {}", id)
+        })),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SimilarQuery {
+    pub limit: Option<usize>,
+}
+
+// Earlier incidents resembling this one, with links
+#[get("/v1/incidents/{id}/similar")]
+pub async fn similar_incidents(
+    incidents: web::Data<Arc<IncidentManager>>,
+    history: web::Data<Arc<HistoryIndexer>>,
+    id: web::Path<String>,
+    query: web::Query<SimilarQuery>,
+) -> HttpResponse {
+    let Some(incident) = incidents.get(&id).await else {
+        return HttpResponse::NotFound().json(json!({
+            "error": format!("Incident not found: {}", id)
+        }));
+    };
+    let text = incident_record(&incident).text;
+    match history.similar(&text, &[HistoryKind::Incident], query.limit, Some(&incident.id)).await {
+        Ok(records) => HttpResponse::Ok().json(records),
+        Err(e) => HttpResponse::InternalServerError().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[post("/v1/incidents/{id}/ack")]
+pub async fn acknowledge_incident(
+    incidents: web::Data<Arc<IncidentManager>>,
+    id: web::Path<String>,
+    request: web::Json<ActorRequest>,
+) -> HttpResponse {
+    match incidents.acknowledge(&id, &request.by).await {
+        Ok(incident) => HttpResponse::Ok().json(incident),
+        Err(e) => incident_error(e),
+    }
+}
+
+#[post("/v1/incidents/{id}/resolve")]
+pub async fn resolve_incident(
+    incidents: web::Data<Arc<IncidentManager>>,
+    id: web::Path<String>,
+    request: web::Json<ActorRequest>,
+) -> HttpResponse {
+    match incidents.resolve(&id, &request.by).await {
+        Ok(incident) => HttpResponse::Ok().json(incident),
+        Err(e) => incident_error(e),
+    }
+}
+
+#[post("/v1/incidents/{id}/reopen")]
+pub async fn reopen_incident(
+    incidents: web::Data<Arc<IncidentManager>>,
+    id: web::Path<String>,
+    request: web::Json<ActorRequest>,
+) -> HttpResponse {
+    match incidents.reopen(&id, &request.by).await {
+        Ok(incident) => HttpResponse::Ok().json(incident),
+        Err(e) => incident_error(e),
+    }
+}
+
+#[post("/v1/incidents/{id}/assign")]
+pub async fn assign_incident(
+    incidents: web::Data<Arc<IncidentManager>>,
+    id: web::Path<String>,
+    request: web::Json<AssignRequest>,
+) -> HttpResponse {
+    match incidents.assign(&id, request.into_inner().assignee).await {
+        Ok(incident) => HttpResponse::Ok().json(incident),
+        Err(e) => incident_error(e),
+    }
+}
+
+#[post("/v1/incidents/{id}/notes")]
+pub async fn add_incident_note(
+    incidents: web::Data<Arc<IncidentManager>>,
+    id: web::Path<String>,
+    request: web::Json<NoteRequest>,
+) -> HttpResponse {
+    match incidents.add_note(&id, &request.author, &request.text).await {
+        Ok(incident) => HttpResponse::Created().json(incident),
+        Err(e) => incident_error(e),
+    }
+}
+
+#[post("/v1/incidents/{id}/evidence")]
+pub async fn attach_incident_evidence(
+    incidents: web::Data<Arc<IncidentManager>>,
+    id: web::Path<String>,
+    clip: web::Json<EvidenceClip>,
+) -> HttpResponse {
+    match incidents.attach_evidence(&id, clip.into_inner()).await {
+        Ok(incident) => HttpResponse::Created().json(incident),
+        Err(e) => incident_error(e),
+    }
+}
@@ -0,0 +1,53 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_web::{get, post, web, HttpResponse};
+use serde_json::json;
+
+use crate::core::jobs::JobManager;
+use crate::core::rag::reindex::Reindexer;
+
+#[get("/v1/jobs")]
+pub async fn list_jobs(jobs: web::Data<Arc<JobManager>>) -> HttpResponse {
+    HttpResponse::Ok().json(jobs.list().await)
+}
+
+#[get("/v1/jobs/{id}")]
+pub async fn get_job(
+    jobs: web::Data<Arc<JobManager>>,
+    id: web::Path<String>,
+) -> HttpResponse {
+    match jobs.get(&id).await {
+        Some(job) => HttpResponse::Ok().json(job),
+        None => HttpResponse::NotFound().json(json!({
+            "error": format!("Job not found: {}", id)
+        })),
+    }
+}
+
+#[post("/v1/jobs/{id}/cancel")]
+pub async fn cancel_job(
+    jobs: web::Data<Arc<JobManager>>,
+    id: web::Path<String>,
+) -> HttpResponse {
+    match jobs.cancel(&id).await {
+        Ok(()) => HttpResponse::Accepted().finish(),
+        Err(e) => HttpResponse::Conflict().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[post("/v1/rag/reindex")]
+pub async fn reindex(reindexer: web::Data<Arc<Reindexer>>) -> HttpResponse {
+    match reindexer.start_reembed().await {
+        Ok(job) => HttpResponse::Accepted().json(job),
+        Err(e) => HttpResponse::Conflict().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[post("/v1/rag/compact")]
+pub async fn compact(reindexer: web::Data<Arc<Reindexer>>) -> HttpResponse {
+    match reindexer.start_compaction().await {
+        Ok(job) => HttpResponse::Accepted().json(job),
+        Err(e) => HttpResponse::Conflict().json(json!({ "error": e.to_string() })),
+    }
+}
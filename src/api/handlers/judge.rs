@@ -0,0 +1,31 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_web::{get, post, web, HttpResponse};
+use serde_json::json;
+
+use crate::api::middleware::quota::MeteredUsage;
+use crate::core::llm::judge::{Judge, JudgeRequest};
+
+#[post("/v1/judge/score")]
+pub async fn score(
+    judge: web::Data<Arc<Judge>>,
+    request: web::Json<JudgeRequest>,
+) -> HttpResponse {
+    if let Some(Err(e)) = request.rubric.as_ref().map(|r| r.validate()) {
+        return HttpResponse::BadRequest().json(json!({ "error": e.to_string() }));
+    }
+    match judge.score(&request).await {
+        Ok(score) => MeteredUsage { tokens: score.tokens_used, frames: 0 }
+            .attach(HttpResponse::Ok().json(score)),
+        Err(e) => {
+            log::error!("Judge scoring failed: {}", e);
+            HttpResponse::BadGateway().json(json!({ "error": e.to_string() }))
+        }
+    }
+}
+
+#[get("/v1/judge/summary")]
+pub async fn summary(judge: web::Data<Arc<Judge>>) -> HttpResponse {
+    HttpResponse::Ok().json(judge.summary().await)
+}
@@ -0,0 +1,86 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_web::{get, post, put, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::api::principal::Principal;
+use crate::core::agent::sessions::SessionManager;
+use crate::core::i18n::{language_name, Localizer};
+
+#[derive(Debug, Deserialize)]
+pub struct LanguageRequest {
+    // None clears the preference
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DetectRequest {
+    pub text: String,
+}
+
+#[get("/v1/languages")]
+pub async fn get_languages(localizer: web::Data<Arc<Localizer>>) -> HttpResponse {
+    let config = localizer.config();
+    HttpResponse::Ok().json(json!({
+        "default": localizer.default_language(),
+        "supported": config.supported,
+        "detect_input": config.detect_input,
+    }))
+}
+
+#[post("/v1/languages/detect")]
+pub async fn detect_language(
+    localizer: web::Data<Arc<Localizer>>,
+    request: web::Json<DetectRequest>,
+) -> HttpResponse {
+    let language = localizer.detect(&request.text);
+    HttpResponse::Ok().json(json!({
+        "name": language.as_deref().map(language_name),
+        "language": language,
+    }))
+}
+
+// Admins can set any tenant's language, other callers only their own
+#[put("/v1/tenants/{tenant}/language")]
+pub async fn set_tenant_language(
+    localizer: web::Data<Arc<Localizer>>,
+    principal: Principal,
+    tenant: web::Path<String>,
+    request: web::Json<LanguageRequest>,
+) -> HttpResponse {
+    if !principal.admin && principal.tenant.as_deref() != Some(tenant.as_str()) {
+        return HttpResponse::Forbidden().json(json!({ "error": format!("Not a member of tenant {}", tenant) }));
+    }
+    match localizer.set_tenant(&tenant, request.language.as_deref()).await {
+        Ok(()) => HttpResponse::Ok().json(json!({
+            "tenant": tenant.as_str(),
+            "language": localizer.tenant_language(&tenant).await,
+        })),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+// Only for a session the caller can reach; anything else is not found,
+// as in the session endpoints
+#[put("/v1/sessions/{id}/language")]
+pub async fn set_session_language(
+    localizer: web::Data<Arc<Localizer>>,
+    sessions: web::Data<Arc<SessionManager>>,
+    principal: Principal,
+    id: web::Path<String>,
+    request: web::Json<LanguageRequest>,
+) -> HttpResponse {
+    let reachable = sessions.info(&id).await.is_some_and(|info| principal.can_access(&info.owner));
+    if !reachable {
+        return HttpResponse::NotFound().json(json!({ "error": format!("Session not found: {}", id) }));
+    }
+    match localizer.set_session(&id, request.language.as_deref()).await {
+        Ok(()) => HttpResponse::Ok().json(json!({
+            "session_id": id.as_str(),
+            "language": localizer.session_language(&id).await,
+        })),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
@@ -0,0 +1,47 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use std::time::Duration;
+use actix_web::{get, put, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::api::middleware::maintenance::MaintenanceMode;
+
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceRequest {
+    pub enabled: bool,
+    #[serde(default)]
+    pub message: Option<String>,
+    // When enabling, wait up to this long for in-flight requests to finish
+    // before answering, so the caller knows when it is safe to upgrade
+    #[serde(default)]
+    pub drain_timeout_secs: Option<u64>,
+}
+
+#[get("/v1/admin/maintenance")]
+pub async fn get_maintenance(mode: web::Data<Arc<MaintenanceMode>>) -> HttpResponse {
+    HttpResponse::Ok().json(mode.status().await)
+}
+
+#[put("/v1/admin/maintenance")]
+pub async fn set_maintenance(
+    mode: web::Data<Arc<MaintenanceMode>>,
+    request: web::Json<MaintenanceRequest>,
+) -> HttpResponse {
+    let request = request.into_inner();
+    if !request.enabled {
+        mode.disable().await;
+        return HttpResponse::Ok().json(mode.status().await);
+    }
+
+    mode.enable(request.message).await;
+    let drained = match request.drain_timeout_secs {
+        Some(secs) => Some(mode.drain(Duration::from_secs(secs.min(3600))).await),
+        None => None,
+    };
+    HttpResponse::Ok().json(json!({
+        "status": mode.status().await,
+        "drained": drained,
+    }))
+}
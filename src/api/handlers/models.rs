@@ -0,0 +1,72 @@
+#![cfg(all(feature = "api", feature = "vision"))]
+
+use std::sync::Arc;
+use actix_web::{delete, get, post, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::vision::shadow::{build_detection_model, DetectionModelConfig, ShadowDeployment};
+
+// The candidate is configured like a production model: a single detector
+// or an ensemble of them, optionally behind a cascade gate
+#[derive(Debug, Deserialize)]
+pub struct ShadowRequest {
+    pub name: String,
+    #[serde(flatten)]
+    pub model: DetectionModelConfig,
+}
+
+#[get("/v1/models/shadow")]
+pub async fn shadow_report(deployment: web::Data<Arc<ShadowDeployment>>) -> HttpResponse {
+    HttpResponse::Ok().json(deployment.report().await)
+}
+
+// Loads the candidate and starts comparing it against production; any
+// earlier candidate and its statistics are replaced
+#[post("/v1/models/shadow")]
+pub async fn start_shadow(
+    deployment: web::Data<Arc<ShadowDeployment>>,
+    request: web::Json<ShadowRequest>,
+) -> HttpResponse {
+    let request = request.into_inner();
+    let name = request.name.clone();
+    let candidate = match build_detection_model(request.model).await {
+        Ok(candidate) => candidate,
+        Err(e) => {
+            log::error!("Failed to load shadow model {}: {}", name, e);
+            return HttpResponse::BadRequest().json(json!({ "error": e.to_string() }));
+        }
+    };
+
+    deployment.start_shadow(&name, candidate).await;
+    HttpResponse::Created().json(deployment.report().await)
+}
+
+#[delete("/v1/models/shadow")]
+pub async fn stop_shadow(deployment: web::Data<Arc<ShadowDeployment>>) -> HttpResponse {
+    match deployment.stop_shadow().await {
+        Some(_) => HttpResponse::NoContent().finish(),
+        None => HttpResponse::NotFound().json(json!({ "error": "No candidate model in shadow" })),
+    }
+}
+
+#[post("/v1/models/shadow/promote")]
+pub async fn promote_shadow(deployment: web::Data<Arc<ShadowDeployment>>) -> HttpResponse {
+    // Captured first: the comparison is what justified the promotion
+    let report = deployment.report().await;
+    match deployment.promote().await {
+        Ok(production) => HttpResponse::Ok().json(json!({
+            "production": production,
+            "shadow_report": report,
+        })),
+        Err(e) => HttpResponse::Conflict().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[post("/v1/models/rollback")]
+pub async fn rollback_model(deployment: web::Data<Arc<ShadowDeployment>>) -> HttpResponse {
+    match deployment.rollback().await {
+        Ok(production) => HttpResponse::Ok().json(json!({ "production": production })),
+        Err(e) => HttpResponse::Conflict().json(json!({ "error": e.to_string() })),
+    }
+}
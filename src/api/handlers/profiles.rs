@@ -0,0 +1,101 @@
+#![cfg(all(feature = "api", feature = "vision"))]
+
+use std::sync::Arc;
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use serde_json::json;
+
+use crate::core::{profiles::StreamProfile, streams::{DeleteProfileError, StreamConfig, StreamRegistry}};
+
+#[get("/v1/profiles")]
+pub async fn list_profiles(registry: web::Data<Arc<StreamRegistry>>) -> HttpResponse {
+    HttpResponse::Ok().json(registry.profiles().list().await)
+}
+
+#[get("/v1/profiles/{name}")]
+pub async fn get_profile(
+    registry: web::Data<Arc<StreamRegistry>>,
+    name: web::Path<String>,
+) -> HttpResponse {
+    match registry.profiles().get(&name).await {
+        Some(profile) => HttpResponse::Ok().json(profile),
+        None => HttpResponse::NotFound().json(json!({
+            "error": format!("Profile not found: {}", name)
+        })),
+    }
+}
+
+#[post("/v1/profiles")]
+pub async fn create_profile(
+    registry: web::Data<Arc<StreamRegistry>>,
+    profile: web::Json<StreamProfile>,
+) -> HttpResponse {
+    match registry.profiles().create(profile.into_inner()).await {
+        Ok(profile) => HttpResponse::Created().json(profile),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[put("/v1/profiles/{name}")]
+pub async fn update_profile(
+    registry: web::Data<Arc<StreamRegistry>>,
+    name: web::Path<String>,
+    profile: web::Json<StreamProfile>,
+) -> HttpResponse {
+    if registry.profiles().get(&name).await.is_none() {
+        return HttpResponse::NotFound().json(json!({
+            "error": format!("Profile not found: {}", name)
+        }));
+    }
+
+    match registry.profiles().update(&name, profile.into_inner()).await {
+        Ok(profile) => HttpResponse::Ok().json(profile),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[delete("/v1/profiles/{name}")]
+pub async fn delete_profile(
+    registry: web::Data<Arc<StreamRegistry>>,
+    name: web::Path<String>,
+) -> HttpResponse {
+    match registry.delete_profile(&name).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(DeleteProfileError::InUse(in_use)) => HttpResponse::Conflict().json(json!({
+            "error": format!("Profile {} is in use", name),
+            "streams": in_use,
+        })),
+        Err(DeleteProfileError::Failed(e)) => HttpResponse::NotFound().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[get("/v1/streams")]
+pub async fn list_streams(registry: web::Data<Arc<StreamRegistry>>) -> HttpResponse {
+    let streams: Vec<StreamConfig> = registry.list().await.iter().map(StreamConfig::redacted).collect();
+    HttpResponse::Ok().json(streams)
+}
+
+// The source may be any `SourceConfig` type, or a bare file/RTSP URI
+#[post("/v1/streams")]
+pub async fn create_stream(
+    registry: web::Data<Arc<StreamRegistry>>,
+    stream: web::Json<StreamConfig>,
+) -> HttpResponse {
+    let stream = stream.into_inner();
+    match registry.add(stream.clone()).await {
+        Ok(()) => HttpResponse::Created().json(stream.redacted()),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[delete("/v1/streams/{id}")]
+pub async fn delete_stream(
+    registry: web::Data<Arc<StreamRegistry>>,
+    id: web::Path<String>,
+) -> HttpResponse {
+    match registry.remove(&id).await {
+        Some(_) => HttpResponse::NoContent().finish(),
+        None => HttpResponse::NotFound().json(json!({
+            "error": format!("Stream not found: {}", id)
+        })),
+    }
+}
@@ -0,0 +1,125 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::core::agent::fewshot::{FewShotExample, FewShotStore};
+use crate::core::prompts::{NewPromptVersion, PromptLibrary};
+
+#[derive(Debug, Deserialize)]
+pub struct ActivateRequest {
+    pub version: u32,
+}
+
+#[get("/v1/prompts")]
+pub async fn list_prompts(library: web::Data<Arc<PromptLibrary>>) -> HttpResponse {
+    HttpResponse::Ok().json(library.list().await)
+}
+
+#[get("/v1/prompts/{name}")]
+pub async fn get_prompt(
+    library: web::Data<Arc<PromptLibrary>>,
+    name: web::Path<String>,
+) -> HttpResponse {
+    match library.get(&name).await {
+        Some(prompt) => HttpResponse::Ok().json(prompt),
+        None => HttpResponse::NotFound().json(json!({
+            "error": format!("Prompt not found: {}", name)
+        })),
+    }
+}
+
+#[post("/v1/prompts/{name}/versions")]
+pub async fn publish_version(
+    library: web::Data<Arc<PromptLibrary>>,
+    name: web::Path<String>,
+    version: web::Json<NewPromptVersion>,
+) -> HttpResponse {
+    match library.publish(&name, version.into_inner()).await {
+        Ok(prompt) => HttpResponse::Created().json(prompt),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[put("/v1/prompts/{name}/active")]
+pub async fn activate_version(
+    library: web::Data<Arc<PromptLibrary>>,
+    name: web::Path<String>,
+    request: web::Json<ActivateRequest>,
+) -> HttpResponse {
+    if library.get(&name).await.is_none() {
+        return HttpResponse::NotFound().json(json!({
+            "error": format!("Prompt not found: {}", name)
+        }));
+    }
+
+    match library.activate(&name, request.version).await {
+        Ok(prompt) => HttpResponse::Ok().json(prompt),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[post("/v1/prompts/{name}/rollback")]
+pub async fn rollback(
+    library: web::Data<Arc<PromptLibrary>>,
+    name: web::Path<String>,
+) -> HttpResponse {
+    if library.get(&name).await.is_none() {
+        return HttpResponse::NotFound().json(json!({
+            "error": format!("Prompt not found: {}", name)
+        }));
+    }
+
+    match library.rollback(&name).await {
+        Ok(prompt) => HttpResponse::Ok().json(prompt),
+        Err(e) => HttpResponse::Conflict().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[delete("/v1/prompts/{name}")]
+pub async fn delete_prompt(
+    library: web::Data<Arc<PromptLibrary>>,
+    name: web::Path<String>,
+) -> HttpResponse {
+    match library.delete(&name).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => HttpResponse::NotFound().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[get("/v1/agents/{agent}/examples")]
+pub async fn list_examples(
+    examples: web::Data<Arc<FewShotStore>>,
+    agent: web::Path<String>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(examples.list(&agent).await)
+}
+
+#[post("/v1/agents/{agent}/examples")]
+pub async fn add_example(
+    examples: web::Data<Arc<FewShotStore>>,
+    agent: web::Path<String>,
+    example: web::Json<FewShotExample>,
+) -> HttpResponse {
+    let mut example = example.into_inner();
+    example.agent = agent.into_inner();
+
+    match examples.add(example).await {
+        Ok(example) => HttpResponse::Created().json(example),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[delete("/v1/agents/{agent}/examples/{id}")]
+pub async fn delete_example(
+    examples: web::Data<Arc<FewShotStore>>,
+    path: web::Path<(String, String)>,
+) -> HttpResponse {
+    let (agent, id) = path.into_inner();
+    match examples.remove(&agent, &id).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => HttpResponse::NotFound().json(json!({ "error": e.to_string() })),
+    }
+}
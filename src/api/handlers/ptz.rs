@@ -0,0 +1,65 @@
+#![cfg(all(feature = "api", feature = "vision"))]
+
+use std::sync::Arc;
+use actix_web::{get, post, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::vision::ptz::{PtzCommand, PtzManager};
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum PtzRequest {
+    AutoTrack {
+        auto_tracking: bool,
+        target_class: Option<String>,
+    },
+    Command(PtzCommand),
+}
+
+#[get("/v1/streams/{id}/ptz")]
+pub async fn get_ptz(
+    ptz: web::Data<Arc<PtzManager>>,
+    stream_id: web::Path<String>,
+) -> HttpResponse {
+    match ptz.get(&stream_id).await {
+        Some(controller) => HttpResponse::Ok().json(json!({
+            "stream_id": stream_id.as_str(),
+            "auto_tracking": controller.auto_track_state().await,
+        })),
+        None => HttpResponse::NotFound().json(json!({
+            "error": format!("Stream {} has no PTZ control configured", stream_id)
+        })),
+    }
+}
+
+#[post("/v1/streams/{id}/ptz")]
+pub async fn control_ptz(
+    ptz: web::Data<Arc<PtzManager>>,
+    stream_id: web::Path<String>,
+    request: web::Json<PtzRequest>,
+) -> HttpResponse {
+    let Some(controller) = ptz.get(&stream_id).await else {
+        return HttpResponse::NotFound().json(json!({
+            "error": format!("Stream {} has no PTZ control configured", stream_id)
+        }));
+    };
+
+    let result = match request.into_inner() {
+        PtzRequest::AutoTrack { auto_tracking, target_class } => {
+            controller.set_auto_tracking(auto_tracking, target_class).await
+        }
+        PtzRequest::Command(command) => controller.execute(&command).await,
+    };
+
+    match result {
+        Ok(()) => HttpResponse::Ok().json(json!({
+            "stream_id": stream_id.as_str(),
+            "auto_tracking": controller.auto_track_state().await,
+        })),
+        Err(e) => {
+            log::error!("PTZ control error for stream {}: {}", stream_id, e);
+            HttpResponse::BadGateway().json(json!({ "error": e.to_string() }))
+        }
+    }
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_web::{delete, get, put, web, HttpResponse};
+use serde_json::json;
+
+use crate::api::middleware::ratelimit::{RateLimitConfig, RateLimitRule, RateLimiter};
+
+#[get("/v1/admin/rate-limits")]
+pub async fn get_rate_limits(limiter: web::Data<Arc<RateLimiter>>) -> HttpResponse {
+    HttpResponse::Ok().json(limiter.config().await)
+}
+
+#[put("/v1/admin/rate-limits")]
+pub async fn replace_rate_limits(
+    limiter: web::Data<Arc<RateLimiter>>,
+    config: web::Json<RateLimitConfig>,
+) -> HttpResponse {
+    match limiter.replace_config(config.into_inner()).await {
+        Ok(()) => {
+            log::warn!("Rate limit configuration replaced");
+            HttpResponse::Ok().json(limiter.config().await)
+        }
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[put("/v1/admin/rate-limits/rules/{name}")]
+pub async fn upsert_rate_limit_rule(
+    limiter: web::Data<Arc<RateLimiter>>,
+    name: web::Path<String>,
+    rule: web::Json<RateLimitRule>,
+) -> HttpResponse {
+    let mut rule = rule.into_inner();
+    rule.name = name.into_inner();
+    match limiter.upsert_rule(rule.clone()).await {
+        Ok(()) => {
+            log::warn!("Rate limit rule {} set to {:?}", rule.name, rule.limit);
+            HttpResponse::Ok().json(rule)
+        }
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[delete("/v1/admin/rate-limits/rules/{name}")]
+pub async fn delete_rate_limit_rule(
+    limiter: web::Data<Arc<RateLimiter>>,
+    name: web::Path<String>,
+) -> HttpResponse {
+    match limiter.remove_rule(&name).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => HttpResponse::NotFound().json(json!({ "error": e.to_string() })),
+    }
+}
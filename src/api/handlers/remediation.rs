@@ -0,0 +1,47 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_web::{get, put, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::core::remediation::Remediator;
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DryRunRequest {
+    pub dry_run: bool,
+}
+
+#[get("/v1/remediation")]
+pub async fn get_remediation(remediator: web::Data<Arc<Remediator>>) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "dry_run": remediator.is_dry_run(),
+        "policies": remediator.policies(),
+    }))
+}
+
+#[get("/v1/remediation/audit")]
+pub async fn remediation_audit(
+    remediator: web::Data<Arc<Remediator>>,
+    query: web::Query<AuditQuery>,
+) -> HttpResponse {
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    HttpResponse::Ok().json(remediator.audit_trail(limit).await)
+}
+
+// Lets operators watch a new policy in dry-run before arming it, without
+// a restart
+#[put("/v1/remediation/dry-run")]
+pub async fn set_remediation_dry_run(
+    remediator: web::Data<Arc<Remediator>>,
+    request: web::Json<DryRunRequest>,
+) -> HttpResponse {
+    remediator.set_dry_run(request.dry_run);
+    log::warn!("Remediation dry run set to {}", request.dry_run);
+    HttpResponse::Ok().json(json!({ "dry_run": remediator.is_dry_run() }))
+}
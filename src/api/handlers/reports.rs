@@ -0,0 +1,60 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_web::{get, post, web, HttpResponse};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::core::reports::ReportGenerator;
+
+#[derive(Debug, Deserialize)]
+pub struct GenerateReportRequest {
+    // Defaults to yesterday, the last complete day
+    pub date: Option<NaiveDate>,
+}
+
+#[get("/v1/reports")]
+pub async fn list_reports(reports: web::Data<Arc<ReportGenerator>>) -> HttpResponse {
+    HttpResponse::Ok().json(reports.list().await)
+}
+
+#[get("/v1/reports/{date}")]
+pub async fn get_report(
+    reports: web::Data<Arc<ReportGenerator>>,
+    date: web::Path<String>,
+) -> HttpResponse {
+    let Ok(day) = date.parse::<NaiveDate>() else {
+        return HttpResponse::BadRequest().json(json!({
+            "error": format!("Invalid date {}, expected YYYY-MM-DD", date)
+        }));
+    };
+
+    match reports.get(day).await {
+        Some(report) => HttpResponse::Ok().json(report),
+        None => HttpResponse::NotFound().json(json!({
+            "error": format!("No report for {}", day)
+        })),
+    }
+}
+
+#[post("/v1/reports")]
+pub async fn generate_report(
+    reports: web::Data<Arc<ReportGenerator>>,
+    request: web::Json<GenerateReportRequest>,
+) -> HttpResponse {
+    let date = request.date.unwrap_or_else(|| reports.today() - chrono::Duration::days(1));
+    if date > reports.today() {
+        return HttpResponse::BadRequest().json(json!({
+            "error": format!("Cannot report on {}, it hasn't happened yet", date)
+        }));
+    }
+
+    match reports.generate(date).await {
+        Ok(report) => HttpResponse::Created().json(report),
+        Err(e) => {
+            log::error!("Report generation for {} failed: {}", date, e);
+            HttpResponse::InternalServerError().json(json!({ "error": e.to_string() }))
+        }
+    }
+}
@@ -0,0 +1,119 @@
+#![cfg(all(feature = "api", feature = "vision"))]
+
+use std::sync::Arc;
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::core::agent::authoring::RuleAuthor;
+use crate::core::rules::{Rule, RuleStore};
+
+#[get("/v1/rules")]
+pub async fn list_rules(rules: web::Data<Arc<RuleStore>>) -> HttpResponse {
+    HttpResponse::Ok().json(rules.list().await)
+}
+
+#[get("/v1/rules/{id}")]
+pub async fn get_rule(
+    rules: web::Data<Arc<RuleStore>>,
+    id: web::Path<String>,
+) -> HttpResponse {
+    match rules.get(&id).await {
+        Some(rule) => HttpResponse::Ok().json(rule),
+        None => HttpResponse::NotFound().json(json!({
+            "error": format!("Rule not found: {}", id)
+        })),
+    }
+}
+
+#[post("/v1/rules")]
+pub async fn create_rule(
+    rules: web::Data<Arc<RuleStore>>,
+    rule: web::Json<Rule>,
+) -> HttpResponse {
+    match rules.create(rule.into_inner()).await {
+        Ok(rule) => HttpResponse::Created().json(rule),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[put("/v1/rules/{id}")]
+pub async fn update_rule(
+    rules: web::Data<Arc<RuleStore>>,
+    id: web::Path<String>,
+    rule: web::Json<Rule>,
+) -> HttpResponse {
+    if rules.get(&id).await.is_none() {
+        return HttpResponse::NotFound().json(json!({
+            "error": format!("Rule not found: {}", id)
+        }));
+    }
+
+    match rules.update(&id, rule.into_inner()).await {
+        Ok(rule) => HttpResponse::Ok().json(rule),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[delete("/v1/rules/{id}")]
+pub async fn delete_rule(
+    rules: web::Data<Arc<RuleStore>>,
+    id: web::Path<String>,
+) -> HttpResponse {
+    match rules.delete(&id).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => HttpResponse::NotFound().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DraftRequest {
+    pub request: String,
+}
+
+// Natural-language authoring: drafting returns a proposal, and nothing is
+// applied until the proposal is confirmed
+#[post("/v1/rule-proposals")]
+pub async fn draft_rule(
+    author: web::Data<Arc<RuleAuthor>>,
+    request: web::Json<DraftRequest>,
+) -> HttpResponse {
+    if request.request.trim().is_empty() {
+        return HttpResponse::BadRequest().json(json!({ "error": "request must not be empty" }));
+    }
+
+    match author.propose(&request.request).await {
+        Ok(proposal) => HttpResponse::Created().json(proposal),
+        Err(e) => HttpResponse::UnprocessableEntity().json(json!({ "error": format!("{:#}", e) })),
+    }
+}
+
+#[get("/v1/rule-proposals")]
+pub async fn list_rule_proposals(author: web::Data<Arc<RuleAuthor>>) -> HttpResponse {
+    HttpResponse::Ok().json(author.pending().await)
+}
+
+#[post("/v1/rule-proposals/{id}/confirm")]
+pub async fn confirm_rule_proposal(
+    author: web::Data<Arc<RuleAuthor>>,
+    id: web::Path<String>,
+) -> HttpResponse {
+    match author.confirm(&id).await {
+        Ok(rule) => HttpResponse::Created().json(rule),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[delete("/v1/rule-proposals/{id}")]
+pub async fn discard_rule_proposal(
+    author: web::Data<Arc<RuleAuthor>>,
+    id: web::Path<String>,
+) -> HttpResponse {
+    if author.discard(&id).await {
+        HttpResponse::NoContent().finish()
+    } else {
+        HttpResponse::NotFound().json(json!({
+            "error": format!("Rule proposal not found: {}", id)
+        }))
+    }
+}
@@ -0,0 +1,150 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_web::{delete, get, post, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+use tokio_util::sync::CancellationToken;
+
+use crate::api::middleware::quota::{MeteredUsage, StreamedUsage};
+use crate::api::principal::Principal;
+use crate::api::sse::stream_response;
+use crate::core::agent::sessions::{CreateSession, SessionInfo, SessionManager};
+
+#[derive(Debug, Deserialize)]
+pub struct SessionListQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionMessagesQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatRequest {
+    pub content: String,
+}
+
+fn not_found(id: &str) -> HttpResponse {
+    HttpResponse::NotFound().json(json!({"error": format!("Session not found: {}", id)}))
+}
+
+// Someone else's session answers exactly like a missing one, so ids can't
+// be probed
+async fn owned(sessions: &SessionManager, principal: &Principal, id: &str) -> Option<SessionInfo> {
+    sessions.info(id).await.filter(|info| principal.can_access(&info.owner))
+}
+
+// The caller's sessions, most recently active first; admins see all
+#[get("/v1/sessions")]
+pub async fn list_sessions(
+    sessions: web::Data<Arc<SessionManager>>,
+    principal: Principal,
+    query: web::Query<SessionListQuery>,
+) -> HttpResponse {
+    let mut list = sessions.list(principal.owner_filter()).await;
+    list.truncate(query.limit.unwrap_or(100));
+    HttpResponse::Ok().json(list)
+}
+
+#[post("/v1/sessions")]
+pub async fn create_session(
+    sessions: web::Data<Arc<SessionManager>>,
+    principal: Principal,
+    request: Option<web::Json<CreateSession>>,
+) -> HttpResponse {
+    let request = request.map(web::Json::into_inner).unwrap_or_default();
+    match sessions.create(&principal.subject, principal.tenant.as_deref(), request).await {
+        Ok(info) => HttpResponse::Created().json(info),
+        Err(e) => HttpResponse::ServiceUnavailable().json(json!({"error": e.to_string()})),
+    }
+}
+
+#[get("/v1/sessions/{id}/messages")]
+pub async fn session_messages(
+    sessions: web::Data<Arc<SessionManager>>,
+    principal: Principal,
+    path: web::Path<String>,
+    query: web::Query<SessionMessagesQuery>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if owned(&sessions, &principal, &id).await.is_none() {
+        return not_found(&id);
+    }
+    match sessions.messages(&id, query.limit.unwrap_or(100)).await {
+        Ok(Some(messages)) => HttpResponse::Ok().json(json!({
+            "session_id": id,
+            "messages": messages,
+        })),
+        Ok(None) => not_found(&id),
+        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+    }
+}
+
+// Continues the conversation: the reply is generated from this session's
+// history only
+#[post("/v1/sessions/{id}/messages")]
+pub async fn send_message(
+    sessions: web::Data<Arc<SessionManager>>,
+    principal: Principal,
+    path: web::Path<String>,
+    request: web::Json<ChatRequest>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if owned(&sessions, &principal, &id).await.is_none() {
+        return not_found(&id);
+    }
+    if request.content.trim().is_empty() {
+        return HttpResponse::BadRequest().json(json!({"error": "Message content must not be empty"}));
+    }
+    match sessions.chat(&id, &request.content).await {
+        Ok(response) => MeteredUsage { tokens: response.usage.total_tokens as u64, frames: 0 }
+            .attach(HttpResponse::Ok().json(json!({
+                "session_id": id,
+                "content": response.content,
+                "model": response.model,
+                "total_tokens": response.usage.total_tokens,
+            }))),
+        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+    }
+}
+
+// send_message() as server-sent events. A client that disconnects cancels
+// the completion, and the unfinished turn isn't recorded.
+#[post("/v1/sessions/{id}/messages/stream")]
+pub async fn stream_message(
+    sessions: web::Data<Arc<SessionManager>>,
+    principal: Principal,
+    path: web::Path<String>,
+    request: web::Json<ChatRequest>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if owned(&sessions, &principal, &id).await.is_none() {
+        return not_found(&id);
+    }
+    if request.content.trim().is_empty() {
+        return HttpResponse::BadRequest().json(json!({"error": "Message content must not be empty"}));
+    }
+    let cancel = CancellationToken::new();
+    let usage = StreamedUsage::default();
+    let deltas = sessions.chat_stream_metered(&id, &request.content, cancel.clone(), usage.counter());
+    usage.attach(stream_response(deltas, cancel))
+}
+
+#[delete("/v1/sessions/{id}")]
+pub async fn delete_session(
+    sessions: web::Data<Arc<SessionManager>>,
+    principal: Principal,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if owned(&sessions, &principal, &id).await.is_none() {
+        return not_found(&id);
+    }
+    match sessions.delete(&id).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => not_found(&id),
+        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+    }
+}
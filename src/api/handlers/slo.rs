@@ -0,0 +1,28 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_web::{get, web, HttpResponse};
+use serde_json::json;
+
+use crate::core::alerts::AlertManager;
+use crate::core::llm::cache::ResponseCache;
+use crate::core::llm::latency::LatencyTracker;
+
+#[get("/v1/metrics/llm/latency")]
+pub async fn llm_latency(tracker: web::Data<Arc<LatencyTracker>>) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "models": tracker.report().await,
+    }))
+}
+
+#[get("/v1/metrics/llm/cache")]
+pub async fn llm_cache(cache: web::Data<Arc<ResponseCache>>) -> HttpResponse {
+    HttpResponse::Ok().json(cache.stats())
+}
+
+#[get("/v1/alerts")]
+pub async fn alerts(alerts: web::Data<Arc<AlertManager>>) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "active": alerts.active_alerts().await,
+    }))
+}
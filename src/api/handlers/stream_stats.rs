@@ -0,0 +1,44 @@
+#![cfg(all(feature = "api", feature = "vision"))]
+
+use std::sync::Arc;
+use actix_web::{get, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::core::stream_stats::StreamStats;
+
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    // e.g. "5m"; every configured window when absent
+    pub window: Option<String>,
+}
+
+#[get("/v1/streams/{id}/stats")]
+pub async fn stream_stats(
+    stats: web::Data<Arc<StreamStats>>,
+    id: web::Path<String>,
+    query: web::Query<StatsQuery>,
+) -> HttpResponse {
+    let windows = match &query.window {
+        Some(window) => vec![window.clone()],
+        None => stats.config().windows.clone(),
+    };
+
+    let mut reports = Vec::with_capacity(windows.len());
+    for window in &windows {
+        match stats.report(&id, window).await {
+            Ok(Some(report)) => reports.push(report),
+            Ok(None) => {
+                return HttpResponse::NotFound().json(json!({
+                    "error": format!("No frames recorded for stream {}", id)
+                }));
+            }
+            Err(e) => return HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+        }
+    }
+
+    HttpResponse::Ok().json(json!({
+        "stream_id": id.as_str(),
+        "windows": reports,
+    }))
+}
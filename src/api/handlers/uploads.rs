@@ -0,0 +1,106 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse};
+use serde_json::json;
+
+use crate::api::principal::Principal;
+use crate::core::uploads::{ChunkError, CreateUpload, UploadManager, UploadSession};
+
+const OFFSET_HEADER: &str = "Upload-Offset";
+
+// Another caller's upload is reported as missing rather than forbidden
+async fn owned(uploads: &UploadManager, principal: &Principal, id: &str) -> Option<UploadSession> {
+    uploads.get(id).await.filter(|session| principal.can_access(&session.owner))
+}
+
+fn not_found(id: &str) -> HttpResponse {
+    HttpResponse::NotFound().json(json!({
+        "error": format!("Upload not found: {}", id)
+    }))
+}
+
+#[post("/v1/uploads")]
+pub async fn create_upload(
+    uploads: web::Data<Arc<UploadManager>>,
+    principal: Principal,
+    request: web::Json<CreateUpload>,
+) -> HttpResponse {
+    match uploads.create(request.into_inner(), &principal.subject).await {
+        Ok(session) => HttpResponse::Created()
+            .insert_header(("Location", format!("/v1/uploads/{}", session.id)))
+            .insert_header((OFFSET_HEADER, session.offset.to_string()))
+            .json(session),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+// Clients call this after a dropped connection to learn where to resume
+#[get("/v1/uploads/{id}")]
+pub async fn get_upload(
+    uploads: web::Data<Arc<UploadManager>>,
+    principal: Principal,
+    id: web::Path<String>,
+) -> HttpResponse {
+    match owned(&uploads, &principal, &id).await {
+        Some(session) => HttpResponse::Ok()
+            .insert_header((OFFSET_HEADER, session.offset.to_string()))
+            .json(session),
+        None => not_found(&id),
+    }
+}
+
+#[patch("/v1/uploads/{id}")]
+pub async fn append_chunk(
+    uploads: web::Data<Arc<UploadManager>>,
+    principal: Principal,
+    id: web::Path<String>,
+    request: HttpRequest,
+    body: web::Bytes,
+) -> HttpResponse {
+    if owned(&uploads, &principal, &id).await.is_none() {
+        return not_found(&id);
+    }
+    let offset = match request.headers().get(OFFSET_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+    {
+        Some(offset) => offset,
+        None => return HttpResponse::BadRequest().json(json!({
+            "error": format!("Missing or invalid {} header", OFFSET_HEADER)
+        })),
+    };
+
+    match uploads.append(&id, offset, &body).await {
+        Ok(session) => HttpResponse::Ok()
+            .insert_header((OFFSET_HEADER, session.offset.to_string()))
+            .json(session),
+        Err(ChunkError::NotFound) => not_found(&id),
+        Err(ChunkError::OffsetMismatch(expected)) => HttpResponse::Conflict()
+            .insert_header((OFFSET_HEADER, expected.to_string()))
+            .json(json!({
+                "error": "Offset mismatch",
+                "expected_offset": expected,
+            })),
+        Err(ChunkError::Invalid(message)) => HttpResponse::BadRequest().json(json!({ "error": message })),
+        Err(ChunkError::Internal(e)) => {
+            log::error!("Upload {} chunk failed: {}", id, e);
+            HttpResponse::InternalServerError().json(json!({ "error": e.to_string() }))
+        }
+    }
+}
+
+#[delete("/v1/uploads/{id}")]
+pub async fn cancel_upload(
+    uploads: web::Data<Arc<UploadManager>>,
+    principal: Principal,
+    id: web::Path<String>,
+) -> HttpResponse {
+    if owned(&uploads, &principal, &id).await.is_none() {
+        return not_found(&id);
+    }
+    match uploads.cancel(&id).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => HttpResponse::NotFound().json(json!({ "error": e.to_string() })),
+    }
+}
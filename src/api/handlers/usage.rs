@@ -0,0 +1,15 @@
+#![cfg(feature = "api")]
+
+use std::sync::Arc;
+use actix_web::{get, web, HttpResponse};
+
+use crate::api::middleware::quota::tenant_of;
+use crate::api::principal::Principal;
+use crate::core::usage::UsageLedger;
+
+// Remaining budget for the calling tenant, per meter and period
+#[get("/v1/usage/quota")]
+pub async fn get_quota(ledger: web::Data<Arc<UsageLedger>>, principal: Principal) -> HttpResponse {
+    let tenant = tenant_of(Some(&principal));
+    HttpResponse::Ok().json(ledger.quota(&tenant).await)
+}
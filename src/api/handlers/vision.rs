@@ -0,0 +1,262 @@
+#![cfg(all(feature = "api", feature = "vision"))]
+
+use std::sync::Arc;
+use actix_multipart::Multipart;
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use futures::{StreamExt, TryStreamExt};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::api::middleware::quota::MeteredUsage;
+use crate::utils::fetch::ImageFetcher;
+use crate::vision::oneshot::{BatchImage, ImageAnalysisService};
+use crate::vision::similarity::{SearchFilter, SearchQuery, SimilarityIndex};
+use crate::vision::sources::{decode_frame, FrameEncoding};
+
+#[derive(Debug, Deserialize)]
+pub struct AnalyzeQuery {
+    // Detections only when false; skips the analyzers entirely
+    #[serde(default = "default_true")]
+    pub analysis: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImageUrlRequest {
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImageUrlBatchRequest {
+    pub urls: Vec<String>,
+}
+
+fn is_json(request: &HttpRequest) -> bool {
+    request.headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"))
+}
+
+// Body is the image itself, or JSON `{"url": "..."}` for the server to fetch
+#[post("/v1/vision/analyze")]
+pub async fn analyze_image(
+    service: web::Data<Arc<ImageAnalysisService>>,
+    fetcher: web::Data<Arc<ImageFetcher>>,
+    query: web::Query<AnalyzeQuery>,
+    request: HttpRequest,
+    payload: web::Payload,
+) -> HttpResponse {
+    // Read against the service's own limit; actix's default body limit is
+    // far below a full-resolution image
+    let limit = service.config().max_image_bytes;
+    let body = match payload.to_bytes_limited(limit).await {
+        Ok(Ok(body)) => body,
+        Ok(Err(e)) => return HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+        Err(_) => return HttpResponse::PayloadTooLarge().json(json!({
+            "error": format!("Image exceeds {} bytes", limit)
+        })),
+    };
+    if body.is_empty() {
+        return HttpResponse::BadRequest().json(json!({ "error": "Request body must contain an image" }));
+    }
+
+    let (content_type, data) = if is_json(&request) {
+        let url = match serde_json::from_slice::<ImageUrlRequest>(&body) {
+            Ok(parsed) => parsed.url,
+            Err(e) => return HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+        };
+        match fetcher.fetch(&url).await {
+            Ok(image) => (image.content_type, image.data),
+            Err(e) => return HttpResponse::UnprocessableEntity().json(json!({ "error": e.to_string() })),
+        }
+    } else {
+        let content_type = request.headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+        (content_type, body.to_vec())
+    };
+
+    match service.analyze(&content_type, &data, query.analysis).await {
+        Ok(result) => MeteredUsage { tokens: 0, frames: 1 }.attach(HttpResponse::Ok().json(result)),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+// Multipart body with one `image` field per image, or JSON `{"urls": [...]}`
+#[post("/v1/vision/analyze_batch")]
+pub async fn analyze_batch(
+    service: web::Data<Arc<ImageAnalysisService>>,
+    fetcher: web::Data<Arc<ImageFetcher>>,
+    query: web::Query<AnalyzeQuery>,
+    request: HttpRequest,
+    payload: web::Payload,
+) -> HttpResponse {
+    let limits = service.config().clone();
+
+    if is_json(&request) {
+        let body = match payload.to_bytes_limited(64 * 1024).await {
+            Ok(Ok(body)) => body,
+            _ => return HttpResponse::PayloadTooLarge().json(json!({ "error": "URL list is too large" })),
+        };
+        let urls = match serde_json::from_slice::<ImageUrlBatchRequest>(&body) {
+            Ok(parsed) => parsed.urls,
+            Err(e) => return HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+        };
+        if urls.len() > limits.max_batch_images {
+            return HttpResponse::PayloadTooLarge().json(json!({
+                "error": format!("Batch exceeds {} images", limits.max_batch_images)
+            }));
+        }
+
+        // Failed fetches become per-image errors like any other bad image
+        let fetched = futures::future::join_all(urls.iter().map(|url| fetcher.fetch(url))).await;
+        let images: Vec<BatchImage> = urls.into_iter()
+            .zip(fetched)
+            .map(|(url, outcome)| match outcome {
+                Ok(image) => BatchImage {
+                    name: url,
+                    content_type: image.content_type,
+                    data: image.data,
+                    error: None,
+                },
+                Err(e) => BatchImage {
+                    name: url,
+                    content_type: String::new(),
+                    data: Vec::new(),
+                    error: Some(e.to_string()),
+                },
+            })
+            .collect();
+
+        let frames = images.len() as u64;
+        return match service.analyze_batch(images, query.analysis).await {
+            Ok(batch) => MeteredUsage { tokens: 0, frames }.attach(HttpResponse::Ok().json(batch)),
+            Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+        };
+    }
+
+    let mut payload = Multipart::new(request.headers(), payload);
+    let mut images = Vec::new();
+
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        if field.name() != "image" {
+            continue;
+        }
+        if images.len() >= limits.max_batch_images {
+            return HttpResponse::PayloadTooLarge().json(json!({
+                "error": format!("Batch exceeds {} images", limits.max_batch_images)
+            }));
+        }
+
+        let name = field.content_disposition()
+            .get_filename()
+            .map(|f| f.to_string())
+            .unwrap_or_else(|| format!("image-{}", images.len()));
+        let content_type = field.content_type()
+            .map(|m| m.to_string())
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+
+        let mut data = Vec::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => return HttpResponse::BadRequest().json(json!({
+                    "error": format!("Failed to read image {}: {}", name, e)
+                })),
+            };
+            if data.len() + chunk.len() > limits.max_image_bytes {
+                return HttpResponse::PayloadTooLarge().json(json!({
+                    "error": format!("Image {} exceeds {} bytes", name, limits.max_image_bytes)
+                }));
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        images.push(BatchImage { name, content_type, data, error: None });
+    }
+
+    let frames = images.len() as u64;
+    match service.analyze_batch(images, query.analysis).await {
+        Ok(batch) => MeteredUsage { tokens: 0, frames }.attach(HttpResponse::Ok().json(batch)),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+// Exactly one of `text`, `image` (base64) or `url` selects the query
+#[derive(Debug, Deserialize)]
+pub struct SearchRequest {
+    pub text: Option<String>,
+    pub image: Option<String>,
+    pub url: Option<String>,
+    #[serde(default = "default_search_limit")]
+    pub limit: usize,
+    #[serde(default)]
+    pub min_score: f32,
+    #[serde(flatten)]
+    pub filter: SearchFilter,
+}
+
+fn default_search_limit() -> usize {
+    20
+}
+
+#[post("/v1/vision/search")]
+pub async fn search_similar(
+    index: web::Data<Arc<SimilarityIndex>>,
+    service: web::Data<Arc<ImageAnalysisService>>,
+    fetcher: web::Data<Arc<ImageFetcher>>,
+    payload: web::Payload,
+) -> HttpResponse {
+    // web::Json stops at 32KB, which a base64 query image rarely fits in
+    let limit = service.config().max_image_bytes / 3 * 4 + 64 * 1024;
+    let body = match payload.to_bytes_limited(limit).await {
+        Ok(Ok(body)) => body,
+        Ok(Err(e)) => return HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+        Err(_) => return HttpResponse::PayloadTooLarge().json(json!({ "error": "Search request is too large" })),
+    };
+    let request = match serde_json::from_slice::<SearchRequest>(&body) {
+        Ok(request) => request,
+        Err(e) => return HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    };
+    let image = match (&request.text, &request.image, &request.url) {
+        (Some(_), None, None) => None,
+        (None, Some(encoded), None) => match BASE64.decode(encoded) {
+            Ok(data) => Some(("image/jpeg".to_string(), data)),
+            Err(_) => return HttpResponse::BadRequest().json(json!({ "error": "image must be base64" })),
+        },
+        (None, None, Some(url)) => match fetcher.fetch(url).await {
+            Ok(fetched) => Some((fetched.content_type, fetched.data)),
+            Err(e) => return HttpResponse::UnprocessableEntity().json(json!({ "error": e.to_string() })),
+        },
+        _ => return HttpResponse::BadRequest().json(json!({
+            "error": "Provide exactly one of text, image or url"
+        })),
+    };
+
+    let query = match image {
+        None => SearchQuery::Text(request.text.unwrap_or_default()),
+        Some((content_type, data)) => {
+            // Base64 uploads don't say what they are; PNG decodes fine via
+            // the JPEG path since imdecode sniffs the format
+            let decoded = FrameEncoding::from_content_type(&content_type, None, None)
+                .and_then(|encoding| decode_frame(encoding, &data));
+            match decoded {
+                Ok(image) => SearchQuery::Image(image),
+                Err(e) => return HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+            }
+        }
+    };
+
+    let limit = request.limit.clamp(1, 200);
+    match index.search(query, &request.filter, limit, request.min_score).await {
+        Ok(hits) => HttpResponse::Ok().json(json!({ "results": hits })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({ "error": e.to_string() })),
+    }
+}
@@ -0,0 +1,72 @@
+#![cfg(all(feature = "api", feature = "vision"))]
+
+use std::sync::Arc;
+use actix_web::{delete, get, post, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::vision::zone_suggest::{ObserveRequest, ZoneSuggester};
+use crate::vision::zones::LineCrossings;
+
+// Starts watching a stream's traffic; suggestions appear once the job ends
+#[post("/v1/streams/{id}/zone-suggestions/observe")]
+pub async fn observe(
+    suggester: web::Data<Arc<ZoneSuggester>>,
+    id: web::Path<String>,
+    request: Option<web::Json<ObserveRequest>>,
+) -> HttpResponse {
+    let request = request.map(|r| r.into_inner()).unwrap_or_default();
+    match suggester.observe(&id, request).await {
+        Ok(job) => HttpResponse::Accepted().json(job),
+        Err(e) => HttpResponse::Conflict().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[get("/v1/streams/{id}/zone-suggestions")]
+pub async fn list_suggestions(
+    suggester: web::Data<Arc<ZoneSuggester>>,
+    id: web::Path<String>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(suggester.list(&id).await)
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AcceptRequest {
+    // Replaces the generated name, e.g. "entrance-1" -> "north-gate"
+    pub name: Option<String>,
+}
+
+#[post("/v1/streams/{id}/zone-suggestions/{suggestion}/accept")]
+pub async fn accept_suggestion(
+    suggester: web::Data<Arc<ZoneSuggester>>,
+    path: web::Path<(String, String)>,
+    request: Option<web::Json<AcceptRequest>>,
+) -> HttpResponse {
+    let (id, suggestion) = path.into_inner();
+    let name = request.and_then(|r| r.into_inner().name);
+    match suggester.accept(&id, &suggestion, name).await {
+        Ok(profile) => HttpResponse::Ok().json(profile),
+        Err(e) => HttpResponse::BadRequest().json(json!({ "error": e.to_string() })),
+    }
+}
+
+#[delete("/v1/streams/{id}/zone-suggestions/{suggestion}")]
+pub async fn reject_suggestion(
+    suggester: web::Data<Arc<ZoneSuggester>>,
+    path: web::Path<(String, String)>,
+) -> HttpResponse {
+    let (id, suggestion) = path.into_inner();
+    match suggester.reject(&id, &suggestion).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => HttpResponse::NotFound().json(json!({ "error": e.to_string() })),
+    }
+}
+
+// Tracks counted over each of the stream's lines since startup
+#[get("/v1/streams/{id}/lines/counts")]
+pub async fn line_counts(
+    crossings: web::Data<Arc<LineCrossings>>,
+    id: web::Path<String>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(crossings.counts(&id))
+}
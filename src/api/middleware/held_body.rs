@@ -0,0 +1,33 @@
+#![cfg(feature = "api")]
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::web::Bytes;
+
+// A response body that keeps `guard` alive until the body has been written
+// out or the connection drops it. Middleware that counts requests in flight
+// hands its guard over here, so a streamed or SSE response stays counted
+// after the handler has returned.
+pub struct HeldBody<B, G> {
+    body: Pin<Box<B>>,
+    _guard: G,
+}
+
+impl<B, G> HeldBody<B, G> {
+    pub fn new(body: B, guard: G) -> Self {
+        Self { body: Box::pin(body), _guard: guard }
+    }
+}
+
+impl<B: MessageBody, G: Unpin> MessageBody for HeldBody<B, G> {
+    type Error = B::Error;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        self.get_mut().body.as_mut().poll_next(cx)
+    }
+}
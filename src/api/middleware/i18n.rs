@@ -0,0 +1,142 @@
+#![cfg(feature = "api")]
+
+use std::rc::Rc;
+use std::sync::Arc;
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{self, HeaderValue},
+    Error, HttpMessage, HttpResponse,
+};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use serde_json::Value;
+
+use crate::api::principal::Principal;
+use crate::core::i18n::{parse_accept_language, Localizer};
+
+// Translates the "error" field of JSON error responses. The language is the
+// first Accept-Language entry with a catalog, then the preference of the
+// caller's tenant, then the default; successful responses pass through
+// untouched. The tenant comes from the Principal, so this has to be
+// wrapped inside the auth middleware.
+pub struct Localize {
+    localizer: Arc<Localizer>,
+}
+
+impl Localize {
+    pub fn new(localizer: Arc<Localizer>) -> Self {
+        Self { localizer }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Localize
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = LocalizeMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(LocalizeMiddleware {
+            service: Rc::new(service),
+            localizer: self.localizer.clone(),
+        }))
+    }
+}
+
+pub struct LocalizeMiddleware<S> {
+    service: Rc<S>,
+    localizer: Arc<Localizer>,
+}
+
+async fn response_language(req: &ServiceRequest, localizer: &Localizer) -> String {
+    let accepted = req.headers().get(header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .map(parse_accept_language)
+        .unwrap_or_default();
+    for code in accepted {
+        if localizer.check(&code).is_ok() && localizer.has_catalog(&code).await {
+            return code;
+        }
+    }
+
+    let tenant = req.extensions().get::<Principal>().and_then(|p| p.tenant.clone());
+    if let Some(code) = match tenant.as_deref() {
+        Some(tenant) => localizer.tenant_language(tenant).await,
+        None => None,
+    } {
+        return code;
+    }
+    localizer.default_language()
+}
+
+impl<S, B> Service<ServiceRequest> for LocalizeMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let localizer = self.localizer.clone();
+
+        Box::pin(async move {
+            let language = response_language(&req, &localizer).await;
+            let res = service.call(req).await?;
+
+            let is_json = res.headers().get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| v.starts_with("application/json"));
+            if language == "en" || !is_json || !(res.status().is_client_error() || res.status().is_server_error()) {
+                return Ok(res.map_into_boxed_body());
+            }
+
+            let (request, response) = res.into_parts();
+            let status = response.status();
+            let headers = response.headers().clone();
+            let bytes = match body::to_bytes(response.into_body()).await {
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    let response = HttpResponse::InternalServerError().finish();
+                    return Ok(ServiceResponse::new(request, response));
+                }
+            };
+
+            let mut translated = false;
+            let bytes = match serde_json::from_slice::<Value>(&bytes) {
+                Ok(mut payload) => match payload.get("error").and_then(Value::as_str) {
+                    Some(message) => {
+                        let localized = localizer.localize(message, &language).await;
+                        translated = localized != message;
+                        payload["error"] = Value::String(localized);
+                        serde_json::to_vec(&payload).map(Into::into).unwrap_or(bytes)
+                    }
+                    None => bytes,
+                },
+                Err(_) => bytes,
+            };
+
+            let mut response = HttpResponse::build(status);
+            for (name, value) in headers.iter() {
+                if name != header::CONTENT_LENGTH {
+                    response.append_header((name.clone(), value.clone()));
+                }
+            }
+            if translated {
+                if let Ok(value) = HeaderValue::from_str(&language) {
+                    response.insert_header((header::CONTENT_LANGUAGE, value));
+                }
+            }
+            Ok(ServiceResponse::new(request, response.body(bytes)))
+        })
+    }
+}
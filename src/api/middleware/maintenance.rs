@@ -0,0 +1,221 @@
+#![cfg(feature = "api")]
+
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::{Notify, RwLock};
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::Method,
+    Error, HttpResponse,
+};
+use chrono::{DateTime, Utc};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+
+use crate::api::middleware::held_body::HeldBody;
+use crate::utils::pattern::wildcard_matches;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    pub message: String,
+    pub retry_after_secs: u64,
+    // Never blocked, so maintenance can be switched off again and probes
+    // keep reporting
+    pub exempt_paths: Vec<String>,
+    // Reads that start new long-running work and are refused too
+    pub blocked_reads: Vec<String>,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            message: "The service is undergoing maintenance, please retry shortly".to_string(),
+            retry_after_secs: 120,
+            exempt_paths: vec!["/v1/admin/*".to_string(), "/health".to_string(), "/metrics".to_string()],
+            blocked_reads: vec!["/v1/agent/*".to_string(), "/v1/incidents/events".to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+    pub message: String,
+    pub since: Option<DateTime<Utc>>,
+    pub in_flight: u64,
+}
+
+pub struct MaintenanceMode {
+    config: MaintenanceConfig,
+    enabled: AtomicBool,
+    message: RwLock<String>,
+    since: RwLock<Option<DateTime<Utc>>>,
+    in_flight: Arc<AtomicU64>,
+    drained: Arc<Notify>,
+}
+
+impl MaintenanceMode {
+    pub fn new(config: MaintenanceConfig) -> Self {
+        Self {
+            message: RwLock::new(config.message.clone()),
+            config,
+            enabled: AtomicBool::new(false),
+            since: RwLock::new(None),
+            in_flight: Arc::new(AtomicU64::new(0)),
+            drained: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Acquire)
+    }
+
+    pub async fn enable(&self, message: Option<String>) {
+        *self.message.write().await = message.unwrap_or_else(|| self.config.message.clone());
+        *self.since.write().await = Some(Utc::now());
+        self.enabled.store(true, Ordering::Release);
+        log::warn!("Maintenance mode enabled");
+    }
+
+    pub async fn disable(&self) {
+        self.enabled.store(false, Ordering::Release);
+        *self.since.write().await = None;
+        log::warn!("Maintenance mode disabled");
+    }
+
+    pub async fn status(&self) -> MaintenanceStatus {
+        MaintenanceStatus {
+            enabled: self.is_enabled(),
+            message: self.message.read().await.clone(),
+            since: *self.since.read().await,
+            in_flight: self.in_flight.load(Ordering::Acquire),
+        }
+    }
+
+    // Waits for requests admitted before maintenance started to finish.
+    // Returns false if some were still running at the timeout.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let notified = self.drained.notified();
+            if self.in_flight.load(Ordering::Acquire) == 0 {
+                return true;
+            }
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                return self.in_flight.load(Ordering::Acquire) == 0;
+            }
+        }
+    }
+
+    // Whether maintenance mode refuses this request. These are also the
+    // requests a drain waits for; reads keep flowing throughout.
+    pub fn is_restricted(&self, method: &Method, path: &str) -> bool {
+        if self.config.exempt_paths.iter().any(|p| wildcard_matches(p, path)) {
+            return false;
+        }
+        let is_read = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+        !is_read || self.config.blocked_reads.iter().any(|p| wildcard_matches(p, path))
+    }
+
+    fn track(&self) -> InFlight {
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+        InFlight {
+            in_flight: self.in_flight.clone(),
+            drained: self.drained.clone(),
+        }
+    }
+}
+
+// Counts a restricted request until its response body is done
+pub struct InFlight {
+    in_flight: Arc<AtomicU64>,
+    drained: Arc<Notify>,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if self.in_flight.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.drained.notify_waiters();
+        }
+    }
+}
+
+pub struct Maintenance {
+    mode: Arc<MaintenanceMode>,
+}
+
+impl Maintenance {
+    pub fn new(mode: Arc<MaintenanceMode>) -> Self {
+        Self { mode }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Maintenance
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<HeldBody<B, Option<InFlight>>>>;
+    type Error = Error;
+    type Transform = MaintenanceMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(MaintenanceMiddleware {
+            service: Rc::new(service),
+            mode: self.mode.clone(),
+        }))
+    }
+}
+
+pub struct MaintenanceMiddleware<S> {
+    service: Rc<S>,
+    mode: Arc<MaintenanceMode>,
+}
+
+impl<S, B> Service<ServiceRequest> for MaintenanceMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<HeldBody<B, Option<InFlight>>>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let mode = self.mode.clone();
+
+        Box::pin(async move {
+            if !mode.is_restricted(req.method(), req.path()) {
+                return service.call(req).await
+                    .map(|res| res.map_body(|_, body| HeldBody::new(body, None)).map_into_left_body());
+            }
+
+            // Counted before the check so a drain started right after
+            // can't miss a request that is about to be admitted
+            let in_flight = mode.track();
+            if mode.is_enabled() {
+                drop(in_flight);
+                let message = mode.message.read().await.clone();
+                let response = HttpResponse::ServiceUnavailable()
+                    .insert_header(("Retry-After", mode.config.retry_after_secs.to_string()))
+                    .json(json!({ "error": message, "maintenance": true }));
+                return Ok(req.into_response(response).map_into_right_body());
+            }
+
+            // The body holds the count, so a drain also waits for
+            // streamed responses that are still being written
+            let res = service.call(req).await?;
+            Ok(res.map_body(|_, body| HeldBody::new(body, Some(in_flight))).map_into_left_body())
+        })
+    }
+}
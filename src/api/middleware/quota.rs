@@ -0,0 +1,222 @@
+#![cfg(feature = "api")]
+
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderMap, HeaderName, HeaderValue},
+    Error, HttpMessage, HttpResponse,
+};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use serde_json::json;
+
+use crate::api::middleware::held_body::HeldBody;
+use crate::api::principal::Principal;
+use crate::core::usage::{Meter, QuotaStatus, UsageLedger, ANONYMOUS_TENANT};
+
+// Handlers that consume budget attach this to their response, e.g.
+// `MeteredUsage { tokens: usage.total_tokens as u64, frames: 0 }.attach(response)`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MeteredUsage {
+    pub tokens: u64,
+    pub frames: u64,
+}
+
+impl MeteredUsage {
+    pub fn attach(self, mut response: HttpResponse) -> HttpResponse {
+        response.extensions_mut().insert(self);
+        response
+    }
+
+    pub fn of(&self, meter: Meter) -> u64 {
+        match meter {
+            Meter::Tokens => self.tokens,
+            Meter::Frames => self.frames,
+        }
+    }
+}
+
+// A streamed response only knows its usage once the body has been sent.
+// The handler attaches this and adds tokens to `counter()` as they're
+// produced; the reservation is held until the body finishes or the client
+// goes away, then settled with whatever was counted.
+#[derive(Debug, Clone, Default)]
+pub struct StreamedUsage {
+    tokens: Arc<AtomicU64>,
+}
+
+impl StreamedUsage {
+    pub fn attach(&self, mut response: HttpResponse) -> HttpResponse {
+        response.extensions_mut().insert(self.clone());
+        response
+    }
+
+    pub fn counter(&self) -> Arc<AtomicU64> {
+        self.tokens.clone()
+    }
+
+    fn usage(&self) -> MeteredUsage {
+        MeteredUsage { tokens: self.tokens.load(Ordering::Relaxed), frames: 0 }
+    }
+}
+
+// Settles a streamed response's reservation when its body is dropped
+pub struct Settlement {
+    ledger: Arc<UsageLedger>,
+    tenant: String,
+    reserved: Option<(Meter, u64)>,
+    usage: StreamedUsage,
+}
+
+impl Drop for Settlement {
+    fn drop(&mut self) {
+        let (ledger, tenant, reserved) = (self.ledger.clone(), std::mem::take(&mut self.tenant), self.reserved);
+        let usage = self.usage.usage();
+        tokio::spawn(async move { charge(&ledger, &tenant, reserved, usage).await });
+    }
+}
+
+async fn charge(ledger: &UsageLedger, tenant: &str, reserved: Option<(Meter, u64)>, usage: MeteredUsage) {
+    for other in [Meter::Tokens, Meter::Frames] {
+        match reserved {
+            Some((meter, held)) if meter == other => ledger.settle(tenant, meter, held, usage.of(meter)).await,
+            _ => ledger.record(tenant, other, usage.of(other)).await,
+        }
+    }
+}
+
+// Budgets belong to the authenticated tenant, or to the caller itself when
+// its token carries none
+pub fn tenant_of(principal: Option<&Principal>) -> String {
+    match principal {
+        Some(principal) => principal.tenant.clone().unwrap_or_else(|| principal.subject.clone()),
+        None => ANONYMOUS_TENANT.to_string(),
+    }
+}
+
+fn refused(status: &QuotaStatus) -> HttpResponse {
+    let mut response = HttpResponse::TooManyRequests().json(json!({
+        "error": "Quota exceeded",
+        "meter": status.meter,
+        "period": status.period,
+        "resets_at": status.resets_at,
+    }));
+    insert_quota_headers(response.headers_mut(), status);
+    response
+}
+
+fn insert_quota_headers(headers: &mut HeaderMap, status: &QuotaStatus) {
+    let values = [
+        ("x-quota-limit", status.limit.to_string()),
+        ("x-quota-remaining", status.remaining.to_string()),
+        ("x-quota-reset", status.resets_at.timestamp().to_string()),
+        ("x-quota-period", serde_json::to_value(status.period)
+            .ok()
+            .and_then(|v| v.as_str().map(|s| s.to_string()))
+            .unwrap_or_default()),
+    ];
+    for (name, value) in values {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(HeaderName::from_static(name), value);
+        }
+    }
+}
+
+pub struct Quota {
+    ledger: Arc<UsageLedger>,
+}
+
+impl Quota {
+    pub fn new(ledger: Arc<UsageLedger>) -> Self {
+        Self { ledger }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Quota
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<HeldBody<B, Option<Settlement>>>>;
+    type Error = Error;
+    type Transform = QuotaMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(QuotaMiddleware {
+            service: Rc::new(service),
+            ledger: self.ledger.clone(),
+        }))
+    }
+}
+
+pub struct QuotaMiddleware<S> {
+    service: Rc<S>,
+    ledger: Arc<UsageLedger>,
+}
+
+impl<S, B> Service<ServiceRequest> for QuotaMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let ledger = self.ledger.clone();
+
+        Box::pin(async move {
+            if !ledger.config().enabled {
+                return service.call(req).await
+                    .map(|res| res.map_body(|_, body| HeldBody::new(body, None)).map_into_left_body());
+            }
+            let tenant = tenant_of(req.extensions().get::<Principal>());
+            let meter = ledger.meter_for_path(req.path());
+
+            // Token usage isn't known until the model has answered, so an
+            // estimate is held for the duration of the call
+            let reserved = match meter {
+                Some(meter) => match ledger.reserve(&tenant, meter).await {
+                    Ok(reserved) => Some((meter, reserved)),
+                    Err(status) => {
+                        return Ok(req.into_response(refused(&status)).map_into_right_body());
+                    }
+                },
+                None => None,
+            };
+
+            let mut res = match service.call(req).await {
+                Ok(res) => res,
+                Err(e) => {
+                    if let Some((meter, reserved)) = reserved {
+                        ledger.settle(&tenant, meter, reserved, 0).await;
+                    }
+                    return Err(e);
+                }
+            };
+            let streamed = res.response().extensions().get::<StreamedUsage>().cloned();
+            let settlement = match streamed {
+                Some(usage) => Some(Settlement { ledger: ledger.clone(), tenant: tenant.clone(), reserved, usage }),
+                None => {
+                    let usage = res.response().extensions().get::<MeteredUsage>().copied().unwrap_or_default();
+                    charge(&ledger, &tenant, reserved, usage).await;
+                    None
+                }
+            };
+            if let Some(meter) = meter {
+                if let Some(status) = ledger.tightest(&tenant, meter).await {
+                    insert_quota_headers(res.headers_mut(), &status);
+                }
+            }
+            Ok(res.map_body(|_, body| HeldBody::new(body, settlement)).map_into_left_body())
+        })
+    }
+}
@@ -0,0 +1,524 @@
+#![cfg(feature = "api")]
+
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderMap, HeaderName, HeaderValue},
+    Error, HttpResponse,
+};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::api::middleware::held_body::HeldBody;
+use crate::api::principal::Principal;
+use crate::utils::pattern::wildcard_matches;
+
+// Fixed windows let a client send twice the limit across a window boundary.
+// The sliding log doesn't, at the cost of a timestamp per request, and
+// concurrency caps requests in flight regardless of rate (window_secs is
+// ignored), which suits GPU-bound endpoints better than either.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitAlgorithm {
+    #[default]
+    FixedWindow,
+    SlidingWindow,
+    Concurrency,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LimitSpec {
+    #[serde(default)]
+    pub algorithm: LimitAlgorithm,
+    pub requests: u32,
+    #[serde(default)]
+    pub window_secs: u64,
+}
+
+// Every selector that is set has to match. "path" is an exact path, or a
+// prefix when it ends in '*' ("/v1/vision/*"). "tenant" is the
+// authenticated caller's tenant. "api_key" is stored and returned as its
+// SHA-256 ("sha256:<hex>"); a raw key given to the admin API is hashed on
+// the way in.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RateLimitRule {
+    pub name: String,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub method: Option<String>,
+    #[serde(default)]
+    pub tenant: Option<String>,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    pub limit: LimitSpec,
+}
+
+impl RateLimitRule {
+    fn matches(&self, request: &RequestInfo) -> bool {
+        let path_matches = self.path.as_ref().is_none_or(|pattern| wildcard_matches(pattern, &request.path));
+        let method_matches = self.method.as_ref()
+            .is_none_or(|m| m.eq_ignore_ascii_case(&request.method));
+        let tenant_matches = self.tenant.as_ref()
+            .is_none_or(|t| request.tenant.as_ref() == Some(t));
+        let key_matches = self.api_key.as_ref()
+            .is_none_or(|k| request.api_key.as_ref().is_some_and(|presented| hash_key(presented) == *k));
+        path_matches && method_matches && tenant_matches && key_matches
+    }
+
+    fn specificity(&self) -> usize {
+        [self.path.is_some(), self.method.is_some(), self.tenant.is_some(), self.api_key.is_some()]
+            .iter()
+            .filter(|set| **set)
+            .count()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    // Applies when no rule matches; None leaves such requests unlimited
+    pub default_limit: Option<LimitSpec>,
+    pub rules: Vec<RateLimitRule>,
+    pub api_key_header: String,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            default_limit: Some(LimitSpec { algorithm: LimitAlgorithm::FixedWindow, requests: 60, window_secs: 60 }),
+            rules: Vec::new(),
+            api_key_header: "X-Api-Key".to_string(),
+        }
+    }
+}
+
+const KEY_HASH_PREFIX: &str = "sha256:";
+
+fn hash_key(key: &str) -> String {
+    format!("{}{}", KEY_HASH_PREFIX, hex::encode(Sha256::digest(key.as_bytes())))
+}
+
+impl RateLimitConfig {
+    // Configured keys are kept only as hashes
+    fn hash_keys(&mut self) {
+        for rule in &mut self.rules {
+            if let Some(key) = rule.api_key.as_mut().filter(|k| !k.starts_with(KEY_HASH_PREFIX)) {
+                *key = hash_key(key);
+            }
+        }
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let specs = self.default_limit.iter().chain(self.rules.iter().map(|r| &r.limit));
+        for spec in specs {
+            if spec.requests == 0 {
+                return Err(anyhow::anyhow!("Rate limits need to allow at least one request"));
+            }
+            if spec.window_secs == 0 && spec.algorithm != LimitAlgorithm::Concurrency {
+                return Err(anyhow::anyhow!("Windowed rate limits need a window_secs above zero"));
+            }
+        }
+        for (i, rule) in self.rules.iter().enumerate() {
+            if self.rules[..i].iter().any(|r| r.name == rule.name) {
+                return Err(anyhow::anyhow!("Duplicate rate limit rule: {}", rule.name));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Subject and tenant come from the authenticated principal, so the limiter
+// has to run inside the auth middleware. The presented key only selects
+// api_key rules, which it can only match by being the configured key.
+#[derive(Debug, Clone)]
+pub struct RequestInfo {
+    pub path: String,
+    pub method: String,
+    pub subject: Option<String>,
+    pub tenant: Option<String>,
+    pub api_key: Option<String>,
+    pub peer: Option<String>,
+}
+
+impl RequestInfo {
+    pub fn from_request(req: &ServiceRequest, config: &RateLimitConfig) -> Self {
+        let header = |name: &str| req.headers().get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+        let bearer = header("Authorization")
+            .and_then(|v| v.strip_prefix("Bearer ").map(|t| t.trim().to_string()));
+        let principal = req.extensions().get::<Principal>().cloned();
+
+        Self {
+            path: req.path().to_string(),
+            method: req.method().to_string(),
+            subject: principal.as_ref().map(|p| p.subject.clone()),
+            tenant: principal.and_then(|p| p.tenant),
+            api_key: header(&config.api_key_header).or(bearer),
+            // The socket address: forwarding headers are client-controlled
+            peer: req.peer_addr().map(|a| a.ip().to_string()),
+        }
+    }
+
+    // Who the counter belongs to. Only identities a client can't pick for
+    // itself count: the matched key, the authenticated subject (or its
+    // tenant, under a tenant rule) and otherwise the peer address.
+    fn client(&self, rule: Option<&RateLimitRule>) -> String {
+        if let Some(key) = rule.and_then(|r| r.api_key.as_ref()) {
+            return format!("key:{}", key);
+        }
+        if rule.is_some_and(|r| r.tenant.is_some()) {
+            if let Some(tenant) = &self.tenant {
+                return format!("tenant:{}", tenant);
+            }
+        }
+        if let Some(subject) = &self.subject {
+            return format!("subject:{}", subject);
+        }
+        format!("ip:{}", self.peer.as_deref().unwrap_or("unknown"))
+    }
+}
+
+// Held for the duration of a request under a concurrency limit, until
+// its response body has been sent
+#[derive(Debug)]
+pub struct ConcurrencyPermit {
+    in_flight: Arc<AtomicU32>,
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[derive(Debug)]
+pub struct Decision {
+    pub allowed: bool,
+    pub rule: Option<String>,
+    pub limit: u32,
+    pub remaining: u32,
+    pub retry_after_secs: u64,
+    // Until the window resets (fixed) or a slot frees up (sliding)
+    pub reset_secs: u64,
+    pub permit: Option<ConcurrencyPermit>,
+}
+
+impl Decision {
+    fn unlimited() -> Self {
+        Self { allowed: true, rule: None, limit: 0, remaining: 0, retry_after_secs: 0, reset_secs: 0, permit: None }
+    }
+}
+
+enum Counter {
+    Fixed { started: Instant, count: u32 },
+    Sliding(VecDeque<Instant>),
+    InFlight(Arc<AtomicU32>),
+}
+
+struct Tracked {
+    spec: LimitSpec,
+    counter: Counter,
+}
+
+impl Tracked {
+    fn new(spec: &LimitSpec, now: Instant) -> Self {
+        let counter = match spec.algorithm {
+            LimitAlgorithm::FixedWindow => Counter::Fixed { started: now, count: 0 },
+            LimitAlgorithm::SlidingWindow => Counter::Sliding(VecDeque::new()),
+            LimitAlgorithm::Concurrency => Counter::InFlight(Arc::new(AtomicU32::new(0))),
+        };
+        Self { spec: spec.clone(), counter }
+    }
+
+    fn is_idle(&self, now: Instant) -> bool {
+        let window = Duration::from_secs(self.spec.window_secs);
+        match &self.counter {
+            Counter::Fixed { started, .. } => now.duration_since(*started) >= window,
+            Counter::Sliding(log) => log.back().is_none_or(|last| now.duration_since(*last) >= window),
+            Counter::InFlight(in_flight) => in_flight.load(Ordering::Acquire) == 0,
+        }
+    }
+
+    fn admit(&mut self, limit: &LimitSpec, now: Instant) -> (bool, u32, Duration, Option<ConcurrencyPermit>) {
+        // A changed algorithm or window starts over; a changed request
+        // count applies to what is already counted
+        if self.spec.algorithm != limit.algorithm || self.spec.window_secs != limit.window_secs {
+            *self = Tracked::new(limit, now);
+        }
+        self.spec = limit.clone();
+        let window = Duration::from_secs(limit.window_secs);
+
+        match &mut self.counter {
+            Counter::Fixed { started, count } => {
+                if now.duration_since(*started) >= window {
+                    *started = now;
+                    *count = 0;
+                }
+                let allowed = *count < limit.requests;
+                if allowed {
+                    *count += 1;
+                }
+                let resets_in = window.saturating_sub(now.duration_since(*started));
+                (allowed, limit.requests.saturating_sub(*count), resets_in, None)
+            }
+            Counter::Sliding(log) => {
+                while log.front().is_some_and(|t| now.duration_since(*t) >= window) {
+                    log.pop_front();
+                }
+                let allowed = (log.len() as u32) < limit.requests;
+                if allowed {
+                    log.push_back(now);
+                }
+                // A slot frees up when the oldest request in the log ages out
+                let frees_in = log.front()
+                    .map_or(Duration::ZERO, |oldest| window.saturating_sub(now.duration_since(*oldest)));
+                (allowed, limit.requests.saturating_sub(log.len() as u32), frees_in, None)
+            }
+            Counter::InFlight(in_flight) => {
+                let current = in_flight.load(Ordering::Acquire);
+                if current >= limit.requests {
+                    return (false, 0, Duration::from_secs(1), None);
+                }
+                in_flight.fetch_add(1, Ordering::AcqRel);
+                let permit = ConcurrencyPermit { in_flight: in_flight.clone() };
+                (true, limit.requests - current - 1, Duration::ZERO, Some(permit))
+            }
+        }
+    }
+}
+
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+// Counters whose window has passed are dropped on a timer. If the map is
+// still full, new clients share one overflow counter per rule instead of
+// growing it.
+struct Windows {
+    tracked: HashMap<String, Tracked>,
+    swept: Instant,
+}
+
+impl Windows {
+    fn sweep(&mut self, now: Instant) {
+        if now.duration_since(self.swept) < SWEEP_INTERVAL && self.tracked.len() < MAX_TRACKED_CLIENTS {
+            return;
+        }
+        self.tracked.retain(|_, tracked| !tracked.is_idle(now));
+        self.swept = now;
+    }
+}
+
+pub struct RateLimiter {
+    config: RwLock<RateLimitConfig>,
+    windows: Mutex<Windows>,
+}
+
+impl RateLimiter {
+    pub fn new(mut config: RateLimitConfig) -> anyhow::Result<Self> {
+        config.validate()?;
+        config.hash_keys();
+        Ok(Self {
+            config: RwLock::new(config),
+            windows: Mutex::new(Windows { tracked: HashMap::new(), swept: Instant::now() }),
+        })
+    }
+
+
+    pub async fn config(&self) -> RateLimitConfig {
+        self.config.read().await.clone()
+    }
+
+    // Counters of rules that still exist are kept, so tightening a limit
+    // at runtime applies to traffic already in the window
+    pub async fn replace_config(&self, mut config: RateLimitConfig) -> anyhow::Result<()> {
+        config.validate()?;
+        config.hash_keys();
+        *self.config.write().await = config;
+        Ok(())
+    }
+
+    pub async fn upsert_rule(&self, rule: RateLimitRule) -> anyhow::Result<()> {
+        let mut config = self.config.read().await.clone();
+        match config.rules.iter_mut().find(|r| r.name == rule.name) {
+            Some(existing) => *existing = rule,
+            None => config.rules.push(rule),
+        }
+        self.replace_config(config).await
+    }
+
+    pub async fn remove_rule(&self, name: &str) -> anyhow::Result<()> {
+        let mut config = self.config.read().await.clone();
+        let before = config.rules.len();
+        config.rules.retain(|r| r.name != name);
+        if config.rules.len() == before {
+            return Err(anyhow::anyhow!("Rate limit rule not found: {}", name));
+        }
+        self.replace_config(config).await?;
+        let prefix = format!("{}|", name);
+        self.windows.lock().await.tracked.retain(|key, _| !key.starts_with(&prefix));
+        Ok(())
+    }
+
+    // The most specific matching rule wins; among equals, the first listed
+    pub async fn resolve(&self, request: &RequestInfo) -> Option<(Option<RateLimitRule>, LimitSpec)> {
+        let config = self.config.read().await;
+        if !config.enabled {
+            return None;
+        }
+        let mut best: Option<&RateLimitRule> = None;
+        for rule in config.rules.iter().filter(|r| r.matches(request)) {
+            if best.is_none_or(|b| rule.specificity() > b.specificity()) {
+                best = Some(rule);
+            }
+        }
+        match best {
+            Some(rule) => Some((Some(rule.clone()), rule.limit.clone())),
+            None => config.default_limit.clone().map(|limit| (None, limit)),
+        }
+    }
+
+    pub async fn check(&self, request: &RequestInfo) -> Decision {
+        let (rule, limit) = match self.resolve(request).await {
+            Some(resolved) => resolved,
+            None => return Decision::unlimited(),
+        };
+        let rule_name = rule.as_ref().map_or("default", |r| r.name.as_str()).to_string();
+        let mut key = format!("{}|{}", rule_name, request.client(rule.as_ref()));
+        let now = Instant::now();
+
+        let mut windows = self.windows.lock().await;
+        windows.sweep(now);
+        if windows.tracked.len() >= MAX_TRACKED_CLIENTS && !windows.tracked.contains_key(&key) {
+            key = format!("{}|overflow", rule_name);
+        }
+        let (allowed, remaining, retry_in, permit) = windows.tracked.entry(key)
+            .or_insert_with(|| Tracked::new(&limit, now))
+            .admit(&limit, now);
+
+        Decision {
+            allowed,
+            rule: Some(rule_name),
+            limit: limit.requests,
+            remaining,
+            retry_after_secs: if allowed { 0 } else { retry_in.as_secs().max(1) },
+            reset_secs: retry_in.as_secs(),
+            permit,
+        }
+    }
+}
+
+fn insert_rate_limit_headers(headers: &mut HeaderMap, decision: &Decision) {
+    if decision.rule.is_none() {
+        return;
+    }
+    let values = [
+        ("x-ratelimit-limit", decision.limit),
+        ("x-ratelimit-remaining", decision.remaining),
+    ];
+    for (name, value) in values {
+        headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+    }
+    headers.insert(HeaderName::from_static("x-ratelimit-reset"), HeaderValue::from(decision.reset_secs));
+}
+
+pub struct RateLimit {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimit {
+    // A single limit for everything the middleware wraps
+    pub fn new(requests: u32, window_secs: u64) -> Self {
+        let config = RateLimitConfig {
+            default_limit: Some(LimitSpec { algorithm: LimitAlgorithm::FixedWindow, requests, window_secs }),
+            ..RateLimitConfig::default()
+        };
+        Self {
+            limiter: Arc::new(RateLimiter::new(config).expect("Invalid rate limit")),
+        }
+    }
+
+    // Shares the limiter with the admin API so changes apply immediately
+    pub fn from_limiter(limiter: Arc<RateLimiter>) -> Self {
+        Self { limiter }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<HeldBody<B, Option<ConcurrencyPermit>>>>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+            limiter: self.limiter.clone(),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<HeldBody<B, Option<ConcurrencyPermit>>>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let limiter = self.limiter.clone();
+
+        Box::pin(async move {
+            let request = {
+                let config = limiter.config.read().await;
+                RequestInfo::from_request(&req, &config)
+            };
+            let mut decision = limiter.check(&request).await;
+            if decision.allowed {
+                // The body holds the concurrency slot, so a streamed
+                // response keeps it until the last chunk is written
+                let permit = decision.permit.take();
+                let mut res = service.call(req).await?;
+                insert_rate_limit_headers(res.headers_mut(), &decision);
+                return Ok(res.map_body(|_, body| HeldBody::new(body, permit)).map_into_left_body());
+            }
+
+            let mut response = HttpResponse::TooManyRequests()
+                .insert_header(("Retry-After", decision.retry_after_secs.to_string()))
+                .json(json!({
+                    "error": "Rate limit exceeded",
+                    "rule": decision.rule,
+                    "retry_after_secs": decision.retry_after_secs,
+                }));
+            insert_rate_limit_headers(response.headers_mut(), &decision);
+            Ok(req.into_response(response).map_into_right_body())
+        })
+    }
+}
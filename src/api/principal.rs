@@ -0,0 +1,46 @@
+#![cfg(feature = "api")]
+
+use std::future::{ready, Ready};
+use actix_web::{dev::Payload, error::InternalError, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+
+// The authenticated caller. The auth middleware inserts one into the
+// request extensions once it has verified the bearer token; handlers that
+// scope data to a caller take it as an extractor. Anything derived from
+// request headers is client-controlled and must not be used in its place.
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Principal {
+    // Token subject: a user or API client id
+    pub subject: String,
+    pub tenant: Option<String>,
+    #[serde(default)]
+    pub admin: bool,
+}
+
+impl Principal {
+    // Admins see everything; otherwise only what the caller owns
+    pub fn can_access(&self, owner: &str) -> bool {
+        self.admin || self.subject == owner
+    }
+
+    // Owner filter for listings: None for admins, who see every owner
+    pub fn owner_filter(&self) -> Option<&str> {
+        (!self.admin).then_some(self.subject.as_str())
+    }
+}
+
+impl FromRequest for Principal {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(req.extensions().get::<Principal>().cloned().ok_or_else(|| {
+            InternalError::from_response(
+                "missing principal",
+                HttpResponse::Unauthorized().json(json!({"error": "Authentication required"})),
+            ).into()
+        }))
+    }
+}
@@ -0,0 +1,434 @@
+#![cfg(all(feature = "api", feature = "vision"))]
+
+use anyhow::{Result, Context};
+use chrono::{DateTime, TimeZone, Utc};
+use opencv::core::{Point, Rect};
+
+use crate::core::sinks::SinkRecord;
+use crate::vision::{
+    detector::{Detection, BBox},
+    conditions::SceneCondition,
+    analyzer::{
+        Analysis, SceneInfo, MotionInfo, MotionVector, BehaviorInfo, PatternInfo,
+        Activity, Interaction, Anomaly, Pattern, Repetition, TemporalInfo,
+    },
+    density::DensityInfo,
+    scene::SceneLabel,
+};
+
+pub mod v1 {
+    include!(concat!(env!("OUT_DIR"), "/vae.v1.rs"));
+}
+
+fn to_timestamp(time: &DateTime<Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: time.timestamp(),
+        nanos: time.timestamp_subsec_nanos() as i32,
+    }
+}
+
+fn from_timestamp(timestamp: Option<prost_types::Timestamp>) -> Result<DateTime<Utc>> {
+    let timestamp = timestamp.context("Missing timestamp")?;
+    Utc.timestamp_opt(timestamp.seconds, timestamp.nanos as u32)
+        .single()
+        .ok_or_else(|| anyhow::anyhow!("Invalid timestamp: {:?}", timestamp))
+}
+
+impl From<&BBox> for v1::BBox {
+    fn from(bbox: &BBox) -> Self {
+        Self {
+            x: bbox.x,
+            y: bbox.y,
+            width: bbox.width,
+            height: bbox.height,
+        }
+    }
+}
+
+impl From<v1::BBox> for BBox {
+    fn from(bbox: v1::BBox) -> Self {
+        Self {
+            x: bbox.x,
+            y: bbox.y,
+            width: bbox.width,
+            height: bbox.height,
+        }
+    }
+}
+
+impl From<&Detection> for v1::Detection {
+    fn from(detection: &Detection) -> Self {
+        Self {
+            bbox: Some((&detection.bbox).into()),
+            class_id: detection.class_id as u64,
+            class_name: detection.class_name.clone(),
+            confidence: detection.confidence,
+            frame_id: detection.frame_id,
+            timestamp: Some(to_timestamp(&detection.timestamp)),
+        }
+    }
+}
+
+impl TryFrom<v1::Detection> for Detection {
+    type Error = anyhow::Error;
+
+    fn try_from(detection: v1::Detection) -> Result<Self> {
+        Ok(Self {
+            bbox: detection.bbox.context("Detection is missing bbox")?.into(),
+            class_id: detection.class_id as usize,
+            class_name: detection.class_name,
+            confidence: detection.confidence,
+            frame_id: detection.frame_id,
+            timestamp: from_timestamp(detection.timestamp)?,
+        })
+    }
+}
+
+impl From<&Point> for v1::Point {
+    fn from(point: &Point) -> Self {
+        Self { x: point.x, y: point.y }
+    }
+}
+
+impl From<v1::Point> for Point {
+    fn from(point: v1::Point) -> Self {
+        Point::new(point.x, point.y)
+    }
+}
+
+impl From<&Rect> for v1::Rect {
+    fn from(rect: &Rect) -> Self {
+        Self {
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            height: rect.height,
+        }
+    }
+}
+
+impl From<v1::Rect> for Rect {
+    fn from(rect: v1::Rect) -> Self {
+        Rect::new(rect.x, rect.y, rect.width, rect.height)
+    }
+}
+
+impl From<&SceneInfo> for v1::SceneInfo {
+    fn from(info: &SceneInfo) -> Self {
+        Self {
+            scene_type: info.scene_type.clone(),
+            confidence: info.confidence,
+            labels: info.labels.iter()
+                .map(|l| v1::SceneLabel { label: l.label.clone(), confidence: l.confidence })
+                .collect(),
+            objects: info.objects.clone(),
+            lighting: info.lighting.clone(),
+            composition: info.composition.clone(),
+            condition: info.condition.map(|c| c.to_string()).unwrap_or_default(),
+        }
+    }
+}
+
+impl TryFrom<v1::SceneInfo> for SceneInfo {
+    type Error = anyhow::Error;
+
+    fn try_from(info: v1::SceneInfo) -> Result<Self> {
+        let condition = if info.condition.is_empty() {
+            None
+        } else {
+            Some(info.condition.parse::<SceneCondition>()?)
+        };
+
+        Ok(Self {
+            scene_type: info.scene_type,
+            confidence: info.confidence,
+            labels: info.labels.into_iter()
+                .map(|l| SceneLabel { label: l.label, confidence: l.confidence })
+                .collect(),
+            objects: info.objects,
+            lighting: info.lighting,
+            composition: info.composition,
+            condition,
+        })
+    }
+}
+
+impl From<&MotionInfo> for v1::MotionInfo {
+    fn from(info: &MotionInfo) -> Self {
+        Self {
+            motion_vectors: info.motion_vectors.iter()
+                .map(|v| v1::MotionVector {
+                    start: Some((&v.start).into()),
+                    end: Some((&v.end).into()),
+                    magnitude: v.magnitude,
+                    direction: v.direction,
+                })
+                .collect(),
+            global_motion: info.global_motion,
+            motion_areas: info.motion_areas.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<v1::MotionInfo> for MotionInfo {
+    type Error = anyhow::Error;
+
+    fn try_from(info: v1::MotionInfo) -> Result<Self> {
+        let motion_vectors = info.motion_vectors.into_iter()
+            .map(|v| {
+                Ok(MotionVector {
+                    start: v.start.context("Motion vector is missing start")?.into(),
+                    end: v.end.context("Motion vector is missing end")?.into(),
+                    magnitude: v.magnitude,
+                    direction: v.direction,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            motion_vectors,
+            global_motion: info.global_motion,
+            motion_areas: info.motion_areas.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
+impl From<&Anomaly> for v1::Anomaly {
+    fn from(anomaly: &Anomaly) -> Self {
+        Self {
+            anomaly_type: anomaly.anomaly_type.clone(),
+            confidence: anomaly.confidence,
+            description: anomaly.description.clone(),
+        }
+    }
+}
+
+impl From<v1::Anomaly> for Anomaly {
+    fn from(anomaly: v1::Anomaly) -> Self {
+        Self {
+            anomaly_type: anomaly.anomaly_type,
+            confidence: anomaly.confidence,
+            description: anomaly.description,
+        }
+    }
+}
+
+impl From<&BehaviorInfo> for v1::BehaviorInfo {
+    fn from(info: &BehaviorInfo) -> Self {
+        Self {
+            activities: info.activities.iter()
+                .map(|a| v1::Activity {
+                    action_type: a.action_type.clone(),
+                    confidence: a.confidence,
+                    duration: a.duration,
+                    objects_involved: a.objects_involved.clone(),
+                })
+                .collect(),
+            interactions: info.interactions.iter()
+                .map(|i| v1::Interaction {
+                    interaction_type: i.interaction_type.clone(),
+                    objects: i.objects.clone(),
+                    duration: i.duration,
+                })
+                .collect(),
+            anomalies: info.anomalies.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<v1::BehaviorInfo> for BehaviorInfo {
+    fn from(info: v1::BehaviorInfo) -> Self {
+        Self {
+            activities: info.activities.into_iter()
+                .map(|a| Activity {
+                    action_type: a.action_type,
+                    confidence: a.confidence,
+                    duration: a.duration,
+                    objects_involved: a.objects_involved,
+                })
+                .collect(),
+            interactions: info.interactions.into_iter()
+                .map(|i| Interaction {
+                    interaction_type: i.interaction_type,
+                    objects: i.objects,
+                    duration: i.duration,
+                })
+                .collect(),
+            anomalies: info.anomalies.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<&PatternInfo> for v1::PatternInfo {
+    fn from(info: &PatternInfo) -> Self {
+        Self {
+            patterns: info.patterns.iter()
+                .map(|p| v1::Pattern {
+                    pattern_type: p.pattern_type.clone(),
+                    confidence: p.confidence,
+                    description: p.description.clone(),
+                })
+                .collect(),
+            repetitions: info.repetitions.iter()
+                .map(|r| v1::Repetition {
+                    event_type: r.event_type.clone(),
+                    frequency: r.frequency,
+                    duration: r.duration,
+                })
+                .collect(),
+            temporal_info: Some(v1::TemporalInfo {
+                start_time: Some(to_timestamp(&info.temporal_info.start_time)),
+                end_time: Some(to_timestamp(&info.temporal_info.end_time)),
+                duration: info.temporal_info.duration,
+            }),
+        }
+    }
+}
+
+impl TryFrom<v1::PatternInfo> for PatternInfo {
+    type Error = anyhow::Error;
+
+    fn try_from(info: v1::PatternInfo) -> Result<Self> {
+        let temporal = info.temporal_info.context("Pattern info is missing temporal_info")?;
+
+        Ok(Self {
+            patterns: info.patterns.into_iter()
+                .map(|p| Pattern {
+                    pattern_type: p.pattern_type,
+                    confidence: p.confidence,
+                    description: p.description,
+                })
+                .collect(),
+            repetitions: info.repetitions.into_iter()
+                .map(|r| Repetition {
+                    event_type: r.event_type,
+                    frequency: r.frequency,
+                    duration: r.duration,
+                })
+                .collect(),
+            temporal_info: TemporalInfo {
+                start_time: from_timestamp(temporal.start_time)?,
+                end_time: from_timestamp(temporal.end_time)?,
+                duration: temporal.duration,
+            },
+        })
+    }
+}
+
+impl From<&DensityInfo> for v1::DensityInfo {
+    fn from(info: &DensityInfo) -> Self {
+        Self {
+            count: info.count,
+            rows: info.rows as u32,
+            cols: info.cols as u32,
+            cells: info.cells.clone(),
+        }
+    }
+}
+
+impl TryFrom<v1::DensityInfo> for DensityInfo {
+    type Error = anyhow::Error;
+
+    fn try_from(info: v1::DensityInfo) -> Result<Self> {
+        // rows and cols come off the wire; their product can overflow
+        let expected = (info.rows as usize).checked_mul(info.cols as usize);
+        if expected != Some(info.cells.len()) {
+            return Err(anyhow::anyhow!(
+                "Density grid has {} cells, expected {}x{}", info.cells.len(), info.rows, info.cols
+            ));
+        }
+        Ok(Self {
+            count: info.count,
+            rows: info.rows as usize,
+            cols: info.cols as usize,
+            cells: info.cells,
+        })
+    }
+}
+
+impl From<&Analysis> for v1::Analysis {
+    fn from(analysis: &Analysis) -> Self {
+        Self {
+            frame_id: analysis.frame_id,
+            timestamp: Some(to_timestamp(&analysis.timestamp)),
+            scene_info: analysis.scene_info.as_ref().map(Into::into),
+            motion_info: analysis.motion_info.as_ref().map(Into::into),
+            behavior_info: analysis.behavior_info.as_ref().map(Into::into),
+            pattern_info: analysis.pattern_info.as_ref().map(Into::into),
+            density_info: analysis.density_info.as_ref().map(Into::into),
+        }
+    }
+}
+
+impl TryFrom<v1::Analysis> for Analysis {
+    type Error = anyhow::Error;
+
+    fn try_from(analysis: v1::Analysis) -> Result<Self> {
+        Ok(Self {
+            frame_id: analysis.frame_id,
+            timestamp: from_timestamp(analysis.timestamp)?,
+            scene_info: analysis.scene_info.map(TryInto::try_into).transpose()?,
+            motion_info: analysis.motion_info.map(TryInto::try_into).transpose()?,
+            behavior_info: analysis.behavior_info.map(Into::into),
+            pattern_info: analysis.pattern_info.map(TryInto::try_into).transpose()?,
+            density_info: analysis.density_info.map(TryInto::try_into).transpose()?,
+        })
+    }
+}
+
+impl From<&SinkRecord> for v1::PipelineResult {
+    fn from(record: &SinkRecord) -> Self {
+        Self {
+            delivery_id: record.delivery_id.clone(),
+            frame_id: record.frame_id,
+            source: record.source.clone(),
+            timestamp: Some(to_timestamp(&record.timestamp)),
+            detections: record.detections.iter().map(Into::into).collect(),
+            analysis: record.analysis.as_ref().map(Into::into),
+            metadata: record.metadata.clone().into_iter().collect(),
+        }
+    }
+}
+
+impl v1::Event {
+    pub fn detections(stream_id: &str, frame_id: u64, detections: &[Detection]) -> Self {
+        Self {
+            stream_id: stream_id.to_string(),
+            frame_id,
+            timestamp: Some(to_timestamp(&Utc::now())),
+            payload: Some(v1::event::Payload::Detections(v1::DetectionBatch {
+                detections: detections.iter().map(Into::into).collect(),
+            })),
+        }
+    }
+
+    pub fn analysis(stream_id: &str, analysis: &Analysis) -> Self {
+        Self {
+            stream_id: stream_id.to_string(),
+            frame_id: analysis.frame_id,
+            timestamp: Some(to_timestamp(&analysis.timestamp)),
+            payload: Some(v1::event::Payload::Analysis(analysis.into())),
+        }
+    }
+
+    pub fn anomaly(stream_id: &str, frame_id: u64, anomaly: &Anomaly) -> Self {
+        Self {
+            stream_id: stream_id.to_string(),
+            frame_id,
+            timestamp: Some(to_timestamp(&Utc::now())),
+            payload: Some(v1::event::Payload::Anomaly(anomaly.into())),
+        }
+    }
+
+    pub fn error(stream_id: &str, frame_id: u64, stage: &str, message: &str) -> Self {
+        Self {
+            stream_id: stream_id.to_string(),
+            frame_id,
+            timestamp: Some(to_timestamp(&Utc::now())),
+            payload: Some(v1::event::Payload::Error(v1::ErrorEvent {
+                message: message.to_string(),
+                stage: stage.to_string(),
+            })),
+        }
+    }
+}
@@ -0,0 +1,146 @@
+#![cfg(feature = "api")]
+
+use std::time::Duration;
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use serde::{Serialize, Deserialize};
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamShapingConfig {
+    // Upper bound on delivery rate per connection; None disables pacing
+    pub max_tokens_per_sec: Option<f32>,
+    // Chunks are coalesced until an event holds at least this many chars...
+    pub min_event_chars: usize,
+    // ...or the oldest buffered text has waited this long
+    pub max_coalesce_delay_ms: u64,
+    // Larger events are split so a single SSE frame stays bounded
+    pub max_event_chars: usize,
+}
+
+impl Default for StreamShapingConfig {
+    fn default() -> Self {
+        Self {
+            max_tokens_per_sec: None,
+            min_event_chars: 16,
+            max_coalesce_delay_ms: 50,
+            max_event_chars: 1024,
+        }
+    }
+}
+
+// Rough chars-per-token ratio for pacing; exact counts aren't needed to
+// protect slow clients
+const CHARS_PER_TOKEN: f32 = 4.0;
+
+struct TokenBucket {
+    rate: f32,
+    capacity: f32,
+    tokens: f32,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f32) -> Self {
+        // Allow roughly one second of burst
+        let capacity = rate.max(1.0);
+        Self {
+            rate,
+            capacity,
+            tokens: capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    async fn acquire(&mut self, tokens: f32) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f32();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens < tokens {
+            let wait = (tokens - self.tokens) / self.rate;
+            tokio::time::sleep(Duration::from_secs_f32(wait)).await;
+            self.tokens = 0.0;
+            self.last_refill = Instant::now();
+        } else {
+            self.tokens -= tokens;
+        }
+    }
+}
+
+// Wraps a stream of text deltas, coalescing tiny chunks and pacing output.
+// Errors from the inner stream flush any buffered text first, then propagate.
+pub fn shape_stream<S>(inner: S, config: StreamShapingConfig) -> impl Stream<Item = Result<String>>
+where
+    S: Stream<Item = Result<String>> + Send + Unpin + 'static,
+{
+    async_stream::stream! {
+        let mut inner = inner;
+        let mut buffer = String::new();
+        let mut bucket = config.max_tokens_per_sec
+            .filter(|rate| *rate > 0.0)
+            .map(TokenBucket::new);
+        let delay = Duration::from_millis(config.max_coalesce_delay_ms);
+        let mut deadline: Option<Instant> = None;
+        let mut finished = false;
+
+        while !finished {
+            let next = match deadline {
+                Some(deadline) => match tokio::time::timeout_at(deadline, inner.next()).await {
+                    Ok(item) => Some(item),
+                    Err(_) => None,
+                },
+                None => Some(inner.next().await),
+            };
+
+            let mut error = None;
+            let flush = match next {
+                // Coalesce deadline expired
+                None => true,
+                Some(Some(Ok(text))) => {
+                    if buffer.is_empty() {
+                        deadline = Some(Instant::now() + delay);
+                    }
+                    buffer.push_str(&text);
+                    buffer.len() >= config.min_event_chars
+                }
+                Some(Some(Err(e))) => {
+                    error = Some(e);
+                    true
+                }
+                Some(None) => {
+                    finished = true;
+                    true
+                }
+            };
+
+            if flush && !buffer.is_empty() {
+                for event in split_event(&buffer, config.max_event_chars) {
+                    if let Some(bucket) = bucket.as_mut() {
+                        bucket.acquire(event.chars().count() as f32 / CHARS_PER_TOKEN).await;
+                    }
+                    yield Ok(event);
+                }
+                buffer.clear();
+            }
+            if flush {
+                deadline = None;
+            }
+
+            if let Some(e) = error {
+                yield Err(e);
+                finished = true;
+            }
+        }
+    }
+}
+
+fn split_event(text: &str, max_chars: usize) -> Vec<String> {
+    if max_chars == 0 || text.chars().count() <= max_chars {
+        return vec![text.to_string()];
+    }
+
+    let chars: Vec<char> = text.chars().collect();
+    chars.chunks(max_chars).map(|c| c.iter().collect()).collect()
+}
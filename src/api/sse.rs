@@ -0,0 +1,106 @@
+#![cfg(feature = "api")]
+
+use std::time::Duration;
+use actix_web::{web, HttpResponse};
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use serde_json::json;
+use tokio_util::sync::CancellationToken;
+
+use crate::core::llm::annotated::AnnotatedChunk;
+
+// Comment lines sent while the model is quiet. Besides keeping proxies
+// from timing out the connection, a write is what tells actix the client
+// has gone, so this bounds how long an abandoned completion keeps running.
+const KEEPALIVE: Duration = Duration::from_secs(10);
+
+// One SSE frame; multi-line text becomes multiple data lines
+pub fn event(name: Option<&str>, data: &str) -> web::Bytes {
+    let mut frame = String::new();
+    if let Some(name) = name {
+        frame.push_str(&format!("event: {}\n", name));
+    }
+    for line in data.split('\n') {
+        frame.push_str(&format!("data: {}\n", line));
+    }
+    frame.push('\n');
+    web::Bytes::from(frame)
+}
+
+enum Next<T> {
+    Item(T),
+    Failed(anyhow::Error),
+    Keepalive,
+    Finished,
+}
+
+// Streams text deltas to the client as server-sent events. Actix drops the
+// body when the client disconnects, which cancels `cancel`; pass the same
+// token to complete_stream() (and anything else working for this request)
+// so the upstream completion stops with it. A stream that
+// runs to the end leaves the token alone.
+pub fn stream_response<S>(events: S, cancel: CancellationToken) -> HttpResponse
+where
+    S: Stream<Item = Result<String>> + Send + 'static,
+{
+    respond(events, cancel, |text| event(None, &text))
+}
+
+// Like stream_response(), but each event's data is the chunk as JSON, so
+// UIs get the model, finish reason and logprobs next to the text:
+//
+//   data: {"content":"Hel","model":"claude-sonnet-4"}
+//   data: {"content":"","finish_reason":"stop","model":"claude-sonnet-4"}
+pub fn stream_annotated<S>(chunks: S, cancel: CancellationToken) -> HttpResponse
+where
+    S: Stream<Item = Result<AnnotatedChunk>> + Send + 'static,
+{
+    respond(chunks, cancel, |chunk| {
+        event(None, &serde_json::to_string(&chunk).unwrap_or_default())
+    })
+}
+
+fn respond<S, T, F>(events: S, cancel: CancellationToken, render: F) -> HttpResponse
+where
+    S: Stream<Item = Result<T>> + Send + 'static,
+    T: Send + 'static,
+    F: Fn(T) -> web::Bytes + Send + 'static,
+{
+    let guard = cancel.drop_guard();
+    let body = async_stream::stream! {
+        let guard = guard;
+        let mut events = Box::pin(events);
+        let mut keepalive = tokio::time::interval(KEEPALIVE);
+        keepalive.tick().await;
+
+        loop {
+            let next = tokio::select! {
+                item = events.next() => match item {
+                    Some(Ok(item)) => Next::Item(item),
+                    Some(Err(e)) => Next::Failed(e),
+                    None => Next::Finished,
+                },
+                _ = keepalive.tick() => Next::Keepalive,
+            };
+            match next {
+                Next::Item(item) => {
+                    keepalive.reset();
+                    yield Ok::<_, actix_web::Error>(render(item));
+                }
+                Next::Keepalive => yield Ok(web::Bytes::from_static(b": keepalive\n\n")),
+                Next::Failed(e) => {
+                    log::warn!("Stream failed mid-response: {}", e);
+                    yield Ok(event(Some("error"), &json!({ "error": e.to_string() }).to_string()));
+                    break;
+                }
+                Next::Finished => break,
+            }
+        }
+        guard.disarm();
+    };
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(body)
+}
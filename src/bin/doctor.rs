@@ -0,0 +1,22 @@
+// Checks the environment against a config file and prints what to fix.
+//
+//     cargo run --bin doctor -- config/vae.yaml
+//
+// Exits non-zero when any check fails, so it can gate deploys.
+
+use anyhow::{Context, Result};
+use vae::utils::doctor::{run_checks, CheckStatus, DoctorConfig};
+
+fn main() -> Result<()> {
+    let path = std::env::args().nth(1).unwrap_or_else(|| "config/vae.yaml".to_string());
+    let raw = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path))?;
+    let config = DoctorConfig::from_yaml(&raw)?;
+
+    let report = run_checks(&config);
+    print!("{}", report.render());
+    if report.status() == CheckStatus::Fail {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,458 @@
+// Long-running soak test for the pipeline workers and backpressure. Pumps
+// synthetic frames through stages that randomly fail, panic and stall, then
+// checks that memory stayed flat, no frame got stuck and latency stayed
+// bounded. Needs the vision feature.
+//
+//     cargo run --release --bin soak -- --duration 3600 --fps 120 --fail-rate 0.02
+//
+// Exits non-zero when a check fails, so it can run as a nightly job.
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use vae::core::dead_letter::DeadLetterConfig;
+use vae::core::pipeline::{Pipeline, PipelineConfig, PipelineData, PipelineStage, StageType};
+use vae::core::sinks::ResultSink;
+use vae::core::stream_stats::{StreamStats, StreamStatsConfig};
+use vae::core::watchdog::WatchdogConfig;
+use vae::vision::processor::{Frame, FrameMetadata};
+use vae::vision::sources::{FrameSource, SyntheticSource};
+
+const USAGE: &str = "usage: soak [--duration SECS] [--fps N] [--workers N] [--buffer N] \
+[--width PX] [--height PX] [--stage-ms MS] [--fail-rate P] [--panic-rate P] [--stall-rate P] \
+[--stall-secs SECS] [--warmup SECS] [--max-p99-ms MS] [--max-rss-growth-mb MB] [--stuck-secs SECS]";
+
+#[derive(Debug, Clone)]
+struct SoakConfig {
+    duration_secs: u64,
+    fps: f64,
+    workers: usize,
+    buffer_size: usize,
+    width: u32,
+    height: u32,
+    // Simulated work per stage
+    stage_ms: u64,
+    // Per-stage probabilities of an injected error, panic or stall
+    fail_rate: f64,
+    panic_rate: f64,
+    stall_rate: f64,
+    stall_secs: u64,
+    // Memory is measured against a baseline taken after warmup, once
+    // buffers and worker state have reached their steady size
+    warmup_secs: u64,
+    max_p99_ms: f64,
+    max_rss_growth_mb: f64,
+    // Frames are in flight but none has completed for this long
+    stuck_secs: u64,
+}
+
+impl Default for SoakConfig {
+    fn default() -> Self {
+        Self {
+            duration_secs: 300,
+            fps: 60.0,
+            workers: 4,
+            buffer_size: 64,
+            width: 640,
+            height: 480,
+            stage_ms: 2,
+            fail_rate: 0.01,
+            panic_rate: 0.001,
+            stall_rate: 0.0005,
+            stall_secs: 3,
+            warmup_secs: 30,
+            max_p99_ms: 250.0,
+            max_rss_growth_mb: 64.0,
+            stuck_secs: 30,
+        }
+    }
+}
+
+impl SoakConfig {
+    fn from_args(args: &[String]) -> Result<Self> {
+        let mut config = Self::default();
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let value = args.next()
+                .with_context(|| format!("Missing value for {}\n{}", flag, USAGE))?;
+            let invalid = || format!("Invalid value for {}: {}", flag, value);
+            match flag.as_str() {
+                "--duration" => config.duration_secs = value.parse().with_context(invalid)?,
+                "--fps" => config.fps = value.parse().with_context(invalid)?,
+                "--workers" => config.workers = value.parse().with_context(invalid)?,
+                "--buffer" => config.buffer_size = value.parse().with_context(invalid)?,
+                "--width" => config.width = value.parse().with_context(invalid)?,
+                "--height" => config.height = value.parse().with_context(invalid)?,
+                "--stage-ms" => config.stage_ms = value.parse().with_context(invalid)?,
+                "--fail-rate" => config.fail_rate = value.parse().with_context(invalid)?,
+                "--panic-rate" => config.panic_rate = value.parse().with_context(invalid)?,
+                "--stall-rate" => config.stall_rate = value.parse().with_context(invalid)?,
+                "--stall-secs" => config.stall_secs = value.parse().with_context(invalid)?,
+                "--warmup" => config.warmup_secs = value.parse().with_context(invalid)?,
+                "--max-p99-ms" => config.max_p99_ms = value.parse().with_context(invalid)?,
+                "--max-rss-growth-mb" => config.max_rss_growth_mb = value.parse().with_context(invalid)?,
+                "--stuck-secs" => config.stuck_secs = value.parse().with_context(invalid)?,
+                other => return Err(anyhow::anyhow!("Unknown option {}\n{}", other, USAGE)),
+            }
+        }
+        if config.workers == 0 || config.buffer_size == 0 {
+            return Err(anyhow::anyhow!("--workers and --buffer must be at least 1"));
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Default)]
+struct Injected {
+    errors: AtomicU64,
+    panics: AtomicU64,
+    stalls: AtomicU64,
+}
+
+struct ChaosStage {
+    name: String,
+    stage_type: StageType,
+    work: Duration,
+    config: SoakConfig,
+    injected: Arc<Injected>,
+}
+
+#[async_trait]
+impl PipelineStage for ChaosStage {
+    async fn process(&self, input: PipelineData) -> Result<PipelineData> {
+        let roll: f64 = rand::random();
+        if roll < self.config.panic_rate {
+            self.injected.panics.fetch_add(1, Ordering::Relaxed);
+            panic!("injected panic in {} on frame {}", self.name, input.frame.id);
+        }
+        if roll < self.config.panic_rate + self.config.fail_rate {
+            self.injected.errors.fetch_add(1, Ordering::Relaxed);
+            return Err(anyhow::anyhow!("injected failure in {}", self.name));
+        }
+        if roll < self.config.panic_rate + self.config.fail_rate + self.config.stall_rate {
+            self.injected.stalls.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(Duration::from_secs(self.config.stall_secs)).await;
+        }
+        tokio::time::sleep(self.work).await;
+        Ok(input)
+    }
+
+    fn stage_type(&self) -> StageType {
+        self.stage_type.clone()
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+// Fixed 1ms buckets so recording latency doesn't itself grow memory over a
+// long run; anything slower than the last bucket lands in it
+struct LatencyHistogram {
+    buckets: Vec<u64>,
+    count: u64,
+    max_ms: f64,
+}
+
+impl LatencyHistogram {
+    fn new() -> Self {
+        Self { buckets: vec![0; 60_000], count: 0, max_ms: 0.0 }
+    }
+
+    fn record(&mut self, ms: f64) {
+        let index = (ms.max(0.0) as usize).min(self.buckets.len() - 1);
+        self.buckets[index] += 1;
+        self.count += 1;
+        self.max_ms = self.max_ms.max(ms);
+    }
+
+    fn percentile(&self, p: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let target = ((self.count as f64) * p).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (ms, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return Some((ms + 1) as f64);
+            }
+        }
+        Some(self.max_ms)
+    }
+}
+
+struct SoakSink {
+    completed: AtomicU64,
+    last_completed: Mutex<Instant>,
+    latency: Mutex<LatencyHistogram>,
+}
+
+#[async_trait]
+impl ResultSink for SoakSink {
+    fn name(&self) -> String {
+        "soak".to_string()
+    }
+
+    async fn write(&self, data: &PipelineData) -> Result<()> {
+        let ms = (chrono::Utc::now() - data.timestamp).num_microseconds().unwrap_or(0) as f64 / 1000.0;
+        self.latency.lock().unwrap().record(ms);
+        *self.last_completed.lock().unwrap() = Instant::now();
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+// Resident set size from procfs; None where that isn't available
+fn rss_mb() -> Option<f64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kb: f64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb / 1024.0)
+}
+
+struct Progress {
+    sent: u64,
+    completed: u64,
+    dropped: u64,
+}
+
+impl Progress {
+    fn in_flight(&self) -> u64 {
+        self.sent.saturating_sub(self.completed + self.dropped)
+    }
+}
+
+async fn progress(pipeline: &Pipeline, sent: &AtomicU64, sink: &SoakSink, injected: &Injected) -> Progress {
+    // Frames end in the sink, in an injected error or panic, or with a
+    // worker the watchdog aborted
+    let restarts = pipeline.get_metrics().await.stage_restarts;
+    Progress {
+        sent: sent.load(Ordering::Relaxed),
+        completed: sink.completed.load(Ordering::Relaxed),
+        dropped: injected.errors.load(Ordering::Relaxed) + injected.panics.load(Ordering::Relaxed) + restarts,
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = SoakConfig::from_args(&args)?;
+    println!("soak: {:?}", config);
+
+    // Injected panics are expected; keep them out of the output
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = info.payload().downcast_ref::<String>().map(|s| s.as_str()).unwrap_or("");
+        if !message.starts_with("injected panic") {
+            default_hook(info);
+        }
+    }));
+
+    let injected = Arc::new(Injected::default());
+    let stages: Vec<Arc<dyn PipelineStage>> = [
+        ("preprocess", StageType::PreProcess),
+        ("detect", StageType::Detection),
+        ("analyze", StageType::Analysis),
+    ]
+    .into_iter()
+    .map(|(name, stage_type)| Arc::new(ChaosStage {
+        name: name.to_string(),
+        stage_type,
+        work: Duration::from_millis(config.stage_ms),
+        config: config.clone(),
+        injected: injected.clone(),
+    }) as Arc<dyn PipelineStage>)
+    .collect();
+
+    let mut pipeline = Pipeline::with_stages(PipelineConfig {
+        stages: Vec::new(),
+        max_parallel_stages: config.workers,
+        buffer_size: config.buffer_size,
+        timeout_ms: 5000,
+        retry_count: 0,
+        retry_backoff_ms: 0,
+        retry_backoff_max_ms: 0,
+        watchdog: WatchdogConfig {
+            enabled: true,
+            check_interval_secs: 1,
+            stall_threshold_secs: config.stall_secs.saturating_sub(1).max(1) as i64,
+        },
+        sinks: Vec::new(),
+        // Failed frames pile up here for the whole run; the byte budget
+        // has to keep RSS flat
+        dead_letters: Some(DeadLetterConfig { max_bytes: 64 * 1024 * 1024, ..DeadLetterConfig::default() }),
+        outbox: None,
+        similarity: None,
+        models: Default::default(),
+    }, stages).await?;
+
+    let sink = Arc::new(SoakSink {
+        completed: AtomicU64::new(0),
+        last_completed: Mutex::new(Instant::now()),
+        latency: Mutex::new(LatencyHistogram::new()),
+    });
+    pipeline.add_sink(sink.clone()).await;
+    let stats = Arc::new(StreamStats::new(StreamStatsConfig {
+        retention_secs: config.duration_secs as i64 + 60,
+        windows: vec![format!("{}s", config.duration_secs + 60)],
+        ..StreamStatsConfig::default()
+    }));
+    pipeline.attach_stream_stats(stats.clone()).await;
+    pipeline.start().await?;
+    let pipeline = Arc::new(pipeline);
+
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(config.duration_secs);
+    let sent = Arc::new(AtomicU64::new(0));
+    // Longest a producer waited on a full input channel
+    let max_blocked_ms = Arc::new(AtomicU64::new(0));
+
+    let producer = {
+        let pipeline = pipeline.clone();
+        let sent = sent.clone();
+        let max_blocked_ms = max_blocked_ms.clone();
+        let mut source = SyntheticSource::new(config.width, config.height, None, config.fps);
+        tokio::spawn(async move {
+            let mut id = 0u64;
+            while Instant::now() < deadline {
+                let data = match source.next_frame().await? {
+                    Some(data) => data,
+                    None => break,
+                };
+                id += 1;
+                let frame = Frame {
+                    id,
+                    timestamp: chrono::Utc::now(),
+                    data: Arc::new(data),
+                    metadata: FrameMetadata {
+                        width: config.width,
+                        height: config.height,
+                        channels: 3,
+                        format: "bgr".to_string(),
+                        source: "soak".to_string(),
+                        privacy_masked: false,
+                        stream_id: Some("soak".to_string()),
+                    },
+                };
+
+                let before = Instant::now();
+                pipeline.process(frame).await?;
+                max_blocked_ms.fetch_max(before.elapsed().as_millis() as u64, Ordering::Relaxed);
+                sent.fetch_add(1, Ordering::Relaxed);
+            }
+            anyhow::Ok(())
+        })
+    };
+
+    let mut failures: Vec<String> = Vec::new();
+    let mut baseline_rss: Option<f64> = None;
+    let mut peak_rss: Option<f64> = None;
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    let drain_deadline = deadline + Duration::from_secs(config.stall_secs + config.stuck_secs);
+
+    loop {
+        interval.tick().await;
+        let now = Instant::now();
+        let state = progress(&pipeline, &sent, &sink, &injected).await;
+
+        if let Some(rss) = rss_mb() {
+            if baseline_rss.is_none() && now >= started + Duration::from_secs(config.warmup_secs) {
+                baseline_rss = Some(rss);
+            }
+            if baseline_rss.is_some() {
+                peak_rss = Some(peak_rss.map_or(rss, |peak: f64| peak.max(rss)));
+            }
+        }
+
+        let idle = sink.last_completed.lock().unwrap().elapsed();
+        if state.in_flight() > 0 && idle > Duration::from_secs(config.stuck_secs) {
+            failures.push(format!(
+                "{} frames in flight but nothing completed for {}s", state.in_flight(), idle.as_secs()
+            ));
+            break;
+        }
+
+        let elapsed = started.elapsed().as_secs();
+        if elapsed % 10 == 0 {
+            println!(
+                "[{:>5}s] sent {} completed {} dropped {} in flight {} rss {}",
+                elapsed, state.sent, state.completed, state.dropped, state.in_flight(),
+                rss_mb().map(|mb| format!("{:.1}MB", mb)).unwrap_or_else(|| "n/a".to_string()),
+            );
+        }
+
+        if producer.is_finished() {
+            if state.in_flight() == 0 {
+                break;
+            }
+            if now > drain_deadline {
+                failures.push(format!("{} frames never completed after the run ended", state.in_flight()));
+                break;
+            }
+        }
+    }
+
+    if producer.is_finished() {
+        producer.await.context("Producer task failed")??;
+    } else {
+        producer.abort();
+    }
+
+    let state = progress(&pipeline, &sent, &sink, &injected).await;
+    let metrics = pipeline.get_metrics().await;
+    let latency = sink.latency.lock().unwrap();
+    let p50 = latency.percentile(0.50);
+    let p99 = latency.percentile(0.99);
+
+    println!();
+    println!("frames sent       {}", state.sent);
+    println!("frames completed  {}", state.completed);
+    println!("injected          {} errors, {} panics, {} stalls",
+        injected.errors.load(Ordering::Relaxed),
+        injected.panics.load(Ordering::Relaxed),
+        injected.stalls.load(Ordering::Relaxed));
+    println!("worker restarts   {}", metrics.stage_restarts);
+    println!("latency           p50 {:?}ms p99 {:?}ms max {:.1}ms", p50, p99, latency.max_ms);
+    println!("max producer wait {}ms", max_blocked_ms.load(Ordering::Relaxed));
+    if let Some(report) = stats.report("soak", &stats.config().windows[0]).await? {
+        println!("stream stats      {:.1} fps, inference p99 {:?}ms, {} drops {:?}",
+            report.fps, report.inference_latency.p99_ms, report.drops.total, report.drops.by_reason);
+    }
+    if let Some(dead_letters) = pipeline.dead_letters().await {
+        let dead = dead_letters.stats().await;
+        println!("dead letters      {} held ({:.1}MB), {} evicted",
+            dead.entries, dead.bytes as f64 / (1024.0 * 1024.0), dead.evicted);
+    }
+
+    if state.completed == 0 {
+        failures.push("No frames completed".to_string());
+    }
+    if let Some(p99) = p99 {
+        if p99 > config.max_p99_ms {
+            failures.push(format!("p99 latency {}ms exceeds {}ms", p99, config.max_p99_ms));
+        }
+    }
+    match (baseline_rss, peak_rss) {
+        (Some(baseline), Some(peak)) => {
+            println!("rss               {:.1}MB after warmup, peak {:.1}MB", baseline, peak);
+            if peak - baseline > config.max_rss_growth_mb {
+                failures.push(format!(
+                    "RSS grew {:.1}MB after warmup, over the {}MB budget", peak - baseline, config.max_rss_growth_mb
+                ));
+            }
+        }
+        _ => println!("rss               not measured (no procfs, or the run was shorter than warmup)"),
+    }
+
+    if failures.is_empty() {
+        println!("PASS");
+        return Ok(());
+    }
+    for failure in &failures {
+        println!("FAIL: {}", failure);
+    }
+    std::process::exit(1);
+}
@@ -0,0 +1,225 @@
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+
+use crate::core::llm::{LLMTrait, types::Message};
+use crate::core::llm::structured::parse_reply;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredConversation {
+    pub session_id: String,
+    pub messages: Vec<Message>,
+    pub ended_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait ConversationStore: Send + Sync {
+    async fn conversations_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<StoredConversation>>;
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Sentiment {
+    Positive,
+    Neutral,
+    Negative,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationLabels {
+    pub session_id: String,
+    pub topic: String,
+    pub sentiment: Sentiment,
+    pub resolved: bool,
+    pub ended_at: DateTime<Utc>,
+    pub classified_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationAnalyticsConfig {
+    pub topics: Vec<String>,
+    pub schedule_interval_secs: Option<u64>,
+    // Only this much of each transcript is sent to the classifier
+    pub max_transcript_chars: usize,
+}
+
+impl Default for ConversationAnalyticsConfig {
+    fn default() -> Self {
+        Self {
+            topics: vec![
+                "cameras".to_string(),
+                "detections".to_string(),
+                "alerts".to_string(),
+                "configuration".to_string(),
+                "account".to_string(),
+                "other".to_string(),
+            ],
+            schedule_interval_secs: Some(3600),
+            max_transcript_chars: 8000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationAggregates {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub total: usize,
+    pub by_topic: HashMap<String, usize>,
+    pub by_sentiment: HashMap<Sentiment, usize>,
+    pub resolution_rate: Option<f32>,
+    pub unresolved_by_topic: HashMap<String, usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClassifierOutput {
+    topic: String,
+    sentiment: Sentiment,
+    resolved: bool,
+}
+
+pub struct ConversationAnalytics {
+    config: ConversationAnalyticsConfig,
+    llm: Arc<dyn LLMTrait>,
+    store: Arc<dyn ConversationStore>,
+    labels: Arc<RwLock<HashMap<String, ConversationLabels>>>,
+}
+
+impl ConversationAnalytics {
+    pub fn new(config: ConversationAnalyticsConfig, llm: Arc<dyn LLMTrait>, store: Arc<dyn ConversationStore>) -> Self {
+        Self {
+            config,
+            llm,
+            store,
+            labels: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    // Classifies conversations in the range that haven't been labelled yet
+    pub async fn run(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<usize> {
+        let conversations = self.store.conversations_between(start, end).await
+            .context("Failed to load conversations")?;
+        let mut classified = 0;
+
+        for conversation in conversations {
+            if self.labels.read().await.contains_key(&conversation.session_id) {
+                continue;
+            }
+
+            match self.classify(&conversation).await {
+                Ok(labels) => {
+                    self.labels.write().await.insert(conversation.session_id.clone(), labels);
+                    classified += 1;
+                }
+                Err(e) => log::warn!("Failed to classify conversation {}: {}", conversation.session_id, e),
+            }
+        }
+
+        Ok(classified)
+    }
+
+    async fn classify(&self, conversation: &StoredConversation) -> Result<ConversationLabels> {
+        let mut transcript = String::new();
+        for message in conversation.messages.iter().filter(|m| m.role != "system") {
+            transcript.push_str(&format!("{}: {}\n", message.role, message.content));
+        }
+        if transcript.len() > self.config.max_transcript_chars {
+            // Keep the end; that's where resolution shows up
+            let mut cut = transcript.len() - self.config.max_transcript_chars;
+            while !transcript.is_char_boundary(cut) {
+                cut += 1;
+            }
+            transcript = transcript[cut..].to_string();
+        }
+
+        let instructions = format!(
+            "Classify this support conversation. Reply with JSON only: \
+             {{\"topic\": one of [{}], \"sentiment\": \"positive\"|\"neutral\"|\"negative\", \
+             \"resolved\": true|false}}. Sentiment is the user's, resolved means the user's \
+             issue appears to have been addressed.",
+            self.config.topics.join(", ")
+        );
+
+        let response = self.llm.complete(vec![
+            Message::new("system", &instructions),
+            Message::new("user", &transcript),
+        ]).await.context("Classifier request failed")?;
+
+        let output: ClassifierOutput = parse_reply(&response.content, "classifier output")?;
+
+        let topic = if self.config.topics.contains(&output.topic) {
+            output.topic
+        } else {
+            "other".to_string()
+        };
+
+        Ok(ConversationLabels {
+            session_id: conversation.session_id.clone(),
+            topic,
+            sentiment: output.sentiment,
+            resolved: output.resolved,
+            ended_at: conversation.ended_at,
+            classified_at: Utc::now(),
+        })
+    }
+
+    pub async fn aggregates(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> ConversationAggregates {
+        let labels = self.labels.read().await;
+        let in_range: Vec<_> = labels.values()
+            .filter(|l| l.ended_at >= start && l.ended_at < end)
+            .collect();
+
+        let mut aggregates = ConversationAggregates {
+            start,
+            end,
+            total: in_range.len(),
+            by_topic: HashMap::new(),
+            by_sentiment: HashMap::new(),
+            resolution_rate: None,
+            unresolved_by_topic: HashMap::new(),
+        };
+
+        for label in &in_range {
+            *aggregates.by_topic.entry(label.topic.clone()).or_insert(0) += 1;
+            *aggregates.by_sentiment.entry(label.sentiment).or_insert(0) += 1;
+            if !label.resolved {
+                *aggregates.unresolved_by_topic.entry(label.topic.clone()).or_insert(0) += 1;
+            }
+        }
+
+        if !in_range.is_empty() {
+            let resolved = in_range.iter().filter(|l| l.resolved).count();
+            aggregates.resolution_rate = Some(resolved as f32 / in_range.len() as f32);
+        }
+
+        aggregates
+    }
+
+    pub fn start_schedule(self: &Arc<Self>) {
+        let Some(interval_secs) = self.config.schedule_interval_secs else {
+            return;
+        };
+        let analytics = self.clone();
+
+        tokio::spawn(async move {
+            let period = chrono::Duration::seconds(interval_secs as i64);
+            let mut interval = tokio::time::interval(
+                tokio::time::Duration::from_secs(interval_secs)
+            );
+
+            loop {
+                interval.tick().await;
+                let end = Utc::now();
+                // Overlap the previous run; already-labelled sessions are skipped
+                match analytics.run(end - period * 2, end).await {
+                    Ok(count) => log::info!("Classified {} conversations", count),
+                    Err(e) => log::error!("Conversation analytics run failed: {}", e),
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,241 @@
+#![cfg(feature = "vision")]
+
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use crate::core::agent::tools::Tool;
+use crate::core::llm::{LLMTrait, types::Message};
+use crate::core::llm::structured::extract_json;
+use crate::core::rules::{Rule, RuleStore};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthoringConfig {
+    // How long a drafted rule waits for confirmation before it's discarded
+    pub proposal_ttl_secs: i64,
+    pub max_pending: usize,
+}
+
+impl Default for AuthoringConfig {
+    fn default() -> Self {
+        Self {
+            proposal_ttl_secs: 600,
+            max_pending: 50,
+        }
+    }
+}
+
+// A validated rule that has not been applied yet. Nothing changes until
+// someone confirms it by id.
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleProposal {
+    pub id: String,
+    pub request: String,
+    pub rule: Rule,
+    pub summary: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+pub struct RuleAuthor {
+    config: AuthoringConfig,
+    llm: Arc<dyn LLMTrait>,
+    rules: Arc<RuleStore>,
+    proposals: RwLock<HashMap<String, RuleProposal>>,
+}
+
+impl RuleAuthor {
+    pub fn new(config: AuthoringConfig, llm: Arc<dyn LLMTrait>, rules: Arc<RuleStore>) -> Self {
+        Self {
+            config,
+            llm,
+            rules,
+            proposals: RwLock::new(HashMap::new()),
+        }
+    }
+
+    // The model only sees streams and zones that exist, and whatever it
+    // produces still goes through RuleStore::validate
+    async fn describe_streams(&self) -> String {
+        let mut lines = Vec::new();
+        for stream in self.rules.registry().list().await {
+            let zones = match self.rules.registry().resolve(&stream.id).await {
+                Ok((_, profile)) => profile.zones.iter().map(|z| z.name.clone()).collect::<Vec<_>>(),
+                Err(_) => Vec::new(),
+            };
+            lines.push(format!(
+                "- stream \"{}\": zones [{}]",
+                stream.id,
+                zones.iter().map(|z| format!("\"{}\"", z)).collect::<Vec<_>>().join(", "),
+            ));
+        }
+        if lines.is_empty() {
+            "(no streams configured)".to_string()
+        } else {
+            lines.join("\n")
+        }
+    }
+
+    pub async fn propose(&self, request: &str) -> Result<RuleProposal> {
+        let streams = self.describe_streams().await;
+        let system = format!(
+            "Translate the user's monitoring request into one alert rule. Reply with a single \
+             JSON object and nothing else:\n\
+             {{\"name\": string, \"stream_id\": string, \"zone\": string|null, \
+             \"classes\": [string], \"schedule\": {{\"start\": \"HH:MM:SS\", \"end\": \"HH:MM:SS\", \
+             \"days\": [\"Mon\"...], \"utc_offset_minutes\": int}}|null, \"min_confidence\": number, \
+             \"min_count\": int, \"severity\": \"info\"|\"warning\"|\"critical\"}}\n\
+             Use detector class names such as person, car, truck, bicycle. \"Anyone\" means person. \
+             A schedule ending before it starts runs past midnight. Only use these streams and zones:\n{}",
+            streams,
+        );
+
+        let response = self.llm.complete(vec![
+            Message::new("system", &system),
+            Message::new("user", request),
+        ]).await.context("Rule drafting request failed")?;
+
+        let json = extract_json(&response.content)
+            .ok_or_else(|| anyhow::anyhow!("Model did not return a rule"))?;
+        let rule: Rule = serde_json::from_str(json).context("Failed to parse drafted rule")?;
+        self.rules.validate(&rule).await.context("Drafted rule is invalid")?;
+
+        let now = Utc::now();
+        let proposal = RuleProposal {
+            id: Uuid::new_v4().to_string(),
+            request: request.to_string(),
+            summary: rule.describe(),
+            rule,
+            expires_at: now + Duration::seconds(self.config.proposal_ttl_secs),
+        };
+
+        let mut proposals = self.proposals.write().await;
+        proposals.retain(|_, p| p.expires_at > now);
+        if proposals.len() >= self.config.max_pending {
+            return Err(anyhow::anyhow!("Too many unconfirmed rule proposals"));
+        }
+        proposals.insert(proposal.id.clone(), proposal.clone());
+        Ok(proposal)
+    }
+
+    pub async fn pending(&self) -> Vec<RuleProposal> {
+        let now = Utc::now();
+        let mut pending: Vec<_> = self.proposals.read().await.values()
+            .filter(|p| p.expires_at > now)
+            .cloned()
+            .collect();
+        pending.sort_by(|a, b| a.expires_at.cmp(&b.expires_at));
+        pending
+    }
+
+    pub async fn confirm(&self, proposal_id: &str) -> Result<Rule> {
+        let proposal = self.proposals.write().await.remove(proposal_id)
+            .ok_or_else(|| anyhow::anyhow!("Rule proposal not found: {}", proposal_id))?;
+        if proposal.expires_at <= Utc::now() {
+            return Err(anyhow::anyhow!("Rule proposal {} has expired", proposal_id));
+        }
+        // Validated again: streams and zones may have changed since drafting
+        self.rules.create(proposal.rule).await
+    }
+
+    pub async fn discard(&self, proposal_id: &str) -> bool {
+        self.proposals.write().await.remove(proposal_id).is_some()
+    }
+}
+
+pub struct DraftRuleTool {
+    author: Arc<RuleAuthor>,
+}
+
+impl DraftRuleTool {
+    pub fn new(author: Arc<RuleAuthor>) -> Self {
+        Self { author }
+    }
+}
+
+#[async_trait]
+impl Tool for DraftRuleTool {
+    fn name(&self) -> String {
+        "draft_rule".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Turn a monitoring request such as \"alert me if anyone enters the loading dock \
+         after 10pm\" into an alert rule. The rule is not applied: show the returned summary \
+         to the user, who confirms or rejects the proposal themselves.".to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "request": { "type": "string" }
+            },
+            "required": ["request"]
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<Value> {
+        let request = args.get("request")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow::anyhow!("draft_rule requires a request"))?;
+
+        let proposal = self.author.propose(request).await?;
+        Ok(json!({
+            "proposal_id": proposal.id,
+            "summary": proposal.summary,
+            "rule": proposal.rule,
+            "expires_at": proposal.expires_at,
+        }))
+    }
+}
+
+// Lets the agent withdraw a draft the user turned down. There is
+// deliberately no tool that applies one: confirmation is the user's own
+// call to POST /v1/rule-proposals/{id}/confirm, which the model can't
+// make on their behalf.
+pub struct DiscardRuleTool {
+    author: Arc<RuleAuthor>,
+}
+
+impl DiscardRuleTool {
+    pub fn new(author: Arc<RuleAuthor>) -> Self {
+        Self { author }
+    }
+}
+
+#[async_trait]
+impl Tool for DiscardRuleTool {
+    fn name(&self) -> String {
+        "discard_rule".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Discard a rule drafted by draft_rule that the user does not want.".to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "proposal_id": { "type": "string" }
+            },
+            "required": ["proposal_id"]
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<Value> {
+        #[derive(Deserialize)]
+        struct Args {
+            proposal_id: String,
+        }
+        let args: Args = serde_json::from_value(args).context("Invalid discard_rule arguments")?;
+        let discarded = self.author.discard(&args.proposal_id).await;
+        Ok(json!({ "discarded": discarded }))
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::VecDeque;
+use tokio::sync::RwLock;
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use chrono::{DateTime, Utc};
+
+use crate::core::llm::latency::percentile;
+
+// Latency percentiles come from each arm's most recent sessions, so a
+// long-running canary doesn't grow without bound
+const MAX_LATENCY_SAMPLES: usize = 10_000;
+use crate::core::prompts::PromptRef;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AgentVariant {
+    pub model: String,
+    pub prompt: PromptRef,
+    // USD per 1k tokens, for the cost comparison
+    #[serde(default)]
+    pub prompt_price: f64,
+    #[serde(default)]
+    pub completion_price: f64,
+}
+
+impl AgentVariant {
+    pub fn cost(&self, prompt_tokens: u32, completion_tokens: u32) -> f64 {
+        (prompt_tokens as f64 * self.prompt_price + completion_tokens as f64 * self.completion_price) / 1000.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Arm {
+    Baseline,
+    Candidate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CanaryGuardrails {
+    // Sessions each arm needs before the comparison means anything
+    pub min_sessions: u64,
+    // Absolute drop in mean judge score (0-1) tolerated
+    pub max_quality_drop: f32,
+    // Relative increases tolerated, 0.2 = 20% worse
+    pub max_latency_increase: f64,
+    pub max_cost_increase: f64,
+    pub max_error_rate_increase: f64,
+    pub auto_rollback: bool,
+}
+
+impl Default for CanaryGuardrails {
+    fn default() -> Self {
+        Self {
+            min_sessions: 50,
+            max_quality_drop: 0.05,
+            max_latency_increase: 0.2,
+            max_cost_increase: 0.25,
+            max_error_rate_increase: 0.02,
+            auto_rollback: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryConfig {
+    pub baseline: AgentVariant,
+    pub candidate: AgentVariant,
+    pub percent: u8,
+    #[serde(default)]
+    pub guardrails: CanaryGuardrails,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CanaryStatus {
+    Running,
+    Promoted,
+    RolledBack,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ArmStats {
+    sessions: u64,
+    errors: u64,
+    latencies_ms: VecDeque<f64>,
+    quality_sum: f64,
+    quality_samples: u64,
+    cost: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArmReport {
+    pub sessions: u64,
+    pub error_rate: f64,
+    pub p50_latency_ms: Option<f64>,
+    pub p95_latency_ms: Option<f64>,
+    pub mean_quality: Option<f32>,
+    pub cost_per_session: Option<f64>,
+}
+
+impl ArmStats {
+    fn report(&self) -> ArmReport {
+        let mut sorted: Vec<f64> = self.latencies_ms.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        ArmReport {
+            sessions: self.sessions,
+            error_rate: if self.sessions == 0 { 0.0 } else { self.errors as f64 / self.sessions as f64 },
+            p50_latency_ms: percentile(&sorted, 0.5),
+            p95_latency_ms: percentile(&sorted, 0.95),
+            mean_quality: (self.quality_samples > 0).then(|| (self.quality_sum / self.quality_samples as f64) as f32),
+            cost_per_session: (self.sessions > 0).then(|| self.cost / self.sessions as f64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "verdict", rename_all = "snake_case")]
+pub enum CanaryVerdict {
+    InsufficientData,
+    Healthy,
+    Regressed { reasons: Vec<String> },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CanaryReport {
+    pub status: CanaryStatus,
+    pub percent: u8,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub end_reason: Option<String>,
+    pub baseline: ArmReport,
+    pub candidate: ArmReport,
+    pub verdict: CanaryVerdict,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SessionObservation {
+    pub latency_ms: f64,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    // Judge score for the session, when it was sampled
+    pub quality: Option<f32>,
+    pub error: bool,
+}
+
+struct CanaryState {
+    config: CanaryConfig,
+    status: CanaryStatus,
+    started_at: DateTime<Utc>,
+    ended_at: Option<DateTime<Utc>>,
+    end_reason: Option<String>,
+    baseline: ArmStats,
+    candidate: ArmStats,
+}
+
+// Routes sessions between the current agent configuration and a candidate.
+// Without a running canary every session gets the stable variant.
+pub struct CanaryRouter {
+    stable: RwLock<AgentVariant>,
+    canary: RwLock<Option<CanaryState>>,
+}
+
+// Sticky: the same session lands in the same arm for the whole canary, so a
+// conversation never switches model halfway through
+pub fn bucket(session_id: &str) -> u8 {
+    let digest = Sha256::digest(session_id.as_bytes());
+    (u16::from_be_bytes([digest[0], digest[1]]) % 100) as u8
+}
+
+fn compare(baseline: &ArmReport, candidate: &ArmReport, guardrails: &CanaryGuardrails) -> CanaryVerdict {
+    if baseline.sessions < guardrails.min_sessions || candidate.sessions < guardrails.min_sessions {
+        return CanaryVerdict::InsufficientData;
+    }
+
+    let mut reasons = Vec::new();
+    if let (Some(base), Some(cand)) = (baseline.mean_quality, candidate.mean_quality) {
+        if base - cand > guardrails.max_quality_drop {
+            reasons.push(format!("quality dropped from {:.3} to {:.3}", base, cand));
+        }
+    }
+    if let (Some(base), Some(cand)) = (baseline.p95_latency_ms, candidate.p95_latency_ms) {
+        if base > 0.0 && cand > base * (1.0 + guardrails.max_latency_increase) {
+            reasons.push(format!("p95 latency rose from {:.0}ms to {:.0}ms", base, cand));
+        }
+    }
+    if let (Some(base), Some(cand)) = (baseline.cost_per_session, candidate.cost_per_session) {
+        if base > 0.0 && cand > base * (1.0 + guardrails.max_cost_increase) {
+            reasons.push(format!("cost per session rose from ${:.4} to ${:.4}", base, cand));
+        }
+    }
+    if candidate.error_rate - baseline.error_rate > guardrails.max_error_rate_increase {
+        reasons.push(format!(
+            "error rate rose from {:.1}% to {:.1}%",
+            baseline.error_rate * 100.0, candidate.error_rate * 100.0,
+        ));
+    }
+
+    if reasons.is_empty() {
+        CanaryVerdict::Healthy
+    } else {
+        CanaryVerdict::Regressed { reasons }
+    }
+}
+
+impl CanaryRouter {
+    pub fn new(stable: AgentVariant) -> Self {
+        Self {
+            stable: RwLock::new(stable),
+            canary: RwLock::new(None),
+        }
+    }
+
+    pub async fn stable(&self) -> AgentVariant {
+        self.stable.read().await.clone()
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.canary.read().await.as_ref().is_some_and(|c| c.status == CanaryStatus::Running)
+    }
+
+    // Replaces the stable variant outright; refused mid-canary, where the
+    // baseline is what the candidate is being compared against
+    pub async fn set_stable(&self, variant: AgentVariant) -> Result<()> {
+        let canary = self.canary.read().await;
+        if canary.as_ref().is_some_and(|c| c.status == CanaryStatus::Running) {
+            return Err(anyhow::anyhow!("Cannot replace the agent configuration while a canary is running"));
+        }
+        *self.stable.write().await = variant;
+        Ok(())
+    }
+
+    pub async fn start(&self, config: CanaryConfig) -> Result<()> {
+        if config.percent > 100 {
+            return Err(anyhow::anyhow!("Canary percent must be between 0 and 100"));
+        }
+        if config.baseline == config.candidate {
+            return Err(anyhow::anyhow!("Candidate is identical to the baseline"));
+        }
+
+        let mut canary = self.canary.write().await;
+        if canary.as_ref().is_some_and(|c| c.status == CanaryStatus::Running) {
+            return Err(anyhow::anyhow!("A canary is already running"));
+        }
+        *self.stable.write().await = config.baseline.clone();
+        *canary = Some(CanaryState {
+            config,
+            status: CanaryStatus::Running,
+            started_at: Utc::now(),
+            ended_at: None,
+            end_reason: None,
+            baseline: ArmStats::default(),
+            candidate: ArmStats::default(),
+        });
+        Ok(())
+    }
+
+    pub async fn set_percent(&self, percent: u8) -> Result<()> {
+        if percent > 100 {
+            return Err(anyhow::anyhow!("Canary percent must be between 0 and 100"));
+        }
+        let mut canary = self.canary.write().await;
+        match canary.as_mut().filter(|c| c.status == CanaryStatus::Running) {
+            Some(state) => {
+                state.config.percent = percent;
+                Ok(())
+            }
+            None => Err(anyhow::anyhow!("No canary is running")),
+        }
+    }
+
+    pub async fn route(&self, session_id: &str) -> (Arm, AgentVariant) {
+        let canary = self.canary.read().await;
+        if let Some(state) = canary.as_ref().filter(|c| c.status == CanaryStatus::Running) {
+            if bucket(session_id) < state.config.percent {
+                return (Arm::Candidate, state.config.candidate.clone());
+            }
+            return (Arm::Baseline, state.config.baseline.clone());
+        }
+        drop(canary);
+        (Arm::Baseline, self.stable().await)
+    }
+
+    // Returns the verdict when this observation tripped an automatic rollback
+    pub async fn record(&self, arm: Arm, observation: SessionObservation) -> Option<CanaryVerdict> {
+        let mut canary = self.canary.write().await;
+        let state = canary.as_mut().filter(|c| c.status == CanaryStatus::Running)?;
+
+        let variant = match arm {
+            Arm::Baseline => &state.config.baseline,
+            Arm::Candidate => &state.config.candidate,
+        };
+        let cost = variant.cost(observation.prompt_tokens, observation.completion_tokens);
+        let stats = match arm {
+            Arm::Baseline => &mut state.baseline,
+            Arm::Candidate => &mut state.candidate,
+        };
+        stats.sessions += 1;
+        stats.cost += cost;
+        if observation.error {
+            stats.errors += 1;
+        } else {
+            if stats.latencies_ms.len() == MAX_LATENCY_SAMPLES {
+                stats.latencies_ms.pop_front();
+            }
+            stats.latencies_ms.push_back(observation.latency_ms);
+        }
+        if let Some(quality) = observation.quality {
+            stats.quality_sum += quality as f64;
+            stats.quality_samples += 1;
+        }
+
+        if !state.config.guardrails.auto_rollback {
+            return None;
+        }
+        let verdict = compare(&state.baseline.report(), &state.candidate.report(), &state.config.guardrails);
+        if let CanaryVerdict::Regressed { reasons } = &verdict {
+            let reason = format!("automatic rollback: {}", reasons.join("; "));
+            log::warn!("Canary rolled back: {}", reason);
+            state.status = CanaryStatus::RolledBack;
+            state.ended_at = Some(Utc::now());
+            state.end_reason = Some(reason);
+            return Some(verdict);
+        }
+        None
+    }
+
+    // Instant: the next route() call already goes to the baseline
+    pub async fn rollback(&self, reason: &str) -> Result<()> {
+        let mut canary = self.canary.write().await;
+        let state = canary.as_mut()
+            .filter(|c| c.status == CanaryStatus::Running)
+            .ok_or_else(|| anyhow::anyhow!("No canary is running"))?;
+        state.status = CanaryStatus::RolledBack;
+        state.ended_at = Some(Utc::now());
+        state.end_reason = Some(reason.to_string());
+        log::warn!("Canary rolled back: {}", reason);
+        Ok(())
+    }
+
+    pub async fn promote(&self) -> Result<AgentVariant> {
+        let mut canary = self.canary.write().await;
+        let state = canary.as_mut()
+            .filter(|c| c.status == CanaryStatus::Running)
+            .ok_or_else(|| anyhow::anyhow!("No canary is running"))?;
+        state.status = CanaryStatus::Promoted;
+        state.ended_at = Some(Utc::now());
+        state.end_reason = Some("promoted".to_string());
+
+        let candidate = state.config.candidate.clone();
+        *self.stable.write().await = candidate.clone();
+        Ok(candidate)
+    }
+
+    pub async fn report(&self) -> Option<CanaryReport> {
+        let canary = self.canary.read().await;
+        let state = canary.as_ref()?;
+        let baseline = state.baseline.report();
+        let candidate = state.candidate.report();
+        Some(CanaryReport {
+            status: state.status,
+            percent: state.config.percent,
+            started_at: state.started_at,
+            ended_at: state.ended_at,
+            end_reason: state.end_reason.clone(),
+            verdict: compare(&baseline, &candidate, &state.config.guardrails),
+            baseline,
+            candidate,
+        })
+    }
+}
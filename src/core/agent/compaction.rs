@@ -0,0 +1,195 @@
+use std::sync::Arc;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+
+use crate::core::agent::sqlite_memory::SqliteMemory;
+use crate::core::llm::{LLMTrait, types::Message};
+use crate::core::llm::tokenizer::Tokenizer;
+
+// Keeps a long session's stored history bounded. Once the history passes
+// max_history_tokens, the oldest compact_messages turns are summarised by
+// the model and replaced with one system message holding the summary. The
+// next pass folds the previous summary in with the turns after it, so facts
+// from the start of the session carry forward instead of falling off.
+//
+// However many passes run, the result is one contiguous run of the original
+// history, [start, end), replaced by one summary at `start`. The report
+// carries that span so a store can rewrite exactly those rows.
+//
+// Unlike the context guard, which trims only what is sent, this rewrites
+// the memory itself.
+
+pub const SUMMARY_PREFIX: &str = "Summary of the earlier conversation: ";
+
+// Nested under `MemoryConfig.compaction`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompactionConfig {
+    pub enabled: bool,
+    pub max_history_tokens: usize,
+    // Turns summarised per pass
+    pub compact_messages: usize,
+    // Never summarised, so the conversation stays coherent
+    pub keep_recent: usize,
+    pub summary_max_tokens: usize,
+    pub summary_prompt: String,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_history_tokens: 6000,
+            compact_messages: 20,
+            keep_recent: 10,
+            summary_max_tokens: 400,
+            summary_prompt: String::from(
+                "Summarize this conversation between a user and Lilith, a video analytics assistant, \
+                 so it can replace the original messages. Keep every name, number, camera, zone, \
+                 time, preference, decision and open question; drop pleasantries. If it starts with \
+                 an earlier summary, merge it in rather than summarizing it again. Reply with the \
+                 summary only."
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompactionReport {
+    // Span of the original history that `summary` replaced
+    pub start: usize,
+    pub end: usize,
+    pub summary: Message,
+    // Model calls made; each pass folds the previous summary back in
+    pub passes: usize,
+    pub tokens_before: usize,
+    pub tokens_after: usize,
+}
+
+pub fn is_summary(message: &Message) -> bool {
+    message.role == "system" && message.content.starts_with(SUMMARY_PREFIX)
+}
+
+pub struct Compactor {
+    config: CompactionConfig,
+    llm: Arc<dyn LLMTrait>,
+    tokenizer: Tokenizer,
+}
+
+impl Compactor {
+    pub fn new(config: CompactionConfig, llm: Arc<dyn LLMTrait>) -> Self {
+        let tokenizer = Tokenizer::for_model(llm.get_model());
+        Self { config, llm, tokenizer }
+    }
+
+    pub fn needs_compaction(&self, history: &[Message]) -> bool {
+        self.config.enabled && self.tokenizer.count_tokens(history) > self.config.max_history_tokens
+    }
+
+    // The oldest run of messages a pass would replace: a leading summary
+    // if there is one, then up to compact_messages turns. Other system
+    // messages are instructions and are left alone.
+    fn span(&self, history: &[Message]) -> Option<(usize, usize)> {
+        let protected = history.len().saturating_sub(self.config.keep_recent);
+        let start = history.iter().position(|m| m.role != "system" || is_summary(m))?;
+        if start >= protected {
+            return None;
+        }
+
+        let mut end = start;
+        let mut turns = 0;
+        while end < protected && turns < self.config.compact_messages.max(1) {
+            if history[end].role == "system" && !is_summary(&history[end]) {
+                break;
+            }
+            if !is_summary(&history[end]) {
+                turns += 1;
+            }
+            end += 1;
+        }
+        // A lone summary has nothing new to fold in
+        (turns > 0).then_some((start, end))
+    }
+
+    // Rewrites `history` in place. On error it is left as it was.
+    pub async fn compact(&self, history: &mut Vec<Message>) -> Result<Option<CompactionReport>> {
+        if !self.needs_compaction(history) {
+            return Ok(None);
+        }
+        let tokens_before = self.tokenizer.count_tokens(history);
+        let mut replaced: Option<(usize, usize)> = None;
+        let mut passes = 0;
+
+        let mut working = history.clone();
+        while self.tokenizer.count_tokens(&working) > self.config.max_history_tokens {
+            let Some((start, end)) = self.span(&working) else {
+                break;
+            };
+            // After the first pass the span starts at the summary, which
+            // stands for original[start..previous end]
+            let original_end = match replaced {
+                None => end,
+                Some((first, previous_end)) if first == start => previous_end + (end - start - 1),
+                Some(_) => break,
+            };
+            let summary = self.summarize(&working[start..end]).await?;
+            passes += 1;
+            replaced = Some((start, original_end));
+            working.splice(start..end, [Message::new("system", &format!("{}{}", SUMMARY_PREFIX, summary))]);
+        }
+
+        let Some((start, end)) = replaced else {
+            log::warn!(
+                "History is over {} tokens but only recent or system messages are left to compact",
+                self.config.max_history_tokens
+            );
+            return Ok(None);
+        };
+
+        let report = CompactionReport {
+            start,
+            end,
+            summary: working[start].clone(),
+            passes,
+            tokens_before,
+            tokens_after: self.tokenizer.count_tokens(&working),
+        };
+        *history = working;
+        log::info!(
+            "Compacted {} messages in {} pass(es): {} -> {} tokens",
+            report.end - report.start, report.passes, report.tokens_before, report.tokens_after
+        );
+        Ok(Some(report))
+    }
+
+    // Compacts a stored history. Only the replaced rows are rewritten, so a
+    // stored system prompt and anything appended meanwhile stay as they are.
+    pub async fn compact_memory(&self, memory: &SqliteMemory) -> Result<Option<CompactionReport>> {
+        let (ids, mut history): (Vec<i64>, Vec<Message>) = memory.entries().await?.into_iter().unzip();
+        let Some(report) = self.compact(&mut history).await? else {
+            return Ok(None);
+        };
+        memory.replace_rows(&ids[report.start..report.end], report.summary.clone()).await?;
+        Ok(Some(report))
+    }
+
+    async fn summarize(&self, messages: &[Message]) -> Result<String> {
+        let transcript: String = messages.iter()
+            .map(|m| match m.content.strip_prefix(SUMMARY_PREFIX) {
+                Some(summary) if m.role == "system" => format!("Earlier summary: {}\n", summary),
+                _ => format!("{}: {}\n", m.role, m.content),
+            })
+            .collect();
+
+        let response = self.llm.complete(vec![
+            Message::new("system", &self.config.summary_prompt),
+            Message::new("user", &transcript),
+        ]).await.context("Summary request failed")?;
+
+        let summary = response.content.trim();
+        if summary.is_empty() {
+            return Err(anyhow::anyhow!("Model returned an empty summary"));
+        }
+        Ok(self.tokenizer.truncate(summary, self.config.summary_max_tokens))
+    }
+}
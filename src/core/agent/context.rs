@@ -0,0 +1,262 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+use crate::core::alerts::AlertManager;
+use crate::core::llm::types::Message;
+use crate::core::state::StateManager;
+#[cfg(feature = "vision")]
+use crate::vision::detector::Detection;
+
+// Supplies dynamic context that is injected into the prompt before each
+// completion. Returning None means there is nothing worth mentioning.
+#[async_trait]
+pub trait ContextProvider: Send + Sync {
+    fn name(&self) -> String;
+    async fn provide(&self) -> Result<Option<String>>;
+}
+
+pub struct PipelineMetricsProvider {
+    state: Arc<StateManager>,
+}
+
+impl PipelineMetricsProvider {
+    pub fn new(state: Arc<StateManager>) -> Self {
+        Self { state }
+    }
+}
+
+#[async_trait]
+impl ContextProvider for PipelineMetricsProvider {
+    fn name(&self) -> String {
+        "pipeline".to_string()
+    }
+
+    async fn provide(&self) -> Result<Option<String>> {
+        let state = self.state.get_current_state().await?;
+        let engine = &state.engine_state;
+        let pipeline = &state.pipeline_state;
+        let resources = &state.resource_state;
+
+        Ok(Some(format!(
+            "Engine {:?}, {:.1} fps, {} frames processed. Queue size {}, latency {:.1}ms, \
+             active stages: {}. GPU {:.0}%, CPU {:.0}%, memory {:.0}%. {} errors recorded.",
+            engine.status, engine.fps, engine.frames_processed,
+            pipeline.queue_size, pipeline.processing_latency,
+            pipeline.active_stages.join(", "),
+            resources.gpu_usage, resources.cpu_usage, resources.memory_usage,
+            state.error_state.error_count,
+        )))
+    }
+}
+
+pub struct ActiveAlertsProvider {
+    alerts: Arc<AlertManager>,
+    max_alerts: usize,
+}
+
+impl ActiveAlertsProvider {
+    pub fn new(alerts: Arc<AlertManager>, max_alerts: usize) -> Self {
+        Self { alerts, max_alerts }
+    }
+}
+
+#[async_trait]
+impl ContextProvider for ActiveAlertsProvider {
+    fn name(&self) -> String {
+        "alerts".to_string()
+    }
+
+    async fn provide(&self) -> Result<Option<String>> {
+        let alerts = self.alerts.active_alerts().await;
+        if alerts.is_empty() {
+            return Ok(Some("No active alerts.".to_string()));
+        }
+
+        let mut lines: Vec<String> = alerts.iter()
+            .take(self.max_alerts)
+            .map(|a| format!(
+                "- [{}] {}: {} (since {})",
+                a.severity.to_string(), a.name, a.message, a.raised_at.to_rfc3339()
+            ))
+            .collect();
+        if alerts.len() > self.max_alerts {
+            lines.push(format!("- ...and {} more", alerts.len() - self.max_alerts));
+        }
+        Ok(Some(lines.join("\n")))
+    }
+}
+
+// Pipeline consumers record detections here; the provider summarizes the
+// recent window per stream and class
+pub struct RecentDetectionsProvider {
+    window: chrono::Duration,
+    detections: RwLock<VecDeque<(String, String, DateTime<Utc>)>>,
+}
+
+impl RecentDetectionsProvider {
+    pub fn new(window: chrono::Duration) -> Self {
+        Self {
+            window,
+            detections: RwLock::new(VecDeque::new()),
+        }
+    }
+
+    #[cfg(feature = "vision")]
+    pub async fn record(&self, stream_id: &str, detections: &[Detection]) {
+        let mut recent = self.detections.write().await;
+        for detection in detections {
+            recent.push_back((stream_id.to_string(), detection.class_name.clone(), detection.timestamp));
+        }
+
+        let cutoff = Utc::now() - self.window;
+        while recent.front().is_some_and(|(_, _, at)| *at < cutoff) {
+            recent.pop_front();
+        }
+    }
+}
+
+#[async_trait]
+impl ContextProvider for RecentDetectionsProvider {
+    fn name(&self) -> String {
+        "detections".to_string()
+    }
+
+    async fn provide(&self) -> Result<Option<String>> {
+        let cutoff = Utc::now() - self.window;
+        let mut counts: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+        let recent = self.detections.read().await;
+        for (stream, class, at) in recent.iter() {
+            if *at >= cutoff {
+                *counts.entry((stream.as_str(), class.as_str())).or_insert(0) += 1;
+            }
+        }
+
+        if counts.is_empty() {
+            return Ok(None);
+        }
+
+        let mut lines = vec![format!("Detections in the last {} minutes:", self.window.num_minutes())];
+        for ((stream, class), count) in counts {
+            lines.push(format!("- {}: {} x {}", stream, count, class));
+        }
+        Ok(Some(lines.join("\n")))
+    }
+}
+
+pub struct ClockProvider {
+    timezone: chrono_tz::Tz,
+    locale: String,
+}
+
+impl ClockProvider {
+    pub fn new(timezone: chrono_tz::Tz, locale: &str) -> Self {
+        Self {
+            timezone,
+            locale: locale.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl ContextProvider for ClockProvider {
+    fn name(&self) -> String {
+        "time".to_string()
+    }
+
+    async fn provide(&self) -> Result<Option<String>> {
+        let now = Utc::now().with_timezone(&self.timezone);
+        Ok(Some(format!(
+            "Current time: {} ({}), locale {}",
+            now.format("%A %Y-%m-%d %H:%M"), self.timezone, self.locale
+        )))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextConfig {
+    pub enabled: bool,
+    // A slow provider is skipped rather than delaying the completion
+    pub provider_timeout_ms: u64,
+    pub max_chars: usize,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            provider_timeout_ms: 500,
+            max_chars: 4000,
+        }
+    }
+}
+
+pub struct ContextInjector {
+    config: ContextConfig,
+    providers: Vec<Arc<dyn ContextProvider>>,
+}
+
+impl ContextInjector {
+    pub fn new(config: ContextConfig) -> Self {
+        Self {
+            config,
+            providers: Vec::new(),
+        }
+    }
+
+    pub fn add_provider(&mut self, provider: Arc<dyn ContextProvider>) {
+        self.providers.push(provider);
+    }
+
+    pub async fn gather(&self) -> String {
+        let timeout = Duration::from_millis(self.config.provider_timeout_ms);
+        let results = futures::future::join_all(self.providers.iter().map(|provider| async move {
+            (provider.name(), tokio::time::timeout(timeout, provider.provide()).await)
+        })).await;
+
+        let mut sections = Vec::new();
+        for (name, result) in results {
+            match result {
+                Ok(Ok(Some(text))) => sections.push(format!("## {}\n{}", name, text)),
+                Ok(Ok(None)) => {}
+                Ok(Err(e)) => log::warn!("Context provider {} failed: {}", name, e),
+                Err(_) => log::warn!("Context provider {} timed out", name),
+            }
+        }
+
+        let mut context = sections.join("\n\n");
+        if context.len() > self.config.max_chars {
+            let mut cut = self.config.max_chars;
+            while !context.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            context.truncate(cut);
+        }
+        context
+    }
+
+    // Adds the gathered context as a system message after the agent's own
+    // system prompt so it never overrides persona instructions
+    pub async fn inject(&self, messages: Vec<Message>) -> Vec<Message> {
+        if !self.config.enabled || self.providers.is_empty() {
+            return messages;
+        }
+
+        let context = self.gather().await;
+        if context.is_empty() {
+            return messages;
+        }
+
+        let split = messages.iter().take_while(|m| m.role == "system").count();
+        let mut result = Vec::with_capacity(messages.len() + 1);
+        result.extend_from_slice(&messages[..split]);
+        result.push(Message::new("system", &format!("Current system context:\n\n{}", context)));
+        result.extend_from_slice(&messages[split..]);
+        result
+    }
+}
@@ -0,0 +1,314 @@
+use std::sync::Arc;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::core::agent::tools::Tool;
+use crate::core::state::{StateManager, StateSnapshot};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricPoint {
+    pub timestamp: DateTime<Utc>,
+    pub value: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricChange {
+    pub from: MetricPoint,
+    pub to: MetricPoint,
+    pub delta: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesSummary {
+    pub metric: String,
+    pub samples: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    // Biggest step between consecutive samples, so "why did X drop" gets a
+    // timestamp to look around
+    pub largest_drop: Option<MetricChange>,
+    pub largest_rise: Option<MetricChange>,
+}
+
+pub const METRICS: &[&str] = &[
+    "fps",
+    "frames_processed",
+    "queue_size",
+    "processing_latency",
+    "error_count",
+    "cpu_usage",
+    "gpu_usage",
+    "memory_usage",
+    "disk_usage",
+    "temperature",
+];
+
+// Stage metrics are addressed as "stage:<name>.<field>", e.g.
+// "stage:detector.average_time"
+pub fn metric_value(snapshot: &StateSnapshot, metric: &str) -> Option<f32> {
+    let state = &snapshot.state;
+    if let Some(stage) = metric.strip_prefix("stage:") {
+        let (name, field) = stage.rsplit_once('.')?;
+        let stage = state.pipeline_state.stage_metrics.get(name)?;
+        return match field {
+            "processed_items" => Some(stage.processed_items as f32),
+            "errors" => Some(stage.errors as f32),
+            "average_time" => Some(stage.average_time),
+            _ => None,
+        };
+    }
+
+    match metric {
+        "fps" => Some(state.engine_state.fps),
+        "frames_processed" => Some(state.engine_state.frames_processed as f32),
+        "queue_size" => Some(state.pipeline_state.queue_size as f32),
+        "processing_latency" => Some(state.pipeline_state.processing_latency),
+        "error_count" => Some(state.error_state.error_count as f32),
+        "cpu_usage" => Some(state.resource_state.cpu_usage),
+        "gpu_usage" => Some(state.resource_state.gpu_usage),
+        "memory_usage" => Some(state.resource_state.memory_usage),
+        "disk_usage" => Some(state.resource_state.disk_usage),
+        "temperature" => Some(state.resource_state.temperature),
+        _ => None,
+    }
+}
+
+pub fn series(snapshots: &[StateSnapshot], metric: &str) -> Vec<MetricPoint> {
+    snapshots.iter()
+        .filter_map(|s| metric_value(s, metric).map(|value| MetricPoint { timestamp: s.timestamp, value }))
+        .collect()
+}
+
+pub fn summarize(metric: &str, points: &[MetricPoint]) -> Option<SeriesSummary> {
+    if points.is_empty() {
+        return None;
+    }
+
+    let values = points.iter().map(|p| p.value);
+    let mut summary = SeriesSummary {
+        metric: metric.to_string(),
+        samples: points.len(),
+        min: values.clone().fold(f32::INFINITY, f32::min),
+        max: values.clone().fold(f32::NEG_INFINITY, f32::max),
+        mean: values.sum::<f32>() / points.len() as f32,
+        largest_drop: None,
+        largest_rise: None,
+    };
+
+    for pair in points.windows(2) {
+        let delta = pair[1].value - pair[0].value;
+        let change = || MetricChange { from: pair[0].clone(), to: pair[1].clone(), delta };
+        if delta < 0.0 && summary.largest_drop.as_ref().is_none_or(|c| delta < c.delta) {
+            summary.largest_drop = Some(change());
+        }
+        if delta > 0.0 && summary.largest_rise.as_ref().is_none_or(|c| delta > c.delta) {
+            summary.largest_rise = Some(change());
+        }
+    }
+    Some(summary)
+}
+
+// Keeps every Nth point so long ranges fit in the model's context, at most
+// `max_points` of them. The last point is always kept.
+pub fn downsample(points: Vec<MetricPoint>, max_points: usize) -> Vec<MetricPoint> {
+    if max_points == 0 || points.len() <= max_points {
+        return points;
+    }
+    if max_points == 1 {
+        return points.into_iter().last().into_iter().collect();
+    }
+    // The last point takes one slot; the step spreads the rest over the
+    // points before it
+    let last = points.len() - 1;
+    let step = last.div_ceil(max_points - 1);
+    points.into_iter()
+        .enumerate()
+        .filter(|(i, _)| (i % step == 0 && *i < last) || *i == last)
+        .map(|(_, p)| p)
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct RangeArgs {
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+}
+
+impl RangeArgs {
+    // Defaults to the last hour
+    fn resolve(&self) -> (DateTime<Utc>, DateTime<Utc>) {
+        let until = self.until.unwrap_or_else(Utc::now);
+        let since = self.since.unwrap_or(until - Duration::hours(1));
+        (since, until)
+    }
+}
+
+pub struct SystemStateTool {
+    state: Arc<StateManager>,
+}
+
+impl SystemStateTool {
+    pub fn new(state: Arc<StateManager>) -> Self {
+        Self { state }
+    }
+}
+
+#[async_trait]
+impl Tool for SystemStateTool {
+    fn name(&self) -> String {
+        "get_system_state".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Current engine status, FPS, pipeline stages and queue, resource usage and the \
+         most recent error.".to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        json!({ "type": "object", "properties": {} })
+    }
+
+    async fn call(&self, _args: Value) -> Result<Value> {
+        let state = self.state.get_current_state().await?;
+        Ok(json!({
+            "engine": state.engine_state,
+            "pipeline": state.pipeline_state,
+            "resources": state.resource_state,
+            "error_count": state.error_state.error_count,
+            "last_error": state.error_state.last_error,
+        }))
+    }
+}
+
+pub struct MetricsHistoryTool {
+    state: Arc<StateManager>,
+    max_points: usize,
+}
+
+impl MetricsHistoryTool {
+    pub fn new(state: Arc<StateManager>) -> Self {
+        Self { state, max_points: 60 }
+    }
+}
+
+#[async_trait]
+impl Tool for MetricsHistoryTool {
+    fn name(&self) -> String {
+        "query_metrics_history".to_string()
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Time series of system metrics between since and until (default: the last hour), \
+             with min/max/mean and the largest drop and rise per metric. Metrics: {}, or \
+             stage:<stage>.<processed_items|errors|average_time> for pipeline stages. To explain \
+             a drop, query around its timestamp and compare with query_errors.",
+            METRICS.join(", "),
+        )
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "metrics": { "type": "array", "items": { "type": "string" } },
+                "since": { "type": "string", "format": "date-time" },
+                "until": { "type": "string", "format": "date-time" }
+            },
+            "required": ["metrics"]
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<Value> {
+        #[derive(Deserialize)]
+        struct Args {
+            metrics: Vec<String>,
+            #[serde(flatten)]
+            range: RangeArgs,
+        }
+        let args: Args = serde_json::from_value(args).context("Invalid query_metrics_history arguments")?;
+        let (since, until) = args.range.resolve();
+        let snapshots = self.state.history_between(since, until).await;
+
+        let mut results = Vec::new();
+        for metric in &args.metrics {
+            if !metric.starts_with("stage:") && !METRICS.contains(&metric.as_str()) {
+                results.push(json!({ "metric": metric, "error": "unknown metric" }));
+                continue;
+            }
+            let points = series(&snapshots, metric);
+            results.push(json!({
+                "metric": metric,
+                "summary": summarize(metric, &points),
+                "points": downsample(points, self.max_points),
+            }));
+        }
+
+        Ok(json!({
+            "since": since,
+            "until": until,
+            "snapshots": snapshots.len(),
+            "metrics": results,
+        }))
+    }
+}
+
+pub struct ErrorLogTool {
+    state: Arc<StateManager>,
+}
+
+impl ErrorLogTool {
+    pub fn new(state: Arc<StateManager>) -> Self {
+        Self { state }
+    }
+}
+
+#[async_trait]
+impl Tool for ErrorLogTool {
+    fn name(&self) -> String {
+        "query_errors".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Recorded errors between since and until (default: the last hour), newest first, \
+         optionally filtered by error type.".to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "since": { "type": "string", "format": "date-time" },
+                "until": { "type": "string", "format": "date-time" },
+                "error_type": { "type": "string" },
+                "limit": { "type": "integer", "minimum": 1, "maximum": 100 }
+            }
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<Value> {
+        #[derive(Deserialize)]
+        struct Args {
+            error_type: Option<String>,
+            limit: Option<usize>,
+            #[serde(flatten)]
+            range: RangeArgs,
+        }
+        let args: Args = serde_json::from_value(args).context("Invalid query_errors arguments")?;
+        let (since, until) = args.range.resolve();
+
+        let mut errors = self.state.errors_between(since, until).await;
+        if let Some(error_type) = &args.error_type {
+            errors.retain(|e| &e.error_type == error_type);
+        }
+        let total = errors.len();
+        errors.reverse();
+        errors.truncate(args.limit.unwrap_or(20).clamp(1, 100));
+
+        Ok(json!({ "total": total, "errors": errors }))
+    }
+}
@@ -0,0 +1,286 @@
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+
+use crate::core::crypto::Encryptor;
+use crate::core::llm::{LLMTrait, types::Message};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityKind {
+    User,
+    Person,
+    Object,
+    Location,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum FactSource {
+    Conversation { session_id: String },
+    Vision { stream_id: String },
+    Manual,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fact {
+    pub value: String,
+    pub source: FactSource,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entity {
+    pub id: String,
+    pub name: String,
+    pub kind: EntityKind,
+    pub aliases: Vec<String>,
+    pub facts: HashMap<String, Fact>,
+    pub last_seen: DateTime<Utc>,
+}
+
+impl Entity {
+    fn mentioned_in(&self, text: &str) -> bool {
+        std::iter::once(&self.name)
+            .chain(self.aliases.iter())
+            .any(|name| contains_word(text, &name.to_lowercase()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntityMemoryConfig {
+    pub persist: bool,
+    pub entities_file: String,
+    pub max_recalled: usize,
+}
+
+impl Default for EntityMemoryConfig {
+    fn default() -> Self {
+        Self {
+            persist: true,
+            entities_file: String::from("entities.json"),
+            max_recalled: 5,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ExtractedFact {
+    entity: String,
+    kind: EntityKind,
+    attribute: String,
+    value: String,
+}
+
+// Structured facts keyed by entity, kept apart from the raw message buffer
+// so they survive memory trimming
+pub struct EntityMemory {
+    config: EntityMemoryConfig,
+    entities: Arc<RwLock<HashMap<String, Entity>>>,
+    encryptor: Option<Arc<Encryptor>>,
+}
+
+impl EntityMemory {
+    pub async fn new(config: EntityMemoryConfig) -> Result<Self> {
+        Self::open(config, None).await
+    }
+
+    // The entities file is written sealed; a plaintext one from before
+    // encryption was enabled is still read
+    pub async fn new_encrypted(config: EntityMemoryConfig, encryptor: Arc<Encryptor>) -> Result<Self> {
+        Self::open(config, Some(encryptor)).await
+    }
+
+    async fn open(config: EntityMemoryConfig, encryptor: Option<Arc<Encryptor>>) -> Result<Self> {
+        let entities = if config.persist && tokio::fs::try_exists(&config.entities_file).await? {
+            let contents = match &encryptor {
+                Some(encryptor) => encryptor.read_file(&config.entities_file).await?,
+                None => tokio::fs::read(&config.entities_file).await
+                    .context("Failed to read entities file")?,
+            };
+            let list: Vec<Entity> = serde_json::from_slice(&contents)
+                .context("Failed to parse entities file")?;
+            list.into_iter().map(|e| (e.id.clone(), e)).collect()
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            config,
+            entities: Arc::new(RwLock::new(entities)),
+            encryptor,
+        })
+    }
+
+    pub async fn get(&self, name: &str) -> Option<Entity> {
+        self.entities.read().await.get(&entity_id(name)).cloned()
+    }
+
+    pub async fn upsert_fact(
+        &self,
+        name: &str,
+        kind: EntityKind,
+        attribute: &str,
+        value: &str,
+        source: FactSource,
+    ) -> Result<Entity> {
+        let now = Utc::now();
+        let id = entity_id(name);
+        if id.is_empty() {
+            return Err(anyhow::anyhow!("Entity name must not be empty"));
+        }
+
+        let mut entities = self.entities.write().await;
+        let entity = entities.entry(id.clone()).or_insert_with(|| Entity {
+            id,
+            name: name.trim().to_string(),
+            kind,
+            aliases: Vec::new(),
+            facts: HashMap::new(),
+            last_seen: now,
+        });
+
+        // Newer statements replace older ones for the same attribute
+        entity.facts.insert(attribute.to_lowercase(), Fact {
+            value: value.to_string(),
+            source,
+            updated_at: now,
+        });
+        entity.last_seen = now;
+        let entity = entity.clone();
+        drop(entities);
+
+        self.persist().await?;
+        Ok(entity)
+    }
+
+    pub async fn add_alias(&self, name: &str, alias: &str) -> Result<()> {
+        let mut entities = self.entities.write().await;
+        let entity = entities.get_mut(&entity_id(name))
+            .ok_or_else(|| anyhow::anyhow!("Entity not found: {}", name))?;
+        if !entity.aliases.iter().any(|a| a.eq_ignore_ascii_case(alias)) {
+            entity.aliases.push(alias.to_string());
+        }
+        drop(entities);
+
+        self.persist().await
+    }
+
+    pub async fn forget(&self, name: &str) -> Result<()> {
+        if self.entities.write().await.remove(&entity_id(name)).is_none() {
+            return Err(anyhow::anyhow!("Entity not found: {}", name));
+        }
+        self.persist().await
+    }
+
+    // Entities whose name or alias appears in the text, most recent first
+    pub async fn recall(&self, text: &str) -> Vec<Entity> {
+        let text = text.to_lowercase();
+        let mut matches: Vec<_> = self.entities.read().await.values()
+            .filter(|e| e.mentioned_in(&text))
+            .cloned()
+            .collect();
+        matches.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+        matches.truncate(self.config.max_recalled);
+        matches
+    }
+
+    // Uses the model to pull durable facts out of a user message
+    pub async fn extract(&self, llm: &dyn LLMTrait, session_id: &str, message: &Message) -> Result<usize> {
+        let response = llm.complete(vec![
+            Message::new(
+                "system",
+                "Extract durable facts about people, the user, objects or locations \
+                 from the message. Reply with a JSON array only: \
+                 [{\"entity\": \"...\", \"kind\": \"user|person|object|location|other\", \
+                 \"attribute\": \"...\", \"value\": \"...\"}]. Use entity \"user\" for \
+                 facts about the speaker. Reply [] if there are none.",
+            ),
+            message.clone(),
+        ]).await.context("Entity extraction request failed")?;
+
+        let content = response.content.trim();
+        let json = match (content.find('['), content.rfind(']')) {
+            (Some(start), Some(end)) if end > start => &content[start..=end],
+            _ => "[]",
+        };
+        let facts: Vec<ExtractedFact> = serde_json::from_str(json)
+            .context("Failed to parse extracted entities")?;
+
+        for fact in &facts {
+            self.upsert_fact(
+                &fact.entity,
+                fact.kind,
+                &fact.attribute,
+                &fact.value,
+                FactSource::Conversation { session_id: session_id.to_string() },
+            ).await?;
+        }
+
+        Ok(facts.len())
+    }
+
+    // Vision keeps track of recurring objects per stream, e.g. "delivery van"
+    pub async fn record_sighting(&self, stream_id: &str, class_name: &str, location: Option<&str>) -> Result<()> {
+        let name = format!("{} at {}", class_name, stream_id);
+        let sightings = self.get(&name).await
+            .and_then(|e| e.facts.get("sightings").and_then(|f| f.value.parse::<u64>().ok()))
+            .unwrap_or(0);
+        let source = || FactSource::Vision { stream_id: stream_id.to_string() };
+
+        self.upsert_fact(&name, EntityKind::Object, "sightings", &(sightings + 1).to_string(), source()).await?;
+        self.upsert_fact(&name, EntityKind::Object, "last_seen_at", &Utc::now().to_rfc3339(), source()).await?;
+        if let Some(location) = location {
+            self.upsert_fact(&name, EntityKind::Object, "location", location, source()).await?;
+        }
+        Ok(())
+    }
+
+    pub fn format_for_prompt(entities: &[Entity]) -> String {
+        let mut lines = Vec::new();
+        for entity in entities {
+            let mut facts: Vec<_> = entity.facts.iter()
+                .map(|(attribute, fact)| format!("{}: {}", attribute, fact.value))
+                .collect();
+            facts.sort();
+            lines.push(format!("- {} ({:?}): {}", entity.name, entity.kind, facts.join("; ")));
+        }
+        lines.join("\n")
+    }
+
+    async fn persist(&self) -> Result<()> {
+        if !self.config.persist {
+            return Ok(());
+        }
+
+        let list: Vec<Entity> = self.entities.read().await.values().cloned().collect();
+        let serialized = serde_json::to_string_pretty(&list)?;
+        match &self.encryptor {
+            Some(encryptor) => encryptor.write_file(&self.config.entities_file, serialized.as_bytes()).await
+                .context("Failed to persist entities")?,
+            None => tokio::fs::write(&self.config.entities_file, serialized).await
+                .context("Failed to persist entities")?,
+        }
+        Ok(())
+    }
+}
+
+fn entity_id(name: &str) -> String {
+    name.trim().to_lowercase().split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+fn contains_word(text: &str, word: &str) -> bool {
+    if word.is_empty() {
+        return false;
+    }
+    text.match_indices(word).any(|(start, _)| {
+        let end = start + word.len();
+        let before = text[..start].chars().next_back().map_or(true, |c| !c.is_alphanumeric());
+        let after = text[end..].chars().next().map_or(true, |c| !c.is_alphanumeric());
+        before && after
+    })
+}
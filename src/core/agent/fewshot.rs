@@ -0,0 +1,175 @@
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::core::llm::types::Message;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExampleTurn {
+    pub role: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FewShotExample {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub agent: String,
+    pub turns: Vec<ExampleTurn>,
+    // Higher priority examples are kept first when the budget is tight
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl FewShotExample {
+    fn validate(&self) -> Result<()> {
+        if self.agent.trim().is_empty() {
+            return Err(anyhow::anyhow!("Example must name an agent"));
+        }
+        if self.turns.is_empty() {
+            return Err(anyhow::anyhow!("Example must contain at least one turn"));
+        }
+        for turn in &self.turns {
+            if turn.role != "user" && turn.role != "assistant" {
+                return Err(anyhow::anyhow!("Invalid example role: {}", turn.role));
+            }
+        }
+        Ok(())
+    }
+
+    fn estimated_tokens(&self) -> usize {
+        self.turns.iter().map(|t| estimate_tokens(&t.content)).sum()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FewShotConfig {
+    pub persist: bool,
+    // Kept next to prompts.json so prompts and their exemplars move together
+    pub examples_file: String,
+    pub token_budget: usize,
+    pub max_examples: usize,
+}
+
+impl Default for FewShotConfig {
+    fn default() -> Self {
+        Self {
+            persist: true,
+            examples_file: String::from("prompt_examples.json"),
+            token_budget: 1000,
+            max_examples: 5,
+        }
+    }
+}
+
+pub struct FewShotStore {
+    config: FewShotConfig,
+    examples: Arc<RwLock<Vec<FewShotExample>>>,
+}
+
+impl FewShotStore {
+    pub async fn new(config: FewShotConfig) -> Result<Self> {
+        let examples = if config.persist && tokio::fs::try_exists(&config.examples_file).await? {
+            let contents = tokio::fs::read_to_string(&config.examples_file).await
+                .context("Failed to read few-shot examples file")?;
+            serde_json::from_str(&contents)
+                .context("Failed to parse few-shot examples file")?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            config,
+            examples: Arc::new(RwLock::new(examples)),
+        })
+    }
+
+    pub async fn list(&self, agent: &str) -> Vec<FewShotExample> {
+        let mut examples: Vec<_> = self.examples.read().await.iter()
+            .filter(|e| e.agent == agent)
+            .cloned()
+            .collect();
+        examples.sort_by(|a, b| b.priority.cmp(&a.priority).then(a.created_at.cmp(&b.created_at)));
+        examples
+    }
+
+    pub async fn add(&self, mut example: FewShotExample) -> Result<FewShotExample> {
+        example.validate()?;
+        example.id = Uuid::new_v4().to_string();
+        example.created_at = Utc::now();
+
+        self.examples.write().await.push(example.clone());
+        self.persist().await?;
+        Ok(example)
+    }
+
+    pub async fn remove(&self, agent: &str, id: &str) -> Result<()> {
+        let mut examples = self.examples.write().await;
+        let before = examples.len();
+        examples.retain(|e| !(e.agent == agent && e.id == id));
+        if examples.len() == before {
+            return Err(anyhow::anyhow!("Example not found: {}", id));
+        }
+        drop(examples);
+
+        self.persist().await
+    }
+
+    // Inserts the agent's examples after any leading system messages,
+    // dropping lower priority examples once the token budget is spent
+    pub async fn inject(&self, agent: &str, messages: Vec<Message>) -> Vec<Message> {
+        let mut remaining = self.config.token_budget;
+        let mut selected = Vec::new();
+
+        for example in self.list(agent).await {
+            if selected.len() >= self.config.max_examples {
+                break;
+            }
+            let cost = example.estimated_tokens();
+            if cost > remaining {
+                continue;
+            }
+            remaining -= cost;
+            selected.push(example);
+        }
+
+        if selected.is_empty() {
+            return messages;
+        }
+
+        let split = messages.iter().take_while(|m| m.role == "system").count();
+        let mut result = Vec::with_capacity(messages.len() + selected.len() * 2);
+        result.extend_from_slice(&messages[..split]);
+        for example in &selected {
+            for turn in &example.turns {
+                result.push(Message::new(&turn.role, &turn.content));
+            }
+        }
+        result.extend_from_slice(&messages[split..]);
+        result
+    }
+
+    async fn persist(&self) -> Result<()> {
+        if !self.config.persist {
+            return Ok(());
+        }
+
+        let serialized = serde_json::to_string_pretty(&*self.examples.read().await)?;
+        tokio::fs::write(&self.config.examples_file, serialized).await
+            .context("Failed to persist few-shot examples")?;
+        Ok(())
+    }
+}
+
+// Rough estimate; avoids pulling a tokenizer in just for budgeting
+fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
@@ -0,0 +1,287 @@
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use chrono::{DateTime, Utc};
+
+use crate::core::agent::tools::Tool;
+use crate::core::llm::{LLMTrait, types::Message};
+#[cfg(feature = "vision")]
+use crate::vision::detector::Detection;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Node {
+    pub id: String,
+    pub label: String,
+    pub kind: String,
+    pub properties: HashMap<String, String>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Edge {
+    pub from: String,
+    pub relation: String,
+    pub to: String,
+    pub timestamp: DateTime<Utc>,
+    pub source: String,
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GraphQuery {
+    #[serde(default)]
+    pub subject: Option<String>,
+    #[serde(default)]
+    pub relation: Option<String>,
+    #[serde(default)]
+    pub object: Option<String>,
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphFact {
+    pub subject: String,
+    pub relation: String,
+    pub object: String,
+    pub timestamp: DateTime<Utc>,
+    pub source: String,
+    pub properties: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphConfig {
+    // Edges with the oldest timestamps are evicted first once the cap is reached
+    pub max_edges: usize,
+}
+
+impl Default for GraphConfig {
+    fn default() -> Self {
+        Self { max_edges: 100_000 }
+    }
+}
+
+#[derive(Default)]
+struct GraphState {
+    nodes: HashMap<String, Node>,
+    edges: VecDeque<Edge>,
+}
+
+pub struct GraphStore {
+    config: GraphConfig,
+    state: RwLock<GraphState>,
+}
+
+impl GraphStore {
+    pub fn new(config: GraphConfig) -> Self {
+        Self {
+            config,
+            state: RwLock::new(GraphState::default()),
+        }
+    }
+
+    pub async fn upsert_node(&self, label: &str, kind: &str, at: DateTime<Utc>) -> String {
+        let id = node_id(label);
+        let mut state = self.state.write().await;
+        let node = state.nodes.entry(id.clone()).or_insert_with(|| Node {
+            id: id.clone(),
+            label: label.trim().to_string(),
+            kind: kind.to_string(),
+            properties: HashMap::new(),
+            first_seen: at,
+            last_seen: at,
+        });
+        if at > node.last_seen {
+            node.last_seen = at;
+        }
+        id
+    }
+
+    pub async fn relate(
+        &self,
+        (subject, subject_kind): (&str, &str),
+        relation: &str,
+        (object, object_kind): (&str, &str),
+        at: DateTime<Utc>,
+        source: &str,
+        properties: HashMap<String, String>,
+    ) {
+        let from = self.upsert_node(subject, subject_kind, at).await;
+        let to = self.upsert_node(object, object_kind, at).await;
+
+        // Edges stay in timestamp order, so a late or replayed event lands
+        // where it belongs and `query` and eviction see the true newest and
+        // oldest rather than arrival order
+        let mut state = self.state.write().await;
+        let position = state.edges.partition_point(|e| e.timestamp <= at);
+        state.edges.insert(position, Edge {
+            from,
+            relation: relation.to_lowercase().replace(' ', "_"),
+            to,
+            timestamp: at,
+            source: source.to_string(),
+            properties,
+        });
+        while state.edges.len() > self.config.max_edges {
+            state.edges.pop_front();
+        }
+    }
+
+    // Vision events become `<class> seen_at <stream>` and `<class> seen_near <zone>`
+    #[cfg(feature = "vision")]
+    pub async fn record_detection(&self, stream_id: &str, detection: &Detection, zones: &[String]) {
+        let properties = HashMap::from([
+            ("confidence".to_string(), format!("{:.2}", detection.confidence)),
+            ("frame_id".to_string(), detection.frame_id.to_string()),
+        ]);
+        let subject = (detection.class_name.as_str(), "object");
+        let source = format!("vision:{}", stream_id);
+
+        self.relate(subject, "seen_at", (stream_id, "camera"), detection.timestamp, &source, properties.clone()).await;
+        for zone in zones {
+            self.relate(subject, "seen_near", (zone, "location"), detection.timestamp, &source, properties.clone()).await;
+        }
+    }
+
+    pub async fn extract_from_conversation(&self, llm: &dyn LLMTrait, session_id: &str, text: &str) -> Result<usize> {
+        let response = llm.complete(vec![
+            Message::new(
+                "system",
+                "Extract factual relations from the text as a JSON array of \
+                 {\"subject\", \"subject_kind\", \"relation\", \"object\", \"object_kind\"}. \
+                 Kinds are person, object, location, camera or other. Relations are \
+                 short snake_case verbs. Reply [] if there are none.",
+            ),
+            Message::new("user", text),
+        ]).await.context("Relation extraction request failed")?;
+
+        #[derive(Deserialize)]
+        struct Triple {
+            subject: String,
+            subject_kind: String,
+            relation: String,
+            object: String,
+            object_kind: String,
+        }
+
+        let content = response.content.trim();
+        let json = match (content.find('['), content.rfind(']')) {
+            (Some(start), Some(end)) if end > start => &content[start..=end],
+            _ => "[]",
+        };
+        let triples: Vec<Triple> = serde_json::from_str(json)
+            .context("Failed to parse extracted relations")?;
+
+        let now = Utc::now();
+        let source = format!("conversation:{}", session_id);
+        for triple in &triples {
+            self.relate(
+                (&triple.subject, &triple.subject_kind),
+                &triple.relation,
+                (&triple.object, &triple.object_kind),
+                now,
+                &source,
+                HashMap::new(),
+            ).await;
+        }
+        Ok(triples.len())
+    }
+
+    // Most recent matching facts first; subject/object match on node id or
+    // on a label containing the query text
+    pub async fn query(&self, query: &GraphQuery) -> Vec<GraphFact> {
+        let state = self.state.read().await;
+        let matches_node = |id: &str, wanted: &Option<String>| match wanted {
+            None => true,
+            Some(wanted) => {
+                let wanted_id = node_id(wanted);
+                id == wanted_id || state.nodes.get(id).map_or(false, |n| {
+                    n.label.to_lowercase().contains(&wanted.to_lowercase())
+                })
+            }
+        };
+        let relation = query.relation.as_ref().map(|r| r.to_lowercase().replace(' ', "_"));
+
+        state.edges.iter()
+            .rev()
+            .filter(|e| relation.as_ref().map_or(true, |r| &e.relation == r))
+            .filter(|e| query.since.map_or(true, |since| e.timestamp >= since))
+            .filter(|e| query.until.map_or(true, |until| e.timestamp <= until))
+            .filter(|e| matches_node(&e.from, &query.subject) && matches_node(&e.to, &query.object))
+            .take(query.limit.unwrap_or(20))
+            .map(|e| GraphFact {
+                subject: state.nodes.get(&e.from).map_or(e.from.clone(), |n| n.label.clone()),
+                relation: e.relation.clone(),
+                object: state.nodes.get(&e.to).map_or(e.to.clone(), |n| n.label.clone()),
+                timestamp: e.timestamp,
+                source: e.source.clone(),
+                properties: e.properties.clone(),
+            })
+            .collect()
+    }
+
+    pub async fn node(&self, label: &str) -> Option<Node> {
+        self.state.read().await.nodes.get(&node_id(label)).cloned()
+    }
+}
+
+fn node_id(label: &str) -> String {
+    label.trim().to_lowercase().split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+pub struct QueryGraphTool {
+    graph: Arc<GraphStore>,
+}
+
+impl QueryGraphTool {
+    pub fn new(graph: Arc<GraphStore>) -> Self {
+        Self { graph }
+    }
+}
+
+#[async_trait]
+impl Tool for QueryGraphTool {
+    fn name(&self) -> String {
+        "query_graph".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Look up remembered facts and sightings as subject-relation-object triples, \
+         newest first. Vision sightings use relations seen_at (camera) and seen_near \
+         (zone). Example: subject \"truck\", relation \"seen_near\", object \"gate 3\", \
+         limit 1 finds when a truck was last seen near gate 3.".to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "subject": { "type": "string" },
+                "relation": { "type": "string" },
+                "object": { "type": "string" },
+                "since": { "type": "string", "format": "date-time" },
+                "until": { "type": "string", "format": "date-time" },
+                "limit": { "type": "integer", "minimum": 1, "maximum": 100 }
+            }
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<Value> {
+        let mut query: GraphQuery = serde_json::from_value(args)
+            .context("Invalid query_graph arguments")?;
+        query.limit = Some(query.limit.unwrap_or(20).clamp(1, 100));
+
+        let facts = self.graph.query(&query).await;
+        Ok(json!({ "facts": facts }))
+    }
+}
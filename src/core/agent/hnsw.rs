@@ -0,0 +1,355 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use tokio::sync::RwLock;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+
+use crate::core::agent::vector_memory::{MemoryIndex, MemoryMatch, MemoryPoint};
+use crate::core::rag::store::cosine_similarity;
+
+// In-process approximate nearest neighbour index (HNSW, Malkov & Yashunin).
+// Points live in a stack of proximity graphs, sparser towards the top;
+// a search walks greedily down from the single entry point and explores
+// ef candidates on the bottom layer. Deletes leave a tombstone that still
+// routes searches, and the graph is rebuilt once tombstones outnumber live
+// points. Each scope gets a graph of its own, so a small session is never
+// crowded out of the walk by a large one. Nothing is persisted.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HnswConfig {
+    // Links per node and layer; the bottom layer gets twice as many
+    pub m: usize,
+    pub ef_construction: usize,
+    pub ef_search: usize,
+    // Below this many points an exact scan is as fast and never misses
+    pub exact_below: usize,
+    pub seed: u64,
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 100,
+            ef_search: 64,
+            exact_below: 512,
+            seed: 0x5eed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate {
+    distance: f32,
+    node: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance.total_cmp(&other.distance).then(self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+struct Node {
+    point: MemoryPoint,
+    // Neighbours per layer, from 0 up to the node's level
+    neighbors: Vec<Vec<usize>>,
+    deleted: bool,
+}
+
+struct Graph {
+    nodes: Vec<Node>,
+    ids: HashMap<String, usize>,
+    entry: Option<usize>,
+    live: usize,
+    rng: u64,
+}
+
+impl Graph {
+    fn new(seed: u64) -> Self {
+        Self {
+            nodes: Vec::new(),
+            ids: HashMap::new(),
+            entry: None,
+            live: 0,
+            // xorshift is stuck at zero
+            rng: seed.max(1),
+        }
+    }
+
+    fn distance(&self, query: &[f32], node: usize) -> f32 {
+        1.0 - cosine_similarity(query, &self.nodes[node].point.embedding)
+    }
+
+    // Geometric: each layer up holds about 1/m of the one below
+    fn random_level(&mut self, m: usize) -> usize {
+        let mut x = self.rng;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rng = x;
+        let bits = x.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+        let uniform = (bits as f64 + 0.5) / (1u64 << 53) as f64;
+        (-uniform.ln() / (m.max(2) as f64).ln()).floor() as usize
+    }
+
+    fn top_level(&self) -> usize {
+        self.entry.map_or(0, |entry| self.nodes[entry].neighbors.len() - 1)
+    }
+
+    // The `ef` nodes closest to `query` reachable on `layer`, nearest first
+    fn search_layer(&self, query: &[f32], entry: &[usize], ef: usize, layer: usize) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entry.iter().copied().collect();
+        let mut frontier = BinaryHeap::new();
+        let mut found = BinaryHeap::new();
+        for &node in entry {
+            let candidate = Candidate { distance: self.distance(query, node), node };
+            frontier.push(Reverse(candidate));
+            found.push(candidate);
+        }
+
+        while let Some(Reverse(current)) = frontier.pop() {
+            let furthest = found.peek().map_or(f32::INFINITY, |c| c.distance);
+            if current.distance > furthest && found.len() >= ef {
+                break;
+            }
+            let Some(neighbors) = self.nodes[current.node].neighbors.get(layer) else {
+                continue;
+            };
+            for &neighbor in neighbors {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let distance = self.distance(query, neighbor);
+                let furthest = found.peek().map_or(f32::INFINITY, |c| c.distance);
+                if found.len() < ef || distance < furthest {
+                    let candidate = Candidate { distance, node: neighbor };
+                    frontier.push(Reverse(candidate));
+                    found.push(candidate);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        found.into_sorted_vec()
+    }
+
+    fn greedy_descent(&self, query: &[f32], down_to: usize) -> Vec<usize> {
+        let Some(entry) = self.entry else {
+            return Vec::new();
+        };
+        let mut nearest = vec![entry];
+        for layer in (down_to + 1..=self.top_level()).rev() {
+            nearest = vec![self.search_layer(query, &nearest, 1, layer)[0].node];
+        }
+        nearest
+    }
+
+    fn insert(&mut self, point: MemoryPoint, config: &HnswConfig) {
+        self.remove(&point.id);
+
+        let level = self.random_level(config.m);
+        let index = self.nodes.len();
+        let query = point.embedding.clone();
+        self.ids.insert(point.id.clone(), index);
+        self.nodes.push(Node { point, neighbors: vec![Vec::new(); level + 1], deleted: false });
+        self.live += 1;
+
+        if self.entry.is_none() {
+            self.entry = Some(index);
+            return;
+        }
+        let top = self.top_level();
+        let mut nearest = self.greedy_descent(&query, level);
+
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&query, &nearest, config.ef_construction.max(1), layer);
+            let selected: Vec<usize> = found.iter()
+                .filter(|c| c.node != index)
+                .take(config.m.max(1))
+                .map(|c| c.node)
+                .collect();
+            let cap = if layer == 0 { config.m.max(1) * 2 } else { config.m.max(1) };
+
+            self.nodes[index].neighbors[layer] = selected.clone();
+            for neighbor in selected {
+                self.nodes[neighbor].neighbors[layer].push(index);
+                if self.nodes[neighbor].neighbors[layer].len() > cap {
+                    self.prune(neighbor, layer, cap);
+                }
+            }
+            nearest = found.into_iter().map(|c| c.node).collect();
+        }
+
+        if level > top {
+            self.entry = Some(index);
+        }
+    }
+
+    // Keeps a node's closest `cap` links on a layer
+    fn prune(&mut self, node: usize, layer: usize, cap: usize) {
+        let base = &self.nodes[node].point.embedding;
+        let mut links: Vec<Candidate> = self.nodes[node].neighbors[layer].iter()
+            .map(|&n| Candidate { distance: 1.0 - cosine_similarity(base, &self.nodes[n].point.embedding), node: n })
+            .collect();
+        links.sort();
+        links.truncate(cap);
+        self.nodes[node].neighbors[layer] = links.into_iter().map(|c| c.node).collect();
+    }
+
+    fn remove(&mut self, id: &str) -> bool {
+        let Some(index) = self.ids.remove(id) else {
+            return false;
+        };
+        self.nodes[index].deleted = true;
+        self.live -= 1;
+        true
+    }
+
+    fn search(&self, query: &[f32], k: usize, config: &HnswConfig) -> Vec<Candidate> {
+        if self.live <= config.exact_below {
+            let mut all: Vec<Candidate> = (0..self.nodes.len())
+                .filter(|&node| !self.nodes[node].deleted)
+                .map(|node| Candidate { distance: self.distance(query, node), node })
+                .collect();
+            all.sort();
+            all.truncate(k);
+            return all;
+        }
+
+        let nearest = self.greedy_descent(query, 0);
+        if nearest.is_empty() {
+            return Vec::new();
+        }
+        // Tombstones are filtered after the walk, so look at more than k
+        let ef = config.ef_search.max(k * 4);
+        self.search_layer(query, &nearest, ef, 0).into_iter()
+            .filter(|c| !self.nodes[c.node].deleted)
+            .take(k)
+            .collect()
+    }
+
+    // Drops tombstones by inserting the live points into a fresh graph
+    fn rebuild(&mut self, config: &HnswConfig) {
+        let mut fresh = Graph::new(self.rng);
+        let nodes = std::mem::take(&mut self.nodes);
+        for node in nodes.into_iter().filter(|n| !n.deleted) {
+            fresh.insert(node.point, config);
+        }
+        *self = fresh;
+    }
+}
+
+#[derive(Default)]
+struct Scopes {
+    graphs: HashMap<String, Graph>,
+    // The scope each id was stored under
+    owners: HashMap<String, String>,
+}
+
+impl Scopes {
+    fn remove(&mut self, id: &str) -> Option<String> {
+        let scope = self.owners.remove(id)?;
+        if let Some(graph) = self.graphs.get_mut(&scope) {
+            graph.remove(id);
+        }
+        Some(scope)
+    }
+}
+
+pub struct HnswIndex {
+    config: HnswConfig,
+    scopes: RwLock<Scopes>,
+}
+
+impl HnswIndex {
+    pub fn new(config: HnswConfig) -> Self {
+        Self { config, scopes: RwLock::new(Scopes::default()) }
+    }
+
+    pub async fn rebuild(&self) {
+        let mut scopes = self.scopes.write().await;
+        for graph in scopes.graphs.values_mut() {
+            graph.rebuild(&self.config);
+        }
+    }
+}
+
+#[async_trait]
+impl MemoryIndex for HnswIndex {
+    async fn upsert(&self, points: Vec<MemoryPoint>) -> Result<()> {
+        let mut scopes = self.scopes.write().await;
+        for point in points {
+            // A point that moves scope leaves its old graph
+            scopes.remove(&point.id);
+            scopes.owners.insert(point.id.clone(), point.scope.clone());
+            let seed = self.config.seed;
+            scopes.graphs.entry(point.scope.clone())
+                .or_insert_with(|| Graph::new(seed))
+                .insert(point, &self.config);
+        }
+        Ok(())
+    }
+
+    async fn search(&self, embedding: &[f32], k: usize, scope: &str) -> Result<Vec<MemoryMatch>> {
+        let scopes = self.scopes.read().await;
+        let Some(graph) = scopes.graphs.get(scope) else {
+            return Ok(Vec::new());
+        };
+        Ok(graph.search(embedding, k, &self.config).into_iter()
+            .map(|c| {
+                let point = &graph.nodes[c.node].point;
+                MemoryMatch {
+                    id: point.id.clone(),
+                    message: point.message.clone(),
+                    score: 1.0 - c.distance,
+                    stored_at: point.stored_at,
+                }
+            })
+            .collect())
+    }
+
+    async fn delete(&self, ids: &[String]) -> Result<()> {
+        let mut scopes = self.scopes.write().await;
+        let touched: HashSet<String> = ids.iter().filter_map(|id| scopes.remove(id)).collect();
+        for scope in touched {
+            let Some(graph) = scopes.graphs.get_mut(&scope) else {
+                continue;
+            };
+            if graph.live == 0 {
+                scopes.graphs.remove(&scope);
+            } else if graph.nodes.len() - graph.live > graph.live.max(self.config.exact_below) {
+                graph.rebuild(&self.config);
+            }
+        }
+        Ok(())
+    }
+
+    async fn ids(&self, scope: &str) -> Result<Vec<String>> {
+        let scopes = self.scopes.read().await;
+        let Some(graph) = scopes.graphs.get(scope) else {
+            return Ok(Vec::new());
+        };
+        let mut points: Vec<&MemoryPoint> = graph.nodes.iter()
+            .filter(|n| !n.deleted)
+            .map(|n| &n.point)
+            .collect();
+        points.sort_by_key(|p| p.stored_at);
+        Ok(points.into_iter().map(|p| p.id.clone()).collect())
+    }
+
+    async fn count(&self, scope: &str) -> Result<usize> {
+        Ok(self.scopes.read().await.graphs.get(scope).map_or(0, |g| g.live))
+    }
+}
@@ -0,0 +1,131 @@
+#![cfg(feature = "vision")]
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+
+use crate::core::agent::tools::Tool;
+use crate::core::llm::multimodal::{ImagePart, MultimodalLLM, MultimodalMessage};
+use crate::core::pipeline::PipelineData;
+use crate::core::sinks::ResultSink;
+use crate::vision::{privacy::PrivacyMasker, processor::Frame};
+
+// Longest side of frames sent to the model; GPT-4o and Claude both
+// downscale anything larger
+const DEFAULT_MAX_SIDE: u32 = 1024;
+
+// The most recent processed frame of each stream. Attach it to the
+// pipeline with `add_sink`; frames are kept after masking, as sinks see them.
+#[derive(Default)]
+pub struct LatestFrames {
+    frames: RwLock<HashMap<String, Frame>>,
+}
+
+impl LatestFrames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn get(&self, stream_id: &str) -> Option<Frame> {
+        self.frames.read().await.get(stream_id).cloned()
+    }
+
+    pub async fn streams(&self) -> Vec<String> {
+        let mut streams: Vec<_> = self.frames.read().await.keys().cloned().collect();
+        streams.sort();
+        streams
+    }
+}
+
+#[async_trait]
+impl ResultSink for LatestFrames {
+    fn name(&self) -> String {
+        "latest_frames".to_string()
+    }
+
+    async fn write(&self, data: &PipelineData) -> Result<()> {
+        let Some(stream_id) = data.frame.metadata.stream_id.clone() else {
+            return Ok(());
+        };
+        self.frames.write().await.insert(stream_id, data.frame.clone());
+        Ok(())
+    }
+}
+
+// Lets the agent look at a camera: the stream's latest frame goes to a
+// vision-capable model together with the question, and the answer comes back
+pub struct LookAtCameraTool {
+    frames: Arc<LatestFrames>,
+    llm: Arc<dyn MultimodalLLM>,
+    masker: Arc<PrivacyMasker>,
+    max_side: u32,
+}
+
+impl LookAtCameraTool {
+    pub fn new(frames: Arc<LatestFrames>, llm: Arc<dyn MultimodalLLM>, masker: Arc<PrivacyMasker>) -> Self {
+        Self { frames, llm, masker, max_side: DEFAULT_MAX_SIDE }
+    }
+
+    pub fn with_max_side(mut self, max_side: u32) -> Self {
+        self.max_side = max_side;
+        self
+    }
+}
+
+#[async_trait]
+impl Tool for LookAtCameraTool {
+    fn name(&self) -> String {
+        "look_at_camera".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Look at the latest frame from a camera stream and answer a question about what \
+         it shows, e.g. \"is the loading dock door open?\"".to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "stream_id": { "type": "string" },
+                "question": { "type": "string" }
+            },
+            "required": ["stream_id", "question"]
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<Value> {
+        let stream_id = args.get("stream_id")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow::anyhow!("look_at_camera requires a stream_id"))?;
+        let question = args.get("question")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow::anyhow!("look_at_camera requires a question"))?;
+
+        let frame = self.frames.get(stream_id).await.ok_or_else(|| anyhow::anyhow!(
+            "No frame from stream {} yet (streams with frames: {})",
+            stream_id,
+            self.frames.streams().await.join(", "),
+        ))?;
+        let image = ImagePart::from_frame(&frame, &self.masker, self.max_side)?;
+        let prompt = format!(
+            "This is the latest frame from camera stream \"{}\", captured at {}. {}",
+            stream_id,
+            frame.timestamp.to_rfc3339(),
+            question,
+        );
+
+        let response = self.llm
+            .complete_multimodal(vec![MultimodalMessage::new("user", &prompt).with_image(image)])
+            .await?;
+        Ok(json!({
+            "stream_id": stream_id,
+            "frame_id": frame.id,
+            "captured_at": frame.timestamp,
+            "answer": response.content,
+        }))
+    }
+}
@@ -0,0 +1,252 @@
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use reqwest::Method;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use chrono::{DateTime, Utc};
+use tokio::sync::OnceCell;
+
+use crate::core::agent::vector_memory::{MemoryIndex, MemoryMatch, MemoryPoint};
+use crate::core::llm::types::Message;
+use crate::utils::egress::EgressClient;
+
+// Memory index kept in a Qdrant collection over its REST API, so memories
+// survive restarts and can be shared between replicas. The collection is
+// created on first write with the embedder's dimensions and cosine
+// distance; an existing collection is used as is.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QdrantConfig {
+    pub url: String,
+    pub collection: String,
+    pub api_key: Option<String>,
+}
+
+impl Default for QdrantConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://localhost:6333".to_string(),
+            collection: "vae_memory".to_string(),
+            api_key: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct QdrantResponse<T> {
+    result: T,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScoredPoint {
+    score: f32,
+    payload: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CountResult {
+    count: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScrollResult {
+    points: Vec<ScrolledPoint>,
+    next_page_offset: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScrolledPoint {
+    payload: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StoredId {
+    memory_id: String,
+    stored_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Payload {
+    memory_id: String,
+    role: String,
+    content: String,
+    stored_at: DateTime<Utc>,
+}
+
+pub struct QdrantIndex {
+    config: QdrantConfig,
+    client: EgressClient,
+    collection_ready: OnceCell<()>,
+}
+
+impl QdrantIndex {
+    pub fn new(config: QdrantConfig, client: EgressClient) -> Self {
+        Self { config, client, collection_ready: OnceCell::new() }
+    }
+
+    async fn call(&self, method: Method, path: &str, body: Value) -> Result<reqwest::Response> {
+        let url = format!("{}/collections/{}{}", self.config.url.trim_end_matches('/'), self.config.collection, path);
+        let mut request = self.client.request(method, &url)?.json(&body);
+        if let Some(key) = &self.config.api_key {
+            request = request.header("api-key", key);
+        }
+        let response = request.send().await.context("Failed to reach Qdrant")?;
+        let status = response.status();
+        if !status.is_success() {
+            let detail = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("Qdrant returned {}: {}", status, detail));
+        }
+        Ok(response)
+    }
+
+    async fn exists(&self) -> Result<bool> {
+        if self.collection_ready.get().is_some() {
+            return Ok(true);
+        }
+        let url = format!("{}/collections/{}", self.config.url.trim_end_matches('/'), self.config.collection);
+        let mut request = self.client.get(&url)?;
+        if let Some(key) = &self.config.api_key {
+            request = request.header("api-key", key);
+        }
+        Ok(request.send().await.context("Failed to reach Qdrant")?.status().is_success())
+    }
+
+    async fn ensure_collection(&self, dimensions: usize) -> Result<()> {
+        self.collection_ready.get_or_try_init(|| async {
+            if !self.exists().await? {
+                self.call(Method::PUT, "", json!({
+                    "vectors": { "size": dimensions, "distance": "Cosine" }
+                })).await.context("Failed to create Qdrant collection")?;
+                // Searches always filter on the scope
+                self.call(Method::PUT, "/index", json!({
+                    "field_name": "scope",
+                    "field_schema": "keyword"
+                })).await.context("Failed to index the scope field")?;
+            }
+            Ok::<_, anyhow::Error>(())
+        }).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MemoryIndex for QdrantIndex {
+    async fn upsert(&self, points: Vec<MemoryPoint>) -> Result<()> {
+        let Some(first) = points.first() else {
+            return Ok(());
+        };
+        self.ensure_collection(first.embedding.len()).await?;
+
+        // Qdrant ids must be UUIDs or integers; memory ids are UUIDs
+        let points: Vec<Value> = points.into_iter()
+            .map(|point| json!({
+                "id": point.id,
+                "vector": point.embedding,
+                "payload": {
+                    "memory_id": point.id,
+                    "scope": point.scope,
+                    "role": point.message.role,
+                    "content": point.message.content,
+                    "stored_at": point.stored_at,
+                },
+            }))
+            .collect();
+        self.call(Method::PUT, "/points?wait=true", json!({ "points": points })).await?;
+        Ok(())
+    }
+
+    async fn search(&self, embedding: &[f32], k: usize, scope: &str) -> Result<Vec<MemoryMatch>> {
+        if self.collection_ready.get().is_none() {
+            self.ensure_collection(embedding.len()).await?;
+        }
+        let response: QdrantResponse<Vec<ScoredPoint>> = self.call(Method::POST, "/points/search", json!({
+            "vector": embedding,
+            "limit": k,
+            "with_payload": true,
+            "filter": scope_filter(scope),
+        })).await?
+            .json()
+            .await
+            .context("Failed to parse Qdrant search response")?;
+
+        let mut matches = Vec::with_capacity(response.result.len());
+        for point in response.result {
+            let Some(payload) = point.payload else {
+                continue;
+            };
+            match serde_json::from_value::<Payload>(payload) {
+                Ok(payload) => matches.push(MemoryMatch {
+                    id: payload.memory_id,
+                    message: Message::new(&payload.role, &payload.content),
+                    score: point.score,
+                    stored_at: payload.stored_at,
+                }),
+                // Written by something else; not ours to interpret
+                Err(e) => log::warn!("Skipping Qdrant point with unexpected payload: {}", e),
+            }
+        }
+        Ok(matches)
+    }
+
+    async fn delete(&self, ids: &[String]) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        self.call(Method::POST, "/points/delete?wait=true", json!({ "points": ids })).await?;
+        Ok(())
+    }
+
+    async fn ids(&self, scope: &str) -> Result<Vec<String>> {
+        // Nothing has been written yet
+        if !self.exists().await? {
+            return Ok(Vec::new());
+        }
+        let mut stored = Vec::new();
+        let mut offset = Value::Null;
+        loop {
+            let response: QdrantResponse<ScrollResult> = self.call(Method::POST, "/points/scroll", json!({
+                "filter": scope_filter(scope),
+                "limit": 256,
+                "offset": offset,
+                "with_payload": ["memory_id", "stored_at"],
+                "with_vector": false,
+            })).await?
+                .json()
+                .await
+                .context("Failed to parse Qdrant scroll response")?;
+
+            for payload in response.result.points.into_iter().filter_map(|p| p.payload) {
+                match serde_json::from_value::<StoredId>(payload) {
+                    Ok(id) => stored.push(id),
+                    Err(e) => log::warn!("Skipping Qdrant point with unexpected payload: {}", e),
+                }
+            }
+            match response.result.next_page_offset {
+                Some(next) => offset = next,
+                None => break,
+            }
+        }
+        stored.sort_by_key(|id| id.stored_at);
+        Ok(stored.into_iter().map(|id| id.memory_id).collect())
+    }
+
+    async fn count(&self, scope: &str) -> Result<usize> {
+        if !self.exists().await? {
+            return Ok(0);
+        }
+        let response: QdrantResponse<CountResult> = self.call(Method::POST, "/points/count", json!({
+            "exact": true,
+            "filter": scope_filter(scope),
+        }))
+            .await?
+            .json()
+            .await
+            .context("Failed to parse Qdrant count response")?;
+        Ok(response.result.count)
+    }
+}
+
+fn scope_filter(scope: &str) -> Value {
+    json!({ "must": [{ "key": "scope", "match": { "value": scope } }] })
+}
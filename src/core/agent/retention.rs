@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+use crate::core::llm::{LLMTrait, types::Message};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetainedMessage {
+    pub message: Message,
+    pub stored_at: DateTime<Utc>,
+    // Overrides the configured TTL for this message; None uses the default
+    pub ttl_secs: Option<u64>,
+    // 0.0 (noise) ..= 1.0 (must keep)
+    pub importance: f32,
+    pub pinned: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScorerKind {
+    Heuristic,
+    Llm,
+}
+
+// Nested under `MemoryConfig.retention`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionConfig {
+    pub max_messages: usize,
+    pub default_ttl_secs: Option<u64>,
+    #[serde(default)]
+    pub role_ttl_secs: HashMap<String, u64>,
+    // The newest messages are always kept so the conversation stays coherent
+    pub keep_recent: usize,
+    pub importance_weight: f32,
+    pub recency_weight: f32,
+    pub scorer: ScorerKind,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            max_messages: 100,
+            default_ttl_secs: None,
+            role_ttl_secs: HashMap::new(),
+            keep_recent: 10,
+            importance_weight: 0.7,
+            recency_weight: 0.3,
+            scorer: ScorerKind::Heuristic,
+        }
+    }
+}
+
+#[async_trait]
+pub trait ImportanceScorer: Send + Sync {
+    async fn score(&self, message: &Message) -> Result<f32>;
+}
+
+pub struct HeuristicScorer;
+
+const HIGH_SIGNAL_PHRASES: [&str; 10] = [
+    "remember", "always", "never", "prefer", "my name", "important",
+    "don't", "call me", "from now on", "i am",
+];
+
+#[async_trait]
+impl ImportanceScorer for HeuristicScorer {
+    async fn score(&self, message: &Message) -> Result<f32> {
+        Ok(heuristic_importance(message))
+    }
+}
+
+pub fn heuristic_importance(message: &Message) -> f32 {
+    let content = message.content.to_lowercase();
+    let mut score: f32 = match message.role.as_str() {
+        "system" => 0.9,
+        "user" => 0.4,
+        _ => 0.3,
+    };
+
+    if HIGH_SIGNAL_PHRASES.iter().any(|p| content.contains(p)) {
+        score += 0.3;
+    }
+    if content.chars().any(|c| c.is_ascii_digit()) {
+        score += 0.1;
+    }
+    // Acknowledgements and greetings carry little information
+    if content.split_whitespace().count() <= 3 {
+        score -= 0.2;
+    }
+
+    score.clamp(0.0, 1.0)
+}
+
+pub struct LlmScorer {
+    llm: Arc<dyn LLMTrait>,
+}
+
+impl LlmScorer {
+    pub fn new(llm: Arc<dyn LLMTrait>) -> Self {
+        Self { llm }
+    }
+}
+
+#[async_trait]
+impl ImportanceScorer for LlmScorer {
+    async fn score(&self, message: &Message) -> Result<f32> {
+        let response = self.llm.complete(vec![
+            Message::new(
+                "system",
+                "Rate how important it is to remember this message for future \
+                 conversations, from 0 (irrelevant) to 10 (critical fact or \
+                 preference). Reply with a single integer.",
+            ),
+            Message::new("user", &format!("{}: {}", message.role, message.content)),
+        ]).await.context("Importance scoring request failed")?;
+
+        let rating: f32 = response.content.trim()
+            .split(|c: char| !c.is_ascii_digit())
+            .find(|s| !s.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("No rating in scorer response"))?
+            .parse()?;
+        Ok((rating / 10.0).clamp(0.0, 1.0))
+    }
+}
+
+impl RetainedMessage {
+    pub fn is_expired(&self, config: &RetentionConfig, now: DateTime<Utc>) -> bool {
+        if self.pinned {
+            return false;
+        }
+        let ttl = self.ttl_secs
+            .or_else(|| config.role_ttl_secs.get(&self.message.role).copied())
+            .or(config.default_ttl_secs);
+
+        match ttl {
+            Some(ttl) => now - self.stored_at > chrono::Duration::seconds(ttl as i64),
+            None => false,
+        }
+    }
+}
+
+// Drops expired messages, then trims to `max_messages` keeping the most
+// recent ones plus the highest scoring older ones, in original order
+pub fn apply_retention(entries: &mut Vec<RetainedMessage>, config: &RetentionConfig, now: DateTime<Utc>) {
+    entries.retain(|e| !e.is_expired(config, now));
+
+    if entries.len() <= config.max_messages {
+        return;
+    }
+
+    let recent_start = entries.len().saturating_sub(config.keep_recent.min(config.max_messages));
+    let budget = config.max_messages - (entries.len() - recent_start);
+    let oldest = entries.first().map(|e| e.stored_at).unwrap_or(now);
+    let span = (now - oldest).num_seconds().max(1) as f32;
+
+    let mut candidates: Vec<(usize, f32)> = entries[..recent_start].iter()
+        .enumerate()
+        .map(|(i, e)| {
+            if e.pinned {
+                return (i, f32::INFINITY);
+            }
+            let recency = 1.0 - (now - e.stored_at).num_seconds() as f32 / span;
+            (i, config.importance_weight * e.importance + config.recency_weight * recency.clamp(0.0, 1.0))
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut keep = vec![false; entries.len()];
+    for (i, _) in candidates.into_iter().take(budget) {
+        keep[i] = true;
+    }
+    for flag in keep.iter_mut().skip(recent_start) {
+        *flag = true;
+    }
+
+    let mut index = 0;
+    entries.retain(|_| {
+        let kept = keep[index];
+        index += 1;
+        kept
+    });
+}
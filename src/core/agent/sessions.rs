@@ -0,0 +1,765 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{Mutex, RwLock};
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use rusqlite::params;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use futures::{Stream, StreamExt};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::core::agent::canary::{Arm, CanaryRouter, SessionObservation};
+use crate::core::agent::compaction::Compactor;
+use crate::core::agent::sqlite_memory::{MemoryBackend, MemoryConfig, SqliteMemory};
+use crate::core::agent::titles::SessionTitler;
+use crate::core::crypto::Encryptor;
+use crate::core::i18n::{enforce_language, Localizer};
+use crate::core::lifecycle::{DataCategory, DataStore, DataSubject};
+use crate::core::prompt_template::{PromptContext, PromptTemplates};
+use crate::core::llm::{LLMTrait, types::{Message, Response}};
+use crate::core::llm::finetune::ConversationSource;
+use crate::core::llm::overrides::{ModelOverride, ModelOverrides};
+use crate::core::llm::tokenizer::Tokenizer;
+use crate::core::prompts::PromptLibrary;
+use crate::utils::sqlite::{self, Pool};
+
+// Independent conversations with Lilith. Each session belongs to the
+// principal that created it and has its own memory, so a client can keep
+// several threads going and come back to any of them by id. chat() runs a
+// turn against the session's own history.
+//
+// With the SQLite memory backend, sessions (owner, title, metadata) live in
+// a sessions table next to their messages, all over one connection pool,
+// and are picked up again when the manager starts.
+
+// Keeps session scopes apart from the agent's own memory in the same file
+const SCOPE_PREFIX: &str = "session:";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    pub max_sessions: usize,
+    // Sessions untouched for this long are removed by cleanup_idle()
+    pub idle_ttl_secs: Option<i64>,
+    pub memory: MemoryConfig,
+    pub system_prompt: String,
+    // Fills {{ persona }} when the system prompt comes from a template
+    pub persona: String,
+    // Most recent messages sent with each turn
+    pub context_messages: usize,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            max_sessions: 1000,
+            idle_ttl_secs: Some(7 * 86_400),
+            memory: MemoryConfig::default(),
+            system_prompt: String::from("You are Lilith, a video analytics assistant."),
+            persona: String::from("Lilith"),
+            context_messages: 50,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CreateSession {
+    pub title: Option<String>,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    pub id: String,
+    // Principal that created the session; empty for sessions recorded
+    // before owners were, which only admins can reach
+    pub owner: String,
+    pub tenant: Option<String>,
+    // Set by the client, or generated once the conversation gets going
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    pub metadata: HashMap<String, String>,
+    pub message_count: usize,
+    pub created_at: DateTime<Utc>,
+    pub last_active_at: DateTime<Utc>,
+}
+
+enum SessionMemory {
+    InMemory(RwLock<Vec<Message>>),
+    Sqlite(SqliteMemory),
+}
+
+pub struct Session {
+    info: RwLock<SessionInfo>,
+    memory: SessionMemory,
+    // One turn at a time, so concurrent requests don't interleave
+    turn: Mutex<()>,
+}
+
+impl Session {
+    fn new(info: SessionInfo, memory: SessionMemory) -> Self {
+        Self { info: RwLock::new(info), memory, turn: Mutex::new(()) }
+    }
+
+    pub async fn info(&self) -> SessionInfo {
+        self.info.read().await.clone()
+    }
+
+    pub async fn append(&self, message: Message) -> Result<()> {
+        match &self.memory {
+            SessionMemory::InMemory(messages) => messages.write().await.push(message),
+            SessionMemory::Sqlite(memory) => memory.store(message).await?,
+        }
+        let mut info = self.info.write().await;
+        info.message_count += 1;
+        info.last_active_at = Utc::now();
+        Ok(())
+    }
+
+    // The newest `limit`, oldest first
+    pub async fn messages(&self, limit: usize) -> Result<Vec<Message>> {
+        match &self.memory {
+            SessionMemory::InMemory(messages) => {
+                let messages = messages.read().await;
+                Ok(messages[messages.len().saturating_sub(limit)..].to_vec())
+            }
+            SessionMemory::Sqlite(memory) => memory.get_recent(limit).await,
+        }
+    }
+
+    // Everything, oldest first
+    pub async fn history(&self) -> Result<Vec<Message>> {
+        match &self.memory {
+            SessionMemory::InMemory(messages) => Ok(messages.read().await.clone()),
+            SessionMemory::Sqlite(memory) => {
+                Ok(memory.entries().await?.into_iter().map(|(_, message)| message).collect())
+            }
+        }
+    }
+
+    // Summarises the oldest turns once the history is over budget. The
+    // model call runs without holding the history, so a turn recorded
+    // meanwhile is kept.
+    async fn compact(&self, compactor: &Compactor) -> Result<()> {
+        let report = match &self.memory {
+            SessionMemory::Sqlite(memory) => compactor.compact_memory(memory).await?,
+            SessionMemory::InMemory(messages) => {
+                let mut history = messages.read().await.clone();
+                let Some(report) = compactor.compact(&mut history).await? else {
+                    return Ok(());
+                };
+                messages.write().await.splice(report.start..report.end, [report.summary.clone()]);
+                Some(report)
+            }
+        };
+        if let Some(report) = report {
+            let mut info = self.info.write().await;
+            info.message_count = info.message_count.saturating_sub(report.end - report.start - 1);
+        }
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<()> {
+        match &self.memory {
+            SessionMemory::InMemory(messages) => messages.write().await.clear(),
+            SessionMemory::Sqlite(memory) => memory.clear().await?,
+        }
+        Ok(())
+    }
+}
+
+// Fixed width, so timestamps compare correctly as text in SQL
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn parse_time(at: &str) -> Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(at)
+        .with_context(|| format!("Bad session timestamp {}", at))?
+        .with_timezone(&Utc))
+}
+
+pub struct SessionManager {
+    config: SessionConfig,
+    sessions: RwLock<HashMap<String, Arc<Session>>>,
+    // Shared by every session's memory and the sessions table
+    pool: Option<Pool>,
+    encryptor: Option<Arc<Encryptor>>,
+    llm: Option<Arc<dyn LLMTrait>>,
+    titler: Option<Arc<SessionTitler>>,
+    compactor: Option<Arc<Compactor>>,
+    localizer: Option<Arc<Localizer>>,
+    templates: Option<Arc<PromptTemplates>>,
+    canary: Option<SessionCanary>,
+}
+
+// What a canary needs to run a turn on a session's arm: the variant's
+// model goes through the per-call overrides, its system prompt comes from
+// the prompt library
+struct SessionCanary {
+    router: Arc<CanaryRouter>,
+    overrides: Arc<ModelOverrides>,
+    prompts: Arc<PromptLibrary>,
+}
+
+type Variant = (Arc<dyn LLMTrait>, Option<String>);
+
+impl SessionManager {
+    pub async fn new(config: SessionConfig) -> Result<Self> {
+        Self::open(config, None).await
+    }
+
+    // Messages in the SQLite backend are sealed with `encryptor`. Sessions
+    // are resumed while opening, so it can't be set afterwards.
+    pub async fn new_encrypted(config: SessionConfig, encryptor: Arc<Encryptor>) -> Result<Self> {
+        Self::open(config, Some(encryptor)).await
+    }
+
+    async fn open(config: SessionConfig, encryptor: Option<Arc<Encryptor>>) -> Result<Self> {
+        let pool = match config.memory.backend {
+            MemoryBackend::InMemory => None,
+            MemoryBackend::Sqlite => {
+                let memory = config.memory.clone();
+                Some(tokio::task::spawn_blocking(move || SqliteMemory::open_pool(&memory)).await??)
+            }
+        };
+        let manager = Self {
+            config,
+            sessions: RwLock::new(HashMap::new()),
+            pool,
+            encryptor,
+            llm: None,
+            titler: None,
+            compactor: None,
+            localizer: None,
+            templates: None,
+            canary: None,
+        };
+        manager.resume_persisted().await?;
+        Ok(manager)
+    }
+
+    // The model chat() talks to; the same instance Lilith uses. Histories
+    // are compacted with it per `memory.compaction` unless with_compactor
+    // sets another compactor.
+    pub fn with_llm(mut self, llm: Arc<dyn LLMTrait>) -> Self {
+        let compaction = &self.config.memory.compaction;
+        if self.compactor.is_none() && compaction.enabled {
+            self.compactor = Some(Arc::new(Compactor::new(compaction.clone(), llm.clone())));
+        }
+        self.llm = Some(llm);
+        self
+    }
+
+    pub fn with_compactor(mut self, compactor: Arc<Compactor>) -> Self {
+        self.compactor = Some(compactor);
+        self
+    }
+
+    pub fn with_titler(mut self, titler: Arc<SessionTitler>) -> Self {
+        self.titler = Some(titler);
+        self
+    }
+
+    // Turns are answered in the language the localizer resolves for the
+    // session, its input and its tenant
+    pub fn with_localizer(mut self, localizer: Arc<Localizer>) -> Self {
+        self.localizer = Some(localizer);
+        self
+    }
+
+    // The system prompt is rendered from the templates' system template
+    // instead of `system_prompt`, which stays the fallback if it fails
+    pub fn with_templates(mut self, templates: Arc<PromptTemplates>) -> Self {
+        self.templates = Some(templates);
+        self
+    }
+
+    // Turns run on the variant the router assigns each session, and every
+    // turn is recorded against its arm so guardrails can roll back a bad
+    // candidate. `overrides` wraps the same model as with_llm.
+    pub fn with_canary(
+        mut self,
+        router: Arc<CanaryRouter>,
+        overrides: Arc<ModelOverrides>,
+        prompts: Arc<PromptLibrary>,
+    ) -> Self {
+        self.canary = Some(SessionCanary { router, overrides, prompts });
+        self
+    }
+
+    fn memory_for(&self, id: &str) -> SessionMemory {
+        match &self.pool {
+            Some(pool) => {
+                let memory = SqliteMemory::with_pool(
+                    pool.clone(),
+                    &self.config.memory,
+                    &format!("{}{}", SCOPE_PREFIX, id),
+                );
+                SessionMemory::Sqlite(match &self.encryptor {
+                    Some(encryptor) => memory.with_encryption(encryptor.clone()),
+                    None => memory,
+                })
+            }
+            None => SessionMemory::InMemory(RwLock::new(Vec::new())),
+        }
+    }
+
+    // Loads the sessions table. Idle sessions past their TTL are removed
+    // first; if more than max_sessions remain, the most recently active are
+    // loaded and the rest stay on disk until the limit allows them back.
+    async fn resume_persisted(&self) -> Result<()> {
+        let Some(pool) = &self.pool else {
+            return Ok(());
+        };
+        let cutoff = self.config.idle_ttl_secs.map(|ttl| timestamp(Utc::now() - Duration::seconds(ttl)));
+        let limit = i64::try_from(self.config.max_sessions).unwrap_or(i64::MAX);
+
+        let (rows, total) = sqlite::run(pool, move |conn| {
+            if let Some(cutoff) = cutoff {
+                let tx = conn.transaction()?;
+                tx.execute(
+                    "DELETE FROM messages WHERE scope IN
+                     (SELECT 'session:' || id FROM sessions WHERE last_active_at < ?1)",
+                    params![cutoff],
+                )?;
+                let expired = tx.execute("DELETE FROM sessions WHERE last_active_at < ?1", params![cutoff])?;
+                tx.commit()?;
+                if expired > 0 {
+                    log::info!("Removed {} idle session(s) on startup", expired);
+                }
+            }
+
+            let total: i64 = conn.query_row("SELECT COUNT(*) FROM sessions", [], |row| row.get(0))?;
+            let mut statement = conn.prepare(
+                "SELECT s.id, s.owner, s.tenant, s.title, s.metadata, s.created_at, s.last_active_at,
+                        (SELECT COUNT(*) FROM messages m WHERE m.scope = 'session:' || s.id)
+                 FROM sessions s ORDER BY s.last_active_at DESC LIMIT ?1",
+            )?;
+            let rows = statement.query_map(params![limit], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, String>(6)?,
+                    row.get::<_, i64>(7)?,
+                ))
+            })?;
+            Ok((rows.collect::<rusqlite::Result<Vec<_>>>()?, total as usize))
+        }).await?;
+
+        let mut sessions = self.sessions.write().await;
+        for (id, owner, tenant, title, metadata, created_at, last_active_at, count) in rows {
+            let info = SessionInfo {
+                id: id.clone(),
+                owner,
+                tenant,
+                title,
+                summary: None,
+                metadata: serde_json::from_str(&metadata).unwrap_or_default(),
+                message_count: count as usize,
+                created_at: parse_time(&created_at)?,
+                last_active_at: parse_time(&last_active_at)?,
+            };
+            let memory = self.memory_for(&id);
+            sessions.insert(id, Arc::new(Session::new(info, memory)));
+        }
+        if total > sessions.len() {
+            log::warn!(
+                "{} sessions on disk but max_sessions is {}; the {} least recently active were not loaded",
+                total, self.config.max_sessions, total - sessions.len()
+            );
+        }
+        if !sessions.is_empty() {
+            log::info!("Resumed {} persisted session(s)", sessions.len());
+        }
+        Ok(())
+    }
+
+    pub async fn create(&self, owner: &str, tenant: Option<&str>, request: CreateSession) -> Result<SessionInfo> {
+        if self.sessions.read().await.len() >= self.config.max_sessions {
+            self.cleanup_idle().await?;
+        }
+
+        let mut sessions = self.sessions.write().await;
+        if sessions.len() >= self.config.max_sessions {
+            return Err(anyhow::anyhow!("Session limit of {} reached", self.config.max_sessions));
+        }
+
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let info = SessionInfo {
+            id: id.clone(),
+            owner: owner.to_string(),
+            tenant: tenant.map(str::to_string),
+            title: request.title.filter(|t| !t.trim().is_empty()),
+            summary: None,
+            metadata: request.metadata,
+            message_count: 0,
+            created_at: now,
+            last_active_at: now,
+        };
+
+        if let Some(pool) = &self.pool {
+            let row = info.clone();
+            let metadata = serde_json::to_string(&row.metadata)?;
+            sqlite::run(pool, move |conn| {
+                conn.execute(
+                    "INSERT INTO sessions (id, owner, tenant, title, metadata, created_at, last_active_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+                    params![row.id, row.owner, row.tenant, row.title, metadata, timestamp(row.created_at)],
+                ).context("Failed to store session")?;
+                Ok(())
+            }).await?;
+        }
+
+        let memory = self.memory_for(&id);
+        sessions.insert(id, Arc::new(Session::new(info.clone(), memory)));
+        Ok(info)
+    }
+
+    pub async fn get(&self, id: &str) -> Option<Arc<Session>> {
+        self.sessions.read().await.get(id).cloned()
+    }
+
+    pub async fn info(&self, id: &str) -> Option<SessionInfo> {
+        let session = self.get(id).await?;
+        Some(self.with_digest(session.info().await).await)
+    }
+
+    // Generated titles fill in where the client didn't set one
+    async fn with_digest(&self, mut info: SessionInfo) -> SessionInfo {
+        let Some(titler) = &self.titler else {
+            return info;
+        };
+        if let Some(digest) = titler.get(&info.id).await {
+            info.title.get_or_insert(digest.title);
+            info.summary = Some(digest.summary).filter(|s| !s.is_empty());
+        }
+        info
+    }
+
+    // Most recently active first; None lists every owner's sessions
+    pub async fn list(&self, owner: Option<&str>) -> Vec<SessionInfo> {
+        let sessions: Vec<Arc<Session>> = self.sessions.read().await.values().cloned().collect();
+        let mut infos = Vec::with_capacity(sessions.len());
+        for session in sessions {
+            let info = session.info().await;
+            if owner.is_some_and(|owner| owner != info.owner) {
+                continue;
+            }
+            infos.push(self.with_digest(info).await);
+        }
+        infos.sort_by(|a, b| b.last_active_at.cmp(&a.last_active_at));
+        infos
+    }
+
+    pub async fn messages(&self, id: &str, limit: usize) -> Result<Option<Vec<Message>>> {
+        match self.get(id).await {
+            Some(session) => Ok(Some(session.messages(limit).await?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn history(&self, id: &str) -> Result<Option<Vec<Message>>> {
+        match self.get(id).await {
+            Some(session) => Ok(Some(session.history().await?)),
+            None => Ok(None),
+        }
+    }
+
+    // One turn: the session's recent history goes to the model with the
+    // new message, and both sides are recorded
+    pub async fn chat(&self, id: &str, content: &str) -> Result<Response> {
+        let llm = self.llm.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Session chat is not configured"))?;
+        let session = self.get(id).await
+            .ok_or_else(|| anyhow::anyhow!("Session not found: {}", id))?;
+        let _turn = session.turn.lock().await;
+
+        let user = Message::new("user", content);
+        let started = Instant::now();
+        let (arm, variant) = self.variant(id, llm).await;
+        let response = async {
+            let (llm, system) = variant?;
+            let messages = self.prompt(&session, &user, system).await?;
+            llm.complete(messages).await
+        }.await;
+        let usage = response.as_ref().ok().map(|r| (r.usage.prompt_tokens, r.usage.completion_tokens));
+        self.observe(arm, started, usage).await;
+
+        let response = response?;
+        self.record_turn(id, user, Message::new("assistant", &response.content)).await?;
+        Ok(response)
+    }
+
+    // chat() with the reply streamed as text deltas. The turn is recorded
+    // once the model finishes; a cancelled stream records nothing, so the
+    // history never holds half an answer.
+    pub fn chat_stream(
+        self: &Arc<Self>,
+        id: &str,
+        content: &str,
+        cancel: CancellationToken,
+    ) -> impl Stream<Item = Result<String>> + Send + 'static {
+        self.chat_stream_metered(id, content, cancel, Arc::default())
+    }
+
+    // chat_stream() that adds the tokens it uses to `used` as it goes: the
+    // prompt once the model starts answering, then each delta. A stream the
+    // client abandons has still been charged for what it consumed.
+    pub fn chat_stream_metered(
+        self: &Arc<Self>,
+        id: &str,
+        content: &str,
+        cancel: CancellationToken,
+        used: Arc<AtomicU64>,
+    ) -> impl Stream<Item = Result<String>> + Send + 'static {
+        let (manager, id, content) = (self.clone(), id.to_string(), content.to_string());
+        async_stream::try_stream! {
+            let llm = manager.llm.clone()
+                .ok_or_else(|| anyhow::anyhow!("Session chat is not configured"))?;
+            let session = manager.get(&id).await
+                .ok_or_else(|| anyhow::anyhow!("Session not found: {}", id))?;
+            let _turn = session.turn.lock().await;
+
+            let user = Message::new("user", &content);
+            let started = Instant::now();
+            let (arm, variant) = manager.variant(&id, &llm).await;
+            let opened = async {
+                let (llm, system) = variant?;
+                let messages = manager.prompt(&session, &user, system).await?;
+                let tokenizer = Tokenizer::for_model(llm.get_model());
+                let prompt_tokens = tokenizer.count_tokens(&messages) as u32;
+                let stream = llm.complete_stream(messages, cancel.clone()).await?;
+                Ok::<_, anyhow::Error>((stream, tokenizer, prompt_tokens))
+            }.await;
+            let (mut stream, tokenizer, prompt_tokens) = match opened {
+                Ok(opened) => opened,
+                Err(e) => {
+                    manager.observe(arm, started, None).await;
+                    Err(e)?
+                }
+            };
+            used.fetch_add(prompt_tokens as u64, Ordering::Relaxed);
+
+            let mut reply = String::new();
+            while let Some(chunk) = stream.next().await {
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        manager.observe(arm, started, None).await;
+                        Err(e)?
+                    }
+                };
+                used.fetch_add(tokenizer.count(&chunk.content) as u64, Ordering::Relaxed);
+                reply.push_str(&chunk.content);
+                yield chunk.content;
+            }
+            if !cancel.is_cancelled() {
+                // Streams report no usage, so the canary's cost comparison
+                // uses counted tokens
+                manager.observe(arm, started, Some((prompt_tokens, tokenizer.count(&reply) as u32))).await;
+                manager.record_turn(&id, user, Message::new("assistant", &reply)).await?;
+            }
+        }
+    }
+
+    // The model and system prompt for a session's turn. With a canary they
+    // come from the session's arm; otherwise the shared model answers with
+    // the configured prompt.
+    async fn variant(&self, id: &str, llm: &Arc<dyn LLMTrait>) -> (Option<Arm>, Result<Variant>) {
+        let Some(canary) = &self.canary else {
+            return (None, Ok((llm.clone(), None)));
+        };
+        let (arm, variant) = canary.router.route(id).await;
+        let resolved = async {
+            let model = ModelOverride { model: Some(variant.model.clone()), ..ModelOverride::default() };
+            let llm = canary.overrides.resolve(&model)
+                .with_context(|| format!("Canary {:?} arm can't use model {}", arm, variant.model))?;
+            let system = canary.prompts.resolve(&variant.prompt).await?;
+            Ok::<_, anyhow::Error>((llm, Some(system)))
+        }.await;
+        (Some(arm), resolved)
+    }
+
+    // A turn counts as one session for the canary comparison; `usage` is
+    // None when the turn failed
+    async fn observe(&self, arm: Option<Arm>, started: Instant, usage: Option<(u32, u32)>) {
+        let (Some(canary), Some(arm)) = (&self.canary, arm) else {
+            return;
+        };
+        let (prompt_tokens, completion_tokens) = usage.unwrap_or_default();
+        canary.router.record(arm, SessionObservation {
+            latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+            prompt_tokens,
+            completion_tokens,
+            quality: None,
+            error: usage.is_none(),
+        }).await;
+    }
+
+    // System prompt, recent history and the new message. A canary
+    // variant's prompt replaces the configured or templated one.
+    async fn prompt(&self, session: &Session, user: &Message, system: Option<String>) -> Result<Vec<Message>> {
+        let language = match &self.localizer {
+            Some(localizer) => {
+                let info = session.info().await;
+                Some(localizer.resolve(Some(&info.id), info.tenant.as_deref(), &user.content).await)
+            }
+            None => None,
+        };
+        let system = match (system, &self.templates) {
+            (Some(system), _) => system,
+            (None, Some(templates)) => {
+                let mut context = PromptContext::new(&self.config.persona);
+                if let Some(language) = &language {
+                    context = context.with_language(language);
+                }
+                templates.render_system(&context).await.unwrap_or_else(|e| {
+                    log::warn!("Falling back to the configured system prompt: {:#}", e);
+                    self.config.system_prompt.clone()
+                })
+            }
+            (None, None) => self.config.system_prompt.clone(),
+        };
+
+        let mut messages = vec![Message::new("system", &system)];
+        messages.extend(session.messages(self.config.context_messages).await?);
+        messages.push(user.clone());
+        if let Some(language) = &language {
+            enforce_language(&mut messages, language);
+        }
+        Ok(messages)
+    }
+
+    // Stores one exchange, lets the titler know the conversation moved and
+    // compacts the history if it has grown past its budget. A failed
+    // compaction leaves the history as it was and is retried next turn.
+    pub async fn record_turn(&self, id: &str, user: Message, reply: Message) -> Result<()> {
+        let session = self.get(id).await
+            .ok_or_else(|| anyhow::anyhow!("Session not found: {}", id))?;
+        session.append(user).await?;
+        session.append(reply).await?;
+
+        if let Some(pool) = &self.pool {
+            let (id, at) = (id.to_string(), timestamp(Utc::now()));
+            sqlite::run(pool, move |conn| {
+                conn.execute("UPDATE sessions SET last_active_at = ?2 WHERE id = ?1", params![id, at])?;
+                Ok(())
+            }).await?;
+        }
+
+        if let Some(titler) = &self.titler {
+            titler.record_turn(id, &session.history().await?).await;
+        }
+        if let Some(compactor) = &self.compactor {
+            if let Err(e) = session.compact(compactor).await {
+                log::warn!("Failed to compact session {}: {}", id, e);
+            }
+        }
+        Ok(())
+    }
+
+    // Removes the session and its messages; false when it didn't exist
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        let Some(session) = self.sessions.write().await.remove(id) else {
+            return Ok(false);
+        };
+        session.clear().await?;
+        if let Some(pool) = &self.pool {
+            let id = id.to_string();
+            sqlite::run(pool, move |conn| {
+                conn.execute("DELETE FROM sessions WHERE id = ?1", params![id])?;
+                Ok(())
+            }).await?;
+        }
+        if let Some(titler) = &self.titler {
+            titler.remove(id).await;
+        }
+        if let Some(localizer) = &self.localizer {
+            localizer.forget_session(id).await;
+        }
+        Ok(true)
+    }
+
+    // Returns how many sessions were removed
+    pub async fn cleanup_idle(&self) -> Result<usize> {
+        let Some(ttl) = self.config.idle_ttl_secs else {
+            return Ok(0);
+        };
+        let cutoff = Utc::now() - Duration::seconds(ttl);
+        let mut idle = Vec::new();
+        for (id, session) in self.sessions.read().await.iter() {
+            if session.info.read().await.last_active_at < cutoff {
+                idle.push(id.clone());
+            }
+        }
+        for id in &idle {
+            self.delete(id).await?;
+        }
+        Ok(idle.len())
+    }
+}
+
+impl SessionManager {
+    async fn subject_sessions(&self, subject: &DataSubject) -> Vec<SessionInfo> {
+        self.list(None).await
+            .into_iter()
+            .filter(|info| match subject {
+                DataSubject::Session(id) => &info.id == id,
+                DataSubject::Identity(owner) => &info.owner == owner,
+                DataSubject::Tenant(tenant) => info.tenant.as_ref() == Some(tenant),
+            })
+            .collect()
+    }
+}
+
+// Sessions are attributed to their id, their owner and the owner's tenant
+// Training data for fine-tunes: a session's full history
+#[async_trait]
+impl ConversationSource for SessionManager {
+    async fn conversation(&self, session_id: &str, owner: Option<&str>) -> Result<Vec<Message>> {
+        let session = match self.get(session_id).await {
+            Some(session) if owner.is_none_or(|owner| owner == session.info().await.owner) => session,
+            _ => return Err(anyhow::anyhow!("Session not found: {}", session_id)),
+        };
+        session.history().await
+    }
+}
+
+#[async_trait]
+impl DataStore for SessionManager {
+    fn category(&self) -> DataCategory {
+        DataCategory::Conversations
+    }
+
+    async fn export(&self, subject: &DataSubject) -> Result<serde_json::Value> {
+        let mut sessions = Vec::new();
+        for info in self.subject_sessions(subject).await {
+            let messages = self.history(&info.id).await?.unwrap_or_default();
+            sessions.push(serde_json::json!({ "session": info, "messages": messages }));
+        }
+        Ok(serde_json::Value::Array(sessions))
+    }
+
+    // Counts sessions and messages removed
+    async fn delete(&self, subject: &DataSubject) -> Result<u64> {
+        let mut records = 0;
+        for info in self.subject_sessions(subject).await {
+            let messages = self.history(&info.id).await?.map_or(0, |m| m.len());
+            if SessionManager::delete(self, &info.id).await? {
+                records += 1 + messages as u64;
+            }
+        }
+        Ok(records)
+    }
+}
@@ -0,0 +1,311 @@
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{Result, Context};
+use rusqlite::{params, OptionalExtension};
+use serde::{Serialize, Deserialize};
+use chrono::Utc;
+
+use crate::core::agent::compaction::CompactionConfig;
+use crate::core::crypto::Encryptor;
+use crate::core::llm::types::Message;
+use crate::utils::sqlite::{self, Pool};
+
+// Conversation memory in a SQLite file, so it survives restarts. Same
+// store/get_recent/cleanup contract as the in-memory backend, but async:
+// queries run on the blocking pool. Messages are kept per scope (a session
+// or agent id) in one table, and every scope over the same file shares one
+// connection pool. The database runs in WAL mode so readers aren't blocked
+// while the agent writes.
+//
+//   memory:
+//     backend: sqlite
+//     path: data/memory.db
+//     max_messages: 1000
+//     compaction:
+//       max_history_tokens: 6000
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryBackend {
+    InMemory,
+    Sqlite,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryConfig {
+    pub backend: MemoryBackend,
+    pub path: String,
+    // Per scope; cleanup() drops the oldest beyond this
+    pub max_messages: usize,
+    pub busy_timeout_ms: u64,
+    pub max_connections: u32,
+    pub compaction: CompactionConfig,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            backend: MemoryBackend::InMemory,
+            path: "data/memory.db".to_string(),
+            max_messages: 1000,
+            busy_timeout_ms: 5000,
+            max_connections: 4,
+            compaction: CompactionConfig::default(),
+        }
+    }
+}
+
+const MIGRATIONS: &[&str] = &[
+    // payload is the whole message as JSON, so fields beyond role and
+    // content survive
+    "CREATE TABLE messages (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        scope TEXT NOT NULL,
+        role TEXT NOT NULL,
+        content TEXT NOT NULL,
+        stored_at TEXT NOT NULL,
+        payload TEXT
+    );
+    CREATE INDEX messages_scope_id ON messages (scope, id);",
+    // Conversations managed by SessionManager; their messages are the
+    // "session:<id>" scopes. Sessions from before this table existed have
+    // no recorded owner, so only admins can reach them.
+    "CREATE TABLE sessions (
+        id TEXT PRIMARY KEY,
+        owner TEXT NOT NULL,
+        tenant TEXT,
+        title TEXT,
+        metadata TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        last_active_at TEXT NOT NULL
+    );
+    CREATE INDEX sessions_owner ON sessions (owner);
+    INSERT INTO sessions (id, owner, metadata, created_at, last_active_at)
+        SELECT substr(scope, 9), '', '{}', MIN(stored_at), MAX(stored_at)
+        FROM messages WHERE substr(scope, 1, 8) = 'session:' GROUP BY scope;",
+];
+
+#[derive(Clone)]
+pub struct SqliteMemory {
+    pool: Pool,
+    scope: String,
+    max_messages: usize,
+    // Seals content and payload; rows written before it was set stay readable
+    encryptor: Option<Arc<Encryptor>>,
+}
+
+type Row = (String, String, Option<String>);
+
+impl SqliteMemory {
+    // Opens and migrates the file; share the pool between scopes with
+    // with_pool() or with_scope()
+    pub fn open_pool(config: &MemoryConfig) -> Result<Pool> {
+        let pool = sqlite::pool(&config.path, Duration::from_millis(config.busy_timeout_ms), config.max_connections)?;
+        sqlite::migrate(&mut pool.get()?, "Memory", MIGRATIONS)?;
+        Ok(pool)
+    }
+
+    pub fn open(config: &MemoryConfig, scope: &str) -> Result<Self> {
+        Ok(Self::with_pool(Self::open_pool(config)?, config, scope))
+    }
+
+    pub fn open_in_memory(config: &MemoryConfig, scope: &str) -> Result<Self> {
+        let pool = sqlite::pool_in_memory()?;
+        sqlite::migrate(&mut pool.get()?, "Memory", MIGRATIONS)?;
+        Ok(Self::with_pool(pool, config, scope))
+    }
+
+    // The pool must come from open_pool(), so the schema is in place
+    pub fn with_pool(pool: Pool, config: &MemoryConfig, scope: &str) -> Self {
+        Self {
+            pool,
+            scope: scope.to_string(),
+            max_messages: config.max_messages,
+            encryptor: None,
+        }
+    }
+
+    pub fn with_encryption(mut self, encryptor: Arc<Encryptor>) -> Self {
+        self.encryptor = Some(encryptor);
+        self
+    }
+
+    // Another scope over the same pool, e.g. for a new session
+    pub fn with_scope(&self, scope: &str) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            scope: scope.to_string(),
+            max_messages: self.max_messages,
+            encryptor: self.encryptor.clone(),
+        }
+    }
+
+    pub fn scope(&self) -> &str {
+        &self.scope
+    }
+
+    // Content and payload as written to the table
+    async fn seal(&self, message: &Message) -> Result<(String, String)> {
+        let payload = serde_json::to_string(message)?;
+        match &self.encryptor {
+            Some(encryptor) => Ok((
+                encryptor.encrypt_text(&message.content).await?,
+                encryptor.encrypt_text(&payload).await?,
+            )),
+            None => Ok((message.content.clone(), payload)),
+        }
+    }
+
+    async fn decode(&self, (role, content, payload): Row) -> Result<Message> {
+        let (content, payload) = match &self.encryptor {
+            Some(encryptor) => (
+                encryptor.decrypt_text(content).await?,
+                match payload {
+                    Some(payload) => Some(encryptor.decrypt_text(payload).await?),
+                    None => None,
+                },
+            ),
+            None => (content, payload),
+        };
+        Ok(payload
+            .and_then(|p| serde_json::from_str::<Message>(&p).ok())
+            .unwrap_or_else(|| Message::new(&role, &content)))
+    }
+
+    pub async fn store(&self, message: Message) -> Result<()> {
+        let scope = self.scope.clone();
+        let (content, payload) = self.seal(&message).await?;
+        sqlite::run(&self.pool, move |conn| {
+            conn.execute(
+                "INSERT INTO messages (scope, role, content, stored_at, payload) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![scope, message.role, content, Utc::now().to_rfc3339(), payload],
+            ).context("Failed to store message")?;
+            Ok(())
+        }).await
+    }
+
+    // Oldest first
+    pub async fn get_recent(&self, count: usize) -> Result<Vec<Message>> {
+        // SQLite takes a signed limit
+        let limit = i64::try_from(count).unwrap_or(i64::MAX);
+        let scope = self.scope.clone();
+        let rows: Vec<Row> = sqlite::run(&self.pool, move |conn| {
+            let mut statement = conn.prepare_cached(
+                "SELECT role, content, payload FROM messages WHERE scope = ?1 ORDER BY id DESC LIMIT ?2",
+            )?;
+            let rows = statement.query_map(params![scope, limit], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+            Ok(rows.collect::<rusqlite::Result<_>>()?)
+        }).await?;
+
+        let mut messages = Vec::with_capacity(rows.len());
+        for row in rows.into_iter().rev() {
+            messages.push(self.decode(row).await?);
+        }
+        Ok(messages)
+    }
+
+    pub async fn count(&self) -> Result<usize> {
+        let scope = self.scope.clone();
+        sqlite::run(&self.pool, move |conn| {
+            let count: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM messages WHERE scope = ?1",
+                params![scope],
+                |row| row.get(0),
+            )?;
+            Ok(count as usize)
+        }).await
+    }
+
+    pub async fn cleanup(&self) -> Result<()> {
+        if self.max_messages == 0 {
+            return self.clear().await;
+        }
+        let scope = self.scope.clone();
+        let offset = (self.max_messages - 1) as i64;
+        sqlite::run(&self.pool, move |conn| {
+            // Id of the oldest message to keep
+            let cutoff: Option<i64> = conn.query_row(
+                "SELECT id FROM messages WHERE scope = ?1 ORDER BY id DESC LIMIT 1 OFFSET ?2",
+                params![scope, offset],
+                |row| row.get(0),
+            ).optional()?;
+            if let Some(cutoff) = cutoff {
+                conn.execute("DELETE FROM messages WHERE scope = ?1 AND id < ?2", params![scope, cutoff])?;
+            }
+            Ok(())
+        }).await
+    }
+
+    // Every message in the scope with its row id, oldest first
+    pub async fn entries(&self) -> Result<Vec<(i64, Message)>> {
+        let scope = self.scope.clone();
+        let rows: Vec<(i64, Row)> = sqlite::run(&self.pool, move |conn| {
+            let mut statement = conn.prepare_cached(
+                "SELECT id, role, content, payload FROM messages WHERE scope = ?1 ORDER BY id",
+            )?;
+            let rows = statement.query_map(params![scope], |row| {
+                Ok((row.get(0)?, (row.get(1)?, row.get(2)?, row.get(3)?)))
+            })?;
+            Ok(rows.collect::<rusqlite::Result<_>>()?)
+        }).await?;
+
+        let mut entries = Vec::with_capacity(rows.len());
+        for (id, row) in rows {
+            entries.push((id, self.decode(row).await?));
+        }
+        Ok(entries)
+    }
+
+    // For compaction: swaps a consecutive run of rows, as returned by
+    // entries(), for one summary that takes the first one's place. Fails
+    // without changes if any of them has gone in the meantime.
+    pub async fn replace_rows(&self, ids: &[i64], summary: Message) -> Result<()> {
+        let (Some(&first), Some(&last)) = (ids.first(), ids.last()) else {
+            return Ok(());
+        };
+        let expected = ids.len();
+        let scope = self.scope.clone();
+        let (content, payload) = self.seal(&summary).await?;
+        sqlite::run(&self.pool, move |conn| {
+            let tx = conn.transaction()?;
+            let deleted = tx.execute(
+                "DELETE FROM messages WHERE scope = ?1 AND id BETWEEN ?2 AND ?3",
+                params![scope, first, last],
+            )?;
+            if deleted != expected {
+                return Err(anyhow::anyhow!(
+                    "Memory changed during compaction ({} of {} rows left)", deleted, expected
+                ));
+            }
+            tx.execute(
+                "INSERT INTO messages (id, scope, role, content, stored_at, payload) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![first, scope, summary.role, content, Utc::now().to_rfc3339(), payload],
+            )?;
+            tx.commit().context("Failed to replace compacted messages")?;
+            Ok(())
+        }).await
+    }
+
+    pub async fn clear(&self) -> Result<()> {
+        let scope = self.scope.clone();
+        sqlite::run(&self.pool, move |conn| {
+            conn.execute("DELETE FROM messages WHERE scope = ?1", params![scope])?;
+            Ok(())
+        }).await
+    }
+
+    pub async fn is_within_limits(&self) -> bool {
+        self.count().await.is_ok_and(|count| count <= self.max_messages)
+    }
+
+    // Folds the WAL back into the main file; worth calling on shutdown
+    pub async fn checkpoint(&self) -> Result<()> {
+        sqlite::run(&self.pool, |conn| {
+            conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+            Ok(())
+        }).await
+    }
+}
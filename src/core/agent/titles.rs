@@ -0,0 +1,251 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+use crate::core::llm::{LLMTrait, types::Message};
+use crate::core::llm::structured::parse_reply;
+
+// Short titles and summaries for conversations, for history sidebars. The
+// agent reports each finished turn; once a session has min_turns user
+// messages a title is generated in the background, and refreshed every
+// refresh_every_turns after that as the conversation drifts. Digests are
+// written to digests_file so sidebars keep their titles across restarts.
+// Digests carry no owner, so they are only served attached to a session by
+// SessionManager, which scopes listings to the caller.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TitleConfig {
+    pub min_turns: usize,
+    // None keeps the first title
+    pub refresh_every_turns: Option<usize>,
+    pub max_title_chars: usize,
+    // Only the start and end of long transcripts are sent
+    pub max_transcript_chars: usize,
+    pub persist: bool,
+    pub digests_file: String,
+}
+
+impl Default for TitleConfig {
+    fn default() -> Self {
+        Self {
+            min_turns: 3,
+            refresh_every_turns: Some(10),
+            max_title_chars: 60,
+            max_transcript_chars: 6000,
+            persist: true,
+            digests_file: String::from("data/session_titles.json"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionDigest {
+    pub session_id: String,
+    pub title: String,
+    pub summary: String,
+    // User turns covered by this digest
+    pub turns: usize,
+    // False when the model failed and the title is the first question
+    pub llm_generated: bool,
+    pub generated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TitleOutput {
+    title: String,
+    summary: String,
+}
+
+const TITLE_PROMPT: &str = "Give this conversation between a user and Lilith, a video analytics \
+    assistant, a title and a summary. Reply with JSON only: {\"title\": at most eight words, no \
+    quotes or trailing punctuation, \"summary\": one or two sentences on what the user wanted \
+    and what came of it}. Write both in the language of the conversation.";
+
+fn clip(text: &str, max_chars: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...", text[..end].trim_end()),
+        None => text,
+    }
+}
+
+fn transcript(messages: &[Message], max_chars: usize) -> String {
+    let lines: Vec<String> = messages.iter()
+        .filter(|m| m.role != "system")
+        .map(|m| format!("{}: {}", m.role, m.content))
+        .collect();
+    let full = lines.join("\n");
+    if full.len() <= max_chars {
+        return full;
+    }
+    // The opening says what the conversation is about, the end where it went
+    let half = max_chars / 2;
+    let mut head = half;
+    while !full.is_char_boundary(head) {
+        head -= 1;
+    }
+    let mut tail = full.len() - half;
+    while !full.is_char_boundary(tail) {
+        tail += 1;
+    }
+    format!("{}\n[...]\n{}", &full[..head], &full[tail..])
+}
+
+pub fn user_turns(messages: &[Message]) -> usize {
+    messages.iter().filter(|m| m.role == "user").count()
+}
+
+pub struct SessionTitler {
+    config: TitleConfig,
+    llm: Arc<dyn LLMTrait>,
+    digests: Arc<RwLock<HashMap<String, SessionDigest>>>,
+    pending: Arc<RwLock<HashSet<String>>>,
+}
+
+impl SessionTitler {
+    pub async fn new(config: TitleConfig, llm: Arc<dyn LLMTrait>) -> Result<Self> {
+        let digests = if config.persist && tokio::fs::try_exists(&config.digests_file).await? {
+            let contents = tokio::fs::read_to_string(&config.digests_file).await
+                .context("Failed to read session titles")?;
+            let list: Vec<SessionDigest> = serde_json::from_str(&contents)
+                .context("Failed to parse session titles")?;
+            list.into_iter().map(|d| (d.session_id.clone(), d)).collect()
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            config,
+            llm,
+            digests: Arc::new(RwLock::new(digests)),
+            pending: Arc::new(RwLock::new(HashSet::new())),
+        })
+    }
+
+    pub async fn get(&self, session_id: &str) -> Option<SessionDigest> {
+        self.digests.read().await.get(session_id).cloned()
+    }
+
+    // Also drops a title still being generated, so a deleted session's
+    // digest can't land after it is gone
+    pub async fn remove(&self, session_id: &str) {
+        let mut pending = self.pending.write().await;
+        pending.remove(session_id);
+        let removed = self.digests.write().await.remove(session_id).is_some();
+        drop(pending);
+        if removed {
+            self.persist_logged().await;
+        }
+    }
+
+    // Written to a temporary file and renamed, so a crash mid-write can't
+    // leave a truncated file behind
+    async fn persist(&self) -> Result<()> {
+        if !self.config.persist {
+            return Ok(());
+        }
+        let list: Vec<SessionDigest> = self.digests.read().await.values().cloned().collect();
+        let serialized = serde_json::to_string_pretty(&list)?;
+        let path = std::path::Path::new(&self.config.digests_file);
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let temp = path.with_extension("json.tmp");
+        tokio::fs::write(&temp, serialized).await.context("Failed to persist session titles")?;
+        tokio::fs::rename(&temp, path).await.context("Failed to persist session titles")?;
+        Ok(())
+    }
+
+    // A failed write costs the titles on restart, not the turn
+    async fn persist_logged(&self) {
+        if let Err(e) = self.persist().await {
+            log::warn!("{}", e);
+        }
+    }
+
+    async fn is_due(&self, session_id: &str, turns: usize) -> bool {
+        if turns < self.config.min_turns.max(1) {
+            return false;
+        }
+        match self.digests.read().await.get(session_id) {
+            None => true,
+            Some(digest) => self.config.refresh_every_turns
+                .is_some_and(|every| turns >= digest.turns + every.max(1)),
+        }
+    }
+
+    // Called after each turn with the session's full history. Generation
+    // runs in the background so the reply isn't held up; a session already
+    // being titled is skipped.
+    pub async fn record_turn(self: &Arc<Self>, session_id: &str, messages: &[Message]) {
+        let turns = user_turns(messages);
+        if !self.is_due(session_id, turns).await {
+            return;
+        }
+        if !self.pending.write().await.insert(session_id.to_string()) {
+            return;
+        }
+
+        let titler = self.clone();
+        let session_id = session_id.to_string();
+        let messages = messages.to_vec();
+        tokio::spawn(async move {
+            let digest = titler.generate(&session_id, &messages).await;
+            let mut pending = titler.pending.write().await;
+            if !pending.remove(&session_id) {
+                return;
+            }
+            titler.digests.write().await.insert(session_id, digest);
+            drop(pending);
+            titler.persist_logged().await;
+        });
+    }
+
+    // Never fails: without the model the first question stands in as the
+    // title, so the sidebar always has something to show
+    pub async fn generate(&self, session_id: &str, messages: &[Message]) -> SessionDigest {
+        let turns = user_turns(messages);
+        let output = match self.ask(messages).await {
+            Ok(output) => Some(output),
+            Err(e) => {
+                log::warn!("Failed to title session {}: {}", session_id, e);
+                None
+            }
+        };
+
+        let (title, summary, llm_generated) = match output {
+            Some(output) => (output.title, output.summary, true),
+            None => {
+                let first = messages.iter().find(|m| m.role == "user").map(|m| m.content.as_str()).unwrap_or("");
+                (first.to_string(), String::new(), false)
+            }
+        };
+        let title = clip(title.trim().trim_matches('"').trim_end_matches('.'), self.config.max_title_chars);
+
+        SessionDigest {
+            session_id: session_id.to_string(),
+            title,
+            summary: summary.trim().to_string(),
+            turns,
+            llm_generated,
+            generated_at: Utc::now(),
+        }
+    }
+
+    async fn ask(&self, messages: &[Message]) -> Result<TitleOutput> {
+        let response = self.llm.complete(vec![
+            Message::new("system", TITLE_PROMPT),
+            Message::new("user", &transcript(messages, self.config.max_transcript_chars)),
+        ]).await.context("Title request failed")?;
+
+        let output: TitleOutput = parse_reply(&response.content, "title output")?;
+        if output.title.trim().is_empty() {
+            return Err(anyhow::anyhow!("Model returned an empty title"));
+        }
+        Ok(output)
+    }
+}
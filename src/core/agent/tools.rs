@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::Value;
+
+// A capability the agent can invoke by name with JSON arguments
+#[async_trait]
+pub trait Tool: Send + Sync {
+    fn name(&self) -> String;
+    fn description(&self) -> String;
+    // JSON schema for the arguments object
+    fn parameters(&self) -> Value;
+    async fn call(&self, args: Value) -> Result<Value>;
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: Value,
+}
+
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: HashMap<String, Arc<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, tool: Arc<dyn Tool>) {
+        self.tools.insert(tool.name(), tool);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools.get(name).cloned()
+    }
+
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        let mut definitions: Vec<_> = self.tools.values()
+            .map(|t| ToolDefinition {
+                name: t.name(),
+                description: t.description(),
+                parameters: t.parameters(),
+            })
+            .collect();
+        definitions.sort_by(|a, b| a.name.cmp(&b.name));
+        definitions
+    }
+
+    pub async fn call(&self, name: &str, args: Value) -> Result<Value> {
+        let tool = self.get(name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown tool: {}", name))?;
+        tool.call(args).await
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use tokio::sync::{RwLock, RwLockMappedWriteGuard, RwLockWriteGuard};
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::core::agent::hnsw::{HnswConfig, HnswIndex};
+use crate::core::agent::qdrant::{QdrantConfig, QdrantIndex};
+use crate::core::llm::types::Message;
+use crate::core::rag::store::Embedder;
+use crate::utils::egress::EgressClient;
+
+// Conversation memory that can be searched by meaning as well as by
+// recency. Every stored message is kept in a recent window like the plain
+// memory; user and assistant messages are also embedded and indexed, so
+// search_similar() can bring back something said fifty turns ago when the
+// user comes back to it.
+//
+// One index can back many memories: entries carry the scope (a session or
+// agent id) and searches stay within their own scope.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryPoint {
+    pub id: String,
+    pub scope: String,
+    pub message: Message,
+    pub embedding: Vec<f32>,
+    pub stored_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryMatch {
+    pub id: String,
+    pub message: Message,
+    pub score: f32,
+    pub stored_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait MemoryIndex: Send + Sync {
+    async fn upsert(&self, points: Vec<MemoryPoint>) -> Result<()>;
+    // Best first, by cosine similarity, within `scope`
+    async fn search(&self, embedding: &[f32], k: usize, scope: &str) -> Result<Vec<MemoryMatch>>;
+    async fn delete(&self, ids: &[String]) -> Result<()>;
+    // Ids stored under `scope`, oldest first
+    async fn ids(&self, scope: &str) -> Result<Vec<String>>;
+    async fn count(&self, scope: &str) -> Result<usize>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum MemoryIndexConfig {
+    Hnsw(HnswConfig),
+    Qdrant(QdrantConfig),
+}
+
+impl Default for MemoryIndexConfig {
+    fn default() -> Self {
+        MemoryIndexConfig::Hnsw(HnswConfig::default())
+    }
+}
+
+pub fn build_index(config: &MemoryIndexConfig, client: EgressClient) -> Arc<dyn MemoryIndex> {
+    match config {
+        MemoryIndexConfig::Hnsw(hnsw) => Arc::new(HnswIndex::new(hnsw.clone())),
+        MemoryIndexConfig::Qdrant(qdrant) => Arc::new(QdrantIndex::new(qdrant.clone(), client)),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VectorMemoryConfig {
+    pub index: MemoryIndexConfig,
+    // Messages kept for get_recent()
+    pub max_recent: usize,
+    // Indexed messages kept per scope; the oldest go first
+    pub max_indexed: usize,
+    pub roles: Vec<String>,
+    // "ok" and "thanks" would match everything
+    pub min_chars: usize,
+    // Matches this weak are left out of search_similar()
+    pub min_score: f32,
+    // The last few messages are in the prompt already, so searches skip them
+    pub skip_recent: usize,
+}
+
+impl Default for VectorMemoryConfig {
+    fn default() -> Self {
+        Self {
+            index: MemoryIndexConfig::default(),
+            max_recent: 100,
+            max_indexed: 10_000,
+            roles: vec!["user".to_string(), "assistant".to_string()],
+            min_chars: 12,
+            min_score: 0.3,
+            skip_recent: 10,
+        }
+    }
+}
+
+pub struct VectorMemory {
+    config: VectorMemoryConfig,
+    scope: String,
+    embedder: Arc<dyn Embedder>,
+    index: Arc<dyn MemoryIndex>,
+    recent: RwLock<VecDeque<(Option<String>, Message)>>,
+    // Ids indexed under this scope, oldest first; None until read back
+    // from the index, so a restarted memory can still trim and clear what
+    // an earlier one stored
+    indexed: RwLock<Option<VecDeque<String>>>,
+}
+
+impl VectorMemory {
+    pub fn new(
+        config: VectorMemoryConfig,
+        scope: &str,
+        embedder: Arc<dyn Embedder>,
+        index: Arc<dyn MemoryIndex>,
+    ) -> Self {
+        Self {
+            config,
+            scope: scope.to_string(),
+            embedder,
+            index,
+            recent: RwLock::new(VecDeque::new()),
+            indexed: RwLock::new(None),
+        }
+    }
+
+    pub fn scope(&self) -> &str {
+        &self.scope
+    }
+
+    async fn indexed(&self) -> Result<RwLockMappedWriteGuard<'_, VecDeque<String>>> {
+        let mut indexed = self.indexed.write().await;
+        if indexed.is_none() {
+            let ids = self.index.ids(&self.scope).await.context("Failed to list indexed memories")?;
+            *indexed = Some(ids.into());
+        }
+        Ok(RwLockWriteGuard::map(indexed, |ids| ids.get_or_insert_with(VecDeque::new)))
+    }
+
+    fn should_index(&self, message: &Message) -> bool {
+        self.config.roles.iter().any(|r| *r == message.role)
+            && message.content.trim().chars().count() >= self.config.min_chars
+    }
+
+    // A message that fails to index is still remembered, just not findable
+    // by search_similar(); losing it from the conversation would be worse
+    pub async fn store(&self, message: Message) {
+        let id = if self.should_index(&message) {
+            match self.index_message(&message).await {
+                Ok(id) => Some(id),
+                Err(e) => {
+                    log::warn!("Failed to index message for memory {}: {:#}", self.scope, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let mut recent = self.recent.write().await;
+        recent.push_back((id, message));
+        while recent.len() > self.config.max_recent {
+            recent.pop_front();
+        }
+    }
+
+    async fn index_message(&self, message: &Message) -> Result<String> {
+        let point = MemoryPoint {
+            id: Uuid::new_v4().to_string(),
+            scope: self.scope.clone(),
+            message: message.clone(),
+            embedding: self.embed(&message.content).await?,
+            stored_at: Utc::now(),
+        };
+        let id = point.id.clone();
+        // Loaded before the upsert, or the new id would be listed twice
+        let mut indexed = self.indexed().await?;
+        self.index.upsert(vec![point]).await.context("Failed to index memory")?;
+        indexed.push_back(id.clone());
+        Ok(id)
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.embedder.embed(&[text.to_string()]).await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Embedder returned no vector"))
+    }
+
+    // Oldest first, like the plain memory
+    pub async fn get_recent(&self, count: usize) -> Vec<Message> {
+        let recent = self.recent.read().await;
+        recent.iter().skip(recent.len().saturating_sub(count)).map(|(_, m)| m.clone()).collect()
+    }
+
+    pub async fn search_similar(&self, query: &str, k: usize) -> Result<Vec<MemoryMatch>> {
+        if k == 0 || query.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let embedding = self.embed(query).await?;
+        let skip: HashSet<String> = {
+            let recent = self.recent.read().await;
+            recent.iter().rev().take(self.config.skip_recent).filter_map(|(id, _)| id.clone()).collect()
+        };
+
+        let matches = self.index.search(&embedding, k + skip.len(), &self.scope).await?;
+        Ok(matches.into_iter()
+            .filter(|m| !skip.contains(&m.id) && m.score >= self.config.min_score)
+            .take(k)
+            .collect())
+    }
+
+    // Trims both the recent window and the index to their limits
+    pub async fn cleanup(&self) -> Result<()> {
+        {
+            let mut recent = self.recent.write().await;
+            while recent.len() > self.config.max_recent {
+                recent.pop_front();
+            }
+        }
+
+        let expired: Vec<String> = {
+            let mut indexed = self.indexed().await?;
+            let excess = indexed.len().saturating_sub(self.config.max_indexed);
+            indexed.drain(..excess).collect()
+        };
+        if !expired.is_empty() {
+            self.index.delete(&expired).await.context("Failed to drop expired memories")?;
+        }
+        Ok(())
+    }
+
+    pub async fn clear(&self) -> Result<()> {
+        self.recent.write().await.clear();
+        let ids: Vec<String> = self.indexed().await?.drain(..).collect();
+        self.index.delete(&ids).await
+    }
+
+    pub async fn is_within_limits(&self) -> bool {
+        self.recent.read().await.len() <= self.config.max_recent
+            && self.indexed().await.is_ok_and(|ids| ids.len() <= self.config.max_indexed)
+    }
+}
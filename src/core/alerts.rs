@@ -0,0 +1,180 @@
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+
+use crate::core::incidents::IncidentManager;
+use crate::core::notify::{Notification, NotificationDispatcher, Severity};
+use crate::core::remediation::Remediator;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Alert {
+    pub key: String,
+    pub name: String,
+    pub severity: Severity,
+    pub message: String,
+    pub labels: HashMap<String, String>,
+    pub raised_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub occurrences: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertConfig {
+    pub history_size: usize,
+    pub notify_on_resolve: bool,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            history_size: 1000,
+            notify_on_resolve: true,
+        }
+    }
+}
+
+// Alerts are keyed so a condition that keeps firing updates one alert
+// instead of flooding notification channels
+pub struct AlertManager {
+    config: AlertConfig,
+    active: Arc<RwLock<HashMap<String, Alert>>>,
+    history: Arc<RwLock<Vec<Alert>>>,
+    dispatcher: Option<Arc<NotificationDispatcher>>,
+    incidents: Option<Arc<IncidentManager>>,
+    remediator: Option<Arc<Remediator>>,
+}
+
+impl AlertManager {
+    pub fn new(config: AlertConfig, dispatcher: Option<Arc<NotificationDispatcher>>) -> Self {
+        Self {
+            config,
+            active: Arc::new(RwLock::new(HashMap::new())),
+            history: Arc::new(RwLock::new(Vec::new())),
+            dispatcher,
+            incidents: None,
+            remediator: None,
+        }
+    }
+
+    // New alerts and escalations are grouped into incidents as well as notified
+    pub fn with_incidents(mut self, incidents: Arc<IncidentManager>) -> Self {
+        self.incidents = Some(incidents);
+        self
+    }
+
+    // Remediations run in the background so a slow restart doesn't hold up
+    // the alert path
+    pub fn with_remediation(mut self, remediator: Arc<Remediator>) -> Self {
+        self.remediator = Some(remediator);
+        self
+    }
+
+    pub async fn raise(
+        &self,
+        key: &str,
+        name: &str,
+        severity: Severity,
+        message: &str,
+        labels: HashMap<String, String>,
+    ) -> Alert {
+        let now = Utc::now();
+        let mut active = self.active.write().await;
+
+        if let Some(alert) = active.get_mut(key) {
+            alert.last_seen = now;
+            alert.occurrences += 1;
+            alert.message = message.to_string();
+            // Escalations are notified again, repeats at the same level are not
+            if severity > alert.severity {
+                alert.severity = severity;
+                let alert = alert.clone();
+                drop(active);
+                self.notify(&alert, false).await;
+                self.follow_up(&alert).await;
+                return alert;
+            }
+            return alert.clone();
+        }
+
+        let alert = Alert {
+            key: key.to_string(),
+            name: name.to_string(),
+            severity,
+            message: message.to_string(),
+            labels,
+            raised_at: now,
+            last_seen: now,
+            resolved_at: None,
+            occurrences: 1,
+        };
+        active.insert(key.to_string(), alert.clone());
+        drop(active);
+
+        log::warn!("Alert raised: {} ({})", name, message);
+        self.notify(&alert, false).await;
+        self.follow_up(&alert).await;
+        alert
+    }
+
+    pub async fn resolve(&self, key: &str) -> Option<Alert> {
+        let mut alert = self.active.write().await.remove(key)?;
+        alert.resolved_at = Some(Utc::now());
+
+        let mut history = self.history.write().await;
+        history.push(alert.clone());
+        while history.len() > self.config.history_size {
+            history.remove(0);
+        }
+        drop(history);
+
+        log::info!("Alert resolved: {}", alert.name);
+        if self.config.notify_on_resolve {
+            self.notify(&alert, true).await;
+        }
+        Some(alert)
+    }
+
+    pub async fn active_alerts(&self) -> Vec<Alert> {
+        let mut alerts: Vec<_> = self.active.read().await.values().cloned().collect();
+        alerts.sort_by(|a, b| b.raised_at.cmp(&a.raised_at));
+        alerts
+    }
+
+    pub async fn history(&self) -> Vec<Alert> {
+        self.history.read().await.clone()
+    }
+
+    async fn follow_up(&self, alert: &Alert) {
+        if let Some(incidents) = &self.incidents {
+            incidents.ingest_alert(alert).await;
+        }
+        if let Some(remediator) = &self.remediator {
+            let remediator = remediator.clone();
+            let alert = alert.clone();
+            tokio::spawn(async move {
+                remediator.on_alert(&alert).await;
+            });
+        }
+    }
+
+    async fn notify(&self, alert: &Alert, resolved: bool) {
+        let Some(dispatcher) = &self.dispatcher else {
+            return;
+        };
+
+        let title = if resolved {
+            format!("Resolved: {}", alert.name)
+        } else {
+            alert.name.clone()
+        };
+        let mut notification = Notification::new(&title, &alert.message, alert.severity, "alerts");
+        for (key, value) in &alert.labels {
+            notification = notification.with_field(key, value);
+        }
+
+        dispatcher.dispatch(&notification).await;
+    }
+}
@@ -0,0 +1,277 @@
+#![cfg(feature = "chaos")]
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::Stream;
+#[cfg(feature = "vision")]
+use opencv::core::Mat;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Serialize, Deserialize};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::core::llm::{
+    LLMTrait,
+    types::{Message, ModelConfig, Response, StreamChunk},
+};
+#[cfg(feature = "vision")]
+use crate::models::inference::Model;
+use crate::utils::pattern::wildcard_matches;
+
+type ChunkStream = Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>;
+
+// Fault injection for resilience tests, compiled only with the `chaos`
+// feature. Wrapped components call `inject` with a target name before doing
+// real work, and matching rules add latency or fail the call.
+//
+// Targets used by the wrappers here:
+//   llm.<provider>      completions and streams, per provider
+//   gpu.<model>         inference calls, per detector model
+//   channel.<name>      sends through a ChaosSender; frames entering a
+//                       pipeline use channel.pipeline.input
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FaultRule {
+    pub target: String,
+    pub latency_ms: u64,
+    // Added on top of latency_ms, uniformly from 0..=jitter
+    pub jitter_ms: u64,
+    pub error_rate: f64,
+    // Phrased like a provider error so retry and failover classification
+    // treats it the way it would a real outage
+    pub error_message: String,
+    // Calls that pass untouched before the rule kicks in
+    pub skip_first: u64,
+    // Stops injecting after this many faults
+    pub max_faults: Option<u64>,
+}
+
+impl Default for FaultRule {
+    fn default() -> Self {
+        Self {
+            target: "*".to_string(),
+            latency_ms: 0,
+            jitter_ms: 0,
+            error_rate: 0.0,
+            error_message: "injected fault: upstream returned 503 Service Unavailable".to_string(),
+            skip_first: 0,
+            max_faults: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChaosConfig {
+    pub enabled: bool,
+    // Fixed seed for reproducible runs; random otherwise
+    pub seed: Option<u64>,
+    pub rules: Vec<FaultRule>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FaultStats {
+    pub calls: u64,
+    pub delayed: u64,
+    pub failed: u64,
+}
+
+#[derive(Default)]
+struct RuleState {
+    calls: u64,
+    faults: u64,
+}
+
+pub struct FaultInjector {
+    config: ChaosConfig,
+    rng: Mutex<StdRng>,
+    rules: Mutex<Vec<RuleState>>,
+    stats: Mutex<HashMap<String, FaultStats>>,
+}
+
+static GLOBAL: OnceLock<Arc<FaultInjector>> = OnceLock::new();
+
+impl FaultInjector {
+    pub fn new(config: ChaosConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Self {
+            rules: Mutex::new(config.rules.iter().map(|_| RuleState::default()).collect()),
+            rng: Mutex::new(rng),
+            stats: Mutex::new(HashMap::new()),
+            config,
+        }
+    }
+
+    // Installed once from `config.chaos`; detectors and pipelines built
+    // afterwards route through it. Later calls return the first injector.
+    pub fn install(config: ChaosConfig) -> Arc<Self> {
+        GLOBAL.get_or_init(|| Arc::new(Self::new(config))).clone()
+    }
+
+    pub fn global() -> Option<Arc<Self>> {
+        GLOBAL.get().cloned()
+    }
+
+    // Sleeps and/or fails according to the first rule matching `target`
+    pub async fn inject(&self, target: &str) -> Result<()> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let Some(index) = self.config.rules.iter().position(|r| wildcard_matches(&r.target, target)) else {
+            return Ok(());
+        };
+        let rule = &self.config.rules[index];
+
+        // Decided up front so no lock is held across the sleep
+        let (delay, fail) = {
+            let mut states = self.rules.lock().unwrap();
+            let state = &mut states[index];
+            state.calls += 1;
+            let exhausted = rule.max_faults.is_some_and(|max| state.faults >= max);
+            if state.calls <= rule.skip_first || exhausted {
+                (Duration::ZERO, false)
+            } else {
+                let mut rng = self.rng.lock().unwrap();
+                let jitter = if rule.jitter_ms > 0 { rng.gen_range(0..=rule.jitter_ms) } else { 0 };
+                let fail = rule.error_rate > 0.0 && rng.gen::<f64>() < rule.error_rate;
+                let delay = Duration::from_millis(rule.latency_ms + jitter);
+                if fail || !delay.is_zero() {
+                    state.faults += 1;
+                }
+                (delay, fail)
+            }
+        };
+
+        {
+            let mut stats = self.stats.lock().unwrap();
+            let entry = stats.entry(target.to_string()).or_default();
+            entry.calls += 1;
+            entry.delayed += u64::from(!delay.is_zero());
+            entry.failed += u64::from(fail);
+        }
+
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        if fail {
+            log::warn!("Chaos: failing call to {}", target);
+            return Err(anyhow::anyhow!("{}", rule.error_message));
+        }
+        Ok(())
+    }
+
+    pub fn stats(&self) -> HashMap<String, FaultStats> {
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+pub struct ChaosLLM {
+    inner: Box<dyn LLMTrait>,
+    injector: Arc<FaultInjector>,
+    target: String,
+}
+
+impl ChaosLLM {
+    pub fn new(inner: Box<dyn LLMTrait>, injector: Arc<FaultInjector>, provider: &str) -> Self {
+        Self { inner, injector, target: format!("llm.{}", provider) }
+    }
+}
+
+#[async_trait]
+impl LLMTrait for ChaosLLM {
+    async fn complete(&self, messages: Vec<Message>) -> Result<Response> {
+        self.injector.inject(&self.target).await?;
+        self.inner.complete(messages).await
+    }
+
+    async fn complete_stream(&self, messages: Vec<Message>, cancel: CancellationToken) -> Result<ChunkStream> {
+        self.injector.inject(&self.target).await?;
+        self.inner.complete_stream(messages, cancel).await
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.inner.is_initialized()
+    }
+
+    fn get_model(&self) -> &str {
+        self.inner.get_model()
+    }
+
+    fn set_model_config(&mut self, config: ModelConfig) {
+        self.inner.set_model_config(config);
+    }
+
+    fn get_model_config(&self) -> ModelConfig {
+        self.inner.get_model_config()
+    }
+}
+
+#[cfg(feature = "vision")]
+pub struct ChaosModel {
+    inner: Arc<dyn Model>,
+    injector: Arc<FaultInjector>,
+    target: String,
+}
+
+#[cfg(feature = "vision")]
+impl ChaosModel {
+    pub fn new(inner: Arc<dyn Model>, injector: Arc<FaultInjector>, name: &str) -> Self {
+        Self { inner, injector, target: format!("gpu.{}", name) }
+    }
+}
+
+#[cfg(feature = "vision")]
+#[async_trait]
+impl Model for ChaosModel {
+    async fn infer(&self, input: &Mat) -> Result<Mat> {
+        self.injector.inject(&self.target).await?;
+        self.inner.infer(input).await
+    }
+}
+
+// An mpsc sender whose sends can be delayed or refused, to exercise
+// backpressure and dropped-message handling
+pub struct ChaosSender<T> {
+    inner: mpsc::Sender<T>,
+    injector: Arc<FaultInjector>,
+    target: String,
+}
+
+impl<T> Clone for ChaosSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            injector: self.injector.clone(),
+            target: self.target.clone(),
+        }
+    }
+}
+
+impl<T: Send> ChaosSender<T> {
+    pub fn new(inner: mpsc::Sender<T>, injector: Arc<FaultInjector>, name: &str) -> Self {
+        Self { inner, injector, target: format!("channel.{}", name) }
+    }
+
+    pub async fn send(&self, value: T) -> Result<()> {
+        self.injector.inject(&self.target).await?;
+        self.inner.send(value).await
+            .map_err(|_| anyhow::anyhow!("Channel {} is closed", self.target))
+    }
+}
+
+pub fn chaos_channel<T: Send>(
+    buffer: usize,
+    injector: Arc<FaultInjector>,
+    name: &str,
+) -> (ChaosSender<T>, mpsc::Receiver<T>) {
+    let (tx, rx) = mpsc::channel(buffer);
+    (ChaosSender::new(tx, injector, name), rx)
+}
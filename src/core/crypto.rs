@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+
+const MAGIC: &[u8; 4] = b"VAE1";
+const NONCE_LEN: usize = 12;
+
+#[async_trait]
+pub trait SecretsProvider: Send + Sync {
+    async fn get_secret(&self, name: &str) -> Result<Vec<u8>>;
+}
+
+// Reads base64-encoded secrets from environment variables, e.g.
+// VAE_KEY_2024_01 for key id "2024_01" with prefix "VAE_KEY_"
+pub struct EnvSecretsProvider {
+    prefix: String,
+}
+
+impl EnvSecretsProvider {
+    pub fn new(prefix: &str) -> Self {
+        Self { prefix: prefix.to_string() }
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for EnvSecretsProvider {
+    async fn get_secret(&self, name: &str) -> Result<Vec<u8>> {
+        let var = format!("{}{}", self.prefix, name.to_uppercase());
+        let value = std::env::var(&var)
+            .with_context(|| format!("Secret {} is not set", var))?;
+        BASE64.decode(value.trim()).with_context(|| format!("Secret {} is not valid base64", var))
+    }
+}
+
+// Reads base64-encoded secrets from files in a directory (e.g. mounted
+// Kubernetes or Docker secrets), one file per key id
+pub struct FileSecretsProvider {
+    directory: String,
+}
+
+impl FileSecretsProvider {
+    pub fn new(directory: &str) -> Self {
+        Self { directory: directory.to_string() }
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for FileSecretsProvider {
+    async fn get_secret(&self, name: &str) -> Result<Vec<u8>> {
+        let path = Path::new(&self.directory).join(name);
+        let value = tokio::fs::read_to_string(&path).await
+            .with_context(|| format!("Failed to read secret {}", path.display()))?;
+        BASE64.decode(value.trim())
+            .with_context(|| format!("Secret {} is not valid base64", path.display()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    pub enabled: bool,
+    // Key used for all new writes
+    pub active_key: String,
+    // Retired keys still accepted for decryption until data is re-encrypted
+    #[serde(default)]
+    pub previous_keys: Vec<String>,
+}
+
+pub struct Encryptor {
+    active_key: RwLock<String>,
+    keys: RwLock<HashMap<String, Aes256Gcm>>,
+    secrets: Arc<dyn SecretsProvider>,
+}
+
+impl Encryptor {
+    pub async fn new(config: &EncryptionConfig, secrets: Arc<dyn SecretsProvider>) -> Result<Self> {
+        let encryptor = Self {
+            active_key: RwLock::new(config.active_key.clone()),
+            keys: RwLock::new(HashMap::new()),
+            secrets,
+        };
+
+        encryptor.load_key(&config.active_key).await?;
+        for key_id in &config.previous_keys {
+            encryptor.load_key(key_id).await?;
+        }
+
+        Ok(encryptor)
+    }
+
+    async fn load_key(&self, key_id: &str) -> Result<()> {
+        if key_id.is_empty() || key_id.len() > u8::MAX as usize {
+            return Err(anyhow::anyhow!("Invalid encryption key id: {:?}", key_id));
+        }
+
+        let material = self.secrets.get_secret(key_id).await?;
+        if material.len() != 32 {
+            return Err(anyhow::anyhow!(
+                "Encryption key {} must be 32 bytes, got {}", key_id, material.len()
+            ));
+        }
+
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&material));
+        self.keys.write().await.insert(key_id.to_string(), cipher);
+        Ok(())
+    }
+
+    // New writes switch to `key_id` immediately; old keys remain loaded so
+    // existing data stays readable until `reencrypt` has been run over it
+    pub async fn rotate(&self, key_id: &str) -> Result<()> {
+        self.load_key(key_id).await?;
+        *self.active_key.write().await = key_id.to_string();
+        log::info!("Encryption key rotated to {}", key_id);
+        Ok(())
+    }
+
+    pub async fn active_key(&self) -> String {
+        self.active_key.read().await.clone()
+    }
+
+    pub fn is_encrypted(data: &[u8]) -> bool {
+        data.starts_with(MAGIC)
+    }
+
+    // Layout: MAGIC | key id length (u8) | key id | nonce | ciphertext+tag
+    pub async fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let key_id = self.active_key().await;
+        let keys = self.keys.read().await;
+        let cipher = keys.get(&key_id)
+            .ok_or_else(|| anyhow::anyhow!("Active encryption key {} is not loaded", key_id))?;
+
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher.encrypt(&nonce, plaintext)
+            .map_err(|_| anyhow::anyhow!("Encryption failed"))?;
+
+        let mut out = Vec::with_capacity(MAGIC.len() + 1 + key_id.len() + NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(MAGIC);
+        out.push(key_id.len() as u8);
+        out.extend_from_slice(key_id.as_bytes());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    pub async fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let (key_id, nonce, ciphertext) = parse_envelope(data)?;
+        let keys = self.keys.read().await;
+        let cipher = keys.get(key_id)
+            .ok_or_else(|| anyhow::anyhow!("Data was encrypted with unknown key {}", key_id))?;
+
+        cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("Decryption failed: data corrupt or wrong key"))
+    }
+
+    // Accepts both plaintext and encrypted data, so enabling encryption on
+    // an existing deployment doesn't break reads of older files
+    pub async fn decrypt_if_needed(&self, data: Vec<u8>) -> Result<Vec<u8>> {
+        if Self::is_encrypted(&data) {
+            self.decrypt(&data).await
+        } else {
+            Ok(data)
+        }
+    }
+
+    pub async fn reencrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let plaintext = if Self::is_encrypted(data) {
+            self.decrypt(data).await?
+        } else {
+            data.to_vec()
+        };
+        self.encrypt(&plaintext).await
+    }
+
+    // For text columns: the envelope as base64. Text that isn't one is
+    // returned as is, like decrypt_if_needed.
+    pub async fn encrypt_text(&self, plaintext: &str) -> Result<String> {
+        Ok(BASE64.encode(self.encrypt(plaintext.as_bytes()).await?))
+    }
+
+    pub async fn decrypt_text(&self, text: String) -> Result<String> {
+        match BASE64.decode(&text) {
+            Ok(data) if Self::is_encrypted(&data) => String::from_utf8(self.decrypt(&data).await?)
+                .context("Decrypted text is not valid UTF-8"),
+            _ => Ok(text),
+        }
+    }
+
+    pub async fn write_file(&self, path: impl AsRef<Path>, plaintext: &[u8]) -> Result<()> {
+        let path = path.as_ref();
+        let encrypted = self.encrypt(plaintext).await?;
+
+        // Write to a temp file first so a crash never leaves a torn file
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, encrypted).await
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        tokio::fs::rename(&tmp, path).await
+            .with_context(|| format!("Failed to replace {}", path.display()))?;
+        Ok(())
+    }
+
+    pub async fn read_file(&self, path: impl AsRef<Path>) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+        let data = tokio::fs::read(path).await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        self.decrypt_if_needed(data).await
+    }
+
+    pub async fn reencrypt_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let plaintext = self.read_file(path.as_ref()).await?;
+        self.write_file(path, &plaintext).await
+    }
+}
+
+fn parse_envelope(data: &[u8]) -> Result<(&str, &[u8], &[u8])> {
+    if !data.starts_with(MAGIC) || data.len() < MAGIC.len() + 1 {
+        return Err(anyhow::anyhow!("Data is not in the encrypted envelope format"));
+    }
+
+    let key_len = data[MAGIC.len()] as usize;
+    let key_start = MAGIC.len() + 1;
+    let nonce_start = key_start + key_len;
+    let body_start = nonce_start + NONCE_LEN;
+    if data.len() < body_start {
+        return Err(anyhow::anyhow!("Encrypted envelope is truncated"));
+    }
+
+    let key_id = std::str::from_utf8(&data[key_start..nonce_start])
+        .context("Encrypted envelope has an invalid key id")?;
+    Ok((key_id, &data[nonce_start..body_start], &data[body_start..]))
+}
@@ -0,0 +1,265 @@
+#![cfg(feature = "vision")]
+
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::{mpsc, RwLock};
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use opencv::prelude::*;
+use uuid::Uuid;
+
+use crate::core::pipeline::PipelineData;
+
+// Frames that failed a stage after every retry, kept so they can be looked
+// at and pushed through again once the cause is fixed, rather than silently
+// missing from a batch job's output. Entries hold the frame as it entered
+// the failing stage, and a re-injected frame picks up there. Stages that
+// keep per-source state have moved on by then, so they are skipped.
+//
+// A re-injected frame carries its entry id in the `dead_letter_id`
+// metadata key, so failing again updates the same entry instead of adding
+// a second one, and the stage to resume at in `dead_letter_stage`.
+
+pub const DEAD_LETTER_ID_KEY: &str = "dead_letter_id";
+pub const DEAD_LETTER_STAGE_KEY: &str = "dead_letter_stage";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeadLetterConfig {
+    // The oldest entries are evicted past either limit
+    pub max_entries: usize,
+    // Pixels pinned by held frames; a 1080p BGR frame is about 6MB
+    pub max_bytes: usize,
+}
+
+impl Default for DeadLetterConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 1000,
+            max_bytes: 512 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetter {
+    pub id: String,
+    pub frame_id: u64,
+    pub stream: String,
+    pub stage: String,
+    pub stage_index: usize,
+    pub error: String,
+    // Tries at the failing stage, including the first
+    pub attempts: u32,
+    pub reinjections: u32,
+    pub bytes: usize,
+    pub metadata: HashMap<String, String>,
+    pub frame_timestamp: DateTime<Utc>,
+    pub first_failed_at: DateTime<Utc>,
+    pub last_failed_at: DateTime<Utc>,
+    #[serde(skip)]
+    pub data: PipelineData,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DeadLetterFilter {
+    pub stream: Option<String>,
+    pub stage: Option<String>,
+}
+
+impl DeadLetterFilter {
+    fn matches(&self, entry: &DeadLetter) -> bool {
+        self.stream.as_ref().is_none_or(|s| *s == entry.stream)
+            && self.stage.as_ref().is_none_or(|s| *s == entry.stage)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetterStats {
+    pub entries: usize,
+    pub bytes: usize,
+    pub evicted: u64,
+    pub by_stage: HashMap<String, usize>,
+}
+
+#[derive(Default)]
+struct Entries {
+    order: VecDeque<String>,
+    by_id: HashMap<String, DeadLetter>,
+    bytes: usize,
+    evicted: u64,
+}
+
+impl Entries {
+    fn take(&mut self, id: &str) -> Option<DeadLetter> {
+        let entry = self.by_id.remove(id)?;
+        self.bytes -= entry.bytes;
+        Some(entry)
+    }
+
+    // Always keeps the newest entry, however large
+    fn evict(&mut self, config: &DeadLetterConfig) {
+        while self.by_id.len() > config.max_entries.max(1)
+            || (self.bytes > config.max_bytes && self.by_id.len() > 1)
+        {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if self.take(&oldest).is_some() {
+                self.evicted += 1;
+            }
+        }
+    }
+}
+
+fn frame_bytes(data: &PipelineData) -> usize {
+    let mat = &data.frame.data;
+    mat.total() * mat.elem_size().unwrap_or(1)
+}
+
+pub struct DeadLetterQueue {
+    config: DeadLetterConfig,
+    entries: RwLock<Entries>,
+    target: RwLock<Option<mpsc::Sender<PipelineData>>>,
+}
+
+impl DeadLetterQueue {
+    pub fn new(config: DeadLetterConfig) -> Self {
+        Self {
+            config,
+            entries: RwLock::new(Entries::default()),
+            target: RwLock::new(None),
+        }
+    }
+
+    // Where re-injected frames go; set when a pipeline attaches the queue
+    pub async fn set_target(&self, sender: mpsc::Sender<PipelineData>) {
+        *self.target.write().await = Some(sender);
+    }
+
+    // `input` is the frame as it entered stage `stage_index`
+    pub async fn push(&self, input: PipelineData, stage_index: usize, stage: &str, error: &str, attempts: u32) -> String {
+        let now = Utc::now();
+        let bytes = frame_bytes(&input);
+        let mut entries = self.entries.write().await;
+
+        let previous = input.metadata.get(DEAD_LETTER_ID_KEY).cloned();
+        if let Some(entry) = previous.as_ref().and_then(|id| entries.by_id.get_mut(id)) {
+            entry.stage = stage.to_string();
+            entry.stage_index = stage_index;
+            entry.error = error.to_string();
+            entry.attempts = attempts;
+            let held = std::mem::replace(&mut entry.bytes, bytes);
+            entry.last_failed_at = now;
+            entry.data = input;
+            let id = entry.id.clone();
+            entries.bytes = entries.bytes - held + bytes;
+            entries.evict(&self.config);
+            return id;
+        }
+
+        let id = previous.unwrap_or_else(|| Uuid::new_v4().to_string());
+        let mut metadata = input.metadata.clone();
+        metadata.remove(DEAD_LETTER_ID_KEY);
+        metadata.remove(DEAD_LETTER_STAGE_KEY);
+        let entry = DeadLetter {
+            id: id.clone(),
+            frame_id: input.frame.id,
+            stream: input.frame.metadata.source.clone(),
+            stage: stage.to_string(),
+            stage_index,
+            error: error.to_string(),
+            attempts,
+            reinjections: 0,
+            bytes,
+            metadata,
+            frame_timestamp: input.frame.timestamp,
+            first_failed_at: now,
+            last_failed_at: now,
+            data: input,
+        };
+        log::warn!(
+            "Frame {} from {} dead-lettered at stage {} after {} attempt(s): {}",
+            entry.frame_id, entry.stream, stage, attempts, error
+        );
+
+        entries.order.push_back(id.clone());
+        entries.by_id.insert(id.clone(), entry);
+        entries.bytes += bytes;
+        entries.evict(&self.config);
+        id
+    }
+
+    pub async fn get(&self, id: &str) -> Option<DeadLetter> {
+        self.entries.read().await.by_id.get(id).cloned()
+    }
+
+    // Oldest first
+    pub async fn list(&self, filter: &DeadLetterFilter, limit: usize) -> Vec<DeadLetter> {
+        let entries = self.entries.read().await;
+        entries.order.iter()
+            .filter_map(|id| entries.by_id.get(id))
+            .filter(|e| filter.matches(e))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    pub async fn remove(&self, id: &str) -> Option<DeadLetter> {
+        let mut entries = self.entries.write().await;
+        let removed = entries.take(id);
+        if removed.is_some() {
+            entries.order.retain(|e| e != id);
+        }
+        removed
+    }
+
+    pub async fn purge(&self, filter: &DeadLetterFilter) -> usize {
+        let mut entries = self.entries.write().await;
+        let ids: Vec<String> = entries.by_id.values().filter(|e| filter.matches(e)).map(|e| e.id.clone()).collect();
+        for id in &ids {
+            entries.take(id);
+        }
+        entries.order.retain(|id| !ids.contains(id));
+        ids.len()
+    }
+
+    pub async fn stats(&self) -> DeadLetterStats {
+        let entries = self.entries.read().await;
+        let mut by_stage: HashMap<String, usize> = HashMap::new();
+        for entry in entries.by_id.values() {
+            *by_stage.entry(entry.stage.clone()).or_default() += 1;
+        }
+        DeadLetterStats { entries: entries.by_id.len(), bytes: entries.bytes, evicted: entries.evicted, by_stage }
+    }
+
+    // Sends the frame back into the pipeline. The entry stays until the
+    // frame gets through; a frame that fails again updates it.
+    pub async fn reinject(&self, id: &str) -> Result<()> {
+        let target = self.target.read().await.clone()
+            .ok_or_else(|| anyhow::anyhow!("No pipeline is attached to the dead letter queue"))?;
+
+        let (mut data, stage_index) = {
+            let mut entries = self.entries.write().await;
+            let entry = entries.by_id.get_mut(id)
+                .ok_or_else(|| anyhow::anyhow!("Dead letter not found: {}", id))?;
+            entry.reinjections += 1;
+            (entry.data.clone(), entry.stage_index)
+        };
+        data.metadata.insert(DEAD_LETTER_ID_KEY.to_string(), id.to_string());
+        data.metadata.insert(DEAD_LETTER_STAGE_KEY.to_string(), stage_index.to_string());
+
+        target.send(data).await
+            .map_err(|_| anyhow::anyhow!("Pipeline input is closed"))?;
+        Ok(())
+    }
+
+    // Called when a re-injected frame makes it through
+    pub async fn resolve(&self, data: &PipelineData) {
+        if let Some(id) = data.metadata.get(DEAD_LETTER_ID_KEY) {
+            if self.remove(id).await.is_some() {
+                log::info!("Dead-lettered frame {} from {} processed on re-injection", data.frame.id, data.frame.metadata.source);
+            }
+        }
+    }
+}
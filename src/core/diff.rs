@@ -0,0 +1,186 @@
+#![cfg(feature = "vision")]
+
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::vision::detector::Detection;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum OutputMode {
+    #[default]
+    Full,
+    Differential(DiffConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiffConfig {
+    // Confidence change on a matched object that counts as material
+    pub confidence_jump: f32,
+    // Minimum overlap for a detection to be the same object as before
+    pub match_iou: f32,
+    // Emit anyway after this long so consumers can tell a quiet stream
+    // from a dead one
+    pub heartbeat_secs: Option<i64>,
+}
+
+impl Default for DiffConfig {
+    fn default() -> Self {
+        Self {
+            confidence_jump: 0.2,
+            match_iou: 0.3,
+            heartbeat_secs: Some(60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum ChangeReason {
+    Initial,
+    NewObject { class_name: String },
+    ClassCountChanged { class_name: String, previous: usize, current: usize },
+    ConfidenceJump { class_name: String, previous: f32, current: f32 },
+    Heartbeat,
+}
+
+struct EmittedState {
+    detections: Vec<Detection>,
+    emitted_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+pub struct DiffFilter {
+    modes: HashMap<String, DiffConfig>,
+    last_emitted: HashMap<String, EmittedState>,
+    suppressed: u64,
+}
+
+impl DiffFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_mode(&mut self, stream: &str, mode: OutputMode) {
+        match mode {
+            OutputMode::Full => {
+                self.modes.remove(stream);
+            }
+            OutputMode::Differential(config) => {
+                self.modes.insert(stream.to_string(), config);
+            }
+        }
+        self.last_emitted.remove(stream);
+    }
+
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed
+    }
+
+    // `None` means the result should be dropped. Streams in full mode always
+    // pass with no reasons attached.
+    pub fn filter(
+        &mut self,
+        stream: &str,
+        detections: &[Detection],
+        now: DateTime<Utc>,
+    ) -> Option<Vec<ChangeReason>> {
+        let config = match self.modes.get(stream) {
+            Some(config) => config,
+            None => return Some(Vec::new()),
+        };
+
+        // Compared against the last *emitted* set, not the last seen one, so
+        // slow drift eventually crosses a threshold instead of hiding forever
+        let reasons = match self.last_emitted.get(stream) {
+            None => vec![ChangeReason::Initial],
+            Some(last) => {
+                let mut reasons = diff_detections(&last.detections, detections, config);
+                let heartbeat_due = config.heartbeat_secs
+                    .is_some_and(|secs| now - last.emitted_at >= Duration::seconds(secs));
+                if reasons.is_empty() && heartbeat_due {
+                    reasons.push(ChangeReason::Heartbeat);
+                }
+                reasons
+            }
+        };
+
+        if reasons.is_empty() {
+            self.suppressed += 1;
+            return None;
+        }
+
+        self.last_emitted.insert(stream.to_string(), EmittedState {
+            detections: detections.to_vec(),
+            emitted_at: now,
+        });
+        Some(reasons)
+    }
+}
+
+pub fn diff_detections(
+    previous: &[Detection],
+    current: &[Detection],
+    config: &DiffConfig,
+) -> Vec<ChangeReason> {
+    let mut reasons = Vec::new();
+
+    let mut previous_counts: HashMap<&str, usize> = HashMap::new();
+    for detection in previous {
+        *previous_counts.entry(detection.class_name.as_str()).or_insert(0) += 1;
+    }
+    let mut current_counts: HashMap<&str, usize> = HashMap::new();
+    for detection in current {
+        *current_counts.entry(detection.class_name.as_str()).or_insert(0) += 1;
+    }
+
+    let mut classes: Vec<&str> = previous_counts.keys().chain(current_counts.keys()).copied().collect();
+    classes.sort();
+    classes.dedup();
+    for class_name in classes {
+        let previous = previous_counts.get(class_name).copied().unwrap_or(0);
+        let current = current_counts.get(class_name).copied().unwrap_or(0);
+        if previous != current {
+            reasons.push(ChangeReason::ClassCountChanged {
+                class_name: class_name.to_string(),
+                previous,
+                current,
+            });
+        }
+    }
+
+    // Greedy same-class matching; an unmatched detection is a new object
+    // even when the class count is unchanged (one left, another arrived)
+    let mut matched = vec![false; previous.len()];
+    for detection in current {
+        let best = previous.iter()
+            .enumerate()
+            .filter(|(i, p)| !matched[*i] && p.class_name == detection.class_name)
+            .map(|(i, p)| (i, p.bbox.iou(&detection.bbox)))
+            .filter(|(_, iou)| *iou >= config.match_iou)
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        match best {
+            Some((i, _)) => {
+                matched[i] = true;
+                let before = previous[i].confidence;
+                if (detection.confidence - before).abs() >= config.confidence_jump {
+                    reasons.push(ChangeReason::ConfidenceJump {
+                        class_name: detection.class_name.clone(),
+                        previous: before,
+                        current: detection.confidence,
+                    });
+                }
+            }
+            None => {
+                reasons.push(ChangeReason::NewObject {
+                    class_name: detection.class_name.clone(),
+                });
+            }
+        }
+    }
+
+    reasons
+}
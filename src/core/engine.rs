@@ -1,10 +1,23 @@
+#![cfg(feature = "vision")]
+
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc, Mutex as AsyncMutex};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use anyhow::{Result, Context};
 use async_trait::async_trait;
 use serde::{Serialize, Deserialize};
 
-use crate::vision::{processor::Frame, detector::Detection, analyzer::Analysis};
+use crate::vision::{processor::Frame, detector::Detection, analyzer::{Analysis, Anomaly}};
+use crate::vision::behaviors::{BehaviorAnomaly, BehaviorConfig, BehaviorDetectors, FrameRef};
+use crate::vision::interpolate::{DetectionInterpolator, InterpolationConfig};
+use crate::vision::privacy::{PrivacyConfig, PrivacyMasker};
+use crate::core::streams::StreamRegistry;
+use crate::vision::ptz::PtzManager;
+use crate::vision::tracker::{TrackEvent, Tracker, TrackerConfig};
+use crate::vision::shadow::{build_detection_model, DetectionModel, DetectionModelConfig, ShadowConfig, ShadowDeployment};
+use crate::vision::verify::{build_verifier, DetectionVerifier, VerifierSettings};
 use crate::models::inference::InferenceResult;
 use crate::runtime::gpu::GPUManager;
 
@@ -16,6 +29,35 @@ pub struct EngineConfig {
     pub model_precision: String,
     pub detection_threshold: f32,
     pub enable_analytics: bool,
+    // Events buffered per subscriber before slow subscribers start lagging
+    #[serde(default = "default_event_buffer")]
+    pub event_buffer: usize,
+    #[serde(default)]
+    pub tracking: TrackerConfig,
+    // Track-based detectors; they only run while tracking is enabled
+    #[serde(default)]
+    pub behaviors: BehaviorConfig,
+    // Runs inference on every Nth frame and interpolates the rest
+    #[serde(default)]
+    pub interpolation: InterpolationConfig,
+    // LLM check on ambiguous detections before they're published
+    #[serde(default)]
+    pub verification: Option<VerifierSettings>,
+    // Detects with this model instead of the GPU manager's. With a cascade
+    // the heavy model only runs on frames, or regions, with activity.
+    #[serde(default)]
+    pub detection: Option<DetectionModelConfig>,
+    // For candidates run next to `detection` through the model endpoints
+    #[serde(default)]
+    pub shadow: ShadowConfig,
+    // Masks the frame that verification crops, track snapshots and
+    // behavior evidence are cut from
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+}
+
+fn default_event_buffer() -> usize {
+    256
 }
 
 impl Default for EngineConfig {
@@ -27,17 +69,58 @@ impl Default for EngineConfig {
             model_precision: String::from("fp16"),
             detection_threshold: 0.5,
             enable_analytics: true,
+            event_buffer: default_event_buffer(),
+            tracking: TrackerConfig::default(),
+            behaviors: BehaviorConfig::default(),
+            interpolation: InterpolationConfig::default(),
+            verification: None,
+            detection: None,
+            shadow: ShadowConfig::default(),
+            privacy: PrivacyConfig::default(),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ProcessingResult {
     pub frame_id: u64,
     pub detections: Vec<Detection>,
     pub analysis: Option<Analysis>,
     pub inference: Option<InferenceResult>,
     pub timestamp: chrono::DateTime<chrono::Utc>,
+    // Detections were carried over from an earlier frame, not inferred
+    pub interpolated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EngineEvent {
+    FrameProcessed {
+        frame_id: u64,
+        detection_count: usize,
+        timestamp: chrono::DateTime<chrono::Utc>,
+    },
+    DetectionBatch {
+        frame_id: u64,
+        detections: Vec<Detection>,
+    },
+    Anomaly {
+        frame_id: u64,
+        anomaly: Anomaly,
+    },
+    StateChanged {
+        running: bool,
+    },
+    Track {
+        event: TrackEvent,
+    },
+    Behavior {
+        frame_id: u64,
+        anomaly: BehaviorAnomaly,
+    },
+    Error {
+        message: String,
+    },
 }
 
 #[async_trait]
@@ -48,9 +131,35 @@ pub trait FrameProcessor: Send + Sync {
 pub struct Engine {
     config: EngineConfig,
     gpu_manager: Arc<GPUManager>,
+    // Wraps the model from `detection`, so shadow candidates see the same
+    // frames and a promotion takes over detection; None detects through
+    // the GPU manager
+    shadow: Option<Arc<ShadowDeployment>>,
     frame_processor: Arc<dyn FrameProcessor>,
     processing_queue: mpsc::Sender<Frame>,
+    // Shared by the workers so the engine runs without any outside driver
+    // pulling frames off the queue
+    queued_frames: Arc<AsyncMutex<mpsc::Receiver<Frame>>>,
     result_channel: mpsc::Receiver<ProcessingResult>,
+    events: broadcast::Sender<EngineEvent>,
+    // One tracker per frame source, created on first sight
+    trackers: Arc<AsyncMutex<HashMap<String, (Tracker, BehaviorDetectors)>>>,
+    // Per source as well; only used when interpolation is enabled
+    interpolators: Arc<AsyncMutex<HashMap<String, Arc<Mutex<DetectionInterpolator>>>>>,
+    // Workers send each result here once it has been verified and masked
+    result_sender: mpsc::Sender<ProcessingResult>,
+    // Optional LLM check on ambiguous detections before they're published
+    verifier: Option<Arc<DetectionVerifier>>,
+    // Set when `privacy` is enabled
+    masker: Option<Arc<PrivacyMasker>>,
+    // Streams with a PTZ controller get their detections for auto-tracking
+    ptz: Option<Arc<PtzManager>>,
+    // Told each stream's scene condition, so its profile adapts to it
+    streams: Option<Arc<StreamRegistry>>,
+    // The running workers and what stops them; stop() waits for them so a
+    // restart doesn't leave the previous pool running alongside the new one
+    workers: Vec<JoinHandle<()>>,
+    shutdown: CancellationToken,
     state: Arc<Mutex<EngineState>>,
 }
 
@@ -60,32 +169,145 @@ struct EngineState {
     frames_processed: u64,
     error_count: u64,
     last_error: Option<String>,
+    // Latest density-based crowd count per source
+    crowd_counts: HashMap<String, f32>,
     start_time: chrono::DateTime<chrono::Utc>,
 }
 
+// Chainable alternative to filling in EngineConfig by hand:
+// `Engine::builder().gpu(true).threads(8).build().await`
+#[derive(Debug, Clone, Default)]
+pub struct EngineBuilder {
+    config: EngineConfig,
+}
+
+impl EngineBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn gpu(mut self, enabled: bool) -> Self {
+        self.config.enable_gpu = enabled;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.config.processing_threads = threads;
+        self
+    }
+
+    pub fn batch_size(mut self, size: usize) -> Self {
+        self.config.max_batch_size = size;
+        self
+    }
+
+    pub fn precision(mut self, precision: &str) -> Self {
+        self.config.model_precision = precision.to_string();
+        self
+    }
+
+    pub fn detection_threshold(mut self, threshold: f32) -> Self {
+        self.config.detection_threshold = threshold;
+        self
+    }
+
+    pub fn analytics(mut self, enabled: bool) -> Self {
+        self.config.enable_analytics = enabled;
+        self
+    }
+
+    pub fn event_buffer(mut self, size: usize) -> Self {
+        self.config.event_buffer = size;
+        self
+    }
+
+    pub fn privacy(mut self, privacy: PrivacyConfig) -> Self {
+        self.config.privacy = privacy;
+        self
+    }
+
+    pub fn into_config(self) -> Result<EngineConfig> {
+        if self.config.processing_threads == 0 {
+            return Err(anyhow::anyhow!("Engine needs at least one processing thread"));
+        }
+        if self.config.event_buffer == 0 {
+            return Err(anyhow::anyhow!("Engine event buffer must be at least 1"));
+        }
+        if self.config.max_batch_size == 0 {
+            return Err(anyhow::anyhow!("Engine batch size must be at least 1"));
+        }
+        if !(0.0..=1.0).contains(&self.config.detection_threshold) {
+            return Err(anyhow::anyhow!("Detection threshold must be between 0 and 1"));
+        }
+        Ok(self.config)
+    }
+
+    pub async fn build(self) -> Result<Engine> {
+        Engine::new(self.into_config()?).await
+    }
+}
+
 impl Engine {
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::new()
+    }
+
     pub async fn new(config: EngineConfig) -> Result<Self> {
         let (tx, rx) = mpsc::channel(config.max_batch_size);
         let (result_tx, result_rx) = mpsc::channel(config.max_batch_size);
 
+        let (events, _) = broadcast::channel(config.event_buffer.max(1));
+
+        let verifier = config.verification.as_ref()
+            .filter(|v| v.verification.enabled)
+            .map(build_verifier)
+            .transpose()?;
+        let shadow = match &config.detection {
+            Some(detection) => {
+                let model = build_detection_model(detection.clone()).await
+                    .context("Failed to load the engine's detection model")?;
+                let name = detection.detector.as_ref().map_or("ensemble", |d| d.name.as_str());
+                Some(Arc::new(ShadowDeployment::new(config.shadow.clone(), name, model)))
+            }
+            None => None,
+        };
+        let detection_model = shadow.clone().map(|s| s as Arc<dyn DetectionModel>);
+        let masker = if config.privacy.enabled {
+            Some(Arc::new(PrivacyMasker::new(config.privacy.clone())?))
+        } else {
+            None
+        };
         let gpu_manager = Arc::new(GPUManager::new(config.enable_gpu)?);
         let frame_processor = Arc::new(DefaultFrameProcessor::new(
             config.clone(),
             gpu_manager.clone(),
-            result_tx,
+            detection_model,
         ));
 
         let engine = Self {
             config,
             gpu_manager,
+            shadow,
             frame_processor,
             processing_queue: tx,
+            queued_frames: Arc::new(AsyncMutex::new(rx)),
             result_channel: result_rx,
+            events,
+            trackers: Arc::new(AsyncMutex::new(HashMap::new())),
+            interpolators: Arc::new(AsyncMutex::new(HashMap::new())),
+            result_sender: result_tx,
+            verifier,
+            masker,
+            ptz: None,
+            streams: None,
+            workers: Vec::new(),
+            shutdown: CancellationToken::new(),
             state: Arc::new(Mutex::new(EngineState {
                 is_running: false,
                 frames_processed: 0,
                 error_count: 0,
                 last_error: None,
+                crowd_counts: HashMap::new(),
                 start_time: chrono::Utc::now(),
             })),
         };
@@ -102,6 +324,7 @@ impl Engine {
         state.is_running = true;
         state.start_time = chrono::Utc::now();
         drop(state);
+        let _ = self.events.send(EngineEvent::StateChanged { running: true });
 
         self.initialize_workers().await?;
         Ok(())
@@ -115,35 +338,251 @@ impl Engine {
 
         state.is_running = false;
         drop(state);
+        self.shutdown.cancel();
+        for worker in self.workers.drain(..) {
+            if let Err(e) = worker.await {
+                log::warn!("Engine worker ended abnormally: {}", e);
+            }
+        }
+        let _ = self.events.send(EngineEvent::StateChanged { running: false });
+
+        // Live tracks end with the run so consumers see a death for every birth
+        for (_, (mut tracker, _)) in self.trackers.lock().await.drain() {
+            for event in tracker.flush() {
+                let _ = self.events.send(EngineEvent::Track { event });
+            }
+        }
 
         // Cleanup resources
         self.gpu_manager.cleanup().await?;
         Ok(())
     }
 
+    // What the model endpoints start, promote and roll back shadow
+    // candidates on; None without a `detection` model
+    pub fn shadow(&self) -> Option<Arc<ShadowDeployment>> {
+        self.shadow.clone()
+    }
+
+    // Replaces the one built from `verification`; takes effect for workers
+    // started after this call
+    pub fn set_verifier(&mut self, verifier: Arc<DetectionVerifier>) {
+        self.verifier = Some(verifier);
+    }
+
+    // Like set_verifier(), applies to workers started after this call
+    pub fn set_ptz(&mut self, ptz: Arc<PtzManager>) {
+        self.ptz = Some(ptz);
+    }
+
+    pub fn set_streams(&mut self, streams: Arc<StreamRegistry>) {
+        self.streams = Some(streams);
+    }
+
     pub async fn process_frame(&self, frame: Frame) -> Result<()> {
         self.processing_queue.send(frame).await
             .context("Failed to send frame to processing queue")?;
         Ok(())
     }
 
+    // Each subscriber gets every event from the point it subscribed; a
+    // subscriber that falls behind by more than `event_buffer` events sees
+    // `RecvError::Lagged` and skips ahead
+    pub fn subscribe(&self) -> broadcast::Receiver<EngineEvent> {
+        self.events.subscribe()
+    }
+
+    // Buffers up to `max_batch_size` results; while the buffer is full new
+    // results are dropped, so use subscribe() to see every frame
     pub async fn get_result(&mut self) -> Option<ProcessingResult> {
         self.result_channel.recv().await
     }
 
-    async fn initialize_workers(&self) -> Result<()> {
+    async fn initialize_workers(&mut self) -> Result<()> {
         let num_workers = self.config.processing_threads;
         let processor = self.frame_processor.clone();
-        
+        self.shutdown = CancellationToken::new();
+
         for _ in 0..num_workers {
             let processor = processor.clone();
-            tokio::spawn(async move {
-                while let Some(frame) = processor.process_frame().await {
-                    if let Err(e) = processor.process_frame(frame).await {
-                        log::error!("Frame processing error: {}", e);
+            let queue = self.queued_frames.clone();
+            let state = self.state.clone();
+            let events = self.events.clone();
+            let trackers = self.trackers.clone();
+            let tracking = self.config.tracking.clone();
+            let behaviors = self.config.behaviors.clone();
+            let verifier = self.verifier.clone();
+            let masker = self.masker.clone();
+            let ptz = self.ptz.clone();
+            let streams = self.streams.clone();
+            let interpolators = self.interpolators.clone();
+            let interpolation = self.config.interpolation.clone();
+            let results = self.result_sender.clone();
+            let shutdown = self.shutdown.clone();
+
+            self.workers.push(tokio::spawn(async move {
+                loop {
+                    let next = tokio::select! {
+                        biased;
+                        _ = shutdown.cancelled() => break,
+                        next = async { queue.lock().await.recv().await } => next,
+                    };
+                    let frame = match next {
+                        Some(frame) => frame,
+                        None => break,
+                    };
+
+                    let source = frame.metadata.source.clone();
+                    let stream_id = frame.metadata.stream_id.clone();
+                    let timestamp = frame.timestamp;
+                    let original = frame.clone();
+                    // Flow and box matching run on the blocking pool with
+                    // only this source's interpolator locked
+                    let interpolator = if interpolation.enabled {
+                        Some(interpolators.lock().await
+                            .entry(source.clone())
+                            .or_insert_with(|| Arc::new(Mutex::new(DetectionInterpolator::new(interpolation.clone()))))
+                            .clone())
+                    } else {
+                        None
+                    };
+                    let is_keyframe = interpolator.as_ref().is_none_or(|i| i.lock().unwrap().next_is_keyframe());
+                    let outcome = match interpolator {
+                        Some(interpolator) if !is_keyframe => interpolate(interpolator, frame).await,
+                        interpolator => {
+                            let keyframe = interpolator.map(|interpolator| (interpolator, frame.clone()));
+                            let outcome = processor.process_frame(frame).await;
+                            if let (Some((interpolator, keyframe)), Ok(result)) = (keyframe, &outcome) {
+                                let detections = result.detections.clone();
+                                let updated = tokio::task::spawn_blocking(move || {
+                                    interpolator.lock().unwrap().keyframe(&keyframe, &detections)
+                                }).await;
+                                if let Err(e) = updated.map_err(anyhow::Error::from).and_then(|r| r) {
+                                    log::warn!("Failed to update interpolation for {}: {}", source, e);
+                                }
+                            }
+                            outcome
+                        }
+                    };
+                    match outcome {
+                        Ok(mut result) => {
+                            // Verification crops, track snapshots and
+                            // behavior evidence are all cut from this image
+                            let image = match &masker {
+                                Some(masker) => match masker.mask(&original, &result.detections).await {
+                                    Ok(masked) => masked.data,
+                                    Err(e) => {
+                                        log::error!("Failed to mask frame {}, dropping it: {}", result.frame_id, e);
+                                        state.lock().unwrap().error_count += 1;
+                                        continue;
+                                    }
+                                },
+                                None => original.data.clone(),
+                            };
+                            // Interpolated boxes were already verified on
+                            // their keyframe
+                            let needs_verification = !result.interpolated && !result.detections.is_empty();
+                            if let Some(verifier) = verifier.as_ref().filter(|_| needs_verification) {
+                                // Anomalies are what page people, so every
+                                // detection on such a frame is worth checking
+                                let rule_triggered = result.analysis.iter()
+                                    .filter_map(|a| a.behavior_info.as_ref())
+                                    .any(|b| !b.anomalies.is_empty());
+                                let verified = verifier.verify(
+                                    &image,
+                                    &source,
+                                    result.frame_id,
+                                    &result.detections,
+                                    rule_triggered,
+                                ).await;
+                                result.detections = verifier.alertable(&verified);
+                                // With nothing left to back them up, the
+                                // frame's anomalies were false positives too
+                                if rule_triggered && result.detections.is_empty() {
+                                    for analysis in result.analysis.iter_mut() {
+                                        if let Some(behavior) = analysis.behavior_info.as_mut() {
+                                            behavior.anomalies.clear();
+                                        }
+                                    }
+                                }
+                            }
+                            {
+                                let mut state = state.lock().unwrap();
+                                state.frames_processed += 1;
+                                let density = result.analysis.as_ref().and_then(|a| a.density_info.as_ref());
+                                if let Some(density) = density {
+                                    state.crowd_counts.insert(source.clone(), density.count);
+                                }
+                            }
+                            let condition = result.analysis.as_ref()
+                                .and_then(|a| a.scene_info.as_ref())
+                                .and_then(|s| s.condition);
+                            if let (Some(streams), Some(stream_id), Some(condition)) = (&streams, &stream_id, condition) {
+                                streams.set_condition(stream_id, condition).await;
+                            }
+                            offer_result(&results, &result);
+                            publish_result(&events, &result);
+
+                            let controller = match (&ptz, &stream_id) {
+                                (Some(ptz), Some(stream_id)) => ptz.get(stream_id).await,
+                                _ => None,
+                            };
+                            if let Some(controller) = controller {
+                                // The camera round trip mustn't hold up the worker
+                                let detections = result.detections.clone();
+                                let (width, height) = (original.metadata.width as f32, original.metadata.height as f32);
+                                let stream_id = stream_id.clone().unwrap_or_default();
+                                tokio::spawn(async move {
+                                    if let Err(e) = controller.on_detections(&detections, width, height).await {
+                                        log::warn!("PTZ auto-tracking failed for stream {}: {}", stream_id, e);
+                                    }
+                                });
+                            }
+
+                            if tracking.enabled {
+                                let mut trackers = trackers.lock().await;
+                                let (tracker, detectors) = trackers.entry(source.clone())
+                                    .or_insert_with(|| (
+                                        Tracker::new(&source, tracking.clone()),
+                                        BehaviorDetectors::new(&behaviors),
+                                    ));
+
+                                let track_events = tracker.update(result.frame_id, Some(&image), &result.detections);
+                                for event in track_events {
+                                    let _ = events.send(EngineEvent::Track { event });
+                                }
+
+                                if !detectors.is_empty() {
+                                    let frame_ref = FrameRef {
+                                        frame_id: result.frame_id,
+                                        timestamp,
+                                        image: Some(&image),
+                                    };
+                                    for anomaly in detectors.update(&frame_ref, &tracker.active_tracks()) {
+                                        let _ = events.send(EngineEvent::Anomaly {
+                                            frame_id: result.frame_id,
+                                            anomaly: Anomaly::from(&anomaly),
+                                        });
+                                        let _ = events.send(EngineEvent::Behavior {
+                                            frame_id: result.frame_id,
+                                            anomaly,
+                                        });
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            log::error!("Frame processing error: {}", e);
+                            {
+                                let mut state = state.lock().unwrap();
+                                state.error_count += 1;
+                                state.last_error = Some(e.to_string());
+                            }
+                            let _ = events.send(EngineEvent::Error { message: e.to_string() });
+                        }
                     }
                 }
-            });
+            }));
         }
 
         Ok(())
@@ -154,16 +593,67 @@ impl Engine {
         Ok(EngineMetrics {
             frames_processed: state.frames_processed,
             error_count: state.error_count,
+            crowd_counts: state.crowd_counts.clone(),
             uptime: chrono::Utc::now() - state.start_time,
             is_running: state.is_running,
         })
     }
 }
 
+async fn interpolate(interpolator: Arc<Mutex<DetectionInterpolator>>, frame: Frame) -> Result<ProcessingResult> {
+    let frame_id = frame.id;
+    let detections = tokio::task::spawn_blocking(move || interpolator.lock().unwrap().predict(&frame))
+        .await
+        .context("Interpolation task failed")??;
+    let result = ProcessingResult {
+        frame_id,
+        detections,
+        analysis: None,
+        inference: None,
+        timestamp: chrono::Utc::now(),
+        interpolated: true,
+    };
+    Ok(result)
+}
+
+// Nothing has to call get_result(), so once the channel is full results
+// are dropped rather than stalling the workers until someone does
+fn offer_result(results: &mpsc::Sender<ProcessingResult>, result: &ProcessingResult) {
+    if let Err(mpsc::error::TrySendError::Full(_)) = results.try_send(result.clone()) {
+        log::debug!("Result channel is full, dropping result for frame {}", result.frame_id);
+    }
+}
+
+fn publish_result(events: &broadcast::Sender<EngineEvent>, result: &ProcessingResult) {
+    let _ = events.send(EngineEvent::FrameProcessed {
+        frame_id: result.frame_id,
+        detection_count: result.detections.len(),
+        timestamp: result.timestamp,
+    });
+
+    if !result.detections.is_empty() {
+        let _ = events.send(EngineEvent::DetectionBatch {
+            frame_id: result.frame_id,
+            detections: result.detections.clone(),
+        });
+    }
+
+    let anomalies = result.analysis.iter()
+        .filter_map(|a| a.behavior_info.as_ref())
+        .flat_map(|b| b.anomalies.iter());
+    for anomaly in anomalies {
+        let _ = events.send(EngineEvent::Anomaly {
+            frame_id: result.frame_id,
+            anomaly: anomaly.clone(),
+        });
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct EngineMetrics {
     pub frames_processed: u64,
     pub error_count: u64,
+    pub crowd_counts: HashMap<String, f32>,
     pub uptime: chrono::Duration,
     pub is_running: bool,
 }
@@ -171,19 +661,19 @@ pub struct EngineMetrics {
 struct DefaultFrameProcessor {
     config: EngineConfig,
     gpu_manager: Arc<GPUManager>,
-    result_sender: mpsc::Sender<ProcessingResult>,
+    detection_model: Option<Arc<dyn DetectionModel>>,
 }
 
 impl DefaultFrameProcessor {
     fn new(
         config: EngineConfig,
         gpu_manager: Arc<GPUManager>,
-        result_sender: mpsc::Sender<ProcessingResult>,
+        detection_model: Option<Arc<dyn DetectionModel>>,
     ) -> Self {
         Self {
             config,
             gpu_manager,
-            result_sender,
+            detection_model,
         }
     }
 }
@@ -192,7 +682,9 @@ impl DefaultFrameProcessor {
 impl FrameProcessor for DefaultFrameProcessor {
     async fn process_frame(&self, frame: Frame) -> Result<ProcessingResult> {
         // Process frame using GPU if available
-        let detections = if self.config.enable_gpu {
+        let detections = if let Some(model) = &self.detection_model {
+            model.detect(&frame).await?
+        } else if self.config.enable_gpu {
             self.gpu_manager.detect_objects(&frame).await?
         } else {
             vec![] // CPU fallback implementation
@@ -205,18 +697,14 @@ impl FrameProcessor for DefaultFrameProcessor {
             None
         };
 
-        let result = ProcessingResult {
+        Ok(ProcessingResult {
             frame_id: frame.id,
             detections,
             analysis,
             inference: None, // Add inference results if needed
             timestamp: chrono::Utc::now(),
-        };
-
-        self.result_sender.send(result.clone()).await
-            .context("Failed to send processing result")?;
-
-        Ok(result)
+            interpolated: false,
+        })
     }
 }
 
@@ -226,5 +714,6 @@ impl Drop for Engine {
         if let Ok(mut state) = self.state.lock() {
             state.is_running = false;
         }
+        self.shutdown.cancel();
     }
 }
\ No newline at end of file
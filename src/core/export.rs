@@ -0,0 +1,280 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use arrow::array::{
+    ArrayRef, Float32Array, StringArray, TimestampMillisecondArray, UInt64Array,
+};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+#[cfg(feature = "vision")]
+use crate::vision::detector::Detection;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportConfig {
+    pub output_dir: String,
+    pub schedule_interval_secs: Option<u64>,
+    pub datasets: Vec<ExportDataset>,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            output_dir: String::from("exports"),
+            schedule_interval_secs: None,
+            datasets: vec![
+                #[cfg(feature = "vision")]
+                ExportDataset::Detections,
+                ExportDataset::Tracks,
+                ExportDataset::Metrics,
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportDataset {
+    #[cfg(feature = "vision")]
+    Detections,
+    Tracks,
+    Metrics,
+}
+
+impl ExportDataset {
+    fn file_prefix(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "vision")]
+            ExportDataset::Detections => "detections",
+            ExportDataset::Tracks => "tracks",
+            ExportDataset::Metrics => "metrics",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ExportRange {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackRecord {
+    pub track_id: u64,
+    pub stream_id: String,
+    pub class_name: String,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub frames: u64,
+    pub avg_confidence: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsRecord {
+    pub timestamp: DateTime<Utc>,
+    pub frames_processed: u64,
+    pub fps: f32,
+    pub error_count: u64,
+    pub queue_size: u64,
+    pub processing_latency: f32,
+    // Latest density estimate, when the density analyzer is running
+    pub crowd_count: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportManifest {
+    pub range: ExportRange,
+    pub files: Vec<ExportedFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedFile {
+    pub dataset: ExportDataset,
+    pub path: PathBuf,
+    pub rows: usize,
+}
+
+// Where exported rows come from; implemented by whatever stores analytics
+#[async_trait]
+pub trait AnalyticsSource: Send + Sync {
+    #[cfg(feature = "vision")]
+    async fn detections(&self, range: ExportRange) -> Result<Vec<Detection>>;
+    async fn tracks(&self, range: ExportRange) -> Result<Vec<TrackRecord>>;
+    async fn metrics(&self, range: ExportRange) -> Result<Vec<MetricsRecord>>;
+}
+
+pub struct ParquetExporter {
+    config: ExportConfig,
+    source: Arc<dyn AnalyticsSource>,
+}
+
+impl ParquetExporter {
+    pub fn new(config: ExportConfig, source: Arc<dyn AnalyticsSource>) -> Self {
+        Self { config, source }
+    }
+
+    pub async fn export(&self, range: ExportRange, datasets: &[ExportDataset]) -> Result<ExportManifest> {
+        if range.end <= range.start {
+            return Err(anyhow::anyhow!("Export range end must be after start"));
+        }
+
+        tokio::fs::create_dir_all(&self.config.output_dir).await
+            .context("Failed to create export directory")?;
+
+        let mut files = Vec::with_capacity(datasets.len());
+        for dataset in datasets {
+            let batch = match dataset {
+                #[cfg(feature = "vision")]
+                ExportDataset::Detections => detections_batch(&self.source.detections(range).await?)?,
+                ExportDataset::Tracks => tracks_batch(&self.source.tracks(range).await?)?,
+                ExportDataset::Metrics => metrics_batch(&self.source.metrics(range).await?)?,
+            };
+
+            let path = Path::new(&self.config.output_dir).join(format!(
+                "{}_{}_{}.parquet",
+                dataset.file_prefix(),
+                range.start.format("%Y%m%dT%H%M%S"),
+                range.end.format("%Y%m%dT%H%M%S"),
+            ));
+            let rows = batch.num_rows();
+
+            let target = path.clone();
+            tokio::task::spawn_blocking(move || write_parquet(&target, &batch)).await??;
+
+            files.push(ExportedFile { dataset: *dataset, path, rows });
+        }
+
+        Ok(ExportManifest { range, files })
+    }
+
+    pub fn start_schedule(self: &Arc<Self>) {
+        let Some(interval_secs) = self.config.schedule_interval_secs else {
+            return;
+        };
+        let exporter = self.clone();
+
+        tokio::spawn(async move {
+            let period = chrono::Duration::seconds(interval_secs as i64);
+            let mut interval = tokio::time::interval(
+                tokio::time::Duration::from_secs(interval_secs)
+            );
+            // The first tick completes immediately; skip it so we export full periods
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+                let end = Utc::now();
+                let range = ExportRange { start: end - period, end };
+
+                if let Err(e) = exporter.export(range, &exporter.config.datasets).await {
+                    log::error!("Scheduled analytics export failed: {}", e);
+                }
+            }
+        });
+    }
+}
+
+fn timestamp_field(name: &str) -> Field {
+    Field::new(name, DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())), false)
+}
+
+fn timestamp_array(values: impl Iterator<Item = i64>) -> ArrayRef {
+    Arc::new(TimestampMillisecondArray::from_iter_values(values).with_timezone("UTC"))
+}
+
+#[cfg(feature = "vision")]
+fn detections_batch(detections: &[Detection]) -> Result<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("frame_id", DataType::UInt64, false),
+        timestamp_field("timestamp"),
+        Field::new("class_id", DataType::UInt64, false),
+        Field::new("class_name", DataType::Utf8, false),
+        Field::new("confidence", DataType::Float32, false),
+        Field::new("x", DataType::Float32, false),
+        Field::new("y", DataType::Float32, false),
+        Field::new("width", DataType::Float32, false),
+        Field::new("height", DataType::Float32, false),
+    ]));
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt64Array::from_iter_values(detections.iter().map(|d| d.frame_id))),
+        timestamp_array(detections.iter().map(|d| d.timestamp.timestamp_millis())),
+        Arc::new(UInt64Array::from_iter_values(detections.iter().map(|d| d.class_id as u64))),
+        Arc::new(StringArray::from_iter_values(detections.iter().map(|d| d.class_name.as_str()))),
+        Arc::new(Float32Array::from_iter_values(detections.iter().map(|d| d.confidence))),
+        Arc::new(Float32Array::from_iter_values(detections.iter().map(|d| d.bbox.x))),
+        Arc::new(Float32Array::from_iter_values(detections.iter().map(|d| d.bbox.y))),
+        Arc::new(Float32Array::from_iter_values(detections.iter().map(|d| d.bbox.width))),
+        Arc::new(Float32Array::from_iter_values(detections.iter().map(|d| d.bbox.height))),
+    ];
+
+    RecordBatch::try_new(schema, columns).context("Failed to build detections batch")
+}
+
+fn tracks_batch(tracks: &[TrackRecord]) -> Result<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("track_id", DataType::UInt64, false),
+        Field::new("stream_id", DataType::Utf8, false),
+        Field::new("class_name", DataType::Utf8, false),
+        timestamp_field("first_seen"),
+        timestamp_field("last_seen"),
+        Field::new("frames", DataType::UInt64, false),
+        Field::new("avg_confidence", DataType::Float32, false),
+    ]));
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt64Array::from_iter_values(tracks.iter().map(|t| t.track_id))),
+        Arc::new(StringArray::from_iter_values(tracks.iter().map(|t| t.stream_id.as_str()))),
+        Arc::new(StringArray::from_iter_values(tracks.iter().map(|t| t.class_name.as_str()))),
+        timestamp_array(tracks.iter().map(|t| t.first_seen.timestamp_millis())),
+        timestamp_array(tracks.iter().map(|t| t.last_seen.timestamp_millis())),
+        Arc::new(UInt64Array::from_iter_values(tracks.iter().map(|t| t.frames))),
+        Arc::new(Float32Array::from_iter_values(tracks.iter().map(|t| t.avg_confidence))),
+    ];
+
+    RecordBatch::try_new(schema, columns).context("Failed to build tracks batch")
+}
+
+fn metrics_batch(metrics: &[MetricsRecord]) -> Result<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        timestamp_field("timestamp"),
+        Field::new("frames_processed", DataType::UInt64, false),
+        Field::new("fps", DataType::Float32, false),
+        Field::new("error_count", DataType::UInt64, false),
+        Field::new("queue_size", DataType::UInt64, false),
+        Field::new("processing_latency", DataType::Float32, false),
+        Field::new("crowd_count", DataType::Float32, true),
+    ]));
+
+    let columns: Vec<ArrayRef> = vec![
+        timestamp_array(metrics.iter().map(|m| m.timestamp.timestamp_millis())),
+        Arc::new(UInt64Array::from_iter_values(metrics.iter().map(|m| m.frames_processed))),
+        Arc::new(Float32Array::from_iter_values(metrics.iter().map(|m| m.fps))),
+        Arc::new(UInt64Array::from_iter_values(metrics.iter().map(|m| m.error_count))),
+        Arc::new(UInt64Array::from_iter_values(metrics.iter().map(|m| m.queue_size))),
+        Arc::new(Float32Array::from_iter_values(metrics.iter().map(|m| m.processing_latency))),
+        Arc::new(Float32Array::from(metrics.iter().map(|m| m.crowd_count).collect::<Vec<_>>())),
+    ];
+
+    RecordBatch::try_new(schema, columns).context("Failed to build metrics batch")
+}
+
+fn write_parquet(path: &Path, batch: &RecordBatch) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create export file {}", path.display()))?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(props))?;
+    writer.write(batch)?;
+    writer.close()?;
+    Ok(())
+}
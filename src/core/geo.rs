@@ -0,0 +1,184 @@
+#![cfg(feature = "vision")]
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::core::streams::StreamRegistry;
+use crate::vision::detector::Detection;
+
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct GeoPoint {
+    pub lat: f64,
+    pub lon: f64,
+    #[serde(default)]
+    pub altitude_m: Option<f64>,
+}
+
+impl GeoPoint {
+    pub fn new(lat: f64, lon: f64) -> Self {
+        Self { lat, lon, altitude_m: None }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(-90.0..=90.0).contains(&self.lat) || !(-180.0..=180.0).contains(&self.lon) {
+            return Err(anyhow::anyhow!("Invalid coordinates: {}, {}", self.lat, self.lon));
+        }
+        Ok(())
+    }
+
+    // Great-circle distance in meters
+    pub fn distance_to(&self, other: &GeoPoint) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.lon - self.lon).to_radians();
+
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeoEvent {
+    pub id: String,
+    pub stream_id: String,
+    pub kind: String,
+    pub location: GeoPoint,
+    pub timestamp: DateTime<Utc>,
+    pub properties: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GeoQuery {
+    pub lat: f64,
+    pub lon: f64,
+    pub radius_m: f64,
+    #[serde(default)]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeoIndexConfig {
+    pub max_events: usize,
+}
+
+impl Default for GeoIndexConfig {
+    fn default() -> Self {
+        Self { max_events: 100_000 }
+    }
+}
+
+// Events are tagged with their camera's coordinates at record time, so
+// moving a camera later doesn't rewrite where past events happened
+pub struct GeoEventIndex {
+    config: GeoIndexConfig,
+    streams: Arc<StreamRegistry>,
+    events: RwLock<VecDeque<GeoEvent>>,
+}
+
+impl GeoEventIndex {
+    pub fn new(config: GeoIndexConfig, streams: Arc<StreamRegistry>) -> Self {
+        Self {
+            config,
+            streams,
+            events: RwLock::new(VecDeque::new()),
+        }
+    }
+
+    pub async fn record(
+        &self,
+        stream_id: &str,
+        kind: &str,
+        timestamp: DateTime<Utc>,
+        properties: serde_json::Map<String, Value>,
+    ) -> Option<GeoEvent> {
+        let location = self.streams.get(stream_id).await?.geo?;
+        let event = GeoEvent {
+            id: Uuid::new_v4().to_string(),
+            stream_id: stream_id.to_string(),
+            kind: kind.to_string(),
+            location,
+            timestamp,
+            properties,
+        };
+
+        let mut events = self.events.write().await;
+        events.push_back(event.clone());
+        while events.len() > self.config.max_events {
+            events.pop_front();
+        }
+        Some(event)
+    }
+
+    pub async fn record_detections(&self, stream_id: &str, detections: &[Detection]) {
+        for detection in detections {
+            let mut properties = serde_json::Map::new();
+            properties.insert("class".to_string(), json!(detection.class_name));
+            properties.insert("confidence".to_string(), json!(detection.confidence));
+            properties.insert("frame_id".to_string(), json!(detection.frame_id));
+
+            if self.record(stream_id, "detection", detection.timestamp, properties).await.is_none() {
+                // Stream has no coordinates configured
+                return;
+            }
+        }
+    }
+
+    pub async fn within_radius(&self, query: &GeoQuery) -> Result<Vec<GeoEvent>> {
+        let center = GeoPoint::new(query.lat, query.lon);
+        center.validate()?;
+        if query.radius_m <= 0.0 {
+            return Err(anyhow::anyhow!("radius_m must be positive"));
+        }
+
+        let events = self.events.read().await;
+        Ok(events.iter()
+            .rev()
+            .filter(|e| query.kind.as_ref().map_or(true, |k| &e.kind == k))
+            .filter(|e| query.since.map_or(true, |since| e.timestamp >= since))
+            .filter(|e| query.until.map_or(true, |until| e.timestamp <= until))
+            .filter(|e| center.distance_to(&e.location) <= query.radius_m)
+            .take(query.limit.unwrap_or(1000))
+            .cloned()
+            .collect())
+    }
+}
+
+// RFC 7946 FeatureCollection; GeoJSON coordinates are [lon, lat]
+pub fn to_geojson(events: &[GeoEvent]) -> Value {
+    let features: Vec<Value> = events.iter()
+        .map(|e| {
+            let mut properties = e.properties.clone();
+            properties.insert("id".to_string(), json!(e.id));
+            properties.insert("stream_id".to_string(), json!(e.stream_id));
+            properties.insert("kind".to_string(), json!(e.kind));
+            properties.insert("timestamp".to_string(), json!(e.timestamp.to_rfc3339()));
+
+            let mut coordinates = vec![e.location.lon, e.location.lat];
+            if let Some(altitude) = e.location.altitude_m {
+                coordinates.push(altitude);
+            }
+
+            json!({
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": coordinates },
+                "properties": properties,
+            })
+        })
+        .collect();
+
+    json!({ "type": "FeatureCollection", "features": features })
+}
@@ -0,0 +1,398 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+
+use crate::core::llm::types::Message;
+
+// Which language the agent answers in, and translations of API error
+// messages. Languages are ISO 639-1 codes ("en", "fr"). For a turn the
+// language is, first match wins:
+//
+//   1. the session's preference
+//   2. the language the user wrote in, when detection is confident
+//   3. the tenant's preference
+//   4. default_language
+//
+// Error catalogs are YAML files named <code>.yaml in messages_dir, mapping
+// the English message to its translation. `{}` stands for a variable part
+// and is carried over in order:
+//
+//   "Incident not found: {}": "Incidente no encontrado: {}"
+
+// ISO 639-1 to 639-3, the codes the detector works with
+const LANGUAGES: &[(&str, &str)] = &[
+    ("ar", "ara"), ("bg", "bul"), ("cs", "ces"), ("da", "dan"), ("de", "deu"),
+    ("el", "ell"), ("en", "eng"), ("es", "spa"), ("fi", "fin"), ("fr", "fra"),
+    ("he", "heb"), ("hi", "hin"), ("hu", "hun"), ("id", "ind"), ("it", "ita"),
+    ("ja", "jpn"), ("ko", "kor"), ("nl", "nld"), ("no", "nob"), ("pl", "pol"),
+    ("pt", "por"), ("ro", "ron"), ("ru", "rus"), ("sv", "swe"), ("th", "tha"),
+    ("tr", "tur"), ("uk", "ukr"), ("vi", "vie"), ("zh", "cmn"),
+];
+
+fn to_iso639_3(code: &str) -> Option<&'static str> {
+    LANGUAGES.iter().find(|(short, _)| *short == code).map(|(_, long)| *long)
+}
+
+fn to_iso639_1(code: &str) -> Option<&'static str> {
+    LANGUAGES.iter().find(|(_, long)| *long == code).map(|(short, _)| *short)
+}
+
+// "fr-CA" and "FR" both mean "fr"
+pub fn normalize(code: &str) -> Option<String> {
+    let primary = code.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+    to_iso639_3(&primary).map(|_| primary)
+}
+
+pub fn language_name(code: &str) -> String {
+    to_iso639_3(code)
+        .and_then(whatlang::Lang::from_code)
+        .map(|lang| lang.eng_name().to_string())
+        .unwrap_or_else(|| code.to_string())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LanguageConfig {
+    pub default_language: String,
+    // Empty allows every language the detector knows
+    pub supported: Vec<String>,
+    pub detect_input: bool,
+    // Shorter messages ("ok", "thanks") are too ambiguous to detect
+    pub min_detect_chars: usize,
+    pub min_confidence: f64,
+    pub tenants: HashMap<String, String>,
+    pub messages_dir: Option<String>,
+    // Caps the session and tenant preferences set through the API; each
+    // map refuses new keys once full
+    pub max_preferences: usize,
+}
+
+impl Default for LanguageConfig {
+    fn default() -> Self {
+        Self {
+            default_language: "en".to_string(),
+            supported: Vec::new(),
+            detect_input: true,
+            min_detect_chars: 20,
+            min_confidence: 0.5,
+            tenants: HashMap::new(),
+            messages_dir: None,
+            max_preferences: 10_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LanguageSource {
+    Session,
+    Detected,
+    Tenant,
+    Default,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedLanguage {
+    pub code: String,
+    pub name: String,
+    pub source: LanguageSource,
+}
+
+// An English message with `{}` placeholders and its translation
+#[derive(Debug, Clone)]
+struct CatalogEntry {
+    parts: Vec<String>,
+    translation: String,
+}
+
+impl CatalogEntry {
+    fn new(pattern: &str, translation: &str) -> Self {
+        Self {
+            parts: pattern.split("{}").map(|s| s.to_string()).collect(),
+            translation: translation.to_string(),
+        }
+    }
+
+    // The variable parts of `message`, if it has this entry's shape
+    fn captures<'a>(&self, message: &'a str) -> Option<Vec<&'a str>> {
+        let (first, rest) = self.parts.split_first()?;
+        let mut remaining = message.strip_prefix(first.as_str())?;
+        let mut captures = Vec::new();
+        for (i, part) in rest.iter().enumerate() {
+            let end = if i == rest.len() - 1 {
+                // The last literal anchors at the end of the message
+                remaining.strip_suffix(part.as_str()).map(|s| s.len())?
+            } else {
+                remaining.find(part.as_str())?
+            };
+            captures.push(&remaining[..end]);
+            remaining = &remaining[end + part.len()..];
+        }
+        remaining.is_empty().then_some(captures)
+    }
+
+    fn translate(&self, message: &str) -> Option<String> {
+        let captures = self.captures(message)?;
+        let mut output = String::new();
+        let mut captures = captures.into_iter();
+        let mut pieces = self.translation.split("{}").peekable();
+        while let Some(piece) = pieces.next() {
+            output.push_str(piece);
+            if pieces.peek().is_some() {
+                output.push_str(captures.next().unwrap_or(""));
+            }
+        }
+        Some(output)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    // Longest patterns first, so the most specific one wins
+    entries: Vec<CatalogEntry>,
+}
+
+impl Catalog {
+    pub fn from_entries(entries: &HashMap<String, String>) -> Self {
+        let mut entries: Vec<CatalogEntry> = entries.iter()
+            .map(|(pattern, translation)| CatalogEntry::new(pattern, translation))
+            .collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.parts.iter().map(String::len).sum::<usize>()));
+        Self { entries }
+    }
+
+    pub fn translate(&self, message: &str) -> Option<String> {
+        self.entries.iter().find_map(|entry| entry.translate(message))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+pub struct Localizer {
+    config: LanguageConfig,
+    catalogs: Arc<RwLock<HashMap<String, Catalog>>>,
+    sessions: Arc<RwLock<HashMap<String, String>>>,
+    tenants: Arc<RwLock<HashMap<String, String>>>,
+}
+
+impl Localizer {
+    pub async fn new(config: LanguageConfig) -> Result<Self> {
+        if normalize(&config.default_language).is_none() {
+            return Err(anyhow::anyhow!("Unknown default language: {}", config.default_language));
+        }
+        let mut tenants = HashMap::new();
+        for (tenant, code) in &config.tenants {
+            let code = normalize(code)
+                .ok_or_else(|| anyhow::anyhow!("Unknown language {} for tenant {}", code, tenant))?;
+            tenants.insert(tenant.clone(), code);
+        }
+
+        let localizer = Self {
+            config,
+            catalogs: Arc::new(RwLock::new(HashMap::new())),
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+            tenants: Arc::new(RwLock::new(tenants)),
+        };
+        localizer.load_catalogs().await?;
+        Ok(localizer)
+    }
+
+    pub fn config(&self) -> &LanguageConfig {
+        &self.config
+    }
+
+    pub fn default_language(&self) -> String {
+        normalize(&self.config.default_language).unwrap_or_else(|| "en".to_string())
+    }
+
+    pub async fn load_catalogs(&self) -> Result<usize> {
+        let Some(dir) = &self.config.messages_dir else {
+            return Ok(0);
+        };
+        let dir = Path::new(dir);
+        if !tokio::fs::try_exists(dir).await? {
+            log::warn!("Message catalog directory {} does not exist", dir.display());
+            return Ok(0);
+        }
+
+        let mut catalogs = HashMap::new();
+        let mut entries = tokio::fs::read_dir(dir).await
+            .with_context(|| format!("Failed to read {}", dir.display()))?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if !matches!(path.extension().and_then(|e| e.to_str()), Some("yaml" | "yml")) {
+                continue;
+            }
+            let Some(code) = path.file_stem().and_then(|s| s.to_str()).and_then(normalize) else {
+                log::warn!("Skipping message catalog {}: not a language code", path.display());
+                continue;
+            };
+            let contents = tokio::fs::read_to_string(&path).await?;
+            let entries: HashMap<String, String> = serde_yaml::from_str(&contents)
+                .with_context(|| format!("Invalid message catalog {}", path.display()))?;
+            catalogs.insert(code, Catalog::from_entries(&entries));
+        }
+
+        let count = catalogs.len();
+        *self.catalogs.write().await = catalogs;
+        Ok(count)
+    }
+
+    pub async fn set_catalog(&self, code: &str, catalog: Catalog) -> Result<()> {
+        let code = self.check(code)?;
+        self.catalogs.write().await.insert(code, catalog);
+        Ok(())
+    }
+
+    fn is_supported(&self, code: &str) -> bool {
+        self.config.supported.is_empty()
+            || self.config.supported.iter().any(|s| normalize(s).as_deref() == Some(code))
+    }
+
+    // Normalized code, if it is known and supported
+    pub fn check(&self, code: &str) -> Result<String> {
+        let normalized = normalize(code).ok_or_else(|| anyhow::anyhow!("Unknown language: {}", code))?;
+        if !self.is_supported(&normalized) {
+            return Err(anyhow::anyhow!("Unsupported language: {}", code));
+        }
+        Ok(normalized)
+    }
+
+    pub fn detect(&self, text: &str) -> Option<String> {
+        if text.trim().chars().count() < self.config.min_detect_chars {
+            return None;
+        }
+        let info = whatlang::detect(text)?;
+        if !info.is_reliable() || info.confidence() < self.config.min_confidence {
+            return None;
+        }
+        let code = to_iso639_1(info.lang().code())?;
+        self.is_supported(code).then(|| code.to_string())
+    }
+
+    pub async fn set_session(&self, session_id: &str, code: Option<&str>) -> Result<()> {
+        self.set_preference(&self.sessions, session_id, code).await
+    }
+
+    pub async fn set_tenant(&self, tenant: &str, code: Option<&str>) -> Result<()> {
+        self.set_preference(&self.tenants, tenant, code).await
+    }
+
+    // For deleted sessions, so their preferences don't pile up
+    pub async fn forget_session(&self, session_id: &str) {
+        self.sessions.write().await.remove(session_id);
+    }
+
+    async fn set_preference(&self, map: &RwLock<HashMap<String, String>>, key: &str, code: Option<&str>) -> Result<()> {
+        let Some(code) = code else {
+            map.write().await.remove(key);
+            return Ok(());
+        };
+        let code = self.check(code)?;
+        let mut map = map.write().await;
+        if !map.contains_key(key) && map.len() >= self.config.max_preferences {
+            return Err(anyhow::anyhow!("Too many language preferences, at most {}", self.config.max_preferences));
+        }
+        map.insert(key.to_string(), code);
+        Ok(())
+    }
+
+    pub async fn session_language(&self, session_id: &str) -> Option<String> {
+        self.sessions.read().await.get(session_id).cloned()
+    }
+
+    pub async fn tenant_language(&self, tenant: &str) -> Option<String> {
+        self.tenants.read().await.get(tenant).cloned()
+    }
+
+    pub async fn resolve(&self, session_id: Option<&str>, tenant: Option<&str>, input: &str) -> ResolvedLanguage {
+        let resolved = |code: String, source| ResolvedLanguage { name: language_name(&code), code, source };
+
+        if let Some(code) = match session_id {
+            Some(id) => self.session_language(id).await,
+            None => None,
+        } {
+            return resolved(code, LanguageSource::Session);
+        }
+        if self.config.detect_input {
+            if let Some(code) = self.detect(input) {
+                return resolved(code, LanguageSource::Detected);
+            }
+        }
+        if let Some(code) = match tenant {
+            Some(tenant) => self.tenant_language(tenant).await,
+            None => None,
+        } {
+            return resolved(code, LanguageSource::Tenant);
+        }
+        resolved(self.default_language(), LanguageSource::Default)
+    }
+
+    // The message in `code`, or unchanged when there is no translation
+    pub async fn localize(&self, message: &str, code: &str) -> String {
+        if code == "en" {
+            return message.to_string();
+        }
+        self.catalogs.read().await.get(code)
+            .and_then(|catalog| catalog.translate(message))
+            .unwrap_or_else(|| message.to_string())
+    }
+
+    pub async fn has_catalog(&self, code: &str) -> bool {
+        code == "en" || self.catalogs.read().await.contains_key(code)
+    }
+}
+
+pub fn language_instruction(language: &ResolvedLanguage) -> String {
+    format!(
+        "Always respond in {} ({}), whatever language documents, tool results or earlier \
+         messages are in. Keep identifiers, stream names and quoted log lines as they are.",
+        language.name, language.code,
+    )
+}
+
+// Appends the instruction to the system message, adding one when there is
+// none. Goes last so it overrides language hints in the rest of the prompt.
+pub fn enforce_language(messages: &mut Vec<Message>, language: &ResolvedLanguage) {
+    let instruction = language_instruction(language);
+    match messages.iter_mut().find(|m| m.role == "system") {
+        Some(system) => {
+            system.content.push_str("\n\n");
+            system.content.push_str(&instruction);
+        }
+        None => messages.insert(0, Message::new("system", &instruction)),
+    }
+}
+
+// Accept-Language, best quality first: "fr-CH, fr;q=0.9, en;q=0.8"
+pub fn parse_accept_language(header: &str) -> Vec<String> {
+    let mut ranked: Vec<(f32, usize, String)> = header.split(',')
+        .enumerate()
+        .filter_map(|(index, item)| {
+            let mut parts = item.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .find_map(|p| p.trim().strip_prefix("q=").and_then(|q| q.parse::<f32>().ok()))
+                .unwrap_or(1.0);
+            let code = normalize(tag)?;
+            (quality > 0.0).then_some((quality, index, code))
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    let mut codes: Vec<String> = Vec::new();
+    for (_, _, code) in ranked {
+        if !codes.contains(&code) {
+            codes.push(code);
+        }
+    }
+    codes
+}
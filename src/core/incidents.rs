@@ -0,0 +1,360 @@
+use std::sync::Arc;
+use tokio::sync::{broadcast, RwLock};
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use crate::core::alerts::Alert;
+use crate::core::notify::Severity;
+
+// Stands in for the author of notes whose writer asked to be erased
+pub const ERASED_AUTHOR: &str = "[erased]";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IncidentStatus {
+    Open,
+    Acknowledged,
+    Resolved,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncidentNote {
+    pub author: String,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// A pointer to recorded footage; the recording itself stays wherever the
+// recorder put it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvidenceClip {
+    pub stream_id: String,
+    pub uri: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Incident {
+    pub id: String,
+    pub title: String,
+    pub severity: Severity,
+    pub status: IncidentStatus,
+    pub assignee: Option<String>,
+    // Alert keys grouped into this incident, oldest first
+    pub alerts: Vec<String>,
+    pub alert_count: u64,
+    pub group_key: Option<String>,
+    pub notes: Vec<IncidentNote>,
+    pub evidence: Vec<EvidenceClip>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IncidentEvent {
+    Opened { incident: Incident },
+    AlertGrouped { incident_id: String, alert_key: String, severity: Severity },
+    StatusChanged { incident_id: String, status: IncidentStatus, by: Option<String> },
+    Assigned { incident_id: String, assignee: Option<String> },
+    NoteAdded { incident_id: String, note: IncidentNote },
+    EvidenceAttached { incident_id: String, clip: EvidenceClip },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IncidentConfig {
+    // Alerts sharing these label values land in the same open incident
+    pub group_by: Vec<String>,
+    // A grouped alert only joins an incident updated within this window;
+    // later ones open a new incident
+    pub group_window_secs: i64,
+    pub max_resolved: usize,
+    pub event_buffer: usize,
+}
+
+impl Default for IncidentConfig {
+    fn default() -> Self {
+        Self {
+            group_by: vec!["stream_id".to_string()],
+            group_window_secs: 900,
+            max_resolved: 1000,
+            event_buffer: 256,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct IncidentFilter {
+    pub status: Option<IncidentStatus>,
+    pub assignee: Option<String>,
+    pub min_severity: Option<Severity>,
+    pub limit: Option<usize>,
+}
+
+pub struct IncidentManager {
+    config: IncidentConfig,
+    incidents: Arc<RwLock<HashMap<String, Incident>>>,
+    events: broadcast::Sender<IncidentEvent>,
+}
+
+impl IncidentManager {
+    pub fn new(config: IncidentConfig) -> Self {
+        let (events, _) = broadcast::channel(config.event_buffer.max(1));
+        Self {
+            config,
+            incidents: Arc::new(RwLock::new(HashMap::new())),
+            events,
+        }
+    }
+
+    // Same contract as Engine::subscribe: lagging subscribers skip ahead
+    pub fn subscribe(&self) -> broadcast::Receiver<IncidentEvent> {
+        self.events.subscribe()
+    }
+
+    fn publish(&self, event: IncidentEvent) {
+        let _ = self.events.send(event);
+    }
+
+    fn group_key(&self, alert: &Alert) -> Option<String> {
+        if self.config.group_by.is_empty() {
+            return None;
+        }
+        let values: Vec<&str> = self.config.group_by.iter()
+            .map(|label| alert.labels.get(label).map(String::as_str).unwrap_or(""))
+            .collect();
+        if values.iter().all(|v| v.is_empty()) {
+            return None;
+        }
+        Some(values.join("|"))
+    }
+
+    // Groups the alert into a matching unresolved incident, or opens one
+    pub async fn ingest_alert(&self, alert: &Alert) -> Incident {
+        let now = Utc::now();
+        let group_key = self.group_key(alert);
+        let window = Duration::seconds(self.config.group_window_secs);
+        let mut incidents = self.incidents.write().await;
+
+        let existing = incidents.values_mut()
+            .filter(|i| i.status != IncidentStatus::Resolved)
+            .filter(|i| now - i.updated_at <= window)
+            .find(|i| {
+                i.alerts.contains(&alert.key)
+                    || (group_key.is_some() && i.group_key == group_key)
+            });
+
+        if let Some(incident) = existing {
+            if !incident.alerts.contains(&alert.key) {
+                incident.alerts.push(alert.key.clone());
+            }
+            incident.alert_count += 1;
+            incident.severity = incident.severity.max(alert.severity);
+            incident.updated_at = now;
+            let incident = incident.clone();
+            drop(incidents);
+
+            self.publish(IncidentEvent::AlertGrouped {
+                incident_id: incident.id.clone(),
+                alert_key: alert.key.clone(),
+                severity: alert.severity,
+            });
+            return incident;
+        }
+
+        let incident = Incident {
+            id: Uuid::new_v4().to_string(),
+            title: alert.name.clone(),
+            severity: alert.severity,
+            status: IncidentStatus::Open,
+            assignee: None,
+            alerts: vec![alert.key.clone()],
+            alert_count: 1,
+            group_key,
+            notes: Vec::new(),
+            evidence: Vec::new(),
+            created_at: now,
+            updated_at: now,
+            acknowledged_at: None,
+            resolved_at: None,
+        };
+        incidents.insert(incident.id.clone(), incident.clone());
+        drop(incidents);
+
+        self.publish(IncidentEvent::Opened { incident: incident.clone() });
+        incident
+    }
+
+    pub async fn get(&self, id: &str) -> Option<Incident> {
+        self.incidents.read().await.get(id).cloned()
+    }
+
+    // Most severe first, then most recently updated
+    pub async fn list(&self, filter: &IncidentFilter) -> Vec<Incident> {
+        let mut incidents: Vec<_> = self.incidents.read().await.values()
+            .filter(|i| filter.status.map_or(true, |s| i.status == s))
+            .filter(|i| filter.assignee.as_ref().map_or(true, |a| i.assignee.as_ref() == Some(a)))
+            .filter(|i| filter.min_severity.map_or(true, |s| i.severity >= s))
+            .cloned()
+            .collect();
+        incidents.sort_by(|a, b| b.severity.cmp(&a.severity).then_with(|| b.updated_at.cmp(&a.updated_at)));
+        incidents.truncate(filter.limit.unwrap_or(100));
+        incidents
+    }
+
+    async fn modify<F>(&self, id: &str, apply: F) -> Result<Incident>
+    where
+        F: FnOnce(&mut Incident) -> Result<()>,
+    {
+        let mut incidents = self.incidents.write().await;
+        let incident = incidents.get_mut(id)
+            .ok_or_else(|| anyhow::anyhow!("Incident not found: {}", id))?;
+        apply(incident)?;
+        incident.updated_at = Utc::now();
+        Ok(incident.clone())
+    }
+
+    pub async fn acknowledge(&self, id: &str, by: &str) -> Result<Incident> {
+        let incident = self.modify(id, |incident| {
+            if incident.status == IncidentStatus::Resolved {
+                return Err(anyhow::anyhow!("Incident {} is already resolved", incident.id));
+            }
+            incident.status = IncidentStatus::Acknowledged;
+            incident.acknowledged_at.get_or_insert_with(Utc::now);
+            // Acknowledging an unowned incident takes it
+            incident.assignee.get_or_insert_with(|| by.to_string());
+            Ok(())
+        }).await?;
+
+        self.publish(IncidentEvent::StatusChanged {
+            incident_id: incident.id.clone(),
+            status: incident.status,
+            by: Some(by.to_string()),
+        });
+        Ok(incident)
+    }
+
+    pub async fn resolve(&self, id: &str, by: &str) -> Result<Incident> {
+        let incident = self.modify(id, |incident| {
+            incident.status = IncidentStatus::Resolved;
+            incident.resolved_at = Some(Utc::now());
+            Ok(())
+        }).await?;
+
+        self.publish(IncidentEvent::StatusChanged {
+            incident_id: incident.id.clone(),
+            status: incident.status,
+            by: Some(by.to_string()),
+        });
+        self.prune_resolved().await;
+        Ok(incident)
+    }
+
+    pub async fn reopen(&self, id: &str, by: &str) -> Result<Incident> {
+        let incident = self.modify(id, |incident| {
+            incident.status = IncidentStatus::Open;
+            incident.resolved_at = None;
+            Ok(())
+        }).await?;
+
+        self.publish(IncidentEvent::StatusChanged {
+            incident_id: incident.id.clone(),
+            status: incident.status,
+            by: Some(by.to_string()),
+        });
+        Ok(incident)
+    }
+
+    pub async fn assign(&self, id: &str, assignee: Option<String>) -> Result<Incident> {
+        let incident = self.modify(id, |incident| {
+            incident.assignee = assignee;
+            Ok(())
+        }).await?;
+
+        self.publish(IncidentEvent::Assigned {
+            incident_id: incident.id.clone(),
+            assignee: incident.assignee.clone(),
+        });
+        Ok(incident)
+    }
+
+    pub async fn add_note(&self, id: &str, author: &str, text: &str) -> Result<Incident> {
+        if text.trim().is_empty() {
+            return Err(anyhow::anyhow!("Note text must not be empty"));
+        }
+        let note = IncidentNote {
+            author: author.to_string(),
+            text: text.to_string(),
+            created_at: Utc::now(),
+        };
+        let incident = self.modify(id, |incident| {
+            incident.notes.push(note.clone());
+            Ok(())
+        }).await?;
+
+        self.publish(IncidentEvent::NoteAdded { incident_id: incident.id.clone(), note });
+        Ok(incident)
+    }
+
+    pub async fn attach_evidence(&self, id: &str, clip: EvidenceClip) -> Result<Incident> {
+        if clip.end < clip.start {
+            return Err(anyhow::anyhow!("Evidence clip ends before it starts"));
+        }
+        let incident = self.modify(id, |incident| {
+            incident.evidence.push(clip.clone());
+            Ok(())
+        }).await?;
+
+        self.publish(IncidentEvent::EvidenceAttached { incident_id: incident.id.clone(), clip });
+        Ok(incident)
+    }
+
+    // For erasure requests: unassigns the person and takes their name off
+    // their notes, keeping what the notes say. Returns the incidents that
+    // changed.
+    pub async fn erase_person(&self, person: &str) -> Vec<Incident> {
+        let mut changed = Vec::new();
+        for incident in self.incidents.write().await.values_mut() {
+            let mut touched = false;
+            if incident.assignee.as_deref() == Some(person) {
+                incident.assignee = None;
+                touched = true;
+            }
+            for note in incident.notes.iter_mut().filter(|n| n.author == person) {
+                note.author = ERASED_AUTHOR.to_string();
+                touched = true;
+            }
+            if touched {
+                incident.updated_at = Utc::now();
+                changed.push(incident.clone());
+            }
+        }
+        changed
+    }
+
+    async fn prune_resolved(&self) {
+        let mut incidents = self.incidents.write().await;
+        let mut resolved: Vec<(DateTime<Utc>, String)> = incidents.values()
+            .filter(|i| i.status == IncidentStatus::Resolved)
+            .map(|i| (i.resolved_at.unwrap_or(i.updated_at), i.id.clone()))
+            .collect();
+        if resolved.len() <= self.config.max_resolved {
+            return;
+        }
+
+        resolved.sort();
+        let excess = resolved.len() - self.config.max_resolved;
+        for (_, id) in resolved.into_iter().take(excess) {
+            incidents.remove(&id);
+        }
+    }
+}
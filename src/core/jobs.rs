@@ -0,0 +1,201 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::RwLock;
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub kind: String,
+    pub status: JobStatus,
+    pub total: Option<u64>,
+    pub completed: u64,
+    pub message: Option<String>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+// Given to running jobs to report progress and observe cancellation
+#[derive(Clone)]
+pub struct JobHandle {
+    id: String,
+    jobs: Arc<RwLock<HashMap<String, Job>>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl JobHandle {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub async fn set_total(&self, total: u64) {
+        if let Some(job) = self.jobs.write().await.get_mut(&self.id) {
+            job.total = Some(total);
+        }
+    }
+
+    pub async fn advance(&self, count: u64) {
+        if let Some(job) = self.jobs.write().await.get_mut(&self.id) {
+            job.completed += count;
+        }
+    }
+
+    pub async fn set_message(&self, message: &str) {
+        if let Some(job) = self.jobs.write().await.get_mut(&self.id) {
+            job.message = Some(message.to_string());
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct JobManager {
+    jobs: Arc<RwLock<HashMap<String, Job>>>,
+    cancel_flags: Arc<RwLock<HashMap<String, Arc<AtomicBool>>>>,
+}
+
+impl JobManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn spawn<F, Fut>(&self, kind: &str, task: F) -> Job
+    where
+        F: FnOnce(JobHandle) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let job = new_job(kind);
+        self.jobs.write().await.insert(job.id.clone(), job.clone());
+        self.start(job, task).await
+    }
+
+    // Like `spawn`, but refuses while another job of the same kind is queued
+    // or running. The check and the insert happen under one lock, so two
+    // callers racing each other cannot both start one.
+    pub async fn spawn_exclusive<F, Fut>(&self, kind: &str, task: F) -> Result<Job>
+    where
+        F: FnOnce(JobHandle) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let job = new_job(kind);
+        {
+            let mut jobs = self.jobs.write().await;
+            if jobs.values().any(|j| j.kind == kind && is_active(j.status)) {
+                return Err(anyhow::anyhow!("A {} job is already running", kind));
+            }
+            jobs.insert(job.id.clone(), job.clone());
+        }
+        Ok(self.start(job, task).await)
+    }
+
+    async fn start<F, Fut>(&self, job: Job, task: F) -> Job
+    where
+        F: FnOnce(JobHandle) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.cancel_flags.write().await.insert(job.id.clone(), cancelled.clone());
+
+        let handle = JobHandle {
+            id: job.id.clone(),
+            jobs: self.jobs.clone(),
+            cancelled,
+        };
+        let jobs = self.jobs.clone();
+        let cancel_flags = self.cancel_flags.clone();
+
+        tokio::spawn(async move {
+            let id = handle.id.clone();
+            if let Some(job) = jobs.write().await.get_mut(&id) {
+                job.status = JobStatus::Running;
+                job.started_at = Some(Utc::now());
+            }
+
+            // Run on its own task so a panic surfaces as a JoinError here
+            // instead of leaving the job Running forever
+            let result = match tokio::spawn(task(handle.clone())).await {
+                Ok(result) => result,
+                Err(e) if e.is_panic() => Err(anyhow::anyhow!("Job panicked")),
+                Err(e) => Err(anyhow::anyhow!("Job aborted: {}", e)),
+            };
+
+            if let Some(job) = jobs.write().await.get_mut(&id) {
+                job.finished_at = Some(Utc::now());
+                job.status = match result {
+                    Ok(()) if handle.is_cancelled() => JobStatus::Cancelled,
+                    Ok(()) => JobStatus::Succeeded,
+                    Err(e) => {
+                        log::error!("Job {} ({}) failed: {}", id, job.kind, e);
+                        job.error = Some(e.to_string());
+                        JobStatus::Failed
+                    }
+                };
+            }
+            cancel_flags.write().await.remove(&id);
+        });
+
+        job
+    }
+
+    pub async fn get(&self, id: &str) -> Option<Job> {
+        self.jobs.read().await.get(id).cloned()
+    }
+
+    pub async fn list(&self) -> Vec<Job> {
+        let mut jobs: Vec<_> = self.jobs.read().await.values().cloned().collect();
+        jobs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        jobs
+    }
+
+    pub async fn is_running(&self, kind: &str) -> bool {
+        self.jobs.read().await.values().any(|j| j.kind == kind && is_active(j.status))
+    }
+
+    // Jobs check the flag between batches, so cancellation is cooperative
+    pub async fn cancel(&self, id: &str) -> Result<()> {
+        let flags = self.cancel_flags.read().await;
+        let flag = flags.get(id)
+            .ok_or_else(|| anyhow::anyhow!("Job not running: {}", id))?;
+        flag.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+fn new_job(kind: &str) -> Job {
+    Job {
+        id: Uuid::new_v4().to_string(),
+        kind: kind.to_string(),
+        status: JobStatus::Queued,
+        total: None,
+        completed: 0,
+        message: None,
+        error: None,
+        created_at: Utc::now(),
+        started_at: None,
+        finished_at: None,
+    }
+}
+
+fn is_active(status: JobStatus) -> bool {
+    matches!(status, JobStatus::Queued | JobStatus::Running)
+}
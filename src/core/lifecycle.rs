@@ -0,0 +1,299 @@
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::io::AsyncWriteExt;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(tag = "kind", content = "id", rename_all = "snake_case")]
+pub enum DataSubject {
+    Session(String),
+    Tenant(String),
+    Identity(String),
+}
+
+impl DataSubject {
+    pub fn parse(kind: &str, id: &str) -> Result<Self> {
+        if id.is_empty() {
+            return Err(anyhow::anyhow!("Data subject id must not be empty"));
+        }
+        match kind {
+            "session" => Ok(DataSubject::Session(id.to_string())),
+            "tenant" => Ok(DataSubject::Tenant(id.to_string())),
+            "identity" => Ok(DataSubject::Identity(id.to_string())),
+            _ => Err(anyhow::anyhow!("Unknown data subject kind: {}", kind)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DataCategory {
+    Conversations,
+    Embeddings,
+    Recordings,
+    Detections,
+    History,
+}
+
+impl DataCategory {
+    pub const ALL: [DataCategory; 5] = [
+        DataCategory::Conversations,
+        DataCategory::Embeddings,
+        DataCategory::Recordings,
+        DataCategory::Detections,
+        DataCategory::History,
+    ];
+}
+
+// Implemented by every subsystem that persists data attributable to a
+// subject. A store that can't attribute anything to a kind of subject
+// exports nothing and deletes nothing for it.
+#[async_trait]
+pub trait DataStore: Send + Sync {
+    fn category(&self) -> DataCategory;
+    async fn export(&self, subject: &DataSubject) -> Result<serde_json::Value>;
+    async fn delete(&self, subject: &DataSubject) -> Result<u64>;
+}
+
+// Refused before anything is touched: answering a data subject request
+// while a store holding their data isn't registered would acknowledge an
+// erasure that didn't happen
+#[derive(Debug, Clone)]
+pub enum LifecycleError {
+    MissingStores(Vec<DataCategory>),
+}
+
+impl std::fmt::Display for LifecycleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LifecycleError::MissingStores(categories) => {
+                write!(f, "No data store registered for {:?}", categories)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LifecycleError {}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LifecycleAction {
+    Export,
+    Delete,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryOutcome {
+    pub category: DataCategory,
+    pub records: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub action: LifecycleAction,
+    pub subject: DataSubject,
+    pub requested_by: String,
+    pub reason: Option<String>,
+    pub outcomes: Vec<CategoryOutcome>,
+}
+
+impl AuditEntry {
+    pub fn is_complete(&self) -> bool {
+        self.outcomes.iter().all(|o| o.error.is_none())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportBundle {
+    pub subject: DataSubject,
+    pub generated_at: DateTime<Utc>,
+    pub data: HashMap<DataCategory, Vec<serde_json::Value>>,
+    pub audit_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LifecycleConfig {
+    pub audit_file: String,
+    // Categories that must have a registered store before requests are
+    // served; drop one only when the deployment doesn't hold that data
+    pub required: Vec<DataCategory>,
+}
+
+impl Default for LifecycleConfig {
+    fn default() -> Self {
+        Self {
+            audit_file: "data/lifecycle_audit.jsonl".to_string(),
+            required: DataCategory::ALL.to_vec(),
+        }
+    }
+}
+
+pub struct LifecycleManager {
+    stores: Vec<Arc<dyn DataStore>>,
+    audit_log: Arc<RwLock<Vec<AuditEntry>>>,
+    config: LifecycleConfig,
+}
+
+impl LifecycleManager {
+    pub async fn new(config: LifecycleConfig) -> Result<Self> {
+        // The audit trail must survive restarts, so reload it on startup
+        let mut entries = Vec::new();
+        if tokio::fs::try_exists(&config.audit_file).await? {
+            let contents = tokio::fs::read_to_string(&config.audit_file).await
+                .context("Failed to read lifecycle audit log")?;
+            for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+                entries.push(serde_json::from_str(line).context("Corrupt lifecycle audit entry")?);
+            }
+        }
+
+        Ok(Self {
+            stores: Vec::new(),
+            audit_log: Arc::new(RwLock::new(entries)),
+            config,
+        })
+    }
+
+    pub fn register_store(&mut self, store: Arc<dyn DataStore>) {
+        self.stores.push(store);
+    }
+
+    pub fn missing_stores(&self) -> Vec<DataCategory> {
+        self.config.required.iter()
+            .copied()
+            .filter(|c| !self.stores.iter().any(|s| s.category() == *c))
+            .collect()
+    }
+
+    fn ensure_complete(&self) -> Result<()> {
+        let missing = self.missing_stores();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(LifecycleError::MissingStores(missing).into())
+        }
+    }
+
+    pub async fn export_subject(
+        &self,
+        subject: &DataSubject,
+        requested_by: &str,
+    ) -> Result<ExportBundle> {
+        self.ensure_complete()?;
+        let mut data = HashMap::new();
+        let mut outcomes = Vec::with_capacity(self.stores.len());
+
+        for store in &self.stores {
+            match store.export(subject).await {
+                Ok(value) => {
+                    let records = value.as_array().map(|a| a.len() as u64).unwrap_or(1);
+                    outcomes.push(CategoryOutcome { category: store.category(), records, error: None });
+                    // Several stores may share a category
+                    let items = data.entry(store.category()).or_insert_with(Vec::new);
+                    match value {
+                        serde_json::Value::Array(values) => items.extend(values),
+                        value => items.push(value),
+                    }
+                }
+                Err(e) => {
+                    log::error!("Export of {:?} failed for {:?}: {}", store.category(), subject, e);
+                    outcomes.push(CategoryOutcome {
+                        category: store.category(),
+                        records: 0,
+                        error: Some(e.to_string()),
+                    });
+                }
+            }
+        }
+
+        let entry = self.record(LifecycleAction::Export, subject, requested_by, None, outcomes).await?;
+        if !entry.is_complete() {
+            return Err(anyhow::anyhow!("Export incomplete, see audit entry {}", entry.id));
+        }
+
+        Ok(ExportBundle {
+            subject: subject.clone(),
+            generated_at: Utc::now(),
+            data,
+            audit_id: entry.id,
+        })
+    }
+
+    // Every store is attempted even if an earlier one fails, so a single
+    // broken backend doesn't leave the rest of the subject's data behind
+    pub async fn delete_subject(
+        &self,
+        subject: &DataSubject,
+        requested_by: &str,
+        reason: Option<String>,
+    ) -> Result<AuditEntry> {
+        self.ensure_complete()?;
+        let mut outcomes = Vec::with_capacity(self.stores.len());
+
+        for store in &self.stores {
+            let outcome = match store.delete(subject).await {
+                Ok(records) => CategoryOutcome { category: store.category(), records, error: None },
+                Err(e) => {
+                    log::error!("Deletion of {:?} failed for {:?}: {}", store.category(), subject, e);
+                    CategoryOutcome {
+                        category: store.category(),
+                        records: 0,
+                        error: Some(e.to_string()),
+                    }
+                }
+            };
+            outcomes.push(outcome);
+        }
+
+        self.record(LifecycleAction::Delete, subject, requested_by, reason, outcomes).await
+    }
+
+    pub async fn audit_trail(&self, subject: Option<&DataSubject>) -> Vec<AuditEntry> {
+        self.audit_log.read().await
+            .iter()
+            .filter(|e| subject.is_none_or(|s| &e.subject == s))
+            .cloned()
+            .collect()
+    }
+
+    async fn record(
+        &self,
+        action: LifecycleAction,
+        subject: &DataSubject,
+        requested_by: &str,
+        reason: Option<String>,
+        outcomes: Vec<CategoryOutcome>,
+    ) -> Result<AuditEntry> {
+        let entry = AuditEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            action,
+            subject: subject.clone(),
+            requested_by: requested_by.to_string(),
+            reason,
+            outcomes,
+        };
+
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.config.audit_file)
+            .await
+            .context("Failed to open lifecycle audit log")?;
+        file.write_all(line.as_bytes()).await?;
+        file.sync_data().await?;
+
+        self.audit_log.write().await.push(entry.clone());
+        Ok(entry)
+    }
+}
@@ -0,0 +1,182 @@
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use serde::{Serialize, Deserialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::core::llm::{
+    LLMTrait,
+    types::{Message, StreamChunk},
+};
+
+// Stream chunks with the per-token metadata UIs use to show confidence and
+// why generation ended. Everything beyond the text is optional: providers
+// fill in what they report and leave the rest unset.
+//
+// The metadata travels beside the plain stream rather than in it. While an
+// annotated stream is being opened, a StreamReport sits in a task-local
+// (like the call override in overrides.rs); providers pick it up when they
+// start streaming and write to it as they go, so it passes through the
+// router, cache and other wrappers untouched.
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TopLogprob {
+    pub token: String,
+    pub logprob: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TokenLogprob {
+    pub token: String,
+    pub logprob: f32,
+    // Most likely alternatives at this position, when requested
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ChunkMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<Vec<TokenLogprob>>,
+    // Set on the last chunk only: "stop", "length", ...
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+impl ChunkMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.logprobs.is_none() && self.finish_reason.is_none() && self.model.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AnnotatedChunk {
+    pub content: String,
+    #[serde(flatten)]
+    pub metadata: ChunkMetadata,
+}
+
+impl From<StreamChunk> for AnnotatedChunk {
+    fn from(chunk: StreamChunk) -> Self {
+        Self { content: chunk.content, metadata: ChunkMetadata::default() }
+    }
+}
+
+pub type AnnotatedChunkStream = Pin<Box<dyn Stream<Item = Result<AnnotatedChunk>> + Send>>;
+
+#[derive(Debug, Default)]
+struct Reported {
+    model: Option<String>,
+    finish_reason: Option<String>,
+    logprobs: Vec<TokenLogprob>,
+}
+
+// What the provider serving a stream says about it. Later writes win, so a
+// route that fails before its first chunk is replaced by the one after it.
+#[derive(Debug, Clone, Default)]
+pub struct StreamReport(Arc<Mutex<Reported>>);
+
+tokio::task_local! {
+    static REPORT: StreamReport;
+}
+
+impl StreamReport {
+    // The report for the stream being opened. Outside an annotated call
+    // this is a fresh one nobody reads, so providers can always write.
+    pub fn current() -> Self {
+        REPORT.try_with(|report| report.clone()).unwrap_or_default()
+    }
+
+    pub fn model(&self, model: &str) {
+        self.0.lock().unwrap().model = Some(model.to_string());
+    }
+
+    pub fn finish(&self, reason: &str) {
+        self.0.lock().unwrap().finish_reason = Some(reason.to_string());
+    }
+
+    // For the text in the chunk the provider yields next
+    pub fn logprob(&self, logprob: TokenLogprob) {
+        self.0.lock().unwrap().logprobs.push(logprob);
+    }
+
+    // Pushed logprobs belong to the chunk that follows them
+    fn chunk_metadata(&self, model: &str) -> ChunkMetadata {
+        let mut reported = self.0.lock().unwrap();
+        let logprobs = std::mem::take(&mut reported.logprobs);
+        ChunkMetadata {
+            logprobs: (!logprobs.is_empty()).then_some(logprobs),
+            finish_reason: None,
+            model: Some(reported.model.clone().unwrap_or_else(|| model.to_string())),
+        }
+    }
+
+    fn final_metadata(&self, model: &str) -> ChunkMetadata {
+        let reported = self.0.lock().unwrap();
+        ChunkMetadata {
+            logprobs: None,
+            finish_reason: Some(reported.finish_reason.clone().unwrap_or_else(|| "stop".to_string())),
+            model: Some(reported.model.clone().unwrap_or_else(|| model.to_string())),
+        }
+    }
+}
+
+// Tags every chunk of a plain stream with `model` and, once the stream ends
+// without an error, adds an empty final chunk with finish_reason "stop".
+// Nothing reported it, so that is all there is to say.
+pub fn annotate<S>(stream: S, model: String) -> AnnotatedChunkStream
+where
+    S: Stream<Item = Result<StreamChunk>> + Send + 'static,
+{
+    annotate_reported(stream, model, StreamReport::default())
+}
+
+// annotate(), with whatever the provider put in `report` taking precedence
+// over the defaults
+fn annotate_reported<S>(stream: S, model: String, report: StreamReport) -> AnnotatedChunkStream
+where
+    S: Stream<Item = Result<StreamChunk>> + Send + 'static,
+{
+    let chunks = stream.map({
+        let (model, report) = (model.clone(), report.clone());
+        move |item| item.map(|chunk| AnnotatedChunk {
+            content: chunk.content,
+            metadata: report.chunk_metadata(&model),
+        })
+    });
+    let done = async_stream::stream! {
+        yield Ok(AnnotatedChunk {
+            content: String::new(),
+            metadata: report.final_metadata(&model),
+        });
+    };
+
+    Box::pin(chunks
+        .chain(done)
+        .scan(false, |errored, item| {
+            // Nothing follows an error, including the finish chunk
+            if *errored {
+                return futures::future::ready(None);
+            }
+            *errored = item.is_err();
+            futures::future::ready(Some(item))
+        }))
+}
+
+#[async_trait]
+pub trait AnnotatedStream {
+    async fn complete_stream_annotated(&self, messages: Vec<Message>, cancel: CancellationToken) -> Result<AnnotatedChunkStream>;
+}
+
+#[async_trait]
+impl<T: LLMTrait + Send + Sync + ?Sized> AnnotatedStream for T {
+    async fn complete_stream_annotated(&self, messages: Vec<Message>, cancel: CancellationToken) -> Result<AnnotatedChunkStream> {
+        let report = StreamReport::default();
+        let stream = REPORT.scope(report.clone(), self.complete_stream(messages, cancel)).await?;
+        Ok(annotate_reported(stream, self.get_model().to_string(), report))
+    }
+}
@@ -0,0 +1,324 @@
+#![cfg(feature = "llm-anthropic")]
+
+use std::pin::Pin;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio_util::sync::CancellationToken;
+
+use crate::core::llm::{
+    LLMTrait,
+    annotated::StreamReport,
+    cancel::{unless_cancelled, until_cancelled},
+    multimodal::{anthropic_block, ContentPart, MultimodalLLM, MultimodalMessage},
+    overrides::for_call,
+    router::ApiError,
+    types::{Message, ModelConfig, Response, StreamChunk, Usage},
+};
+use crate::utils::{config::Config, egress::EgressClient};
+
+type ChunkStream = Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>;
+
+const API_BASE: &str = "https://api.anthropic.com/v1";
+const API_VERSION: &str = "2023-06-01";
+pub const DEFAULT_MODEL: &str = "claude-sonnet-4-5";
+// Stands in for the user turn that opened a history the API would reject
+const EARLIER_TURNS: &str = "(earlier conversation omitted)";
+
+#[derive(Clone)]
+pub struct Anthropic {
+    client: EgressClient,
+    api_key: String,
+    api_base: String,
+    model: String,
+    model_config: ModelConfig,
+}
+
+impl Anthropic {
+    // Requests go through `client`, so the proxy and egress allow-list
+    // apply to the API host like to any other outbound call
+    pub fn new(config: &Config, client: EgressClient) -> Self {
+        Self {
+            client,
+            api_key: config.anthropic_key.clone().unwrap_or_default(),
+            api_base: API_BASE.to_string(),
+            model: config.anthropic_model.clone().unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+            model_config: ModelConfig::default(),
+        }
+    }
+
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = api_key.to_string();
+        self
+    }
+
+    pub fn with_api_base(mut self, api_base: &str) -> Self {
+        self.api_base = api_base.trim_end_matches('/').to_string();
+        self
+    }
+
+    async fn send(&self, body: Value) -> Result<reqwest::Response> {
+        let response = self.client.post(&format!("{}/messages", self.api_base))?
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION)
+            .json(&body)
+            .send()
+            .await
+            .context("Failed to call Anthropic API")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let detail = response.text().await.unwrap_or_default();
+            return Err(ApiError::new("Anthropic", status.as_u16(), &api_error_message(&detail)).into());
+        }
+        Ok(response)
+    }
+}
+
+fn api_error_message(body: &str) -> String {
+    serde_json::from_str::<Value>(body).ok()
+        .and_then(|v| v["error"]["message"].as_str().map(|s| s.to_string()))
+        .unwrap_or_else(|| body.to_string())
+}
+
+// The Messages API takes system prompts as a separate field and expects
+// user and assistant turns to alternate, starting with a user turn, so
+// consecutive turns from the same role are merged and a history that opens
+// with the assistant (e.g. after trimming) gets a placeholder user turn.
+// Tool and function roles are sent as user turns.
+pub fn build_request(model: &str, config: &ModelConfig, messages: &[Message], stream: bool) -> Result<Value> {
+    let messages: Vec<MultimodalMessage> = messages.iter().map(MultimodalMessage::from).collect();
+    build_multimodal_request(model, config, &messages, stream)
+}
+
+// Text-only turns are sent as a plain string, turns with images as a list
+// of content blocks
+pub fn build_multimodal_request(
+    model: &str,
+    config: &ModelConfig,
+    messages: &[MultimodalMessage],
+    stream: bool,
+) -> Result<Value> {
+    let mut system = Vec::new();
+    let mut turns: Vec<(String, Vec<ContentPart>)> = Vec::new();
+    for message in messages {
+        let role = match message.role.as_str() {
+            "system" => {
+                if message.has_images() {
+                    return Err(anyhow::anyhow!("Anthropic system prompts cannot contain images"));
+                }
+                system.push(message.text());
+                continue;
+            }
+            "assistant" => "assistant",
+            _ => "user",
+        };
+        if role == "assistant" && message.has_images() {
+            return Err(anyhow::anyhow!("Anthropic only accepts images in user turns"));
+        }
+        match turns.last_mut() {
+            Some((last_role, parts)) if last_role == role => parts.extend(message.parts.iter().cloned()),
+            _ => turns.push((role.to_string(), message.parts.clone())),
+        }
+    }
+    if turns.is_empty() {
+        return Err(anyhow::anyhow!("Anthropic requests need at least one user or assistant message"));
+    }
+    if turns[0].0 == "assistant" {
+        turns.insert(0, ("user".to_string(), vec![ContentPart::Text { text: EARLIER_TURNS.to_string() }]));
+    }
+
+    let turns: Vec<Value> = turns.into_iter()
+        .map(|(role, parts)| {
+            let turn = MultimodalMessage { role, parts };
+            let content = if turn.has_images() {
+                json!(turn.parts.iter().map(anthropic_block).collect::<Vec<_>>())
+            } else {
+                json!(turn.text())
+            };
+            json!({ "role": turn.role, "content": content })
+        })
+        .collect();
+
+    let mut body = json!({
+        "model": model,
+        "max_tokens": config.max_tokens.max(1),
+        "messages": turns,
+        "stream": stream,
+    });
+    if !system.is_empty() {
+        body["system"] = json!(system.join("\n\n"));
+    }
+    // Claude samples with either temperature or top_p, not both; a
+    // non-default top_p wins. Its temperature range is 0-1. Frequency and
+    // presence penalties have no equivalent and are dropped.
+    if config.top_p < 1.0 {
+        body["top_p"] = json!(config.top_p);
+    } else {
+        body["temperature"] = json!(config.temperature.clamp(0.0, 1.0));
+    }
+    Ok(body)
+}
+
+#[derive(Deserialize)]
+struct MessagesResponse {
+    model: String,
+    content: Vec<ContentBlock>,
+    usage: ApiUsage,
+}
+
+#[derive(Deserialize)]
+struct ContentBlock {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiUsage {
+    #[serde(default)]
+    input_tokens: u32,
+    #[serde(default)]
+    output_tokens: u32,
+}
+
+impl Anthropic {
+    async fn complete_body(&self, body: Value) -> Result<Response> {
+        let response: MessagesResponse = self.send(body).await?
+            .json()
+            .await
+            .context("Failed to parse Anthropic response")?;
+
+        let content: String = response.content.iter()
+            .filter(|b| b.kind == "text")
+            .filter_map(|b| b.text.as_deref())
+            .collect();
+        Ok(Response {
+            content,
+            role: "assistant".to_string(),
+            model: response.model,
+            usage: Usage {
+                prompt_tokens: response.usage.input_tokens,
+                completion_tokens: response.usage.output_tokens,
+                total_tokens: response.usage.input_tokens + response.usage.output_tokens,
+            },
+        })
+    }
+}
+
+enum StreamEvent {
+    Text(String),
+    // message_start names the model that actually answered
+    Started(String),
+    // message_delta carries the stop reason, already mapped to the
+    // OpenAI-style finish reason annotated streams use
+    Stopped(String),
+    Other,
+}
+
+fn parse_event(data: &str) -> Result<StreamEvent> {
+    let event: Value = serde_json::from_str(data).context("Failed to parse Anthropic stream event")?;
+    match event["type"].as_str() {
+        Some("content_block_delta") if event["delta"]["type"] == "text_delta" => {
+            Ok(event["delta"]["text"].as_str().map_or(StreamEvent::Other, |s| StreamEvent::Text(s.to_string())))
+        }
+        Some("message_start") => {
+            Ok(event["message"]["model"].as_str().map_or(StreamEvent::Other, |s| StreamEvent::Started(s.to_string())))
+        }
+        Some("message_delta") => Ok(event["delta"]["stop_reason"].as_str().map_or(StreamEvent::Other, |reason| {
+            StreamEvent::Stopped(finish_reason(reason).to_string())
+        })),
+        Some("error") => Err(anyhow::anyhow!(
+            "Anthropic stream error: {}",
+            event["error"]["message"].as_str().unwrap_or("unknown error"),
+        )),
+        _ => Ok(StreamEvent::Other),
+    }
+}
+
+fn finish_reason(stop_reason: &str) -> &str {
+    match stop_reason {
+        "end_turn" | "stop_sequence" => "stop",
+        "max_tokens" => "length",
+        "tool_use" => "tool_calls",
+        other => other,
+    }
+}
+
+// One server-sent event's data payload. Only text deltas and errors matter;
+// pings, block starts and stops are skipped.
+pub fn parse_stream_event(data: &str) -> Result<Option<String>> {
+    match parse_event(data)? {
+        StreamEvent::Text(text) => Ok(Some(text)),
+        _ => Ok(None),
+    }
+}
+
+#[async_trait]
+impl LLMTrait for Anthropic {
+    async fn complete(&self, messages: Vec<Message>) -> Result<Response> {
+        let (model, config) = for_call(&self.model, &self.model_config);
+        let body = build_request(&model, &config, &messages, false)?;
+        self.complete_body(body).await
+    }
+
+    async fn complete_stream(&self, messages: Vec<Message>, cancel: CancellationToken) -> Result<ChunkStream> {
+        let (model, config) = for_call(&self.model, &self.model_config);
+        let body = build_request(&model, &config, &messages, true)?;
+        let mut body_stream = unless_cancelled(self.send(body), &cancel).await?.bytes_stream();
+        let report = StreamReport::current();
+
+        let stream = async_stream::try_stream! {
+            // Split on raw bytes so a character spanning two network chunks
+            // isn't mangled
+            let mut buffer: Vec<u8> = Vec::new();
+            while let Some(bytes) = body_stream.next().await {
+                let bytes = bytes.context("Anthropic stream interrupted")?;
+                buffer.extend_from_slice(&bytes);
+
+                while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=end).collect();
+                    let line = String::from_utf8_lossy(&line);
+                    let Some(data) = line.trim_end().strip_prefix("data:") else {
+                        continue;
+                    };
+                    match parse_event(data.trim())? {
+                        StreamEvent::Text(text) if !text.is_empty() => yield StreamChunk { content: text },
+                        StreamEvent::Started(model) => report.model(&model),
+                        StreamEvent::Stopped(reason) => report.finish(&reason),
+                        _ => {}
+                    }
+                }
+            }
+        };
+        Ok(until_cancelled(Box::pin(stream), cancel))
+    }
+
+    fn is_initialized(&self) -> bool {
+        !self.api_key.is_empty()
+    }
+
+    fn get_model(&self) -> &str {
+        &self.model
+    }
+
+    fn set_model_config(&mut self, config: ModelConfig) {
+        self.model_config = config;
+    }
+
+    fn get_model_config(&self) -> ModelConfig {
+        self.model_config.clone()
+    }
+}
+
+#[async_trait]
+impl MultimodalLLM for Anthropic {
+    async fn complete_multimodal(&self, messages: Vec<MultimodalMessage>) -> Result<Response> {
+        let (model, config) = for_call(&self.model, &self.model_config);
+        let body = build_multimodal_request(&model, &config, &messages, false)?;
+        self.complete_body(body).await
+    }
+}
@@ -0,0 +1,395 @@
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::Stream;
+use lru::LruCache;
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use tokio_util::sync::CancellationToken;
+
+use crate::core::llm::{
+    LLMTrait,
+    annotated::StreamReport,
+    multimodal::{ContentPart, MultimodalLLM, MultimodalMessage},
+    overrides::for_call,
+    types::{Message, ModelConfig, Response, StreamChunk, Usage},
+};
+
+type ChunkStream = Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>;
+
+// Short-circuits identical completions. The key is a hash of the model, its
+// sampling settings and every message, so any change to the prompt misses.
+// An in-process LRU answers first; with a Redis URL configured, entries are
+// also shared between instances and survive restarts.
+
+tokio::task_local! {
+    static BYPASS: ();
+}
+
+// Runs `future` with every cache skipped, for callers that want fresh
+// samples of the same prompt (best-of-n candidates)
+pub async fn bypassing<F: Future>(future: F) -> F::Output {
+    BYPASS.scope((), future).await
+}
+
+fn bypassed() -> bool {
+    BYPASS.try_with(|_| ()).is_ok()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub ttl_secs: u64,
+    pub max_entries: usize,
+    // e.g. "redis://cache:6379/0"; needs the `redis` Cargo feature
+    pub redis_url: Option<String>,
+    pub key_prefix: String,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 3600,
+            max_entries: 1000,
+            redis_url: None,
+            key_prefix: String::from("vae:llm:"),
+        }
+    }
+}
+
+// What is stored; kept separate from Response so the stored format doesn't
+// change with it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub content: String,
+    pub role: String,
+    pub model: String,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+}
+
+impl From<&Response> for CachedResponse {
+    fn from(response: &Response) -> Self {
+        Self {
+            content: response.content.clone(),
+            role: response.role.clone(),
+            model: response.model.clone(),
+            prompt_tokens: response.usage.prompt_tokens,
+            completion_tokens: response.usage.completion_tokens,
+        }
+    }
+}
+
+impl From<CachedResponse> for Response {
+    fn from(cached: CachedResponse) -> Self {
+        Response {
+            content: cached.content,
+            role: cached.role,
+            model: cached.model,
+            usage: Usage {
+                prompt_tokens: cached.prompt_tokens,
+                completion_tokens: cached.completion_tokens,
+                total_tokens: cached.prompt_tokens + cached.completion_tokens,
+            },
+        }
+    }
+}
+
+// Fields are length-prefixed so ("ab", "c") and ("a", "bc") differ
+fn field(hasher: &mut Sha256, value: &str) {
+    hasher.update((value.len() as u64).to_le_bytes());
+    hasher.update(value.as_bytes());
+}
+
+fn settings(hasher: &mut Sha256, model: &str, config: &ModelConfig) {
+    field(hasher, model);
+    field(hasher, &format!(
+        "{}:{}:{}:{}:{}",
+        config.temperature, config.top_p, config.max_tokens, config.frequency_penalty, config.presence_penalty,
+    ));
+}
+
+pub fn cache_key(model: &str, config: &ModelConfig, messages: &[Message]) -> String {
+    let mut hasher = Sha256::new();
+    settings(&mut hasher, model, config);
+    for message in messages {
+        field(&mut hasher, &message.role);
+        field(&mut hasher, &message.content);
+    }
+    hex::encode(hasher.finalize())
+}
+
+// Images are keyed by their data (or URL), so the same frame asked the
+// same question hits
+pub fn multimodal_cache_key(model: &str, config: &ModelConfig, messages: &[MultimodalMessage]) -> String {
+    let mut hasher = Sha256::new();
+    settings(&mut hasher, model, config);
+    for message in messages {
+        field(&mut hasher, &message.role);
+        for part in &message.parts {
+            match part {
+                ContentPart::Text { text } => {
+                    field(&mut hasher, "text");
+                    field(&mut hasher, text);
+                }
+                ContentPart::Image(image) => {
+                    field(&mut hasher, "image");
+                    field(&mut hasher, &image.data_url());
+                    field(&mut hasher, &format!("{:?}", image.detail));
+                }
+            }
+        }
+    }
+    hex::encode(hasher.finalize())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+    pub entries: usize,
+    // Redis failures; the request goes on to the provider
+    pub errors: u64,
+}
+
+#[cfg(feature = "redis")]
+struct RedisTier {
+    client: redis::Client,
+    // Connected on first use, since providers are built synchronously
+    connection: tokio::sync::OnceCell<redis::aio::ConnectionManager>,
+}
+
+#[cfg(feature = "redis")]
+impl RedisTier {
+    async fn connection(&self) -> Result<redis::aio::ConnectionManager> {
+        let connection = self.connection
+            .get_or_try_init(|| redis::aio::ConnectionManager::new(self.client.clone()))
+            .await?;
+        Ok(connection.clone())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>> {
+        let mut connection = self.connection().await?;
+        let raw: Option<String> = redis::AsyncCommands::get(&mut connection, key).await?;
+        Ok(raw.map(|raw| serde_json::from_str(&raw)).transpose()?)
+    }
+
+    async fn put(&self, key: &str, response: &CachedResponse, ttl_secs: u64) -> Result<()> {
+        let mut connection = self.connection().await?;
+        let raw = serde_json::to_string(response)?;
+        redis::AsyncCommands::set_ex::<_, _, ()>(&mut connection, key, raw, ttl_secs).await?;
+        Ok(())
+    }
+}
+
+pub struct ResponseCache {
+    config: CacheConfig,
+    memory: Mutex<LruCache<String, (Instant, CachedResponse)>>,
+    #[cfg(feature = "redis")]
+    redis: Option<RedisTier>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl ResponseCache {
+    pub fn new(config: CacheConfig) -> Result<Self> {
+        let capacity = NonZeroUsize::new(config.max_entries.max(1)).unwrap_or(NonZeroUsize::MIN);
+
+        #[cfg(feature = "redis")]
+        let redis = match &config.redis_url {
+            Some(url) => Some(RedisTier {
+                client: redis::Client::open(url.as_str())
+                    .map_err(|e| anyhow::anyhow!("Invalid LLM cache Redis URL {}: {}", url, e))?,
+                connection: tokio::sync::OnceCell::new(),
+            }),
+            None => None,
+        };
+        #[cfg(not(feature = "redis"))]
+        if config.redis_url.is_some() {
+            return Err(anyhow::anyhow!("llm_cache.redis_url needs the redis Cargo feature"));
+        }
+
+        Ok(Self {
+            memory: Mutex::new(LruCache::new(capacity)),
+            #[cfg(feature = "redis")]
+            redis,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            config,
+        })
+    }
+
+    fn ttl(&self) -> Duration {
+        Duration::from_secs(self.config.ttl_secs)
+    }
+
+    pub async fn get(&self, key: &str) -> Option<CachedResponse> {
+        let found = {
+            let mut memory = self.memory.lock().unwrap();
+            match memory.get(key) {
+                Some((stored, response)) if stored.elapsed() < self.ttl() => Some(response.clone()),
+                Some(_) => {
+                    memory.pop(key);
+                    None
+                }
+                None => None,
+            }
+        };
+
+        #[cfg(feature = "redis")]
+        let found = match (found, &self.redis) {
+            (None, Some(redis)) => match redis.get(&format!("{}{}", self.config.key_prefix, key)).await {
+                Ok(Some(response)) => {
+                    // Redis applies its own TTL; the local copy gets a
+                    // fresh one, which at worst doubles it
+                    self.memory.lock().unwrap().put(key.to_string(), (Instant::now(), response.clone()));
+                    Some(response)
+                }
+                Ok(None) => None,
+                Err(e) => {
+                    log::warn!("LLM cache lookup in Redis failed: {}", e);
+                    self.errors.fetch_add(1, Ordering::Relaxed);
+                    None
+                }
+            },
+            (found, _) => found,
+        };
+
+        let counter = if found.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    pub async fn put(&self, key: &str, response: CachedResponse) {
+        #[cfg(feature = "redis")]
+        if let Some(redis) = &self.redis {
+            let redis_key = format!("{}{}", self.config.key_prefix, key);
+            if let Err(e) = redis.put(&redis_key, &response, self.config.ttl_secs.max(1)).await {
+                log::warn!("LLM cache write to Redis failed: {}", e);
+                self.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.memory.lock().unwrap().put(key.to_string(), (Instant::now(), response));
+    }
+
+    pub fn clear(&self) {
+        self.memory.lock().unwrap().clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        CacheStats {
+            hits,
+            misses,
+            hit_rate: if hits + misses == 0 { 0.0 } else { hits as f64 / (hits + misses) as f64 },
+            entries: self.memory.lock().unwrap().len(),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub struct CachedLLM {
+    inner: Box<dyn LLMTrait>,
+    cache: Arc<ResponseCache>,
+}
+
+impl CachedLLM {
+    pub fn new(inner: Box<dyn LLMTrait>, cache: Arc<ResponseCache>) -> Self {
+        Self { inner, cache }
+    }
+
+    fn key(&self, messages: &[Message]) -> String {
+        let (model, config) = for_call(self.inner.get_model(), &self.inner.get_model_config());
+        cache_key(&model, &config, messages)
+    }
+}
+
+#[async_trait]
+impl LLMTrait for CachedLLM {
+    async fn complete(&self, messages: Vec<Message>) -> Result<Response> {
+        if bypassed() {
+            return self.inner.complete(messages).await;
+        }
+        let key = self.key(&messages);
+        if let Some(cached) = self.cache.get(&key).await {
+            return Ok(cached.into());
+        }
+        let response = self.inner.complete(messages).await?;
+        self.cache.put(&key, CachedResponse::from(&response)).await;
+        Ok(response)
+    }
+
+    // Hits are replayed as a single chunk. Misses stream straight through
+    // and aren't stored, since the caller may stop reading part way.
+    async fn complete_stream(&self, messages: Vec<Message>, cancel: CancellationToken) -> Result<ChunkStream> {
+        if bypassed() {
+            return self.inner.complete_stream(messages, cancel).await;
+        }
+        let key = self.key(&messages);
+        if let Some(cached) = self.cache.get(&key).await {
+            StreamReport::current().model(&cached.model);
+            let chunk = StreamChunk { content: cached.content };
+            return Ok(Box::pin(futures::stream::iter([Ok(chunk)])));
+        }
+        self.inner.complete_stream(messages, cancel).await
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.inner.is_initialized()
+    }
+
+    fn get_model(&self) -> &str {
+        self.inner.get_model()
+    }
+
+    fn set_model_config(&mut self, config: ModelConfig) {
+        self.inner.set_model_config(config);
+    }
+
+    fn get_model_config(&self) -> ModelConfig {
+        self.inner.get_model_config()
+    }
+}
+
+// The same cache in front of a vision-capable provider, for captions and
+// other image prompts that repeat
+pub struct CachedMultimodal<L> {
+    inner: L,
+    cache: Arc<ResponseCache>,
+}
+
+impl<L: LLMTrait + MultimodalLLM> CachedMultimodal<L> {
+    pub fn new(inner: L, cache: Arc<ResponseCache>) -> Self {
+        Self { inner, cache }
+    }
+
+    pub fn inner(&self) -> &L {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<L: LLMTrait + MultimodalLLM> MultimodalLLM for CachedMultimodal<L> {
+    async fn complete_multimodal(&self, messages: Vec<MultimodalMessage>) -> Result<Response> {
+        if bypassed() {
+            return self.inner.complete_multimodal(messages).await;
+        }
+        let (model, config) = for_call(self.inner.get_model(), &self.inner.get_model_config());
+        let key = multimodal_cache_key(&model, &config, &messages);
+        if let Some(cached) = self.cache.get(&key).await {
+            return Ok(cached.into());
+        }
+        let response = self.inner.complete_multimodal(messages).await?;
+        self.cache.put(&key, CachedResponse::from(&response)).await;
+        Ok(response)
+    }
+}
@@ -0,0 +1,25 @@
+use std::future::Future;
+use std::pin::Pin;
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use tokio_util::sync::CancellationToken;
+
+// Ends `stream` as soon as `cancel` fires. The provider stream is dropped
+// right away, which closes its connection, so generation stops instead of
+// running on (and being billed) for a reader that has gone.
+pub fn until_cancelled<T: Send + 'static>(
+    stream: Pin<Box<dyn Stream<Item = Result<T>> + Send>>,
+    cancel: CancellationToken,
+) -> Pin<Box<dyn Stream<Item = Result<T>> + Send>> {
+    Box::pin(stream.take_until(async move { cancel.cancelled().await }))
+}
+
+// For the request that opens a stream, so a cancellation while waiting for
+// the provider's first byte doesn't wait for it
+pub async fn unless_cancelled<T>(future: impl Future<Output = Result<T>>, cancel: &CancellationToken) -> Result<T> {
+    tokio::select! {
+        biased;
+        _ = cancel.cancelled() => Err(anyhow::anyhow!("Completion cancelled")),
+        result = future => result,
+    }
+}
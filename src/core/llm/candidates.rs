@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::Stream;
+use serde::{Serialize, Deserialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::core::llm::{
+    LLMTrait,
+    annotated::StreamReport,
+    cache::bypassing,
+    cancel::unless_cancelled,
+    judge::{Judge, JudgeRequest, Rubric},
+    types::{Message, ModelConfig, Response, StreamChunk, Usage},
+};
+
+type ChunkStream = Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>;
+
+// Best-of-n: the same request is completed several times concurrently and a
+// selector keeps one answer. Candidates only differ when the model samples,
+// so this needs a temperature above zero to be worth the extra tokens.
+
+#[async_trait]
+pub trait CompleteN {
+    // Up to `n` completions of the same messages. Failed candidates are
+    // dropped; it only fails when every one of them does.
+    async fn complete_n(&self, messages: Vec<Message>, n: usize) -> Result<Vec<Response>>;
+}
+
+#[async_trait]
+impl<T: LLMTrait + Send + Sync + ?Sized> CompleteN for T {
+    async fn complete_n(&self, messages: Vec<Message>, n: usize) -> Result<Vec<Response>> {
+        let n = n.max(1);
+        // A cached model would answer every candidate with the same entry
+        let results = bypassing(futures::future::join_all((0..n).map(|_| self.complete(messages.clone())))).await;
+
+        let mut candidates = Vec::with_capacity(n);
+        let mut last_error = None;
+        for result in results {
+            match result {
+                Ok(response) => candidates.push(response),
+                Err(e) => {
+                    log::warn!("Candidate completion failed: {}", e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        match last_error {
+            Some(e) if candidates.is_empty() => Err(e.context(format!("All {} candidate completions failed", n))),
+            _ => Ok(candidates),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SelectorKind {
+    #[default]
+    Consensus,
+    // Scored by the primary model, one extra call per candidate
+    Judge,
+}
+
+// `best_of` in the config; create_llm wraps the model in BestOfN with it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BestOfConfig {
+    // 1 turns it off
+    pub n: usize,
+    pub selector: SelectorKind,
+}
+
+impl Default for BestOfConfig {
+    fn default() -> Self {
+        Self {
+            n: 3,
+            selector: SelectorKind::Consensus,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Selection {
+    // Into `candidates`
+    pub index: usize,
+    // Spent on choosing, e.g. judge calls
+    pub tokens_used: u64,
+}
+
+#[async_trait]
+pub trait CandidateSelector: Send + Sync {
+    async fn select(&self, messages: &[Message], candidates: &[Response]) -> Result<Selection>;
+}
+
+// Scores every candidate with the judge's rubric and keeps the highest
+// overall score. Costs one judge call per candidate.
+pub struct JudgeSelector {
+    judge: Arc<Judge>,
+    rubric: Option<Rubric>,
+}
+
+impl JudgeSelector {
+    pub fn new(judge: Arc<Judge>) -> Self {
+        Self { judge, rubric: None }
+    }
+
+    pub fn with_rubric(mut self, rubric: Rubric) -> Self {
+        self.rubric = Some(rubric);
+        self
+    }
+}
+
+#[async_trait]
+impl CandidateSelector for JudgeSelector {
+    async fn select(&self, messages: &[Message], candidates: &[Response]) -> Result<Selection> {
+        let prompt = messages.iter()
+            .rev()
+            .find(|m| m.role == "user")
+            .map(|m| m.content.clone())
+            .unwrap_or_default();
+
+        let requests: Vec<JudgeRequest> = candidates.iter()
+            .map(|candidate| JudgeRequest {
+                prompt: prompt.clone(),
+                response: candidate.content.clone(),
+                reference: None,
+                rubric: self.rubric.clone(),
+            })
+            .collect();
+        let scores = futures::future::join_all(requests.iter().map(|r| self.judge.score(r))).await;
+
+        let mut best: Option<(usize, f32)> = None;
+        let mut tokens_used = 0;
+        for (index, score) in scores.into_iter().enumerate() {
+            match score {
+                Ok(score) => {
+                    tokens_used += score.tokens_used;
+                    // Ties keep the earlier candidate
+                    if best.is_none_or(|(_, b)| score.overall > b) {
+                        best = Some((index, score.overall));
+                    }
+                }
+                Err(e) => log::warn!("Failed to score candidate {}: {}", index, e),
+            }
+        }
+        best.map(|(index, _)| Selection { index, tokens_used })
+            .ok_or_else(|| anyhow::anyhow!("The judge could not score any of {} candidates", candidates.len()))
+    }
+}
+
+// Self-consistency: the answer given most often wins, compared after
+// trimming, lowercasing and collapsing whitespace. Needs no extra calls but
+// only suits short answers (a label, a number, yes or no).
+pub struct ConsensusSelector;
+
+fn normalize(content: &str) -> String {
+    content.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+#[async_trait]
+impl CandidateSelector for ConsensusSelector {
+    async fn select(&self, _messages: &[Message], candidates: &[Response]) -> Result<Selection> {
+        let mut votes: HashMap<String, (usize, usize)> = HashMap::new();
+        for (index, candidate) in candidates.iter().enumerate() {
+            votes.entry(normalize(&candidate.content)).or_insert((index, 0)).1 += 1;
+        }
+        // Most votes, then the answer that appeared first
+        votes.into_values()
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+            .map(|(index, _)| Selection { index, tokens_used: 0 })
+            .ok_or_else(|| anyhow::anyhow!("No candidates to select from"))
+    }
+}
+
+// Makes every complete() a best-of-n. The returned usage covers all
+// candidates, since all of them were paid for, and the selector's tokens
+// are added to the total.
+pub struct BestOfN {
+    inner: Box<dyn LLMTrait>,
+    n: usize,
+    selector: Arc<dyn CandidateSelector>,
+}
+
+impl BestOfN {
+    pub fn new(inner: Box<dyn LLMTrait>, n: usize, selector: Arc<dyn CandidateSelector>) -> Self {
+        if n > 1 && inner.get_model_config().temperature <= 0.0 {
+            log::warn!("Best-of-{} with temperature 0 on {} will get identical candidates", n, inner.get_model());
+        }
+        Self { inner, n: n.max(1), selector }
+    }
+}
+
+#[async_trait]
+impl LLMTrait for BestOfN {
+    async fn complete(&self, messages: Vec<Message>) -> Result<Response> {
+        let mut candidates = self.inner.complete_n(messages.clone(), self.n).await?;
+        let mut usage = candidates.iter().fold(
+            Usage { prompt_tokens: 0, completion_tokens: 0, total_tokens: 0 },
+            |total, c| Usage {
+                prompt_tokens: total.prompt_tokens + c.usage.prompt_tokens,
+                completion_tokens: total.completion_tokens + c.usage.completion_tokens,
+                total_tokens: total.total_tokens + c.usage.total_tokens,
+            },
+        );
+
+        let index = if candidates.len() == 1 {
+            0
+        } else {
+            match self.selector.select(&messages, &candidates).await {
+                Ok(selection) => {
+                    usage.total_tokens = usage.total_tokens.saturating_add(u32::try_from(selection.tokens_used).unwrap_or(u32::MAX));
+                    if selection.index < candidates.len() {
+                        selection.index
+                    } else {
+                        log::warn!("Candidate selector picked {} of {} candidates", selection.index, candidates.len());
+                        0
+                    }
+                }
+                // Any candidate is a valid answer, so selection failing
+                // doesn't fail the request
+                Err(e) => {
+                    log::warn!("Candidate selection failed, keeping the first: {}", e);
+                    0
+                }
+            }
+        };
+
+        let mut chosen = candidates.swap_remove(index);
+        chosen.usage = usage;
+        Ok(chosen)
+    }
+
+    // Candidates have to finish before one can be picked, so the winner
+    // arrives as a single chunk and cancelling gives up on all of them
+    async fn complete_stream(&self, messages: Vec<Message>, cancel: CancellationToken) -> Result<ChunkStream> {
+        let chosen = unless_cancelled(self.complete(messages), &cancel).await?;
+        StreamReport::current().model(&chosen.model);
+        let chunk = StreamChunk { content: chosen.content };
+        Ok(Box::pin(futures::stream::iter([Ok(chunk)])))
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.inner.is_initialized()
+    }
+
+    fn get_model(&self) -> &str {
+        self.inner.get_model()
+    }
+
+    fn set_model_config(&mut self, config: ModelConfig) {
+        self.inner.set_model_config(config);
+    }
+
+    fn get_model_config(&self) -> ModelConfig {
+        self.inner.get_model_config()
+    }
+}
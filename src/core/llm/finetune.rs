@@ -0,0 +1,387 @@
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::core::llm::types::Message;
+use crate::utils::egress::EgressClient;
+
+// Where training conversations come from; SessionManager implements it.
+// With an owner, conversations belonging to anyone else are refused as if
+// they didn't exist, since they're about to leave for the provider.
+#[async_trait]
+pub trait ConversationSource: Send + Sync {
+    async fn conversation(&self, session_id: &str, owner: Option<&str>) -> Result<Vec<Message>>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalExample {
+    pub prompt: String,
+    pub expected: String,
+    #[serde(default)]
+    pub system: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FineTuneRequest {
+    pub base_model: String,
+    #[serde(default)]
+    pub suffix: Option<String>,
+    #[serde(default)]
+    pub session_ids: Vec<String>,
+    #[serde(default)]
+    pub evals: Vec<EvalExample>,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FineTuneStatus {
+    Preparing,
+    Uploading,
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl FineTuneStatus {
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, FineTuneStatus::Succeeded | FineTuneStatus::Failed | FineTuneStatus::Cancelled)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FineTuneJob {
+    pub id: String,
+    pub base_model: String,
+    pub status: FineTuneStatus,
+    pub provider_job_id: Option<String>,
+    pub training_file_id: Option<String>,
+    pub example_count: usize,
+    pub fine_tuned_model: Option<String>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisteredModel {
+    pub provider: String,
+    pub base_model: String,
+    pub model: String,
+    pub job_id: String,
+    pub registered_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FineTuneConfig {
+    pub provider: String,
+    pub api_base: String,
+    pub api_key: String,
+    pub poll_interval_secs: u64,
+    pub min_examples: usize,
+    // Fine-tuned models are appended here so the provider config can pick them up
+    pub models_file: String,
+}
+
+impl Default for FineTuneConfig {
+    fn default() -> Self {
+        Self {
+            provider: String::from("openai"),
+            api_base: String::from("https://api.openai.com/v1"),
+            api_key: String::new(),
+            poll_interval_secs: 60,
+            min_examples: 10,
+            models_file: String::from("fine_tuned_models.json"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct TrainingRecord<'a> {
+    messages: Vec<TrainingMessage<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct TrainingMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+// Chat fine-tune format: one `{"messages": [...]}` object per line
+pub fn to_training_jsonl(conversations: &[Vec<Message>]) -> Result<String> {
+    let mut output = String::new();
+    for conversation in conversations {
+        let record = TrainingRecord {
+            messages: conversation.iter()
+                .map(|m| TrainingMessage { role: &m.role, content: &m.content })
+                .collect(),
+        };
+        output.push_str(&serde_json::to_string(&record)?);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderFile {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderJob {
+    id: String,
+    status: String,
+    fine_tuned_model: Option<String>,
+    error: Option<ProviderJobError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderJobError {
+    message: Option<String>,
+}
+
+pub struct FineTuneManager {
+    config: FineTuneConfig,
+    client: EgressClient,
+    source: Arc<dyn ConversationSource>,
+    jobs: Arc<RwLock<HashMap<String, FineTuneJob>>>,
+}
+
+impl FineTuneManager {
+    pub fn new(config: FineTuneConfig, client: EgressClient, source: Arc<dyn ConversationSource>) -> Self {
+        Self {
+            config,
+            client,
+            source,
+            jobs: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub async fn get(&self, id: &str) -> Option<FineTuneJob> {
+        self.jobs.read().await.get(id).cloned()
+    }
+
+    pub async fn list(&self) -> Vec<FineTuneJob> {
+        let mut jobs: Vec<_> = self.jobs.read().await.values().cloned().collect();
+        jobs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        jobs
+    }
+
+    // `owner` restricts which sessions may be used, as in
+    // SessionManager::list; None allows all of them
+    pub async fn submit(&self, request: FineTuneRequest, owner: Option<&str>) -> Result<FineTuneJob> {
+        let conversations = self.collect(&request, owner).await?;
+        if conversations.len() < self.config.min_examples {
+            return Err(anyhow::anyhow!(
+                "Fine-tuning needs at least {} examples, got {}",
+                self.config.min_examples, conversations.len()
+            ));
+        }
+
+        let now = Utc::now();
+        let mut job = FineTuneJob {
+            id: Uuid::new_v4().to_string(),
+            base_model: request.base_model.clone(),
+            status: FineTuneStatus::Uploading,
+            provider_job_id: None,
+            training_file_id: None,
+            example_count: conversations.len(),
+            fine_tuned_model: None,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        };
+        self.jobs.write().await.insert(job.id.clone(), job.clone());
+
+        match self.launch(&request, &conversations).await {
+            Ok((file_id, provider_job)) => {
+                job.training_file_id = Some(file_id);
+                job.provider_job_id = Some(provider_job.id);
+                job.status = map_status(&provider_job.status);
+            }
+            Err(e) => {
+                log::error!("Fine-tune submission failed: {}", e);
+                job.status = FineTuneStatus::Failed;
+                job.error = Some(e.to_string());
+            }
+        }
+        job.updated_at = Utc::now();
+        self.jobs.write().await.insert(job.id.clone(), job.clone());
+
+        Ok(job)
+    }
+
+    async fn collect(&self, request: &FineTuneRequest, owner: Option<&str>) -> Result<Vec<Vec<Message>>> {
+        let mut conversations = Vec::new();
+
+        for session_id in &request.session_ids {
+            let mut messages = self.source.conversation(session_id, owner).await
+                .with_context(|| format!("Failed to load session {}", session_id))?;
+            // Provider system prompts differ from training time; use the requested one
+            if let Some(system) = &request.system_prompt {
+                messages.retain(|m| m.role != "system");
+                messages.insert(0, Message::new("system", system));
+            }
+            if messages.iter().any(|m| m.role == "assistant") {
+                conversations.push(messages);
+            }
+        }
+
+        for eval in &request.evals {
+            let mut messages = Vec::new();
+            if let Some(system) = eval.system.as_ref().or(request.system_prompt.as_ref()) {
+                messages.push(Message::new("system", system));
+            }
+            messages.push(Message::new("user", &eval.prompt));
+            messages.push(Message::new("assistant", &eval.expected));
+            conversations.push(messages);
+        }
+
+        Ok(conversations)
+    }
+
+    async fn launch(&self, request: &FineTuneRequest, conversations: &[Vec<Message>]) -> Result<(String, ProviderJob)> {
+        let training = to_training_jsonl(conversations)?;
+
+        let form = reqwest::multipart::Form::new()
+            .text("purpose", "fine-tune")
+            .part("file", reqwest::multipart::Part::bytes(training.into_bytes())
+                .file_name("training.jsonl"));
+
+        let file: ProviderFile = self.client.post(&format!("{}/files", self.config.api_base))?
+            .bearer_auth(&self.config.api_key)
+            .multipart(form)
+            .send()
+            .await
+            .context("Failed to upload training file")?
+            .error_for_status()
+            .context("Provider rejected training file")?
+            .json()
+            .await
+            .context("Failed to parse file upload response")?;
+
+        let mut body = serde_json::json!({
+            "training_file": file.id,
+            "model": request.base_model,
+        });
+        if let Some(suffix) = &request.suffix {
+            body["suffix"] = serde_json::json!(suffix);
+        }
+
+        let job: ProviderJob = self.client.post(&format!("{}/fine_tuning/jobs", self.config.api_base))?
+            .bearer_auth(&self.config.api_key)
+            .json(&body)
+            .send()
+            .await
+            .context("Failed to create fine-tune job")?
+            .error_for_status()
+            .context("Provider rejected fine-tune job")?
+            .json()
+            .await
+            .context("Failed to parse fine-tune job response")?;
+
+        Ok((file.id, job))
+    }
+
+    pub fn start_polling(self: &Arc<Self>) {
+        let manager = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(
+                tokio::time::Duration::from_secs(manager.config.poll_interval_secs)
+            );
+
+            loop {
+                interval.tick().await;
+                if let Err(e) = manager.refresh().await {
+                    log::error!("Fine-tune status refresh error: {}", e);
+                }
+            }
+        });
+    }
+
+    pub async fn refresh(&self) -> Result<()> {
+        let pending: Vec<_> = self.jobs.read().await.values()
+            .filter(|j| !j.status.is_terminal())
+            .filter_map(|j| j.provider_job_id.clone().map(|p| (j.id.clone(), p)))
+            .collect();
+
+        for (id, provider_job_id) in pending {
+            let remote: ProviderJob = self.client
+                .get(&format!("{}/fine_tuning/jobs/{}", self.config.api_base, provider_job_id))?
+                .bearer_auth(&self.config.api_key)
+                .send()
+                .await
+                .context("Failed to fetch fine-tune job")?
+                .error_for_status()
+                .context("Provider rejected fine-tune status request")?
+                .json()
+                .await
+                .context("Failed to parse fine-tune job")?;
+
+            let mut jobs = self.jobs.write().await;
+            let Some(job) = jobs.get_mut(&id) else {
+                continue;
+            };
+            job.status = map_status(&remote.status);
+            job.fine_tuned_model = remote.fine_tuned_model;
+            job.error = remote.error.and_then(|e| e.message);
+            job.updated_at = Utc::now();
+            let job = job.clone();
+            drop(jobs);
+
+            if job.status == FineTuneStatus::Succeeded {
+                if let Some(model) = &job.fine_tuned_model {
+                    self.register_model(&job, model).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn register_model(&self, job: &FineTuneJob, model: &str) -> Result<()> {
+        let mut models: Vec<RegisteredModel> = if tokio::fs::try_exists(&self.config.models_file).await? {
+            let contents = tokio::fs::read_to_string(&self.config.models_file).await
+                .context("Failed to read fine-tuned models file")?;
+            serde_json::from_str(&contents).context("Failed to parse fine-tuned models file")?
+        } else {
+            Vec::new()
+        };
+
+        if models.iter().any(|m| m.model == model) {
+            return Ok(());
+        }
+        models.push(RegisteredModel {
+            provider: self.config.provider.clone(),
+            base_model: job.base_model.clone(),
+            model: model.to_string(),
+            job_id: job.id.clone(),
+            registered_at: Utc::now(),
+        });
+
+        tokio::fs::write(&self.config.models_file, serde_json::to_string_pretty(&models)?).await
+            .context("Failed to write fine-tuned models file")?;
+        log::info!("Registered fine-tuned model {} from job {}", model, job.id);
+        Ok(())
+    }
+}
+
+fn map_status(status: &str) -> FineTuneStatus {
+    match status {
+        "validating_files" | "queued" => FineTuneStatus::Queued,
+        "running" => FineTuneStatus::Running,
+        "succeeded" => FineTuneStatus::Succeeded,
+        "cancelled" => FineTuneStatus::Cancelled,
+        "failed" => FineTuneStatus::Failed,
+        _ => FineTuneStatus::Preparing,
+    }
+}
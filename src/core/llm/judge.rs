@@ -0,0 +1,263 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+
+use crate::core::llm::{LLMTrait, types::Message};
+use crate::core::llm::structured::parse_reply;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Criterion {
+    pub name: String,
+    pub description: String,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rubric {
+    pub name: String,
+    pub criteria: Vec<Criterion>,
+    pub scale_max: u32,
+}
+
+impl Default for Rubric {
+    fn default() -> Self {
+        let criterion = |name: &str, description: &str| Criterion {
+            name: name.to_string(),
+            description: description.to_string(),
+            weight: 1.0,
+        };
+
+        Self {
+            name: String::from("default"),
+            criteria: vec![
+                criterion("correctness", "The response is factually accurate and answers the question"),
+                criterion("helpfulness", "The response is actionable and addresses the user's need"),
+                criterion("clarity", "The response is concise and easy to follow"),
+            ],
+            scale_max: 5,
+        }
+    }
+}
+
+impl Rubric {
+    // Rubrics arrive in request bodies, so nothing about them can be assumed
+    pub fn validate(&self) -> Result<()> {
+        if self.criteria.is_empty() {
+            return Err(anyhow::anyhow!("Rubric must have at least one criterion"));
+        }
+        if self.scale_max < 1 {
+            return Err(anyhow::anyhow!("Rubric scale_max must be at least 1"));
+        }
+        if let Some(criterion) = self.criteria.iter().find(|c| c.weight < 0.0 || !c.weight.is_finite()) {
+            return Err(anyhow::anyhow!("Criterion {} has an invalid weight", criterion.name));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JudgeRequest {
+    pub prompt: String,
+    pub response: String,
+    #[serde(default)]
+    pub reference: Option<String>,
+    #[serde(default)]
+    pub rubric: Option<Rubric>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CriterionScore {
+    pub name: String,
+    pub score: u32,
+    pub reasoning: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JudgeScore {
+    pub rubric: String,
+    pub criteria: Vec<CriterionScore>,
+    // Weighted average normalized to 0.0..=1.0
+    pub overall: f32,
+    pub judge_model: String,
+    pub scored_at: DateTime<Utc>,
+    // Charged to the caller's quota, not part of the score
+    #[serde(skip)]
+    pub tokens_used: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JudgeConfig {
+    // Fraction of live responses scored for quality monitoring
+    pub live_sample_rate: f32,
+    pub history_size: usize,
+}
+
+impl Default for JudgeConfig {
+    fn default() -> Self {
+        Self {
+            live_sample_rate: 0.0,
+            history_size: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QualitySummary {
+    pub samples: usize,
+    pub mean_overall: Option<f32>,
+    pub mean_by_criterion: HashMap<String, f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JudgeOutput {
+    scores: Vec<CriterionScore>,
+}
+
+const JUDGE_SYSTEM_PROMPT: &str = "You are a strict evaluator. Grade the assistant response \
+against each rubric criterion. Reply with JSON only, in the form \
+{\"scores\": [{\"name\": \"<criterion>\", \"score\": <integer>, \"reasoning\": \"<one sentence>\"}]}.";
+
+pub struct Judge {
+    config: JudgeConfig,
+    llm: Arc<dyn LLMTrait>,
+    live_scores: Arc<RwLock<VecDeque<JudgeScore>>>,
+}
+
+impl Judge {
+    pub fn new(config: JudgeConfig, llm: Arc<dyn LLMTrait>) -> Self {
+        Self {
+            config,
+            llm,
+            live_scores: Arc::new(RwLock::new(VecDeque::new())),
+        }
+    }
+
+    pub async fn score(&self, request: &JudgeRequest) -> Result<JudgeScore> {
+        let rubric = request.rubric.clone().unwrap_or_default();
+        rubric.validate()?;
+
+        let messages = vec![
+            Message::new("system", JUDGE_SYSTEM_PROMPT),
+            Message::new("user", &build_judge_prompt(request, &rubric)),
+        ];
+        let response = self.llm.complete(messages).await
+            .context("Judge model request failed")?;
+
+        let output: JudgeOutput = parse_reply(&response.content, "judge output")?;
+
+        let criteria: Vec<CriterionScore> = rubric.criteria.iter()
+            .map(|criterion| {
+                output.scores.iter()
+                    .find(|s| s.name.eq_ignore_ascii_case(&criterion.name))
+                    .map(|s| CriterionScore {
+                        name: criterion.name.clone(),
+                        score: s.score.clamp(1, rubric.scale_max),
+                        reasoning: s.reasoning.clone(),
+                    })
+                    .ok_or_else(|| anyhow::anyhow!("Judge did not score criterion {}", criterion.name))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(JudgeScore {
+            rubric: rubric.name.clone(),
+            overall: weighted_score(&rubric, &criteria),
+            criteria,
+            judge_model: response.model,
+            scored_at: Utc::now(),
+            tokens_used: response.usage.total_tokens as u64,
+        })
+    }
+
+    // Called on the response path; scores a sample in the background so
+    // judge latency never reaches the user
+    pub fn sample_live(self: &Arc<Self>, prompt: &str, response: &str) {
+        if self.config.live_sample_rate <= 0.0
+            || rand::thread_rng().gen::<f32>() >= self.config.live_sample_rate
+        {
+            return;
+        }
+
+        let judge = self.clone();
+        let request = JudgeRequest {
+            prompt: prompt.to_string(),
+            response: response.to_string(),
+            reference: None,
+            rubric: None,
+        };
+
+        tokio::spawn(async move {
+            match judge.score(&request).await {
+                Ok(score) => {
+                    let mut scores = judge.live_scores.write().await;
+                    scores.push_back(score);
+                    while scores.len() > judge.config.history_size {
+                        scores.pop_front();
+                    }
+                }
+                Err(e) => log::warn!("Live judge scoring failed: {}", e),
+            }
+        });
+    }
+
+    pub async fn summary(&self) -> QualitySummary {
+        let scores = self.live_scores.read().await;
+        let mut totals: HashMap<String, (f32, usize)> = HashMap::new();
+
+        for score in scores.iter() {
+            for criterion in &score.criteria {
+                let entry = totals.entry(criterion.name.clone()).or_insert((0.0, 0));
+                entry.0 += criterion.score as f32;
+                entry.1 += 1;
+            }
+        }
+
+        QualitySummary {
+            samples: scores.len(),
+            mean_overall: if scores.is_empty() {
+                None
+            } else {
+                Some(scores.iter().map(|s| s.overall).sum::<f32>() / scores.len() as f32)
+            },
+            mean_by_criterion: totals.into_iter()
+                .map(|(name, (sum, count))| (name, sum / count as f32))
+                .collect(),
+        }
+    }
+}
+
+fn build_judge_prompt(request: &JudgeRequest, rubric: &Rubric) -> String {
+    let mut prompt = format!("Score each criterion from 1 to {}.\n\nCriteria:\n", rubric.scale_max);
+    for criterion in &rubric.criteria {
+        prompt.push_str(&format!("- {}: {}\n", criterion.name, criterion.description));
+    }
+
+    prompt.push_str(&format!("\nUser prompt:\n{}\n\nAssistant response:\n{}\n", request.prompt, request.response));
+    if let Some(reference) = &request.reference {
+        prompt.push_str(&format!("\nReference answer:\n{}\n", reference));
+    }
+    prompt
+}
+
+pub fn weighted_score(rubric: &Rubric, scores: &[CriterionScore]) -> f32 {
+    let span = rubric.scale_max.saturating_sub(1).max(1) as f32;
+    let mut total = 0.0;
+    let mut weights = 0.0;
+
+    for criterion in &rubric.criteria {
+        if let Some(score) = scores.iter().find(|s| s.name == criterion.name) {
+            total += criterion.weight * (score.score.saturating_sub(1) as f32 / span);
+            weights += criterion.weight;
+        }
+    }
+
+    if weights > 0.0 { total / weights } else { 0.0 }
+}
@@ -0,0 +1,262 @@
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::RwLock;
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::core::alerts::AlertManager;
+use crate::core::llm::types::StreamChunk;
+use crate::core::notify::Severity;
+
+type ChunkStream = Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>;
+
+// Upper bounds in milliseconds; the last bucket is open-ended
+const BUCKETS_MS: [f64; 10] = [50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0, 30000.0, f64::INFINITY];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SloConfig {
+    pub provider: String,
+    pub model: String,
+    pub p95_ttft_ms: f64,
+    pub p95_completion_ms: f64,
+    // Fraction of requests expected to meet the TTFT target, e.g. 0.95
+    pub objective: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencyTrackerConfig {
+    pub slos: Vec<SloConfig>,
+    pub sample_retention_minutes: i64,
+    pub evaluation_interval_secs: u64,
+    pub short_window_minutes: i64,
+    pub long_window_minutes: i64,
+    // Alert when both windows burn error budget faster than this
+    pub burn_rate_threshold: f64,
+}
+
+impl Default for LatencyTrackerConfig {
+    fn default() -> Self {
+        Self {
+            slos: Vec::new(),
+            sample_retention_minutes: 60,
+            evaluation_interval_secs: 60,
+            short_window_minutes: 5,
+            long_window_minutes: 60,
+            burn_rate_threshold: 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Sample {
+    at: DateTime<Utc>,
+    ttft_ms: Option<f64>,
+    completion_ms: f64,
+}
+
+#[derive(Debug, Default)]
+struct Series {
+    ttft_buckets: [u64; BUCKETS_MS.len()],
+    completion_buckets: [u64; BUCKETS_MS.len()],
+    samples: VecDeque<Sample>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramSnapshot {
+    pub buckets: Vec<(f64, u64)>,
+    pub count: u64,
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+    pub p99_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyReport {
+    pub provider: String,
+    pub model: String,
+    pub time_to_first_token: HistogramSnapshot,
+    pub time_to_completion: HistogramSnapshot,
+    pub burn_rate_short: Option<f64>,
+    pub burn_rate_long: Option<f64>,
+}
+
+pub struct LatencyTracker {
+    config: LatencyTrackerConfig,
+    series: RwLock<HashMap<(String, String), Series>>,
+}
+
+impl LatencyTracker {
+    pub fn new(config: LatencyTrackerConfig) -> Self {
+        Self {
+            config,
+            series: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub async fn record(&self, provider: &str, model: &str, ttft_ms: Option<f64>, completion_ms: f64) {
+        let mut series = self.series.write().await;
+        let entry = series.entry((provider.to_string(), model.to_string())).or_default();
+
+        if let Some(ttft) = ttft_ms {
+            entry.ttft_buckets[bucket_index(ttft)] += 1;
+        }
+        entry.completion_buckets[bucket_index(completion_ms)] += 1;
+
+        let now = Utc::now();
+        entry.samples.push_back(Sample { at: now, ttft_ms, completion_ms });
+        let cutoff = now - Duration::minutes(self.config.sample_retention_minutes);
+        while entry.samples.front().map_or(false, |s| s.at < cutoff) {
+            entry.samples.pop_front();
+        }
+    }
+
+    // Wraps a provider stream and records TTFT and completion time once it ends
+    pub fn track_stream(self: &Arc<Self>, provider: &str, model: &str, stream: ChunkStream) -> ChunkStream {
+        let tracker = self.clone();
+        let provider = provider.to_string();
+        let model = model.to_string();
+
+        Box::pin(async_stream::stream! {
+            let started = Instant::now();
+            let mut first_token: Option<f64> = None;
+            let mut stream = stream;
+
+            while let Some(item) = stream.next().await {
+                if first_token.is_none() && matches!(&item, Ok(chunk) if !chunk.content.is_empty()) {
+                    first_token = Some(started.elapsed().as_secs_f64() * 1000.0);
+                }
+                yield item;
+            }
+
+            let completion = started.elapsed().as_secs_f64() * 1000.0;
+            tracker.record(&provider, &model, first_token, completion).await;
+        })
+    }
+
+    pub async fn report(&self) -> Vec<LatencyReport> {
+        let series = self.series.read().await;
+        let now = Utc::now();
+
+        series.iter()
+            .map(|((provider, model), series)| {
+                let slo = self.slo_for(provider, model);
+                LatencyReport {
+                    provider: provider.clone(),
+                    model: model.clone(),
+                    time_to_first_token: histogram_snapshot(
+                        &series.ttft_buckets,
+                        series.samples.iter().filter_map(|s| s.ttft_ms).collect(),
+                    ),
+                    time_to_completion: histogram_snapshot(
+                        &series.completion_buckets,
+                        series.samples.iter().map(|s| s.completion_ms).collect(),
+                    ),
+                    burn_rate_short: slo.and_then(|slo| {
+                        burn_rate(series, slo, now - Duration::minutes(self.config.short_window_minutes))
+                    }),
+                    burn_rate_long: slo.and_then(|slo| {
+                        burn_rate(series, slo, now - Duration::minutes(self.config.long_window_minutes))
+                    }),
+                }
+            })
+            .collect()
+    }
+
+    fn slo_for(&self, provider: &str, model: &str) -> Option<&SloConfig> {
+        self.config.slos.iter().find(|s| s.provider == provider && s.model == model)
+    }
+
+    pub fn start_slo_monitor(self: &Arc<Self>, alerts: Arc<AlertManager>) {
+        let tracker = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(
+                tokio::time::Duration::from_secs(tracker.config.evaluation_interval_secs)
+            );
+
+            loop {
+                interval.tick().await;
+                for report in tracker.report().await {
+                    let Some(slo) = tracker.slo_for(&report.provider, &report.model) else {
+                        continue;
+                    };
+                    tracker.evaluate(&alerts, slo, &report).await;
+                }
+            }
+        });
+    }
+
+    async fn evaluate(&self, alerts: &AlertManager, slo: &SloConfig, report: &LatencyReport) {
+        let key = format!("llm_latency_slo:{}:{}", slo.provider, slo.model);
+        let labels = HashMap::from([
+            ("provider".to_string(), slo.provider.clone()),
+            ("model".to_string(), slo.model.clone()),
+        ]);
+
+        let p95_ttft = report.time_to_first_token.p95_ms.unwrap_or(0.0);
+        let p95_completion = report.time_to_completion.p95_ms.unwrap_or(0.0);
+        let threshold = self.config.burn_rate_threshold;
+        let burning = matches!(
+            (report.burn_rate_short, report.burn_rate_long),
+            (Some(short), Some(long)) if short > threshold && long > threshold
+        );
+
+        if p95_ttft > slo.p95_ttft_ms || p95_completion > slo.p95_completion_ms || burning {
+            let severity = if burning { Severity::Critical } else { Severity::Warning };
+            let message = format!(
+                "{}/{}: p95 TTFT {:.0}ms (target {:.0}ms), p95 completion {:.0}ms (target {:.0}ms), burn rate {:.2}/{:.2}",
+                slo.provider, slo.model,
+                p95_ttft, slo.p95_ttft_ms,
+                p95_completion, slo.p95_completion_ms,
+                report.burn_rate_short.unwrap_or(0.0),
+                report.burn_rate_long.unwrap_or(0.0),
+            );
+            alerts.raise(&key, "LLM latency SLO breach", severity, &message, labels).await;
+        } else {
+            alerts.resolve(&key).await;
+        }
+    }
+}
+
+fn bucket_index(value_ms: f64) -> usize {
+    BUCKETS_MS.iter().position(|bound| value_ms <= *bound).unwrap_or(BUCKETS_MS.len() - 1)
+}
+
+fn histogram_snapshot(buckets: &[u64; BUCKETS_MS.len()], mut samples: Vec<f64>) -> HistogramSnapshot {
+    samples.sort_by(|a, b| a.total_cmp(b));
+
+    HistogramSnapshot {
+        buckets: BUCKETS_MS.iter().copied().zip(buckets.iter().copied()).collect(),
+        count: buckets.iter().sum(),
+        p50_ms: percentile(&samples, 0.50),
+        p95_ms: percentile(&samples, 0.95),
+        p99_ms: percentile(&samples, 0.99),
+    }
+}
+
+pub fn percentile(sorted: &[f64], q: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (q * (sorted.len() - 1) as f64).round() as usize;
+    Some(sorted[rank.min(sorted.len() - 1)])
+}
+
+// Ratio of the observed bad-request rate to the rate the SLO allows; 1.0
+// means the error budget is being consumed exactly as fast as planned
+fn burn_rate(series: &Series, slo: &SloConfig, since: DateTime<Utc>) -> Option<f64> {
+    let window: Vec<_> = series.samples.iter().filter(|s| s.at >= since).collect();
+    if window.is_empty() {
+        return None;
+    }
+
+    let bad = window.iter()
+        .filter(|s| s.ttft_ms.map_or(true, |ttft| ttft > slo.p95_ttft_ms))
+        .count();
+    let budget = (1.0 - slo.objective).max(f64::EPSILON);
+    Some((bad as f64 / window.len() as f64) / budget)
+}
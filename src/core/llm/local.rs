@@ -0,0 +1,362 @@
+#![cfg(feature = "llm-local")]
+
+use std::num::NonZeroU32;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use serde::{Serialize, Deserialize};
+use tokio::sync::{mpsc, Semaphore};
+use llama_cpp_2::{
+    context::params::LlamaContextParams,
+    llama_backend::LlamaBackend,
+    llama_batch::LlamaBatch,
+    model::{params::LlamaModelParams, AddBos, LlamaChatMessage, LlamaModel, Special},
+    sampling::LlamaSampler,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::core::llm::{
+    LLMTrait,
+    annotated::{StreamReport, TokenLogprob},
+    cancel::{unless_cancelled, until_cancelled},
+    overrides::for_call,
+    types::{Message, ModelConfig, Response, StreamChunk, Usage},
+};
+
+type ChunkStream = Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LocalLLMConfig {
+    pub model_path: String,
+    pub context_size: u32,
+    // Layers offloaded to the GPU; 0 runs entirely on the CPU
+    pub gpu_layers: u32,
+    pub threads: Option<i32>,
+    // Each generation needs its own KV cache, so this bounds memory too
+    pub max_parallel: usize,
+    pub seed: u32,
+    // Report each sampled token's logprob on annotated streams. Costs a
+    // pass over the vocabulary per token.
+    pub logprobs: bool,
+}
+
+impl Default for LocalLLMConfig {
+    fn default() -> Self {
+        Self {
+            model_path: String::new(),
+            context_size: 4096,
+            gpu_layers: 0,
+            threads: None,
+            max_parallel: 1,
+            seed: 1234,
+            logprobs: false,
+        }
+    }
+}
+
+enum Generated {
+    Prompt(u32),
+    // The text with the tokens it was decoded from, when logprobs are on
+    Text(String, Vec<TokenLogprob>),
+    // Tokens generated and the finish reason
+    Done(u32, &'static str),
+}
+
+// Runs GGUF models in-process through llama.cpp. Generation is blocking
+// and happens on the blocking thread pool; text is forwarded as it is
+// sampled.
+#[derive(Clone)]
+pub struct LocalLLM {
+    config: LocalLLMConfig,
+    backend: &'static LlamaBackend,
+    model: Arc<LlamaModel>,
+    name: String,
+    model_config: ModelConfig,
+    slots: Arc<Semaphore>,
+}
+
+impl LocalLLM {
+    pub fn new(config: LocalLLMConfig) -> Result<Self> {
+        if !std::path::Path::new(&config.model_path).is_file() {
+            return Err(anyhow::anyhow!("GGUF model not found: {}", config.model_path));
+        }
+
+        let backend = backend()?;
+        let params = LlamaModelParams::default().with_n_gpu_layers(config.gpu_layers);
+        let model = LlamaModel::load_from_file(backend, &config.model_path, &params)
+            .with_context(|| format!("Failed to load GGUF model {}", config.model_path))?;
+        let name = std::path::Path::new(&config.model_path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| config.model_path.clone());
+        log::info!("Loaded local model {} ({} GPU layers)", name, config.gpu_layers);
+
+        Ok(Self {
+            slots: Arc::new(Semaphore::new(config.max_parallel.max(1))),
+            config,
+            backend,
+            model: Arc::new(model),
+            name,
+            model_config: ModelConfig::default(),
+        })
+    }
+
+    // Uses the chat template embedded in the GGUF file, falling back to
+    // ChatML for models that don't ship one
+    fn render_prompt(&self, messages: &[Message]) -> Result<String> {
+        if messages.is_empty() {
+            return Err(anyhow::anyhow!("No messages to complete"));
+        }
+        if let Ok(template) = self.model.chat_template(None) {
+            let chat = messages.iter()
+                .map(|m| LlamaChatMessage::new(m.role.clone(), m.content.clone()))
+                .collect::<Result<Vec<_>, _>>()
+                .context("Invalid chat message")?;
+            if let Ok(prompt) = self.model.apply_chat_template(&template, &chat, true) {
+                return Ok(prompt);
+            }
+        }
+        Ok(chatml(messages))
+    }
+
+    async fn generate(&self, messages: Vec<Message>, cancel: CancellationToken) -> Result<mpsc::Receiver<Result<Generated>>> {
+        let prompt = self.render_prompt(&messages)?;
+        // Read here: the task-local override doesn't reach the blocking pool
+        let (_, model_config) = for_call(&self.name, &self.model_config);
+        let permit = unless_cancelled(
+            async { self.slots.clone().acquire_owned().await.context("Local model is shutting down") },
+            &cancel,
+        ).await?;
+        let (tx, rx) = mpsc::channel(64);
+
+        let llm = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            if let Err(e) = llm.run(&prompt, &model_config, &tx, &cancel) {
+                let _ = tx.blocking_send(Err(e));
+            }
+        });
+        Ok(rx)
+    }
+
+    fn run(
+        &self,
+        prompt: &str,
+        model_config: &ModelConfig,
+        tx: &mpsc::Sender<Result<Generated>>,
+        cancel: &CancellationToken,
+    ) -> Result<()> {
+        let mut params = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(self.config.context_size));
+        if let Some(threads) = self.config.threads {
+            params = params.with_n_threads(threads).with_n_threads_batch(threads);
+        }
+        let mut ctx = self.model.new_context(self.backend, params)
+            .context("Failed to create llama.cpp context")?;
+
+        let mut tokens = self.model.str_to_token(prompt, AddBos::Always)
+            .context("Failed to tokenize prompt")?;
+        // Chat templates usually start with the BOS text themselves, which
+        // tokenizes to a second one
+        let bos = self.model.token_bos();
+        if tokens.len() > 1 && tokens[0] == bos && tokens[1] == bos {
+            tokens.remove(0);
+        }
+        let n_ctx = ctx.n_ctx() as usize;
+        let max_tokens = model_config.max_tokens as usize;
+        if tokens.len() + 1 >= n_ctx {
+            return Err(anyhow::anyhow!(
+                "Prompt is {} tokens but the context holds {}", tokens.len(), n_ctx
+            ));
+        }
+        if tx.blocking_send(Ok(Generated::Prompt(tokens.len() as u32))).is_err() {
+            return Ok(());
+        }
+
+        // A decode takes at most n_batch tokens, so longer prompts go in
+        // several; only the last position needs logits
+        let n_batch = (ctx.n_batch() as usize).max(1);
+        let mut batch = LlamaBatch::new(n_batch, 1);
+        let last = tokens.len() - 1;
+        for (chunk, span) in tokens.chunks(n_batch).enumerate() {
+            batch.clear();
+            for (offset, token) in span.iter().enumerate() {
+                let i = chunk * n_batch + offset;
+                batch.add(*token, i as i32, &[0], i == last)?;
+            }
+            ctx.decode(&mut batch).context("Failed to evaluate prompt")?;
+        }
+
+        // llama.cpp samples with both; unlike hosted APIs there's no reason
+        // to pick one. Penalties map onto its repetition sampler.
+        let mut sampler = LlamaSampler::chain_simple([
+            LlamaSampler::penalties(
+                64,
+                1.0,
+                model_config.frequency_penalty,
+                model_config.presence_penalty,
+            ),
+            LlamaSampler::top_p(model_config.top_p, 1),
+            LlamaSampler::temp(model_config.temperature),
+            LlamaSampler::dist(self.config.seed),
+        ]);
+
+        // A multi-byte character can be split across tokens; bytes are held
+        // until they form complete UTF-8
+        let mut pending: Vec<u8> = Vec::new();
+        let mut logprobs = Vec::new();
+        let mut position = tokens.len() as i32;
+        let mut generated = 0u32;
+        let mut finish_reason = "length";
+        while (generated as usize) < max_tokens && (position as usize) < n_ctx {
+            if cancel.is_cancelled() {
+                return Ok(());
+            }
+            let token = sampler.sample(&ctx, batch.n_tokens() - 1);
+            sampler.accept(token);
+            if self.model.is_eog_token(token) {
+                finish_reason = "stop";
+                break;
+            }
+            generated += 1;
+
+            let bytes = self.model.token_to_bytes(token, Special::Tokenize)?;
+            if self.config.logprobs {
+                let logits = ctx.get_logits_ith(batch.n_tokens() - 1);
+                logprobs.push(TokenLogprob {
+                    token: String::from_utf8_lossy(&bytes).to_string(),
+                    logprob: log_softmax(logits, token.0 as usize),
+                    top_logprobs: Vec::new(),
+                });
+            }
+            pending.extend(bytes);
+            let piece = take_utf8(&mut pending);
+            // The receiver is gone when the caller stopped reading; stop
+            // generating rather than burning the slot
+            if !piece.is_empty()
+                && tx.blocking_send(Ok(Generated::Text(piece, std::mem::take(&mut logprobs)))).is_err()
+            {
+                return Ok(());
+            }
+
+            batch.clear();
+            batch.add(token, position, &[0], true)?;
+            ctx.decode(&mut batch).context("Failed to evaluate token")?;
+            position += 1;
+        }
+
+        if !pending.is_empty() {
+            let _ = tx.blocking_send(Ok(Generated::Text(String::from_utf8_lossy(&pending).to_string(), logprobs)));
+        }
+        let _ = tx.blocking_send(Ok(Generated::Done(generated, finish_reason)));
+        Ok(())
+    }
+}
+
+fn log_softmax(logits: &[f32], index: usize) -> f32 {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let sum: f32 = logits.iter().map(|logit| (logit - max).exp()).sum();
+    logits[index] - max - sum.ln()
+}
+
+// llama.cpp's backend is process-wide and can only be initialized once;
+// every model shares it
+fn backend() -> Result<&'static LlamaBackend> {
+    static BACKEND: OnceLock<Result<LlamaBackend, String>> = OnceLock::new();
+    BACKEND.get_or_init(|| LlamaBackend::init().map_err(|e| e.to_string()))
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("Failed to initialize llama.cpp backend: {}", e))
+}
+
+fn take_utf8(pending: &mut Vec<u8>) -> String {
+    let valid = match std::str::from_utf8(pending) {
+        Ok(_) => pending.len(),
+        // Invalid bytes (not just an incomplete tail) are replaced rather
+        // than held forever
+        Err(e) if e.error_len().is_some() => return String::from_utf8_lossy(&std::mem::take(pending)).to_string(),
+        Err(e) => e.valid_up_to(),
+    };
+    let rest = pending.split_off(valid);
+    String::from_utf8(std::mem::replace(pending, rest)).unwrap_or_default()
+}
+
+fn chatml(messages: &[Message]) -> String {
+    let mut prompt = String::new();
+    for message in messages {
+        prompt.push_str(&format!("<|im_start|>{}\n{}<|im_end|>\n", message.role, message.content));
+    }
+    prompt.push_str("<|im_start|>assistant\n");
+    prompt
+}
+
+#[async_trait]
+impl LLMTrait for LocalLLM {
+    async fn complete(&self, messages: Vec<Message>) -> Result<Response> {
+        let mut rx = self.generate(messages, CancellationToken::new()).await?;
+        let mut content = String::new();
+        let mut usage = Usage { prompt_tokens: 0, completion_tokens: 0, total_tokens: 0 };
+        while let Some(item) = rx.recv().await {
+            match item? {
+                Generated::Prompt(tokens) => usage.prompt_tokens = tokens,
+                Generated::Text(text, _) => content.push_str(&text),
+                Generated::Done(tokens, _) => usage.completion_tokens = tokens,
+            }
+        }
+        usage.total_tokens = usage.prompt_tokens + usage.completion_tokens;
+
+        Ok(Response {
+            content,
+            role: "assistant".to_string(),
+            model: self.name.clone(),
+            usage,
+        })
+    }
+
+    async fn complete_stream(&self, messages: Vec<Message>, cancel: CancellationToken) -> Result<ChunkStream> {
+        let rx = self.generate(messages, cancel.clone()).await?;
+        // Generation runs ahead of the reader, so logprobs are handed to
+        // the report as their chunk goes out, not as they are sampled
+        let report = StreamReport::current();
+        report.model(&self.name);
+        let stream = futures::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|item| (item, rx))
+        })
+        .filter_map(move |item| {
+            let report = report.clone();
+            async move {
+                match item {
+                    Ok(Generated::Text(text, logprobs)) => {
+                        logprobs.into_iter().for_each(|logprob| report.logprob(logprob));
+                        Some(Ok(StreamChunk { content: text }))
+                    }
+                    Ok(Generated::Done(_, reason)) => {
+                        report.finish(reason);
+                        None
+                    }
+                    Ok(Generated::Prompt(_)) => None,
+                    Err(e) => Some(Err(e)),
+                }
+            }
+        });
+        Ok(until_cancelled(Box::pin(stream), cancel))
+    }
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn get_model(&self) -> &str {
+        &self.name
+    }
+
+    fn set_model_config(&mut self, config: ModelConfig) {
+        self.model_config = config;
+    }
+
+    fn get_model_config(&self) -> ModelConfig {
+        self.model_config.clone()
+    }
+}
@@ -0,0 +1,194 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+
+use crate::core::llm::types::{Message, Response};
+#[cfg(feature = "vision")]
+use crate::vision::{privacy::PrivacyMasker, processor::Frame};
+
+// Messages that carry images next to text, for vision-capable models such
+// as GPT-4o and Claude. Plain `Message` stays text-only; anything that
+// needs images builds these instead, and text messages convert losslessly.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ImageSource {
+    Base64 { media_type: String, data: String },
+    Url { url: String },
+}
+
+// OpenAI's resolution hint; other providers ignore it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageDetail {
+    #[default]
+    Auto,
+    Low,
+    High,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImagePart {
+    pub source: ImageSource,
+    #[serde(default)]
+    pub detail: ImageDetail,
+}
+
+impl ImagePart {
+    pub fn base64(media_type: &str, data: &str) -> Self {
+        Self {
+            source: ImageSource::Base64 { media_type: media_type.to_string(), data: data.to_string() },
+            detail: ImageDetail::default(),
+        }
+    }
+
+    pub fn jpeg(bytes: &[u8]) -> Self {
+        Self::base64("image/jpeg", &BASE64.encode(bytes))
+    }
+
+    pub fn url(url: &str) -> Self {
+        Self {
+            source: ImageSource::Url { url: url.to_string() },
+            detail: ImageDetail::default(),
+        }
+    }
+
+    pub fn with_detail(mut self, detail: ImageDetail) -> Self {
+        self.detail = detail;
+        self
+    }
+
+    // Inline images as a data: URL, remote ones as they are
+    pub fn data_url(&self) -> String {
+        match &self.source {
+            ImageSource::Base64 { media_type, data } => format!("data:{};base64,{}", media_type, data),
+            ImageSource::Url { url } => url.clone(),
+        }
+    }
+
+    // Captured frames go through the privacy masker like every other egress
+    // point, and are downscaled so the longest side is at most `max_side`
+    // pixels (0 keeps the original size); providers resize large images
+    // anyway and bill for the upload.
+    #[cfg(feature = "vision")]
+    pub fn from_frame(frame: &Frame, masker: &PrivacyMasker, max_side: u32) -> Result<Self> {
+        use anyhow::Context;
+        use opencv::{core::{Mat, Size, Vector}, imgcodecs, imgproc, prelude::*};
+
+        masker.ensure_masked(frame)?;
+        let image: &Mat = &frame.data;
+        let longest = image.cols().max(image.rows());
+        let resized;
+        let image = if max_side > 0 && longest > max_side as i32 {
+            let scale = max_side as f64 / longest as f64;
+            let mut out = Mat::default();
+            imgproc::resize(image, &mut out, Size::new(0, 0), scale, scale, imgproc::INTER_AREA)
+                .context("Failed to resize frame")?;
+            resized = out;
+            &resized
+        } else {
+            image
+        };
+
+        let mut buffer = Vector::<u8>::new();
+        imgcodecs::imencode(".jpg", image, &mut buffer, &Vector::new())
+            .context("Failed to encode frame")?;
+        Ok(Self::jpeg(buffer.as_slice()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ContentPart {
+    Text { text: String },
+    Image(ImagePart),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MultimodalMessage {
+    pub role: String,
+    pub parts: Vec<ContentPart>,
+}
+
+impl MultimodalMessage {
+    pub fn new(role: &str, text: &str) -> Self {
+        Self { role: role.to_string(), parts: Vec::new() }.with_text(text)
+    }
+
+    pub fn with_text(mut self, text: &str) -> Self {
+        if !text.is_empty() {
+            self.parts.push(ContentPart::Text { text: text.to_string() });
+        }
+        self
+    }
+
+    pub fn with_image(mut self, image: ImagePart) -> Self {
+        self.parts.push(ContentPart::Image(image));
+        self
+    }
+
+    pub fn has_images(&self) -> bool {
+        self.parts.iter().any(|p| matches!(p, ContentPart::Image(_)))
+    }
+
+    // The text parts only, for providers or logs that can't take images
+    pub fn text(&self) -> String {
+        self.parts.iter()
+            .filter_map(|p| match p {
+                ContentPart::Text { text } => Some(text.as_str()),
+                ContentPart::Image(_) => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+impl From<Message> for MultimodalMessage {
+    fn from(message: Message) -> Self {
+        Self::new(&message.role, &message.content)
+    }
+}
+
+impl From<&Message> for MultimodalMessage {
+    fn from(message: &Message) -> Self {
+        Self::new(&message.role, &message.content)
+    }
+}
+
+// OpenAI chat format: text-only messages keep a plain string content so
+// older compatible servers still accept them
+pub fn openai_message(message: &MultimodalMessage) -> Value {
+    if !message.has_images() {
+        return json!({ "role": message.role, "content": message.text() });
+    }
+    let content: Vec<Value> = message.parts.iter()
+        .map(|part| match part {
+            ContentPart::Text { text } => json!({ "type": "text", "text": text }),
+            ContentPart::Image(image) => json!({
+                "type": "image_url",
+                "image_url": { "url": image.data_url(), "detail": image.detail },
+            }),
+        })
+        .collect();
+    json!({ "role": message.role, "content": content })
+}
+
+pub fn openai_messages(messages: &[MultimodalMessage]) -> Vec<Value> {
+    messages.iter().map(openai_message).collect()
+}
+
+// Anthropic content blocks; images are only allowed in user turns there
+pub fn anthropic_block(part: &ContentPart) -> Value {
+    match part {
+        ContentPart::Text { text } => json!({ "type": "text", "text": text }),
+        ContentPart::Image(image) => json!({ "type": "image", "source": image.source }),
+    }
+}
+
+// Providers that can look at images implement this next to LLMTrait
+#[async_trait]
+pub trait MultimodalLLM: Send + Sync {
+    async fn complete_multimodal(&self, messages: Vec<MultimodalMessage>) -> Result<Response>;
+}
@@ -0,0 +1,125 @@
+#![cfg(feature = "llm-openai")]
+
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::core::llm::{
+    multimodal::{openai_messages, MultimodalLLM, MultimodalMessage},
+    overrides::for_call,
+    types::{ModelConfig, Response, Usage},
+};
+use crate::utils::egress::EgressClient;
+
+const API_BASE: &str = "https://api.openai.com/v1";
+pub const DEFAULT_MODEL: &str = "gpt-4o";
+
+// GPT-4o and other OpenAI-compatible chat models, with images sent as
+// image_url parts. Goes through the EgressClient so frames only leave for
+// allowlisted hosts.
+pub struct OpenAIVision {
+    client: EgressClient,
+    api_key: String,
+    api_base: String,
+    model: String,
+    model_config: ModelConfig,
+}
+
+impl OpenAIVision {
+    pub fn new(api_key: &str, client: EgressClient) -> Self {
+        Self {
+            client,
+            api_key: api_key.to_string(),
+            api_base: API_BASE.to_string(),
+            model: DEFAULT_MODEL.to_string(),
+            model_config: ModelConfig::default(),
+        }
+    }
+
+    pub fn with_api_base(mut self, api_base: &str) -> Self {
+        self.api_base = api_base.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+
+    pub fn with_model_config(mut self, config: ModelConfig) -> Self {
+        self.model_config = config;
+        self
+    }
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    model: String,
+    choices: Vec<ChatChoice>,
+    #[serde(default)]
+    usage: Option<ChatUsage>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatUsage {
+    #[serde(default)]
+    prompt_tokens: u32,
+    #[serde(default)]
+    completion_tokens: u32,
+}
+
+#[async_trait]
+impl MultimodalLLM for OpenAIVision {
+    async fn complete_multimodal(&self, messages: Vec<MultimodalMessage>) -> Result<Response> {
+        let (model, config) = for_call(&self.model, &self.model_config);
+        let body = json!({
+            "model": model,
+            "messages": openai_messages(&messages),
+            "max_tokens": config.max_tokens.max(1),
+            "temperature": config.temperature,
+            "top_p": config.top_p,
+            "frequency_penalty": config.frequency_penalty,
+            "presence_penalty": config.presence_penalty,
+        });
+
+        let response: ChatResponse = self.client.post(&format!("{}/chat/completions", self.api_base))?
+            .bearer_auth(&self.api_key)
+            .json(&body)
+            .send()
+            .await
+            .context("Failed to call OpenAI API")?
+            .error_for_status()
+            .context("OpenAI API rejected request")?
+            .json()
+            .await
+            .context("Failed to parse OpenAI response")?;
+
+        let content = response.choices.into_iter()
+            .next()
+            .and_then(|c| c.message.content)
+            .unwrap_or_default();
+        let (prompt_tokens, completion_tokens) = response.usage
+            .map_or((0, 0), |u| (u.prompt_tokens, u.completion_tokens));
+        Ok(Response {
+            content,
+            role: "assistant".to_string(),
+            model: response.model,
+            usage: Usage {
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens + completion_tokens,
+            },
+        })
+    }
+}
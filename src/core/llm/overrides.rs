@@ -0,0 +1,245 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::Stream;
+use serde::{Serialize, Deserialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::core::llm::{
+    LLMTrait,
+    types::{Message, ModelConfig, Response, StreamChunk},
+};
+use crate::utils::pattern::wildcard_matches;
+
+type ChunkStream = Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>;
+
+// Per-request `model`, `temperature` and `max_tokens` from a completion
+// body. Clients may only pick models the operator allowed:
+//
+//   llm_overrides:
+//     allowed_models:
+//       - model: claude-haiku-4-5
+//         provider: anthropic
+//       - model: "claude-sonnet-*"
+//         provider: anthropic
+//     max_tokens: 4096
+//
+// A trailing '*' matches any suffix. With no allowed models, requests can
+// still tune temperature and max_tokens but not switch models.
+//
+// Overrides never touch the shared instance and never build a new one.
+// The call runs through the shared stack (router, cache, context guard,
+// fault injection) with the override in a task-local, and providers apply
+// it when they build the request; see for_call().
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllowedModel {
+    pub model: String,
+    // Provider name as in `llm_provider`
+    pub provider: String,
+}
+
+impl AllowedModel {
+    fn matches(&self, model: &str) -> bool {
+        wildcard_matches(&self.model, model)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelOverrideConfig {
+    pub allowed_models: Vec<AllowedModel>,
+    pub min_temperature: f32,
+    pub max_temperature: f32,
+    // Upper bound for a requested max_tokens
+    pub max_tokens: u32,
+}
+
+impl Default for ModelOverrideConfig {
+    fn default() -> Self {
+        Self {
+            allowed_models: Vec::new(),
+            min_temperature: 0.0,
+            max_temperature: 2.0,
+            max_tokens: 4096,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelOverride {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+}
+
+impl ModelOverride {
+    pub fn is_empty(&self) -> bool {
+        self.model.is_none() && self.temperature.is_none() && self.max_tokens.is_none()
+    }
+}
+
+// What one overridden call changes. `provider` restricts the router to
+// that provider, since the model only exists there.
+#[derive(Debug, Clone)]
+struct CallOverride {
+    provider: Option<String>,
+    model: Option<String>,
+    config: ModelConfig,
+}
+
+tokio::task_local! {
+    static CALL: CallOverride;
+}
+
+// The model and sampling config a provider should use for the current
+// call: its own, unless the call is overridden
+pub fn for_call(model: &str, config: &ModelConfig) -> (String, ModelConfig) {
+    CALL.try_with(|call| (
+        call.model.clone().unwrap_or_else(|| model.to_string()),
+        call.config.clone(),
+    ))
+    .unwrap_or_else(|_| (model.to_string(), config.clone()))
+}
+
+// The provider an overridden call is pinned to, if any
+pub fn provider_for_call() -> Option<String> {
+    CALL.try_with(|call| call.provider.clone()).ok().flatten()
+}
+
+// The shared instance with one request's overrides applied to each call
+pub struct OverriddenLLM {
+    inner: Arc<dyn LLMTrait>,
+    model: String,
+    call: CallOverride,
+}
+
+#[async_trait]
+impl LLMTrait for OverriddenLLM {
+    async fn complete(&self, messages: Vec<Message>) -> Result<Response> {
+        CALL.scope(self.call.clone(), self.inner.complete(messages)).await
+    }
+
+    // Providers read the override when the request is sent, which happens
+    // before the stream is returned
+    async fn complete_stream(&self, messages: Vec<Message>, cancel: CancellationToken) -> Result<ChunkStream> {
+        CALL.scope(self.call.clone(), self.inner.complete_stream(messages, cancel)).await
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.inner.is_initialized()
+    }
+
+    fn get_model(&self) -> &str {
+        &self.model
+    }
+
+    fn set_model_config(&mut self, config: ModelConfig) {
+        self.call.config = config;
+    }
+
+    fn get_model_config(&self) -> ModelConfig {
+        self.call.config.clone()
+    }
+}
+
+pub struct ModelOverrides {
+    config: ModelOverrideConfig,
+    default_provider: String,
+    // Providers behind the shared instance, the default one first
+    providers: Vec<String>,
+    default: Arc<dyn LLMTrait>,
+}
+
+impl ModelOverrides {
+    pub fn new(config: ModelOverrideConfig, default_provider: &str, default: Arc<dyn LLMTrait>) -> Self {
+        Self {
+            config,
+            default_provider: default_provider.to_string(),
+            providers: vec![default_provider.to_string()],
+            default,
+        }
+    }
+
+    // The router's fallbacks, so allowed models on them can be reached
+    pub fn with_fallbacks(mut self, fallbacks: &[String]) -> Self {
+        self.providers.extend(fallbacks.iter().cloned());
+        self
+    }
+
+    pub fn config(&self) -> &ModelOverrideConfig {
+        &self.config
+    }
+
+    // The provider for `model`, if clients may use it. The default model is
+    // always allowed.
+    pub fn provider_for(&self, model: &str) -> Option<&str> {
+        if model == self.default.get_model() {
+            return Some(&self.default_provider);
+        }
+        self.config.allowed_models.iter()
+            .find(|allowed| allowed.matches(model))
+            .map(|allowed| allowed.provider.as_str())
+    }
+
+    // Errors name the offending field, for a 400 response
+    pub fn validate(&self, request: &ModelOverride) -> Result<()> {
+        if let Some(model) = &request.model {
+            let Some(provider) = self.provider_for(model) else {
+                return Err(anyhow::anyhow!("model: {} is not allowed", model));
+            };
+            if model != self.default.get_model() {
+                if !self.providers.iter().any(|p| p.eq_ignore_ascii_case(provider)) {
+                    return Err(anyhow::anyhow!("model: {} needs provider {}, which isn't configured", model, provider));
+                }
+                // Only providers that can be pointed at another model by name
+                if !provider.eq_ignore_ascii_case("anthropic") {
+                    return Err(anyhow::anyhow!("model: provider {} can't switch models per request", provider));
+                }
+            }
+        }
+        if let Some(temperature) = request.temperature {
+            let (min, max) = (self.config.min_temperature, self.config.max_temperature);
+            if !temperature.is_finite() || temperature < min || temperature > max {
+                return Err(anyhow::anyhow!("temperature: must be between {} and {}", min, max));
+            }
+        }
+        if let Some(max_tokens) = request.max_tokens {
+            if max_tokens == 0 || max_tokens > self.config.max_tokens {
+                return Err(anyhow::anyhow!("max_tokens: must be between 1 and {}", self.config.max_tokens));
+            }
+        }
+        Ok(())
+    }
+
+    // The server's LLM when nothing is overridden, otherwise the same
+    // instance with the override applied per call
+    pub fn resolve(&self, request: &ModelOverride) -> Result<Arc<dyn LLMTrait>> {
+        if request.is_empty() {
+            return Ok(self.default.clone());
+        }
+        self.validate(request)?;
+
+        let model = request.model.as_deref().filter(|m| *m != self.default.get_model());
+        let mut config = self.default.get_model_config();
+        if let Some(temperature) = request.temperature {
+            config.temperature = temperature;
+        }
+        if let Some(max_tokens) = request.max_tokens {
+            config.max_tokens = max_tokens;
+        }
+        Ok(Arc::new(OverriddenLLM {
+            inner: self.default.clone(),
+            model: model.unwrap_or(self.default.get_model()).to_string(),
+            call: CallOverride {
+                provider: model.and_then(|m| self.provider_for(m)).map(str::to_string),
+                model: model.map(str::to_string),
+                config,
+            },
+        }))
+    }
+}
@@ -0,0 +1,135 @@
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Result;
+
+use crate::core::llm::LLMTrait;
+use crate::core::llm::cache::{CachedLLM, ResponseCache};
+use crate::core::llm::candidates::{BestOfN, CandidateSelector, ConsensusSelector, JudgeSelector, SelectorKind};
+use crate::core::llm::judge::{Judge, JudgeConfig};
+use crate::core::llm::router::LLMRouter;
+use crate::core::llm::tokenizer::ContextGuard;
+use crate::utils::{config::Config, egress::{EgressClient, EgressConfig}, logger::Logger};
+
+// Picks the backend from `config.llm_provider`; "openai" when unset. With
+// fallbacks configured in `llm_router`, returns a router that fails over
+// through them in order. Requests are trimmed to the smallest context
+// window among those models on the way in. With `best_of`, every request
+// is completed n times and one answer kept. With a cache from
+// `build_llm_cache`, identical requests are answered from it; the same
+// instance is what the cache metrics endpoint reports on. Hosted providers
+// send through an EgressClient built from `egress`.
+pub fn create_llm(
+    config: &Config,
+    logger: Logger,
+    egress: &EgressConfig,
+    cache: Option<Arc<ResponseCache>>,
+) -> Result<Arc<dyn LLMTrait>> {
+    let primary = config.llm_provider.as_deref().unwrap_or("openai");
+    let router_config = config.llm_router.clone().unwrap_or_default();
+    // Fault injection for resilience tests, targeted per provider as
+    // `llm.<name>`. Installed process-wide so detectors and pipelines share it.
+    #[cfg(feature = "chaos")]
+    let injector = config.chaos.clone().map(crate::core::chaos::FaultInjector::install);
+    let client = EgressClient::new(egress, Duration::from_secs(config.timeout))?;
+    let build = |name: &str| -> Result<Box<dyn LLMTrait>> {
+        let llm = build_provider(name, config, logger.clone(), &client)?;
+        #[cfg(feature = "chaos")]
+        if let Some(injector) = &injector {
+            return Ok(Box::new(crate::core::chaos::ChaosLLM::new(llm, injector.clone(), name)));
+        }
+        Ok(llm)
+    };
+
+    let mut context = config.llm_context.clone().unwrap_or_default();
+    let mut models = Vec::new();
+    let mut built = |name: &str| -> Result<Box<dyn LLMTrait>> {
+        let llm = build(name)?;
+        // A local model's window is the context it was loaded with, not
+        // the generic default
+        #[cfg(feature = "llm-local")]
+        if name.eq_ignore_ascii_case("local") {
+            if let Some(local) = &config.local_llm {
+                context.context_windows.entry(llm.get_model().to_string())
+                    .or_insert(local.context_size as usize);
+            }
+        }
+        models.push(llm.get_model().to_string());
+        Ok(llm)
+    };
+
+    let llm: Box<dyn LLMTrait> = if router_config.fallbacks.is_empty() {
+        built(primary)?
+    } else {
+        let names: Vec<String> = std::iter::once(primary.to_string())
+            .chain(router_config.fallbacks.iter().cloned())
+            .collect();
+        let mut router = LLMRouter::new(router_config);
+        for name in &names {
+            router = router.with_provider(name, built(name)?);
+        }
+        Box::new(router)
+    };
+
+    let guarded: Box<dyn LLMTrait> = Box::new(ContextGuard::new(llm, context).with_models(models));
+    let guarded: Box<dyn LLMTrait> = match config.best_of.clone().filter(|best_of| best_of.n > 1) {
+        Some(best_of) => {
+            let selector: Arc<dyn CandidateSelector> = match best_of.selector {
+                SelectorKind::Consensus => Arc::new(ConsensusSelector),
+                // Its own instance of the primary, so judge calls aren't
+                // best-of-n themselves
+                SelectorKind::Judge => Arc::new(JudgeSelector::new(Arc::new(
+                    Judge::new(JudgeConfig::default(), Arc::from(build(primary)?)),
+                ))),
+            };
+            Box::new(BestOfN::new(guarded, best_of.n, selector))
+        }
+        None => guarded,
+    };
+    // Outermost, so a hit skips trimming and any summary call too
+    Ok(match cache {
+        Some(cache) => Arc::new(CachedLLM::new(guarded, cache)),
+        None => Arc::from(guarded),
+    })
+}
+
+// Built once at startup from `llm_cache` and shared by every model and the
+// metrics endpoint
+pub fn build_llm_cache(config: &Config) -> Result<Option<Arc<ResponseCache>>> {
+    config.llm_cache.clone()
+        .map(|cache| ResponseCache::new(cache).map(Arc::new))
+        .transpose()
+}
+
+fn build_provider(name: &str, config: &Config, logger: Logger, client: &EgressClient) -> Result<Box<dyn LLMTrait>> {
+    let provider = name.to_ascii_lowercase();
+    match provider.as_str() {
+        #[cfg(feature = "llm-openai")]
+        "openai" => Ok(Box::new(crate::core::llm::OpenAI::new(config, logger))),
+        #[cfg(feature = "llm-anthropic")]
+        "anthropic" => {
+            let key = api_key(config.anthropic_key.as_deref(), "ANTHROPIC_API_KEY").ok_or_else(|| {
+                anyhow::anyhow!("LLM provider \"anthropic\" is selected but neither anthropic_key nor ANTHROPIC_API_KEY is set")
+            })?;
+            Ok(Box::new(crate::core::llm::anthropic::Anthropic::new(config, client.clone()).with_api_key(&key)))
+        }
+        #[cfg(feature = "llm-local")]
+        "local" => {
+            let local = config.local_llm.clone()
+                .ok_or_else(|| anyhow::anyhow!("LLM provider \"local\" is selected but local_llm is not configured"))?;
+            Ok(Box::new(crate::core::llm::local::LocalLLM::new(local)?))
+        }
+        other => Err(anyhow::anyhow!(
+            "Unknown or disabled LLM provider \"{}\" (check the llm-* Cargo features)", other
+        )),
+    }
+}
+
+// A configured key wins, then the provider's usual environment variable.
+// The doctor checks resolve keys the same way, so a config they pass is
+// one the providers can start with.
+pub fn api_key(configured: Option<&str>, env: &str) -> Option<String> {
+    configured.map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
+        .or_else(|| std::env::var(env).ok().map(|key| key.trim().to_string()).filter(|key| !key.is_empty()))
+}
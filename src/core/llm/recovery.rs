@@ -0,0 +1,152 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use serde::{Serialize, Deserialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::core::llm::{
+    LLMTrait,
+    types::{Message, StreamChunk},
+};
+
+type ChunkStream = Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryConfig {
+    pub enabled: bool,
+    pub max_continuations: u32,
+    pub continue_instruction: String,
+    // How much of the continuation is buffered to detect text the model
+    // repeated from the end of the partial output
+    pub overlap_window: usize,
+}
+
+impl Default for RecoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_continuations: 2,
+            continue_instruction: String::from(
+                "Your previous response was cut off. Continue exactly where it \
+                 stopped, without repeating any text and without commentary."
+            ),
+            overlap_window: 200,
+        }
+    }
+}
+
+// Streams a completion, and if the provider stream dies mid-generation,
+// re-prompts with the partial answer so clients still receive one
+// continuous response. Errors surface only once continuations run out.
+// Cancelling `cancel` ends the stream and rules out further continuations.
+pub fn complete_stream_with_recovery(
+    llm: Arc<dyn LLMTrait>,
+    messages: Vec<Message>,
+    config: RecoveryConfig,
+    cancel: CancellationToken,
+) -> impl Stream<Item = Result<StreamChunk>> {
+    async_stream::stream! {
+        let mut partial = String::new();
+        let mut attempts = 0u32;
+        let mut stream: ChunkStream = match llm.complete_stream(messages.clone(), cancel.clone()).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                yield Err(e);
+                return;
+            }
+        };
+        // Continuation text held back until overlap with `partial` is resolved,
+        // along with the latest chunk so its metadata can carry the text
+        let mut pending: Option<String> = None;
+        let mut held: Option<StreamChunk> = None;
+
+        loop {
+            match stream.next().await {
+                Some(Ok(mut chunk)) => {
+                    if let Some(buffer) = pending.as_mut() {
+                        buffer.push_str(&chunk.content);
+                        if buffer.len() < config.overlap_window {
+                            held = Some(chunk);
+                            continue;
+                        }
+                        chunk.content = strip_overlap(&partial, buffer).to_string();
+                        pending = None;
+                        held = None;
+                    }
+
+                    if chunk.content.is_empty() {
+                        continue;
+                    }
+                    partial.push_str(&chunk.content);
+                    yield Ok(chunk);
+                }
+                Some(Err(e)) => {
+                    if let (Some(buffer), Some(mut chunk)) = (pending.take(), held.take()) {
+                        chunk.content = strip_overlap(&partial, &buffer).to_string();
+                        partial.push_str(&chunk.content);
+                        yield Ok(chunk);
+                    }
+
+                    if cancel.is_cancelled() {
+                        return;
+                    }
+                    if !config.enabled || attempts >= config.max_continuations {
+                        yield Err(e);
+                        return;
+                    }
+                    attempts += 1;
+                    log::warn!(
+                        "LLM stream failed after {} chars ({}), continuation attempt {}",
+                        partial.len(), e, attempts
+                    );
+
+                    let mut retry_messages = messages.clone();
+                    if !partial.is_empty() {
+                        retry_messages.push(Message::new("assistant", &partial));
+                        retry_messages.push(Message::new("user", &config.continue_instruction));
+                        pending = Some(String::new());
+                    }
+
+                    stream = match llm.complete_stream(retry_messages, cancel.clone()).await {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            yield Err(e);
+                            return;
+                        }
+                    };
+                }
+                None => {
+                    if let (Some(buffer), Some(mut chunk)) = (pending.take(), held.take()) {
+                        chunk.content = strip_overlap(&partial, &buffer).to_string();
+                        if !chunk.content.is_empty() {
+                            yield Ok(chunk);
+                        }
+                    }
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// Models asked to continue often restate the last few words; drop the
+// longest prefix of `continuation` that is also a suffix of `partial`
+pub fn strip_overlap<'a>(partial: &str, continuation: &'a str) -> &'a str {
+    let max = partial.len().min(continuation.len());
+
+    for len in (1..=max).rev() {
+        if !continuation.is_char_boundary(len) || !partial.is_char_boundary(partial.len() - len) {
+            continue;
+        }
+        if partial.ends_with(&continuation[..len]) {
+            // Ignore trivially short matches like a single shared space
+            if len < 4 {
+                break;
+            }
+            return &continuation[len..];
+        }
+    }
+
+    continuation
+}
@@ -0,0 +1,253 @@
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use serde::{Serialize, Deserialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::core::llm::{
+    LLMTrait,
+    annotated::StreamReport,
+    overrides::provider_for_call,
+    types::{Message, ModelConfig, Response, StreamChunk},
+};
+
+type ChunkStream = Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouterConfig {
+    // Providers tried after `llm_provider`, in order
+    pub fallbacks: Vec<String>,
+    // A provider that failed over is skipped for this long, unless every
+    // provider is cooling down
+    pub cooldown_secs: u64,
+}
+
+impl Default for RouterConfig {
+    fn default() -> Self {
+        Self {
+            fallbacks: Vec::new(),
+            cooldown_secs: 30,
+        }
+    }
+}
+
+struct Route {
+    name: String,
+    llm: Box<dyn LLMTrait>,
+    cooling_until: Mutex<Option<Instant>>,
+}
+
+// Wraps providers in priority order. Requests go to the first healthy one
+// and move down the list on transient failures (5xx, timeouts, rate
+// limits); anything else, like a rejected prompt, is returned as is since
+// another provider would fail the same way.
+pub struct LLMRouter {
+    config: RouterConfig,
+    routes: Vec<Route>,
+}
+
+impl LLMRouter {
+    pub fn new(config: RouterConfig) -> Self {
+        Self { config, routes: Vec::new() }
+    }
+
+    pub fn with_provider(mut self, name: &str, llm: Box<dyn LLMTrait>) -> Self {
+        self.routes.push(Route {
+            name: name.to_string(),
+            llm,
+            cooling_until: Mutex::new(None),
+        });
+        self
+    }
+
+    pub fn providers(&self) -> Vec<String> {
+        self.routes.iter().map(|r| r.name.clone()).collect()
+    }
+
+    // Healthy providers in priority order, then the cooling ones as a last
+    // resort so an outage of everything still gets a try. A call overridden
+    // to another model only goes to the provider that serves it.
+    fn order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let pinned = provider_for_call();
+        let (healthy, cooling): (Vec<usize>, Vec<usize>) = (0..self.routes.len())
+            .filter(|&i| pinned.as_deref().is_none_or(|p| self.routes[i].name.eq_ignore_ascii_case(p)))
+            .partition(|&i| {
+                !matches!(*self.routes[i].cooling_until.lock().unwrap(), Some(until) if until > now)
+            });
+        healthy.into_iter().chain(cooling).collect()
+    }
+
+    fn failed(&self, index: usize, error: &anyhow::Error) {
+        let route = &self.routes[index];
+        log::warn!("LLM provider {} failed, trying the next one: {:#}", route.name, error);
+        *route.cooling_until.lock().unwrap() = Some(Instant::now() + Duration::from_secs(self.config.cooldown_secs));
+    }
+
+    fn succeeded(&self, index: usize) {
+        *self.routes[index].cooling_until.lock().unwrap() = None;
+    }
+
+    fn exhausted(last: Option<anyhow::Error>) -> anyhow::Error {
+        match last {
+            Some(e) => e.context("All LLM providers failed"),
+            None => anyhow::anyhow!("No LLM providers configured"),
+        }
+    }
+}
+
+// A provider's non-success HTTP response. Providers return this rather
+// than a formatted message so failover is decided on the status alone;
+// a 400 that mentions a timeout is still a 400.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub provider: String,
+    pub status: u16,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(provider: &str, status: u16, message: &str) -> Self {
+        Self { provider: provider.to_string(), status, message: message.to_string() }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        retryable_status(self.status)
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} API returned {}: {}", self.provider, self.status, self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+fn retryable_status(status: u16) -> bool {
+    status == 429 || (500..600).contains(&status)
+}
+
+// Whether another provider might succeed where this one failed
+pub fn is_retryable(error: &anyhow::Error) -> bool {
+    for cause in error.chain() {
+        if let Some(e) = cause.downcast_ref::<ApiError>() {
+            return e.is_retryable();
+        }
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            if e.is_timeout() || e.is_connect() {
+                return true;
+            }
+            if let Some(status) = e.status() {
+                return retryable_status(status.as_u16());
+            }
+        }
+        if cause.is::<tokio::time::error::Elapsed>() {
+            return true;
+        }
+    }
+
+    // Untyped errors, e.g. from providers that format their own messages.
+    // A status anywhere in the message decides; the phrases below only
+    // count when there is none.
+    let text = format!("{:#}", error).to_ascii_lowercase();
+    if let Some(status) = mentioned_status(&text) {
+        return retryable_status(status);
+    }
+    ["timed out", "rate limit", "too many requests", "overloaded"]
+        .iter()
+        .any(|marker| text.contains(marker))
+}
+
+// "returned 503", "status 503", "status: 503" or "status code 503"; the
+// number has to be a whole three-digit word, so "returned 5000 tokens"
+// isn't a status
+fn mentioned_status(text: &str) -> Option<u16> {
+    let words: Vec<&str> = text.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    words.windows(2).find_map(|pair| {
+        if !matches!(pair[0], "returned" | "status" | "code") || pair[1].len() != 3 {
+            return None;
+        }
+        pair[1].parse::<u16>().ok().filter(|status| (100..600).contains(status))
+    })
+}
+
+#[async_trait]
+impl LLMTrait for LLMRouter {
+    async fn complete(&self, messages: Vec<Message>) -> Result<Response> {
+        let mut last = None;
+        for index in self.order() {
+            match self.routes[index].llm.complete(messages.clone()).await {
+                Ok(response) => {
+                    self.succeeded(index);
+                    return Ok(response);
+                }
+                Err(e) if is_retryable(&e) => {
+                    self.failed(index, &e);
+                    last = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(Self::exhausted(last))
+    }
+
+    // Fails over only until the first chunk arrives; after that the client
+    // has partial output and recovery is up to the caller
+    async fn complete_stream(&self, messages: Vec<Message>, cancel: CancellationToken) -> Result<ChunkStream> {
+        let mut last = None;
+        for index in self.order() {
+            // Nobody is waiting for a replacement once cancelled
+            if cancel.is_cancelled() {
+                return Err(anyhow::anyhow!("Completion cancelled"));
+            }
+            // Providers that know the exact model overwrite this
+            StreamReport::current().model(self.routes[index].llm.get_model());
+            let mut stream = match self.routes[index].llm.complete_stream(messages.clone(), cancel.clone()).await {
+                Ok(stream) => stream,
+                Err(e) if is_retryable(&e) => {
+                    self.failed(index, &e);
+                    last = Some(e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            match stream.next().await {
+                Some(Err(e)) if is_retryable(&e) => {
+                    self.failed(index, &e);
+                    last = Some(e);
+                }
+                first => {
+                    self.succeeded(index);
+                    return Ok(Box::pin(futures::stream::iter(first).chain(stream)));
+                }
+            }
+        }
+        Err(Self::exhausted(last))
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.routes.iter().any(|r| r.llm.is_initialized())
+    }
+
+    fn get_model(&self) -> &str {
+        self.routes.first().map(|r| r.llm.get_model()).unwrap_or("")
+    }
+
+    fn set_model_config(&mut self, config: ModelConfig) {
+        for route in self.routes.iter_mut() {
+            route.llm.set_model_config(config.clone());
+        }
+    }
+
+    fn get_model_config(&self) -> ModelConfig {
+        self.routes.first().map(|r| r.llm.get_model_config()).unwrap_or_default()
+    }
+}
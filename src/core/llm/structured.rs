@@ -0,0 +1,20 @@
+use anyhow::{Result, Context};
+use serde::de::DeserializeOwned;
+
+// Models asked for JSON often wrap it in prose or code fences. Every place
+// that parses a structured reply goes through here, so they all tolerate
+// the same wrappers.
+
+// The outermost {...} in the reply, if there is one
+pub fn extract_json(text: &str) -> Option<&str> {
+    match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if end > start => Some(&text[start..=end]),
+        _ => None,
+    }
+}
+
+// `what` names the expected output in errors, e.g. "title output"
+pub fn parse_reply<T: DeserializeOwned>(text: &str, what: &str) -> Result<T> {
+    let json = extract_json(text).ok_or_else(|| anyhow::anyhow!("No JSON object in {}", what))?;
+    serde_json::from_str(json).with_context(|| format!("Failed to parse {}", what))
+}
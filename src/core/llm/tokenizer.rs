@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::OnceLock;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use futures::Stream;
+use serde::{Serialize, Deserialize};
+use tiktoken_rs::CoreBPE;
+use tokio_util::sync::CancellationToken;
+
+use crate::core::llm::{
+    LLMTrait,
+    overrides::for_call,
+    types::{Message, ModelConfig, Response, StreamChunk},
+};
+
+type ChunkStream = Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>;
+
+// Chat formatting overhead per OpenAI's accounting: every message is
+// wrapped in a few control tokens, and the reply is primed with three more
+const TOKENS_PER_MESSAGE: usize = 3;
+const TOKENS_PER_REPLY: usize = 3;
+
+fn cl100k() -> &'static CoreBPE {
+    static BPE: OnceLock<CoreBPE> = OnceLock::new();
+    BPE.get_or_init(|| tiktoken_rs::cl100k_base().expect("cl100k_base vocabulary is embedded"))
+}
+
+fn o200k() -> &'static CoreBPE {
+    static BPE: OnceLock<CoreBPE> = OnceLock::new();
+    BPE.get_or_init(|| tiktoken_rs::o200k_base().expect("o200k_base vocabulary is embedded"))
+}
+
+// Counts with the model's own encoding where it is known. Other models
+// (Claude, local GGUF) are counted with cl100k, which is close but not
+// exact; the trimmer's safety margin covers the difference.
+#[derive(Clone, Copy)]
+pub struct Tokenizer {
+    bpe: &'static CoreBPE,
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Self { bpe: cl100k() }
+    }
+}
+
+impl Tokenizer {
+    pub fn for_model(model: &str) -> Self {
+        let model = model.to_ascii_lowercase();
+        let o200k_models = ["gpt-4o", "gpt-4.1", "gpt-5", "o1", "o3", "o4"];
+        if o200k_models.iter().any(|prefix| model.starts_with(prefix)) {
+            Self { bpe: o200k() }
+        } else {
+            Self::default()
+        }
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        self.bpe.encode_with_special_tokens(text).len()
+    }
+
+    pub fn count_message(&self, message: &Message) -> usize {
+        TOKENS_PER_MESSAGE + self.count(&message.role) + self.count(&message.content)
+    }
+
+    pub fn count_tokens(&self, messages: &[Message]) -> usize {
+        messages.iter().map(|m| self.count_message(m)).sum::<usize>() + TOKENS_PER_REPLY
+    }
+
+    // Cuts text down to at most `max_tokens`, keeping the start
+    pub fn truncate(&self, text: &str, max_tokens: usize) -> String {
+        let tokens = self.bpe.encode_with_special_tokens(text);
+        if tokens.len() <= max_tokens {
+            return text.to_string();
+        }
+        self.bpe.decode(tokens[..max_tokens].to_vec()).unwrap_or_default()
+    }
+}
+
+pub fn count_tokens(messages: &[Message]) -> usize {
+    Tokenizer::default().count_tokens(messages)
+}
+
+// Known context windows by model name prefix; more specific prefixes
+// come first
+pub fn context_window(model: &str) -> Option<usize> {
+    let model = model.to_ascii_lowercase();
+    let windows: [(&str, usize); 10] = [
+        ("gpt-4.1", 1_047_576),
+        ("gpt-4o", 128_000),
+        ("gpt-4-turbo", 128_000),
+        ("gpt-4-32k", 32_768),
+        ("gpt-4", 8_192),
+        ("gpt-3.5-turbo", 16_385),
+        ("gpt-5", 400_000),
+        ("o1", 200_000),
+        ("o3", 200_000),
+        ("claude", 200_000),
+    ];
+    windows.iter().find(|(prefix, _)| model.starts_with(prefix)).map(|(_, window)| *window)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextConfig {
+    pub enabled: bool,
+    // Overrides and additions to the built-in table, by model name prefix
+    pub context_windows: HashMap<String, usize>,
+    // For models found in neither
+    pub default_context_window: usize,
+    // Share of the window left unused to absorb counting differences
+    pub safety_margin: f32,
+    // Replace trimmed history with a model-written summary instead of
+    // dropping it outright
+    pub summarize: bool,
+    pub summary_max_tokens: usize,
+    pub summary_prompt: String,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            context_windows: HashMap::new(),
+            default_context_window: 8_192,
+            safety_margin: 0.05,
+            summarize: false,
+            summary_max_tokens: 300,
+            summary_prompt: String::from(
+                "Summarize the earlier part of this conversation in a few sentences. \
+                 Keep names, numbers, decisions and open questions."
+            ),
+        }
+    }
+}
+
+impl ContextConfig {
+    pub fn window_for(&self, model: &str) -> usize {
+        let lower = model.to_ascii_lowercase();
+        self.context_windows.iter()
+            .filter(|(prefix, _)| lower.starts_with(&prefix.to_ascii_lowercase()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, window)| *window)
+            .or_else(|| context_window(model))
+            .unwrap_or(self.default_context_window)
+    }
+
+    // Prompt tokens available once the completion and margin are set aside
+    pub fn prompt_budget(&self, model: &str, max_tokens: usize) -> usize {
+        let window = self.window_for(model);
+        let margin = (window as f32 * self.safety_margin) as usize;
+        window.saturating_sub(max_tokens).saturating_sub(margin)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Trimmed {
+    pub messages: Vec<Message>,
+    // Oldest first, in their original order
+    pub dropped: Vec<Message>,
+}
+
+// Drops the oldest conversation turns until the messages fit the budget.
+// System messages and the latest message are always kept; if those alone
+// don't fit there is nothing sensible to cut and it's an error.
+pub fn trim_to_budget(tokenizer: &Tokenizer, messages: &[Message], budget: usize) -> Result<Trimmed> {
+    let total = tokenizer.count_tokens(messages);
+    if total <= budget {
+        return Ok(Trimmed { messages: messages.to_vec(), dropped: Vec::new() });
+    }
+
+    let last = messages.len().checked_sub(1).context("No messages to complete")?;
+    let pinned = |i: usize| i == last || messages[i].role == "system";
+    let mut used = TOKENS_PER_REPLY + messages.iter().enumerate()
+        .filter(|(i, _)| pinned(*i))
+        .map(|(_, m)| tokenizer.count_message(m))
+        .sum::<usize>();
+    if used > budget {
+        return Err(anyhow::anyhow!(
+            "Prompt needs {} tokens even after dropping history, but only {} fit the context window",
+            used, budget
+        ));
+    }
+
+    // Newest turns first, until the next one no longer fits
+    let mut keep = vec![false; messages.len()];
+    let mut full = false;
+    for i in (0..messages.len()).rev() {
+        if pinned(i) {
+            keep[i] = true;
+            continue;
+        }
+        let cost = tokenizer.count_message(&messages[i]);
+        if !full && used + cost <= budget {
+            used += cost;
+            keep[i] = true;
+        } else {
+            full = true;
+        }
+    }
+
+    let (kept, dropped): (Vec<_>, Vec<_>) = messages.iter().cloned().zip(keep).partition(|(_, k)| *k);
+    Ok(Trimmed {
+        messages: kept.into_iter().map(|(m, _)| m).collect(),
+        dropped: dropped.into_iter().map(|(m, _)| m).collect(),
+    })
+}
+
+// Wraps a provider and fits every request into the model's context window
+// before it is sent, so oversized histories are trimmed here instead of
+// failing at the API
+pub struct ContextGuard {
+    inner: Box<dyn LLMTrait>,
+    config: ContextConfig,
+    // Every model a router behind `inner` may send the request to
+    models: Vec<String>,
+}
+
+impl ContextGuard {
+    pub fn new(inner: Box<dyn LLMTrait>, config: ContextConfig) -> Self {
+        Self { inner, config, models: Vec::new() }
+    }
+
+    // Requests are fitted to the smallest window among these, so a
+    // failover to a smaller model doesn't overflow it
+    pub fn with_models(mut self, models: Vec<String>) -> Self {
+        self.models = models;
+        self
+    }
+
+    async fn fit(&self, messages: Vec<Message>) -> Result<Vec<Message>> {
+        if !self.config.enabled {
+            return Ok(messages);
+        }
+        let (model, config) = for_call(self.inner.get_model(), &self.inner.get_model_config());
+        let max_tokens = config.max_tokens as usize;
+        // A call overridden to one model is only sent to that model
+        let model = if model != self.inner.get_model() {
+            model.as_str()
+        } else {
+            self.models.iter()
+                .map(String::as_str)
+                .chain(std::iter::once(model.as_str()))
+                .min_by_key(|m| self.config.prompt_budget(m, max_tokens))
+                .unwrap_or(model.as_str())
+        };
+        let tokenizer = Tokenizer::for_model(model);
+        let budget = self.config.prompt_budget(model, max_tokens);
+        if tokenizer.count_tokens(&messages) <= budget {
+            return Ok(messages);
+        }
+
+        // Room for the summary is set aside before deciding what to drop
+        let reserve = if self.config.summarize {
+            self.config.summary_max_tokens + TOKENS_PER_MESSAGE + 8
+        } else {
+            0
+        };
+        let trimmed = trim_to_budget(&tokenizer, &messages, budget.saturating_sub(reserve))?;
+        log::info!("Trimmed {} old messages to fit the {} context window", trimmed.dropped.len(), model);
+        if !self.config.summarize || trimmed.dropped.is_empty() {
+            return Ok(trimmed.messages);
+        }
+
+        match self.summarize(&tokenizer, &trimmed.dropped, budget).await {
+            Ok(summary) => {
+                let mut messages = trimmed.messages;
+                let at = messages.iter().take_while(|m| m.role == "system").count();
+                messages.insert(at, Message::new("system", &format!("Summary of the earlier conversation: {}", summary)));
+                Ok(messages)
+            }
+            Err(e) => {
+                log::warn!("Failed to summarize trimmed history, dropping it: {}", e);
+                Ok(trimmed.messages)
+            }
+        }
+    }
+
+    async fn summarize(&self, tokenizer: &Tokenizer, dropped: &[Message], budget: usize) -> Result<String> {
+        let transcript: String = dropped.iter()
+            .map(|m| format!("{}: {}\n", m.role, m.content))
+            .collect();
+        // The summary request has to fit the window too, so a very long
+        // history is only summarized as far as it fits
+        let room = budget.saturating_sub(tokenizer.count(&self.config.summary_prompt) + 16);
+        let transcript = tokenizer.truncate(&transcript, room);
+
+        let response = self.inner.complete(vec![
+            Message::new("system", &self.config.summary_prompt),
+            Message::new("user", &transcript),
+        ]).await?;
+        Ok(tokenizer.truncate(response.content.trim(), self.config.summary_max_tokens))
+    }
+}
+
+#[async_trait]
+impl LLMTrait for ContextGuard {
+    async fn complete(&self, messages: Vec<Message>) -> Result<Response> {
+        let messages = self.fit(messages).await?;
+        self.inner.complete(messages).await
+    }
+
+    async fn complete_stream(&self, messages: Vec<Message>, cancel: CancellationToken) -> Result<ChunkStream> {
+        let messages = self.fit(messages).await?;
+        self.inner.complete_stream(messages, cancel).await
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.inner.is_initialized()
+    }
+
+    fn get_model(&self) -> &str {
+        self.inner.get_model()
+    }
+
+    fn set_model_config(&mut self, config: ModelConfig) {
+        self.inner.set_model_config(config);
+    }
+
+    fn get_model_config(&self) -> ModelConfig {
+        self.inner.get_model_config()
+    }
+}
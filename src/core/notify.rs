@@ -0,0 +1,423 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message as EmailMessage, Tokio1Executor,
+    message::Mailbox,
+    transport::smtp::authentication::Credentials,
+};
+
+use crate::utils::egress::{EgressClient, EgressPolicy};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub title: String,
+    pub message: String,
+    pub severity: Severity,
+    pub source: String,
+    pub fields: HashMap<String, String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Notification {
+    pub fn new(title: &str, message: &str, severity: Severity, source: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            message: message.to_string(),
+            severity,
+            source: source.to_string(),
+            fields: HashMap::new(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    pub fn with_field(mut self, key: &str, value: &str) -> Self {
+        self.fields.insert(key.to_string(), value.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelConfig {
+    pub name: String,
+    pub channel: ChannelType,
+    pub template: Option<String>,
+    pub min_severity: Severity,
+    pub rate_limit: Option<NotificationRateLimit>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ChannelType {
+    Slack {
+        webhook_url: String,
+    },
+    Email {
+        smtp_host: String,
+        smtp_port: u16,
+        username: String,
+        password: String,
+        from: String,
+        to: Vec<String>,
+    },
+    Telegram {
+        bot_token: String,
+        chat_id: String,
+    },
+}
+
+// Channel configs end up in logs and error messages, so the SMTP password
+// and bot token are left out
+impl std::fmt::Debug for ChannelType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChannelType::Slack { webhook_url } => f.debug_struct("Slack")
+                .field("webhook_url", webhook_url)
+                .finish(),
+            ChannelType::Email { smtp_host, smtp_port, username, from, to, .. } => f.debug_struct("Email")
+                .field("smtp_host", smtp_host)
+                .field("smtp_port", smtp_port)
+                .field("username", username)
+                .field("password", &"***")
+                .field("from", from)
+                .field("to", to)
+                .finish(),
+            ChannelType::Telegram { chat_id, .. } => f.debug_struct("Telegram")
+                .field("bot_token", &"***")
+                .field("chat_id", chat_id)
+                .finish(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationRateLimit {
+    pub max_per_window: usize,
+    pub window_secs: u64,
+}
+
+pub const DEFAULT_TEMPLATE: &str = "[{{severity}}] {{title}}\n{{message}}";
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    fn name(&self) -> String;
+    async fn send(&self, subject: &str, body: &str) -> Result<()>;
+}
+
+// Renders `{{title}}`, `{{message}}`, `{{severity}}`, `{{source}}`,
+// `{{timestamp}}` and `{{fields.<key>}}` placeholders
+pub fn render_template(template: &str, notification: &Notification) -> String {
+    let mut rendered = template
+        .replace("{{title}}", &notification.title)
+        .replace("{{message}}", &notification.message)
+        .replace("{{severity}}", &notification.severity.to_string())
+        .replace("{{source}}", &notification.source)
+        .replace("{{timestamp}}", &notification.timestamp.to_rfc3339());
+
+    for (key, value) in &notification.fields {
+        rendered = rendered.replace(&format!("{{{{fields.{}}}}}", key), value);
+    }
+
+    rendered
+}
+
+pub struct SlackNotifier {
+    name: String,
+    webhook_url: String,
+    client: EgressClient,
+}
+
+impl SlackNotifier {
+    pub fn new(name: &str, webhook_url: &str, client: EgressClient) -> Self {
+        Self {
+            name: name.to_string(),
+            webhook_url: webhook_url.to_string(),
+            client,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for SlackNotifier {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn send(&self, _subject: &str, body: &str) -> Result<()> {
+        self.client.post(&self.webhook_url)?
+            .json(&serde_json::json!({ "text": body }))
+            .send()
+            .await
+            .context("Failed to post Slack notification")?
+            .error_for_status()
+            .context("Slack webhook rejected notification")?;
+        Ok(())
+    }
+}
+
+pub struct EmailNotifier {
+    name: String,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl EmailNotifier {
+    pub fn new(
+        name: &str,
+        smtp_host: &str,
+        smtp_port: u16,
+        username: &str,
+        password: &str,
+        from: &str,
+        to: Vec<String>,
+        policy: &EgressPolicy,
+    ) -> Result<Self> {
+        // SMTP doesn't go through the HTTP proxy, but the allow-list still applies
+        policy.check_host(smtp_host)?;
+
+        // Addresses are checked here so a typo fails at startup, not on the
+        // first alert
+        let from: Mailbox = from.parse()
+            .with_context(|| format!("Channel {} has an invalid sender address: {}", name, from))?;
+        if to.is_empty() {
+            return Err(anyhow::anyhow!("Channel {} has no recipients", name));
+        }
+        let to = to.iter()
+            .map(|recipient| recipient.parse::<Mailbox>()
+                .with_context(|| format!("Channel {} has an invalid recipient address: {}", name, recipient)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::relay(smtp_host)
+            .context("Failed to configure SMTP relay")?
+            .port(smtp_port)
+            .credentials(Credentials::new(username.to_string(), password.to_string()))
+            .build();
+
+        Ok(Self {
+            name: name.to_string(),
+            from,
+            to,
+            transport,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    // Every recipient is tried; one that's refused doesn't cost the rest
+    // their copy
+    async fn send(&self, subject: &str, body: &str) -> Result<()> {
+        let mut failed = Vec::new();
+        for recipient in &self.to {
+            let sent = async {
+                let email = EmailMessage::builder()
+                    .from(self.from.clone())
+                    .to(recipient.clone())
+                    .subject(subject)
+                    .body(body.to_string())?;
+                self.transport.send(email).await?;
+                Ok::<_, anyhow::Error>(())
+            }.await;
+            if let Err(e) = sent {
+                log::warn!("Failed to send email to {}: {}", recipient, e);
+                failed.push(recipient.to_string());
+            }
+        }
+        if !failed.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Failed to send email to {} of {} recipients: {}",
+                failed.len(), self.to.len(), failed.join(", ")
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub struct TelegramNotifier {
+    name: String,
+    bot_token: String,
+    chat_id: String,
+    client: EgressClient,
+}
+
+impl TelegramNotifier {
+    pub fn new(name: &str, bot_token: &str, chat_id: &str, client: EgressClient) -> Self {
+        Self {
+            name: name.to_string(),
+            bot_token: bot_token.to_string(),
+            chat_id: chat_id.to_string(),
+            client,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for TelegramNotifier {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    // The bot token is part of the URL, and both the URL checks and
+    // reqwest's errors quote the URL, so none of them are passed on as-is:
+    // they end up in logs
+    async fn send(&self, _subject: &str, body: &str) -> Result<()> {
+        let url = format!("https://api.telegram.org/bot{}/sendMessage", self.bot_token);
+        self.client.post(&url)
+            .map_err(|_| anyhow::anyhow!("Telegram bot token does not form a valid URL"))?
+            .json(&serde_json::json!({ "chat_id": self.chat_id, "text": body }))
+            .send()
+            .await
+            .map_err(|e| e.without_url())
+            .context("Failed to post Telegram notification")?
+            .error_for_status()
+            .map_err(|e| e.without_url())
+            .context("Telegram API rejected notification")?;
+        Ok(())
+    }
+}
+
+pub fn create_notifier(config: &ChannelConfig, client: EgressClient) -> Result<Arc<dyn Notifier>> {
+    match &config.channel {
+        ChannelType::Slack { webhook_url } => {
+            Ok(Arc::new(SlackNotifier::new(&config.name, webhook_url, client)))
+        }
+        ChannelType::Email { smtp_host, smtp_port, username, password, from, to } => {
+            Ok(Arc::new(EmailNotifier::new(
+                &config.name, smtp_host, *smtp_port, username, password, from, to.clone(),
+                client.policy(),
+            )?))
+        }
+        ChannelType::Telegram { bot_token, chat_id } => {
+            Ok(Arc::new(TelegramNotifier::new(&config.name, bot_token, chat_id, client)))
+        }
+    }
+}
+
+struct Channel {
+    notifier: Arc<dyn Notifier>,
+    template: String,
+    min_severity: Severity,
+    rate_limit: Option<NotificationRateLimit>,
+    sent: Mutex<VecDeque<Instant>>,
+    suppressed: Mutex<u64>,
+}
+
+impl Channel {
+    async fn allow(&self) -> bool {
+        let Some(limit) = &self.rate_limit else {
+            return true;
+        };
+
+        let window = Duration::from_secs(limit.window_secs);
+        let now = Instant::now();
+        let mut sent = self.sent.lock().await;
+
+        while sent.front().is_some_and(|t| now.duration_since(*t) > window) {
+            sent.pop_front();
+        }
+
+        if sent.len() >= limit.max_per_window {
+            return false;
+        }
+
+        sent.push_back(now);
+        true
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelStats {
+    pub name: String,
+    pub suppressed: u64,
+}
+
+pub struct NotificationDispatcher {
+    channels: Vec<Channel>,
+}
+
+impl NotificationDispatcher {
+    pub fn new(configs: &[ChannelConfig], client: EgressClient) -> Result<Self> {
+        let mut dispatcher = Self { channels: Vec::new() };
+        for config in configs {
+            let notifier = create_notifier(config, client.clone())?;
+            dispatcher.add_channel(notifier, config);
+        }
+
+        Ok(dispatcher)
+    }
+
+    pub fn add_channel(&mut self, notifier: Arc<dyn Notifier>, config: &ChannelConfig) {
+        self.channels.push(Channel {
+            notifier,
+            template: config.template.clone().unwrap_or_else(|| DEFAULT_TEMPLATE.to_string()),
+            min_severity: config.min_severity,
+            rate_limit: config.rate_limit.clone(),
+            sent: Mutex::new(VecDeque::new()),
+            suppressed: Mutex::new(0),
+        });
+    }
+
+    pub async fn dispatch(&self, notification: &Notification) {
+        let subject = format!("[{}] {}", notification.severity, notification.title);
+
+        for channel in &self.channels {
+            if notification.severity < channel.min_severity {
+                continue;
+            }
+
+            if !channel.allow().await {
+                *channel.suppressed.lock().await += 1;
+                log::warn!(
+                    "Notification rate limit reached for channel {}, dropping '{}'",
+                    channel.notifier.name(),
+                    notification.title
+                );
+                continue;
+            }
+
+            let body = render_template(&channel.template, notification);
+            if let Err(e) = channel.notifier.send(&subject, &body).await {
+                log::error!("Notification channel {} error: {}", channel.notifier.name(), e);
+            }
+        }
+    }
+
+    pub async fn stats(&self) -> Vec<ChannelStats> {
+        let mut stats = Vec::with_capacity(self.channels.len());
+        for channel in &self.channels {
+            stats.push(ChannelStats {
+                name: channel.notifier.name(),
+                suppressed: *channel.suppressed.lock().await,
+            });
+        }
+        stats
+    }
+}
@@ -0,0 +1,458 @@
+#![cfg(feature = "vision")]
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use anyhow::{Result, Context};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::core::pipeline::PipelineData;
+use crate::core::sinks::{ResultSink, SinkRecord};
+use crate::utils::sqlite;
+
+// Transactional outbox between the pipeline and its sinks. A result is
+// written to a local SQLite table once per sink before any sink sees it,
+// and a row only leaves the pending state when its sink acknowledges the
+// write (Kafka broker ack, 2xx from the webhook, database commit). Rows
+// that fail are retried with backoff, across restarts, until max_attempts.
+// A row is claimed (status 'sending') before it goes out, so the retry
+// loop and the first attempt from submit() never send it twice at once.
+//
+// Delivery is at least once; the delivery id on every record makes it
+// effectively once. The outbox won't take the same (sink, delivery id)
+// twice while it remembers it, and sinks pass the id on so the far side
+// can drop repeats from retries that succeeded but weren't acknowledged.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutboxConfig {
+    pub path: String,
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub poll_interval_ms: u64,
+    pub batch_size: usize,
+    // Delivered rows are kept this long to catch duplicates
+    pub dedup_retention_secs: i64,
+    // Rows out of attempts are kept this long for retry_failed()
+    pub failed_retention_secs: i64,
+    pub busy_timeout_ms: u64,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            path: "data/outbox.db".to_string(),
+            max_attempts: 20,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 300_000,
+            poll_interval_ms: 1000,
+            batch_size: 100,
+            dedup_retention_secs: 86_400,
+            failed_retention_secs: 7 * 86_400,
+            busy_timeout_ms: 5000,
+        }
+    }
+}
+
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE outbox (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        sink TEXT NOT NULL,
+        delivery_id TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending',
+        attempts INTEGER NOT NULL DEFAULT 0,
+        next_attempt_at INTEGER NOT NULL,
+        last_error TEXT,
+        created_at INTEGER NOT NULL,
+        delivered_at INTEGER,
+        UNIQUE (sink, delivery_id)
+    );
+    CREATE INDEX outbox_due ON outbox (status, next_attempt_at);",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutboxStatus {
+    Pending,
+    Delivered,
+    // Out of attempts; left for inspection and retry_failed()
+    Failed,
+}
+
+impl OutboxStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            OutboxStatus::Pending => "pending",
+            OutboxStatus::Delivered => "delivered",
+            OutboxStatus::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub sink: String,
+    pub delivery_id: String,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OutboxStats {
+    pub pending: u64,
+    pub delivered: u64,
+    pub failed: u64,
+    pub oldest_pending_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SubmitOutcome {
+    pub enqueued: usize,
+    // Sinks that already had this delivery id
+    pub duplicates: usize,
+    pub delivered: usize,
+}
+
+struct Row {
+    id: i64,
+    sink: String,
+    delivery_id: String,
+    payload: String,
+    attempts: u32,
+}
+
+fn millis(at: DateTime<Utc>) -> i64 {
+    at.timestamp_millis()
+}
+
+fn from_millis(ms: i64) -> DateTime<Utc> {
+    Utc.timestamp_millis_opt(ms).single().unwrap_or_default()
+}
+
+// Where a delivery attempt left a row
+struct Outcome {
+    id: i64,
+    status: OutboxStatus,
+    attempts: u32,
+    next_attempt_at: i64,
+    error: Option<String>,
+}
+
+fn read_row(row: &rusqlite::Row) -> rusqlite::Result<Row> {
+    Ok(Row {
+        id: row.get(0)?,
+        sink: row.get(1)?,
+        delivery_id: row.get(2)?,
+        payload: row.get(3)?,
+        attempts: row.get(4)?,
+    })
+}
+
+pub struct SinkOutbox {
+    config: OutboxConfig,
+    conn: Arc<Mutex<Connection>>,
+    sinks: RwLock<HashMap<String, Arc<dyn ResultSink>>>,
+}
+
+impl SinkOutbox {
+    pub fn open(config: OutboxConfig) -> Result<Self> {
+        let conn = sqlite::open(&config.path, Duration::from_millis(config.busy_timeout_ms))?;
+        Self::init(conn, config)
+    }
+
+    pub fn open_in_memory(config: OutboxConfig) -> Result<Self> {
+        Self::init(sqlite::open_in_memory()?, config)
+    }
+
+    fn init(mut conn: Connection, config: OutboxConfig) -> Result<Self> {
+        // Ok from submit() promises the rows survive a power cut
+        conn.execute_batch("PRAGMA synchronous = FULL")?;
+        sqlite::migrate(&mut conn, "Outbox", MIGRATIONS)?;
+        // Claimed by a process that is gone now
+        let released = conn.execute("UPDATE outbox SET status = 'pending' WHERE status = 'sending'", [])?;
+        if released > 0 {
+            log::info!("Outbox released {} row(s) left mid-delivery", released);
+        }
+        Ok(Self {
+            config,
+            conn: Arc::new(Mutex::new(conn)),
+            sinks: RwLock::new(HashMap::new()),
+        })
+    }
+
+    // SQLite calls block, so they run on the blocking thread pool rather
+    // than the async executor
+    async fn with_conn<T, F>(&self, work: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || work(&mut conn.lock().unwrap()))
+            .await
+            .context("Outbox task panicked")?
+    }
+
+    // Rows are matched to sinks by name, so two sinks with the same name
+    // would share rows
+    pub async fn register(&self, sink: Arc<dyn ResultSink>) {
+        let name = sink.name();
+        if self.sinks.write().await.insert(name.clone(), sink).is_some() {
+            log::warn!("Outbox sink {} registered twice; the last one wins", name);
+        }
+    }
+
+    // Persists the result for every registered sink, then tries each
+    // delivery once. Whatever fails stays queued for the retry loop, so an
+    // Ok here means the result is safe, not that it was delivered.
+    pub async fn submit(&self, data: &PipelineData) -> Result<SubmitOutcome> {
+        let record = SinkRecord::from(data);
+        let payload = serde_json::to_string(&record)?;
+        let sinks: Vec<String> = self.sinks.read().await.keys().cloned().collect();
+        let now = millis(Utc::now());
+
+        let mut outcome = SubmitOutcome::default();
+        let delivery_id = record.delivery_id.clone();
+        let (rows, duplicates) = self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            let mut rows = Vec::with_capacity(sinks.len());
+            let mut duplicates = 0;
+            for sink in sinks {
+                // Claimed from the start; this call makes the first attempt
+                let inserted = tx.execute(
+                    "INSERT OR IGNORE INTO outbox (sink, delivery_id, payload, status, next_attempt_at, created_at)
+                     VALUES (?1, ?2, ?3, 'sending', ?4, ?4)",
+                    params![sink, delivery_id, payload, now],
+                )?;
+                if inserted == 0 {
+                    duplicates += 1;
+                    continue;
+                }
+                rows.push(Row {
+                    id: tx.last_insert_rowid(),
+                    sink,
+                    delivery_id: delivery_id.clone(),
+                    payload: payload.clone(),
+                    attempts: 0,
+                });
+            }
+            tx.commit().context("Failed to persist results to the outbox")?;
+            Ok((rows, duplicates))
+        }).await?;
+        outcome.duplicates = duplicates;
+        outcome.enqueued = rows.len();
+        if outcome.duplicates > 0 {
+            log::info!(
+                "Frame {} from {} already in the outbox for {} sink(s); not sending again",
+                record.frame_id, record.source, outcome.duplicates
+            );
+        }
+
+        outcome.delivered = self.attempt(rows).await;
+        Ok(outcome)
+    }
+
+    // Sends the rows due for another try; returns how many were delivered
+    pub async fn dispatch_due(&self) -> Result<usize> {
+        let rows = self.claim_due(Utc::now()).await?;
+        Ok(self.attempt(rows).await)
+    }
+
+    // Only rows still pending are taken, so one just claimed elsewhere is
+    // left alone
+    async fn claim_due(&self, now: DateTime<Utc>) -> Result<Vec<Row>> {
+        let limit = self.config.batch_size as i64;
+        self.with_conn(move |conn| {
+            let mut statement = conn.prepare_cached(
+                "UPDATE outbox SET status = 'sending'
+                 WHERE id IN (
+                     SELECT id FROM outbox WHERE status = 'pending' AND next_attempt_at <= ?1
+                     ORDER BY id LIMIT ?2
+                 )
+                 RETURNING id, sink, delivery_id, payload, attempts",
+            )?;
+            let mut rows = statement.query_map(params![millis(now), limit], read_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            rows.sort_by_key(|row| row.id);
+            Ok(rows)
+        }).await
+    }
+
+    async fn attempt(&self, rows: Vec<Row>) -> usize {
+        let sinks = self.sinks.read().await.clone();
+
+        let deliveries = rows.iter().map(|row| {
+            let sink = sinks.get(&row.sink).cloned();
+            async move {
+                let Some(sink) = sink else {
+                    return Err(anyhow::anyhow!("Sink {} is not registered", row.sink));
+                };
+                let record: Value = serde_json::from_str(&row.payload).context("Corrupt outbox payload")?;
+                sink.deliver(&row.delivery_id, &record).await
+            }
+        });
+        let results = futures::future::join_all(deliveries).await;
+
+        let mut delivered = 0;
+        let mut updates = Vec::with_capacity(rows.len());
+        for (row, result) in rows.iter().zip(results) {
+            match result {
+                Ok(()) => {
+                    delivered += 1;
+                    updates.push(Outcome {
+                        id: row.id,
+                        status: OutboxStatus::Delivered,
+                        attempts: row.attempts + 1,
+                        next_attempt_at: 0,
+                        error: None,
+                    });
+                }
+                Err(e) => updates.push(self.failure(row, &e)),
+            }
+        }
+        if updates.is_empty() {
+            return delivered;
+        }
+
+        let now = millis(Utc::now());
+        let recorded = self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            for update in updates {
+                tx.execute(
+                    "UPDATE outbox SET status = ?2, attempts = ?3, next_attempt_at = ?4, last_error = ?5,
+                         delivered_at = CASE WHEN ?2 = 'delivered' THEN ?6 END
+                     WHERE id = ?1",
+                    params![
+                        update.id, update.status.as_str(), update.attempts, update.next_attempt_at, update.error, now
+                    ],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        }).await;
+        // The rows stay claimed until the next restart releases them
+        if let Err(e) = recorded {
+            log::error!("Failed to update {} outbox row(s): {}", rows.len(), e);
+        }
+        delivered
+    }
+
+    fn failure(&self, row: &Row, error: &anyhow::Error) -> Outcome {
+        let attempts = row.attempts + 1;
+        let status = if attempts >= self.config.max_attempts.max(1) {
+            log::error!(
+                "Giving up on {} delivery {} after {} attempts: {}",
+                row.sink, row.delivery_id, attempts, error
+            );
+            OutboxStatus::Failed
+        } else {
+            log::warn!("{} delivery {} failed (attempt {}): {}", row.sink, row.delivery_id, attempts, error);
+            OutboxStatus::Pending
+        };
+
+        let next = Utc::now() + chrono::Duration::milliseconds(self.backoff_ms(attempts) as i64);
+        Outcome { id: row.id, status, attempts, next_attempt_at: millis(next), error: Some(error.to_string()) }
+    }
+
+    fn backoff_ms(&self, attempts: u32) -> u64 {
+        let exponent = attempts.saturating_sub(1).min(32);
+        self.config.initial_backoff_ms.saturating_mul(1u64 << exponent).min(self.config.max_backoff_ms)
+    }
+
+    // Drops delivered rows past the dedup window and failed ones past
+    // theirs; returns how many
+    pub fn purge(&self) -> Result<usize> {
+        let now = Utc::now();
+        let delivered = now - chrono::Duration::seconds(self.config.dedup_retention_secs);
+        let failed = now - chrono::Duration::seconds(self.config.failed_retention_secs);
+        Ok(self.conn.lock().unwrap().execute(
+            "DELETE FROM outbox
+             WHERE (status = 'delivered' AND delivered_at < ?1) OR (status = 'failed' AND created_at < ?2)",
+            params![millis(delivered), millis(failed)],
+        )?)
+    }
+
+    pub fn stats(&self) -> Result<OutboxStats> {
+        let conn = self.conn.lock().unwrap();
+        let mut stats = OutboxStats::default();
+        let mut statement = conn.prepare_cached("SELECT status, COUNT(*) FROM outbox GROUP BY status")?;
+        let counts = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?;
+        for count in counts {
+            let (status, count) = count?;
+            match status.as_str() {
+                // Rows mid-delivery are still owed
+                "pending" | "sending" => stats.pending += count as u64,
+                "delivered" => stats.delivered = count as u64,
+                "failed" => stats.failed = count as u64,
+                _ => {}
+            }
+        }
+        stats.oldest_pending_at = conn.query_row(
+            "SELECT MIN(created_at) FROM outbox WHERE status IN ('pending', 'sending')",
+            [],
+            |row| row.get::<_, Option<i64>>(0),
+        ).optional()?.flatten().map(from_millis);
+        Ok(stats)
+    }
+
+    pub fn list(&self, status: OutboxStatus, limit: usize) -> Result<Vec<OutboxEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare_cached(
+            "SELECT id, sink, delivery_id, attempts, last_error, created_at FROM outbox
+             WHERE status = ?1 ORDER BY id LIMIT ?2",
+        )?;
+        let entries = statement.query_map(params![status.as_str(), limit as i64], |row| {
+            Ok(OutboxEntry {
+                id: row.get(0)?,
+                sink: row.get(1)?,
+                delivery_id: row.get(2)?,
+                attempts: row.get(3)?,
+                last_error: row.get(4)?,
+                created_at: from_millis(row.get(5)?),
+            })
+        })?;
+        Ok(entries.collect::<rusqlite::Result<_>>()?)
+    }
+
+    // Puts rows that ran out of attempts back in the queue with a fresh
+    // count, optionally for one sink
+    pub fn retry_failed(&self, sink: Option<&str>) -> Result<usize> {
+        Ok(self.conn.lock().unwrap().execute(
+            "UPDATE outbox SET status = 'pending', attempts = 0, next_attempt_at = ?1
+             WHERE status = 'failed' AND (?2 IS NULL OR sink = ?2)",
+            params![millis(Utc::now()), sink],
+        )?)
+    }
+
+    // Retries due rows every poll interval, and prunes old rows. Runs until
+    // the handle is aborted.
+    pub fn start(self: &Arc<Self>) -> JoinHandle<()> {
+        let outbox = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_millis(outbox.config.poll_interval_ms.max(10)));
+            loop {
+                interval.tick().await;
+                match outbox.dispatch_due().await {
+                    Ok(0) => {}
+                    Ok(delivered) => log::info!("Outbox delivered {} queued result(s)", delivered),
+                    Err(e) => log::error!("Outbox dispatch failed: {}", e),
+                }
+                let purging = outbox.clone();
+                match tokio::task::spawn_blocking(move || purging.purge()).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => log::error!("Failed to prune outbox rows: {}", e),
+                    Err(e) => log::error!("Outbox prune task panicked: {}", e),
+                }
+            }
+        })
+    }
+}
@@ -1,14 +1,31 @@
+#![cfg(feature = "vision")]
+
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use futures::FutureExt;
+use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::task::JoinHandle;
 use anyhow::{Result, Context};
 use async_trait::async_trait;
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
 
+use crate::core::dead_letter::{DeadLetterConfig, DeadLetterQueue, DEAD_LETTER_ID_KEY, DEAD_LETTER_STAGE_KEY};
+use crate::core::diff::{DiffFilter, OutputMode};
+use crate::core::outbox::{OutboxConfig, SinkOutbox};
+use crate::core::sinks::{build_sink, ResultSink, SinkConfig};
+use crate::core::stream_stats::StreamStats;
+use crate::core::watchdog::{describe_stall, find_stalled, WatchdogConfig, WorkerActivity};
 use crate::vision::{
     processor::Frame,
     detector::Detection,
-    analyzer::Analysis
+    analyzer::{Analysis, BehaviorInfo},
+    actions::{self, ActionModelConfig, ActionRecognizer, ActionTrack},
+    privacy::{PrivacyConfig, PrivacyMasker},
+    lpr::{LprConfig, LprPipeline, PlateIndex},
+    similarity::{build_index, SimilarityIndex, SimilaritySettings},
+    shadow::{build_detection_model, DetectionModel, DetectionModelConfig},
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,15 +34,380 @@ pub struct PipelineConfig {
     pub max_parallel_stages: usize,
     pub buffer_size: usize,
     pub timeout_ms: u64,
+    // Extra tries at a failing stage before the frame is dead-lettered
     pub retry_count: u32,
+    // Doubles with each retry, up to retry_backoff_max_ms
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+    #[serde(default = "default_retry_backoff_max_ms")]
+    pub retry_backoff_max_ms: u64,
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
+    // When empty, results go to the output channel read by `get_result`
+    #[serde(default)]
+    pub sinks: Vec<SinkConfig>,
+    // Keeps frames that still fail after retry_count retries
+    #[serde(default)]
+    pub dead_letters: Option<DeadLetterConfig>,
+    // Routes results to the sinks through a persistent outbox
+    #[serde(default)]
+    pub outbox: Option<OutboxConfig>,
+    // Embeds processed frames into the index behind /v1/vision/search
+    #[serde(default)]
+    pub similarity: Option<SimilaritySettings>,
+    // Detection models by name: a detector or an ensemble, optionally
+    // behind a cascade gate. Detection stages whose `model` names one of
+    // these run it.
+    #[serde(default)]
+    pub models: HashMap<String, DetectionModelConfig>,
+}
+
+impl PipelineConfig {
+    pub fn from_json(raw: &str) -> Result<Self> {
+        let deserializer = &mut serde_json::Deserializer::from_str(raw);
+        Self::load(deserializer)
+    }
+
+    pub fn from_yaml(raw: &str) -> Result<Self> {
+        Self::load(serde_yaml::Deserializer::from_str(raw))
+    }
+
+    // Unknown keys are almost always typos, so they're reported rather
+    // than silently dropped
+    fn load<'de, D>(deserializer: D) -> Result<Self>
+    where
+        D: serde::Deserializer<'de>,
+        D::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let mut unknown = Vec::new();
+        let config: PipelineConfig = serde_ignored::deserialize(deserializer, |path| {
+            unknown.push(path.to_string());
+        })
+        .context("Invalid pipeline config")?;
+
+        for path in unknown {
+            log::warn!("Unknown pipeline config field `{}` ignored", path);
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.max_parallel_stages == 0 {
+            return Err(anyhow::anyhow!("max_parallel_stages must be at least 1"));
+        }
+        if self.buffer_size == 0 {
+            return Err(anyhow::anyhow!("Pipeline buffer size must be at least 1"));
+        }
+        for stage in &self.stages {
+            stage.settings.validate()
+                .with_context(|| format!("Invalid config for stage '{}'", stage.name))?;
+        }
+        for (name, model) in &self.models {
+            if model.detector.is_some() == model.ensemble.is_some() {
+                return Err(anyhow::anyhow!("Model '{}' needs exactly one of detector or ensemble", name));
+            }
+        }
+        self.validate_order()
+    }
+
+    // LPR reads plates off detected vehicles and adds the plate boxes to
+    // the detections, so it needs a detection stage ahead of it and every
+    // privacy stage after it, or plates reach the frame unmasked
+    fn validate_order(&self) -> Result<()> {
+        let enabled: Vec<&StageConfig> = self.stages.iter().filter(|s| s.enabled).collect();
+        let position = |t: StageType| enabled.iter().position(|s| s.stage_type() == t);
+        let mut hash_keys = Vec::new();
+        for (i, stage) in enabled.iter().enumerate() {
+            let StageSettings::Lpr(lpr) = &stage.settings else {
+                continue;
+            };
+            if position(StageType::Detection).is_none_or(|d| d > i) {
+                return Err(anyhow::anyhow!("LPR stage '{}' must come after a detection stage", stage.name));
+            }
+            if position(StageType::Privacy).is_some_and(|p| p < i) {
+                return Err(anyhow::anyhow!("LPR stage '{}' must come before every privacy stage", stage.name));
+            }
+            hash_keys.push(&lpr.hash_key);
+        }
+        if hash_keys.windows(2).any(|w| w[0] != w[1]) {
+            return Err(anyhow::anyhow!("LPR stages must share one hash_key"));
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "RawStageConfig")]
 pub struct StageConfig {
     pub name: String,
-    pub stage_type: StageType,
     pub enabled: bool,
-    pub params: HashMap<String, String>,
+    pub settings: StageSettings,
+}
+
+// Stages written before typed settings had a `stage_type` and a string map
+// of `params`. They still load, with a warning, by reading each value as
+// JSON where it parses and as a string otherwise; the old privacy stage
+// kept its whole config as JSON under a key named after the stage.
+#[derive(Deserialize)]
+struct RawStageConfig {
+    name: String,
+    #[serde(default = "default_stage_enabled")]
+    enabled: bool,
+    #[serde(default)]
+    settings: Option<StageSettings>,
+    #[serde(default)]
+    stage_type: Option<StageType>,
+    #[serde(default)]
+    params: Option<HashMap<String, String>>,
+}
+
+impl TryFrom<RawStageConfig> for StageConfig {
+    type Error = String;
+
+    fn try_from(raw: RawStageConfig) -> std::result::Result<Self, String> {
+        let settings = match (raw.settings, raw.stage_type) {
+            (Some(settings), _) => {
+                if raw.params.is_some() {
+                    log::warn!(
+                        "Stage '{}' has both settings and deprecated params; params are ignored",
+                        raw.name
+                    );
+                }
+                settings
+            }
+            (None, Some(stage_type)) => {
+                log::warn!(
+                    "Stage '{}' uses the deprecated stage_type and params fields; move them into settings",
+                    raw.name
+                );
+                legacy_settings(&stage_type, raw.params.unwrap_or_default())
+                    .map_err(|e| format!("stage '{}': {:#}", raw.name, e))?
+            }
+            (None, None) => return Err(format!("stage '{}' has no settings", raw.name)),
+        };
+        Ok(Self { name: raw.name, enabled: raw.enabled, settings })
+    }
+}
+
+fn legacy_settings(stage_type: &StageType, params: HashMap<String, String>) -> Result<StageSettings> {
+    let tag = match stage_type {
+        StageType::PreProcess => "pre_process",
+        StageType::Detection => "detection",
+        StageType::Analysis => "analysis",
+        StageType::Inference => "inference",
+        StageType::Privacy => "privacy",
+        StageType::Lpr => "lpr",
+        StageType::Action => "action",
+        StageType::PostProcess => "post_process",
+    };
+    let mut body = serde_json::Map::new();
+    for (key, raw) in params {
+        let value = serde_json::from_str(&raw).unwrap_or(serde_json::Value::String(raw));
+        match value {
+            serde_json::Value::Object(nested) if key == tag => body.extend(nested),
+            value => {
+                body.insert(key, value);
+            }
+        }
+    }
+    serde_json::from_value(serde_json::json!({ tag: body }))
+        .with_context(|| format!("params don't fit the {} settings", tag))
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    100
+}
+
+fn default_retry_backoff_max_ms() -> u64 {
+    10_000
+}
+
+fn default_stage_enabled() -> bool {
+    true
+}
+
+impl StageConfig {
+    pub fn stage_type(&self) -> StageType {
+        self.settings.stage_type()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageSettings {
+    PreProcess(PreProcessSettings),
+    Detection(DetectionSettings),
+    Analysis(AnalysisSettings),
+    Inference(InferenceSettings),
+    Privacy(PrivacyConfig),
+    Lpr(LprConfig),
+    Action(ActionModelConfig),
+    PostProcess(PostProcessSettings),
+}
+
+impl StageSettings {
+    pub fn stage_type(&self) -> StageType {
+        match self {
+            StageSettings::PreProcess(_) => StageType::PreProcess,
+            StageSettings::Detection(_) => StageType::Detection,
+            StageSettings::Analysis(_) => StageType::Analysis,
+            StageSettings::Inference(_) => StageType::Inference,
+            StageSettings::Privacy(_) => StageType::Privacy,
+            StageSettings::Lpr(_) => StageType::Lpr,
+            StageSettings::Action(_) => StageType::Action,
+            StageSettings::PostProcess(_) => StageType::PostProcess,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            StageSettings::PreProcess(s) => {
+                if let Some((width, height)) = s.resize {
+                    if width == 0 || height == 0 {
+                        return Err(anyhow::anyhow!("resize dimensions must be non-zero"));
+                    }
+                }
+            }
+            StageSettings::Detection(s) => {
+                if s.model.is_empty() {
+                    return Err(anyhow::anyhow!("model is required"));
+                }
+                check_unit("confidence_threshold", s.confidence_threshold)?;
+                check_unit("nms_threshold", s.nms_threshold)?;
+                if s.max_detections == 0 {
+                    return Err(anyhow::anyhow!("max_detections must be at least 1"));
+                }
+            }
+            StageSettings::Analysis(s) => {
+                if s.window_size == 0 {
+                    return Err(anyhow::anyhow!("window_size must be at least 1"));
+                }
+                check_unit("anomaly_threshold", s.anomaly_threshold)?;
+            }
+            StageSettings::Inference(s) => {
+                if s.model.is_empty() {
+                    return Err(anyhow::anyhow!("model is required"));
+                }
+                if s.batch_size == 0 {
+                    return Err(anyhow::anyhow!("batch_size must be at least 1"));
+                }
+            }
+            StageSettings::Privacy(s) => {
+                if s.padding < 0.0 {
+                    return Err(anyhow::anyhow!("padding must not be negative"));
+                }
+            }
+            StageSettings::Lpr(s) => {
+                if s.hash_key.len() < 16 {
+                    return Err(anyhow::anyhow!("hash_key must be at least 16 characters"));
+                }
+                if s.attributes.is_none() && s.plates.is_none() {
+                    return Err(anyhow::anyhow!("at least one of attributes or plates is required"));
+                }
+                if let Some(plates) = &s.plates {
+                    check_unit("plates.min_confidence", plates.min_confidence)?;
+                }
+            }
+            StageSettings::Action(s) => {
+                if s.model_path.is_empty() {
+                    return Err(anyhow::anyhow!("model_path is required"));
+                }
+                if s.labels.is_empty() {
+                    return Err(anyhow::anyhow!("labels are required"));
+                }
+                if s.clip_length == 0 || s.frame_stride == 0 {
+                    return Err(anyhow::anyhow!("clip_length and frame_stride must be at least 1"));
+                }
+                check_unit("threshold", s.threshold)?;
+            }
+            StageSettings::PostProcess(s) => {
+                check_unit("min_confidence", s.min_confidence)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_unit(field: &str, value: f32) -> Result<()> {
+    if !(0.0..=1.0).contains(&value) {
+        return Err(anyhow::anyhow!("{} must be between 0 and 1, got {}", field, value));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreProcessSettings {
+    pub resize: Option<(u32, u32)>,
+    pub normalize: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectionSettings {
+    pub model: String,
+    pub confidence_threshold: f32,
+    pub nms_threshold: f32,
+    pub max_detections: usize,
+    // Empty means every class the model emits
+    pub classes: Vec<String>,
+}
+
+impl Default for DetectionSettings {
+    fn default() -> Self {
+        Self {
+            model: String::new(),
+            confidence_threshold: 0.5,
+            nms_threshold: 0.45,
+            max_detections: 100,
+            classes: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalysisSettings {
+    pub window_size: usize,
+    pub anomaly_threshold: f32,
+    pub track_objects: bool,
+}
+
+impl Default for AnalysisSettings {
+    fn default() -> Self {
+        Self {
+            window_size: 30,
+            anomaly_threshold: 0.8,
+            track_objects: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InferenceSettings {
+    pub model: String,
+    pub batch_size: usize,
+    pub device: Option<String>,
+}
+
+impl Default for InferenceSettings {
+    fn default() -> Self {
+        Self {
+            model: String::new(),
+            batch_size: 1,
+            device: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PostProcessSettings {
+    pub min_confidence: f32,
+    pub deduplicate: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -34,6 +416,9 @@ pub enum StageType {
     Detection,
     Analysis,
     Inference,
+    Privacy,
+    Lpr,
+    Action,
     PostProcess,
 }
 
@@ -42,6 +427,12 @@ pub trait PipelineStage: Send + Sync {
     async fn process(&self, input: PipelineData) -> Result<PipelineData>;
     fn stage_type(&self) -> StageType;
     fn name(&self) -> String;
+
+    // Keeps per-source state across frames. Re-injected frames arrive out
+    // of order and skip these stages rather than rewind that state.
+    fn is_stateful(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -59,6 +450,39 @@ pub struct Pipeline {
     input_channel: mpsc::Sender<PipelineData>,
     output_channel: mpsc::Receiver<PipelineData>,
     state: Arc<RwLock<PipelineState>>,
+    workers: Arc<WorkerPool>,
+    plate_index: Arc<PlateIndex>,
+    // The outbox retry loop, while running
+    outbox_task: Option<JoinHandle<()>>,
+    // Wraps input_channel when faults are injected
+    #[cfg(feature = "chaos")]
+    faults: Option<crate::core::chaos::ChaosSender<PipelineData>>,
+}
+
+// Everything a worker needs, cloned into each task so the watchdog can
+// respawn a worker without access to the pipeline itself
+#[derive(Clone)]
+struct WorkerContext {
+    stages: Vec<Arc<dyn PipelineStage>>,
+    state: Arc<RwLock<PipelineState>>,
+    input: Arc<Mutex<mpsc::Receiver<PipelineData>>>,
+    output: mpsc::Sender<PipelineData>,
+    sinks: Arc<RwLock<Vec<Arc<dyn ResultSink>>>>,
+    deliveries: Deliveries,
+    diff: Arc<Mutex<DiffFilter>>,
+    activity: Arc<RwLock<HashMap<usize, WorkerActivity>>>,
+    stats: Arc<RwLock<Option<Arc<StreamStats>>>>,
+    dead_letters: Arc<RwLock<Option<Arc<DeadLetterQueue>>>>,
+    outbox: Arc<RwLock<Option<Arc<SinkOutbox>>>>,
+    similarity: Option<Arc<SimilarityIndex>>,
+    retries: u32,
+    retry_backoff: std::time::Duration,
+    retry_backoff_max: std::time::Duration,
+}
+
+struct WorkerPool {
+    context: WorkerContext,
+    handles: Mutex<HashMap<usize, JoinHandle<()>>>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -66,6 +490,7 @@ struct PipelineState {
     is_running: bool,
     processed_frames: u64,
     errors: u64,
+    stage_restarts: u64,
     stage_metrics: HashMap<String, StageMetrics>,
     start_time: chrono::DateTime<chrono::Utc>,
 }
@@ -76,33 +501,251 @@ struct StageMetrics {
     errors: u64,
     avg_processing_time: f64,
     last_processed: chrono::DateTime<chrono::Utc>,
+    restarts: u64,
+    panics: u64,
+}
+
+// `Pipeline::builder().stage("detect", StageSettings::Detection(..)).build().await`
+#[derive(Debug, Clone)]
+pub struct PipelineBuilder {
+    config: PipelineConfig,
+}
+
+impl Default for PipelineBuilder {
+    fn default() -> Self {
+        Self {
+            config: PipelineConfig {
+                stages: Vec::new(),
+                max_parallel_stages: 4,
+                buffer_size: 64,
+                timeout_ms: 5000,
+                retry_count: 0,
+                retry_backoff_ms: default_retry_backoff_ms(),
+                retry_backoff_max_ms: default_retry_backoff_max_ms(),
+                watchdog: WatchdogConfig::default(),
+                sinks: Vec::new(),
+                dead_letters: None,
+                outbox: None,
+                similarity: None,
+                models: HashMap::new(),
+            },
+        }
+    }
+}
+
+impl PipelineBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stage(mut self, name: &str, settings: StageSettings) -> Self {
+        self.config.stages.push(StageConfig {
+            name: name.to_string(),
+            enabled: true,
+            settings,
+        });
+        self
+    }
+
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.config.max_parallel_stages = workers;
+        self
+    }
+
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.config.buffer_size = size;
+        self
+    }
+
+    pub fn timeout_ms(mut self, timeout_ms: u64) -> Self {
+        self.config.timeout_ms = timeout_ms;
+        self
+    }
+
+    pub fn retries(mut self, count: u32) -> Self {
+        self.config.retry_count = count;
+        self
+    }
+
+    pub fn watchdog(mut self, watchdog: WatchdogConfig) -> Self {
+        self.config.watchdog = watchdog;
+        self
+    }
+
+    pub fn sink(mut self, sink: SinkConfig) -> Self {
+        self.config.sinks.push(sink);
+        self
+    }
+
+    pub fn dead_letters(mut self, dead_letters: DeadLetterConfig) -> Self {
+        self.config.dead_letters = Some(dead_letters);
+        self
+    }
+
+    pub fn outbox(mut self, outbox: OutboxConfig) -> Self {
+        self.config.outbox = Some(outbox);
+        self
+    }
+
+    pub fn similarity(mut self, similarity: SimilaritySettings) -> Self {
+        self.config.similarity = Some(similarity);
+        self
+    }
+
+    pub fn model(mut self, name: &str, model: DetectionModelConfig) -> Self {
+        self.config.models.insert(name.to_string(), model);
+        self
+    }
+
+    pub fn into_config(self) -> Result<PipelineConfig> {
+        if self.config.stages.is_empty() {
+            return Err(anyhow::anyhow!("Pipeline needs at least one stage"));
+        }
+        self.config.validate()?;
+        Ok(self.config)
+    }
+
+    pub async fn build(self) -> Result<Pipeline> {
+        Pipeline::new(self.into_config()?).await
+    }
 }
 
 impl Pipeline {
+    pub fn builder() -> PipelineBuilder {
+        PipelineBuilder::new()
+    }
+
     pub async fn new(config: PipelineConfig) -> Result<Self> {
+        // Configs built in code or embedded in other configs skip `load`
+        config.validate()?;
+
+        // Plate text is dropped at read time when any privacy stage asks for
+        // it, so it never reaches sinks or the plate index in the clear
+        let redact_plates = config.stages.iter().any(|s| {
+            matches!(&s.settings, StageSettings::Privacy(p) if p.redact_plate_text)
+        });
+        let lpr: Vec<&LprConfig> = config.stages.iter()
+            .filter_map(|s| match &s.settings {
+                StageSettings::Lpr(lpr) => Some(lpr),
+                _ => None,
+            })
+            .collect();
+        let plate_history = lpr.iter().map(|l| l.history).max().unwrap_or(1);
+        let plate_index = Arc::new(match lpr.first() {
+            Some(l) => PlateIndex::new(plate_history, &l.hash_key),
+            None => unused_plate_index(),
+        });
+
+        let mut models = HashMap::new();
+        for (name, model) in &config.models {
+            let built = build_detection_model(model.clone()).await
+                .with_context(|| format!("Failed to load detection model '{}'", name))?;
+            models.insert(name.clone(), (built, model.cascade.is_some()));
+        }
+
+        let mut stages: Vec<Arc<dyn PipelineStage>> = Vec::new();
+        for stage_config in &config.stages {
+            let stage = create_stage(stage_config, &plate_index, redact_plates, &models)?;
+            stages.push(Arc::from(stage));
+        }
+
+        Self::assemble(config, stages, plate_index).await
+    }
+
+    // Runs caller-provided stages in place of the configured ones, in order.
+    // Used for custom processing and by the soak harness to inject faults.
+    pub async fn with_stages(config: PipelineConfig, stages: Vec<Arc<dyn PipelineStage>>) -> Result<Self> {
+        if stages.is_empty() {
+            return Err(anyhow::anyhow!("Pipeline needs at least one stage"));
+        }
+        Self::assemble(config, stages, Arc::new(unused_plate_index())).await
+    }
+
+    async fn assemble(
+        config: PipelineConfig,
+        stages: Vec<Arc<dyn PipelineStage>>,
+        plate_index: Arc<PlateIndex>,
+    ) -> Result<Self> {
         let (tx, rx) = mpsc::channel(config.buffer_size);
         let (output_tx, output_rx) = mpsc::channel(config.buffer_size);
 
-        let mut stages = Vec::new();
-        for stage_config in &config.stages {
-            let stage = create_stage(stage_config)?;
-            stages.push(Arc::new(stage));
+        let mut sinks = Vec::new();
+        for sink_config in &config.sinks {
+            sinks.push(build_sink(sink_config).await?);
         }
 
+        let dead_letters = match &config.dead_letters {
+            Some(dead_letters) => {
+                let queue = Arc::new(DeadLetterQueue::new(dead_letters.clone()));
+                queue.set_target(tx.clone()).await;
+                Some(queue)
+            }
+            None => None,
+        };
+
+        let outbox = match &config.outbox {
+            Some(outbox_config) => {
+                let outbox_config = outbox_config.clone();
+                let outbox = tokio::task::spawn_blocking(move || SinkOutbox::open(outbox_config)).await??;
+                for sink in sinks.iter().filter(|s| s.replays()) {
+                    outbox.register(sink.clone()).await;
+                }
+                Some(Arc::new(outbox))
+            }
+            None => None,
+        };
+
+        let similarity = config.similarity.as_ref().map(build_index).transpose()?;
+
+        let sinks = Arc::new(RwLock::new(sinks));
+        let outbox = Arc::new(RwLock::new(outbox));
+        let deliveries = Deliveries::spawn(sinks.clone(), outbox.clone(), config.buffer_size);
+
         let state = Arc::new(RwLock::new(PipelineState {
             is_running: false,
             processed_frames: 0,
             errors: 0,
+            stage_restarts: 0,
             stage_metrics: HashMap::new(),
             start_time: chrono::Utc::now(),
         }));
 
+        let workers = Arc::new(WorkerPool {
+            context: WorkerContext {
+                stages: stages.clone(),
+                state: state.clone(),
+                input: Arc::new(Mutex::new(rx)),
+                output: output_tx,
+                sinks,
+                deliveries,
+                diff: Arc::new(Mutex::new(DiffFilter::new())),
+                activity: Arc::new(RwLock::new(HashMap::new())),
+                stats: Arc::new(RwLock::new(None)),
+                dead_letters: Arc::new(RwLock::new(dead_letters)),
+                outbox,
+                similarity,
+                retries: config.retry_count,
+                retry_backoff: std::time::Duration::from_millis(config.retry_backoff_ms),
+                retry_backoff_max: std::time::Duration::from_millis(config.retry_backoff_max_ms),
+            },
+            handles: Mutex::new(HashMap::new()),
+        });
+
+        #[cfg(feature = "chaos")]
+        let faults = crate::core::chaos::FaultInjector::global()
+            .map(|injector| crate::core::chaos::ChaosSender::new(tx.clone(), injector, "pipeline.input"));
+
         let pipeline = Self {
             config,
             stages,
             input_channel: tx,
             output_channel: output_rx,
             state,
+            workers,
+            plate_index,
+            outbox_task: None,
+            #[cfg(feature = "chaos")]
+            faults,
         };
 
         Ok(pipeline)
@@ -119,6 +762,12 @@ impl Pipeline {
         drop(state);
 
         self.spawn_workers().await?;
+        if self.config.watchdog.enabled {
+            self.start_watchdog();
+        }
+        if let Some(outbox) = self.workers.context.outbox.read().await.as_ref() {
+            self.outbox_task = Some(outbox.start());
+        }
         Ok(())
     }
 
@@ -131,38 +780,153 @@ impl Pipeline {
         state.is_running = false;
         drop(state);
 
+        for (_, handle) in self.workers.handles.lock().await.drain() {
+            handle.abort();
+        }
+        if let Some(task) = self.outbox_task.take() {
+            task.abort();
+        }
+        self.workers.context.activity.write().await.clear();
+
+        // Results the workers finished are still on their way to the sinks
+        if !self.workers.context.deliveries.drain(std::time::Duration::from_secs(5)).await {
+            log::warn!("Stopped with pipeline results still queued for the sinks");
+        }
+        for sink in self.workers.context.sinks.read().await.iter() {
+            if let Err(e) = sink.flush().await {
+                log::error!("Failed to flush sink {}: {}", sink.name(), e);
+            }
+        }
+
         Ok(())
     }
 
+    // Plate reads from LPR stages, for the detections API
+    pub fn plate_index(&self) -> Arc<PlateIndex> {
+        self.plate_index.clone()
+    }
+
+    // Custom sinks beyond the built-in ones; attach before `start`
+    pub async fn add_sink(&self, sink: Arc<dyn ResultSink>) {
+        if let Some(outbox) = self.workers.context.outbox.read().await.as_ref().filter(|_| sink.replays()) {
+            outbox.register(sink.clone()).await;
+        }
+        self.workers.context.sinks.write().await.push(sink);
+    }
+
+    // Routes results to the sinks through a persistent outbox, so a sink
+    // that is down gets them later instead of never. Sinks that can't
+    // replay stored records are still written to directly. Attach before
+    // `start`, which runs the outbox's retry loop.
+    pub async fn attach_outbox(&self, outbox: Arc<SinkOutbox>) {
+        for sink in self.workers.context.sinks.read().await.iter().filter(|s| s.replays()) {
+            outbox.register(sink.clone()).await;
+        }
+        *self.workers.context.outbox.write().await = Some(outbox);
+    }
+
+    // The index from the `similarity` config, for the search API
+    pub fn similarity_index(&self) -> Option<Arc<SimilarityIndex>> {
+        self.workers.context.similarity.clone()
+    }
+
+    // The outbox from the `outbox` config or attach_outbox, for the API
+    pub async fn outbox(&self) -> Option<Arc<SinkOutbox>> {
+        self.workers.context.outbox.read().await.clone()
+    }
+
+    // Per-stream latency, detection and drop samples for the stats API.
+    // Only frames that carry a stream id are counted.
+    pub async fn attach_stream_stats(&self, stats: Arc<StreamStats>) {
+        *self.workers.context.stats.write().await = Some(stats);
+    }
+
+    // The queue from the `dead_letters` config or attach_dead_letters, for
+    // the dead letters API
+    pub async fn dead_letters(&self) -> Option<Arc<DeadLetterQueue>> {
+        self.workers.context.dead_letters.read().await.clone()
+    }
+
+    // Frames that still fail after retry_count retries are kept here, and
+    // frames re-injected from it come back through this pipeline's input
+    pub async fn attach_dead_letters(&self, dead_letters: Arc<DeadLetterQueue>) {
+        dead_letters.set_target(self.input_channel.clone()).await;
+        *self.workers.context.dead_letters.write().await = Some(dead_letters);
+    }
+
+    // Differential streams only emit results whose detection set materially
+    // changed; the reasons are attached under the `changes` metadata key
+    pub async fn set_output_mode(&self, stream: &str, mode: OutputMode) {
+        self.workers.context.diff.lock().await.set_mode(stream, mode);
+    }
+
     async fn spawn_workers(&self) -> Result<()> {
-        let max_parallel = self.config.max_parallel_stages;
-        let stages = self.stages.clone();
-        let state = self.state.clone();
-
-        for i in 0..max_parallel {
-            let stages = stages.clone();
-            let state = state.clone();
-            
-            tokio::spawn(async move {
-                while let Ok(mut data) = self.input_channel.recv().await {
-                    for stage in &stages {
-                        match stage.process(data.clone()).await {
-                            Ok(processed_data) => {
-                                data = processed_data;
-                                update_metrics(&state, &stage.name(), true).await;
-                            }
-                            Err(e) => {
-                                log::error!("Stage {} error: {}", stage.name(), e);
-                                update_metrics(&state, &stage.name(), false).await;
-                                break;
-                            }
+        let mut handles = self.workers.handles.lock().await;
+        for worker_id in 0..self.config.max_parallel_stages {
+            handles.insert(worker_id, spawn_worker(self.workers.context.clone(), worker_id));
+        }
+
+        Ok(())
+    }
+
+    fn start_watchdog(&self) {
+        let config = self.config.watchdog.clone();
+        let workers = self.workers.clone();
+        let stage_names: Vec<String> = self.stages.iter().map(|s| s.name()).collect();
+
+        tokio::spawn(async move {
+            let threshold = chrono::Duration::seconds(config.stall_threshold_secs);
+            let mut interval = tokio::time::interval(
+                tokio::time::Duration::from_secs(config.check_interval_secs)
+            );
+
+            loop {
+                interval.tick().await;
+                let context = &workers.context;
+                if !context.state.read().await.is_running {
+                    break;
+                }
+
+                let last_processed: HashMap<String, chrono::DateTime<chrono::Utc>> = context.state.read().await
+                    .stage_metrics.iter()
+                    .map(|(name, m)| (name.clone(), m.last_processed))
+                    .collect();
+                let stalled = find_stalled(
+                    &*context.activity.read().await,
+                    &last_processed,
+                    threshold,
+                    chrono::Utc::now(),
+                );
+
+                for stall in stalled {
+                    log::error!("Watchdog restarting worker: {}", describe_stall(&stall, &stage_names));
+
+                    let mut handles = workers.handles.lock().await;
+                    if let Some(handle) = handles.remove(&stall.worker_id) {
+                        handle.abort();
+                    }
+                    context.activity.write().await.remove(&stall.worker_id);
+
+                    {
+                        let mut state = context.state.write().await;
+                        state.errors += 1;
+                        state.stage_restarts += 1;
+                        if let Some(metrics) = state.stage_metrics.get_mut(&stall.stage) {
+                            metrics.restarts += 1;
                         }
                     }
+
+                    handles.insert(stall.worker_id, spawn_worker(context.clone(), stall.worker_id));
                 }
-            });
-        }
+            }
+        });
+    }
 
-        Ok(())
+    // Frames entering the pipeline go through the `channel.pipeline.input`
+    // target. Pipelines built after `FaultInjector::install` get this already.
+    #[cfg(feature = "chaos")]
+    pub fn inject_faults(&mut self, injector: Arc<crate::core::chaos::FaultInjector>) {
+        self.faults = Some(crate::core::chaos::ChaosSender::new(self.input_channel.clone(), injector, "pipeline.input"));
     }
 
     pub async fn process(&self, frame: Frame) -> Result<()> {
@@ -174,6 +938,11 @@ impl Pipeline {
             timestamp: chrono::Utc::now(),
         };
 
+        #[cfg(feature = "chaos")]
+        if let Some(faults) = &self.faults {
+            return faults.send(data).await.context("Failed to send data to pipeline");
+        }
+
         self.input_channel.send(data).await
             .context("Failed to send data to pipeline")?;
         Ok(())
@@ -188,6 +957,8 @@ impl Pipeline {
         PipelineMetrics {
             processed_frames: state.processed_frames,
             errors: state.errors,
+            stage_restarts: state.stage_restarts,
+            suppressed_results: self.workers.context.diff.lock().await.suppressed(),
             stage_metrics: state.stage_metrics.clone(),
             uptime: chrono::Utc::now() - state.start_time,
             is_running: state.is_running,
@@ -195,6 +966,256 @@ impl Pipeline {
     }
 }
 
+#[derive(Debug, PartialEq)]
+enum WorkerExit {
+    InputClosed,
+    Panicked,
+}
+
+fn spawn_worker(context: WorkerContext, worker_id: usize) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        // A panicking stage ends the run; start over with fresh worker state
+        // rather than letting the task die and starve the pipeline
+        while run_worker(&context, worker_id).await == WorkerExit::Panicked {
+            log::warn!("Restarting pipeline worker {} after stage panic", worker_id);
+        }
+    })
+}
+
+async fn run_worker(context: &WorkerContext, worker_id: usize) -> WorkerExit {
+    loop {
+        let next = context.input.lock().await.recv().await;
+        let mut data = match next {
+            Some(data) => data,
+            None => return WorkerExit::InputClosed,
+        };
+
+        let mut completed = true;
+        let mut inference_ms = 0.0;
+        let stream = data.frame.metadata.stream_id.clone();
+        // Keyed by stream id, never by the source, which may hold credentials
+        let stats = match &stream {
+            Some(_) => context.stats.read().await.clone(),
+            None => None,
+        };
+        let dead_letters = context.dead_letters.read().await.clone();
+        let reinjected = data.metadata.contains_key(DEAD_LETTER_ID_KEY);
+        // Picks up at the stage that dead-lettered it
+        let resume_at: usize = data.metadata.remove(DEAD_LETTER_STAGE_KEY)
+            .and_then(|stage| stage.parse().ok())
+            .unwrap_or(0);
+
+        for (stage_index, stage) in context.stages.iter().enumerate() {
+            if stage_index < resume_at || (reinjected && stage.is_stateful()) {
+                continue;
+            }
+            let mut attempts = 0;
+            let result = loop {
+                attempts += 1;
+                context.activity.write().await.insert(worker_id, WorkerActivity {
+                    stage: stage.name(),
+                    stage_index,
+                    frame_id: data.frame.id,
+                    started_at: chrono::Utc::now(),
+                });
+
+                let started = std::time::Instant::now();
+                let result = AssertUnwindSafe(stage.process(data.clone())).catch_unwind().await;
+                if matches!(stage.stage_type(), StageType::Detection | StageType::Inference) {
+                    inference_ms += started.elapsed().as_secs_f64() * 1000.0;
+                }
+
+                match result {
+                    Ok(Err(e)) if attempts <= context.retries => {
+                        log::warn!(
+                            "Stage {} failed on frame {} (attempt {}), retrying: {}",
+                            stage.name(), data.frame.id, attempts, e
+                        );
+                        update_metrics(&context.state, &stage.name(), false).await;
+                        let backoff = context.retry_backoff.checked_mul(2u32.saturating_pow(attempts - 1))
+                            .map_or(context.retry_backoff_max, |b| b.min(context.retry_backoff_max));
+                        tokio::time::sleep(backoff).await;
+                    }
+                    result => break result,
+                }
+            };
+
+            match result {
+                Ok(Ok(processed_data)) => {
+                    data = processed_data;
+                    update_metrics(&context.state, &stage.name(), true).await;
+                }
+                Ok(Err(e)) => {
+                    log::error!("Stage {} error: {}", stage.name(), e);
+                    update_metrics(&context.state, &stage.name(), false).await;
+                    if let (Some(stats), Some(stream)) = (&stats, &stream) {
+                        stats.record_drop(stream, &format!("{}_error", stage.name())).await;
+                    }
+                    if let Some(queue) = &dead_letters {
+                        queue.push(data.clone(), stage_index, &stage.name(), &e.to_string(), attempts).await;
+                    }
+                    completed = false;
+                    break;
+                }
+                Err(panic) => {
+                    let message = panic_message(&panic);
+                    log::error!("Stage {} panicked on frame {}: {}", stage.name(), data.frame.id, message);
+                    update_metrics(&context.state, &stage.name(), false).await;
+                    record_panic(&context.state, &stage.name()).await;
+                    if let (Some(stats), Some(stream)) = (&stats, &stream) {
+                        stats.record_drop(stream, &format!("{}_panic", stage.name())).await;
+                    }
+                    if let Some(queue) = &dead_letters {
+                        queue.push(data, stage_index, &stage.name(), &format!("panicked: {}", message), attempts).await;
+                    }
+                    context.activity.write().await.remove(&worker_id);
+                    return WorkerExit::Panicked;
+                }
+            }
+        }
+        context.activity.write().await.remove(&worker_id);
+
+        if completed {
+            context.state.write().await.processed_frames += 1;
+            if let (Some(stats), Some(stream)) = (&stats, &stream) {
+                stats.record_frame(stream, inference_ms, &data.detections).await;
+            }
+            if let Some(queue) = &dead_letters {
+                queue.resolve(&data).await;
+            }
+            data.metadata.remove(DEAD_LETTER_ID_KEY);
+
+            // A re-injected frame may have been indexed on its first pass
+            if let Some(index) = context.similarity.as_ref().filter(|_| !reinjected) {
+                index.submit(&data.frame, &data.detections);
+            }
+
+            // The diff state is per stream too, and has moved past a
+            // re-injected frame; it is always emitted
+            let key = data.frame.metadata.stream_id.as_deref().unwrap_or(&data.frame.metadata.source);
+            let changes = if reinjected {
+                Some(Vec::new())
+            } else {
+                context.diff.lock().await.filter(key, &data.detections, chrono::Utc::now())
+            };
+            match changes {
+                None => continue,
+                Some(reasons) if !reasons.is_empty() => {
+                    if let Ok(encoded) = serde_json::to_string(&reasons) {
+                        data.metadata.insert("changes".to_string(), encoded);
+                    }
+                }
+                Some(_) => {}
+            }
+
+            if context.sinks.read().await.is_empty() {
+                if context.output.send(data).await.is_err() {
+                    return WorkerExit::InputClosed;
+                }
+                continue;
+            }
+            // Sinks are written from the delivery task, so a slow sink only
+            // holds workers up once its queue is full
+            if !context.deliveries.send(data).await {
+                return WorkerExit::InputClosed;
+            }
+        }
+    }
+}
+
+// Hands finished results from the workers to the sinks. One task writes
+// them in the order they were queued; `buffer_size` results can wait.
+#[derive(Clone)]
+struct Deliveries {
+    tx: mpsc::Sender<PipelineData>,
+    // Queued or being written, so `stop` can wait for the sinks to catch up
+    pending: Arc<AtomicUsize>,
+}
+
+impl Deliveries {
+    fn spawn(
+        sinks: Arc<RwLock<Vec<Arc<dyn ResultSink>>>>,
+        outbox: Arc<RwLock<Option<Arc<SinkOutbox>>>>,
+        buffer_size: usize,
+    ) -> Self {
+        let (tx, mut rx) = mpsc::channel::<PipelineData>(buffer_size.max(1));
+        let pending = Arc::new(AtomicUsize::new(0));
+        let done = pending.clone();
+
+        // Ends when the pipeline, and with it the last sender, is dropped
+        tokio::spawn(async move {
+            while let Some(data) = rx.recv().await {
+                let sinks = sinks.read().await.clone();
+                let outbox = outbox.read().await.clone();
+                deliver(&sinks, outbox.as_deref(), &data).await;
+                done.fetch_sub(1, Ordering::SeqCst);
+            }
+        });
+        Self { tx, pending }
+    }
+
+    async fn send(&self, data: PipelineData) -> bool {
+        // Count only once the slot is ours, so a worker aborted while
+        // waiting for room never leaves `pending` counting a lost result
+        let Ok(permit) = self.tx.reserve().await else {
+            return false;
+        };
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        permit.send(data);
+        true
+    }
+
+    // Whether everything queued was written before the timeout
+    async fn drain(&self, timeout: std::time::Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.pending.load(Ordering::SeqCst) > 0 {
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        true
+    }
+}
+
+async fn deliver(sinks: &[Arc<dyn ResultSink>], outbox: Option<&SinkOutbox>, data: &PipelineData) {
+    // The outbox takes the sinks that can replay; the rest, or all of
+    // them if the outbox can't take the result, are written directly
+    let mut direct = sinks.to_vec();
+    if let Some(outbox) = outbox {
+        match outbox.submit(data).await {
+            Ok(_) => direct.retain(|sink| !sink.replays()),
+            Err(e) => log::error!("Failed to queue frame {} for delivery, writing directly: {}", data.frame.id, e),
+        }
+    }
+
+    // One failing sink shouldn't hold back the others
+    let writes = direct.iter().map(|sink| async move { (sink.name(), sink.write(data).await) });
+    for (name, result) in futures::future::join_all(writes).await {
+        if let Err(e) = result {
+            log::error!("Sink {} failed for frame {}: {}", name, data.frame.id, e);
+        }
+    }
+}
+
+fn panic_message(panic: &Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+async fn record_panic(state: &Arc<RwLock<PipelineState>>, stage_name: &str) {
+    let mut state = state.write().await;
+    state.errors += 1;
+    if let Some(metrics) = state.stage_metrics.get_mut(stage_name) {
+        metrics.panics += 1;
+    }
+}
+
 async fn update_metrics(state: &Arc<RwLock<PipelineState>>, stage_name: &str, success: bool) {
     let mut state = state.write().await;
     let metrics = state.stage_metrics.entry(stage_name.to_string())
@@ -203,6 +1224,8 @@ async fn update_metrics(state: &Arc<RwLock<PipelineState>>, stage_name: &str, su
             errors: 0,
             avg_processing_time: 0.0,
             last_processed: chrono::Utc::now(),
+            restarts: 0,
+            panics: 0,
         });
 
     if success {
@@ -217,17 +1240,42 @@ async fn update_metrics(state: &Arc<RwLock<PipelineState>>, stage_name: &str, su
 pub struct PipelineMetrics {
     pub processed_frames: u64,
     pub errors: u64,
+    pub stage_restarts: u64,
+    pub suppressed_results: u64,
     pub stage_metrics: HashMap<String, StageMetrics>,
     pub uptime: chrono::Duration,
     pub is_running: bool,
 }
 
-fn create_stage(config: &StageConfig) -> Result<Box<dyn PipelineStage>> {
-    match config.stage_type {
+// Nothing reads plates into it, but it still shouldn't hash under a key
+// anyone could guess
+fn unused_plate_index() -> PlateIndex {
+    PlateIndex::new(1, &hex::encode(rand::random::<[u8; 32]>()))
+}
+
+// Built detection models by name, and whether each keeps per-source state
+type BuiltModels = HashMap<String, (Arc<dyn DetectionModel>, bool)>;
+
+fn create_stage(
+    config: &StageConfig,
+    plate_index: &Arc<PlateIndex>,
+    redact_plates: bool,
+    models: &BuiltModels,
+) -> Result<Box<dyn PipelineStage>> {
+    match config.stage_type() {
         StageType::PreProcess => Ok(Box::new(PreProcessStage::new(config.clone()))),
-        StageType::Detection => Ok(Box::new(DetectionStage::new(config.clone()))),
+        StageType::Detection => match &config.settings {
+            StageSettings::Detection(settings) if models.contains_key(&settings.model) => {
+                let (model, stateful) = models[&settings.model].clone();
+                Ok(Box::new(ModelStage { settings: settings.clone(), config: config.clone(), model, stateful }))
+            }
+            _ => Ok(Box::new(DetectionStage::new(config.clone()))),
+        },
         StageType::Analysis => Ok(Box::new(AnalysisStage::new(config.clone()))),
         StageType::Inference => Ok(Box::new(InferenceStage::new(config.clone()))),
+        StageType::Privacy => Ok(Box::new(PrivacyStage::new(config.clone())?)),
+        StageType::Lpr => Ok(Box::new(LprStage::new(config.clone(), plate_index.clone(), redact_plates)?)),
+        StageType::Action => Ok(Box::new(ActionStage::new(config.clone())?)),
         StageType::PostProcess => Ok(Box::new(PostProcessStage::new(config.clone()))),
     }
 }
@@ -259,4 +1307,185 @@ impl PipelineStage for PreProcessStage {
     }
 }
 
+// Runs one of the pipeline's configured detection models and keeps what
+// passes the stage's threshold and class filter, most confident first
+struct ModelStage {
+    config: StageConfig,
+    settings: DetectionSettings,
+    model: Arc<dyn DetectionModel>,
+    // Cascade gates track motion per source
+    stateful: bool,
+}
+
+#[async_trait]
+impl PipelineStage for ModelStage {
+    async fn process(&self, mut input: PipelineData) -> Result<PipelineData> {
+        let mut detections: Vec<Detection> = self.model.detect(&input.frame).await?
+            .into_iter()
+            .filter(|d| d.confidence >= self.settings.confidence_threshold)
+            .filter(|d| self.settings.classes.is_empty() || self.settings.classes.contains(&d.class_name))
+            .collect();
+        detections.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        detections.truncate(self.settings.max_detections);
+        input.detections.extend(detections);
+        Ok(input)
+    }
+
+    fn stage_type(&self) -> StageType {
+        StageType::Detection
+    }
+
+    fn name(&self) -> String {
+        self.config.name.clone()
+    }
+
+    fn is_stateful(&self) -> bool {
+        self.stateful
+    }
+}
+
+// Masks faces, plates and static regions so nothing downstream of this stage
+// (recording, previews, LLM calls) sees identifiable imagery
+struct PrivacyStage {
+    config: StageConfig,
+    masker: PrivacyMasker,
+}
+
+impl PrivacyStage {
+    fn new(config: StageConfig) -> Result<Self> {
+        let privacy_config = match &config.settings {
+            // Having the stage in the pipeline is what turns masking on
+            StageSettings::Privacy(settings) => PrivacyConfig { enabled: true, ..settings.clone() },
+            _ => return Err(anyhow::anyhow!("Stage {} is not a privacy stage", config.name)),
+        };
+
+        Ok(Self {
+            masker: PrivacyMasker::new(privacy_config)?,
+            config,
+        })
+    }
+}
+
+#[async_trait]
+impl PipelineStage for PrivacyStage {
+    async fn process(&self, mut input: PipelineData) -> Result<PipelineData> {
+        input.frame = self.masker.mask(&input.frame, &input.detections).await?;
+        Ok(input)
+    }
+
+    fn stage_type(&self) -> StageType {
+        StageType::Privacy
+    }
+
+    fn name(&self) -> String {
+        self.config.name.clone()
+    }
+}
+
+// Reads vehicle attributes and plates. Plate boxes are added to the
+// detections so a later privacy stage masks them; readings go under the
+// `vehicles` metadata key.
+struct LprStage {
+    config: StageConfig,
+    lpr: LprPipeline,
+}
+
+impl LprStage {
+    fn new(config: StageConfig, index: Arc<PlateIndex>, redact_plates: bool) -> Result<Self> {
+        let lpr_config = match &config.settings {
+            StageSettings::Lpr(settings) => settings.clone(),
+            _ => return Err(anyhow::anyhow!("Stage {} is not an LPR stage", config.name)),
+        };
+
+        Ok(Self {
+            lpr: LprPipeline::new(lpr_config, redact_plates, index)?,
+            config,
+        })
+    }
+}
+
+#[async_trait]
+impl PipelineStage for LprStage {
+    async fn process(&self, mut input: PipelineData) -> Result<PipelineData> {
+        let vehicles = self.lpr.process(&input.frame, &input.detections).await?;
+        if vehicles.is_empty() {
+            return Ok(input);
+        }
+
+        input.detections.extend(vehicles.iter().filter_map(|v| v.plate.as_ref()).map(|p| p.to_detection()));
+        input.metadata.insert("vehicles".to_string(), serde_json::to_string(&vehicles)?);
+        Ok(input)
+    }
+
+    fn stage_type(&self) -> StageType {
+        StageType::Lpr
+    }
+
+    fn name(&self) -> String {
+        self.config.name.clone()
+    }
+}
+
+// Buffers frames per source into clips and labels the actions in them.
+// Results go under the `activities` metadata key, and into the behavior
+// analysis when an earlier stage produced one.
+struct ActionStage {
+    config: StageConfig,
+    recognizer: Arc<ActionRecognizer>,
+    // Held only to find a source's track; recognition runs outside it
+    sources: Mutex<HashMap<String, Arc<std::sync::Mutex<ActionTrack>>>>,
+}
+
+impl ActionStage {
+    fn new(config: StageConfig) -> Result<Self> {
+        let action_config = match &config.settings {
+            StageSettings::Action(settings) => settings.clone(),
+            _ => return Err(anyhow::anyhow!("Stage {} is not an action stage", config.name)),
+        };
+
+        Ok(Self {
+            recognizer: Arc::new(ActionRecognizer::new(action_config)?),
+            sources: Mutex::new(HashMap::new()),
+            config,
+        })
+    }
+}
+
+#[async_trait]
+impl PipelineStage for ActionStage {
+    async fn process(&self, mut input: PipelineData) -> Result<PipelineData> {
+        let track = self.sources.lock().await
+            .entry(input.frame.metadata.source.clone())
+            .or_insert_with(|| Arc::new(std::sync::Mutex::new(self.recognizer.track())))
+            .clone();
+        let activities = actions::observe(&self.recognizer, &track, &input.frame, &input.detections).await?;
+        if activities.is_empty() {
+            return Ok(input);
+        }
+
+        input.metadata.insert("activities".to_string(), serde_json::to_string(&activities)?);
+        if let Some(analysis) = input.analysis.as_mut() {
+            let behavior = analysis.behavior_info.get_or_insert_with(|| BehaviorInfo {
+                activities: Vec::new(),
+                interactions: Vec::new(),
+                anomalies: Vec::new(),
+            });
+            behavior.activities = activities;
+        }
+        Ok(input)
+    }
+
+    fn stage_type(&self) -> StageType {
+        StageType::Action
+    }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
+
+    fn name(&self) -> String {
+        self.config.name.clone()
+    }
+}
+
 // Similar implementations for other stages...
\ No newline at end of file
@@ -0,0 +1,235 @@
+#![cfg(feature = "vision")]
+
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+
+use crate::vision::{
+    processor::{ProcessorConfig, PreprocessingStep},
+    detector::ModelConfig,
+    analyzer::AnalyzerType,
+    zones::{Zone, ZoneLine},
+    conditions::SceneCondition,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamProfile {
+    pub name: String,
+    pub description: Option<String>,
+    pub processor: ProcessorConfig,
+    pub models: Vec<ModelConfig>,
+    pub analyzers: Vec<AnalyzerType>,
+    pub zones: Vec<Zone>,
+    #[serde(default)]
+    pub lines: Vec<ZoneLine>,
+    pub recording: RecordingPolicy,
+    #[serde(default)]
+    pub confidence_threshold: Option<f32>,
+    #[serde(default)]
+    pub adaptations: Vec<ConditionOverride>,
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingPolicy {
+    pub mode: RecordingMode,
+    pub pre_event_secs: u32,
+    pub post_event_secs: u32,
+    pub retention_days: u32,
+}
+
+impl Default for RecordingPolicy {
+    fn default() -> Self {
+        Self {
+            mode: RecordingMode::Off,
+            pre_event_secs: 5,
+            post_event_secs: 10,
+            retention_days: 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingMode {
+    Off,
+    Continuous,
+    OnEvent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConditionOverride {
+    pub condition: SceneCondition,
+    pub confidence_threshold: Option<f32>,
+    // Restrict the profile to these model names while the condition is active
+    pub models: Option<Vec<String>>,
+    pub preprocessing: Option<Vec<PreprocessingStep>>,
+}
+
+impl StreamProfile {
+    pub fn adapted_for(&self, condition: SceneCondition) -> StreamProfile {
+        let mut profile = self.clone();
+        let Some(adaptation) = self.adaptations.iter().find(|a| a.condition == condition) else {
+            return profile;
+        };
+
+        if let Some(threshold) = adaptation.confidence_threshold {
+            profile.confidence_threshold = Some(threshold);
+        }
+        if let Some(models) = &adaptation.models {
+            profile.models.retain(|m| models.contains(&m.name));
+        }
+        if let Some(preprocessing) = &adaptation.preprocessing {
+            profile.processor.preprocessing = preprocessing.clone();
+        }
+
+        profile
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            return Err(anyhow::anyhow!("Profile name must not be empty"));
+        }
+        if self.processor.batch_size == 0 {
+            return Err(anyhow::anyhow!("Profile {}: processor batch_size must be > 0", self.name));
+        }
+        for zone in &self.zones {
+            zone.validate()
+                .with_context(|| format!("Profile {} has an invalid zone", self.name))?;
+        }
+        for line in &self.lines {
+            line.validate()
+                .with_context(|| format!("Profile {} has an invalid line", self.name))?;
+        }
+        for adaptation in &self.adaptations {
+            if let Some(models) = &adaptation.models {
+                for name in models {
+                    if !self.models.iter().any(|m| &m.name == name) {
+                        return Err(anyhow::anyhow!(
+                            "Profile {}: {} adaptation references unknown model {}",
+                            self.name, adaptation.condition, name
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileStoreConfig {
+    pub persist: bool,
+    pub profiles_file: String,
+}
+
+pub struct ProfileStore {
+    profiles: Arc<RwLock<HashMap<String, StreamProfile>>>,
+    config: ProfileStoreConfig,
+}
+
+impl ProfileStore {
+    pub async fn new(config: ProfileStoreConfig) -> Result<Self> {
+        let profiles = if config.persist && tokio::fs::try_exists(&config.profiles_file).await? {
+            let contents = tokio::fs::read_to_string(&config.profiles_file).await
+                .context("Failed to read profiles file")?;
+            let list: Vec<StreamProfile> = serde_json::from_str(&contents)
+                .context("Failed to parse profiles file")?;
+            list.into_iter().map(|p| (p.name.clone(), p)).collect()
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            profiles: Arc::new(RwLock::new(profiles)),
+            config,
+        })
+    }
+
+    pub async fn get(&self, name: &str) -> Option<StreamProfile> {
+        self.profiles.read().await.get(name).cloned()
+    }
+
+    pub async fn list(&self) -> Vec<StreamProfile> {
+        let mut profiles: Vec<_> = self.profiles.read().await.values().cloned().collect();
+        profiles.sort_by(|a, b| a.name.cmp(&b.name));
+        profiles
+    }
+
+    pub async fn create(&self, mut profile: StreamProfile) -> Result<StreamProfile> {
+        profile.validate()?;
+        profile.updated_at = Utc::now();
+
+        let mut profiles = self.profiles.write().await;
+        if profiles.contains_key(&profile.name) {
+            return Err(anyhow::anyhow!("Profile already exists: {}", profile.name));
+        }
+        profiles.insert(profile.name.clone(), profile.clone());
+        self.persist(&profiles).await?;
+        Ok(profile)
+    }
+
+    pub async fn update(&self, name: &str, mut profile: StreamProfile) -> Result<StreamProfile> {
+        profile.name = name.to_string();
+        profile.validate()?;
+        profile.updated_at = Utc::now();
+
+        let mut profiles = self.profiles.write().await;
+        if !profiles.contains_key(name) {
+            return Err(anyhow::anyhow!("Profile not found: {}", name));
+        }
+        profiles.insert(name.to_string(), profile.clone());
+        self.persist(&profiles).await?;
+        Ok(profile)
+    }
+
+    // Read-modify-write under the store's lock, for changes that depend on
+    // the profile's current contents; `change` failing leaves it untouched
+    pub async fn modify(
+        &self,
+        name: &str,
+        change: impl FnOnce(&mut StreamProfile) -> Result<()>,
+    ) -> Result<StreamProfile> {
+        let mut profiles = self.profiles.write().await;
+        let mut profile = profiles.get(name).cloned()
+            .ok_or_else(|| anyhow::anyhow!("Profile not found: {}", name))?;
+        change(&mut profile)?;
+        profile.name = name.to_string();
+        profile.validate()?;
+        profile.updated_at = Utc::now();
+        profiles.insert(name.to_string(), profile.clone());
+        self.persist(&profiles).await?;
+        Ok(profile)
+    }
+
+    pub async fn delete(&self, name: &str) -> Result<()> {
+        let mut profiles = self.profiles.write().await;
+        if profiles.remove(name).is_none() {
+            return Err(anyhow::anyhow!("Profile not found: {}", name));
+        }
+        self.persist(&profiles).await
+    }
+
+    // Called with the write lock held, so concurrent changes reach the file
+    // in the order they were made. The file is replaced by a rename, so a
+    // crash mid-write leaves the previous version rather than a torn one.
+    async fn persist(&self, profiles: &HashMap<String, StreamProfile>) -> Result<()> {
+        if !self.config.persist {
+            return Ok(());
+        }
+
+        let mut list: Vec<&StreamProfile> = profiles.values().collect();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        let serialized = serde_json::to_string_pretty(&list)?;
+        let tmp = format!("{}.tmp", self.config.profiles_file);
+        tokio::fs::write(&tmp, serialized).await
+            .context("Failed to persist profiles")?;
+        tokio::fs::rename(&tmp, &self.config.profiles_file).await
+            .context("Failed to persist profiles")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use chrono::{DateTime, Utc};
+use minijinja::Environment;
+use serde::{Serialize, Deserialize};
+
+use crate::core::i18n::ResolvedLanguage;
+use crate::core::prompts::{PromptLibrary, PromptRef};
+
+// Jinja-style templates for system prompts, rendered with minijinja:
+//
+//   You are {{ persona }}. Today is {{ date }}.
+//   {% if memory_summary %}What you remember: {{ memory_summary }}{% endif %}
+//   {% for tool in tools %}- {{ tool.name }}: {{ tool.description }}
+//   {% endfor %}
+//   {% if language %}Answer in {{ language }}.{% endif %}
+//
+// Variables the agent doesn't provide render as empty rather than failing,
+// so a template can be shared between agents with different tools.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolInfo {
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptContext {
+    pub persona: String,
+    pub date: String,
+    pub now: DateTime<Utc>,
+    pub memory_summary: Option<String>,
+    pub tools: Vec<ToolInfo>,
+    // Name of the language to answer in, e.g. "French"
+    pub language: Option<String>,
+    // Anything else a template wants, e.g. the site name or camera count
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl PromptContext {
+    pub fn new(persona: &str) -> Self {
+        let now = Utc::now();
+        Self {
+            persona: persona.to_string(),
+            date: now.format("%A, %-d %B %Y").to_string(),
+            now,
+            memory_summary: None,
+            tools: Vec::new(),
+            language: None,
+            extra: HashMap::new(),
+        }
+    }
+
+    pub fn with_memory(mut self, summary: &str) -> Self {
+        self.memory_summary = (!summary.trim().is_empty()).then(|| summary.to_string());
+        self
+    }
+
+    pub fn with_tool(mut self, name: &str, description: &str) -> Self {
+        self.tools.push(ToolInfo { name: name.to_string(), description: description.to_string() });
+        self
+    }
+
+    pub fn with_language(mut self, language: &ResolvedLanguage) -> Self {
+        self.language = Some(language.name.clone());
+        self
+    }
+
+    pub fn with_var(mut self, name: &str, value: impl Into<serde_json::Value>) -> Self {
+        self.extra.insert(name.to_string(), value.into());
+        self
+    }
+}
+
+// A single template, parsed once on load so syntax errors surface then
+// rather than on the first conversation, and renders reuse the result
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    name: String,
+    source: String,
+    env: Arc<Environment<'static>>,
+}
+
+impl PromptTemplate {
+    pub fn new(name: &str, source: &str) -> Result<Self> {
+        Ok(Self {
+            name: name.to_string(),
+            source: source.to_string(),
+            env: Arc::new(environment(name, source)?),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn render(&self, context: &PromptContext) -> Result<String> {
+        let template = self.env.get_template(&self.name)?;
+        template.render(context)
+            .with_context(|| format!("Failed to render prompt template {}", self.name))
+    }
+}
+
+fn environment(name: &str, source: &str) -> Result<Environment<'static>> {
+    let mut env = Environment::new();
+    // Prompts are plain text; HTML escaping would mangle quotes and angle
+    // brackets in tool descriptions
+    env.set_auto_escape_callback(|_| minijinja::AutoEscape::None);
+    env.set_keep_trailing_newline(true);
+    env.add_template_owned(name.to_string(), source.to_string())
+        .with_context(|| format!("Failed to parse prompt template {}", name))?;
+    Ok(env)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PromptTemplateConfig {
+    // Every *.j2, *.jinja and *.txt file here is a template named after its
+    // file stem
+    pub templates_dir: String,
+    // Template used for the agent's system prompt
+    pub system_template: String,
+    // Re-reads changed files this often; None loads once at startup
+    pub reload_interval_secs: Option<u64>,
+}
+
+impl Default for PromptTemplateConfig {
+    fn default() -> Self {
+        Self {
+            templates_dir: String::from("prompts"),
+            system_template: String::from("system"),
+            reload_interval_secs: Some(5),
+        }
+    }
+}
+
+struct LoadedTemplate {
+    template: PromptTemplate,
+    modified: Option<SystemTime>,
+}
+
+pub struct PromptTemplates {
+    config: PromptTemplateConfig,
+    templates: Arc<RwLock<HashMap<String, LoadedTemplate>>>,
+}
+
+fn is_template(path: &Path) -> bool {
+    matches!(path.extension().and_then(|e| e.to_str()), Some("j2" | "jinja" | "txt"))
+}
+
+impl PromptTemplates {
+    pub async fn new(config: PromptTemplateConfig) -> Result<Self> {
+        let templates = Self {
+            config,
+            templates: Arc::new(RwLock::new(HashMap::new())),
+        };
+        let errors = templates.reload().await?;
+        // A broken template at startup is a config error, unlike one that
+        // breaks while running
+        if let Some((path, e)) = errors.into_iter().next() {
+            return Err(e.context(format!("Invalid prompt template {}", path.display())));
+        }
+        Ok(templates)
+    }
+
+    pub async fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.templates.read().await.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub async fn get(&self, name: &str) -> Option<PromptTemplate> {
+        self.templates.read().await.get(name).map(|t| t.template.clone())
+    }
+
+    pub async fn render(&self, name: &str, context: &PromptContext) -> Result<String> {
+        let template = self.get(name).await
+            .ok_or_else(|| anyhow::anyhow!("Prompt template not found: {}", name))?;
+        template.render(context)
+    }
+
+    pub async fn render_system(&self, context: &PromptContext) -> Result<String> {
+        self.render(&self.config.system_template, context).await
+    }
+
+    // Picks up new and changed files and drops deleted ones. A file that no
+    // longer parses keeps its last good version, and is returned with the
+    // error so the caller can report it.
+    pub async fn reload(&self) -> Result<Vec<(PathBuf, anyhow::Error)>> {
+        let dir = Path::new(&self.config.templates_dir);
+        if !tokio::fs::try_exists(dir).await? {
+            log::warn!("Prompt template directory {} does not exist", dir.display());
+            return Ok(Vec::new());
+        }
+
+        let mut found = Vec::new();
+        let mut entries = tokio::fs::read_dir(dir).await
+            .with_context(|| format!("Failed to read {}", dir.display()))?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if is_template(&path) {
+                let modified = entry.metadata().await.ok().and_then(|m| m.modified().ok());
+                found.push((name.to_string(), path, modified));
+            }
+        }
+
+        let mut errors = Vec::new();
+        let mut templates = self.templates.write().await;
+        templates.retain(|name, _| found.iter().any(|(n, _, _)| n == name));
+        for (name, path, modified) in found {
+            if templates.get(&name).is_some_and(|t| t.modified == modified && modified.is_some()) {
+                continue;
+            }
+            let loaded = match tokio::fs::read_to_string(&path).await {
+                Ok(source) => PromptTemplate::new(&name, &source),
+                Err(e) => Err(anyhow::Error::new(e)),
+            };
+            match loaded {
+                Ok(template) => {
+                    if templates.contains_key(&name) {
+                        log::info!("Reloaded prompt template {}", name);
+                    }
+                    templates.insert(name, LoadedTemplate { template, modified });
+                }
+                Err(e) => {
+                    // Not retried until the file changes again
+                    if let Some(previous) = templates.get_mut(&name) {
+                        previous.modified = modified;
+                    }
+                    errors.push((path, e));
+                }
+            }
+        }
+        Ok(errors)
+    }
+
+    pub fn start_watch(self: &Arc<Self>) {
+        let Some(interval_secs) = self.config.reload_interval_secs else {
+            return;
+        };
+        let templates = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(
+                tokio::time::Duration::from_secs(interval_secs.max(1))
+            );
+
+            loop {
+                interval.tick().await;
+                match templates.reload().await {
+                    Ok(errors) => {
+                        for (path, e) in errors {
+                            log::warn!("Keeping previous version of {}: {:#}", path.display(), e);
+                        }
+                    }
+                    Err(e) => log::error!("Failed to reload prompt templates: {}", e),
+                }
+            }
+        });
+    }
+}
+
+impl PromptLibrary {
+    // Prompts published through the library are templates too, so versioned
+    // prompts can use the same variables
+    pub async fn render(&self, reference: &PromptRef, context: &PromptContext) -> Result<String> {
+        let source = self.resolve(reference).await?;
+        PromptTemplate::new(&reference.name, &source)?.render(context)
+    }
+}
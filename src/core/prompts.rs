@@ -0,0 +1,235 @@
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptKind {
+    System,
+    ToolDescription,
+    Template,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptVersion {
+    pub version: u32,
+    pub content: String,
+    pub author: Option<String>,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Prompt {
+    pub name: String,
+    pub kind: PromptKind,
+    pub description: Option<String>,
+    pub versions: Vec<PromptVersion>,
+    pub active_version: u32,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Prompt {
+    pub fn version(&self, version: u32) -> Option<&PromptVersion> {
+        self.versions.iter().find(|v| v.version == version)
+    }
+
+    pub fn active(&self) -> Option<&PromptVersion> {
+        self.version(self.active_version)
+    }
+
+    fn latest_version(&self) -> u32 {
+        self.versions.iter().map(|v| v.version).max().unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewPromptVersion {
+    pub kind: PromptKind,
+    pub content: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    // Publish without switching agents over, e.g. to stage a change
+    #[serde(default = "default_activate")]
+    pub activate: bool,
+}
+
+fn default_activate() -> bool {
+    true
+}
+
+// How agents point at a prompt; without a pinned version the active one is used
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PromptRef {
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptLibraryConfig {
+    pub persist: bool,
+    pub prompts_file: String,
+}
+
+impl Default for PromptLibraryConfig {
+    fn default() -> Self {
+        Self {
+            persist: true,
+            prompts_file: String::from("prompts.json"),
+        }
+    }
+}
+
+pub struct PromptLibrary {
+    config: PromptLibraryConfig,
+    prompts: Arc<RwLock<HashMap<String, Prompt>>>,
+}
+
+impl PromptLibrary {
+    pub async fn new(config: PromptLibraryConfig) -> Result<Self> {
+        let prompts = if config.persist && tokio::fs::try_exists(&config.prompts_file).await? {
+            let contents = tokio::fs::read_to_string(&config.prompts_file).await
+                .context("Failed to read prompts file")?;
+            let list: Vec<Prompt> = serde_json::from_str(&contents)
+                .context("Failed to parse prompts file")?;
+            list.into_iter().map(|p| (p.name.clone(), p)).collect()
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            config,
+            prompts: Arc::new(RwLock::new(prompts)),
+        })
+    }
+
+    pub async fn get(&self, name: &str) -> Option<Prompt> {
+        self.prompts.read().await.get(name).cloned()
+    }
+
+    pub async fn list(&self) -> Vec<Prompt> {
+        let mut prompts: Vec<_> = self.prompts.read().await.values().cloned().collect();
+        prompts.sort_by(|a, b| a.name.cmp(&b.name));
+        prompts
+    }
+
+    pub async fn resolve(&self, reference: &PromptRef) -> Result<String> {
+        let prompts = self.prompts.read().await;
+        let prompt = prompts.get(&reference.name)
+            .ok_or_else(|| anyhow::anyhow!("Prompt not found: {}", reference.name))?;
+
+        let version = reference.version.unwrap_or(prompt.active_version);
+        prompt.version(version)
+            .map(|v| v.content.clone())
+            .ok_or_else(|| anyhow::anyhow!("Prompt {} has no version {}", reference.name, version))
+    }
+
+    pub async fn publish(&self, name: &str, new_version: NewPromptVersion) -> Result<Prompt> {
+        if name.trim().is_empty() {
+            return Err(anyhow::anyhow!("Prompt name must not be empty"));
+        }
+        if new_version.content.trim().is_empty() {
+            return Err(anyhow::anyhow!("Prompt content must not be empty"));
+        }
+
+        let now = Utc::now();
+        let mut prompts = self.prompts.write().await;
+        let prompt = prompts.entry(name.to_string()).or_insert_with(|| Prompt {
+            name: name.to_string(),
+            kind: new_version.kind,
+            description: None,
+            versions: Vec::new(),
+            active_version: 0,
+            updated_at: now,
+        });
+
+        if prompt.kind != new_version.kind {
+            return Err(anyhow::anyhow!("Prompt {} has a different kind", name));
+        }
+
+        let version = prompt.latest_version() + 1;
+        prompt.versions.push(PromptVersion {
+            version,
+            content: new_version.content,
+            author: new_version.author,
+            note: new_version.note,
+            created_at: now,
+        });
+        if new_version.description.is_some() {
+            prompt.description = new_version.description;
+        }
+        // The first version is always active so references resolve immediately
+        if new_version.activate || prompt.active_version == 0 {
+            prompt.active_version = version;
+        }
+        prompt.updated_at = now;
+
+        let prompt = prompt.clone();
+        drop(prompts);
+
+        self.persist().await?;
+        Ok(prompt)
+    }
+
+    pub async fn activate(&self, name: &str, version: u32) -> Result<Prompt> {
+        let mut prompts = self.prompts.write().await;
+        let prompt = prompts.get_mut(name)
+            .ok_or_else(|| anyhow::anyhow!("Prompt not found: {}", name))?;
+
+        if prompt.version(version).is_none() {
+            return Err(anyhow::anyhow!("Prompt {} has no version {}", name, version));
+        }
+        prompt.active_version = version;
+        prompt.updated_at = Utc::now();
+
+        let prompt = prompt.clone();
+        drop(prompts);
+
+        log::info!("Prompt {} now at version {}", name, version);
+        self.persist().await?;
+        Ok(prompt)
+    }
+
+    // Switches back to the newest version older than the active one
+    pub async fn rollback(&self, name: &str) -> Result<Prompt> {
+        let previous = {
+            let prompts = self.prompts.read().await;
+            let prompt = prompts.get(name)
+                .ok_or_else(|| anyhow::anyhow!("Prompt not found: {}", name))?;
+
+            prompt.versions.iter()
+                .map(|v| v.version)
+                .filter(|v| *v < prompt.active_version)
+                .max()
+                .ok_or_else(|| anyhow::anyhow!("Prompt {} has no earlier version", name))?
+        };
+
+        self.activate(name, previous).await
+    }
+
+    pub async fn delete(&self, name: &str) -> Result<()> {
+        if self.prompts.write().await.remove(name).is_none() {
+            return Err(anyhow::anyhow!("Prompt not found: {}", name));
+        }
+        self.persist().await
+    }
+
+    async fn persist(&self) -> Result<()> {
+        if !self.config.persist {
+            return Ok(());
+        }
+
+        let serialized = serde_json::to_string_pretty(&self.list().await)?;
+        tokio::fs::write(&self.config.prompts_file, serialized).await
+            .context("Failed to persist prompts")?;
+        Ok(())
+    }
+}
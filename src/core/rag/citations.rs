@@ -0,0 +1,250 @@
+use std::sync::Arc;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use regex::Regex;
+use once_cell::sync::Lazy;
+
+use crate::core::llm::{LLMTrait, types::Message};
+use crate::core::rag::store::ScoredChunk;
+
+// Matches `[chunk:<id>]` and `[chunk:<id> "quoted span"]`
+static CITATION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"\[chunk:([^\]\s"]+)(?:\s+"([^"]*)")?\]"#).unwrap()
+});
+
+static TRAILING_CITATIONS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"([.!?])((?:\s*\[chunk:[^\]]*\])+)"#).unwrap()
+});
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum UncitedPolicy {
+    // Keep uncited claims but mark them in the response
+    Flag,
+    // Remove uncited claims from the answer text
+    Drop,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CitationConfig {
+    pub uncited_policy: UncitedPolicy,
+    // Answers below this grounded-claim ratio are flagged as low confidence
+    pub min_grounding: f32,
+}
+
+impl Default for CitationConfig {
+    fn default() -> Self {
+        Self {
+            uncited_policy: UncitedPolicy::Flag,
+            min_grounding: 0.6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Citation {
+    pub chunk_id: String,
+    pub document_id: String,
+    pub filename: String,
+    pub page: Option<u32>,
+    pub section: Option<String>,
+    pub quote: Option<String>,
+    // Char offsets of the quote within the chunk text, when verified
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+    pub verified: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claim {
+    pub text: String,
+    pub citations: Vec<usize>,
+    pub grounded: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroundedAnswer {
+    pub answer: String,
+    pub claims: Vec<Claim>,
+    pub citations: Vec<Citation>,
+    pub grounding: f32,
+    pub low_confidence: bool,
+}
+
+pub fn build_context(chunks: &[ScoredChunk]) -> String {
+    let mut context = String::from(
+        "Answer using only the sources below. After every sentence that uses a \
+         source, cite it as [chunk:<id> \"exact short quote from the source\"]. \
+         If the sources don't contain the answer, say so.\n\nSources:\n"
+    );
+    for scored in chunks {
+        context.push_str(&format!("\n[chunk:{}]\n{}\n", scored.chunk.id, scored.chunk.text));
+    }
+    context
+}
+
+// Locates `quote` in `text`, tolerant of case and whitespace differences.
+// Returns char offsets into `text`.
+pub fn find_span(text: &str, quote: &str) -> Option<(usize, usize)> {
+    let normalize = |s: &str| -> Vec<(usize, char)> {
+        let mut out: Vec<(usize, char)> = Vec::new();
+        for (i, c) in s.chars().enumerate() {
+            if c.is_whitespace() {
+                if out.last().map_or(false, |(_, prev)| *prev == ' ') {
+                    continue;
+                }
+                out.push((i, ' '));
+            } else {
+                out.extend(c.to_lowercase().map(|l| (i, l)));
+            }
+        }
+        out
+    };
+
+    let haystack = normalize(text);
+    let needle: Vec<char> = normalize(quote.trim()).into_iter().map(|(_, c)| c).collect();
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return None;
+    }
+
+    (0..=haystack.len() - needle.len())
+        .find(|&i| haystack[i..i + needle.len()].iter().map(|(_, c)| *c).eq(needle.iter().copied()))
+        .map(|i| (haystack[i].0, haystack[i + needle.len() - 1].0 + 1))
+}
+
+fn split_claims(text: &str) -> Vec<String> {
+    // Citations placed after the full stop belong to that sentence
+    let text = TRAILING_CITATIONS.replace_all(text, "$2$1");
+    let mut claims = Vec::new();
+    let mut current = String::new();
+    let mut in_citation = false;
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        current.push(c);
+        // Punctuation only ends a sentence before whitespace or the end, so
+        // "1.5 m" and "v2.3" stay in one claim
+        let ends_sentence = match c {
+            '\n' => true,
+            '.' | '!' | '?' => chars.peek().is_none_or(|next| next.is_whitespace()),
+            _ => false,
+        };
+        match c {
+            '[' => in_citation = true,
+            ']' => in_citation = false,
+            _ if ends_sentence && !in_citation => {
+                if !current.trim().is_empty() {
+                    claims.push(current.trim().to_string());
+                }
+                current.clear();
+            }
+            _ => {}
+        }
+    }
+    if !current.trim().is_empty() {
+        claims.push(current.trim().to_string());
+    }
+    claims
+}
+
+// Parses model output, verifying that each citation points at a retrieved
+// chunk and that quoted spans really occur in it
+pub fn ground_answer(raw: &str, chunks: &[ScoredChunk], config: &CitationConfig) -> GroundedAnswer {
+    let mut citations: Vec<Citation> = Vec::new();
+    let mut claims = Vec::new();
+
+    for claim_text in split_claims(raw) {
+        let mut indices = Vec::new();
+
+        for captures in CITATION.captures_iter(&claim_text) {
+            let chunk_id = captures[1].to_string();
+            let quote = captures.get(2).map(|m| m.as_str().to_string());
+            let chunk = chunks.iter().find(|c| c.chunk.id == chunk_id);
+
+            let (verified, span) = match (chunk, &quote) {
+                (Some(chunk), Some(quote)) => match find_span(&chunk.chunk.text, quote) {
+                    Some(span) => (true, Some(span)),
+                    None => (false, None),
+                },
+                // A bare citation to a real chunk is accepted without a span
+                (Some(_), None) => (true, None),
+                (None, _) => (false, None),
+            };
+
+            citations.push(Citation {
+                chunk_id,
+                document_id: chunk.map(|c| c.chunk.document_id.clone()).unwrap_or_default(),
+                filename: chunk.map(|c| c.chunk.metadata.filename.clone()).unwrap_or_default(),
+                page: chunk.and_then(|c| c.chunk.metadata.page),
+                section: chunk.and_then(|c| c.chunk.metadata.section.clone()),
+                quote,
+                start: span.map(|s| s.0),
+                end: span.map(|s| s.1),
+                verified,
+            });
+            indices.push(citations.len() - 1);
+        }
+
+        let grounded = indices.iter().any(|i| citations[*i].verified);
+        let text = CITATION.replace_all(&claim_text, "")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .replace(" .", ".")
+            .replace(" ,", ",")
+            .replace(" !", "!")
+            .replace(" ?", "?");
+        claims.push(Claim { text, citations: indices, grounded });
+    }
+
+    let grounding = if claims.is_empty() {
+        0.0
+    } else {
+        claims.iter().filter(|c| c.grounded).count() as f32 / claims.len() as f32
+    };
+
+    let answer = claims.iter()
+        .filter(|c| c.grounded || config.uncited_policy == UncitedPolicy::Flag)
+        .map(|c| c.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if config.uncited_policy == UncitedPolicy::Drop {
+        claims.retain(|c| c.grounded);
+    }
+
+    GroundedAnswer {
+        answer,
+        claims,
+        citations,
+        grounding,
+        low_confidence: grounding < config.min_grounding,
+    }
+}
+
+pub struct GroundedAnswerer {
+    config: CitationConfig,
+    llm: Arc<dyn LLMTrait>,
+}
+
+impl GroundedAnswerer {
+    pub fn new(config: CitationConfig, llm: Arc<dyn LLMTrait>) -> Self {
+        Self { config, llm }
+    }
+
+    pub async fn answer(&self, mut messages: Vec<Message>, chunks: &[ScoredChunk]) -> Result<GroundedAnswer> {
+        let split = messages.iter().take_while(|m| m.role == "system").count();
+        messages.insert(split, Message::new("system", &build_context(chunks)));
+
+        let response = self.llm.complete(messages).await
+            .context("Grounded completion failed")?;
+        let answer = ground_answer(&response.content, chunks, &self.config);
+
+        if answer.low_confidence {
+            log::warn!(
+                "Answer grounding {:.2} below threshold {:.2}",
+                answer.grounding, self.config.min_grounding
+            );
+        }
+        Ok(answer)
+    }
+}
@@ -0,0 +1,551 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+
+use crate::core::agent::tools::Tool;
+use crate::core::alerts::{Alert, AlertManager};
+use crate::core::incidents::{Incident, IncidentEvent, IncidentFilter, IncidentManager, IncidentStatus};
+use crate::core::lifecycle::{DataCategory, DataStore, DataSubject};
+use crate::core::rag::hybrid::{fuse, HybridConfig, KeywordIndex};
+use crate::core::rag::ingest::{ChunkMetadata, DocumentChunk};
+use crate::core::rag::store::{Embedder, Indexer, ScoredChunk, VectorStore};
+use crate::core::reports::{Report, ReportGenerator};
+
+// Keeps incidents, daily reports and alert history in a vector store and
+// keyword index of their own, so the agent can answer "has this happened
+// before?" with links to what it found. Don't hand it the stores of the
+// uploaded documents: they would crowd history out of every search, and
+// erasing an identity here would scan them. Each record is one document:
+//
+//   incident:<id>            /v1/incidents/<id>
+//   report:<date>            /v1/reports/<date>
+//   alert:<key>@<raised_at>  the incident that grouped it, else /v1/alerts
+//
+// Incidents are re-indexed as their events arrive; reports and alerts have
+// no events and are picked up by the periodic sync. Unchanged text is not
+// re-embedded, so syncing often is cheap.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryKind {
+    Incident,
+    Report,
+    Alert,
+}
+
+impl HistoryKind {
+    fn prefix(&self) -> &'static str {
+        match self {
+            HistoryKind::Incident => "incident",
+            HistoryKind::Report => "report",
+            HistoryKind::Alert => "alert",
+        }
+    }
+
+    fn of(document_id: &str) -> Option<(HistoryKind, &str)> {
+        let (prefix, id) = document_id.split_once(':')?;
+        let kind = match prefix {
+            "incident" => HistoryKind::Incident,
+            "report" => HistoryKind::Report,
+            "alert" => HistoryKind::Alert,
+            _ => return None,
+        };
+        Some((kind, id))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    pub sync_interval_secs: Option<u64>,
+    // Fusion settings for similar(); top_k is the default result count
+    pub retrieval: HybridConfig,
+    pub index_alerts: bool,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            sync_interval_secs: Some(300),
+            retrieval: HybridConfig { candidates: 50, ..Default::default() },
+            index_alerts: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PriorRecord {
+    pub kind: HistoryKind,
+    pub id: String,
+    pub title: String,
+    // API path of the record, for the agent to cite
+    pub link: String,
+    pub excerpt: String,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncStats {
+    pub incidents: usize,
+    pub reports: usize,
+    pub alerts: usize,
+    pub embedded: usize,
+}
+
+const EXCERPT_CHARS: usize = 400;
+
+fn status_name(status: IncidentStatus) -> &'static str {
+    match status {
+        IncidentStatus::Open => "open",
+        IncidentStatus::Acknowledged => "acknowledged",
+        IncidentStatus::Resolved => "resolved",
+    }
+}
+
+fn record(kind: HistoryKind, id: &str, title: &str, link: String, text: String) -> DocumentChunk {
+    let document_id = format!("{}:{}", kind.prefix(), id);
+    DocumentChunk {
+        id: format!("{}#0", document_id),
+        document_id,
+        text,
+        metadata: ChunkMetadata {
+            source: kind.prefix().to_string(),
+            filename: link,
+            page: None,
+            section: Some(title.to_string()),
+            chunk_index: 0,
+            owner: None,
+            tenant: None,
+        },
+    }
+}
+
+// Only fields that describe what happened; counters and timestamps that
+// move on every grouped alert would force a re-embed each time
+pub fn incident_record(incident: &Incident) -> DocumentChunk {
+    let mut text = format!(
+        "Incident: {}\nSeverity: {}\nStatus: {}\nOpened: {}",
+        incident.title,
+        incident.severity,
+        status_name(incident.status),
+        incident.created_at.to_rfc3339(),
+    );
+    if let Some(resolved_at) = incident.resolved_at {
+        text.push_str(&format!("\nResolved: {}", resolved_at.to_rfc3339()));
+    }
+    if let Some(assignee) = &incident.assignee {
+        text.push_str(&format!("\nAssignee: {}", assignee));
+    }
+    if !incident.alerts.is_empty() {
+        text.push_str(&format!("\nAlerts: {}", incident.alerts.join(", ")));
+    }
+    for note in &incident.notes {
+        text.push_str(&format!("\nNote from {}: {}", note.author, note.text));
+    }
+    for clip in &incident.evidence {
+        text.push_str(&format!("\nEvidence from {}", clip.stream_id));
+        if let Some(description) = &clip.description {
+            text.push_str(&format!(": {}", description));
+        }
+    }
+    record(
+        HistoryKind::Incident,
+        &incident.id,
+        &incident.title,
+        format!("/v1/incidents/{}", incident.id),
+        text,
+    )
+}
+
+pub fn report_record(report: &Report) -> DocumentChunk {
+    let date = report.date.to_string();
+    record(
+        HistoryKind::Report,
+        &date,
+        &format!("Daily report {}", date),
+        format!("/v1/reports/{}", date),
+        format!("Daily report for {}\n{}", date, report.text),
+    )
+}
+
+pub fn alert_record(alert: &Alert, incident_id: Option<&str>) -> DocumentChunk {
+    let mut labels: Vec<_> = alert.labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    labels.sort();
+    let mut text = format!(
+        "Alert: {}\nSeverity: {}\nRaised: {}\n{}",
+        alert.name,
+        alert.severity,
+        alert.raised_at.to_rfc3339(),
+        alert.message,
+    );
+    if !labels.is_empty() {
+        text.push_str(&format!("\nLabels: {}", labels.join(", ")));
+    }
+    if let Some(resolved_at) = alert.resolved_at {
+        text.push_str(&format!("\nResolved: {}", resolved_at.to_rfc3339()));
+    }
+    let link = match incident_id {
+        Some(id) => format!("/v1/incidents/{}", id),
+        None => "/v1/alerts".to_string(),
+    };
+    record(
+        HistoryKind::Alert,
+        &format!("{}@{}", alert.key, alert.raised_at.timestamp()),
+        &alert.name,
+        link,
+        text,
+    )
+}
+
+fn excerpt(text: &str) -> String {
+    match text.char_indices().nth(EXCERPT_CHARS) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+pub struct HistoryIndexer {
+    config: HistoryConfig,
+    indexer: Indexer,
+    store: Arc<dyn VectorStore>,
+    embedder: Arc<dyn Embedder>,
+    keywords: Arc<KeywordIndex>,
+    incidents: Arc<IncidentManager>,
+    reports: Option<Arc<ReportGenerator>>,
+    alerts: Option<Arc<AlertManager>>,
+}
+
+impl HistoryIndexer {
+    pub fn new(
+        config: HistoryConfig,
+        store: Arc<dyn VectorStore>,
+        embedder: Arc<dyn Embedder>,
+        keywords: Arc<KeywordIndex>,
+        incidents: Arc<IncidentManager>,
+    ) -> Self {
+        Self {
+            config,
+            indexer: Indexer::new(store.clone(), embedder.clone()),
+            store,
+            embedder,
+            keywords,
+            incidents,
+            reports: None,
+            alerts: None,
+        }
+    }
+
+    pub fn with_reports(mut self, reports: Arc<ReportGenerator>) -> Self {
+        self.reports = Some(reports);
+        self
+    }
+
+    pub fn with_alerts(mut self, alerts: Arc<AlertManager>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    async fn index(&self, chunk: DocumentChunk) -> Result<usize> {
+        let document_id = chunk.document_id.clone();
+        self.keywords.upsert(std::slice::from_ref(&chunk)).await;
+        let stats = self.indexer.index_document(&document_id, vec![chunk]).await
+            .with_context(|| format!("Failed to index {}", document_id))?;
+        Ok(stats.embedded)
+    }
+
+    // Incidents that were pruned from the manager stay indexed; keeping
+    // them findable is the point
+    pub async fn index_incident(&self, id: &str) -> Result<usize> {
+        match self.incidents.get(id).await {
+            Some(incident) => self.index(incident_record(&incident)).await,
+            None => Ok(0),
+        }
+    }
+
+    pub async fn sync(&self) -> Result<SyncStats> {
+        let mut stats = SyncStats::default();
+
+        let filter = IncidentFilter { limit: Some(usize::MAX), ..Default::default() };
+        let incidents = self.incidents.list(&filter).await;
+        for incident in &incidents {
+            stats.embedded += self.index(incident_record(incident)).await?;
+            stats.incidents += 1;
+        }
+
+        if let Some(reports) = &self.reports {
+            for report in reports.list().await {
+                stats.embedded += self.index(report_record(&report)).await?;
+                stats.reports += 1;
+            }
+        }
+
+        if let (Some(alerts), true) = (&self.alerts, self.config.index_alerts) {
+            for alert in alerts.history().await {
+                let incident = incidents.iter()
+                    .find(|i| i.alerts.contains(&alert.key) && i.created_at <= alert.last_seen)
+                    .map(|i| i.id.as_str());
+                stats.embedded += self.index(alert_record(&alert, incident)).await?;
+                stats.alerts += 1;
+            }
+        }
+        Ok(stats)
+    }
+
+    async fn on_event(&self, event: IncidentEvent) -> Result<()> {
+        match event {
+            IncidentEvent::Opened { incident } => {
+                self.index(incident_record(&incident)).await?;
+            }
+            IncidentEvent::AlertGrouped { incident_id, .. }
+            | IncidentEvent::StatusChanged { incident_id, .. }
+            | IncidentEvent::Assigned { incident_id, .. }
+            | IncidentEvent::NoteAdded { incident_id, .. }
+            | IncidentEvent::EvidenceAttached { incident_id, .. } => {
+                self.index_incident(&incident_id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // Follows incident events and runs the periodic sync. A subscriber that
+    // falls behind catches up with a full sync.
+    pub fn start(self: &Arc<Self>) {
+        let indexer = self.clone();
+        let mut events = self.incidents.subscribe();
+        tokio::spawn(async move {
+            if let Err(e) = indexer.sync().await {
+                log::warn!("Initial history sync failed: {}", e);
+            }
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        if let Err(e) = indexer.on_event(event).await {
+                            log::warn!("Failed to index incident event: {}", e);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::warn!("History indexer missed {} incident events, resyncing", skipped);
+                        if let Err(e) = indexer.sync().await {
+                            log::warn!("History resync failed: {}", e);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        let Some(interval) = self.config.sync_interval_secs else {
+            return;
+        };
+        let indexer = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(std::time::Duration::from_secs(interval.max(1)));
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = indexer.sync().await {
+                    log::warn!("History sync failed: {}", e);
+                }
+            }
+        });
+    }
+
+    // Past records most like `query`, best first, at most one result per
+    // record. `exclude` drops a record by id, typically the incident the
+    // question is about.
+    pub async fn similar(
+        &self,
+        query: &str,
+        kinds: &[HistoryKind],
+        limit: Option<usize>,
+        exclude: Option<&str>,
+    ) -> Result<Vec<PriorRecord>> {
+        let config = &self.config.retrieval;
+        let embedding = self.embedder.embed(&[query.to_string()]).await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Embedder returned no vector for query"))?;
+        let limit = limit.unwrap_or(config.top_k);
+        let total = self.store.count().await?;
+
+        // The kind and exclude filters run after retrieval, so when they
+        // leave too few the search is widened until the store runs out
+        let mut candidates = config.candidates.max(limit).max(1);
+        loop {
+            let (vector, keyword) = tokio::join!(
+                self.store.search(&embedding, candidates),
+                self.keywords.search(query, candidates),
+            );
+            let records = prior_records(fuse(&vector?, &keyword, config), kinds, limit, exclude);
+            if records.len() >= limit || candidates >= total {
+                return Ok(records);
+            }
+            candidates = candidates.saturating_mul(4).min(total);
+        }
+    }
+}
+
+// The best `limit` history records among fused results, one per record
+fn prior_records(
+    results: Vec<ScoredChunk>,
+    kinds: &[HistoryKind],
+    limit: usize,
+    exclude: Option<&str>,
+) -> Vec<PriorRecord> {
+    let mut seen = HashSet::new();
+    let mut records = Vec::new();
+    for result in results {
+        let Some((kind, id)) = HistoryKind::of(&result.chunk.document_id) else {
+            continue;
+        };
+        if (!kinds.is_empty() && !kinds.contains(&kind)) || exclude == Some(id) {
+            continue;
+        }
+        if !seen.insert(result.chunk.document_id.clone()) {
+            continue;
+        }
+        records.push(PriorRecord {
+            kind,
+            id: id.to_string(),
+            title: result.chunk.metadata.section.clone().unwrap_or_default(),
+            link: result.chunk.metadata.filename.clone(),
+            excerpt: excerpt(&result.chunk.text),
+            score: result.score,
+        });
+        if records.len() >= limit {
+            break;
+        }
+    }
+    records
+}
+
+// Whether an incident record names `person` as assignee or note author,
+// matching the lines incident_record() writes
+fn names_person(text: &str, person: &str) -> bool {
+    let assignee = format!("Assignee: {}", person);
+    let author = format!("Note from {}: ", person);
+    text.lines().any(|line| line == assignee || line.starts_with(&author))
+}
+
+impl HistoryIndexer {
+    // Indexed incident records that name the person, including incidents
+    // already pruned from the manager
+    async fn records_naming(&self, person: &str) -> Result<Vec<DocumentChunk>> {
+        const PAGE: usize = 500;
+        let mut records = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = self.store.scan(offset, PAGE).await?;
+            let done = page.len() < PAGE;
+            offset += page.len();
+            records.extend(page.into_iter()
+                .map(|c| c.chunk)
+                .filter(|c| matches!(HistoryKind::of(&c.document_id), Some((HistoryKind::Incident, _))))
+                .filter(|c| names_person(&c.text, person)));
+            if done {
+                return Ok(records);
+            }
+        }
+    }
+}
+
+// Incident history holds people as assignees and note authors. Erasing an
+// identity takes them off live incidents and re-indexes those; records of
+// pruned incidents that still name them are dropped from the index.
+// Incidents aren't attributed to sessions or tenants.
+#[async_trait]
+impl DataStore for HistoryIndexer {
+    fn category(&self) -> DataCategory {
+        DataCategory::History
+    }
+
+    async fn export(&self, subject: &DataSubject) -> Result<Value> {
+        let DataSubject::Identity(person) = subject else {
+            return Ok(Value::Array(Vec::new()));
+        };
+        let records: Vec<Value> = self.records_naming(person).await?
+            .into_iter()
+            .map(|c| json!({ "record": c.document_id, "link": c.metadata.filename, "text": c.text }))
+            .collect();
+        Ok(Value::Array(records))
+    }
+
+    async fn delete(&self, subject: &DataSubject) -> Result<u64> {
+        let DataSubject::Identity(person) = subject else {
+            return Ok(0);
+        };
+        let changed = self.incidents.erase_person(person).await;
+        for incident in &changed {
+            self.index(incident_record(incident)).await?;
+        }
+
+        let stale: Vec<String> = self.records_naming(person).await?
+            .into_iter()
+            .map(|c| c.id)
+            .collect();
+        self.store.delete(&stale).await?;
+        self.keywords.remove(&stale).await;
+        Ok((changed.len() + stale.len()) as u64)
+    }
+}
+
+pub struct PriorIncidentsTool {
+    history: Arc<HistoryIndexer>,
+}
+
+impl PriorIncidentsTool {
+    pub fn new(history: Arc<HistoryIndexer>) -> Self {
+        Self { history }
+    }
+}
+
+#[async_trait]
+impl Tool for PriorIncidentsTool {
+    fn name(&self) -> String {
+        "search_prior_incidents".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Searches past incidents, daily reports and alert history for records similar to a \
+         description, to answer whether something has happened before. Each result has a \
+         link; cite it when referring to the record. Pass exclude_id to leave out the incident \
+         being discussed.".to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string" },
+                "kinds": {
+                    "type": "array",
+                    "items": { "type": "string", "enum": ["incident", "report", "alert"] }
+                },
+                "exclude_id": { "type": "string" },
+                "limit": { "type": "integer", "minimum": 1, "maximum": 20 }
+            },
+            "required": ["query"]
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<Value> {
+        #[derive(Deserialize)]
+        struct Args {
+            query: String,
+            #[serde(default)]
+            kinds: Vec<HistoryKind>,
+            exclude_id: Option<String>,
+            limit: Option<usize>,
+        }
+        let args: Args = serde_json::from_value(args).context("Invalid search_prior_incidents arguments")?;
+        let limit = args.limit.map(|l| l.clamp(1, 20));
+        let records = self.history
+            .similar(&args.query, &args.kinds, limit, args.exclude_id.as_deref())
+            .await?;
+        Ok(json!({ "query": args.query, "results": records }))
+    }
+}
@@ -0,0 +1,353 @@
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use crate::core::llm::{LLMTrait, types::Message};
+use crate::core::rag::ingest::DocumentChunk;
+use crate::core::rag::store::{Embedder, ScoredChunk, VectorStore};
+use crate::utils::egress::EgressClient;
+
+// Keeps identifiers like "ERR-1042" or "cam_03" intact as one token, and
+// also indexes their parts so partial lookups still match
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for word in text.split(|c: char| c.is_whitespace() || ",;:!?()[]{}\"'".contains(c)) {
+        let word = word.trim_matches(|c: char| c == '.' || c == '-' || c == '_').to_lowercase();
+        if word.is_empty() {
+            continue;
+        }
+        if word.contains(['-', '_', '.', '/']) {
+            tokens.extend(
+                word.split(['-', '_', '.', '/'])
+                    .filter(|p| !p.is_empty())
+                    .map(str::to_string)
+            );
+        }
+        tokens.push(word);
+    }
+    tokens
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bm25Params {
+    pub k1: f32,
+    pub b: f32,
+}
+
+impl Default for Bm25Params {
+    fn default() -> Self {
+        Self { k1: 1.2, b: 0.75 }
+    }
+}
+
+#[derive(Default)]
+struct KeywordState {
+    chunks: HashMap<String, DocumentChunk>,
+    term_freqs: HashMap<String, HashMap<String, u32>>,
+    doc_lengths: HashMap<String, usize>,
+    doc_freqs: HashMap<String, usize>,
+    total_length: usize,
+}
+
+impl KeywordState {
+    fn remove(&mut self, chunk_id: &str) {
+        if let Some(freqs) = self.term_freqs.remove(chunk_id) {
+            for term in freqs.keys() {
+                if let Some(df) = self.doc_freqs.get_mut(term) {
+                    *df -= 1;
+                    if *df == 0 {
+                        self.doc_freqs.remove(term);
+                    }
+                }
+            }
+        }
+        if let Some(length) = self.doc_lengths.remove(chunk_id) {
+            self.total_length -= length;
+        }
+        self.chunks.remove(chunk_id);
+    }
+}
+
+pub struct KeywordIndex {
+    params: Bm25Params,
+    state: RwLock<KeywordState>,
+}
+
+impl KeywordIndex {
+    pub fn new(params: Bm25Params) -> Self {
+        Self {
+            params,
+            state: RwLock::new(KeywordState::default()),
+        }
+    }
+
+    pub async fn upsert(&self, chunks: &[DocumentChunk]) {
+        let mut state = self.state.write().await;
+        for chunk in chunks {
+            state.remove(&chunk.id);
+
+            let tokens = tokenize(&chunk.text);
+            let mut freqs: HashMap<String, u32> = HashMap::new();
+            for token in &tokens {
+                *freqs.entry(token.clone()).or_insert(0) += 1;
+            }
+            for term in freqs.keys() {
+                *state.doc_freqs.entry(term.clone()).or_insert(0) += 1;
+            }
+
+            state.total_length += tokens.len();
+            state.doc_lengths.insert(chunk.id.clone(), tokens.len());
+            state.term_freqs.insert(chunk.id.clone(), freqs);
+            state.chunks.insert(chunk.id.clone(), chunk.clone());
+        }
+    }
+
+    pub async fn remove(&self, chunk_ids: &[String]) {
+        let mut state = self.state.write().await;
+        for id in chunk_ids {
+            state.remove(id);
+        }
+    }
+
+    pub async fn search(&self, query: &str, limit: usize) -> Vec<ScoredChunk> {
+        let state = self.state.read().await;
+        let count = state.chunks.len() as f32;
+        if count == 0.0 {
+            return Vec::new();
+        }
+        let avg_length = state.total_length as f32 / count;
+        let terms = tokenize(query);
+
+        let mut scored: Vec<ScoredChunk> = state.term_freqs.iter()
+            .filter_map(|(id, freqs)| {
+                let length = state.doc_lengths[id] as f32;
+                let score: f32 = terms.iter()
+                    .filter_map(|term| {
+                        let tf = *freqs.get(term)? as f32;
+                        let df = state.doc_freqs[term] as f32;
+                        let idf = ((count - df + 0.5) / (df + 0.5) + 1.0).ln();
+                        let norm = self.params.k1 * (1.0 - self.params.b + self.params.b * length / avg_length);
+                        Some(idf * tf * (self.params.k1 + 1.0) / (tf + norm))
+                    })
+                    .sum();
+                (score > 0.0).then(|| ScoredChunk { chunk: state.chunks[id].clone(), score })
+            })
+            .collect();
+        scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+        scored.truncate(limit);
+        scored
+    }
+}
+
+#[async_trait]
+pub trait Reranker: Send + Sync {
+    async fn rerank(&self, query: &str, candidates: Vec<ScoredChunk>) -> Result<Vec<ScoredChunk>>;
+}
+
+// Talks to a cross-encoder service that returns one relevance score per document
+pub struct CrossEncoderReranker {
+    url: String,
+    client: EgressClient,
+}
+
+impl CrossEncoderReranker {
+    pub fn new(url: &str, client: EgressClient) -> Self {
+        Self { url: url.to_string(), client }
+    }
+}
+
+#[derive(Deserialize)]
+struct CrossEncoderResponse {
+    scores: Vec<f32>,
+}
+
+#[async_trait]
+impl Reranker for CrossEncoderReranker {
+    async fn rerank(&self, query: &str, mut candidates: Vec<ScoredChunk>) -> Result<Vec<ScoredChunk>> {
+        let documents: Vec<&str> = candidates.iter().map(|c| c.chunk.text.as_str()).collect();
+        let response: CrossEncoderResponse = self.client.post(&self.url)?
+            .json(&serde_json::json!({ "query": query, "documents": documents }))
+            .send()
+            .await
+            .context("Failed to call reranker")?
+            .error_for_status()
+            .context("Reranker rejected request")?
+            .json()
+            .await
+            .context("Failed to parse reranker response")?;
+
+        if response.scores.len() != candidates.len() {
+            return Err(anyhow::anyhow!("Reranker returned {} scores for {} documents", response.scores.len(), candidates.len()));
+        }
+        for (candidate, score) in candidates.iter_mut().zip(response.scores) {
+            candidate.score = score;
+        }
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(candidates)
+    }
+}
+
+pub struct LlmReranker {
+    llm: Arc<dyn LLMTrait>,
+}
+
+impl LlmReranker {
+    pub fn new(llm: Arc<dyn LLMTrait>) -> Self {
+        Self { llm }
+    }
+}
+
+#[async_trait]
+impl Reranker for LlmReranker {
+    async fn rerank(&self, query: &str, mut candidates: Vec<ScoredChunk>) -> Result<Vec<ScoredChunk>> {
+        let mut listing = String::new();
+        for (i, candidate) in candidates.iter().enumerate() {
+            listing.push_str(&format!("[{}] {}\n\n", i, candidate.chunk.text));
+        }
+
+        let response = self.llm.complete(vec![
+            Message::new(
+                "system",
+                "Rate how relevant each passage is to the query from 0 to 10. \
+                 Reply with a JSON array of numbers, one per passage, in order.",
+            ),
+            Message::new("user", &format!("Query: {}\n\nPassages:\n{}", query, listing)),
+        ]).await.context("LLM rerank request failed")?;
+
+        let content = response.content.trim();
+        let json = match (content.find('['), content.rfind(']')) {
+            (Some(start), Some(end)) if end > start => &content[start..=end],
+            _ => content,
+        };
+        let scores: Vec<f32> = serde_json::from_str(json).context("Failed to parse rerank scores")?;
+        if scores.len() != candidates.len() {
+            return Err(anyhow::anyhow!("LLM returned {} scores for {} passages", scores.len(), candidates.len()));
+        }
+
+        for (candidate, score) in candidates.iter_mut().zip(scores) {
+            candidate.score = score / 10.0;
+        }
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(candidates)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RerankerKind {
+    None,
+    Llm,
+    CrossEncoder { url: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HybridConfig {
+    pub vector_weight: f32,
+    pub keyword_weight: f32,
+    // Results fetched from each retriever before fusion
+    pub candidates: usize,
+    pub top_k: usize,
+    pub rrf_k: f32,
+    pub reranker: RerankerKind,
+}
+
+impl Default for HybridConfig {
+    fn default() -> Self {
+        Self {
+            vector_weight: 1.0,
+            keyword_weight: 1.0,
+            candidates: 30,
+            top_k: 5,
+            rrf_k: 60.0,
+            reranker: RerankerKind::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetrievalConfig {
+    pub default: HybridConfig,
+    #[serde(default)]
+    pub agents: HashMap<String, HybridConfig>,
+}
+
+// Weighted reciprocal rank fusion; robust to the two retrievers having
+// incomparable score scales
+pub fn fuse(vector: &[ScoredChunk], keyword: &[ScoredChunk], config: &HybridConfig) -> Vec<ScoredChunk> {
+    let mut fused: HashMap<String, ScoredChunk> = HashMap::new();
+
+    for (results, weight) in [(vector, config.vector_weight), (keyword, config.keyword_weight)] {
+        for (rank, result) in results.iter().enumerate() {
+            let contribution = weight / (config.rrf_k + rank as f32 + 1.0);
+            fused.entry(result.chunk.id.clone())
+                .or_insert_with(|| ScoredChunk { chunk: result.chunk.clone(), score: 0.0 })
+                .score += contribution;
+        }
+    }
+
+    let mut fused: Vec<_> = fused.into_values().collect();
+    fused.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.chunk.id.cmp(&b.chunk.id)));
+    fused
+}
+
+pub struct HybridRetriever {
+    config: RetrievalConfig,
+    store: Arc<dyn VectorStore>,
+    embedder: Arc<dyn Embedder>,
+    keywords: Arc<KeywordIndex>,
+    llm_reranker: Option<Arc<dyn Reranker>>,
+    client: EgressClient,
+}
+
+impl HybridRetriever {
+    pub fn new(
+        config: RetrievalConfig,
+        store: Arc<dyn VectorStore>,
+        embedder: Arc<dyn Embedder>,
+        keywords: Arc<KeywordIndex>,
+        llm_reranker: Option<Arc<dyn Reranker>>,
+        client: EgressClient,
+    ) -> Self {
+        Self { config, store, embedder, keywords, llm_reranker, client }
+    }
+
+    pub fn config_for(&self, agent: &str) -> &HybridConfig {
+        self.config.agents.get(agent).unwrap_or(&self.config.default)
+    }
+
+    pub async fn retrieve(&self, agent: &str, query: &str) -> Result<Vec<ScoredChunk>> {
+        let config = self.config_for(agent);
+
+        let embedding = self.embedder.embed(&[query.to_string()]).await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Embedder returned no vector for query"))?;
+        let (vector, keyword) = tokio::join!(
+            self.store.search(&embedding, config.candidates),
+            self.keywords.search(query, config.candidates),
+        );
+
+        let mut results = fuse(&vector?, &keyword, config);
+        results.truncate(config.candidates);
+
+        let reranker: Option<Arc<dyn Reranker>> = match &config.reranker {
+            RerankerKind::None => None,
+            RerankerKind::Llm => self.llm_reranker.clone(),
+            RerankerKind::CrossEncoder { url } => Some(Arc::new(CrossEncoderReranker::new(url, self.client.clone()))),
+        };
+
+        if let Some(reranker) = reranker {
+            match reranker.rerank(query, results.clone()).await {
+                Ok(reranked) => results = reranked,
+                // Fused order is still a reasonable answer
+                Err(e) => log::warn!("Reranking failed, using fused order: {}", e),
+            }
+        }
+
+        results.truncate(config.top_k);
+        Ok(results)
+    }
+}
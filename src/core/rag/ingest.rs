@@ -0,0 +1,363 @@
+use std::io::{Cursor, Read};
+use std::sync::Arc;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use quick_xml::events::Event as XmlEvent;
+
+use crate::core::storage::{FileKind, FileStore};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockKind {
+    Heading,
+    Paragraph,
+    ListItem,
+    Code,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParsedBlock {
+    pub kind: BlockKind,
+    pub text: String,
+    pub page: Option<u32>,
+    // Heading level, only set for headings
+    pub level: Option<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMetadata {
+    pub source: String,
+    pub filename: String,
+    pub page: Option<u32>,
+    pub section: Option<String>,
+    pub chunk_index: usize,
+    // Who uploaded the document, for data subject requests
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub tenant: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentChunk {
+    pub id: String,
+    pub document_id: String,
+    pub text: String,
+    pub metadata: ChunkMetadata,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestConfig {
+    pub max_chunk_chars: usize,
+    pub overlap_chars: usize,
+    // Tiny trailing chunks are merged into the previous one
+    pub min_chunk_chars: usize,
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self {
+            max_chunk_chars: 1500,
+            overlap_chars: 150,
+            min_chunk_chars: 200,
+        }
+    }
+}
+
+pub fn parse_pdf(data: &[u8]) -> Result<Vec<ParsedBlock>> {
+    let pages = pdf_extract::extract_text_from_mem_by_pages(data)
+        .context("Failed to extract PDF text")?;
+
+    let mut blocks = Vec::new();
+    for (index, page) in pages.iter().enumerate() {
+        for paragraph in split_paragraphs(page) {
+            blocks.push(ParsedBlock {
+                kind: guess_plain_kind(&paragraph),
+                level: None,
+                text: paragraph,
+                page: Some(index as u32 + 1),
+            });
+        }
+    }
+    Ok(blocks)
+}
+
+pub fn parse_docx(data: &[u8]) -> Result<Vec<ParsedBlock>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))
+        .context("Failed to open docx archive")?;
+    let mut xml = String::new();
+    archive.by_name("word/document.xml")
+        .context("docx is missing word/document.xml")?
+        .read_to_string(&mut xml)?;
+
+    let mut reader = quick_xml::Reader::from_str(&xml);
+    let mut blocks = Vec::new();
+    let mut text = String::new();
+    let mut style: Option<String> = None;
+    let mut in_text = false;
+
+    loop {
+        match reader.read_event().context("Failed to parse docx XML")? {
+            XmlEvent::Start(e) | XmlEvent::Empty(e) => match e.name().as_ref() {
+                b"w:t" => in_text = true,
+                b"w:tab" => text.push('\t'),
+                b"w:br" => text.push('\n'),
+                b"w:pStyle" => {
+                    style = e.try_get_attribute("w:val")?
+                        .map(|a| String::from_utf8_lossy(&a.value).to_string());
+                }
+                _ => {}
+            },
+            XmlEvent::Text(e) if in_text => text.push_str(&e.unescape()?),
+            XmlEvent::End(e) => match e.name().as_ref() {
+                b"w:t" => in_text = false,
+                b"w:p" => {
+                    let paragraph = text.trim().to_string();
+                    if !paragraph.is_empty() {
+                        let (kind, level) = docx_style_kind(style.as_deref());
+                        blocks.push(ParsedBlock { kind, text: paragraph, page: None, level });
+                    }
+                    text.clear();
+                    style = None;
+                }
+                _ => {}
+            },
+            XmlEvent::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(blocks)
+}
+
+fn docx_style_kind(style: Option<&str>) -> (BlockKind, Option<u8>) {
+    match style {
+        Some(s) if s.starts_with("Heading") => {
+            let level = s.trim_start_matches("Heading").parse().unwrap_or(1);
+            (BlockKind::Heading, Some(level))
+        }
+        Some("Title") => (BlockKind::Heading, Some(1)),
+        Some(s) if s.starts_with("List") => (BlockKind::ListItem, None),
+        _ => (BlockKind::Paragraph, None),
+    }
+}
+
+pub fn parse_html(html: &str) -> Result<Vec<ParsedBlock>> {
+    let document = scraper::Html::parse_document(html);
+    let selector = scraper::Selector::parse("h1, h2, h3, h4, h5, h6, p, li, pre, td, th")
+        .map_err(|e| anyhow::anyhow!("Invalid selector: {:?}", e))?;
+
+    let mut blocks = Vec::new();
+    for element in document.select(&selector) {
+        let text = element.text().collect::<Vec<_>>().join(" ");
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if text.is_empty() {
+            continue;
+        }
+
+        let tag = element.value().name();
+        let (kind, level) = match tag {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => (BlockKind::Heading, tag[1..].parse().ok()),
+            "li" => (BlockKind::ListItem, None),
+            "pre" => (BlockKind::Code, None),
+            _ => (BlockKind::Paragraph, None),
+        };
+        blocks.push(ParsedBlock { kind, text, page: None, level });
+    }
+
+    Ok(blocks)
+}
+
+pub fn parse_text(text: &str) -> Vec<ParsedBlock> {
+    split_paragraphs(text).into_iter()
+        .map(|paragraph| {
+            // Markdown headings keep their structure
+            let hashes = paragraph.chars().take_while(|c| *c == '#').count();
+            if (1..=6).contains(&hashes) && paragraph[hashes..].starts_with(' ') {
+                ParsedBlock {
+                    kind: BlockKind::Heading,
+                    text: paragraph[hashes..].trim().to_string(),
+                    page: None,
+                    level: Some(hashes as u8),
+                }
+            } else {
+                ParsedBlock { kind: guess_plain_kind(&paragraph), text: paragraph, page: None, level: None }
+            }
+        })
+        .collect()
+}
+
+fn split_paragraphs(text: &str) -> Vec<String> {
+    text.split("\n\n")
+        .map(|p| p.lines().map(str::trim).collect::<Vec<_>>().join(" "))
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+fn guess_plain_kind(paragraph: &str) -> BlockKind {
+    if paragraph.starts_with("- ") || paragraph.starts_with("* ") || paragraph.starts_with("• ") {
+        BlockKind::ListItem
+    } else {
+        BlockKind::Paragraph
+    }
+}
+
+// Groups blocks into chunks that never straddle a section or page boundary,
+// so each chunk's page/section metadata is accurate for citations
+pub fn chunk_blocks(
+    blocks: &[ParsedBlock],
+    document_id: &str,
+    source: &str,
+    filename: &str,
+    config: &IngestConfig,
+) -> Vec<DocumentChunk> {
+    let mut chunks: Vec<DocumentChunk> = Vec::new();
+    let mut headings: Vec<(u8, String)> = Vec::new();
+    let mut current = String::new();
+    let mut current_page: Option<u32> = None;
+    let mut current_section: Option<String> = None;
+
+    let flush = |text: &mut String, page: Option<u32>, section: &Option<String>, chunks: &mut Vec<DocumentChunk>| {
+        let body = text.trim();
+        if body.is_empty() {
+            text.clear();
+            return;
+        }
+
+        if let Some(last) = chunks.last_mut() {
+            let same_place = last.metadata.page == page && &last.metadata.section == section;
+            if same_place && body.len() < config.min_chunk_chars
+                && last.text.len() + body.len() <= config.max_chunk_chars
+            {
+                last.text.push_str("\n\n");
+                last.text.push_str(body);
+                text.clear();
+                return;
+            }
+        }
+
+        let index = chunks.len();
+        chunks.push(DocumentChunk {
+            id: format!("{}#{}", document_id, index),
+            document_id: document_id.to_string(),
+            text: body.to_string(),
+            metadata: ChunkMetadata {
+                source: source.to_string(),
+                filename: filename.to_string(),
+                page,
+                section: section.clone(),
+                chunk_index: index,
+                owner: None,
+                tenant: None,
+            },
+        });
+        text.clear();
+    };
+
+    for block in blocks {
+        if block.kind == BlockKind::Heading {
+            flush(&mut current, current_page, &current_section, &mut chunks);
+            let level = block.level.unwrap_or(1);
+            headings.retain(|(l, _)| *l < level);
+            headings.push((level, block.text.clone()));
+            current_section = Some(headings.iter().map(|(_, h)| h.as_str()).collect::<Vec<_>>().join(" > "));
+            continue;
+        }
+
+        if block.page != current_page {
+            flush(&mut current, current_page, &current_section, &mut chunks);
+            current_page = block.page;
+        }
+
+        for piece in split_long(&block.text, config.max_chunk_chars) {
+            if !current.is_empty() && current.len() + piece.len() + 2 > config.max_chunk_chars {
+                let overlap = tail(&current, config.overlap_chars).to_string();
+                flush(&mut current, current_page, &current_section, &mut chunks);
+                current.push_str(&overlap);
+            }
+            if !current.is_empty() {
+                current.push_str("\n\n");
+            }
+            current.push_str(&piece);
+        }
+    }
+    flush(&mut current, current_page, &current_section, &mut chunks);
+
+    chunks
+}
+
+// Splits an oversized block on sentence boundaries where possible
+fn split_long(text: &str, max_chars: usize) -> Vec<String> {
+    if text.len() <= max_chars {
+        return vec![text.to_string()];
+    }
+
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    for sentence in text.split_inclusive(['.', '!', '?']) {
+        if !current.is_empty() && current.len() + sentence.len() > max_chars {
+            pieces.push(current.trim().to_string());
+            current.clear();
+        }
+        if sentence.len() > max_chars {
+            let chars: Vec<char> = sentence.chars().collect();
+            for part in chars.chunks(max_chars) {
+                pieces.push(part.iter().collect::<String>().trim().to_string());
+            }
+            continue;
+        }
+        current.push_str(sentence);
+    }
+    if !current.trim().is_empty() {
+        pieces.push(current.trim().to_string());
+    }
+    pieces
+}
+
+fn tail(text: &str, max_chars: usize) -> &str {
+    if text.len() <= max_chars {
+        return text;
+    }
+    let mut start = text.len() - max_chars;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    // Start the overlap at a word boundary
+    match text[start..].find(char::is_whitespace) {
+        Some(offset) => text[start + offset..].trim_start(),
+        None => &text[start..],
+    }
+}
+
+pub struct DocumentIngestor {
+    config: IngestConfig,
+    files: Arc<FileStore>,
+}
+
+impl DocumentIngestor {
+    pub fn new(config: IngestConfig, files: Arc<FileStore>) -> Self {
+        Self { config, files }
+    }
+
+    pub async fn ingest_file(&self, file_id: &str) -> Result<Vec<DocumentChunk>> {
+        let (file, data) = self.files.read(file_id).await?;
+        let mime = file.content_type.split(';').next().unwrap_or("").trim().to_string();
+
+        let blocks = match (file.kind, mime.as_str()) {
+            (FileKind::Pdf, _) => parse_pdf(&data)?,
+            (FileKind::Document, "text/html") => parse_html(&String::from_utf8_lossy(&data))?,
+            (FileKind::Document, _) => parse_docx(&data)?,
+            (FileKind::Text, _) => parse_text(&String::from_utf8_lossy(&data)),
+            (kind, _) => return Err(anyhow::anyhow!("{:?} files cannot be ingested as documents", kind)),
+        };
+
+        let mut chunks = chunk_blocks(&blocks, &file.id, &format!("file:{}", file.id), &file.filename, &self.config);
+        for chunk in &mut chunks {
+            chunk.metadata.owner = file.owner.clone();
+            chunk.metadata.tenant = file.tenant.clone();
+        }
+        log::info!("Ingested {} into {} chunks", file.filename, chunks.len());
+        Ok(chunks)
+    }
+}
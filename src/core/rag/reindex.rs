@@ -0,0 +1,156 @@
+use std::sync::Arc;
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+
+use crate::core::jobs::{Job, JobHandle, JobManager};
+use crate::core::rag::store::{Embedder, IndexedChunk, VectorStore};
+
+pub const REEMBED_JOB: &str = "rag.reembed";
+pub const COMPACT_JOB: &str = "rag.compact";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReindexConfig {
+    pub batch_size: usize,
+    // How often to check for chunks embedded with an outdated model
+    pub model_check_interval_secs: Option<u64>,
+}
+
+impl Default for ReindexConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 64,
+            model_check_interval_secs: Some(3600),
+        }
+    }
+}
+
+pub struct Reindexer {
+    config: ReindexConfig,
+    store: Arc<dyn VectorStore>,
+    embedder: Arc<dyn Embedder>,
+    jobs: Arc<JobManager>,
+}
+
+impl Reindexer {
+    pub fn new(
+        config: ReindexConfig,
+        store: Arc<dyn VectorStore>,
+        embedder: Arc<dyn Embedder>,
+        jobs: Arc<JobManager>,
+    ) -> Self {
+        Self { config, store, embedder, jobs }
+    }
+
+    pub async fn stale_chunks(&self) -> Result<usize> {
+        let model = self.embedder.model_id();
+        let mut stale = 0;
+        let mut offset = 0;
+
+        loop {
+            let batch = self.store.scan(offset, self.config.batch_size).await?;
+            if batch.is_empty() {
+                return Ok(stale);
+            }
+            offset += batch.len();
+            stale += batch.iter().filter(|c| c.embedding_model != model).count();
+        }
+    }
+
+    pub async fn start_reembed(&self) -> Result<Job> {
+        let store = self.store.clone();
+        let embedder = self.embedder.clone();
+        let batch_size = self.config.batch_size.max(1);
+
+        self.jobs.spawn_exclusive(REEMBED_JOB, move |handle| async move {
+            reembed(store, embedder, handle, batch_size).await
+        }).await
+    }
+
+    pub async fn start_compaction(&self) -> Result<Job> {
+        let store = self.store.clone();
+        self.jobs.spawn_exclusive(COMPACT_JOB, move |handle| async move {
+            handle.set_message("Compacting vector index").await;
+            store.compact().await
+        }).await
+    }
+
+    pub fn start_model_watch(self: &Arc<Self>) {
+        let Some(interval_secs) = self.config.model_check_interval_secs else {
+            return;
+        };
+        let reindexer = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(
+                tokio::time::Duration::from_secs(interval_secs)
+            );
+
+            loop {
+                interval.tick().await;
+                match reindexer.stale_chunks().await {
+                    Ok(0) => {}
+                    Ok(stale) => {
+                        log::info!(
+                            "{} chunks use an outdated embedding model, starting re-embedding",
+                            stale
+                        );
+                        if let Err(e) = reindexer.start_reembed().await {
+                            log::warn!("Could not start re-embedding: {}", e);
+                        }
+                    }
+                    Err(e) => log::error!("Embedding model check failed: {}", e),
+                }
+            }
+        });
+    }
+}
+
+// Walks the store in id order and re-embeds chunks from other models.
+// Upserts keep chunk ids, so paging stays stable while the job runs.
+async fn reembed(
+    store: Arc<dyn VectorStore>,
+    embedder: Arc<dyn Embedder>,
+    handle: JobHandle,
+    batch_size: usize,
+) -> Result<()> {
+    let model = embedder.model_id();
+    handle.set_total(store.count().await? as u64).await;
+    handle.set_message(&format!("Re-embedding with {}", model)).await;
+
+    let mut offset = 0;
+    loop {
+        if handle.is_cancelled() {
+            log::info!("Re-embedding job {} cancelled at offset {}", handle.id(), offset);
+            return Ok(());
+        }
+
+        let batch = store.scan(offset, batch_size).await?;
+        if batch.is_empty() {
+            return Ok(());
+        }
+        offset += batch.len();
+        let scanned = batch.len() as u64;
+
+        let stale: Vec<IndexedChunk> = batch.into_iter()
+            .filter(|c| c.embedding_model != model)
+            .collect();
+        if !stale.is_empty() {
+            let texts: Vec<String> = stale.iter().map(|c| c.chunk.text.clone()).collect();
+            let embeddings = embedder.embed(&texts).await?;
+            if embeddings.len() != stale.len() {
+                return Err(anyhow::anyhow!("Embedder returned {} vectors for {} texts", embeddings.len(), stale.len()));
+            }
+
+            let updated = stale.into_iter().zip(embeddings)
+                .map(|(chunk, embedding)| IndexedChunk {
+                    embedding,
+                    embedding_model: model.clone(),
+                    ..chunk
+                })
+                .collect();
+            store.upsert(updated).await?;
+        }
+
+        handle.advance(scanned).await;
+    }
+}
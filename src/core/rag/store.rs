@@ -0,0 +1,273 @@
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+
+use crate::core::lifecycle::{DataCategory, DataStore, DataSubject};
+use crate::core::rag::hybrid::KeywordIndex;
+use crate::core::rag::ingest::DocumentChunk;
+
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    // Identifies model and version; chunks embedded with another id are stale
+    fn model_id(&self) -> String;
+    fn dimensions(&self) -> usize;
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedChunk {
+    pub chunk: DocumentChunk,
+    pub embedding: Vec<f32>,
+    pub embedding_model: String,
+    pub content_hash: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoredChunk {
+    pub chunk: DocumentChunk,
+    pub score: f32,
+}
+
+#[async_trait]
+pub trait VectorStore: Send + Sync {
+    async fn upsert(&self, chunks: Vec<IndexedChunk>) -> Result<()>;
+    async fn get(&self, chunk_id: &str) -> Result<Option<IndexedChunk>>;
+    async fn delete(&self, chunk_ids: &[String]) -> Result<()>;
+    async fn document_chunks(&self, document_id: &str) -> Result<Vec<IndexedChunk>>;
+    // Stable paging over every chunk, used by reindexing
+    async fn scan(&self, offset: usize, limit: usize) -> Result<Vec<IndexedChunk>>;
+    async fn count(&self) -> Result<usize>;
+    async fn search(&self, embedding: &[f32], limit: usize) -> Result<Vec<ScoredChunk>>;
+    // Reclaims space from deleted entries; a no-op for stores that don't need it
+    async fn compact(&self) -> Result<()>;
+}
+
+pub fn content_hash(text: &str) -> String {
+    hex::encode(Sha256::digest(text.as_bytes()))
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+#[derive(Default)]
+pub struct InMemoryVectorStore {
+    chunks: Arc<RwLock<HashMap<String, IndexedChunk>>>,
+}
+
+impl InMemoryVectorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl VectorStore for InMemoryVectorStore {
+    async fn upsert(&self, chunks: Vec<IndexedChunk>) -> Result<()> {
+        let mut store = self.chunks.write().await;
+        for chunk in chunks {
+            store.insert(chunk.chunk.id.clone(), chunk);
+        }
+        Ok(())
+    }
+
+    async fn get(&self, chunk_id: &str) -> Result<Option<IndexedChunk>> {
+        Ok(self.chunks.read().await.get(chunk_id).cloned())
+    }
+
+    async fn delete(&self, chunk_ids: &[String]) -> Result<()> {
+        let mut store = self.chunks.write().await;
+        for id in chunk_ids {
+            store.remove(id);
+        }
+        Ok(())
+    }
+
+    async fn document_chunks(&self, document_id: &str) -> Result<Vec<IndexedChunk>> {
+        Ok(self.chunks.read().await.values()
+            .filter(|c| c.chunk.document_id == document_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn scan(&self, offset: usize, limit: usize) -> Result<Vec<IndexedChunk>> {
+        let store = self.chunks.read().await;
+        let mut ids: Vec<&String> = store.keys().collect();
+        ids.sort();
+        Ok(ids.into_iter().skip(offset).take(limit).map(|id| store[id].clone()).collect())
+    }
+
+    async fn count(&self) -> Result<usize> {
+        Ok(self.chunks.read().await.len())
+    }
+
+    async fn search(&self, embedding: &[f32], limit: usize) -> Result<Vec<ScoredChunk>> {
+        let store = self.chunks.read().await;
+        let mut scored: Vec<ScoredChunk> = store.values()
+            .map(|c| ScoredChunk {
+                chunk: c.chunk.clone(),
+                score: cosine_similarity(embedding, &c.embedding),
+            })
+            .collect();
+        scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+        scored.truncate(limit);
+        Ok(scored)
+    }
+
+    async fn compact(&self) -> Result<()> {
+        self.chunks.write().await.shrink_to_fit();
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IndexStats {
+    pub embedded: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+pub struct Indexer {
+    store: Arc<dyn VectorStore>,
+    embedder: Arc<dyn Embedder>,
+}
+
+impl Indexer {
+    pub fn new(store: Arc<dyn VectorStore>, embedder: Arc<dyn Embedder>) -> Self {
+        Self { store, embedder }
+    }
+
+    // Re-embeds only chunks whose text or embedding model changed and drops
+    // chunks that no longer exist in the document
+    pub async fn index_document(&self, document_id: &str, chunks: Vec<DocumentChunk>) -> Result<IndexStats> {
+        let model = self.embedder.model_id();
+        let existing: HashMap<String, IndexedChunk> = self.store.document_chunks(document_id).await?
+            .into_iter()
+            .map(|c| (c.chunk.id.clone(), c))
+            .collect();
+
+        let mut stats = IndexStats::default();
+        let mut to_embed = Vec::new();
+        let mut unchanged = Vec::new();
+        for chunk in chunks {
+            let hash = content_hash(&chunk.text);
+            match existing.get(&chunk.id) {
+                Some(current) if current.content_hash == hash && current.embedding_model == model => {
+                    // Metadata may still have moved, e.g. a new section title
+                    unchanged.push(IndexedChunk { chunk, ..current.clone() });
+                    stats.unchanged += 1;
+                }
+                _ => to_embed.push((chunk, hash)),
+            }
+        }
+
+        let live: std::collections::HashSet<String> = to_embed.iter().map(|(c, _)| c.id.clone())
+            .chain(unchanged.iter().map(|c| c.chunk.id.clone()))
+            .collect();
+        let stale: Vec<String> = existing.keys().filter(|id| !live.contains(*id)).cloned().collect();
+
+        if !to_embed.is_empty() {
+            let texts: Vec<String> = to_embed.iter().map(|(c, _)| c.text.clone()).collect();
+            let embeddings = self.embedder.embed(&texts).await?;
+            if embeddings.len() != texts.len() {
+                return Err(anyhow::anyhow!("Embedder returned {} vectors for {} texts", embeddings.len(), texts.len()));
+            }
+
+            stats.embedded = to_embed.len();
+            let indexed = to_embed.into_iter().zip(embeddings)
+                .map(|((chunk, hash), embedding)| IndexedChunk {
+                    chunk,
+                    embedding,
+                    embedding_model: model.clone(),
+                    content_hash: hash,
+                })
+                .collect();
+            self.store.upsert(indexed).await?;
+        }
+        self.store.upsert(unchanged).await?;
+
+        stats.removed = stale.len();
+        self.store.delete(&stale).await?;
+
+        Ok(stats)
+    }
+}
+
+// Data subject requests against the RAG store: chunks of documents the
+// subject uploaded, found by the owner and tenant recorded at ingest.
+// Keyword entries for the same chunks go with them.
+pub struct StoredDocuments {
+    store: Arc<dyn VectorStore>,
+    keywords: Option<Arc<KeywordIndex>>,
+}
+
+const SCAN_PAGE: usize = 500;
+
+impl StoredDocuments {
+    pub fn new(store: Arc<dyn VectorStore>) -> Self {
+        Self { store, keywords: None }
+    }
+
+    pub fn with_keywords(mut self, keywords: Arc<KeywordIndex>) -> Self {
+        self.keywords = Some(keywords);
+        self
+    }
+
+    async fn subject_chunks(&self, subject: &DataSubject) -> Result<Vec<IndexedChunk>> {
+        let mut matched = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = self.store.scan(offset, SCAN_PAGE).await?;
+            let done = page.len() < SCAN_PAGE;
+            offset += page.len();
+            matched.extend(page.into_iter().filter(|c| {
+                let metadata = &c.chunk.metadata;
+                match subject {
+                    DataSubject::Session(_) => false,
+                    DataSubject::Identity(owner) => metadata.owner.as_ref() == Some(owner),
+                    DataSubject::Tenant(tenant) => metadata.tenant.as_ref() == Some(tenant),
+                }
+            }));
+            if done {
+                return Ok(matched);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl DataStore for StoredDocuments {
+    fn category(&self) -> DataCategory {
+        DataCategory::Embeddings
+    }
+
+    async fn export(&self, subject: &DataSubject) -> Result<serde_json::Value> {
+        let chunks: Vec<DocumentChunk> = self.subject_chunks(subject).await?
+            .into_iter()
+            .map(|c| c.chunk)
+            .collect();
+        Ok(serde_json::to_value(chunks)?)
+    }
+
+    async fn delete(&self, subject: &DataSubject) -> Result<u64> {
+        let ids: Vec<String> = self.subject_chunks(subject).await?
+            .into_iter()
+            .map(|c| c.chunk.id)
+            .collect();
+        self.store.delete(&ids).await?;
+        if let Some(keywords) = &self.keywords {
+            keywords.remove(&ids).await;
+        }
+        Ok(ids.len() as u64)
+    }
+}
@@ -0,0 +1,360 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::RwLock;
+use tokio::io::AsyncWriteExt;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::core::alerts::Alert;
+use crate::core::llm::cache::ResponseCache;
+use crate::core::notify::Severity;
+
+// Action fields may reference alert labels as "{label}", so one policy can
+// serve every stream: `{"action": "restart_stream", "stream_id": "{stream_id}"}`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RemediationAction {
+    RestartStream { stream_id: String },
+    ReloadModel { model: String },
+    ReduceFps { stream_id: String, factor: f32 },
+    ClearCache { cache: String },
+}
+
+impl RemediationAction {
+    fn render(&self, labels: &HashMap<String, String>) -> Result<Self> {
+        let fill = |template: &str| -> Result<String> {
+            let mut value = template.to_string();
+            for (key, label) in labels {
+                value = value.replace(&format!("{{{}}}", key), label);
+            }
+            if value.contains('{') {
+                return Err(anyhow::anyhow!("Unresolved placeholder in \"{}\"", template));
+            }
+            Ok(value)
+        };
+
+        Ok(match self {
+            Self::RestartStream { stream_id } => Self::RestartStream { stream_id: fill(stream_id)? },
+            Self::ReloadModel { model } => Self::ReloadModel { model: fill(model)? },
+            Self::ReduceFps { stream_id, factor } => Self::ReduceFps { stream_id: fill(stream_id)?, factor: *factor },
+            Self::ClearCache { cache } => Self::ClearCache { cache: fill(cache)? },
+        })
+    }
+
+    // The "action" tag, which handlers are registered under
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::RestartStream { .. } => "restart_stream",
+            Self::ReloadModel { .. } => "reload_model",
+            Self::ReduceFps { .. } => "reduce_fps",
+            Self::ClearCache { .. } => "clear_cache",
+        }
+    }
+
+    // Actions on the same target share a cool-down regardless of which
+    // policy fired them
+    fn target_key(&self) -> String {
+        let target = match self {
+            Self::RestartStream { stream_id } | Self::ReduceFps { stream_id, .. } => stream_id,
+            Self::ReloadModel { model } => model,
+            Self::ClearCache { cache } => cache,
+        };
+        format!("{}:{}", self.kind(), target)
+    }
+}
+
+// Implemented by whatever owns the streams, models and caches; the
+// remediator only decides what to run and when
+#[async_trait]
+pub trait RemediationExecutor: Send + Sync {
+    async fn execute(&self, action: &RemediationAction) -> Result<String>;
+}
+
+type ActionHandler = Arc<dyn Fn(RemediationAction) -> BoxFuture<'static, Result<String>> + Send + Sync>;
+
+// The executor the server runs remediations with. Whatever owns the
+// streams and models registers a handler per action kind; LLM response
+// caches are cleared directly. Actions nothing handles fail, so the audit
+// trail shows they didn't run.
+#[derive(Default)]
+pub struct ActionHandlers {
+    handlers: HashMap<&'static str, ActionHandler>,
+    caches: HashMap<String, Arc<ResponseCache>>,
+}
+
+impl ActionHandlers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_handler<F, Fut>(mut self, kind: &'static str, handler: F) -> Self
+    where
+        F: Fn(RemediationAction) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String>> + Send + 'static,
+    {
+        self.handlers.insert(kind, Arc::new(move |action| Box::pin(handler(action))));
+        self
+    }
+
+    // `clear_cache` actions naming `name` empty this cache
+    pub fn with_cache(mut self, name: &str, cache: Arc<ResponseCache>) -> Self {
+        self.caches.insert(name.to_string(), cache);
+        self
+    }
+}
+
+#[async_trait]
+impl RemediationExecutor for ActionHandlers {
+    async fn execute(&self, action: &RemediationAction) -> Result<String> {
+        if let RemediationAction::ClearCache { cache } = action {
+            if let Some(target) = self.caches.get(cache) {
+                target.clear();
+                return Ok(format!("Cleared cache {}", cache));
+            }
+        }
+        let handler = self.handlers.get(action.kind())
+            .ok_or_else(|| anyhow::anyhow!("Nothing handles {} here", action.kind()))?;
+        handler(action.clone()).await
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemediationPolicy {
+    pub name: String,
+    // Matched against the alert name; "*" matches every alert
+    #[serde(default)]
+    pub alert_name: Option<String>,
+    // Matched against the alert's rule_id label, for alerts raised by rules
+    #[serde(default)]
+    pub rule_id: Option<String>,
+    #[serde(default = "default_min_severity")]
+    pub min_severity: Severity,
+    pub actions: Vec<RemediationAction>,
+    #[serde(default = "default_cooldown")]
+    pub cooldown_secs: i64,
+}
+
+fn default_min_severity() -> Severity {
+    Severity::Warning
+}
+
+fn default_cooldown() -> i64 {
+    600
+}
+
+impl RemediationPolicy {
+    pub fn matches(&self, alert: &Alert) -> bool {
+        if alert.severity < self.min_severity {
+            return false;
+        }
+        let name_matches = self.alert_name.as_ref()
+            .map_or(true, |name| name == "*" || name == &alert.name);
+        let rule_matches = self.rule_id.as_ref()
+            .map_or(true, |id| alert.labels.get("rule_id") == Some(id));
+        // A policy with no selector at all would fire on everything by accident
+        (self.alert_name.is_some() || self.rule_id.is_some()) && name_matches && rule_matches
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemediationConfig {
+    pub enabled: bool,
+    // Log what would run without running it
+    pub dry_run: bool,
+    pub policies: Vec<RemediationPolicy>,
+    pub audit_file: Option<String>,
+    pub audit_size: usize,
+}
+
+impl Default for RemediationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dry_run: true,
+            policies: Vec::new(),
+            audit_file: None,
+            audit_size: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RemediationOutcome {
+    Executed { detail: String },
+    DryRun,
+    CoolingDown { until: DateTime<Utc> },
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemediationRecord {
+    pub timestamp: DateTime<Utc>,
+    pub policy: String,
+    pub alert_key: String,
+    pub alert_name: String,
+    pub action: RemediationAction,
+    pub outcome: RemediationOutcome,
+}
+
+pub struct Remediator {
+    config: RemediationConfig,
+    dry_run: AtomicBool,
+    executor: Arc<dyn RemediationExecutor>,
+    last_run: RwLock<HashMap<String, DateTime<Utc>>>,
+    audit: RwLock<Vec<RemediationRecord>>,
+}
+
+impl Remediator {
+    pub async fn new(config: RemediationConfig, executor: Arc<dyn RemediationExecutor>) -> Result<Self> {
+        let mut audit = Vec::new();
+        if let Some(path) = &config.audit_file {
+            if tokio::fs::try_exists(path).await? {
+                let contents = tokio::fs::read_to_string(path).await
+                    .context("Failed to read remediation audit log")?;
+                for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+                    audit.push(serde_json::from_str(line).context("Corrupt remediation audit entry")?);
+                }
+            }
+        }
+        let excess = audit.len().saturating_sub(config.audit_size);
+        audit.drain(..excess);
+
+        Ok(Self {
+            dry_run: AtomicBool::new(config.dry_run),
+            config,
+            executor,
+            last_run: RwLock::new(HashMap::new()),
+            audit: RwLock::new(audit),
+        })
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run.load(Ordering::Relaxed)
+    }
+
+    pub fn set_dry_run(&self, dry_run: bool) {
+        self.dry_run.store(dry_run, Ordering::Relaxed);
+    }
+
+    pub fn policies(&self) -> &[RemediationPolicy] {
+        &self.config.policies
+    }
+
+    // Runs every matching policy's actions in order. A failed action
+    // doesn't stop the ones after it.
+    pub async fn on_alert(&self, alert: &Alert) -> Vec<RemediationRecord> {
+        if !self.config.enabled {
+            return Vec::new();
+        }
+
+        let mut records = Vec::new();
+        for policy in self.config.policies.iter().filter(|p| p.matches(alert)) {
+            for template in &policy.actions {
+                let action = match template.render(&alert.labels) {
+                    Ok(action) => action,
+                    Err(e) => {
+                        records.push(self.record(policy, alert, template.clone(), RemediationOutcome::Failed {
+                            error: e.to_string(),
+                        }).await);
+                        continue;
+                    }
+                };
+                let outcome = self.run(policy, &action).await;
+                records.push(self.record(policy, alert, action, outcome).await);
+            }
+        }
+        records
+    }
+
+    async fn run(&self, policy: &RemediationPolicy, action: &RemediationAction) -> RemediationOutcome {
+        let now = Utc::now();
+        let key = action.target_key();
+        let cooldown = Duration::seconds(policy.cooldown_secs);
+
+        {
+            let mut last_run = self.last_run.write().await;
+            if let Some(last) = last_run.get(&key) {
+                if now - *last < cooldown {
+                    return RemediationOutcome::CoolingDown { until: *last + cooldown };
+                }
+            }
+            // Dry runs leave the cool-down alone, so switching dry run off
+            // doesn't find every target already cooling down
+            if !self.is_dry_run() {
+                last_run.insert(key, now);
+            }
+        }
+
+        if self.is_dry_run() {
+            log::info!("Remediation dry run ({}): {:?}", policy.name, action);
+            return RemediationOutcome::DryRun;
+        }
+
+        match self.executor.execute(action).await {
+            Ok(detail) => {
+                log::warn!("Remediation executed ({}): {:?}", policy.name, action);
+                RemediationOutcome::Executed { detail }
+            }
+            Err(e) => {
+                log::error!("Remediation failed ({}): {:?}: {}", policy.name, action, e);
+                RemediationOutcome::Failed { error: e.to_string() }
+            }
+        }
+    }
+
+    async fn record(
+        &self,
+        policy: &RemediationPolicy,
+        alert: &Alert,
+        action: RemediationAction,
+        outcome: RemediationOutcome,
+    ) -> RemediationRecord {
+        let record = RemediationRecord {
+            timestamp: Utc::now(),
+            policy: policy.name.clone(),
+            alert_key: alert.key.clone(),
+            alert_name: alert.name.clone(),
+            action,
+            outcome,
+        };
+
+        if let Some(path) = &self.config.audit_file {
+            if let Err(e) = append_line(path, &record).await {
+                log::error!("Failed to write remediation audit entry: {}", e);
+            }
+        }
+
+        let mut audit = self.audit.write().await;
+        audit.push(record.clone());
+        while audit.len() > self.config.audit_size {
+            audit.remove(0);
+        }
+        record
+    }
+
+    // Newest first
+    pub async fn audit_trail(&self, limit: usize) -> Vec<RemediationRecord> {
+        self.audit.read().await.iter().rev().take(limit).cloned().collect()
+    }
+}
+
+async fn append_line(path: &str, record: &RemediationRecord) -> Result<()> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .context("Failed to open remediation audit log")?;
+    file.write_all(line.as_bytes()).await?;
+    file.sync_data().await?;
+    Ok(())
+}
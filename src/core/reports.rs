@@ -0,0 +1,363 @@
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use crate::core::alerts::AlertManager;
+use crate::core::export::{AnalyticsSource, ExportRange, MetricsRecord};
+use crate::core::incidents::{IncidentFilter, IncidentManager, IncidentStatus};
+use crate::core::llm::{LLMTrait, types::Message};
+use crate::core::notify::{Notification, NotificationDispatcher, Severity};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReportConfig {
+    // Local hour at which the previous day's report is generated; None
+    // disables the schedule and leaves reports on-demand only
+    pub schedule_hour: Option<u32>,
+    pub utc_offset_minutes: i32,
+    // How often the metrics source samples; used to turn samples into uptime
+    pub metrics_interval_secs: u64,
+    pub max_reports: usize,
+    pub reports_file: Option<String>,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            schedule_hour: Some(6),
+            utc_offset_minutes: 0,
+            metrics_interval_secs: 60,
+            max_reports: 90,
+            reports_file: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DailySummary {
+    pub date: NaiveDate,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub detections: u64,
+    pub detections_by_class: BTreeMap<String, u64>,
+    // Local hour (0-23) with the most detections
+    pub busiest_hour: Option<u32>,
+    pub tracks: u64,
+    pub incidents_opened: u64,
+    pub incidents_resolved: u64,
+    pub incidents_unresolved: u64,
+    pub critical_incidents: u64,
+    // Anomalies reach reports through the alerts they raise
+    pub anomalies: BTreeMap<String, u64>,
+    pub frames_processed: u64,
+    pub processing_errors: u64,
+    pub average_fps: f32,
+    pub uptime_ratio: f32,
+    pub peak_crowd_count: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub date: NaiveDate,
+    pub generated_at: DateTime<Utc>,
+    pub summary: DailySummary,
+    pub text: String,
+    // False when the model was unavailable and the text is the plain fallback
+    pub llm_generated: bool,
+}
+
+const REPORT_PROMPT: &str = "You are Lilith, the operations assistant for a video analytics \
+    deployment. Write a short daily report for the on-call team from the JSON statistics \
+    below: two or three sentences of overview, then bullet points for anything that needs \
+    attention (unresolved or critical incidents, error spikes, low uptime). Use only the \
+    numbers given; do not speculate about causes.";
+
+pub struct ReportGenerator {
+    config: ReportConfig,
+    llm: Arc<dyn LLMTrait>,
+    analytics: Arc<dyn AnalyticsSource>,
+    incidents: Option<Arc<IncidentManager>>,
+    alerts: Option<Arc<AlertManager>>,
+    dispatcher: Option<Arc<NotificationDispatcher>>,
+    reports: Arc<RwLock<BTreeMap<NaiveDate, Report>>>,
+}
+
+impl ReportGenerator {
+    pub async fn new(
+        config: ReportConfig,
+        llm: Arc<dyn LLMTrait>,
+        analytics: Arc<dyn AnalyticsSource>,
+    ) -> Result<Self> {
+        let reports = match &config.reports_file {
+            Some(path) if tokio::fs::try_exists(path).await? => {
+                let contents = tokio::fs::read_to_string(path).await
+                    .context("Failed to read reports file")?;
+                let list: Vec<Report> = serde_json::from_str(&contents)
+                    .context("Failed to parse reports file")?;
+                list.into_iter().map(|r| (r.date, r)).collect()
+            }
+            _ => BTreeMap::new(),
+        };
+
+        Ok(Self {
+            config,
+            llm,
+            analytics,
+            incidents: None,
+            alerts: None,
+            dispatcher: None,
+            reports: Arc::new(RwLock::new(reports)),
+        })
+    }
+
+    pub fn with_incidents(mut self, incidents: Arc<IncidentManager>) -> Self {
+        self.incidents = Some(incidents);
+        self
+    }
+
+    pub fn with_alerts(mut self, alerts: Arc<AlertManager>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    pub fn with_dispatcher(mut self, dispatcher: Arc<NotificationDispatcher>) -> Self {
+        self.dispatcher = Some(dispatcher);
+        self
+    }
+
+    fn offset(&self) -> Duration {
+        Duration::minutes(self.config.utc_offset_minutes as i64)
+    }
+
+    pub fn day_range(&self, date: NaiveDate) -> ExportRange {
+        let start = date.and_hms_opt(0, 0, 0).unwrap().and_utc() - self.offset();
+        ExportRange { start, end: start + Duration::days(1) }
+    }
+
+    pub fn today(&self) -> NaiveDate {
+        (Utc::now() + self.offset()).date_naive()
+    }
+
+    pub async fn aggregate(&self, date: NaiveDate) -> Result<DailySummary> {
+        let range = self.day_range(date);
+        let mut summary = DailySummary {
+            date,
+            start: range.start,
+            end: range.end,
+            ..DailySummary::default()
+        };
+
+        // Builds without vision have no detections to count
+        #[cfg(feature = "vision")]
+        {
+            use chrono::Timelike;
+
+            let detections = self.analytics.detections(range).await
+                .context("Failed to load detections for report")?;
+            let mut by_hour = [0u64; 24];
+            for detection in &detections {
+                *summary.detections_by_class.entry(detection.class_name.clone()).or_insert(0) += 1;
+                by_hour[(detection.timestamp + self.offset()).hour() as usize] += 1;
+            }
+            summary.detections = detections.len() as u64;
+            summary.busiest_hour = by_hour.iter()
+                .enumerate()
+                .filter(|(_, count)| **count > 0)
+                .max_by_key(|(hour, count)| (**count, std::cmp::Reverse(*hour)))
+                .map(|(hour, _)| hour as u32);
+        }
+
+        summary.tracks = self.analytics.tracks(range).await
+            .context("Failed to load tracks for report")?
+            .len() as u64;
+
+        let metrics = self.analytics.metrics(range).await
+            .context("Failed to load metrics for report")?;
+        apply_metrics(&mut summary, &metrics, self.config.metrics_interval_secs);
+
+        if let Some(incidents) = &self.incidents {
+            let all = incidents.list(&IncidentFilter { limit: Some(usize::MAX), ..IncidentFilter::default() }).await;
+            let in_range = |t: DateTime<Utc>| t >= range.start && t < range.end;
+            for incident in &all {
+                if in_range(incident.created_at) {
+                    summary.incidents_opened += 1;
+                    if incident.severity == Severity::Critical {
+                        summary.critical_incidents += 1;
+                    }
+                }
+                if incident.resolved_at.map_or(false, in_range) {
+                    summary.incidents_resolved += 1;
+                }
+                if incident.status != IncidentStatus::Resolved && incident.created_at < range.end {
+                    summary.incidents_unresolved += 1;
+                }
+            }
+        }
+
+        if let Some(alerts) = &self.alerts {
+            let mut raised = alerts.history().await;
+            raised.extend(alerts.active_alerts().await);
+            for alert in raised.iter().filter(|a| a.raised_at >= range.start && a.raised_at < range.end) {
+                *summary.anomalies.entry(alert.name.clone()).or_insert(0) += alert.occurrences;
+            }
+        }
+
+        Ok(summary)
+    }
+
+    // Regenerating a day replaces its stored report
+    pub async fn generate(&self, date: NaiveDate) -> Result<Report> {
+        let summary = self.aggregate(date).await?;
+        let stats = serde_json::to_string_pretty(&summary)?;
+
+        let (text, llm_generated) = match self.llm.complete(vec![
+            Message::new("system", REPORT_PROMPT),
+            Message::new("user", &stats),
+        ]).await {
+            Ok(response) if !response.content.trim().is_empty() => (response.content.trim().to_string(), true),
+            Ok(_) => (fallback_text(&summary), false),
+            Err(e) => {
+                log::warn!("Report generation for {} fell back to plain text: {}", date, e);
+                (fallback_text(&summary), false)
+            }
+        };
+
+        let report = Report {
+            date,
+            generated_at: Utc::now(),
+            summary,
+            text,
+            llm_generated,
+        };
+
+        {
+            let mut reports = self.reports.write().await;
+            reports.insert(date, report.clone());
+            while reports.len() > self.config.max_reports.max(1) {
+                let oldest = *reports.keys().next().unwrap();
+                reports.remove(&oldest);
+            }
+        }
+        self.persist().await?;
+        self.deliver(&report).await;
+        Ok(report)
+    }
+
+    async fn deliver(&self, report: &Report) {
+        let Some(dispatcher) = &self.dispatcher else {
+            return;
+        };
+
+        let notification = Notification::new(
+            &format!("Daily report for {}", report.date),
+            &report.text,
+            Severity::Info,
+            "reports",
+        )
+        .with_field("detections", &report.summary.detections.to_string())
+        .with_field("incidents", &report.summary.incidents_opened.to_string())
+        .with_field("uptime", &format!("{:.1}%", report.summary.uptime_ratio * 100.0));
+        dispatcher.dispatch(&notification).await;
+    }
+
+    pub async fn get(&self, date: NaiveDate) -> Option<Report> {
+        self.reports.read().await.get(&date).cloned()
+    }
+
+    // Newest first
+    pub async fn list(&self) -> Vec<Report> {
+        self.reports.read().await.values().rev().cloned().collect()
+    }
+
+    async fn persist(&self) -> Result<()> {
+        let Some(path) = &self.config.reports_file else {
+            return Ok(());
+        };
+
+        let list: Vec<Report> = self.reports.read().await.values().cloned().collect();
+        let serialized = serde_json::to_string_pretty(&list)?;
+        tokio::fs::write(path, serialized).await
+            .context("Failed to persist reports")?;
+        Ok(())
+    }
+
+    pub fn start_schedule(self: &Arc<Self>) {
+        let Some(hour) = self.config.schedule_hour else {
+            return;
+        };
+        let generator = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let now = Utc::now() + generator.offset();
+                let today_run = now.date_naive().and_hms_opt(hour.min(23), 0, 0).unwrap().and_utc();
+                let next_run = if now < today_run { today_run } else { today_run + Duration::days(1) };
+                let wait = (next_run - now).to_std().unwrap_or_default();
+                tokio::time::sleep(wait).await;
+
+                let yesterday = generator.today() - Duration::days(1);
+                match generator.generate(yesterday).await {
+                    Ok(report) => log::info!("Generated daily report for {}", report.date),
+                    Err(e) => log::error!("Daily report for {} failed: {}", yesterday, e),
+                }
+            }
+        });
+    }
+}
+
+fn apply_metrics(summary: &mut DailySummary, metrics: &[MetricsRecord], interval_secs: u64) {
+    if metrics.is_empty() {
+        return;
+    }
+
+    // Counters are cumulative per run, so sum the positive deltas; a drop
+    // means the engine restarted and the counter began again from zero
+    let mut previous: Option<(u64, u64)> = None;
+    for record in metrics {
+        if let Some((frames, errors)) = previous {
+            summary.frames_processed += record.frames_processed.checked_sub(frames).unwrap_or(record.frames_processed);
+            summary.processing_errors += record.error_count.checked_sub(errors).unwrap_or(record.error_count);
+        }
+        previous = Some((record.frames_processed, record.error_count));
+    }
+
+    let live: Vec<_> = metrics.iter().filter(|m| m.fps > 0.0).collect();
+    if !live.is_empty() {
+        summary.average_fps = live.iter().map(|m| m.fps).sum::<f32>() / live.len() as f32;
+    }
+    let day_secs = (summary.end - summary.start).num_seconds().max(1) as f32;
+    summary.uptime_ratio = (live.len() as f32 * interval_secs as f32 / day_secs).min(1.0);
+    summary.peak_crowd_count = metrics.iter()
+        .filter_map(|m| m.crowd_count)
+        .fold(None, |peak: Option<f32>, count| Some(peak.map_or(count, |p| p.max(count))));
+}
+
+pub fn fallback_text(summary: &DailySummary) -> String {
+    let mut classes: Vec<_> = summary.detections_by_class.iter().collect();
+    classes.sort_by(|a, b| b.1.cmp(a.1));
+    let top: Vec<String> = classes.iter().take(3).map(|(class, n)| format!("{} {}", n, class)).collect();
+
+    let mut lines = vec![format!(
+        "{}: {} detections{}, {} incidents opened ({} critical), {} resolved, {} still open. Uptime {:.1}%, {} processing errors.",
+        summary.date,
+        summary.detections,
+        if top.is_empty() { String::new() } else { format!(" ({})", top.join(", ")) },
+        summary.incidents_opened,
+        summary.critical_incidents,
+        summary.incidents_resolved,
+        summary.incidents_unresolved,
+        summary.uptime_ratio * 100.0,
+        summary.processing_errors,
+    )];
+    if let Some(hour) = summary.busiest_hour {
+        lines.push(format!("Busiest hour: {:02}:00.", hour));
+    }
+    if !summary.anomalies.is_empty() {
+        let anomalies: Vec<String> = summary.anomalies.iter().map(|(name, n)| format!("{} x{}", name, n)).collect();
+        lines.push(format!("Anomalies: {}.", anomalies.join(", ")));
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,399 @@
+#![cfg(feature = "vision")]
+
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc, Weekday};
+use uuid::Uuid;
+
+use crate::core::alerts::{Alert, AlertManager};
+use crate::core::notify::Severity;
+use crate::core::pipeline::PipelineData;
+use crate::core::sinks::ResultSink;
+use crate::core::streams::StreamRegistry;
+use crate::vision::detector::Detection;
+
+// When a rule is armed. Times are local to the camera, given as an offset
+// from UTC; an end before the start wraps past midnight (22:00-06:00).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Schedule {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    // Empty means every day. The day is the one the window starts on.
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+
+impl Schedule {
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        let local = at.naive_utc() + Duration::minutes(self.utc_offset_minutes as i64);
+        let time = local.time();
+        let day_allowed = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+
+        if self.start <= self.end {
+            time >= self.start && time < self.end && day_allowed(local.weekday())
+        } else if time >= self.start {
+            day_allowed(local.weekday())
+        } else if time < self.end {
+            // Early-morning part of a window that opened the day before
+            day_allowed(local.weekday().pred())
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub stream_id: String,
+    // Name of a zone in the stream's profile; None watches the whole frame
+    #[serde(default)]
+    pub zone: Option<String>,
+    // Empty matches any class
+    #[serde(default)]
+    pub classes: Vec<String>,
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+    #[serde(default = "default_min_confidence")]
+    pub min_confidence: f32,
+    #[serde(default = "default_min_count")]
+    pub min_count: usize,
+    pub severity: Severity,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+}
+
+fn default_min_confidence() -> f32 {
+    0.5
+}
+
+fn default_min_count() -> usize {
+    1
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Rule {
+    // The rule's own fields; references to streams and zones are checked by
+    // the store
+    pub fn check(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(anyhow::anyhow!("Rule name must not be empty"));
+        }
+        if !(0.0..=1.0).contains(&self.min_confidence) {
+            return Err(anyhow::anyhow!("min_confidence must be between 0 and 1"));
+        }
+        if self.min_count == 0 {
+            return Err(anyhow::anyhow!("min_count must be at least 1"));
+        }
+        if let Some(schedule) = &self.schedule {
+            if schedule.start == schedule.end {
+                return Err(anyhow::anyhow!("Schedule start and end must differ"));
+            }
+            if schedule.utc_offset_minutes.abs() > 14 * 60 {
+                return Err(anyhow::anyhow!("utc_offset_minutes is out of range"));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn describe(&self) -> String {
+        let what = if self.classes.is_empty() {
+            "any object".to_string()
+        } else {
+            self.classes.join(" or ")
+        };
+        let count = if self.min_count > 1 {
+            format!("at least {} of ", self.min_count)
+        } else {
+            String::new()
+        };
+        let place = match &self.zone {
+            Some(zone) => format!("in zone \"{}\" on {}", zone, self.stream_id),
+            None => format!("anywhere on {}", self.stream_id),
+        };
+        let when = match &self.schedule {
+            Some(schedule) => {
+                let days = if schedule.days.is_empty() {
+                    "every day".to_string()
+                } else {
+                    schedule.days.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(", ")
+                };
+                format!(
+                    " between {} and {} (UTC{:+}h, {})",
+                    schedule.start.format("%H:%M"),
+                    schedule.end.format("%H:%M"),
+                    schedule.utc_offset_minutes as f32 / 60.0,
+                    days,
+                )
+            }
+            None => String::new(),
+        };
+        format!(
+            "Raise a {} alert when {}{} is detected {}{}",
+            self.severity, count, what, place, when,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleMatch {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub stream_id: String,
+    pub severity: Severity,
+    pub detections: Vec<Detection>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl RuleMatch {
+    // One alert per rule and stream; repeat matches bump its occurrence count
+    pub async fn raise(&self, alerts: &AlertManager) -> Alert {
+        let labels = HashMap::from([
+            ("rule_id".to_string(), self.rule_id.clone()),
+            ("stream_id".to_string(), self.stream_id.clone()),
+            ("detections".to_string(), self.detections.len().to_string()),
+        ]);
+        let message = format!(
+            "{} detection(s) matched rule \"{}\" on {}",
+            self.detections.len(), self.rule_name, self.stream_id,
+        );
+        alerts.raise(
+            &format!("rule:{}:{}", self.rule_id, self.stream_id),
+            &self.rule_name,
+            self.severity,
+            &message,
+            labels,
+        ).await
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleStoreConfig {
+    pub persist: bool,
+    pub rules_file: String,
+}
+
+impl Default for RuleStoreConfig {
+    fn default() -> Self {
+        Self {
+            persist: false,
+            rules_file: "rules.json".to_string(),
+        }
+    }
+}
+
+pub struct RuleStore {
+    rules: Arc<RwLock<HashMap<String, Rule>>>,
+    registry: Arc<StreamRegistry>,
+    config: RuleStoreConfig,
+    // Serializes snapshot-and-write so an older snapshot can't land last
+    persist_lock: Mutex<()>,
+}
+
+impl RuleStore {
+    pub async fn new(config: RuleStoreConfig, registry: Arc<StreamRegistry>) -> Result<Self> {
+        let rules = if config.persist && tokio::fs::try_exists(&config.rules_file).await? {
+            let contents = tokio::fs::read_to_string(&config.rules_file).await
+                .context("Failed to read rules file")?;
+            let list: Vec<Rule> = serde_json::from_str(&contents)
+                .context("Failed to parse rules file")?;
+            list.into_iter().map(|r| (r.id.clone(), r)).collect()
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            rules: Arc::new(RwLock::new(rules)),
+            registry,
+            config,
+            persist_lock: Mutex::new(()),
+        })
+    }
+
+    pub fn registry(&self) -> &Arc<StreamRegistry> {
+        &self.registry
+    }
+
+    // Checks the rule against the live stream configuration, not just its
+    // own fields: a rule naming a zone the camera doesn't have never fires
+    pub async fn validate(&self, rule: &Rule) -> Result<()> {
+        rule.check()?;
+
+        let (_, profile) = self.registry.resolve(&rule.stream_id).await?;
+        if let Some(zone) = &rule.zone {
+            if !profile.zones.iter().any(|z| &z.name == zone) {
+                let known: Vec<_> = profile.zones.iter().map(|z| z.name.as_str()).collect();
+                return Err(anyhow::anyhow!(
+                    "Stream {} has no zone \"{}\" (zones: {})",
+                    rule.stream_id, zone, if known.is_empty() { "none".to_string() } else { known.join(", ") },
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn get(&self, id: &str) -> Option<Rule> {
+        self.rules.read().await.get(id).cloned()
+    }
+
+    pub async fn list(&self) -> Vec<Rule> {
+        let mut rules: Vec<_> = self.rules.read().await.values().cloned().collect();
+        rules.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+        rules
+    }
+
+    pub async fn create(&self, mut rule: Rule) -> Result<Rule> {
+        self.validate(&rule).await?;
+        rule.id = Uuid::new_v4().to_string();
+        rule.updated_at = Utc::now();
+
+        self.rules.write().await.insert(rule.id.clone(), rule.clone());
+        self.persist().await?;
+        Ok(rule)
+    }
+
+    pub async fn update(&self, id: &str, mut rule: Rule) -> Result<Rule> {
+        rule.id = id.to_string();
+        self.validate(&rule).await?;
+        rule.updated_at = Utc::now();
+
+        let mut rules = self.rules.write().await;
+        if !rules.contains_key(id) {
+            return Err(anyhow::anyhow!("Rule not found: {}", id));
+        }
+        rules.insert(id.to_string(), rule.clone());
+        drop(rules);
+
+        self.persist().await?;
+        Ok(rule)
+    }
+
+    // Keeps the rule's id when it has one, so importing the same rules twice
+    // updates them instead of adding copies
+    pub async fn put(&self, mut rule: Rule) -> Result<Rule> {
+        if rule.id.is_empty() {
+            return self.create(rule).await;
+        }
+        self.validate(&rule).await?;
+        rule.updated_at = Utc::now();
+
+        self.rules.write().await.insert(rule.id.clone(), rule.clone());
+        self.persist().await?;
+        Ok(rule)
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        if self.rules.write().await.remove(id).is_none() {
+            return Err(anyhow::anyhow!("Rule not found: {}", id));
+        }
+        self.persist().await
+    }
+
+    pub async fn evaluate(&self, stream_id: &str, detections: &[Detection], at: DateTime<Utc>) -> Vec<RuleMatch> {
+        let rules: Vec<Rule> = self.rules.read().await.values()
+            .filter(|r| r.enabled && r.stream_id == stream_id)
+            .filter(|r| r.schedule.as_ref().is_none_or(|s| s.contains(at)))
+            .cloned()
+            .collect();
+        if rules.is_empty() {
+            return Vec::new();
+        }
+
+        let zones = match self.registry.resolve(stream_id).await {
+            Ok((_, profile)) => profile.zones,
+            Err(e) => {
+                log::warn!("Skipping rules for stream {}: {}", stream_id, e);
+                return Vec::new();
+            }
+        };
+
+        let mut matches = Vec::new();
+        for rule in rules {
+            let zone = match &rule.zone {
+                Some(name) => match zones.iter().find(|z| &z.name == name) {
+                    Some(zone) => Some(zone),
+                    None => continue,
+                },
+                None => None,
+            };
+
+            let matched: Vec<Detection> = detections.iter()
+                .filter(|d| d.confidence >= rule.min_confidence)
+                .filter(|d| rule.classes.is_empty() || rule.classes.contains(&d.class_name))
+                .filter(|d| zone.is_none_or(|z| z.contains_bbox(&d.bbox)))
+                .cloned()
+                .collect();
+
+            if matched.len() >= rule.min_count {
+                matches.push(RuleMatch {
+                    rule_id: rule.id.clone(),
+                    rule_name: rule.name.clone(),
+                    stream_id: stream_id.to_string(),
+                    severity: rule.severity,
+                    detections: matched,
+                    timestamp: at,
+                });
+            }
+        }
+        matches
+    }
+
+    async fn persist(&self) -> Result<()> {
+        if !self.config.persist {
+            return Ok(());
+        }
+
+        let _guard = self.persist_lock.lock().await;
+        let serialized = serde_json::to_string_pretty(&self.list().await)?;
+        let temp = format!("{}.tmp", self.config.rules_file);
+        tokio::fs::write(&temp, serialized).await
+            .context("Failed to persist rules")?;
+        tokio::fs::rename(&temp, &self.config.rules_file).await
+            .context("Failed to persist rules")?;
+        Ok(())
+    }
+}
+
+// Evaluates every pipeline result against the rules for its stream and
+// raises an alert per match. Frames without a stream id (ad hoc uploads,
+// tests) aren't covered by any rule.
+pub struct RuleSink {
+    rules: Arc<RuleStore>,
+    alerts: Arc<AlertManager>,
+}
+
+impl RuleSink {
+    pub fn new(rules: Arc<RuleStore>, alerts: Arc<AlertManager>) -> Self {
+        Self { rules, alerts }
+    }
+}
+
+#[async_trait]
+impl ResultSink for RuleSink {
+    fn name(&self) -> String {
+        "rules".to_string()
+    }
+
+    async fn write(&self, data: &PipelineData) -> Result<()> {
+        let Some(stream_id) = &data.frame.metadata.stream_id else {
+            return Ok(());
+        };
+        for matched in self.rules.evaluate(stream_id, &data.detections, data.timestamp).await {
+            matched.raise(&self.alerts).await;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,421 @@
+#![cfg(feature = "vision")]
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::core::pipeline::PipelineData;
+use crate::core::webhook::{WebhookConfig, WebhookSender};
+use crate::utils::egress::{EgressClient, EgressConfig};
+use crate::vision::{analyzer::Analysis, detector::Detection};
+
+pub const DELIVERY_ID_HEADER: &str = "delivery-id";
+
+// Serializable view of a pipeline result; frame pixels are never written
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SinkRecord {
+    // Same for every delivery of the same result, so consumers can drop
+    // repeats after a retry
+    pub delivery_id: String,
+    pub frame_id: u64,
+    pub source: String,
+    pub timestamp: DateTime<Utc>,
+    pub detections: Vec<Detection>,
+    pub analysis: Option<Analysis>,
+    pub metadata: HashMap<String, String>,
+}
+
+impl From<&PipelineData> for SinkRecord {
+    fn from(data: &PipelineData) -> Self {
+        Self {
+            delivery_id: delivery_id(data),
+            frame_id: data.frame.id,
+            source: data.frame.metadata.source.clone(),
+            timestamp: data.timestamp,
+            detections: data.detections.clone(),
+            analysis: data.analysis.clone(),
+            metadata: data.metadata.clone(),
+        }
+    }
+}
+
+// Derived from the frame rather than generated, so a frame that comes
+// through the pipeline twice (a re-injected dead letter, a replayed batch)
+// gets the same id
+pub fn delivery_id(data: &PipelineData) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(data.frame.metadata.source.as_bytes());
+    hasher.update([0]);
+    hasher.update(data.frame.id.to_be_bytes());
+    hasher.update(data.frame.timestamp.timestamp_nanos_opt().unwrap_or_default().to_be_bytes());
+    hex::encode(&hasher.finalize()[..16])
+}
+
+// The fields the database and Kafka sinks need out of a serialized record
+fn record_fields(record: &Value) -> Result<(u64, String, DateTime<Utc>)> {
+    let frame_id = record["frame_id"].as_u64().context("Sink record has no frame_id")?;
+    let source = record["source"].as_str().context("Sink record has no source")?.to_string();
+    let timestamp = record["timestamp"].as_str()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .context("Sink record has no timestamp")?
+        .with_timezone(&Utc);
+    Ok((frame_id, source, timestamp))
+}
+
+#[async_trait]
+pub trait ResultSink: Send + Sync {
+    fn name(&self) -> String;
+    async fn write(&self, data: &PipelineData) -> Result<()>;
+
+    // Whether deliver() is implemented. Only these sinks go through the
+    // outbox; the rest are written to directly.
+    fn replays(&self) -> bool {
+        false
+    }
+
+    // Writes an already serialized SinkRecord; the outbox replays stored
+    // records through this. Ok means the far end acknowledged it.
+    async fn deliver(&self, _delivery_id: &str, _record: &Value) -> Result<()> {
+        Err(anyhow::anyhow!("Sink {} can't deliver stored records", self.name()))
+    }
+
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkConfig {
+    Stdout,
+    File {
+        path: String,
+    },
+    Database {
+        url: String,
+        table: String,
+        // Expects a unique delivery_id column and skips rows already written.
+        // Turning it off loses the effectively-once delivery.
+        #[serde(default = "default_idempotent")]
+        idempotent: bool,
+    },
+    Kafka {
+        brokers: String,
+        topic: String,
+        #[serde(default = "default_kafka_timeout_ms")]
+        timeout_ms: u64,
+        #[serde(default)]
+        format: KafkaFormat,
+    },
+    Webhook {
+        webhook: WebhookConfig,
+        #[serde(default)]
+        egress: EgressConfig,
+    },
+}
+
+// Protobuf messages are `vae.v1.PipelineResult`; JSON is the SinkRecord as
+// the other sinks write it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KafkaFormat {
+    #[default]
+    Proto,
+    Json,
+}
+
+fn default_kafka_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_idempotent() -> bool {
+    true
+}
+
+pub async fn build_sink(config: &SinkConfig) -> Result<Arc<dyn ResultSink>> {
+    let sink: Arc<dyn ResultSink> = match config {
+        SinkConfig::Stdout => Arc::new(StdoutSink),
+        SinkConfig::File { path } => Arc::new(FileSink::open(path).await?),
+        SinkConfig::Database { url, table, idempotent } => {
+            Arc::new(DatabaseSink::connect(url, table, *idempotent).await?)
+        }
+        SinkConfig::Kafka { brokers, topic, timeout_ms, format } => {
+            Arc::new(KafkaSink::new(brokers, topic, Duration::from_millis(*timeout_ms))?.with_format(*format)?)
+        }
+        SinkConfig::Webhook { webhook, egress } => {
+            let client = EgressClient::new(egress, Duration::from_millis(webhook.timeout_ms))?;
+            Arc::new(WebhookSink { sender: WebhookSender::new(webhook.clone(), client) })
+        }
+    };
+    Ok(sink)
+}
+
+pub struct StdoutSink;
+
+#[async_trait]
+impl ResultSink for StdoutSink {
+    fn name(&self) -> String {
+        "stdout".to_string()
+    }
+
+    fn replays(&self) -> bool {
+        true
+    }
+
+    async fn write(&self, data: &PipelineData) -> Result<()> {
+        let line = serde_json::to_string(&SinkRecord::from(data))?;
+        println!("{}", line);
+        Ok(())
+    }
+
+    async fn deliver(&self, _delivery_id: &str, record: &Value) -> Result<()> {
+        println!("{}", record);
+        Ok(())
+    }
+}
+
+pub struct FileSink {
+    path: String,
+    file: Mutex<tokio::fs::File>,
+}
+
+impl FileSink {
+    async fn append(&self, mut line: Vec<u8>) -> Result<()> {
+        line.push(b'\n');
+        self.file.lock().await.write_all(&line).await
+            .context("Failed to write sink record")?;
+        Ok(())
+    }
+
+    pub async fn open(path: &str) -> Result<Self> {
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .with_context(|| format!("Failed to open sink file {}", path))?;
+
+        Ok(Self {
+            path: path.to_string(),
+            file: Mutex::new(file),
+        })
+    }
+}
+
+#[async_trait]
+impl ResultSink for FileSink {
+    fn name(&self) -> String {
+        format!("file:{}", self.path)
+    }
+
+    fn replays(&self) -> bool {
+        true
+    }
+
+    async fn write(&self, data: &PipelineData) -> Result<()> {
+        self.append(serde_json::to_vec(&SinkRecord::from(data))?).await
+    }
+
+    async fn deliver(&self, _delivery_id: &str, record: &Value) -> Result<()> {
+        self.append(serde_json::to_vec(record)?).await?;
+        // Acknowledged once it's on disk, not just in the page cache
+        let file = self.file.lock().await;
+        file.sync_data().await.context("Failed to sync sink file")?;
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.file.lock().await.flush().await?;
+        Ok(())
+    }
+}
+
+// Expects `(frame_id BIGINT, source TEXT, recorded_at TIMESTAMPTZ, payload JSONB)`,
+// plus `delivery_id TEXT UNIQUE` when idempotent
+pub struct DatabaseSink {
+    pool: sqlx::PgPool,
+    insert: String,
+    idempotent: bool,
+}
+
+impl DatabaseSink {
+    pub async fn connect(url: &str, table: &str, idempotent: bool) -> Result<Self> {
+        // The table name is interpolated into SQL, so keep it to identifiers
+        if table.is_empty() || !table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+            return Err(anyhow::anyhow!("Invalid sink table name: {}", table));
+        }
+
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .max_connections(4)
+            .connect(url)
+            .await
+            .context("Failed to connect database sink")?;
+
+        let insert = if idempotent {
+            format!(
+                "INSERT INTO {} (frame_id, source, recorded_at, payload, delivery_id) VALUES ($1, $2, $3, $4, $5) \
+                 ON CONFLICT (delivery_id) DO NOTHING",
+                table
+            )
+        } else {
+            format!(
+                "INSERT INTO {} (frame_id, source, recorded_at, payload) VALUES ($1, $2, $3, $4)",
+                table
+            )
+        };
+        Ok(Self { pool, insert, idempotent })
+    }
+}
+
+#[async_trait]
+impl ResultSink for DatabaseSink {
+    fn name(&self) -> String {
+        "database".to_string()
+    }
+
+    fn replays(&self) -> bool {
+        true
+    }
+
+    async fn write(&self, data: &PipelineData) -> Result<()> {
+        let record = SinkRecord::from(data);
+        self.deliver(&record.delivery_id, &serde_json::to_value(&record)?).await
+    }
+
+    // The commit is the acknowledgement; a row that's already there counts
+    async fn deliver(&self, delivery_id: &str, record: &Value) -> Result<()> {
+        let (frame_id, source, timestamp) = record_fields(record)?;
+        let mut query = sqlx::query(&self.insert)
+            .bind(frame_id as i64)
+            .bind(source)
+            .bind(timestamp)
+            .bind(record);
+        if self.idempotent {
+            query = query.bind(delivery_id);
+        }
+        query.execute(&self.pool)
+            .await
+            .context("Failed to insert sink record")?;
+        Ok(())
+    }
+}
+
+pub struct KafkaSink {
+    producer: FutureProducer,
+    topic: String,
+    timeout: Duration,
+    format: KafkaFormat,
+}
+
+impl KafkaSink {
+    pub fn new(brokers: &str, topic: &str, timeout: Duration) -> Result<Self> {
+        // Idempotence stops the producer's own retries from writing a record
+        // twice; repeats after an outbox retry carry the same delivery-id
+        // header for consumers to drop
+        let producer: FutureProducer = rdkafka::ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("message.timeout.ms", timeout.as_millis().to_string())
+            .set("enable.idempotence", "true")
+            .set("acks", "all")
+            .create()
+            .context("Failed to create Kafka producer")?;
+
+        Ok(Self {
+            producer,
+            topic: topic.to_string(),
+            timeout,
+            format: KafkaFormat::default(),
+        })
+    }
+
+    pub fn with_format(mut self, format: KafkaFormat) -> Result<Self> {
+        // The generated protobuf types are only built with the API
+        if format == KafkaFormat::Proto && !cfg!(feature = "api") {
+            return Err(anyhow::anyhow!("Kafka sink {} needs the api feature for protobuf output", self.topic));
+        }
+        self.format = format;
+        Ok(self)
+    }
+}
+
+pub fn encode_record(record: &Value, format: KafkaFormat) -> Result<Vec<u8>> {
+    match format {
+        KafkaFormat::Json => Ok(serde_json::to_vec(record)?),
+        #[cfg(feature = "api")]
+        KafkaFormat::Proto => {
+            use prost::Message;
+            let record: SinkRecord = serde_json::from_value(record.clone())
+                .context("Sink record does not match the PipelineResult schema")?;
+            Ok(crate::api::proto::v1::PipelineResult::from(&record).encode_to_vec())
+        }
+        #[cfg(not(feature = "api"))]
+        KafkaFormat::Proto => Err(anyhow::anyhow!("Protobuf output needs the api feature")),
+    }
+}
+
+#[async_trait]
+impl ResultSink for KafkaSink {
+    fn name(&self) -> String {
+        format!("kafka:{}", self.topic)
+    }
+
+    fn replays(&self) -> bool {
+        true
+    }
+
+    async fn write(&self, data: &PipelineData) -> Result<()> {
+        let record = SinkRecord::from(data);
+        self.deliver(&record.delivery_id, &serde_json::to_value(&record)?).await
+    }
+
+    // Resolves once the brokers have acknowledged the write
+    async fn deliver(&self, delivery_id: &str, record: &Value) -> Result<()> {
+        let (_, source, _) = record_fields(record)?;
+        let payload = encode_record(record, self.format)?;
+        // Keyed by source so a camera's results stay ordered within a partition
+        let delivery = FutureRecord::to(&self.topic)
+            .key(&source)
+            .payload(&payload)
+            .headers(OwnedHeaders::new().insert(Header { key: DELIVERY_ID_HEADER, value: Some(delivery_id) }));
+
+        self.producer.send(delivery, self.timeout).await
+            .map_err(|(e, _)| anyhow::anyhow!("Failed to publish to Kafka: {}", e))?;
+        Ok(())
+    }
+}
+
+pub struct WebhookSink {
+    sender: WebhookSender,
+}
+
+#[async_trait]
+impl ResultSink for WebhookSink {
+    fn name(&self) -> String {
+        "webhook".to_string()
+    }
+
+    fn replays(&self) -> bool {
+        true
+    }
+
+    async fn write(&self, data: &PipelineData) -> Result<()> {
+        let record = SinkRecord::from(data);
+        self.sender.send_with_id("pipeline.result", &record.delivery_id, &record).await
+    }
+
+    // The delivery id doubles as the event id, which receivers already use
+    // to reject replays
+    async fn deliver(&self, delivery_id: &str, record: &Value) -> Result<()> {
+        self.sender.send_with_id("pipeline.result", delivery_id, record).await
+    }
+}
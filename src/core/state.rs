@@ -5,6 +5,8 @@ use std::collections::HashMap;
 use chrono::{DateTime, Utc};
 use anyhow::Result;
 
+use crate::core::crypto::Encryptor;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemState {
     pub engine_state: EngineState,
@@ -76,6 +78,7 @@ pub struct StateManager {
     state: Arc<RwLock<SystemState>>,
     history: Arc<RwLock<Vec<StateSnapshot>>>,
     config: StateConfig,
+    encryptor: Option<Arc<Encryptor>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -87,9 +90,9 @@ pub struct StateConfig {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct StateSnapshot {
-    timestamp: DateTime<Utc>,
-    state: SystemState,
+pub struct StateSnapshot {
+    pub timestamp: DateTime<Utc>,
+    pub state: SystemState,
 }
 
 impl StateManager {
@@ -126,6 +129,7 @@ impl StateManager {
             state: Arc::new(RwLock::new(initial_state)),
             history: Arc::new(RwLock::new(Vec::new())),
             config,
+            encryptor: None,
         };
 
         // Start state monitoring
@@ -134,6 +138,12 @@ impl StateManager {
         Ok(manager)
     }
 
+    // Persisted state is written through the encryptor when one is set
+    pub fn with_encryption(mut self, encryptor: Arc<Encryptor>) -> Self {
+        self.encryptor = Some(encryptor);
+        self
+    }
+
     pub async fn update_engine_state(&self, state: EngineState) -> Result<()> {
         let mut system_state = self.state.write().await;
         system_state.engine_state = state;
@@ -175,6 +185,20 @@ impl StateManager {
         Ok(self.history.read().await.clone())
     }
 
+    pub async fn history_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<StateSnapshot> {
+        self.history.read().await.iter()
+            .filter(|s| s.timestamp >= start && s.timestamp <= end)
+            .cloned()
+            .collect()
+    }
+
+    pub async fn errors_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<ErrorInfo> {
+        self.state.read().await.error_state.error_history.iter()
+            .filter(|e| e.timestamp >= start && e.timestamp <= end)
+            .cloned()
+            .collect()
+    }
+
     async fn take_snapshot(&self) -> Result<()> {
         let current_state = self.state.read().await.clone();
         let snapshot = StateSnapshot {
@@ -200,12 +224,20 @@ impl StateManager {
     async fn persist_state(&self) -> Result<()> {
         let state = self.state.read().await;
         let serialized = serde_json::to_string_pretty(&*state)?;
-        tokio::fs::write(&self.config.state_file, serialized).await?;
+        drop(state);
+
+        match &self.encryptor {
+            Some(encryptor) => {
+                encryptor.write_file(&self.config.state_file, serialized.as_bytes()).await?;
+            }
+            None => tokio::fs::write(&self.config.state_file, serialized).await?,
+        }
         Ok(())
     }
 
     fn start_monitoring(&self) {
         let state = self.state.clone();
+        let history = self.history.clone();
         let config = self.config.clone();
 
         tokio::spawn(async move {
@@ -230,6 +262,20 @@ impl StateManager {
                 if system_state.engine_state.status == EngineStatus::Running {
                     system_state.engine_state.uptime += config.snapshot_interval;
                 }
+
+                // Periodic snapshots give the metrics history a steady time
+                // base, not just points where the engine state changed
+                let snapshot = StateSnapshot {
+                    timestamp: Utc::now(),
+                    state: system_state.clone(),
+                };
+                drop(system_state);
+
+                let mut history = history.write().await;
+                history.push(snapshot);
+                while history.len() > config.history_size {
+                    history.remove(0);
+                }
             }
         });
     }
@@ -0,0 +1,354 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::core::crypto::Encryptor;
+use crate::core::lifecycle::{DataCategory, DataStore, DataSubject};
+
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()>;
+    async fn get(&self, key: &str) -> Result<Vec<u8>>;
+    async fn delete(&self, key: &str) -> Result<()>;
+    async fn list(&self, prefix: &str) -> Result<Vec<String>>;
+}
+
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub async fn new(root: &str) -> Result<Self> {
+        tokio::fs::create_dir_all(root).await
+            .context("Failed to create storage directory")?;
+        Ok(Self { root: PathBuf::from(root) })
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        // Keys are generated internally, but never let one escape the root
+        if key.is_empty() || key.split('/').any(|part| part.is_empty() || part == "." || part == "..") {
+            return Err(anyhow::anyhow!("Invalid storage key: {}", key));
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalStorage {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, data).await
+            .with_context(|| format!("Failed to write {}", key))?;
+        tokio::fs::rename(&tmp, &path).await
+            .with_context(|| format!("Failed to commit {}", key))?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        tokio::fs::read(self.path(key)?).await
+            .with_context(|| format!("Failed to read {}", key))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        tokio::fs::remove_file(self.path(key)?).await
+            .with_context(|| format!("Failed to delete {}", key))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let dir = self.path(prefix)?;
+        let mut keys = Vec::new();
+        if !tokio::fs::try_exists(&dir).await? {
+            return Ok(keys);
+        }
+
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(name) = entry.file_name().to_str() {
+                keys.push(format!("{}/{}", prefix, name));
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+// Seals everything put through it, so recordings and uploads are stored
+// encrypted on any backend. Objects written before encryption was enabled
+// are read as they are.
+pub struct EncryptedStorage {
+    inner: Arc<dyn StorageBackend>,
+    encryptor: Arc<Encryptor>,
+}
+
+impl EncryptedStorage {
+    pub fn new(inner: Arc<dyn StorageBackend>, encryptor: Arc<Encryptor>) -> Self {
+        Self { inner, encryptor }
+    }
+
+    // After a key rotation: rewrites every object under `prefix` with the
+    // active key, so the retired one can be dropped
+    pub async fn reencrypt(&self, prefix: &str) -> Result<usize> {
+        let keys = self.inner.list(prefix).await?;
+        for key in &keys {
+            let data = self.inner.get(key).await?;
+            self.inner.put(key, &self.encryptor.reencrypt(&data).await?).await?;
+        }
+        Ok(keys.len())
+    }
+}
+
+#[async_trait]
+impl StorageBackend for EncryptedStorage {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        self.inner.put(key, &self.encryptor.encrypt(data).await?).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let data = self.inner.get(key).await?;
+        self.encryptor.decrypt_if_needed(data).await
+            .with_context(|| format!("Failed to decrypt {}", key))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.inner.delete(key).await
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        self.inner.list(prefix).await
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    Image,
+    Audio,
+    Pdf,
+    Document,
+    Text,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredFile {
+    pub id: String,
+    pub filename: String,
+    pub content_type: String,
+    pub kind: FileKind,
+    pub size: u64,
+    pub sha256: String,
+    pub created_at: DateTime<Utc>,
+    // The uploader; None for files stored before uploads were attributed
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub tenant: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileStoreConfig {
+    pub max_size_bytes: HashMap<FileKind, u64>,
+}
+
+impl Default for FileStoreConfig {
+    fn default() -> Self {
+        Self {
+            max_size_bytes: HashMap::from([
+                (FileKind::Image, 20 * 1024 * 1024),
+                (FileKind::Audio, 50 * 1024 * 1024),
+                (FileKind::Pdf, 50 * 1024 * 1024),
+                (FileKind::Document, 25 * 1024 * 1024),
+                (FileKind::Text, 5 * 1024 * 1024),
+            ]),
+        }
+    }
+}
+
+pub fn kind_for_content_type(content_type: &str) -> Option<FileKind> {
+    let mime = content_type.split(';').next().unwrap_or("").trim();
+    match mime {
+        "image/jpeg" | "image/png" | "image/gif" | "image/webp" => Some(FileKind::Image),
+        "audio/mpeg" | "audio/wav" | "audio/x-wav" | "audio/ogg" | "audio/webm" => Some(FileKind::Audio),
+        "application/pdf" => Some(FileKind::Pdf),
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+        | "text/html" => Some(FileKind::Document),
+        "text/plain" | "text/markdown" | "text/csv" | "application/json" => Some(FileKind::Text),
+        _ => None,
+    }
+}
+
+// Checks magic bytes so a renamed executable can't pass as an image
+fn content_matches(content_type: &str, data: &[u8]) -> bool {
+    let mime = content_type.split(';').next().unwrap_or("").trim();
+    match mime {
+        "image/jpeg" => data.starts_with(&[0xFF, 0xD8, 0xFF]),
+        "image/png" => data.starts_with(b"\x89PNG\r\n\x1a\n"),
+        "image/gif" => data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a"),
+        "image/webp" => data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP",
+        "audio/wav" | "audio/x-wav" => data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WAVE",
+        "audio/ogg" => data.starts_with(b"OggS"),
+        "audio/mpeg" => data.starts_with(b"ID3") || (data.len() >= 2 && data[0] == 0xFF && data[1] & 0xE0 == 0xE0),
+        "audio/webm" => data.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]),
+        "application/pdf" => data.starts_with(b"%PDF-"),
+        // docx is a zip container
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => data.starts_with(b"PK\x03\x04"),
+        _ => std::str::from_utf8(data).is_ok(),
+    }
+}
+
+pub struct FileStore {
+    config: FileStoreConfig,
+    backend: Arc<dyn StorageBackend>,
+    index: Arc<RwLock<HashMap<String, StoredFile>>>,
+}
+
+impl FileStore {
+    pub async fn new(config: FileStoreConfig, backend: Arc<dyn StorageBackend>) -> Result<Self> {
+        let mut index = HashMap::new();
+        for key in backend.list("meta").await? {
+            let data = backend.get(&key).await?;
+            match serde_json::from_slice::<StoredFile>(&data) {
+                Ok(file) => {
+                    index.insert(file.id.clone(), file);
+                }
+                Err(e) => log::warn!("Skipping unreadable file metadata {}: {}", key, e),
+            }
+        }
+
+        Ok(Self {
+            config,
+            backend,
+            index: Arc::new(RwLock::new(index)),
+        })
+    }
+
+    pub fn validate(&self, content_type: &str, data: &[u8]) -> Result<FileKind> {
+        let kind = kind_for_content_type(content_type)
+            .ok_or_else(|| anyhow::anyhow!("Unsupported content type: {}", content_type))?;
+
+        if let Some(max) = self.config.max_size_bytes.get(&kind) {
+            if data.len() as u64 > *max {
+                return Err(anyhow::anyhow!("File exceeds {} byte limit for {:?}", max, kind));
+            }
+        }
+        if data.is_empty() || !content_matches(content_type, data) {
+            return Err(anyhow::anyhow!("File content does not match {}", content_type));
+        }
+
+        Ok(kind)
+    }
+
+    pub fn max_size(&self) -> u64 {
+        self.config.max_size_bytes.values().copied().max().unwrap_or(0)
+    }
+
+    pub async fn save(
+        &self,
+        filename: &str,
+        content_type: &str,
+        data: &[u8],
+        owner: &str,
+        tenant: Option<&str>,
+    ) -> Result<StoredFile> {
+        let kind = self.validate(content_type, data)?;
+
+        let file = StoredFile {
+            id: format!("file-{}", Uuid::new_v4().simple()),
+            filename: sanitize_filename(filename),
+            content_type: content_type.to_string(),
+            kind,
+            size: data.len() as u64,
+            sha256: hex::encode(Sha256::digest(data)),
+            created_at: Utc::now(),
+            owner: Some(owner.to_string()),
+            tenant: tenant.map(str::to_string),
+        };
+
+        self.backend.put(&format!("data/{}", file.id), data).await?;
+        self.backend.put(&format!("meta/{}.json", file.id), &serde_json::to_vec(&file)?).await?;
+        self.index.write().await.insert(file.id.clone(), file.clone());
+
+        Ok(file)
+    }
+
+    pub async fn get(&self, id: &str) -> Option<StoredFile> {
+        self.index.read().await.get(id).cloned()
+    }
+
+    pub async fn read(&self, id: &str) -> Result<(StoredFile, Vec<u8>)> {
+        let file = self.get(id).await
+            .ok_or_else(|| anyhow::anyhow!("File not found: {}", id))?;
+        let data = self.backend.get(&format!("data/{}", id)).await?;
+        Ok((file, data))
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        if self.index.write().await.remove(id).is_none() {
+            return Err(anyhow::anyhow!("File not found: {}", id));
+        }
+        self.backend.delete(&format!("data/{}", id)).await?;
+        self.backend.delete(&format!("meta/{}.json", id)).await
+    }
+}
+
+fn sanitize_filename(filename: &str) -> String {
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or("");
+    let cleaned: String = name.chars()
+        .filter(|c| !c.is_control())
+        .take(255)
+        .collect();
+    if cleaned.is_empty() { "upload".to_string() } else { cleaned }
+}
+
+impl FileStore {
+    // Uploads belong to identities and tenants, never to a session
+    async fn subject_files(&self, subject: &DataSubject) -> Vec<StoredFile> {
+        self.index.read().await
+            .values()
+            .filter(|file| match subject {
+                DataSubject::Session(_) => false,
+                DataSubject::Identity(owner) => file.owner.as_ref() == Some(owner),
+                DataSubject::Tenant(tenant) => file.tenant.as_ref() == Some(tenant),
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+#[async_trait]
+impl DataStore for FileStore {
+    fn category(&self) -> DataCategory {
+        DataCategory::Recordings
+    }
+
+    // Metadata only; each entry links to its content
+    async fn export(&self, subject: &DataSubject) -> Result<serde_json::Value> {
+        let files: Vec<serde_json::Value> = self.subject_files(subject).await
+            .into_iter()
+            .map(|file| serde_json::json!({
+                "content": format!("/v1/files/{}/content", file.id),
+                "file": file,
+            }))
+            .collect();
+        Ok(serde_json::Value::Array(files))
+    }
+
+    async fn delete(&self, subject: &DataSubject) -> Result<u64> {
+        let files = self.subject_files(subject).await;
+        for file in &files {
+            FileStore::delete(self, &file.id).await?;
+        }
+        Ok(files.len() as u64)
+    }
+}
@@ -0,0 +1,330 @@
+#![cfg(feature = "vision")]
+
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::RwLock;
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::core::llm::latency::percentile;
+use crate::vision::detector::Detection;
+
+// Rolling per-stream frame statistics behind GET /v1/streams/{id}/stats.
+// The processor reports how long each frame took to decode, pipeline
+// workers report the time spent in detection and inference stages, the
+// detections found, and frames dropped on the way, including frames a
+// push stream refused because its buffer was full. Samples are kept for
+// retention_secs and summarised over whichever window is asked for.
+//
+// Streams are keyed by their configured id (FrameMetadata::stream_id).
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamStatsConfig {
+    pub retention_secs: i64,
+    // Windows reported when the request doesn't pick one
+    pub windows: Vec<String>,
+    // Per stream and kind of sample; the oldest go first
+    pub max_samples: usize,
+    // A stream counts as down once frames stop for longer than this
+    pub gap_tolerance_secs: i64,
+}
+
+impl Default for StreamStatsConfig {
+    fn default() -> Self {
+        Self {
+            retention_secs: 3600,
+            windows: vec!["1m".to_string(), "5m".to_string(), "15m".to_string(), "1h".to_string()],
+            max_samples: 200_000,
+            gap_tolerance_secs: 5,
+        }
+    }
+}
+
+// "30s", "5m", "1h", "1d"
+pub fn parse_window(window: &str) -> Result<Duration> {
+    let window = window.trim();
+    let split = window.find(|c: char| !c.is_ascii_digit()).unwrap_or(window.len());
+    let (amount, unit) = window.split_at(split);
+    let amount: i64 = amount.parse()
+        .map_err(|_| anyhow::anyhow!("Invalid window: {}", window))?;
+    let duration = match unit {
+        "s" => Duration::seconds(amount),
+        "m" => Duration::minutes(amount),
+        "h" => Duration::hours(amount),
+        "d" => Duration::days(amount),
+        _ => return Err(anyhow::anyhow!("Invalid window unit in {}; use s, m, h or d", window)),
+    };
+    if amount == 0 {
+        return Err(anyhow::anyhow!("Window must be longer than zero"));
+    }
+    Ok(duration)
+}
+
+#[derive(Debug, Clone)]
+struct FrameSample {
+    at: DateTime<Utc>,
+    inference_ms: f64,
+    classes: Vec<(String, u32)>,
+}
+
+#[derive(Debug, Default)]
+struct StreamSeries {
+    decodes: VecDeque<(DateTime<Utc>, f64)>,
+    frames: VecDeque<FrameSample>,
+    drops: VecDeque<(DateTime<Utc>, String)>,
+    first_seen: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencySummary {
+    pub count: usize,
+    pub mean_ms: Option<f64>,
+    pub p50_ms: Option<f64>,
+    pub p90_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+    pub p99_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassRate {
+    pub detections: u64,
+    pub per_frame: f64,
+    pub per_minute: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DropSummary {
+    pub total: u64,
+    // Of frames that entered the pipeline
+    pub rate: f64,
+    pub by_reason: HashMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UptimeSummary {
+    // Share of the window covered by frames arriving no further apart than
+    // gap_tolerance_secs
+    pub ratio: f64,
+    pub online_since: Option<DateTime<Utc>>,
+    pub last_frame_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamStatsReport {
+    pub stream_id: String,
+    pub window: String,
+    pub window_secs: i64,
+    pub fps: f64,
+    pub decoded_frames: u64,
+    pub processed_frames: u64,
+    pub decode_latency: LatencySummary,
+    pub inference_latency: LatencySummary,
+    pub detections: HashMap<String, ClassRate>,
+    pub drops: DropSummary,
+    pub uptime: UptimeSummary,
+}
+
+pub struct StreamStats {
+    config: StreamStatsConfig,
+    series: RwLock<HashMap<String, StreamSeries>>,
+}
+
+impl StreamStats {
+    pub fn new(config: StreamStatsConfig) -> Self {
+        Self {
+            config,
+            series: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &StreamStatsConfig {
+        &self.config
+    }
+
+    pub async fn record_decode(&self, stream: &str, decode_ms: f64) {
+        self.record_decode_at(stream, decode_ms, Utc::now()).await;
+    }
+
+    pub async fn record_decode_at(&self, stream: &str, decode_ms: f64, at: DateTime<Utc>) {
+        let mut series = self.series.write().await;
+        let entry = series.entry(stream.to_string()).or_default();
+        entry.first_seen.get_or_insert(at);
+        entry.decodes.push_back((at, decode_ms));
+        self.trim(entry, at);
+    }
+
+    // A frame that made it through every stage
+    pub async fn record_frame(&self, stream: &str, inference_ms: f64, detections: &[Detection]) {
+        self.record_frame_at(stream, inference_ms, detections, Utc::now()).await;
+    }
+
+    pub async fn record_frame_at(&self, stream: &str, inference_ms: f64, detections: &[Detection], at: DateTime<Utc>) {
+        let mut classes: Vec<(String, u32)> = Vec::new();
+        for detection in detections {
+            match classes.iter_mut().find(|(name, _)| *name == detection.class_name) {
+                Some((_, count)) => *count += 1,
+                None => classes.push((detection.class_name.clone(), 1)),
+            }
+        }
+
+        let mut series = self.series.write().await;
+        let entry = series.entry(stream.to_string()).or_default();
+        entry.first_seen.get_or_insert(at);
+        entry.frames.push_back(FrameSample { at, inference_ms, classes });
+        self.trim(entry, at);
+    }
+
+    pub async fn record_drop(&self, stream: &str, reason: &str) {
+        self.record_drop_at(stream, reason, Utc::now()).await;
+    }
+
+    pub async fn record_drop_at(&self, stream: &str, reason: &str, at: DateTime<Utc>) {
+        let mut series = self.series.write().await;
+        let entry = series.entry(stream.to_string()).or_default();
+        entry.first_seen.get_or_insert(at);
+        entry.drops.push_back((at, reason.to_string()));
+        self.trim(entry, at);
+    }
+
+    fn trim(&self, series: &mut StreamSeries, now: DateTime<Utc>) {
+        let cutoff = now - Duration::seconds(self.config.retention_secs);
+        let max = self.config.max_samples.max(1);
+        while series.decodes.front().is_some_and(|(at, _)| *at < cutoff) || series.decodes.len() > max {
+            series.decodes.pop_front();
+        }
+        while series.frames.front().is_some_and(|f| f.at < cutoff) || series.frames.len() > max {
+            series.frames.pop_front();
+        }
+        while series.drops.front().is_some_and(|(at, _)| *at < cutoff) || series.drops.len() > max {
+            series.drops.pop_front();
+        }
+    }
+
+    pub async fn streams(&self) -> Vec<String> {
+        let mut streams: Vec<String> = self.series.read().await.keys().cloned().collect();
+        streams.sort();
+        streams
+    }
+
+    pub async fn remove(&self, stream: &str) {
+        self.series.write().await.remove(stream);
+    }
+
+    pub fn check_window(&self, window: &str) -> Result<Duration> {
+        let duration = parse_window(window)?;
+        if duration > Duration::seconds(self.config.retention_secs) {
+            return Err(anyhow::anyhow!(
+                "Window {} is longer than the {}s of samples kept", window, self.config.retention_secs
+            ));
+        }
+        Ok(duration)
+    }
+
+    // None when nothing has been recorded for the stream
+    pub async fn report(&self, stream: &str, window: &str) -> Result<Option<StreamStatsReport>> {
+        self.report_at(stream, window, Utc::now()).await
+    }
+
+    pub async fn report_at(&self, stream: &str, window: &str, now: DateTime<Utc>) -> Result<Option<StreamStatsReport>> {
+        let duration = self.check_window(window)?;
+        let series = self.series.read().await;
+        let Some(series) = series.get(stream) else {
+            return Ok(None);
+        };
+        let since = now - duration;
+        // A stream younger than the window is measured over its lifetime
+        let observed_since = series.first_seen.map_or(since, |first| first.max(since));
+        let observed_secs = ((now - observed_since).num_milliseconds() as f64 / 1000.0).max(1.0);
+
+        let decode_ms: Vec<f64> = series.decodes.iter().filter(|(at, _)| *at >= since).map(|(_, ms)| *ms).collect();
+        let frames: Vec<&FrameSample> = series.frames.iter().filter(|f| f.at >= since).collect();
+        let drops: Vec<&(DateTime<Utc>, String)> = series.drops.iter().filter(|(at, _)| *at >= since).collect();
+
+        let mut detections: HashMap<String, u64> = HashMap::new();
+        for frame in &frames {
+            for (class, count) in &frame.classes {
+                *detections.entry(class.clone()).or_default() += *count as u64;
+            }
+        }
+        let detections = detections.into_iter()
+            .map(|(class, count)| (class, ClassRate {
+                detections: count,
+                per_frame: count as f64 / frames.len().max(1) as f64,
+                per_minute: count as f64 * 60.0 / observed_secs,
+            }))
+            .collect();
+
+        let mut by_reason: HashMap<String, u64> = HashMap::new();
+        for (_, reason) in &drops {
+            *by_reason.entry(reason.clone()).or_default() += 1;
+        }
+        let entered = frames.len() + drops.len();
+
+        let arrivals: Vec<DateTime<Utc>> = frames.iter().map(|f| f.at)
+            .chain(drops.iter().map(|(at, _)| *at))
+            .collect();
+
+        Ok(Some(StreamStatsReport {
+            stream_id: stream.to_string(),
+            window: window.to_string(),
+            window_secs: duration.num_seconds(),
+            fps: frames.len() as f64 / observed_secs,
+            decoded_frames: decode_ms.len() as u64,
+            processed_frames: frames.len() as u64,
+            decode_latency: summarize(decode_ms),
+            inference_latency: summarize(frames.iter().map(|f| f.inference_ms).collect()),
+            detections,
+            drops: DropSummary {
+                total: drops.len() as u64,
+                rate: if entered == 0 { 0.0 } else { drops.len() as f64 / entered as f64 },
+                by_reason,
+            },
+            uptime: self.uptime(arrivals, since, now),
+        }))
+    }
+
+    fn uptime(&self, mut arrivals: Vec<DateTime<Utc>>, since: DateTime<Utc>, now: DateTime<Utc>) -> UptimeSummary {
+        arrivals.sort();
+        let tolerance = Duration::seconds(self.config.gap_tolerance_secs.max(0));
+        let Some(&last) = arrivals.last() else {
+            return UptimeSummary { ratio: 0.0, online_since: None, last_frame_at: None };
+        };
+
+        // Each arrival keeps the stream up for `tolerance` after it
+        let mut covered = Duration::zero();
+        let mut run_start = arrivals[0];
+        let mut run_end = arrivals[0] + tolerance;
+        for &at in &arrivals[1..] {
+            if at > run_end {
+                covered = covered + (run_end - run_start);
+                run_start = at;
+            }
+            run_end = run_end.max(at + tolerance);
+        }
+        covered = covered + (run_end.min(now) - run_start);
+
+        let window = (now - since).num_milliseconds().max(1) as f64;
+        let online = now - last <= tolerance;
+        UptimeSummary {
+            ratio: (covered.num_milliseconds() as f64 / window).clamp(0.0, 1.0),
+            online_since: online.then_some(run_start),
+            last_frame_at: Some(last),
+        }
+    }
+}
+
+fn summarize(mut samples: Vec<f64>) -> LatencySummary {
+    samples.sort_by(|a, b| a.total_cmp(b));
+    let mean = (!samples.is_empty()).then(|| samples.iter().sum::<f64>() / samples.len() as f64);
+    LatencySummary {
+        count: samples.len(),
+        mean_ms: mean,
+        p50_ms: percentile(&samples, 0.50),
+        p90_ms: percentile(&samples, 0.90),
+        p95_ms: percentile(&samples, 0.95),
+        p99_ms: percentile(&samples, 0.99),
+        max_ms: samples.last().copied(),
+    }
+}
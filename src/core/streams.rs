@@ -0,0 +1,218 @@
+#![cfg(feature = "vision")]
+
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use crate::core::diff::OutputMode;
+use crate::core::geo::GeoPoint;
+use crate::core::pipeline::Pipeline;
+use crate::core::profiles::{ProfileStore, StreamProfile};
+use crate::vision::conditions::SceneCondition;
+use crate::vision::ptz::{PtzConfig, PtzManager};
+use crate::vision::sources::{deserialize_source, SourceConfig};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamConfig {
+    pub id: String,
+    // Accepts a typed source or a bare file/RTSP URI
+    #[serde(deserialize_with = "deserialize_source")]
+    pub source: SourceConfig,
+    pub profile: String,
+    pub enabled: bool,
+    #[serde(default)]
+    pub ptz: Option<PtzConfig>,
+    #[serde(default)]
+    pub geo: Option<GeoPoint>,
+    #[serde(default)]
+    pub output: OutputMode,
+}
+
+impl StreamConfig {
+    // Checks the stream on its own; the profile is checked by the registry
+    pub fn validate(&self) -> Result<()> {
+        self.source.validate()
+            .map_err(|e| anyhow::anyhow!("Stream {} has an invalid source: {}", self.id, e))?;
+        if let Some(geo) = &self.geo {
+            geo.validate()?;
+        }
+        Ok(())
+    }
+
+    // For API responses, without the PTZ password
+    pub fn redacted(&self) -> Self {
+        Self { ptz: self.ptz.as_ref().map(PtzConfig::redacted), ..self.clone() }
+    }
+}
+
+#[derive(Debug)]
+pub enum DeleteProfileError {
+    // Streams still using the profile
+    InUse(Vec<String>),
+    Failed(anyhow::Error),
+}
+
+impl std::fmt::Display for DeleteProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeleteProfileError::InUse(streams) => write!(f, "Profile is used by {}", streams.join(", ")),
+            DeleteProfileError::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+pub struct StreamRegistry {
+    streams: Arc<RwLock<HashMap<String, StreamConfig>>>,
+    profiles: Arc<ProfileStore>,
+    // Receives each stream's output mode as streams are added or replaced
+    pipeline: Option<Arc<Pipeline>>,
+    // Gets a controller for each stream with a `ptz` section
+    ptz: Option<Arc<PtzManager>>,
+    // Latest scene condition seen on each stream
+    conditions: RwLock<HashMap<String, SceneCondition>>,
+}
+
+impl StreamRegistry {
+    pub fn new(profiles: Arc<ProfileStore>) -> Self {
+        Self {
+            streams: Arc::new(RwLock::new(HashMap::new())),
+            profiles,
+            pipeline: None,
+            ptz: None,
+            conditions: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_pipeline(mut self, pipeline: Arc<Pipeline>) -> Self {
+        self.pipeline = Some(pipeline);
+        self
+    }
+
+    pub fn with_ptz(mut self, ptz: Arc<PtzManager>) -> Self {
+        self.ptz = Some(ptz);
+        self
+    }
+
+    pub fn profiles(&self) -> &Arc<ProfileStore> {
+        &self.profiles
+    }
+
+    // The profile is checked with the streams locked, so it can't be
+    // deleted between the check and the insert
+    pub async fn add(&self, stream: StreamConfig) -> Result<()> {
+        let mut streams = self.streams.write().await;
+        self.check(&stream).await?;
+        if streams.contains_key(&stream.id) {
+            return Err(anyhow::anyhow!("Stream already exists: {}", stream.id));
+        }
+        self.apply_ptz(&stream.id, stream.ptz.as_ref()).await?;
+        self.apply_output(&stream.id, stream.output.clone()).await;
+        streams.insert(stream.id.clone(), stream);
+        Ok(())
+    }
+
+    // Adds the stream or replaces the one with the same id, returning the
+    // replaced config
+    pub async fn put(&self, stream: StreamConfig) -> Result<Option<StreamConfig>> {
+        let mut streams = self.streams.write().await;
+        self.check(&stream).await?;
+        self.apply_ptz(&stream.id, stream.ptz.as_ref()).await?;
+        self.apply_output(&stream.id, stream.output.clone()).await;
+        Ok(streams.insert(stream.id.clone(), stream))
+    }
+
+    async fn apply_ptz(&self, id: &str, config: Option<&PtzConfig>) -> Result<()> {
+        match &self.ptz {
+            Some(ptz) => ptz.configure(id, config).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn apply_output(&self, id: &str, mode: OutputMode) {
+        if let Some(pipeline) = &self.pipeline {
+            pipeline.set_output_mode(id, mode).await;
+        }
+    }
+
+    async fn check(&self, stream: &StreamConfig) -> Result<()> {
+        if self.profiles.get(&stream.profile).await.is_none() {
+            return Err(anyhow::anyhow!(
+                "Stream {} references unknown profile: {}", stream.id, stream.profile
+            ));
+        }
+        stream.validate()
+    }
+
+    pub async fn remove(&self, id: &str) -> Option<StreamConfig> {
+        let mut streams = self.streams.write().await;
+        let removed = streams.remove(id)?;
+        if let Some(ptz) = &self.ptz {
+            ptz.unregister(id).await;
+        }
+        self.conditions.write().await.remove(id);
+        self.apply_output(id, OutputMode::Full).await;
+        Some(removed)
+    }
+
+    pub async fn get(&self, id: &str) -> Option<StreamConfig> {
+        self.streams.read().await.get(id).cloned()
+    }
+
+    pub async fn list(&self) -> Vec<StreamConfig> {
+        self.streams.read().await.values().cloned().collect()
+    }
+
+    // Deletes the profile unless a stream uses it. Streams stay locked
+    // throughout, so none can start using it in between.
+    pub async fn delete_profile(&self, name: &str) -> std::result::Result<(), DeleteProfileError> {
+        let streams = self.streams.read().await;
+        let mut in_use: Vec<String> = streams.values()
+            .filter(|s| s.profile == name)
+            .map(|s| s.id.clone())
+            .collect();
+        if !in_use.is_empty() {
+            in_use.sort();
+            return Err(DeleteProfileError::InUse(in_use));
+        }
+        self.profiles.delete(name).await.map_err(DeleteProfileError::Failed)
+    }
+
+    pub async fn streams_using_profile(&self, profile: &str) -> Vec<String> {
+        self.streams.read().await
+            .values()
+            .filter(|s| s.profile == profile)
+            .map(|s| s.id.clone())
+            .collect()
+    }
+
+    // Reported by the engine as frames are analyzed
+    pub async fn set_condition(&self, id: &str, condition: SceneCondition) {
+        if self.conditions.read().await.get(id) == Some(&condition) {
+            return;
+        }
+        self.conditions.write().await.insert(id.to_string(), condition);
+    }
+
+    pub async fn condition(&self, id: &str) -> Option<SceneCondition> {
+        self.conditions.read().await.get(id).copied()
+    }
+
+    // Profiles are resolved at call time so profile edits apply on the
+    // next (re)start of the stream. The profile comes adapted to the
+    // stream's current scene condition, if one has been seen.
+    pub async fn resolve(&self, id: &str) -> Result<(StreamConfig, StreamProfile)> {
+        let stream = self.get(id).await
+            .ok_or_else(|| anyhow::anyhow!("Stream not found: {}", id))?;
+        let profile = self.profiles.get(&stream.profile).await
+            .ok_or_else(|| anyhow::anyhow!(
+                "Stream {} references unknown profile: {}", id, stream.profile
+            ))?;
+        let profile = match self.condition(id).await {
+            Some(condition) => profile.adapted_for(condition),
+            None => profile,
+        };
+        Ok((stream, profile))
+    }
+}
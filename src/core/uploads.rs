@@ -0,0 +1,354 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::core::jobs::{Job, JobHandle, JobManager};
+
+pub const BATCH_VIDEO_JOB: &str = "video.batch";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadConfig {
+    pub upload_dir: String,
+    pub max_size_bytes: u64,
+    pub max_chunk_bytes: u64,
+    // Incomplete uploads untouched for this long are discarded
+    pub expire_after_secs: i64,
+    pub allowed_extensions: Vec<String>,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            upload_dir: String::from("uploads"),
+            max_size_bytes: 50 * 1024 * 1024 * 1024,
+            max_chunk_bytes: 64 * 1024 * 1024,
+            expire_after_secs: 24 * 3600,
+            allowed_extensions: vec![
+                "mp4".to_string(), "mkv".to_string(), "mov".to_string(),
+                "avi".to_string(), "ts".to_string(), "webm".to_string(),
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateUpload {
+    pub filename: String,
+    pub size: u64,
+    // Hex SHA-256 of the whole file, checked once the last byte arrives
+    pub sha256: String,
+    #[serde(default)]
+    pub stream_profile: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadStatus {
+    Receiving,
+    Verifying,
+    Complete,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadSession {
+    pub id: String,
+    // Subject of the principal that started the upload
+    #[serde(default)]
+    pub owner: String,
+    pub filename: String,
+    pub size: u64,
+    pub offset: u64,
+    pub sha256: String,
+    pub stream_profile: Option<String>,
+    pub status: UploadStatus,
+    pub error: Option<String>,
+    pub job_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompletedUpload {
+    pub session: UploadSession,
+    pub path: PathBuf,
+}
+
+// Runs the batch analysis for a fully received video
+#[async_trait]
+pub trait UploadProcessor: Send + Sync {
+    async fn process(&self, upload: CompletedUpload, handle: JobHandle) -> Result<()>;
+}
+
+#[derive(Debug)]
+pub enum ChunkError {
+    NotFound,
+    // Client must resume from the returned offset
+    OffsetMismatch(u64),
+    Invalid(String),
+    Internal(anyhow::Error),
+}
+
+impl std::fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkError::NotFound => write!(f, "Upload not found"),
+            ChunkError::OffsetMismatch(offset) => write!(f, "Offset mismatch, expected {}", offset),
+            ChunkError::Invalid(message) => write!(f, "{}", message),
+            ChunkError::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+// Each session is kept next to its data as `<id>.json`, so uploads can be
+// resumed across restarts
+pub struct UploadManager {
+    config: UploadConfig,
+    sessions: Arc<RwLock<HashMap<String, UploadSession>>>,
+    // Serializes writers per upload so concurrent PATCHes can't interleave
+    locks: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+    jobs: Arc<JobManager>,
+    processor: Arc<dyn UploadProcessor>,
+}
+
+impl UploadManager {
+    pub async fn new(config: UploadConfig, jobs: Arc<JobManager>, processor: Arc<dyn UploadProcessor>) -> Result<Self> {
+        tokio::fs::create_dir_all(&config.upload_dir).await
+            .context("Failed to create upload directory")?;
+        let sessions = load_sessions(&config.upload_dir).await?;
+
+        Ok(Self {
+            config,
+            sessions: Arc::new(RwLock::new(sessions)),
+            locks: Mutex::new(HashMap::new()),
+            jobs,
+            processor,
+        })
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        PathBuf::from(&self.config.upload_dir).join(format!("{}.part", id))
+    }
+
+    fn session_path(&self, id: &str) -> PathBuf {
+        PathBuf::from(&self.config.upload_dir).join(format!("{}.json", id))
+    }
+
+    // Written to a temporary file and renamed over the old one, so a crash
+    // never leaves a half-written session behind
+    async fn save(&self, session: &UploadSession) -> Result<()> {
+        let path = self.session_path(&session.id);
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec(session)?).await
+            .context("Failed to write upload session")?;
+        tokio::fs::rename(&tmp, &path).await
+            .context("Failed to write upload session")?;
+        Ok(())
+    }
+
+    pub async fn create(&self, request: CreateUpload, owner: &str) -> Result<UploadSession> {
+        let extension = request.filename.rsplit('.').next().unwrap_or("").to_lowercase();
+        if !self.config.allowed_extensions.contains(&extension) {
+            return Err(anyhow::anyhow!("Unsupported video type: .{}", extension));
+        }
+        if request.size == 0 || request.size > self.config.max_size_bytes {
+            return Err(anyhow::anyhow!("Upload size must be between 1 and {} bytes", self.config.max_size_bytes));
+        }
+        if request.sha256.len() != 64 || !request.sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(anyhow::anyhow!("sha256 must be a 64 character hex digest"));
+        }
+
+        let now = Utc::now();
+        let session = UploadSession {
+            id: Uuid::new_v4().to_string(),
+            owner: owner.to_string(),
+            filename: request.filename,
+            size: request.size,
+            offset: 0,
+            sha256: request.sha256.to_lowercase(),
+            stream_profile: request.stream_profile,
+            status: UploadStatus::Receiving,
+            error: None,
+            job_id: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        tokio::fs::File::create(self.path(&session.id)).await
+            .context("Failed to create upload file")?;
+        self.save(&session).await?;
+        self.sessions.write().await.insert(session.id.clone(), session.clone());
+        Ok(session)
+    }
+
+    pub async fn get(&self, id: &str) -> Option<UploadSession> {
+        self.sessions.read().await.get(id).cloned()
+    }
+
+    pub async fn append(&self, id: &str, offset: u64, data: &[u8]) -> std::result::Result<UploadSession, ChunkError> {
+        let lock = self.locks.lock().await
+            .entry(id.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(())))
+            .clone();
+        let _guard = lock.lock().await;
+
+        let mut session = self.get(id).await.ok_or(ChunkError::NotFound)?;
+        if session.status != UploadStatus::Receiving {
+            return Err(ChunkError::Invalid(format!("Upload is {:?}", session.status)));
+        }
+        if offset != session.offset {
+            return Err(ChunkError::OffsetMismatch(session.offset));
+        }
+        if data.len() as u64 > self.config.max_chunk_bytes {
+            return Err(ChunkError::Invalid(format!("Chunk exceeds {} bytes", self.config.max_chunk_bytes)));
+        }
+        if session.offset + data.len() as u64 > session.size {
+            return Err(ChunkError::Invalid("Chunk extends past declared size".to_string()));
+        }
+
+        // A chunk that failed part-way left bytes past the recorded offset;
+        // cut them off so the retried chunk lands where the client thinks
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .open(self.path(id))
+            .await
+            .map_err(|e| ChunkError::Internal(e.into()))?;
+        file.set_len(session.offset).await.map_err(|e| ChunkError::Internal(e.into()))?;
+        file.seek(std::io::SeekFrom::Start(session.offset)).await.map_err(|e| ChunkError::Internal(e.into()))?;
+        file.write_all(data).await.map_err(|e| ChunkError::Internal(e.into()))?;
+        file.sync_data().await.map_err(|e| ChunkError::Internal(e.into()))?;
+
+        session.offset += data.len() as u64;
+        session.updated_at = Utc::now();
+        if session.offset == session.size {
+            session.status = UploadStatus::Verifying;
+        }
+        // The data is on disk before the offset that covers it
+        self.save(&session).await.map_err(ChunkError::Internal)?;
+        self.sessions.write().await.insert(id.to_string(), session.clone());
+
+        if session.status == UploadStatus::Verifying {
+            return self.finish(session).await.map_err(ChunkError::Internal);
+        }
+        Ok(session)
+    }
+
+    async fn finish(&self, mut session: UploadSession) -> Result<UploadSession> {
+        let path = self.path(&session.id);
+        let digest = file_sha256(&path).await?;
+
+        if digest != session.sha256 {
+            log::warn!("Upload {} failed checksum validation", session.id);
+            session.status = UploadStatus::Failed;
+            session.error = Some(format!("Checksum mismatch: got {}", digest));
+            tokio::fs::remove_file(&path).await.ok();
+        } else {
+            let completed = CompletedUpload { session: session.clone(), path };
+            let processor = self.processor.clone();
+            let job: Job = self.jobs.spawn(BATCH_VIDEO_JOB, move |handle| async move {
+                processor.process(completed, handle).await
+            }).await;
+
+            session.status = UploadStatus::Complete;
+            session.job_id = Some(job.id);
+        }
+
+        session.updated_at = Utc::now();
+        self.save(&session).await?;
+        self.sessions.write().await.insert(session.id.clone(), session.clone());
+        Ok(session)
+    }
+
+    pub async fn cancel(&self, id: &str) -> Result<()> {
+        let session = self.sessions.write().await.remove(id)
+            .ok_or_else(|| anyhow::anyhow!("Upload not found: {}", id))?;
+        self.locks.lock().await.remove(id);
+        if session.status != UploadStatus::Complete {
+            tokio::fs::remove_file(self.path(id)).await.ok();
+        }
+        tokio::fs::remove_file(self.session_path(id)).await.ok();
+        Ok(())
+    }
+
+    pub fn start_cleanup(self: &Arc<Self>) {
+        let manager = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(600));
+            loop {
+                interval.tick().await;
+                let cutoff = Utc::now() - chrono::Duration::seconds(manager.config.expire_after_secs);
+                let expired: Vec<String> = manager.sessions.read().await.values()
+                    .filter(|s| s.status == UploadStatus::Receiving && s.updated_at < cutoff)
+                    .map(|s| s.id.clone())
+                    .collect();
+
+                for id in expired {
+                    log::info!("Discarding stale upload {}", id);
+                    if let Err(e) = manager.cancel(&id).await {
+                        log::error!("Failed to discard upload {}: {}", id, e);
+                    }
+                }
+            }
+        });
+    }
+}
+
+// Sessions saved by an earlier run. Data files without a session are what
+// a crash during create() or cancel() leaves behind, and are removed.
+async fn load_sessions(upload_dir: &str) -> Result<HashMap<String, UploadSession>> {
+    let mut sessions = HashMap::new();
+    let mut parts = Vec::new();
+    let mut entries = tokio::fs::read_dir(upload_dir).await
+        .context("Failed to read upload directory")?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => {
+                let session = tokio::fs::read(&path).await.map_err(anyhow::Error::from)
+                    .and_then(|bytes| Ok(serde_json::from_slice::<UploadSession>(&bytes)?));
+                match session {
+                    Ok(session) => {
+                        sessions.insert(session.id.clone(), session);
+                    }
+                    Err(e) => log::warn!("Skipping unreadable upload session {}: {}", path.display(), e),
+                }
+            }
+            Some("part") => parts.push(path),
+            _ => {}
+        }
+    }
+
+    for part in parts {
+        let id = part.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        if !sessions.contains_key(id) {
+            log::info!("Removing orphaned upload data {}", part.display());
+            tokio::fs::remove_file(&part).await.ok();
+        }
+    }
+    Ok(sessions)
+}
+
+async fn file_sha256(path: &PathBuf) -> Result<String> {
+    let mut file = tokio::fs::File::open(path).await
+        .context("Failed to open upload for verification")?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
+
+use crate::utils::pattern::wildcard_matches;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Meter {
+    Tokens,
+    Frames,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaPeriod {
+    Daily,
+    Monthly,
+}
+
+impl QuotaPeriod {
+    // Periods are calendar days and months in UTC
+    pub fn start(&self, at: DateTime<Utc>) -> NaiveDate {
+        let date = at.date_naive();
+        match self {
+            QuotaPeriod::Daily => date,
+            QuotaPeriod::Monthly => date.with_day(1).unwrap_or(date),
+        }
+    }
+
+    pub fn resets_at(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let start = self.start(at);
+        let next = match self {
+            QuotaPeriod::Daily => start.succ_opt().unwrap_or(start),
+            QuotaPeriod::Monthly => {
+                let (year, month) = if start.month() == 12 {
+                    (start.year() + 1, 1)
+                } else {
+                    (start.year(), start.month() + 1)
+                };
+                NaiveDate::from_ymd_opt(year, month, 1).unwrap_or(start)
+            }
+        };
+        Utc.from_utc_datetime(&next.and_hms_opt(0, 0, 0).unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuotaLimit {
+    pub meter: Meter,
+    pub period: QuotaPeriod,
+    pub limit: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct QuotaPlan {
+    pub name: String,
+    pub limits: Vec<QuotaLimit>,
+}
+
+// Requests to these paths are refused once the meter's budget is spent.
+// Same matching as rate limit rules: exact, or a prefix ending in '*'.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeteredPath {
+    pub path: String,
+    pub meter: Meter,
+}
+
+impl MeteredPath {
+    pub fn matches(&self, path: &str) -> bool {
+        wildcard_matches(&self.path, path)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageConfig {
+    pub enabled: bool,
+    pub persist: bool,
+    pub usage_file: String,
+    pub flush_interval_secs: u64,
+    pub default_plan: QuotaPlan,
+    // Tenant id -> plan; tenants not listed get the default plan
+    pub tenant_plans: HashMap<String, QuotaPlan>,
+    pub metered_paths: Vec<MeteredPath>,
+    // Held against the budget while a metered request runs, then settled
+    // to what it actually used. Capped at what is left, so a tenant with
+    // little budget can still make a call.
+    pub reserve_tokens: u64,
+    pub reserve_frames: u64,
+}
+
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            persist: false,
+            usage_file: "usage.json".to_string(),
+            flush_interval_secs: 30,
+            default_plan: QuotaPlan {
+                name: "default".to_string(),
+                limits: vec![
+                    QuotaLimit { meter: Meter::Tokens, period: QuotaPeriod::Monthly, limit: 5_000_000 },
+                    QuotaLimit { meter: Meter::Frames, period: QuotaPeriod::Daily, limit: 500_000 },
+                ],
+            },
+            tenant_plans: HashMap::new(),
+            metered_paths: vec![
+                MeteredPath { path: "/v1/agent/*".to_string(), meter: Meter::Tokens },
+                MeteredPath { path: "/v1/sessions/*".to_string(), meter: Meter::Tokens },
+                MeteredPath { path: "/v1/judge/*".to_string(), meter: Meter::Tokens },
+                MeteredPath { path: "/v1/frames/*".to_string(), meter: Meter::Frames },
+                MeteredPath { path: "/v1/vision/*".to_string(), meter: Meter::Frames },
+            ],
+            reserve_tokens: 2000,
+            reserve_frames: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaStatus {
+    pub meter: Meter,
+    pub period: QuotaPeriod,
+    pub limit: u64,
+    pub used: u64,
+    pub remaining: u64,
+    pub resets_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TenantQuota {
+    pub tenant: String,
+    pub plan: String,
+    pub quotas: Vec<QuotaStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UsageEntry {
+    tenant: String,
+    meter: Meter,
+    period: QuotaPeriod,
+    start: NaiveDate,
+    used: u64,
+}
+
+type UsageKey = (String, Meter, QuotaPeriod);
+
+// Running totals per tenant for the current day and month. Handlers and
+// background consumers report usage here; the quota middleware reads it.
+pub struct UsageLedger {
+    config: UsageConfig,
+    usage: RwLock<HashMap<UsageKey, (NaiveDate, u64)>>,
+    dirty: AtomicBool,
+}
+
+pub const ANONYMOUS_TENANT: &str = "anonymous";
+
+fn add(usage: &mut HashMap<UsageKey, (NaiveDate, u64)>, tenant: &str, meter: Meter, amount: u64, now: DateTime<Utc>) {
+    for period in [QuotaPeriod::Daily, QuotaPeriod::Monthly] {
+        let start = period.start(now);
+        let entry = usage.entry((tenant.to_string(), meter, period)).or_insert((start, 0));
+        if entry.0 != start {
+            *entry = (start, 0);
+        }
+        entry.1 = entry.1.saturating_add(amount);
+    }
+}
+
+fn status(usage: &HashMap<UsageKey, (NaiveDate, u64)>, tenant: &str, limit: &QuotaLimit, now: DateTime<Utc>) -> QuotaStatus {
+    let used = match usage.get(&(tenant.to_string(), limit.meter, limit.period)) {
+        Some((start, used)) if *start == limit.period.start(now) => *used,
+        _ => 0,
+    };
+    QuotaStatus {
+        meter: limit.meter,
+        period: limit.period,
+        limit: limit.limit,
+        used,
+        remaining: limit.limit.saturating_sub(used),
+        resets_at: limit.period.resets_at(now),
+    }
+}
+
+impl UsageLedger {
+    pub async fn new(config: UsageConfig) -> Result<Self> {
+        let mut usage = HashMap::new();
+        if config.persist && tokio::fs::try_exists(&config.usage_file).await? {
+            let contents = tokio::fs::read_to_string(&config.usage_file).await
+                .context("Failed to read usage file")?;
+            let entries: Vec<UsageEntry> = serde_json::from_str(&contents)
+                .context("Failed to parse usage file")?;
+            for entry in entries {
+                usage.insert((entry.tenant, entry.meter, entry.period), (entry.start, entry.used));
+            }
+        }
+
+        Ok(Self {
+            config,
+            usage: RwLock::new(usage),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    pub fn config(&self) -> &UsageConfig {
+        &self.config
+    }
+
+    pub fn plan_for(&self, tenant: &str) -> &QuotaPlan {
+        self.config.tenant_plans.get(tenant).unwrap_or(&self.config.default_plan)
+    }
+
+    pub fn meter_for_path(&self, path: &str) -> Option<Meter> {
+        self.config.metered_paths.iter().find(|m| m.matches(path)).map(|m| m.meter)
+    }
+
+    // Counted for both periods whether or not the plan limits them, so the
+    // usage endpoint can report it either way
+    pub async fn record(&self, tenant: &str, meter: Meter, amount: u64) {
+        if amount == 0 {
+            return;
+        }
+        let mut usage = self.usage.write().await;
+        add(&mut usage, tenant, meter, amount, Utc::now());
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    // Admission and the hold on the budget happen under one lock, so
+    // concurrent requests can't all pass the check before any of them is
+    // charged. Returns the amount held, to be passed to settle(), or the
+    // exhausted budget.
+    pub async fn reserve(&self, tenant: &str, meter: Meter) -> Result<u64, QuotaStatus> {
+        let now = Utc::now();
+        let mut usage = self.usage.write().await;
+        let tightest = self.plan_for(tenant).limits.iter()
+            .filter(|limit| limit.meter == meter)
+            .map(|limit| status(&usage, tenant, limit, now))
+            .min_by_key(|q| q.remaining);
+        let estimate = match meter {
+            Meter::Tokens => self.config.reserve_tokens,
+            Meter::Frames => self.config.reserve_frames,
+        };
+        let reserved = match tightest {
+            Some(status) if status.remaining == 0 => return Err(status),
+            Some(status) => estimate.min(status.remaining),
+            None => estimate,
+        };
+        add(&mut usage, tenant, meter, reserved, now);
+        self.dirty.store(true, Ordering::Relaxed);
+        Ok(reserved)
+    }
+
+    // Replaces a reservation with the actual usage, which may be more
+    pub async fn settle(&self, tenant: &str, meter: Meter, reserved: u64, actual: u64) {
+        if reserved == actual {
+            return;
+        }
+        let now = Utc::now();
+        let mut usage = self.usage.write().await;
+        if actual > reserved {
+            add(&mut usage, tenant, meter, actual - reserved, now);
+        } else {
+            for period in [QuotaPeriod::Daily, QuotaPeriod::Monthly] {
+                if let Some(entry) = usage.get_mut(&(tenant.to_string(), meter, period)) {
+                    if entry.0 == period.start(now) {
+                        entry.1 = entry.1.saturating_sub(reserved - actual);
+                    }
+                }
+            }
+        }
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    pub async fn used(&self, tenant: &str, meter: Meter, period: QuotaPeriod, at: DateTime<Utc>) -> u64 {
+        let usage = self.usage.read().await;
+        match usage.get(&(tenant.to_string(), meter, period)) {
+            Some((start, used)) if *start == period.start(at) => *used,
+            _ => 0,
+        }
+    }
+
+    pub async fn quota(&self, tenant: &str) -> TenantQuota {
+        let now = Utc::now();
+        let plan = self.plan_for(tenant);
+        let usage = self.usage.read().await;
+        TenantQuota {
+            tenant: tenant.to_string(),
+            plan: plan.name.clone(),
+            quotas: plan.limits.iter().map(|limit| status(&usage, tenant, limit, now)).collect(),
+        }
+    }
+
+    // The tightest budget on this meter, which is what the response headers
+    // report and what decides whether a request is refused
+    pub async fn tightest(&self, tenant: &str, meter: Meter) -> Option<QuotaStatus> {
+        self.quota(tenant).await.quotas.into_iter()
+            .filter(|q| q.meter == meter)
+            .min_by_key(|q| q.remaining)
+    }
+
+    pub async fn persist(&self) -> Result<()> {
+        if !self.config.persist || !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let entries: Vec<UsageEntry> = self.usage.read().await.iter()
+            .map(|((tenant, meter, period), (start, used))| UsageEntry {
+                tenant: tenant.clone(),
+                meter: *meter,
+                period: *period,
+                start: *start,
+                used: *used,
+            })
+            .collect();
+        let serialized = serde_json::to_string_pretty(&entries)?;
+        if let Err(e) = tokio::fs::write(&self.config.usage_file, serialized).await {
+            self.dirty.store(true, Ordering::Relaxed);
+            return Err(e).context("Failed to persist usage");
+        }
+        Ok(())
+    }
+
+    // Usage changes on nearly every request, so it is flushed on an
+    // interval rather than on each write
+    pub fn start_flush(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let ledger = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(
+                std::time::Duration::from_secs(ledger.config.flush_interval_secs.max(1)),
+            );
+            loop {
+                interval.tick().await;
+                if let Err(e) = ledger.persist().await {
+                    log::error!("{}", e);
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Duration, Utc};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchdogConfig {
+    pub enabled: bool,
+    pub check_interval_secs: u64,
+    // A stage that hasn't completed a frame for this long, while a worker
+    // has been inside it for as long, is considered stuck
+    pub stall_threshold_secs: i64,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            check_interval_secs: 5,
+            stall_threshold_secs: 30,
+        }
+    }
+}
+
+// What a worker is doing right now; cleared between frames
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkerActivity {
+    pub stage: String,
+    pub stage_index: usize,
+    pub frame_id: u64,
+    pub started_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StalledWorker {
+    pub worker_id: usize,
+    pub stage: String,
+    pub stage_index: usize,
+    pub frame_id: u64,
+    pub stuck_for: Duration,
+    pub last_processed: Option<DateTime<Utc>>,
+}
+
+pub fn find_stalled(
+    activity: &HashMap<usize, WorkerActivity>,
+    last_processed: &HashMap<String, DateTime<Utc>>,
+    threshold: Duration,
+    now: DateTime<Utc>,
+) -> Vec<StalledWorker> {
+    let mut stalled: Vec<StalledWorker> = activity.iter()
+        .filter(|(_, a)| now - a.started_at > threshold)
+        .filter(|(_, a)| last_processed.get(&a.stage).map_or(true, |t| now - *t > threshold))
+        .map(|(worker_id, a)| StalledWorker {
+            worker_id: *worker_id,
+            stage: a.stage.clone(),
+            stage_index: a.stage_index,
+            frame_id: a.frame_id,
+            stuck_for: now - a.started_at,
+            last_processed: last_processed.get(&a.stage).copied(),
+        })
+        .collect();
+
+    stalled.sort_by_key(|s| s.worker_id);
+    stalled
+}
+
+// Multi-line diagnostic in the spirit of a stack trace: where in the stage
+// chain the worker is parked and what it was holding
+pub fn describe_stall(stall: &StalledWorker, stage_names: &[String]) -> String {
+    let chain: Vec<String> = stage_names.iter()
+        .enumerate()
+        .map(|(i, name)| if i == stall.stage_index { format!("[{}]", name) } else { name.clone() })
+        .collect();
+
+    let last = stall.last_processed
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_else(|| "never".to_string());
+
+    format!(
+        "worker {} stuck in stage '{}' for {}s\n  frame: {}\n  chain: {}\n  stage last completed a frame: {}",
+        stall.worker_id,
+        stall.stage,
+        stall.stuck_for.num_seconds(),
+        stall.frame_id,
+        chain.join(" -> "),
+        last,
+    )
+}
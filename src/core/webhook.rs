@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::utils::egress::EgressClient;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const SIGNATURE_HEADER: &str = "X-VAE-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-VAE-Timestamp";
+pub const EVENT_ID_HEADER: &str = "X-VAE-Event-Id";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookEndpoint {
+    pub name: String,
+    pub url: String,
+    pub secret: String,
+    #[serde(default)]
+    pub event_types: Vec<String>,
+}
+
+impl WebhookEndpoint {
+    fn accepts(&self, event_type: &str) -> bool {
+        self.event_types.is_empty() || self.event_types.iter().any(|t| t == event_type)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub endpoints: Vec<WebhookEndpoint>,
+    pub timeout_ms: u64,
+    pub max_retries: u32,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            timeout_ms: 5000,
+            max_retries: 3,
+        }
+    }
+}
+
+// Signature over "<timestamp>.<event id>.<body>" so a captured body can't be
+// replayed with a different timestamp or under a fresh event id
+fn mac_for(secret: &str, timestamp: i64, event_id: &str, body: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(event_id.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+pub fn sign_payload(secret: &str, timestamp: i64, event_id: &str, body: &[u8]) -> String {
+    hex::encode(mac_for(secret, timestamp, event_id, body).finalize().into_bytes())
+}
+
+pub fn signature_header(secret: &str, timestamp: i64, event_id: &str, body: &[u8]) -> String {
+    format!("t={},v1={}", timestamp, sign_payload(secret, timestamp, event_id, body))
+}
+
+fn parse_signature_header(header: &str) -> Result<(i64, Vec<Vec<u8>>)> {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => {
+                timestamp = Some(value.parse::<i64>().context("Invalid signature timestamp")?);
+            }
+            Some(("v1", value)) => {
+                signatures.push(hex::decode(value).context("Invalid signature encoding")?);
+            }
+            _ => {}
+        }
+    }
+
+    let timestamp = timestamp.ok_or_else(|| anyhow::anyhow!("Signature header has no timestamp"))?;
+    if signatures.is_empty() {
+        return Err(anyhow::anyhow!("Signature header has no v1 signature"));
+    }
+    Ok((timestamp, signatures))
+}
+
+// Stateless check of a webhook delivery: signature validity and timestamp
+// within `tolerance` of `now`. Receivers should prefer `WebhookVerifier`,
+// which also rejects replayed event ids inside the window.
+pub fn verify_signature(
+    secret: &str,
+    header: &str,
+    event_id: &str,
+    body: &[u8],
+    tolerance: Duration,
+    now: i64,
+) -> Result<()> {
+    let (timestamp, signatures) = parse_signature_header(header)?;
+
+    if (now - timestamp).unsigned_abs() > tolerance.as_secs() {
+        return Err(anyhow::anyhow!("Webhook timestamp outside the replay window"));
+    }
+
+    for signature in signatures {
+        // verify_slice compares in constant time
+        if mac_for(secret, timestamp, event_id, body).verify_slice(&signature).is_ok() {
+            return Ok(());
+        }
+    }
+
+    Err(anyhow::anyhow!("Webhook signature mismatch"))
+}
+
+pub struct WebhookVerifier {
+    secret: String,
+    tolerance: Duration,
+    seen: Mutex<HashMap<String, i64>>,
+}
+
+impl WebhookVerifier {
+    pub fn new(secret: &str, tolerance: Duration) -> Self {
+        Self {
+            secret: secret.to_string(),
+            tolerance,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn verify(&self, event_id: &str, signature: &str, body: &[u8]) -> Result<()> {
+        let now = chrono::Utc::now().timestamp();
+        verify_signature(&self.secret, signature, event_id, body, self.tolerance, now)?;
+
+        let mut seen = self.seen.lock().unwrap();
+        let window = self.tolerance.as_secs() as i64;
+        seen.retain(|_, received| now - *received <= window);
+
+        if seen.contains_key(event_id) {
+            return Err(anyhow::anyhow!("Webhook event {} was already delivered", event_id));
+        }
+        seen.insert(event_id.to_string(), now);
+        Ok(())
+    }
+}
+
+pub struct WebhookSender {
+    config: WebhookConfig,
+    client: EgressClient,
+}
+
+impl WebhookSender {
+    pub fn new(config: WebhookConfig, client: EgressClient) -> Self {
+        Self { config, client }
+    }
+
+    pub async fn send<T: Serialize>(&self, event_type: &str, payload: &T) -> Result<()> {
+        self.send_with_id(event_type, &uuid::Uuid::new_v4().to_string(), payload).await
+    }
+
+    // For callers that may send the same event again and want receivers to
+    // recognise it as a repeat
+    pub async fn send_with_id<T: Serialize>(&self, event_type: &str, event_id: &str, payload: &T) -> Result<()> {
+        let body = serde_json::to_vec(&serde_json::json!({
+            "type": event_type,
+            "data": payload,
+        }))?;
+
+        let mut failures = Vec::new();
+        for endpoint in self.config.endpoints.iter().filter(|e| e.accepts(event_type)) {
+            if let Err(e) = self.deliver(endpoint, event_id, &body).await {
+                log::error!("Webhook {} delivery failed: {}", endpoint.name, e);
+                failures.push(endpoint.name.clone());
+            }
+        }
+
+        if !failures.is_empty() {
+            return Err(anyhow::anyhow!("Webhook delivery failed for: {}", failures.join(", ")));
+        }
+        Ok(())
+    }
+
+    async fn deliver(&self, endpoint: &WebhookEndpoint, event_id: &str, body: &[u8]) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            // Re-sign each attempt so retries don't fall out of the replay window
+            let timestamp = chrono::Utc::now().timestamp();
+            let result = self.client.post(&endpoint.url)?
+                .timeout(Duration::from_millis(self.config.timeout_ms))
+                .header("Content-Type", "application/json")
+                .header(EVENT_ID_HEADER, event_id)
+                .header(TIMESTAMP_HEADER, timestamp.to_string())
+                .header(SIGNATURE_HEADER, signature_header(&endpoint.secret, timestamp, event_id, body))
+                .body(body.to_vec())
+                .send()
+                .await
+                .and_then(|r| r.error_for_status());
+
+            match result {
+                Ok(_) => return Ok(()),
+                Err(e) if attempt < self.config.max_retries => {
+                    attempt += 1;
+                    log::warn!("Webhook {} attempt {} failed: {}", endpoint.name, attempt, e);
+                    tokio::time::sleep(Duration::from_millis(200 * 2u64.pow(attempt))).await;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+#![cfg(feature = "vision")]
+
+use std::sync::Arc;
+use anyhow::{Result, Context};
+use opencv::{
+    prelude::*,
+    core::{Mat, Scalar, Size, CV_32F},
+    dnn, imgproc,
+};
+
+use crate::models::inference::Model;
+use crate::vision::detector::{Accelerator, ModelConfig};
+
+// Edge NPU backends. Each one binds the vendor's C runtime and is only
+// compiled with its Cargo feature (`edgetpu`, `hailo`, `rknn`), since the
+// runtimes exist only on the boards that carry the accelerator.
+pub fn load(config: &ModelConfig) -> Result<Arc<dyn Model>> {
+    match config.accelerator {
+        #[cfg(feature = "edgetpu")]
+        Accelerator::EdgeTpu => Ok(Arc::new(crate::models::edgetpu::EdgeTpuModel::load(config)?)),
+        #[cfg(feature = "hailo")]
+        Accelerator::Hailo => Ok(Arc::new(crate::models::hailo::HailoModel::load(config)?)),
+        #[cfg(feature = "rknn")]
+        Accelerator::Rknn => Ok(Arc::new(crate::models::rknn::RknnModel::load(config)?)),
+        Accelerator::Auto => Err(anyhow::anyhow!("Model {} does not use an edge accelerator", config.name)),
+        #[allow(unreachable_patterns)]
+        other => Err(anyhow::anyhow!(
+            "Model {} wants the {:?} accelerator, which is not enabled in this build \
+             (check the edgetpu, hailo and rknn Cargo features)",
+            config.name, other
+        )),
+    }
+}
+
+// Resizes to the model input and returns packed RGB bytes in HWC order,
+// which is what all three runtimes take for quantized image models
+pub fn input_bytes(image: &Mat, input_size: (i32, i32)) -> Result<Vec<u8>> {
+    let (width, height) = input_size;
+    let mut resized = Mat::default();
+    imgproc::resize(image, &mut resized, Size::new(width, height), 0.0, 0.0, imgproc::INTER_LINEAR)
+        .context("Failed to resize model input")?;
+    let mut rgb = Mat::default();
+    imgproc::cvt_color(&resized, &mut rgb, imgproc::COLOR_BGR2RGB, 0)
+        .context("Failed to convert model input to RGB")?;
+    Ok(rgb.data_bytes().context("Model input is not continuous")?.to_vec())
+}
+
+// Normalised NCHW float input, as exported detection models expect
+pub fn input_floats(image: &Mat, input_size: (i32, i32)) -> Result<Vec<f32>> {
+    let (width, height) = input_size;
+    let blob = dnn::blob_from_image(image, 1.0 / 255.0, Size::new(width, height), Scalar::default(), true, false, CV_32F)
+        .context("Failed to prepare model input")?;
+    Ok(blob.data_typed::<f32>()?.to_vec())
+}
+
+// Dequantized output values as a rows x cols float Mat, matching what the
+// ONNX path hands to post-processing
+pub fn output_mat(values: &[f32], cols: usize) -> Result<Mat> {
+    let cols = cols.max(1);
+    if values.len() % cols != 0 {
+        return Err(anyhow::anyhow!("Output of {} values does not split into rows of {}", values.len(), cols));
+    }
+    let mut mat = Mat::new_rows_cols_with_default((values.len() / cols) as i32, cols as i32, CV_32F, Scalar::all(0.0))
+        .context("Failed to allocate output")?;
+    mat.data_typed_mut::<f32>()?.copy_from_slice(values);
+    Ok(mat)
+}
+
+pub fn dequantize(data: &[u8], signed: bool, scale: f32, zero_point: i32) -> Vec<f32> {
+    // A scale of 0 means the tensor isn't quantized but raw uint8
+    let scale = if scale == 0.0 { 1.0 } else { scale };
+    data.iter()
+        .map(|&b| {
+            let q = if signed { b as i8 as i32 } else { b as i32 };
+            (q - zero_point) as f32 * scale
+        })
+        .collect()
+}
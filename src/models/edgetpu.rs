@@ -0,0 +1,289 @@
+#![cfg(feature = "vision")]
+
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::sync::{Arc, Mutex};
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use opencv::core::Mat;
+
+use crate::models::{edge, inference::Model};
+use crate::vision::detector::ModelConfig;
+
+// Coral Edge TPU through the TensorFlow Lite C API with the libedgetpu
+// delegate. Models must be compiled with edgetpu_compiler; ops it couldn't
+// map run on the CPU inside the same interpreter.
+
+#[repr(C)]
+struct TfLiteModel {
+    _private: [u8; 0],
+}
+#[repr(C)]
+struct TfLiteInterpreterOptions {
+    _private: [u8; 0],
+}
+#[repr(C)]
+struct TfLiteInterpreter {
+    _private: [u8; 0],
+}
+#[repr(C)]
+struct TfLiteTensor {
+    _private: [u8; 0],
+}
+#[repr(C)]
+struct TfLiteDelegate {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TfLiteQuantizationParams {
+    scale: f32,
+    zero_point: i32,
+}
+
+#[repr(C)]
+struct EdgeTpuDevice {
+    kind: c_int,
+    path: *const c_char,
+}
+
+const TFLITE_OK: c_int = 0;
+const TFLITE_FLOAT32: c_int = 1;
+const TFLITE_UINT8: c_int = 3;
+const TFLITE_INT8: c_int = 9;
+const EDGETPU_APEX_PCI: c_int = 0;
+const EDGETPU_APEX_USB: c_int = 1;
+
+#[link(name = "tensorflowlite_c")]
+extern "C" {
+    fn TfLiteModelCreateFromFile(path: *const c_char) -> *mut TfLiteModel;
+    fn TfLiteModelDelete(model: *mut TfLiteModel);
+    fn TfLiteInterpreterOptionsCreate() -> *mut TfLiteInterpreterOptions;
+    fn TfLiteInterpreterOptionsDelete(options: *mut TfLiteInterpreterOptions);
+    fn TfLiteInterpreterOptionsAddDelegate(options: *mut TfLiteInterpreterOptions, delegate: *mut TfLiteDelegate);
+    fn TfLiteInterpreterCreate(model: *const TfLiteModel, options: *const TfLiteInterpreterOptions) -> *mut TfLiteInterpreter;
+    fn TfLiteInterpreterDelete(interpreter: *mut TfLiteInterpreter);
+    fn TfLiteInterpreterAllocateTensors(interpreter: *mut TfLiteInterpreter) -> c_int;
+    fn TfLiteInterpreterGetInputTensor(interpreter: *const TfLiteInterpreter, index: i32) -> *mut TfLiteTensor;
+    fn TfLiteInterpreterGetOutputTensor(interpreter: *const TfLiteInterpreter, index: i32) -> *const TfLiteTensor;
+    fn TfLiteInterpreterInvoke(interpreter: *mut TfLiteInterpreter) -> c_int;
+    fn TfLiteTensorType(tensor: *const TfLiteTensor) -> c_int;
+    fn TfLiteTensorNumDims(tensor: *const TfLiteTensor) -> i32;
+    fn TfLiteTensorDim(tensor: *const TfLiteTensor, index: i32) -> i32;
+    fn TfLiteTensorByteSize(tensor: *const TfLiteTensor) -> usize;
+    fn TfLiteTensorData(tensor: *const TfLiteTensor) -> *mut c_void;
+    fn TfLiteTensorQuantizationParams(tensor: *const TfLiteTensor) -> TfLiteQuantizationParams;
+    fn TfLiteTensorCopyFromBuffer(tensor: *mut TfLiteTensor, data: *const c_void, size: usize) -> c_int;
+}
+
+#[link(name = "edgetpu")]
+extern "C" {
+    fn edgetpu_list_devices(num_devices: *mut usize) -> *mut EdgeTpuDevice;
+    fn edgetpu_free_devices(devices: *mut EdgeTpuDevice);
+    fn edgetpu_create_delegate(
+        kind: c_int,
+        name: *const c_char,
+        options: *const c_void,
+        num_options: usize,
+    ) -> *mut TfLiteDelegate;
+    fn edgetpu_free_delegate(delegate: *mut TfLiteDelegate);
+}
+
+// Edge TPUs attached to this machine as (kind, "usb:N" or "pci:N", path),
+// numbered per kind in enumeration order
+fn enumerate_devices() -> Vec<(c_int, String, CString)> {
+    let mut count = 0usize;
+    // SAFETY: libedgetpu returns an array of `count` devices with
+    // NUL-terminated paths, valid until edgetpu_free_devices
+    unsafe {
+        let devices = edgetpu_list_devices(&mut count);
+        if devices.is_null() {
+            return Vec::new();
+        }
+        let (mut usb, mut pci) = (0, 0);
+        let found = std::slice::from_raw_parts(devices, count).iter()
+            .map(|d| {
+                let counter = if d.kind == EDGETPU_APEX_PCI { &mut pci } else { &mut usb };
+                let alias = format!("{}:{}", if d.kind == EDGETPU_APEX_PCI { "pci" } else { "usb" }, *counter);
+                *counter += 1;
+                (d.kind, alias, CStr::from_ptr(d.path).to_owned())
+            })
+            .collect();
+        edgetpu_free_devices(devices);
+        found
+    }
+}
+
+pub fn list_devices() -> Vec<String> {
+    enumerate_devices().into_iter().map(|(_, alias, _)| alias).collect()
+}
+
+// "usb" or "pci" takes the first of that kind, "usb:1" a specific one, and
+// anything else is matched against the raw device path
+fn find_device(spec: &str) -> Option<(c_int, CString)> {
+    let devices = enumerate_devices();
+    let found = devices.iter()
+        .find(|(_, alias, path)| alias == spec || path.to_str() == Ok(spec) || alias.split(':').next() == Some(spec))?;
+    Some((found.0, found.2.clone()))
+}
+
+struct Interpreter {
+    model: *mut TfLiteModel,
+    options: *mut TfLiteInterpreterOptions,
+    delegate: *mut TfLiteDelegate,
+    interpreter: *mut TfLiteInterpreter,
+}
+
+// SAFETY: the interpreter is only touched behind the model's Mutex, and
+// TFLite has no thread affinity
+unsafe impl Send for Interpreter {}
+
+impl Drop for Interpreter {
+    fn drop(&mut self) {
+        // SAFETY: each pointer was created in `open` and is freed once, in
+        // reverse order of creation
+        unsafe {
+            if !self.interpreter.is_null() {
+                TfLiteInterpreterDelete(self.interpreter);
+            }
+            if !self.options.is_null() {
+                TfLiteInterpreterOptionsDelete(self.options);
+            }
+            if !self.delegate.is_null() {
+                edgetpu_free_delegate(self.delegate);
+            }
+            if !self.model.is_null() {
+                TfLiteModelDelete(self.model);
+            }
+        }
+    }
+}
+
+impl Interpreter {
+    fn open(path: &str, device: Option<&str>) -> Result<Self> {
+        let c_path = CString::new(path).context("Model path contains a NUL byte")?;
+        // Without a device the first USB accelerator is taken, which is how
+        // most Corals are attached
+        let (kind, name) = match device {
+            Some(spec) => {
+                let (kind, path) = find_device(spec).ok_or_else(|| anyhow::anyhow!(
+                    "Edge TPU {} not found (found: {})", spec, list_devices().join(", ")
+                ))?;
+                (kind, Some(path))
+            }
+            None => (EDGETPU_APEX_USB, None),
+        };
+
+        let mut this = Self {
+            model: std::ptr::null_mut(),
+            options: std::ptr::null_mut(),
+            delegate: std::ptr::null_mut(),
+            interpreter: std::ptr::null_mut(),
+        };
+        // SAFETY: arguments are valid C strings or null as the API allows;
+        // every result is null-checked and owned by `this`, which frees it
+        unsafe {
+            this.model = TfLiteModelCreateFromFile(c_path.as_ptr());
+            if this.model.is_null() {
+                return Err(anyhow::anyhow!("Failed to load Edge TPU model {}", path));
+            }
+            this.delegate = edgetpu_create_delegate(
+                kind,
+                name.as_ref().map_or(std::ptr::null(), |n| n.as_ptr()),
+                std::ptr::null(),
+                0,
+            );
+            if this.delegate.is_null() {
+                return Err(anyhow::anyhow!(
+                    "Failed to open Edge TPU {} (found: {})",
+                    device.unwrap_or("usb"),
+                    list_devices().join(", "),
+                ));
+            }
+            this.options = TfLiteInterpreterOptionsCreate();
+            TfLiteInterpreterOptionsAddDelegate(this.options, this.delegate);
+            this.interpreter = TfLiteInterpreterCreate(this.model, this.options);
+            if this.interpreter.is_null() {
+                return Err(anyhow::anyhow!("Failed to create TFLite interpreter for {}", path));
+            }
+            if TfLiteInterpreterAllocateTensors(this.interpreter) != TFLITE_OK {
+                return Err(anyhow::anyhow!("Failed to allocate tensors for {}", path));
+            }
+        }
+        Ok(this)
+    }
+
+    // Returns the first output dequantized, with its last dimension
+    fn run(&mut self, input: &[u8]) -> Result<(Vec<f32>, usize)> {
+        // SAFETY: tensors belong to the live interpreter; sizes are checked
+        // before copying and the output slice is read before the next invoke
+        unsafe {
+            let tensor = TfLiteInterpreterGetInputTensor(self.interpreter, 0);
+            let expected = TfLiteTensorByteSize(tensor);
+            if input.len() != expected {
+                return Err(anyhow::anyhow!("Edge TPU model expects {} input bytes, got {}", expected, input.len()));
+            }
+            let input: Vec<u8> = if TfLiteTensorType(tensor) == TFLITE_INT8 {
+                input.iter().map(|b| b ^ 0x80).collect()
+            } else {
+                input.to_vec()
+            };
+            if TfLiteTensorCopyFromBuffer(tensor, input.as_ptr() as *const c_void, input.len()) != TFLITE_OK {
+                return Err(anyhow::anyhow!("Failed to set Edge TPU input"));
+            }
+            if TfLiteInterpreterInvoke(self.interpreter) != TFLITE_OK {
+                return Err(anyhow::anyhow!("Edge TPU inference failed"));
+            }
+
+            let output = TfLiteInterpreterGetOutputTensor(self.interpreter, 0);
+            let dims = TfLiteTensorNumDims(output);
+            let cols = if dims > 0 { TfLiteTensorDim(output, dims - 1) as usize } else { 1 };
+            let bytes = std::slice::from_raw_parts(TfLiteTensorData(output) as *const u8, TfLiteTensorByteSize(output));
+            let values = match TfLiteTensorType(output) {
+                TFLITE_FLOAT32 => bytes.chunks_exact(4)
+                    .map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+                    .collect(),
+                kind @ (TFLITE_UINT8 | TFLITE_INT8) => {
+                    let q = TfLiteTensorQuantizationParams(output);
+                    edge::dequantize(bytes, kind == TFLITE_INT8, q.scale, q.zero_point)
+                }
+                other => return Err(anyhow::anyhow!("Unsupported Edge TPU output type {}", other)),
+            };
+            Ok((values, cols))
+        }
+    }
+}
+
+pub struct EdgeTpuModel {
+    name: String,
+    input_size: (i32, i32),
+    interpreter: Arc<Mutex<Interpreter>>,
+}
+
+impl EdgeTpuModel {
+    pub fn load(config: &ModelConfig) -> Result<Self> {
+        let interpreter = Interpreter::open(&config.path, config.accelerator_device.as_deref())?;
+        log::info!(
+            "Loaded {} on Edge TPU {}",
+            config.name,
+            config.accelerator_device.as_deref().unwrap_or("(first available)")
+        );
+        Ok(Self {
+            name: config.name.clone(),
+            input_size: config.input_size,
+            interpreter: Arc::new(Mutex::new(interpreter)),
+        })
+    }
+}
+
+#[async_trait]
+impl Model for EdgeTpuModel {
+    async fn infer(&self, input: &Mat) -> Result<Mat> {
+        let bytes = edge::input_bytes(input, self.input_size)?;
+        let interpreter = self.interpreter.clone();
+        let (values, cols) = tokio::task::spawn_blocking(move || interpreter.lock().unwrap().run(&bytes))
+            .await
+            .with_context(|| format!("Edge TPU worker for {} panicked", self.name))??;
+        edge::output_mat(&values, cols)
+    }
+}
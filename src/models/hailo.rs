@@ -0,0 +1,270 @@
+#![cfg(feature = "vision")]
+
+use std::ffi::{c_char, c_void, CString};
+use std::sync::{Arc, Mutex};
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use hailort_sys as sys;
+use opencv::core::Mat;
+
+use crate::models::{edge, inference::Model};
+use crate::vision::detector::ModelConfig;
+
+// Hailo-8 and Hailo-8L through the HailoRT C API, bound by hailort-sys so
+// struct layouts come from the installed headers. Models are compiled to
+// .hef with the Dataflow Compiler; inputs are fed as uint8 and outputs read
+// back as float32, so quantization is handled on the device. Models with
+// on-chip NMS emit HailoRT's per-class box lists, which are decoded into
+// the detector's rows.
+
+fn check(status: sys::hailo_status, what: &str) -> Result<()> {
+    if status != sys::hailo_status_HAILO_SUCCESS {
+        return Err(anyhow::anyhow!("{} (HailoRT status {})", what, status));
+    }
+    Ok(())
+}
+
+// Decodes a HAILO_FORMAT_ORDER_HAILO_NMS float32 buffer: for each class a
+// box count followed by that many (y_min, x_min, y_max, x_max, score)
+// boxes, normalized to the input. Rows come back as the detector reads
+// them, (x, y, width, height, score, class) in input pixels.
+pub fn decode_nms(values: &[f32], classes: usize, input_size: (i32, i32)) -> Result<Vec<f32>> {
+    let (width, height) = (input_size.0 as f32, input_size.1 as f32);
+    let mut rows = Vec::new();
+    let mut at = 0;
+    for class in 0..classes {
+        let count = *values.get(at)
+            .ok_or_else(|| anyhow::anyhow!("Hailo NMS output ends before class {}", class))? as usize;
+        at += 1;
+        let boxes = values.get(at..at + count * 5)
+            .ok_or_else(|| anyhow::anyhow!("Hailo NMS output is short of {} boxes for class {}", count, class))?;
+        at += count * 5;
+        for b in boxes.chunks_exact(5) {
+            let (y_min, x_min, y_max, x_max, score) = (b[0], b[1], b[2], b[3], b[4]);
+            rows.extend([
+                x_min * width,
+                y_min * height,
+                (x_max - x_min) * width,
+                (y_max - y_min) * height,
+                score,
+                class as f32,
+            ]);
+        }
+    }
+    Ok(rows)
+}
+
+// The first output's values and the width of its rows
+struct Output {
+    values: Vec<f32>,
+    cols: usize,
+}
+
+struct Session {
+    vdevice: sys::hailo_vdevice,
+    hef: sys::hailo_hef,
+    inputs: Vec<sys::hailo_input_vstream>,
+    outputs: Vec<sys::hailo_output_vstream>,
+    input_size: usize,
+    output_sizes: Vec<usize>,
+    // Class count when the first output is on-chip NMS
+    nms_classes: Option<usize>,
+    model_input: (i32, i32),
+}
+
+// SAFETY: a session is only used behind the model's Mutex; HailoRT handles
+// are not tied to the thread that created them
+unsafe impl Send for Session {}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // SAFETY: handles were created in `open`; streams are released before
+        // the device and HEF they belong to
+        unsafe {
+            if !self.outputs.is_empty() {
+                sys::hailo_release_output_vstreams(self.outputs.as_ptr(), self.outputs.len());
+            }
+            if !self.inputs.is_empty() {
+                sys::hailo_release_input_vstreams(self.inputs.as_ptr(), self.inputs.len());
+            }
+            if !self.vdevice.is_null() {
+                sys::hailo_release_vdevice(self.vdevice);
+            }
+            if !self.hef.is_null() {
+                sys::hailo_release_hef(self.hef);
+            }
+        }
+    }
+}
+
+impl Session {
+    // `device` is a PCIe address such as "0000:01:00.0"; without one HailoRT
+    // picks any free device
+    fn open(path: &str, device: Option<&str>, model_input: (i32, i32)) -> Result<Self> {
+        let c_path = CString::new(path).context("Model path contains a NUL byte")?;
+        // SAFETY: hailo_device_id_t is a plain C char array
+        let mut device_id: sys::hailo_device_id_t = unsafe { std::mem::zeroed() };
+        if let Some(device) = device {
+            if device.len() >= device_id.id.len() {
+                return Err(anyhow::anyhow!("Hailo device id \"{}\" is too long", device));
+            }
+            for (slot, byte) in device_id.id.iter_mut().zip(device.bytes()) {
+                *slot = byte as c_char;
+            }
+        }
+
+        let mut this = Self {
+            vdevice: std::ptr::null_mut(),
+            hef: std::ptr::null_mut(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            input_size: 0,
+            output_sizes: Vec::new(),
+            nms_classes: None,
+            model_input,
+        };
+        // SAFETY: every out-pointer is valid for the call, parameter structs
+        // are the bindings' own types (zeroed, then filled by HailoRT), and
+        // all handles end up owned by `this`
+        unsafe {
+            let mut params: sys::hailo_vdevice_params_t = std::mem::zeroed();
+            check(sys::hailo_init_vdevice_params(&mut params), "Failed to initialise Hailo device parameters")?;
+            if device.is_some() {
+                params.device_count = 1;
+                params.device_ids = &mut device_id;
+            }
+            check(sys::hailo_create_vdevice(&mut params, &mut this.vdevice), "Failed to open Hailo device")?;
+            check(sys::hailo_create_hef_file(&mut this.hef, c_path.as_ptr()), &format!("Failed to load HEF {}", path))?;
+
+            // Too large for the stack of a blocking-pool thread
+            let mut configure: Box<sys::hailo_configure_params_t> = Box::new_zeroed().assume_init();
+            check(
+                sys::hailo_init_configure_params_by_vdevice(this.hef, this.vdevice, &mut *configure),
+                "Failed to prepare Hailo network configuration",
+            )?;
+            let mut groups: Vec<sys::hailo_configured_network_group> =
+                vec![std::ptr::null_mut(); sys::HAILO_MAX_NETWORK_GROUPS as usize];
+            let mut group_count = groups.len();
+            check(
+                sys::hailo_configure_vdevice(this.vdevice, this.hef, &mut *configure, groups.as_mut_ptr(), &mut group_count),
+                &format!("Failed to configure Hailo device for {}", path),
+            )?;
+            if group_count != 1 {
+                return Err(anyhow::anyhow!("HEF {} has {} network groups, expected one", path, group_count));
+            }
+            let group = groups[0];
+
+            let streams = sys::HAILO_MAX_STREAMS_COUNT as usize;
+            let mut input_params: Vec<sys::hailo_input_vstream_params_by_name_t> =
+                (0..streams).map(|_| std::mem::zeroed()).collect();
+            let mut input_count = streams;
+            check(
+                sys::hailo_make_input_vstream_params(
+                    group, true, sys::hailo_format_type_t_HAILO_FORMAT_TYPE_UINT8,
+                    input_params.as_mut_ptr(), &mut input_count,
+                ),
+                "Failed to prepare Hailo input streams",
+            )?;
+            if input_count != 1 {
+                return Err(anyhow::anyhow!("HEF {} has {} inputs, only single-input models are supported", path, input_count));
+            }
+            let mut output_params: Vec<sys::hailo_output_vstream_params_by_name_t> =
+                (0..streams).map(|_| std::mem::zeroed()).collect();
+            let mut output_count = streams;
+            check(
+                sys::hailo_make_output_vstream_params(
+                    group, true, sys::hailo_format_type_t_HAILO_FORMAT_TYPE_FLOAT32,
+                    output_params.as_mut_ptr(), &mut output_count,
+                ),
+                "Failed to prepare Hailo output streams",
+            )?;
+
+            let mut inputs = vec![std::ptr::null_mut(); input_count];
+            check(
+                sys::hailo_create_input_vstreams(group, input_params.as_ptr(), input_count, inputs.as_mut_ptr()),
+                "Failed to create Hailo input stream",
+            )?;
+            this.inputs = inputs;
+            let mut outputs = vec![std::ptr::null_mut(); output_count];
+            check(
+                sys::hailo_create_output_vstreams(group, output_params.as_ptr(), output_count, outputs.as_mut_ptr()),
+                "Failed to create Hailo output streams",
+            )?;
+            this.outputs = outputs;
+
+            check(sys::hailo_get_input_vstream_frame_size(this.inputs[0], &mut this.input_size), "Failed to read Hailo input size")?;
+            for output in &this.outputs {
+                let mut size = 0usize;
+                check(sys::hailo_get_output_vstream_frame_size(*output, &mut size), "Failed to read Hailo output size")?;
+                this.output_sizes.push(size);
+            }
+
+            let mut info: sys::hailo_vstream_info_t = std::mem::zeroed();
+            check(sys::hailo_get_output_vstream_info(this.outputs[0], &mut info), "Failed to read Hailo output format")?;
+            if info.format.order == sys::hailo_format_order_t_HAILO_FORMAT_ORDER_HAILO_NMS {
+                this.nms_classes = Some(info.__bindgen_anon_1.nms_shape.number_of_classes as usize);
+            }
+        }
+        Ok(this)
+    }
+
+    // Every output stream has to be drained for the next frame to go
+    // through; only the first is returned
+    fn run(&mut self, input: &[u8]) -> Result<Output> {
+        if input.len() != self.input_size {
+            return Err(anyhow::anyhow!("Hailo model expects {} input bytes, got {}", self.input_size, input.len()));
+        }
+        let mut first = Vec::new();
+        // SAFETY: buffer sizes match the frame sizes HailoRT reported
+        unsafe {
+            check(
+                sys::hailo_vstream_write_raw_buffer(self.inputs[0], input.as_ptr() as *const c_void, input.len()),
+                "Failed to write Hailo input",
+            )?;
+            for (i, (output, size)) in self.outputs.iter().zip(&self.output_sizes).enumerate() {
+                let mut values = vec![0f32; size / 4];
+                check(
+                    sys::hailo_vstream_read_raw_buffer(*output, values.as_mut_ptr() as *mut c_void, size - size % 4),
+                    "Failed to read Hailo output",
+                )?;
+                if i == 0 {
+                    first = values;
+                }
+            }
+        }
+        match self.nms_classes {
+            Some(classes) => Ok(Output { values: decode_nms(&first, classes, self.model_input)?, cols: 6 }),
+            None => Ok(Output { cols: first.len(), values: first }),
+        }
+    }
+}
+
+pub struct HailoModel {
+    name: String,
+    input_size: (i32, i32),
+    session: Arc<Mutex<Session>>,
+}
+
+impl HailoModel {
+    pub fn load(config: &ModelConfig) -> Result<Self> {
+        let session = Session::open(&config.path, config.accelerator_device.as_deref(), config.input_size)?;
+        log::info!("Loaded {} on Hailo {}", config.name, config.accelerator_device.as_deref().unwrap_or("(any device)"));
+        Ok(Self {
+            name: config.name.clone(),
+            input_size: config.input_size,
+            session: Arc::new(Mutex::new(session)),
+        })
+    }
+}
+
+#[async_trait]
+impl Model for HailoModel {
+    async fn infer(&self, input: &Mat) -> Result<Mat> {
+        let bytes = edge::input_bytes(input, self.input_size)?;
+        let session = self.session.clone();
+        let output = tokio::task::spawn_blocking(move || session.lock().unwrap().run(&bytes))
+            .await
+            .with_context(|| format!("Hailo worker for {} panicked", self.name))??;
+        edge::output_mat(&output.values, output.cols)
+    }
+}
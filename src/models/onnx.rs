@@ -0,0 +1,102 @@
+#![cfg(feature = "vision")]
+
+use std::sync::{Arc, Mutex};
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use opencv::core::Mat;
+use ort::session::Session;
+use ort::value::Tensor;
+
+use crate::models::{edge, inference::Model, server::BatchModel};
+use crate::runtime::backend::{self, BackendConfig, GpuBackend};
+use crate::vision::detector::ModelConfig;
+
+// ONNX models through ONNX Runtime, on the first backend from `backends`
+// that works here
+pub struct OnnxModel {
+    name: String,
+    input_size: (i32, i32),
+    backend: GpuBackend,
+    // Exported with a dynamic first dimension, so a batch runs in one call
+    dynamic_batch: bool,
+    session: Arc<Mutex<Session>>,
+}
+
+impl OnnxModel {
+    pub fn load(config: &ModelConfig, backends: &BackendConfig) -> Result<Self> {
+        let backend = backend::select(backends)?;
+        let session = backend::session_builder(backends)?
+            .commit_from_file(&config.path)
+            .with_context(|| format!("Failed to load ONNX model {}", config.path))?;
+        let dynamic_batch = session.inputs.first()
+            .and_then(|input| input.input_type.tensor_dimensions())
+            .and_then(|dims| dims.first())
+            .is_some_and(|&batch| batch < 0);
+        log::info!("Loaded {} on {}", config.name, backend.name());
+
+        Ok(Self {
+            name: config.name.clone(),
+            input_size: config.input_size,
+            backend,
+            dynamic_batch,
+            session: Arc::new(Mutex::new(session)),
+        })
+    }
+
+    pub fn backend(&self) -> GpuBackend {
+        self.backend
+    }
+}
+
+fn run(session: &mut Session, input: Vec<f32>, batch: usize, input_size: (i32, i32)) -> Result<(Vec<f32>, usize)> {
+    let (width, height) = input_size;
+    let tensor = Tensor::from_array(([batch, 3, height as usize, width as usize], input))
+        .context("Failed to build ONNX Runtime input")?;
+    let outputs = session.run(ort::inputs![tensor]?).context("ONNX Runtime inference failed")?;
+    let (shape, values) = outputs[0].try_extract_raw_tensor::<f32>()
+        .context("Failed to read ONNX Runtime output")?;
+    let cols = shape.last().copied().unwrap_or(1).max(1) as usize;
+    Ok((values.to_vec(), cols))
+}
+
+#[async_trait]
+impl Model for OnnxModel {
+    async fn infer(&self, input: &Mat) -> Result<Mat> {
+        let data = edge::input_floats(input, self.input_size)?;
+        let session = self.session.clone();
+        let input_size = self.input_size;
+        let (values, cols) = tokio::task::spawn_blocking(move || run(&mut session.lock().unwrap(), data, 1, input_size))
+            .await
+            .with_context(|| format!("ONNX Runtime worker for {} panicked", self.name))??;
+        edge::output_mat(&values, cols)
+    }
+}
+
+#[async_trait]
+impl BatchModel for OnnxModel {
+    async fn infer_batch(&self, inputs: &[Mat]) -> Result<Vec<Mat>> {
+        // Models exported with a fixed batch of 1 take the inputs one by one
+        if !self.dynamic_batch || inputs.len() == 1 {
+            let mut outputs = Vec::with_capacity(inputs.len());
+            for input in inputs {
+                outputs.push(self.infer(input).await?);
+            }
+            return Ok(outputs);
+        }
+
+        let mut data = Vec::new();
+        for input in inputs {
+            data.extend(edge::input_floats(input, self.input_size)?);
+        }
+        let batch = inputs.len();
+        let session = self.session.clone();
+        let input_size = self.input_size;
+        let (values, cols) = tokio::task::spawn_blocking(move || run(&mut session.lock().unwrap(), data, batch, input_size))
+            .await
+            .with_context(|| format!("ONNX Runtime worker for {} panicked", self.name))??;
+        if values.len() % batch != 0 {
+            return Err(anyhow::anyhow!("Output of {} values does not split into {} results", values.len(), batch));
+        }
+        values.chunks(values.len() / batch).map(|output| edge::output_mat(output, cols)).collect()
+    }
+}
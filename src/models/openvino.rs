@@ -0,0 +1,97 @@
+#![cfg(feature = "vision")]
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use openvino::{Core, DeviceType, ElementType, InferRequest, RwPropertyKey, Shape, Tensor};
+use opencv::core::Mat;
+
+use crate::models::{edge, inference::Model};
+use crate::vision::detector::ModelConfig;
+
+// Intel CPUs, iGPUs and NPUs through OpenVINO. `path` is the IR .xml with
+// its .bin weights next to it; ONNX files load directly too.
+pub struct OpenVinoModel {
+    name: String,
+    input_size: (i32, i32),
+    device: String,
+    request: Arc<Mutex<InferRequest>>,
+}
+
+impl OpenVinoModel {
+    pub fn load(config: &ModelConfig) -> Result<Self> {
+        let settings = config.openvino.clone().unwrap_or_default();
+        let device = settings.device_name();
+        let mut core = Core::new().context("Failed to initialise OpenVINO")?;
+        if let Some(cache_dir) = &settings.cache_dir {
+            enable_cache(&mut core, &device, cache_dir);
+        }
+
+        let path = Path::new(&config.path);
+        let weights = path.with_extension("bin");
+        let model = if path.extension().is_some_and(|e| e == "xml") {
+            core.read_model_from_file(&config.path, &weights.to_string_lossy())
+        } else {
+            core.read_model_from_file(&config.path, "")
+        }
+        .with_context(|| format!("Failed to read OpenVINO model {}", config.path))?;
+
+        let mut compiled = core.compile_model(&model, DeviceType::Other(device.as_str().into()))
+            .with_context(|| format!("Failed to compile {} for OpenVINO device {}", config.name, device))?;
+        let request = compiled.create_infer_request()
+            .context("Failed to create OpenVINO infer request")?;
+        log::info!("Loaded {} on OpenVINO {}", config.name, device);
+
+        Ok(Self {
+            name: config.name.clone(),
+            input_size: config.input_size,
+            device,
+            request: Arc::new(Mutex::new(request)),
+        })
+    }
+
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+}
+
+// Set on the device being compiled for, which AUTO passes on to the
+// devices it picks. A cache that can't be set up only costs start-up time,
+// so it isn't fatal.
+fn enable_cache(core: &mut Core, device: &str, cache_dir: &str) {
+    if let Err(e) = std::fs::create_dir_all(cache_dir) {
+        log::warn!("Failed to create OpenVINO cache directory {}: {}", cache_dir, e);
+        return;
+    }
+    if let Err(e) = core.set_property(&DeviceType::Other(device.into()), &RwPropertyKey::CacheDir, cache_dir) {
+        log::warn!("Failed to enable OpenVINO model cache: {}", e);
+    }
+}
+
+fn run(request: &mut InferRequest, input: &[f32], input_size: (i32, i32)) -> Result<(Vec<f32>, usize)> {
+    let (width, height) = input_size;
+    let shape = Shape::new(&[1, 3, height as i64, width as i64])?;
+    let mut tensor = Tensor::new(ElementType::F32, &shape)?;
+    tensor.get_data_mut::<f32>()?.copy_from_slice(input);
+    request.set_input_tensor(&tensor).context("Failed to set OpenVINO input")?;
+    request.infer().context("OpenVINO inference failed")?;
+
+    let output = request.get_output_tensor_by_index(0).context("Failed to read OpenVINO output")?;
+    let cols = output.get_shape()?.get_dimensions().last().copied().unwrap_or(1).max(1) as usize;
+    Ok((output.get_data::<f32>()?.to_vec(), cols))
+}
+
+#[async_trait]
+impl Model for OpenVinoModel {
+    async fn infer(&self, input: &Mat) -> Result<Mat> {
+        let data = edge::input_floats(input, self.input_size)?;
+        let request = self.request.clone();
+        let input_size = self.input_size;
+        let (values, cols) = tokio::task::spawn_blocking(move || run(&mut request.lock().unwrap(), &data, input_size))
+            .await
+            .with_context(|| format!("OpenVINO worker for {} panicked", self.name))??;
+        edge::output_mat(&values, cols)
+    }
+}
+
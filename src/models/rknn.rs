@@ -0,0 +1,223 @@
+#![cfg(feature = "vision")]
+
+use std::ffi::{c_char, c_int, c_void};
+use std::sync::{Arc, Mutex};
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use opencv::core::Mat;
+
+use crate::models::{edge, inference::Model};
+use crate::vision::detector::ModelConfig;
+
+// Rockchip NPUs through librknnrt (RKNN Toolkit 2 runtime). Models are
+// converted to .rknn for the target SoC with rknn-toolkit2 beforehand.
+
+type RknnContext = u64;
+
+const RKNN_SUCC: c_int = 0;
+const RKNN_QUERY_IN_OUT_NUM: c_int = 0;
+const RKNN_QUERY_OUTPUT_ATTR: c_int = 2;
+const RKNN_TENSOR_UINT8: c_int = 3;
+const RKNN_TENSOR_NHWC: c_int = 1;
+const RKNN_MAX_DIMS: usize = 16;
+const RKNN_MAX_NAME_LEN: usize = 256;
+
+// Layouts from rknn_api.h; most fields are only read by the runtime
+#[repr(C)]
+#[derive(Default)]
+#[allow(dead_code)]
+struct RknnInputOutputNum {
+    n_input: u32,
+    n_output: u32,
+}
+
+#[repr(C)]
+#[allow(dead_code)]
+struct RknnTensorAttr {
+    index: u32,
+    n_dims: u32,
+    dims: [u32; RKNN_MAX_DIMS],
+    name: [c_char; RKNN_MAX_NAME_LEN],
+    n_elems: u32,
+    size: u32,
+    fmt: c_int,
+    kind: c_int,
+    qnt_type: c_int,
+    fl: i8,
+    zp: i32,
+    scale: f32,
+    w_stride: u32,
+    size_with_stride: u32,
+    pass_through: u8,
+    h_stride: u32,
+}
+
+#[repr(C)]
+#[allow(dead_code)]
+struct RknnInput {
+    index: u32,
+    buf: *mut c_void,
+    size: u32,
+    pass_through: u8,
+    kind: c_int,
+    fmt: c_int,
+}
+
+#[repr(C)]
+#[allow(dead_code)]
+struct RknnOutput {
+    want_float: u8,
+    is_prealloc: u8,
+    index: u32,
+    buf: *mut c_void,
+    size: u32,
+}
+
+#[link(name = "rknnrt")]
+extern "C" {
+    fn rknn_init(ctx: *mut RknnContext, model: *mut c_void, size: u32, flag: u32, extend: *mut c_void) -> c_int;
+    fn rknn_destroy(ctx: RknnContext) -> c_int;
+    fn rknn_query(ctx: RknnContext, cmd: c_int, info: *mut c_void, size: u32) -> c_int;
+    fn rknn_set_core_mask(ctx: RknnContext, core_mask: c_int) -> c_int;
+    fn rknn_inputs_set(ctx: RknnContext, n_inputs: u32, inputs: *mut RknnInput) -> c_int;
+    fn rknn_run(ctx: RknnContext, extend: *mut c_void) -> c_int;
+    fn rknn_outputs_get(ctx: RknnContext, n_outputs: u32, outputs: *mut RknnOutput, extend: *mut c_void) -> c_int;
+    fn rknn_outputs_release(ctx: RknnContext, n_outputs: u32, outputs: *mut RknnOutput) -> c_int;
+}
+
+// "0,2" -> cores 0 and 2. The RK3588 has three NPU cores; the smaller
+// SoCs have one and ignore the mask.
+pub fn parse_core_mask(spec: &str) -> Result<c_int> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .try_fold(0, |mask, core| match core.parse::<u32>() {
+            Ok(core) if core < 3 => Ok(mask | (1 << core)),
+            _ => Err(anyhow::anyhow!("Invalid RKNN core \"{}\" (expected 0, 1 or 2)", core)),
+        })
+}
+
+struct Session {
+    ctx: RknnContext,
+    n_outputs: u32,
+    // Last dimension of the first output
+    cols: usize,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // SAFETY: the context was initialised in `open` and is destroyed once
+        unsafe {
+            rknn_destroy(self.ctx);
+        }
+    }
+}
+
+impl Session {
+    fn open(path: &str, core_mask: c_int) -> Result<Self> {
+        let mut model = std::fs::read(path).with_context(|| format!("Failed to read RKNN model {}", path))?;
+        let mut ctx: RknnContext = 0;
+        // SAFETY: librknnrt copies the model during init, and the query
+        // structs match the rknn_api.h layouts they are filled into
+        unsafe {
+            let status = rknn_init(&mut ctx, model.as_mut_ptr() as *mut c_void, model.len() as u32, 0, std::ptr::null_mut());
+            if status != RKNN_SUCC {
+                return Err(anyhow::anyhow!("Failed to load RKNN model {} (error {})", path, status));
+            }
+            let mut this = Self { ctx, n_outputs: 0, cols: 1 };
+
+            if core_mask != 0 && rknn_set_core_mask(ctx, core_mask) != RKNN_SUCC {
+                log::warn!("NPU core mask {:#b} not supported on this SoC, using automatic placement", core_mask);
+            }
+
+            let mut io = RknnInputOutputNum::default();
+            if rknn_query(ctx, RKNN_QUERY_IN_OUT_NUM, &mut io as *mut _ as *mut c_void, std::mem::size_of::<RknnInputOutputNum>() as u32) != RKNN_SUCC {
+                return Err(anyhow::anyhow!("Failed to query RKNN model {}", path));
+            }
+            this.n_outputs = io.n_output;
+
+            let mut attr: RknnTensorAttr = std::mem::zeroed();
+            if rknn_query(ctx, RKNN_QUERY_OUTPUT_ATTR, &mut attr as *mut _ as *mut c_void, std::mem::size_of::<RknnTensorAttr>() as u32) == RKNN_SUCC
+                && attr.n_dims > 0
+            {
+                this.cols = attr.dims[attr.n_dims as usize - 1].max(1) as usize;
+            }
+            Ok(this)
+        }
+    }
+
+    fn run(&mut self, input: &mut [u8]) -> Result<Vec<f32>> {
+        let mut inputs = [RknnInput {
+            index: 0,
+            buf: input.as_mut_ptr() as *mut c_void,
+            size: input.len() as u32,
+            pass_through: 0,
+            kind: RKNN_TENSOR_UINT8,
+            fmt: RKNN_TENSOR_NHWC,
+        }];
+        let mut outputs: Vec<RknnOutput> = (0..self.n_outputs)
+            .map(|index| RknnOutput { want_float: 1, is_prealloc: 0, index, buf: std::ptr::null_mut(), size: 0 })
+            .collect();
+
+        // SAFETY: the input buffer outlives rknn_run; outputs are allocated
+        // by the runtime, read as floats (want_float) and released here
+        unsafe {
+            if rknn_inputs_set(self.ctx, 1, inputs.as_mut_ptr()) != RKNN_SUCC {
+                return Err(anyhow::anyhow!("Failed to set RKNN input"));
+            }
+            if rknn_run(self.ctx, std::ptr::null_mut()) != RKNN_SUCC {
+                return Err(anyhow::anyhow!("RKNN inference failed"));
+            }
+            if rknn_outputs_get(self.ctx, self.n_outputs, outputs.as_mut_ptr(), std::ptr::null_mut()) != RKNN_SUCC {
+                return Err(anyhow::anyhow!("Failed to read RKNN outputs"));
+            }
+            let first = &outputs[0];
+            let values = std::slice::from_raw_parts(first.buf as *const f32, first.size as usize / 4).to_vec();
+            rknn_outputs_release(self.ctx, self.n_outputs, outputs.as_mut_ptr());
+            Ok(values)
+        }
+    }
+}
+
+// SAFETY: a session is only used behind the model's Mutex; librknnrt
+// allows calls from any thread as long as they don't overlap
+unsafe impl Send for Session {}
+
+pub struct RknnModel {
+    name: String,
+    input_size: (i32, i32),
+    cols: usize,
+    session: Arc<Mutex<Session>>,
+}
+
+impl RknnModel {
+    pub fn load(config: &ModelConfig) -> Result<Self> {
+        let core_mask = match &config.accelerator_device {
+            Some(spec) => parse_core_mask(spec)?,
+            None => 0,
+        };
+        let session = Session::open(&config.path, core_mask)?;
+        if session.n_outputs == 0 {
+            return Err(anyhow::anyhow!("RKNN model {} has no outputs", config.path));
+        }
+        log::info!("Loaded {} on the RKNN NPU", config.name);
+        Ok(Self {
+            name: config.name.clone(),
+            input_size: config.input_size,
+            cols: session.cols,
+            session: Arc::new(Mutex::new(session)),
+        })
+    }
+}
+
+#[async_trait]
+impl Model for RknnModel {
+    async fn infer(&self, input: &Mat) -> Result<Mat> {
+        let mut bytes = edge::input_bytes(input, self.input_size)?;
+        let session = self.session.clone();
+        let values = tokio::task::spawn_blocking(move || session.lock().unwrap().run(&mut bytes))
+            .await
+            .with_context(|| format!("RKNN worker for {} panicked", self.name))??;
+        edge::output_mat(&values, self.cols)
+    }
+}
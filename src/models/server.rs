@@ -0,0 +1,266 @@
+#![cfg(feature = "vision")]
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, OnceLock, Weak};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use opencv::{core::Mat, prelude::*};
+use serde::{Serialize, Deserialize};
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+use crate::models::inference::Model;
+
+// Shares one loaded model between every pipeline and stream that uses it.
+// Callers enqueue frames; a runner on its own thread collects them into
+// micro-batches and runs them back to back on the model's device, so a
+// GPU holds one copy of the weights instead of one per stream.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelServerConfig {
+    pub max_batch_size: usize,
+    // How long the first request of a batch waits for company
+    pub max_wait_ms: u64,
+    // Requests beyond this wait for room, which pushes back on the streams
+    pub queue_capacity: usize,
+}
+
+impl Default for ModelServerConfig {
+    fn default() -> Self {
+        Self {
+            max_batch_size: 8,
+            max_wait_ms: 5,
+            queue_capacity: 64,
+        }
+    }
+}
+
+// Models that can run several inputs in one call. Any `Model` gets this
+// through `Sequential`; ONNX Runtime models with a dynamic batch dimension
+// run the whole batch at once.
+#[async_trait]
+pub trait BatchModel: Send + Sync {
+    async fn infer_batch(&self, inputs: &[Mat]) -> Result<Vec<Mat>>;
+}
+
+pub struct Sequential(pub Arc<dyn Model>);
+
+#[async_trait]
+impl BatchModel for Sequential {
+    async fn infer_batch(&self, inputs: &[Mat]) -> Result<Vec<Mat>> {
+        let mut outputs = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            outputs.push(self.0.infer(input).await?);
+        }
+        Ok(outputs)
+    }
+}
+
+struct Request {
+    input: Mat,
+    reply: oneshot::Sender<Result<Mat>>,
+}
+
+#[derive(Default)]
+struct Counters {
+    requests: AtomicU64,
+    batches: AtomicU64,
+    failed: AtomicU64,
+    // Microseconds spent queued, summed over requests
+    queued_us: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelServerStats {
+    pub name: String,
+    pub device: String,
+    pub requests: u64,
+    pub batches: u64,
+    pub failed: u64,
+    pub avg_batch_size: f64,
+    pub avg_queue_ms: f64,
+    pub queued: usize,
+}
+
+pub struct ModelServer {
+    name: String,
+    device: String,
+    config: ModelServerConfig,
+    tx: mpsc::Sender<(Request, Instant)>,
+    counters: Arc<Counters>,
+}
+
+impl ModelServer {
+    // Starts the runner thread. `device` only labels the thread and stats;
+    // the model is expected to already be loaded onto it.
+    pub fn start(name: &str, device: &str, model: Arc<dyn BatchModel>, config: ModelServerConfig) -> Result<Self> {
+        let (tx, rx) = mpsc::channel(config.queue_capacity.max(1));
+        let counters = Arc::new(Counters::default());
+        let runner_counters = counters.clone();
+        let runner_config = config.clone();
+
+        // A dedicated thread with its own runtime keeps inference off the
+        // shared workers, and the model on one thread for its whole life
+        std::thread::Builder::new()
+            .name(format!("model-{}", name))
+            .spawn(move || {
+                let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                    Ok(runtime) => runtime,
+                    Err(e) => {
+                        log::error!("Failed to start model runner runtime: {}", e);
+                        return;
+                    }
+                };
+                runtime.block_on(run(model, rx, runner_config, runner_counters));
+            })
+            .context("Failed to spawn model runner thread")?;
+
+        log::info!("Serving model {} on {}", name, device);
+        Ok(Self {
+            name: name.to_string(),
+            device: device.to_string(),
+            config,
+            tx,
+            counters,
+        })
+    }
+
+    pub fn stats(&self) -> ModelServerStats {
+        let requests = self.counters.requests.load(Ordering::Relaxed);
+        let batches = self.counters.batches.load(Ordering::Relaxed);
+        ModelServerStats {
+            name: self.name.clone(),
+            device: self.device.clone(),
+            requests,
+            batches,
+            failed: self.counters.failed.load(Ordering::Relaxed),
+            avg_batch_size: if batches == 0 { 0.0 } else { requests as f64 / batches as f64 },
+            avg_queue_ms: if requests == 0 {
+                0.0
+            } else {
+                self.counters.queued_us.load(Ordering::Relaxed) as f64 / requests as f64 / 1000.0
+            },
+            queued: self.config.queue_capacity.max(1) - self.tx.capacity(),
+        }
+    }
+}
+
+#[async_trait]
+impl Model for ModelServer {
+    async fn infer(&self, input: &Mat) -> Result<Mat> {
+        let (reply, response) = oneshot::channel();
+        let input = input.try_clone().context("Failed to copy inference input")?;
+        self.tx.send((Request { input, reply }, Instant::now())).await
+            .map_err(|_| anyhow::anyhow!("Model server {} has stopped", self.name))?;
+        response.await
+            .map_err(|_| anyhow::anyhow!("Model server {} dropped the request", self.name))?
+    }
+}
+
+async fn run(
+    model: Arc<dyn BatchModel>,
+    mut rx: mpsc::Receiver<(Request, Instant)>,
+    config: ModelServerConfig,
+    counters: Arc<Counters>,
+) {
+    let max_batch = config.max_batch_size.max(1);
+    let max_wait = Duration::from_millis(config.max_wait_ms);
+
+    while let Some(first) = rx.recv().await {
+        let mut batch = vec![first];
+        let deadline = tokio::time::Instant::now() + max_wait;
+        while batch.len() < max_batch {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(request)) => batch.push(request),
+                _ => break,
+            }
+        }
+
+        let started = Instant::now();
+        let (inputs, replies): (Vec<Mat>, Vec<_>) = batch.into_iter()
+            .map(|(request, enqueued)| {
+                counters.queued_us.fetch_add(started.duration_since(enqueued).as_micros() as u64, Ordering::Relaxed);
+                (request.input, request.reply)
+            })
+            .unzip();
+        counters.requests.fetch_add(inputs.len() as u64, Ordering::Relaxed);
+        counters.batches.fetch_add(1, Ordering::Relaxed);
+
+        match model.infer_batch(&inputs).await {
+            Ok(outputs) if outputs.len() == replies.len() => {
+                for (reply, output) in replies.into_iter().zip(outputs) {
+                    let _ = reply.send(Ok(output));
+                }
+            }
+            result => {
+                // anyhow errors don't clone, so each caller gets the message
+                let message = match result {
+                    Ok(outputs) => format!("Model returned {} outputs for {} inputs", outputs.len(), inputs.len()),
+                    Err(e) => format!("{:#}", e),
+                };
+                counters.failed.fetch_add(replies.len() as u64, Ordering::Relaxed);
+                for reply in replies {
+                    let _ = reply.send(Err(anyhow::anyhow!("{}", message)));
+                }
+            }
+        }
+    }
+}
+
+// One server per loaded model and device, shared by every detector in the
+// process. Only detectors keep a server alive: once the last one holding it
+// is dropped the runner exits, and the next detector loads a fresh copy.
+#[derive(Default)]
+pub struct ModelServerRegistry {
+    servers: std::sync::Mutex<HashMap<String, Arc<Mutex<Weak<ModelServer>>>>>,
+}
+
+impl ModelServerRegistry {
+    pub fn global() -> &'static ModelServerRegistry {
+        static REGISTRY: OnceLock<ModelServerRegistry> = OnceLock::new();
+        REGISTRY.get_or_init(ModelServerRegistry::default)
+    }
+
+    // Returns the running server for `key`, or loads the model and starts
+    // one. Only the key's own slot is held while loading, so two streams
+    // starting together don't both load the weights and other models load
+    // in parallel.
+    pub async fn get_or_start(
+        &self,
+        key: &str,
+        device: &str,
+        config: &ModelServerConfig,
+        load: impl Future<Output = Result<Arc<dyn BatchModel>>>,
+    ) -> Result<Arc<ModelServer>> {
+        let slot = {
+            let mut servers = self.servers.lock().unwrap();
+            // Slots nobody is loading into and whose server has stopped
+            servers.retain(|_, slot| {
+                Arc::strong_count(slot) > 1 || !slot.try_lock().is_ok_and(|server| server.strong_count() == 0)
+            });
+            servers.entry(key.to_string()).or_default().clone()
+        };
+
+        let mut slot = slot.lock().await;
+        if let Some(server) = slot.upgrade() {
+            return Ok(server);
+        }
+        let server = Arc::new(ModelServer::start(key, device, load.await?, config.clone())?);
+        *slot = Arc::downgrade(&server);
+        Ok(server)
+    }
+
+    // Servers still loading are left out
+    pub fn stats(&self) -> Vec<ModelServerStats> {
+        let servers = self.servers.lock().unwrap();
+        let mut stats: Vec<_> = servers.values()
+            .filter_map(|slot| slot.try_lock().ok()?.upgrade())
+            .map(|server| server.stats())
+            .collect();
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+        stats
+    }
+}
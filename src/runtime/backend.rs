@@ -0,0 +1,233 @@
+#![cfg(feature = "vision")]
+
+use std::sync::OnceLock;
+use anyhow::{Result, Context};
+use ort::execution_providers::{
+    CPUExecutionProvider, CUDAExecutionProvider, CoreMLExecutionProvider,
+    DirectMLExecutionProvider, ExecutionProvider, ExecutionProviderDispatch,
+    MIGraphXExecutionProvider, ROCmExecutionProvider, TensorRTExecutionProvider,
+};
+use ort::session::{builder::SessionBuilder, Session};
+use serde::{Serialize, Deserialize};
+
+use crate::vision::detector::DetectionDevice;
+
+// Inference backends reachable through ONNX Runtime execution providers.
+// Which ones work depends on the platform, the ort build (the `cuda`,
+// `coreml`, `directml` and `rocm` Cargo features) and the drivers
+// installed, so availability is always checked at runtime rather than
+// assumed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GpuBackend {
+    Cpu,
+    Cuda,
+    TensorRt,
+    // Apple GPU and Neural Engine via Core ML, which runs on Metal
+    CoreMl,
+    // Any DirectX 12 GPU on Windows
+    DirectMl,
+    // AMD Instinct and Radeon cards; MIGraphX compiles the whole graph and
+    // is usually faster once warm, ROCm runs kernels op by op
+    MiGraphX,
+    Rocm,
+}
+
+impl GpuBackend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GpuBackend::Cpu => "cpu",
+            GpuBackend::Cuda => "cuda",
+            GpuBackend::TensorRt => "tensorrt",
+            GpuBackend::CoreMl => "coreml",
+            GpuBackend::DirectMl => "directml",
+            GpuBackend::MiGraphX => "migraphx",
+            GpuBackend::Rocm => "rocm",
+        }
+    }
+
+    pub fn is_gpu(&self) -> bool {
+        *self != GpuBackend::Cpu
+    }
+
+    fn provider(&self, device_id: i32) -> ExecutionProviderDispatch {
+        match self {
+            GpuBackend::Cpu => CPUExecutionProvider::default().build(),
+            GpuBackend::Cuda => CUDAExecutionProvider::default().with_device_id(device_id).build(),
+            GpuBackend::TensorRt => TensorRTExecutionProvider::default().with_device_id(device_id).build(),
+            GpuBackend::CoreMl => CoreMLExecutionProvider::default().build(),
+            GpuBackend::DirectMl => DirectMLExecutionProvider::default().with_device_id(device_id).build(),
+            GpuBackend::MiGraphX => MIGraphXExecutionProvider::default().with_device_id(device_id).build(),
+            GpuBackend::Rocm => ROCmExecutionProvider::default().with_device_id(device_id).build(),
+        }
+    }
+
+    // Probing loads the provider libraries and reads sysfs, and neither
+    // answer changes while the process runs
+    pub fn is_available(&self) -> bool {
+        static AVAILABLE: [OnceLock<bool>; 7] = [const { OnceLock::new() }; 7];
+        *AVAILABLE[*self as usize].get_or_init(|| self.probe())
+    }
+
+    fn probe(&self) -> bool {
+        match self {
+            GpuBackend::Cpu => true,
+            GpuBackend::Cuda => CUDAExecutionProvider::default().is_available().unwrap_or(false),
+            GpuBackend::TensorRt => TensorRTExecutionProvider::default().is_available().unwrap_or(false),
+            GpuBackend::CoreMl => {
+                cfg!(target_os = "macos") && CoreMLExecutionProvider::default().is_available().unwrap_or(false)
+            }
+            GpuBackend::DirectMl => {
+                cfg!(target_os = "windows") && DirectMLExecutionProvider::default().is_available().unwrap_or(false)
+            }
+            // The providers load even without a card, so also require one
+            GpuBackend::MiGraphX => {
+                MIGraphXExecutionProvider::default().is_available().unwrap_or(false) && !amd_devices().is_empty()
+            }
+            GpuBackend::Rocm => {
+                ROCmExecutionProvider::default().is_available().unwrap_or(false) && !amd_devices().is_empty()
+            }
+        }
+    }
+
+    // The backends a detector device setting asks for, best first
+    pub fn for_device(device: &DetectionDevice) -> Vec<GpuBackend> {
+        match device {
+            DetectionDevice::CPU => vec![GpuBackend::Cpu],
+            DetectionDevice::CUDA => vec![GpuBackend::TensorRt, GpuBackend::Cuda],
+            DetectionDevice::CoreML => vec![GpuBackend::CoreMl],
+            DetectionDevice::DirectML => vec![GpuBackend::DirectMl],
+            DetectionDevice::ROCm => vec![GpuBackend::MiGraphX, GpuBackend::Rocm],
+            // OpenCL is an OpenCV DNN target, not an ONNX Runtime provider
+            DetectionDevice::OpenCL => platform_defaults(),
+        }
+    }
+}
+
+// What to try when nothing is configured
+pub fn platform_defaults() -> Vec<GpuBackend> {
+    if cfg!(target_os = "macos") {
+        vec![GpuBackend::CoreMl]
+    } else if cfg!(target_os = "windows") {
+        vec![GpuBackend::Cuda, GpuBackend::DirectMl]
+    } else {
+        vec![GpuBackend::TensorRt, GpuBackend::Cuda, GpuBackend::MiGraphX, GpuBackend::Rocm]
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AmdDevice {
+    // ROCm device index, as used for device_id and HIP_VISIBLE_DEVICES
+    pub index: usize,
+    // Graphics target such as gfx90a (MI200) or gfx1100 (RX 7900)
+    pub target: String,
+    pub compute_units: Option<u32>,
+}
+
+// AMD GPUs known to the ROCm kernel driver, from the KFD topology in sysfs.
+// CPU nodes have a gpu_id of 0 and are skipped. Empty on other platforms or
+// without the amdgpu driver.
+pub fn amd_devices() -> Vec<AmdDevice> {
+    let nodes = match std::fs::read_dir("/sys/class/kfd/kfd/topology/nodes") {
+        Ok(nodes) => nodes,
+        Err(_) => return Vec::new(),
+    };
+    let mut nodes: Vec<(usize, std::path::PathBuf)> = nodes
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| Some((entry.file_name().to_str()?.parse().ok()?, entry.path())))
+        .collect();
+    nodes.sort_by_key(|(id, _)| *id);
+
+    let read = |path: &std::path::Path, file: &str| std::fs::read_to_string(path.join(file)).ok();
+    nodes.iter()
+        .filter(|(_, path)| read(path, "gpu_id").and_then(|id| id.trim().parse::<u64>().ok()).unwrap_or(0) != 0)
+        .enumerate()
+        .map(|(index, (_, path))| {
+            let properties = read(path, "properties").unwrap_or_default();
+            let property = |key: &str| properties.lines()
+                .find_map(|line| line.strip_prefix(key)?.trim().parse::<u64>().ok());
+            // gfx_target_version encodes gfx90a as 90010: major, minor, stepping
+            let target = property("gfx_target_version ")
+                .map(|v| format!("gfx{}{:x}{:x}", v / 10000, (v / 100) % 100, v % 100))
+                .or_else(|| read(path, "name").map(|n| n.trim().to_string()))
+                .unwrap_or_default();
+            let compute_units = match (property("simd_count "), property("simd_per_cu ")) {
+                (Some(simds), Some(per_cu)) if per_cu > 0 => Some((simds / per_cu) as u32),
+                _ => None,
+            };
+            AmdDevice { index, target, compute_units }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackendConfig {
+    // Tried in order; empty means the platform defaults. CPU is always the
+    // last resort.
+    pub preferred: Vec<GpuBackend>,
+    pub device_id: i32,
+    // Refuse to start instead of silently falling back to the CPU
+    pub require_gpu: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackendInfo {
+    pub backend: GpuBackend,
+    pub available: bool,
+}
+
+// Every backend this build knows about and whether it works here
+pub fn discover() -> Vec<BackendInfo> {
+    [
+        GpuBackend::TensorRt, GpuBackend::Cuda, GpuBackend::CoreMl, GpuBackend::DirectMl,
+        GpuBackend::MiGraphX, GpuBackend::Rocm, GpuBackend::Cpu,
+    ]
+        .into_iter()
+        .map(|backend| BackendInfo { backend, available: backend.is_available() })
+        .collect()
+}
+
+pub fn candidates(config: &BackendConfig) -> Vec<GpuBackend> {
+    let mut backends = if config.preferred.is_empty() {
+        platform_defaults()
+    } else {
+        config.preferred.clone()
+    };
+    if !backends.contains(&GpuBackend::Cpu) {
+        backends.push(GpuBackend::Cpu);
+    }
+    backends
+}
+
+// First available backend from the candidates
+pub fn select(config: &BackendConfig) -> Result<GpuBackend> {
+    let backend = candidates(config).into_iter()
+        .find(|b| b.is_available())
+        .unwrap_or(GpuBackend::Cpu);
+    if config.require_gpu && !backend.is_gpu() {
+        return Err(anyhow::anyhow!(
+            "No GPU backend available (tried {}); check drivers and the ort build features",
+            candidates(config).iter().filter(|b| b.is_gpu()).map(|b| b.name()).collect::<Vec<_>>().join(", "),
+        ));
+    }
+    if !backend.is_gpu() {
+        log::warn!("No GPU inference backend available, running on the CPU");
+    } else {
+        log::info!("Using {} inference backend", backend.name());
+    }
+    Ok(backend)
+}
+
+// Registers every candidate with ONNX Runtime, which assigns each graph node
+// to the first provider that supports it and falls back down the list
+pub fn session_builder(config: &BackendConfig) -> Result<SessionBuilder> {
+    let providers: Vec<ExecutionProviderDispatch> = candidates(config).iter()
+        .filter(|b| b.is_available())
+        .map(|b| b.provider(config.device_id))
+        .collect();
+    Session::builder()
+        .context("Failed to create ONNX Runtime session builder")?
+        .with_execution_providers(providers)
+        .context("Failed to register execution providers")
+}
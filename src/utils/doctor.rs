@@ -0,0 +1,370 @@
+use std::path::Path;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+
+use crate::core::llm::provider::api_key;
+#[cfg(feature = "vision")]
+use crate::vision::detector::{Accelerator, DetectionDevice, DetectorConfig, ModelConfig, ModelFramework};
+#[cfg(feature = "vision")]
+use crate::runtime::backend::{discover, GpuBackend};
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    // What to change when the check doesn't pass
+    pub hint: Option<String>,
+}
+
+impl CheckResult {
+    fn pass(name: &str, detail: impl Into<String>) -> Self {
+        Self { name: name.to_string(), status: CheckStatus::Pass, detail: detail.into(), hint: None }
+    }
+
+    fn warn(name: &str, detail: impl Into<String>, hint: &str) -> Self {
+        Self { name: name.to_string(), status: CheckStatus::Warn, detail: detail.into(), hint: Some(hint.to_string()) }
+    }
+
+    fn fail(name: &str, detail: impl Into<String>, hint: &str) -> Self {
+        Self { name: name.to_string(), status: CheckStatus::Fail, detail: detail.into(), hint: Some(hint.to_string()) }
+    }
+}
+
+// The parts of config/vae.yaml the checks look at; everything else in the
+// file is ignored
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct DoctorConfig {
+    pub engine: EngineSection,
+    #[cfg(feature = "vision")]
+    pub detector: Option<DetectorConfig>,
+    pub llm_provider: Option<String>,
+    pub openai_key: Option<String>,
+    pub anthropic_key: Option<String>,
+    pub local_llm: Option<LocalModelSection>,
+    // Files and directories the server writes to: state, profiles, rules,
+    // audit logs, uploads
+    pub state_paths: Vec<String>,
+}
+
+// Only the GPU switch matters here; the engine module itself needs vision
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngineSection {
+    pub enable_gpu: bool,
+}
+
+impl Default for EngineSection {
+    fn default() -> Self {
+        Self { enable_gpu: true }
+    }
+}
+
+// Only the path matters here, and this keeps the check available in builds
+// without the llm-local feature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalModelSection {
+    pub model_path: String,
+}
+
+impl DoctorConfig {
+    pub fn from_yaml(raw: &str) -> Result<Self> {
+        serde_yaml::from_str(raw).context("Invalid config file")
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl DoctorReport {
+    pub fn status(&self) -> CheckStatus {
+        self.checks.iter().map(|c| c.status).max().unwrap_or(CheckStatus::Pass)
+    }
+
+    pub fn failures(&self) -> Vec<&CheckResult> {
+        self.checks.iter().filter(|c| c.status == CheckStatus::Fail).collect()
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for check in &self.checks {
+            let marker = match check.status {
+                CheckStatus::Pass => "ok  ",
+                CheckStatus::Warn => "warn",
+                CheckStatus::Fail => "FAIL",
+            };
+            out.push_str(&format!("[{}] {}: {}\n", marker, check.name, check.detail));
+            if let Some(hint) = &check.hint {
+                out.push_str(&format!("       -> {}\n", hint));
+            }
+        }
+        let failed = self.failures().len();
+        let warned = self.checks.iter().filter(|c| c.status == CheckStatus::Warn).count();
+        out.push_str(&format!("\n{} checks, {} failed, {} warnings\n", self.checks.len(), failed, warned));
+        out
+    }
+
+    // For startup: log everything, refuse to start on failures
+    pub fn ensure_healthy(&self) -> Result<()> {
+        for check in &self.checks {
+            match check.status {
+                CheckStatus::Pass => log::info!("Startup check {}: {}", check.name, check.detail),
+                CheckStatus::Warn => log::warn!("Startup check {}: {}", check.name, check.detail),
+                CheckStatus::Fail => log::error!(
+                    "Startup check {}: {} ({})", check.name, check.detail, check.hint.as_deref().unwrap_or(""),
+                ),
+            }
+        }
+        let failures = self.failures();
+        if failures.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow::anyhow!(
+            "Startup checks failed: {}",
+            failures.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", "),
+        ))
+    }
+}
+
+// For main and embedding hosts: checks the config file about to be started
+// with and refuses to go on if any check fails
+pub fn check_startup(path: &str) -> Result<DoctorReport> {
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path))?;
+    let report = run_checks(&DoctorConfig::from_yaml(&raw)?);
+    report.ensure_healthy()?;
+    Ok(report)
+}
+
+pub fn run_checks(config: &DoctorConfig) -> DoctorReport {
+    let mut checks = Vec::new();
+    #[cfg(feature = "vision")]
+    {
+        checks.push(check_opencv_build());
+        checks.push(check_gpu(config));
+        checks.push(check_backends());
+        if let Some(detector) = &config.detector {
+            checks.extend(detector.model_configs.iter().map(check_model));
+        }
+    }
+    #[cfg(not(feature = "vision"))]
+    if config.engine.enable_gpu {
+        checks.push(CheckResult::warn(
+            "gpu",
+            "enable_gpu is set but this build has no vision support",
+            "Rebuild with --features vision,cuda or set engine.enable_gpu: false",
+        ));
+    }
+    checks.extend(config.state_paths.iter().map(|p| check_writable(p)));
+    checks.push(check_llm_provider(config));
+    DoctorReport { checks }
+}
+
+#[cfg(feature = "vision")]
+fn check_opencv_build() -> CheckResult {
+    let info = match opencv::core::get_build_information() {
+        Ok(info) => info,
+        Err(e) => return CheckResult::fail(
+            "opencv", format!("Could not query the OpenCV build: {}", e), "Check the OpenCV installation",
+        ),
+    };
+    // Lines look like "    FFMPEG:                      YES (58.134.100)"
+    let enabled = |feature: &str| info.lines().any(|line| {
+        let line = line.trim();
+        line.starts_with(feature) && line[feature.len()..].trim_start_matches(':').trim_start().starts_with("YES")
+    });
+
+    let version = opencv::core::CV_VERSION;
+    if !enabled("FFMPEG") && !enabled("GStreamer") {
+        return CheckResult::fail(
+            "opencv",
+            format!("OpenCV {} was built without FFMPEG or GStreamer, so video files and RTSP can't be decoded", version),
+            "Install an OpenCV build with video I/O, e.g. the distribution's libopencv-videoio package",
+        );
+    }
+    CheckResult::pass("opencv", format!("OpenCV {} with video I/O", version))
+}
+
+#[cfg(feature = "vision")]
+fn check_gpu(config: &DoctorConfig) -> CheckResult {
+    // Core ML, DirectML and ROCm go through ONNX Runtime rather than
+    // OpenCV's CUDA build
+    if let Some(device) = config.detector.as_ref().map(|d| &d.device) {
+        if matches!(device, DetectionDevice::CoreML | DetectionDevice::DirectML | DetectionDevice::ROCm) {
+            let backends = GpuBackend::for_device(device);
+            let names = backends.iter().map(|b| b.name()).collect::<Vec<_>>().join(", ");
+            return if backends.iter().any(|b| b.is_available()) {
+                CheckResult::pass("gpu", format!("{} inference backend available", names))
+            } else {
+                CheckResult::fail(
+                    "gpu",
+                    format!("detector.device asks for {} but it isn't available on this machine", names),
+                    "CoreML needs macOS and the coreml feature, DirectML needs Windows and the directml feature, ROCm needs the amdgpu driver, ROCm libraries and the rocm feature; or set detector.device: CPU",
+                )
+            };
+        }
+    }
+
+    let wants_cuda = config.engine.enable_gpu
+        || config.detector.as_ref().map_or(false, |d| matches!(d.device, DetectionDevice::CUDA));
+    let devices = opencv::core::get_cuda_enabled_device_count().unwrap_or(0);
+
+    match (wants_cuda, devices) {
+        (true, 0) => CheckResult::fail(
+            "gpu",
+            "GPU processing is enabled but no CUDA device is usable from OpenCV",
+            "Set engine.enable_gpu: false and detector.device: CPU, or install CUDA drivers and a CUDA-enabled OpenCV",
+        ),
+        (true, n) => CheckResult::pass("gpu", format!("{} CUDA device(s) available", n)),
+        (false, 0) => CheckResult::pass("gpu", "GPU disabled, running on CPU"),
+        (false, n) => CheckResult::warn(
+            "gpu",
+            format!("{} CUDA device(s) available but GPU processing is disabled", n),
+            "Set engine.enable_gpu: true to use them",
+        ),
+    }
+}
+
+#[cfg(feature = "vision")]
+fn check_backends() -> CheckResult {
+    let available: Vec<&str> = discover().into_iter()
+        .filter(|b| b.available)
+        .map(|b| b.backend.name())
+        .collect();
+    CheckResult::pass("backends", format!("ONNX Runtime backends available: {}", available.join(", ")))
+}
+
+#[cfg(feature = "vision")]
+fn check_model(model: &ModelConfig) -> CheckResult {
+    let name = format!("model {}", model.name);
+    let path = Path::new(&model.path);
+    if !path.is_file() {
+        return CheckResult::fail(&name, format!("{} does not exist", model.path), "Download the model or fix its path");
+    }
+    if model.input_size.0 <= 0 || model.input_size.1 <= 0 {
+        return CheckResult::fail(
+            &name, format!("input_size {:?} is not a valid shape", model.input_size), "Use the model's input size, e.g. [640, 640]",
+        );
+    }
+    if model.class_names.is_empty() {
+        return CheckResult::warn(&name, "No class_names configured", "Detections will have no labels; list the model's classes");
+    }
+    // Compiled for an NPU; OpenCV can't load those to check the shape
+    if model.accelerator != Accelerator::Auto {
+        return CheckResult::pass(&name, format!("{} present, compiled for {:?}", model.path, model.accelerator));
+    }
+
+    match &model.framework {
+        ModelFramework::ONNX => check_onnx_shape(&name, model),
+        ModelFramework::OpenVINO if !path.with_extension("bin").is_file() => CheckResult::fail(
+            &name,
+            format!("OpenVINO weights {} are missing", path.with_extension("bin").display()),
+            "Place the .bin file next to the .xml",
+        ),
+        _ => CheckResult::pass(&name, format!("{} present", model.path)),
+    }
+}
+
+// Runs one forward pass with a blank input of the configured size, which
+// catches a wrong input_size before the first real frame does
+#[cfg(feature = "vision")]
+fn check_onnx_shape(name: &str, model: &ModelConfig) -> CheckResult {
+    use opencv::{core::{Mat, Scalar, Size, CV_32F, CV_8UC3}, dnn, prelude::*};
+
+    let result = (|| -> opencv::Result<Vec<i32>> {
+        let mut net = dnn::read_net_from_onnx(&model.path)?;
+        let (width, height) = model.input_size;
+        let image = Mat::new_rows_cols_with_default(height, width, CV_8UC3, Scalar::all(0.0))?;
+        let blob = dnn::blob_from_image(&image, 1.0 / 255.0, Size::new(width, height), Scalar::default(), true, false, CV_32F)?;
+        net.set_input(&blob, "", 1.0, Scalar::default())?;
+        let output = net.forward_single("")?;
+        Ok(output.mat_size().iter().copied().collect())
+    })();
+
+    match result {
+        Ok(shape) => CheckResult::pass(name, format!("{} loads; output shape {:?}", model.path, shape)),
+        Err(e) => CheckResult::fail(
+            name,
+            format!("{} rejected a {}x{} input: {}", model.path, model.input_size.0, model.input_size.1, e),
+            "Set input_size to the model's expected input dimensions",
+        ),
+    }
+}
+
+pub fn check_writable(path: &str) -> CheckResult {
+    let name = format!("path {}", path);
+    let target = Path::new(path);
+    // Files may not exist yet; what matters is that their directory takes writes
+    let dir = if target.is_dir() {
+        target.to_path_buf()
+    } else {
+        match target.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => Path::new(".").to_path_buf(),
+        }
+    };
+    if !dir.is_dir() {
+        return CheckResult::fail(&name, format!("{} does not exist", dir.display()), "Create the directory or change the path");
+    }
+
+    let probe = dir.join(format!(".vae-doctor-{}", std::process::id()));
+    match std::fs::write(&probe, b"probe") {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+            if target.is_file() && std::fs::OpenOptions::new().append(true).open(target).is_err() {
+                return CheckResult::fail(&name, "File exists but is read-only", "Fix the file's permissions or owner");
+            }
+            CheckResult::pass(&name, "writable")
+        }
+        Err(e) => CheckResult::fail(
+            &name,
+            format!("{} is not writable: {}", dir.display(), e),
+            "Fix the directory's permissions or owner, or point the setting elsewhere",
+        ),
+    }
+}
+
+fn has_credential(configured: &Option<String>, env: &str) -> bool {
+    api_key(configured.as_deref(), env).is_some()
+}
+
+pub fn check_llm_provider(config: &DoctorConfig) -> CheckResult {
+    let provider = config.llm_provider.as_deref().unwrap_or("openai").to_ascii_lowercase();
+    match provider.as_str() {
+        "openai" if has_credential(&config.openai_key, "OPENAI_API_KEY") => {
+            CheckResult::pass("llm", "OpenAI credentials configured")
+        }
+        "openai" => CheckResult::fail(
+            "llm", "llm_provider is openai but no API key is set", "Set openai_key or OPENAI_API_KEY",
+        ),
+        "anthropic" if has_credential(&config.anthropic_key, "ANTHROPIC_API_KEY") => {
+            CheckResult::pass("llm", "Anthropic credentials configured")
+        }
+        "anthropic" => CheckResult::fail(
+            "llm", "llm_provider is anthropic but no API key is set", "Set anthropic_key or ANTHROPIC_API_KEY",
+        ),
+        "local" => match &config.local_llm {
+            Some(local) if Path::new(&local.model_path).is_file() => {
+                CheckResult::pass("llm", format!("Local model {}", local.model_path))
+            }
+            Some(local) => CheckResult::fail(
+                "llm", format!("GGUF model {} does not exist", local.model_path), "Download the model or fix local_llm.model_path",
+            ),
+            None => CheckResult::fail("llm", "llm_provider is local but local_llm is missing", "Add a local_llm section with model_path"),
+        },
+        other => CheckResult::fail(
+            "llm", format!("Unknown llm_provider \"{}\"", other), "Use openai, anthropic or local",
+        ),
+    }
+}
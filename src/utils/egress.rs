@@ -0,0 +1,138 @@
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use reqwest::{Method, RequestBuilder, Url};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EgressConfig {
+    pub proxy: Option<ProxyConfig>,
+    // When enforced, only hosts matching the allow-list may be contacted.
+    // Entries are exact hostnames or `*.example.com` wildcards.
+    pub enforce_allow_list: bool,
+    #[serde(default)]
+    pub allow_list: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    // Comma-separated hosts that bypass the proxy, same syntax as NO_PROXY
+    pub no_proxy: Option<String>,
+}
+
+impl ProxyConfig {
+    pub fn build(&self) -> Result<reqwest::Proxy> {
+        let mut proxy = reqwest::Proxy::all(&self.url)
+            .with_context(|| format!("Invalid proxy URL: {}", self.url))?;
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            proxy = proxy.basic_auth(username, password);
+        }
+        if let Some(no_proxy) = &self.no_proxy {
+            proxy = proxy.no_proxy(reqwest::NoProxy::from_string(no_proxy));
+        }
+        Ok(proxy)
+    }
+}
+
+#[derive(Debug)]
+pub struct EgressPolicy {
+    enforce: bool,
+    allow_list: Vec<String>,
+}
+
+impl EgressPolicy {
+    pub fn new(config: &EgressConfig) -> Self {
+        Self {
+            enforce: config.enforce_allow_list,
+            allow_list: config.allow_list.iter().map(|h| h.to_lowercase()).collect(),
+        }
+    }
+
+    pub fn is_allowed(&self, host: &str) -> bool {
+        if !self.enforce {
+            return true;
+        }
+
+        let host = host.to_lowercase();
+        self.allow_list.iter().any(|pattern| match pattern.strip_prefix("*.") {
+            Some(suffix) => host.ends_with(&format!(".{}", suffix)),
+            None => &host == pattern,
+        })
+    }
+
+    pub fn check_host(&self, host: &str) -> Result<()> {
+        if self.is_allowed(host) {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("Egress to {} is not in the allow-list", host))
+        }
+    }
+
+    pub fn check_url(&self, url: &Url) -> Result<()> {
+        let host = url.host_str()
+            .ok_or_else(|| anyhow::anyhow!("URL has no host: {}", url))?;
+        self.check_host(host)
+    }
+}
+
+// All outbound HTTP goes through this client so proxy settings and the
+// egress allow-list apply uniformly, including to redirects
+#[derive(Clone)]
+pub struct EgressClient {
+    client: reqwest::Client,
+    policy: Arc<EgressPolicy>,
+}
+
+impl EgressClient {
+    pub fn new(config: &EgressConfig, timeout: Duration) -> Result<Self> {
+        let policy = Arc::new(EgressPolicy::new(config));
+
+        let redirect_policy = {
+            let policy = policy.clone();
+            reqwest::redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() >= 10 {
+                    attempt.error("too many redirects")
+                } else if policy.check_url(attempt.url()).is_err() {
+                    let host = attempt.url().host_str().unwrap_or_default().to_string();
+                    attempt.error(format!("redirect to {} blocked by egress policy", host))
+                } else {
+                    attempt.follow()
+                }
+            })
+        };
+
+        let mut builder = reqwest::Client::builder()
+            .timeout(timeout)
+            .redirect(redirect_policy);
+
+        if let Some(proxy_config) = &config.proxy {
+            builder = builder.proxy(proxy_config.build()?);
+        }
+
+        Ok(Self {
+            client: builder.build().context("Failed to build HTTP client")?,
+            policy,
+        })
+    }
+
+    pub fn policy(&self) -> &Arc<EgressPolicy> {
+        &self.policy
+    }
+
+    pub fn request(&self, method: Method, url: &str) -> Result<RequestBuilder> {
+        let url = Url::parse(url).with_context(|| format!("Invalid URL: {}", url))?;
+        self.policy.check_url(&url)?;
+        Ok(self.client.request(method, url))
+    }
+
+    pub fn get(&self, url: &str) -> Result<RequestBuilder> {
+        self.request(Method::GET, url)
+    }
+
+    pub fn post(&self, url: &str) -> Result<RequestBuilder> {
+        self.request(Method::POST, url)
+    }
+}
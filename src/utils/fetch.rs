@@ -0,0 +1,235 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{Result, Context};
+use futures::StreamExt;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::Url;
+use serde::{Serialize, Deserialize};
+
+use crate::utils::egress::{EgressConfig, EgressPolicy};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchConfig {
+    pub max_bytes: usize,
+    pub timeout_ms: u64,
+    pub connect_timeout_ms: u64,
+    pub max_redirects: usize,
+    pub allowed_content_types: Vec<String>,
+    // Only for trusted deployments that must fetch from internal hosts
+    pub allow_private_addresses: bool,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: 20 * 1024 * 1024,
+            timeout_ms: 10_000,
+            connect_timeout_ms: 3_000,
+            max_redirects: 3,
+            allowed_content_types: vec![
+                "image/jpeg".to_string(),
+                "image/png".to_string(),
+            ],
+            allow_private_addresses: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FetchedImage {
+    pub url: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+// Anything that isn't globally routable: the classic SSRF targets are
+// loopback, RFC 1918, link-local (cloud metadata) and their IPv6 forms
+pub fn is_blocked_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_blocked_v4(v4),
+        IpAddr::V6(v6) => {
+            if let Some(mapped) = v6.to_ipv4_mapped() {
+                return is_blocked_v4(&mapped);
+            }
+            let segments = v6.segments();
+            // NAT64 well-known prefix embeds an IPv4 address
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let embedded = Ipv4Addr::new(
+                    (segments[6] >> 8) as u8, segments[6] as u8,
+                    (segments[7] >> 8) as u8, segments[7] as u8,
+                );
+                return is_blocked_v4(&embedded);
+            }
+
+            v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                || (segments[0] & 0xfe00) == 0xfc00 // unique local
+                || (segments[0] & 0xffc0) == 0xfe80 // link-local
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8) // documentation
+        }
+    }
+}
+
+fn is_blocked_v4(ip: &Ipv4Addr) -> bool {
+    let octets = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || octets[0] == 0
+        || (octets[0] == 100 && (octets[1] & 0xc0) == 64) // carrier-grade NAT
+        || (octets[0] == 192 && octets[1] == 0 && octets[2] == 0) // IETF protocol assignments
+        || (octets[0] == 198 && (octets[1] & 0xfe) == 18) // benchmarking
+        || octets[0] >= 240 // reserved
+}
+
+// Resolves normally, then drops blocked addresses. Checking after
+// resolution (rather than on the hostname) closes DNS rebinding tricks,
+// since the connection uses exactly the addresses vetted here.
+struct SsrfSafeResolver;
+
+impl Resolve for SsrfSafeResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let resolved: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?
+                .filter(|addr| !is_blocked_ip(&addr.ip()))
+                .collect();
+
+            if resolved.is_empty() {
+                return Err(format!("{} does not resolve to a public address", host).into());
+            }
+            let addrs: Addrs = Box::new(resolved.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+fn check_url(url: &Url, allow_private: bool) -> Result<()> {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(anyhow::anyhow!("Only http and https URLs can be fetched"));
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err(anyhow::anyhow!("URLs with credentials are not allowed"));
+    }
+
+    // IP literals never reach the resolver, so check them here
+    let host = url.host_str().ok_or_else(|| anyhow::anyhow!("URL has no host"))?;
+    if let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        if !allow_private && is_blocked_ip(&ip) {
+            return Err(anyhow::anyhow!("Fetching from {} is not allowed", ip));
+        }
+    }
+    Ok(())
+}
+
+pub struct ImageFetcher {
+    config: FetchConfig,
+    client: reqwest::Client,
+    policy: Arc<EgressPolicy>,
+}
+
+impl ImageFetcher {
+    // The egress allow-list applies to the URL and every redirect. A proxy
+    // resolves hosts itself, out of reach of the private-address checks, so
+    // one is only used when those checks are off; otherwise a configured
+    // proxy is refused rather than silently bypassed.
+    pub fn new(config: FetchConfig, egress: &EgressConfig) -> Result<Self> {
+        let allow_private = config.allow_private_addresses;
+        if egress.proxy.is_some() && !allow_private {
+            return Err(anyhow::anyhow!(
+                "Image fetching can't go through the egress proxy without allow_private_addresses, \
+                 since the proxy would resolve hosts past the private-address checks"
+            ));
+        }
+        let policy = Arc::new(EgressPolicy::new(egress));
+        let max_redirects = config.max_redirects;
+        let redirect_policy = {
+            let policy = policy.clone();
+            reqwest::redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() >= max_redirects {
+                    attempt.error("Too many redirects")
+                } else if let Err(e) = check_url(attempt.url(), allow_private) {
+                    attempt.error(e.to_string())
+                } else if let Err(e) = policy.check_url(attempt.url()) {
+                    attempt.error(e.to_string())
+                } else {
+                    attempt.follow()
+                }
+            })
+        };
+
+        let mut builder = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .connect_timeout(Duration::from_millis(config.connect_timeout_ms))
+            .redirect(redirect_policy);
+        builder = match &egress.proxy {
+            Some(proxy) => builder.proxy(proxy.build()?),
+            None => builder.no_proxy(),
+        };
+        if !allow_private {
+            builder = builder.dns_resolver(Arc::new(SsrfSafeResolver));
+        }
+
+        Ok(Self {
+            client: builder.build().context("Failed to build fetch client")?,
+            config,
+            policy,
+        })
+    }
+
+    pub fn config(&self) -> &FetchConfig {
+        &self.config
+    }
+
+    pub async fn fetch(&self, raw_url: &str) -> Result<FetchedImage> {
+        let url = Url::parse(raw_url).context("Invalid image URL")?;
+        check_url(&url, self.config.allow_private_addresses)?;
+        self.policy.check_url(&url)?;
+
+        let response = self.client.get(url).send().await
+            .with_context(|| format!("Failed to fetch {}", raw_url))?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Fetching {} returned {}", raw_url, response.status()));
+        }
+
+        let content_type = response.headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim().to_lowercase())
+            .unwrap_or_default();
+        if !self.config.allowed_content_types.contains(&content_type) {
+            return Err(anyhow::anyhow!("Unsupported content type from {}: {}", raw_url, content_type));
+        }
+
+        if let Some(length) = response.content_length() {
+            if length as usize > self.config.max_bytes {
+                return Err(anyhow::anyhow!("Image at {} exceeds {} bytes", raw_url, self.config.max_bytes));
+            }
+        }
+
+        // Content-Length can lie or be absent, so enforce the limit while reading
+        let final_url = response.url().to_string();
+        let mut data = Vec::new();
+        let mut body = response.bytes_stream();
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.with_context(|| format!("Failed to read {}", raw_url))?;
+            if data.len() + chunk.len() > self.config.max_bytes {
+                return Err(anyhow::anyhow!("Image at {} exceeds {} bytes", raw_url, self.config.max_bytes));
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        Ok(FetchedImage {
+            url: final_url,
+            content_type,
+            data,
+        })
+    }
+}
@@ -0,0 +1,8 @@
+// Paths, models and chaos targets in config are exact, or a prefix when
+// they end in '*' ("/v1/admin/*", "gpt-4o*", "llm.*")
+pub fn wildcard_matches(pattern: &str, value: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => value.starts_with(prefix),
+        None => value == pattern,
+    }
+}
@@ -0,0 +1,105 @@
+use std::path::Path;
+use std::time::Duration;
+use anyhow::{Result, Context};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::Connection;
+
+// Shared setup for the small SQLite stores (agent memory, sink outbox):
+// WAL journaling so readers don't block the writer, and schema migrations
+// tracked in the database's user_version.
+
+pub type Pool = r2d2::Pool<SqliteConnectionManager>;
+
+pub fn open(path: &str, busy_timeout: Duration) -> Result<Connection> {
+    create_parent(path)?;
+    let conn = Connection::open(path)
+        .with_context(|| format!("Failed to open SQLite database {}", path))?;
+    configure(&conn, busy_timeout)?;
+    Ok(conn)
+}
+
+pub fn open_in_memory() -> Result<Connection> {
+    let conn = Connection::open_in_memory()?;
+    configure(&conn, Duration::from_secs(5))?;
+    Ok(conn)
+}
+
+// A pool over one database file, so every scope or session in it shares
+// connections instead of opening its own
+pub fn pool(path: &str, busy_timeout: Duration, max_connections: u32) -> Result<Pool> {
+    create_parent(path)?;
+    let manager = SqliteConnectionManager::file(path)
+        .with_init(move |conn| configure(conn, busy_timeout));
+    r2d2::Pool::builder()
+        .max_size(max_connections.max(1))
+        .build(manager)
+        .with_context(|| format!("Failed to open SQLite database {}", path))
+}
+
+// Every connection to ":memory:" is a separate database, so this pool
+// holds exactly one
+pub fn pool_in_memory() -> Result<Pool> {
+    let manager = SqliteConnectionManager::memory()
+        .with_init(|conn| configure(conn, Duration::from_secs(5)));
+    Ok(r2d2::Pool::builder().max_size(1).build(manager)?)
+}
+
+// Runs blocking SQLite work on the blocking thread pool rather than the
+// async executor
+pub async fn run<T, F>(pool: &Pool, work: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+{
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().context("Failed to get a SQLite connection")?;
+        work(&mut conn)
+    }).await.context("SQLite task panicked")?
+}
+
+fn create_parent(path: &str) -> Result<()> {
+    if let Some(dir) = Path::new(path).parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+fn configure(conn: &Connection, busy_timeout: Duration) -> rusqlite::Result<()> {
+    conn.busy_timeout(busy_timeout)?;
+    // In-memory databases answer "memory"; that's fine
+    let mode: String = conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))?;
+    if mode != "wal" && mode != "memory" {
+        log::warn!("SQLite database is in {} journal mode, not WAL", mode);
+    }
+    conn.execute_batch("PRAGMA synchronous = NORMAL; PRAGMA foreign_keys = ON;")
+}
+
+// Applies the migrations the database hasn't seen yet, each in its own
+// transaction, and returns how many ran. Migration lists are append only:
+// never edit one that has shipped.
+pub fn migrate(conn: &mut Connection, name: &str, migrations: &[&str]) -> Result<usize> {
+    let current = conn.query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0))? as usize;
+    if current > migrations.len() {
+        return Err(anyhow::anyhow!(
+            "{} database is at schema version {}, newer than this build supports ({})",
+            name, current, migrations.len()
+        ));
+    }
+
+    for (index, migration) in migrations.iter().enumerate().skip(current) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)
+            .with_context(|| format!("{} database migration {} failed", name, index + 1))?;
+        // PRAGMA doesn't take parameters
+        tx.execute_batch(&format!("PRAGMA user_version = {}", index + 1))?;
+        tx.commit()?;
+    }
+
+    let applied = migrations.len() - current;
+    if applied > 0 {
+        log::info!("Applied {} {} database migration(s)", applied, name);
+    }
+    Ok(applied)
+}
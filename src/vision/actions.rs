@@ -0,0 +1,396 @@
+#![cfg(feature = "vision")]
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use anyhow::{Result, Context};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Serialize, Deserialize};
+use opencv::{
+    prelude::*,
+    core::{Mat, Scalar, Size, CV_32F},
+    dnn,
+    imgproc,
+};
+
+use crate::vision::analyzer::Activity;
+use crate::vision::detector::Detection;
+use crate::vision::processor::Frame;
+
+// Action recognition over short clips. Frames are sampled into a rolling
+// buffer and, once it holds a full clip, a video model (X3D, SlowFast and
+// the like, exported to ONNX) labels what is happening in it.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionModelConfig {
+    pub model_path: String,
+    // One label per model output, in output order (Kinetics-400 for most
+    // published checkpoints)
+    pub labels: Vec<String>,
+    #[serde(default = "default_action_input")]
+    pub input_size: (i32, i32),
+    // Frames per clip the model was trained on: 16 for X3D-M, 32 for the
+    // SlowFast fast pathway
+    #[serde(default = "default_clip_length")]
+    pub clip_length: usize,
+    // Keeps every Nth frame; at 25 fps a stride of 2 makes a 16 frame clip
+    // cover just over a second
+    #[serde(default = "default_frame_stride")]
+    pub frame_stride: usize,
+    // Sampled frames between runs; half a clip by default, so clips overlap
+    #[serde(default)]
+    pub hop: Option<usize>,
+    // Per-channel normalisation, RGB on a 0-1 scale
+    #[serde(default = "default_mean")]
+    pub mean: [f32; 3],
+    #[serde(default = "default_std")]
+    pub std: [f32; 3],
+    // Two-pathway models take the clip twice: the fast pathway every frame,
+    // the slow one every `alpha`th
+    #[serde(default)]
+    pub slowfast: Option<SlowFastConfig>,
+    #[serde(default = "default_action_threshold")]
+    pub threshold: f32,
+    #[serde(default = "default_max_actions")]
+    pub max_actions: usize,
+    // Activities are reported until their clip is this old, and a gap this
+    // long between frames starts the clip over
+    #[serde(default = "default_activity_ttl")]
+    pub activity_ttl_secs: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlowFastConfig {
+    pub alpha: usize,
+    pub slow_input: String,
+    pub fast_input: String,
+}
+
+fn default_action_input() -> (i32, i32) {
+    (224, 224)
+}
+
+fn default_clip_length() -> usize {
+    16
+}
+
+fn default_frame_stride() -> usize {
+    2
+}
+
+fn default_mean() -> [f32; 3] {
+    [0.45, 0.45, 0.45]
+}
+
+fn default_std() -> [f32; 3] {
+    [0.225, 0.225, 0.225]
+}
+
+fn default_action_threshold() -> f32 {
+    0.3
+}
+
+fn default_max_actions() -> usize {
+    3
+}
+
+fn default_activity_ttl() -> f32 {
+    5.0
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionLabel {
+    pub label: String,
+    pub confidence: f32,
+}
+
+// Frames ready for the model: BGR, already at the model's input size
+pub struct Clip {
+    pub frames: Vec<Mat>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl Clip {
+    pub fn duration(&self) -> f32 {
+        (self.end - self.start).num_milliseconds().max(0) as f32 / 1000.0
+    }
+}
+
+struct ClipFrame {
+    id: u64,
+    timestamp: DateTime<Utc>,
+    image: Mat,
+}
+
+pub struct ClipBuffer {
+    clip_length: usize,
+    frame_stride: usize,
+    hop: usize,
+    input_size: (i32, i32),
+    offered: u64,
+    since_clip: usize,
+    frames: VecDeque<ClipFrame>,
+}
+
+impl ClipBuffer {
+    pub fn new(config: &ActionModelConfig) -> Self {
+        let clip_length = config.clip_length.max(1);
+        Self {
+            clip_length,
+            frame_stride: config.frame_stride.max(1),
+            hop: config.hop.unwrap_or(clip_length / 2).max(1),
+            input_size: config.input_size,
+            offered: 0,
+            since_clip: 0,
+            frames: VecDeque::with_capacity(clip_length),
+        }
+    }
+
+    // Returns a clip when the buffer is full and `hop` new frames have come
+    // in since the last one. Frames are shrunk on the way in, so a buffer
+    // costs a few MB however large the stream.
+    pub fn push(&mut self, frame: &Frame) -> Result<Option<Clip>> {
+        self.offered += 1;
+        if (self.offered - 1) % self.frame_stride as u64 != 0 {
+            return Ok(None);
+        }
+
+        let image = prepare_frame(&frame.data, self.input_size)?;
+        // Pipeline workers can finish frames slightly out of order
+        let position = self.frames.iter().rposition(|f| f.id < frame.id).map_or(0, |i| i + 1);
+        self.frames.insert(position, ClipFrame { id: frame.id, timestamp: frame.timestamp, image });
+        while self.frames.len() > self.clip_length {
+            self.frames.pop_front();
+        }
+
+        self.since_clip += 1;
+        if self.frames.len() < self.clip_length || self.since_clip < self.hop {
+            return Ok(None);
+        }
+        self.since_clip = 0;
+
+        let frames = self.frames.iter()
+            .map(|f| f.image.try_clone())
+            .collect::<opencv::Result<Vec<_>>>()?;
+        Ok(Some(Clip {
+            frames,
+            start: self.frames.front().map(|f| f.timestamp).unwrap_or_default(),
+            end: self.frames.back().map(|f| f.timestamp).unwrap_or_default(),
+        }))
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+// One source's clip buffer and the activities from its latest clip
+pub struct ActionTrack {
+    config: ActionModelConfig,
+    buffer: ClipBuffer,
+    activities: Vec<Activity>,
+    clip_end: Option<DateTime<Utc>>,
+    last_frame: Option<DateTime<Utc>>,
+}
+
+impl ActionTrack {
+    pub fn new(config: &ActionModelConfig) -> Self {
+        Self {
+            config: config.clone(),
+            buffer: ClipBuffer::new(config),
+            activities: Vec::new(),
+            clip_end: None,
+            last_frame: None,
+        }
+    }
+
+    // Adds a frame, runs `recognize` when it completes a clip and returns
+    // the activities still current at this frame
+    pub fn observe(
+        &mut self,
+        frame: &Frame,
+        detections: &[Detection],
+        recognize: impl FnOnce(&Clip) -> Result<Vec<ActionLabel>>,
+    ) -> Result<Vec<Activity>> {
+        let ttl = Duration::milliseconds((self.config.activity_ttl_secs * 1000.0) as i64);
+        if self.last_frame.is_some_and(|last| frame.timestamp - last > ttl) {
+            self.buffer = ClipBuffer::new(&self.config);
+        }
+        self.last_frame = self.last_frame.max(Some(frame.timestamp));
+
+        if let Some(clip) = self.buffer.push(frame)? {
+            self.activities = to_activities(&recognize(&clip)?, &clip, detections);
+            self.clip_end = Some(clip.end);
+        }
+        if self.clip_end.is_none_or(|end| frame.timestamp - end > ttl) {
+            self.activities.clear();
+        }
+        Ok(self.activities.clone())
+    }
+}
+
+// ActionTrack::observe() with the recognizer, on the blocking pool: frames
+// are resized on the way in and a full clip runs the model
+pub async fn observe(
+    recognizer: &Arc<ActionRecognizer>,
+    track: &Arc<Mutex<ActionTrack>>,
+    frame: &Frame,
+    detections: &[Detection],
+) -> Result<Vec<Activity>> {
+    let (recognizer, track) = (recognizer.clone(), track.clone());
+    let (frame, detections) = (frame.clone(), detections.to_vec());
+    tokio::task::spawn_blocking(move || {
+        track.lock().unwrap().observe(&frame, &detections, |clip| recognizer.recognize(clip))
+    })
+    .await
+    .context("Action recognition panicked")?
+}
+
+fn prepare_frame(image: &Mat, input_size: (i32, i32)) -> Result<Mat> {
+    let mut bgr = Mat::default();
+    match image.channels() {
+        1 => imgproc::cvt_color(image, &mut bgr, imgproc::COLOR_GRAY2BGR, 0)?,
+        4 => imgproc::cvt_color(image, &mut bgr, imgproc::COLOR_BGRA2BGR, 0)?,
+        _ => bgr = image.try_clone()?,
+    }
+    let mut resized = Mat::default();
+    imgproc::resize(&bgr, &mut resized, Size::new(input_size.0, input_size.1), 0.0, 0.0, imgproc::INTER_AREA)
+        .context("Failed to resize clip frame")?;
+    Ok(resized)
+}
+
+pub struct ActionRecognizer {
+    config: ActionModelConfig,
+    // dnn::Net isn't Sync and forward() needs &mut
+    net: Mutex<dnn::Net>,
+}
+
+impl ActionRecognizer {
+    pub fn new(config: ActionModelConfig) -> Result<Self> {
+        if config.labels.is_empty() {
+            return Err(anyhow::anyhow!("Action model {} has no labels", config.model_path));
+        }
+        if let Some(slowfast) = &config.slowfast {
+            if slowfast.alpha == 0 || config.clip_length % slowfast.alpha != 0 {
+                return Err(anyhow::anyhow!(
+                    "SlowFast alpha {} must divide the clip length {}", slowfast.alpha, config.clip_length
+                ));
+            }
+        }
+        let net = dnn::read_net_from_onnx(&config.model_path)
+            .with_context(|| format!("Failed to load action model {}", config.model_path))?;
+
+        Ok(Self {
+            config,
+            net: Mutex::new(net),
+        })
+    }
+
+    pub fn config(&self) -> &ActionModelConfig {
+        &self.config
+    }
+
+    pub fn clip_buffer(&self) -> ClipBuffer {
+        ClipBuffer::new(&self.config)
+    }
+
+    pub fn track(&self) -> ActionTrack {
+        ActionTrack::new(&self.config)
+    }
+
+    pub fn recognize(&self, clip: &Clip) -> Result<Vec<ActionLabel>> {
+        let output = {
+            let mut net = self.net.lock().unwrap();
+            match &self.config.slowfast {
+                Some(slowfast) => {
+                    let slow: Vec<&Mat> = clip.frames.iter().step_by(slowfast.alpha).collect();
+                    let fast: Vec<&Mat> = clip.frames.iter().collect();
+                    net.set_input(&self.clip_tensor(&slow)?, &slowfast.slow_input, 1.0, Scalar::default())?;
+                    net.set_input(&self.clip_tensor(&fast)?, &slowfast.fast_input, 1.0, Scalar::default())?;
+                }
+                None => {
+                    let frames: Vec<&Mat> = clip.frames.iter().collect();
+                    net.set_input(&self.clip_tensor(&frames)?, "", 1.0, Scalar::default())?;
+                }
+            }
+            net.forward_single("").context("Action model inference failed")?
+        };
+
+        let logits = output.data_typed::<f32>()?;
+        if logits.len() != self.config.labels.len() {
+            return Err(anyhow::anyhow!(
+                "Action model produced {} outputs for {} labels", logits.len(), self.config.labels.len()
+            ));
+        }
+        Ok(select_actions(logits, &self.config.labels, self.config.threshold, self.config.max_actions))
+    }
+
+    // 1 x C x T x H x W, normalised RGB, the layout video models export with
+    fn clip_tensor(&self, frames: &[&Mat]) -> Result<Mat> {
+        let (width, height) = (self.config.input_size.0 as usize, self.config.input_size.1 as usize);
+        let plane = width * height;
+        let t = frames.len();
+        let mut tensor = Mat::new_nd_with_default(
+            &[1, 3, t as i32, height as i32, width as i32],
+            CV_32F,
+            Scalar::all(0.0),
+        )?;
+        let data = tensor.data_typed_mut::<f32>()?;
+
+        for (index, frame) in frames.iter().enumerate() {
+            let pixels = frame.data_bytes()?;
+            if pixels.len() != plane * 3 {
+                return Err(anyhow::anyhow!("Clip frame {} has the wrong size for the action model", index));
+            }
+            for (i, bgr) in pixels.chunks_exact(3).enumerate() {
+                for c in 0..3 {
+                    // BGR in, RGB out
+                    let value = bgr[2 - c] as f32 / 255.0;
+                    data[(c * t + index) * plane + i] = (value - self.config.mean[c]) / self.config.std[c];
+                }
+            }
+        }
+        Ok(tensor)
+    }
+}
+
+// Single-label video models: softmax across all outputs, then everything
+// above the threshold, best first
+pub fn select_actions(logits: &[f32], labels: &[String], threshold: f32, max_actions: usize) -> Vec<ActionLabel> {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let exps: Vec<f32> = logits.iter().map(|l| (l - max).exp()).collect();
+    let sum: f32 = exps.iter().sum();
+    if sum <= 0.0 || !sum.is_finite() {
+        return Vec::new();
+    }
+
+    let mut selected: Vec<ActionLabel> = exps.iter()
+        .zip(labels)
+        .map(|(e, label)| ActionLabel { label: label.clone(), confidence: e / sum })
+        .filter(|a| a.confidence >= threshold)
+        .collect();
+    selected.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    selected.truncate(max_actions);
+    selected
+}
+
+// The model sees the whole frame, so every class detected in the clip's
+// last frame is taken to be involved
+pub fn to_activities(actions: &[ActionLabel], clip: &Clip, detections: &[Detection]) -> Vec<Activity> {
+    let mut objects: Vec<String> = detections.iter().map(|d| d.class_name.clone()).collect();
+    objects.sort();
+    objects.dedup();
+
+    actions.iter()
+        .map(|action| Activity {
+            action_type: action.label.clone(),
+            confidence: action.confidence,
+            duration: clip.duration(),
+            objects_involved: objects.clone(),
+        })
+        .collect()
+}
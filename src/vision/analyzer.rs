@@ -1,3 +1,6 @@
+#![cfg(feature = "vision")]
+
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use anyhow::{Result, Context};
@@ -9,8 +12,12 @@ use opencv::{
 };
 
 use crate::vision::{
+    actions::{self, ActionModelConfig, ActionRecognizer, ActionTrack},
     processor::Frame,
     detector::Detection,
+    conditions::{ConditionConfig, ConditionDetector, SceneCondition},
+    density::{DensityConfig, DensityEstimator, DensityInfo},
+    scene::{classify_lighting, composition, lighting_stats, SceneClassifier, SceneLabel, SceneModelConfig},
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +27,19 @@ pub struct AnalyzerConfig {
     pub motion_threshold: f32,
     pub tracking_config: TrackingConfig,
     pub batch_size: usize,
+    #[serde(default)]
+    pub condition_config: Option<ConditionConfig>,
+    // Required when `Density` is enabled
+    #[serde(default)]
+    pub density: Option<DensityConfig>,
+    // Without a model the scene analyzer still reports lighting and
+    // composition, with an "unknown" scene type
+    #[serde(default)]
+    pub scene_model: Option<SceneModelConfig>,
+    // Clip-level action recognition for the behavior analyzer; without it
+    // no activities are reported
+    #[serde(default)]
+    pub action_model: Option<ActionModelConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,6 +48,9 @@ pub enum AnalyzerType {
     Motion,
     Behavior,
     Pattern,
+    // Crowd counting from a density map, for scenes too packed for
+    // per-person detection
+    Density,
     Custom(String),
 }
 
@@ -39,7 +62,7 @@ pub struct TrackingConfig {
     pub min_hits: usize,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Analysis {
     pub frame_id: u64,
     pub timestamp: chrono::DateTime<chrono::Utc>,
@@ -47,39 +70,43 @@ pub struct Analysis {
     pub motion_info: Option<MotionInfo>,
     pub behavior_info: Option<BehaviorInfo>,
     pub pattern_info: Option<PatternInfo>,
+    pub density_info: Option<DensityInfo>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SceneInfo {
+    // Highest-confidence label, kept for consumers that want a single type
     pub scene_type: String,
     pub confidence: f32,
+    pub labels: Vec<SceneLabel>,
     pub objects: Vec<String>,
     pub lighting: String,
     pub composition: String,
+    pub condition: Option<SceneCondition>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MotionInfo {
     pub motion_vectors: Vec<MotionVector>,
     pub global_motion: f32,
     pub motion_areas: Vec<Rect>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BehaviorInfo {
     pub activities: Vec<Activity>,
     pub interactions: Vec<Interaction>,
     pub anomalies: Vec<Anomaly>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PatternInfo {
     pub patterns: Vec<Pattern>,
     pub repetitions: Vec<Repetition>,
     pub temporal_info: TemporalInfo,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MotionVector {
     pub start: Point,
     pub end: Point,
@@ -87,7 +114,7 @@ pub struct MotionVector {
     pub direction: f32,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Activity {
     pub action_type: String,
     pub confidence: f32,
@@ -95,35 +122,35 @@ pub struct Activity {
     pub objects_involved: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Interaction {
     pub interaction_type: String,
     pub objects: Vec<String>,
     pub duration: f32,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Anomaly {
     pub anomaly_type: String,
     pub confidence: f32,
     pub description: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Pattern {
     pub pattern_type: String,
     pub confidence: f32,
     pub description: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Repetition {
     pub event_type: String,
     pub frequency: f32,
     pub duration: f32,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TemporalInfo {
     pub start_time: chrono::DateTime<chrono::Utc>,
     pub end_time: chrono::DateTime<chrono::Utc>,
@@ -133,15 +160,36 @@ pub struct TemporalInfo {
 pub struct Analyzer {
     config: AnalyzerConfig,
     previous_frame: Option<Arc<Mat>>,
+    // Scene condition per camera, created on first sight
+    condition_detectors: HashMap<String, ConditionDetector>,
+    density_estimator: Option<DensityEstimator>,
+    scene_classifier: Option<SceneClassifier>,
+    action_recognizer: Option<Arc<ActionRecognizer>>,
+    // Clip buffer and latest activities per camera
+    action_tracks: Mutex<HashMap<String, Arc<std::sync::Mutex<ActionTrack>>>>,
     motion_history: Arc<Mutex<Vec<MotionInfo>>>,
     behavior_history: Arc<Mutex<Vec<BehaviorInfo>>>,
 }
 
 impl Analyzer {
     pub fn new(config: AnalyzerConfig) -> Result<Self> {
+        let wants_density = config.enabled_analyzers.iter().any(|a| matches!(a, AnalyzerType::Density));
+        let density_estimator = match (&config.density, wants_density) {
+            (Some(density), true) => Some(DensityEstimator::new(density.clone())?),
+            (None, true) => return Err(anyhow::anyhow!("Density analyzer enabled without a density model")),
+            (_, false) => None,
+        };
+        let scene_classifier = config.scene_model.clone().map(SceneClassifier::new).transpose()?;
+        let action_recognizer = config.action_model.clone().map(ActionRecognizer::new).transpose()?.map(Arc::new);
+
         Ok(Self {
             config,
             previous_frame: None,
+            condition_detectors: HashMap::new(),
+            density_estimator,
+            scene_classifier,
+            action_recognizer,
+            action_tracks: Mutex::new(HashMap::new()),
             motion_history: Arc::new(Mutex::new(Vec::new())),
             behavior_history: Arc::new(Mutex::new(Vec::new())),
         })
@@ -155,12 +203,22 @@ impl Analyzer {
             motion_info: None,
             behavior_info: None,
             pattern_info: None,
+            density_info: None,
+        };
+
+        let condition = match &self.config.condition_config {
+            Some(config) => {
+                let detector = self.condition_detectors.entry(frame.metadata.source.clone())
+                    .or_insert_with(|| ConditionDetector::new(config.clone()));
+                Some(detector.update(&frame.data)?)
+            }
+            None => None,
         };
 
         for analyzer_type in &self.config.enabled_analyzers {
             match analyzer_type {
                 AnalyzerType::Scene => {
-                    analysis.scene_info = Some(self.analyze_scene(frame, detections)?);
+                    analysis.scene_info = Some(self.analyze_scene(frame, detections, condition)?);
                 }
                 AnalyzerType::Motion => {
                     analysis.motion_info = Some(self.analyze_motion(frame)?);
@@ -171,6 +229,11 @@ impl Analyzer {
                 AnalyzerType::Pattern => {
                     analysis.pattern_info = Some(self.analyze_patterns(frame, detections).await?);
                 }
+                AnalyzerType::Density => {
+                    if let Some(estimator) = &self.density_estimator {
+                        analysis.density_info = Some(estimator.estimate(&frame.data)?);
+                    }
+                }
                 AnalyzerType::Custom(name) => {
                     self.run_custom_analysis(name, frame, detections)?;
                 }
@@ -181,16 +244,42 @@ impl Analyzer {
         Ok(analysis)
     }
 
-    fn analyze_scene(&self, frame: &Frame, detections: &[Detection]) -> Result<SceneInfo> {
-        // Implement scene analysis logic
+    pub fn current_condition(&self, source: &str) -> Option<SceneCondition> {
+        self.condition_detectors.get(source).map(|d| d.current())
+    }
+
+    fn analyze_scene(
+        &self,
+        frame: &Frame,
+        detections: &[Detection],
+        condition: Option<SceneCondition>,
+    ) -> Result<SceneInfo> {
+        let labels = match &self.scene_classifier {
+            Some(classifier) => classifier.classify(&frame.data)?,
+            None => Vec::new(),
+        };
+        let (scene_type, confidence) = labels.first()
+            .map(|l| (l.label.clone(), l.confidence))
+            .unwrap_or_else(|| ("unknown".to_string(), 0.0));
+
+        let mut objects: Vec<String> = detections.iter()
+            .map(|d| d.class_name.clone())
+            .collect();
+        objects.sort();
+        objects.dedup();
+
         Ok(SceneInfo {
-            scene_type: "indoor".to_string(),
-            confidence: 0.95,
-            objects: detections.iter()
-                .map(|d| d.class_name.clone())
-                .collect(),
-            lighting: "bright".to_string(),
-            composition: "balanced".to_string(),
+            scene_type,
+            confidence,
+            labels,
+            objects,
+            lighting: classify_lighting(&lighting_stats(&frame.data)?).to_string(),
+            composition: composition(
+                detections,
+                frame.metadata.width as f32,
+                frame.metadata.height as f32,
+            ).to_string(),
+            condition,
         })
     }
 
@@ -218,9 +307,19 @@ impl Analyzer {
     }
 
     async fn analyze_behavior(&self, frame: &Frame, detections: &[Detection]) -> Result<BehaviorInfo> {
-        // Implement behavior analysis logic
+        let activities = match &self.action_recognizer {
+            Some(recognizer) => {
+                let track = self.action_tracks.lock().await
+                    .entry(frame.metadata.source.clone())
+                    .or_insert_with(|| Arc::new(std::sync::Mutex::new(recognizer.track())))
+                    .clone();
+                actions::observe(recognizer, &track, frame, detections).await?
+            }
+            None => Vec::new(),
+        };
+
         Ok(BehaviorInfo {
-            activities: Vec::new(),
+            activities,
             interactions: Vec::new(),
             anomalies: Vec::new(),
         })
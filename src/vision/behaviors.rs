@@ -0,0 +1,337 @@
+#![cfg(feature = "vision")]
+
+use std::collections::{HashMap, HashSet};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use opencv::core::Mat;
+
+use crate::vision::analyzer::Anomaly;
+use crate::vision::detector::BBox;
+use crate::vision::geometry::{center, distance};
+use crate::vision::tracker::{encode_crop, TrackSummary};
+use crate::vision::zones::Zone;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BehaviorConfig {
+    #[serde(default)]
+    pub loitering: Option<LoiteringConfig>,
+    #[serde(default)]
+    pub abandoned_object: Option<AbandonedObjectConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoiteringConfig {
+    pub classes: Vec<String>,
+    // Empty means the whole frame is watched
+    pub zones: Vec<Zone>,
+    pub dwell_secs: f64,
+    // Movement (in pixels) beyond which the dwell clock restarts
+    pub max_displacement: f32,
+}
+
+impl Default for LoiteringConfig {
+    fn default() -> Self {
+        Self {
+            classes: vec!["person".to_string()],
+            zones: Vec::new(),
+            dwell_secs: 60.0,
+            max_displacement: 50.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AbandonedObjectConfig {
+    pub object_classes: Vec<String>,
+    pub owner_classes: Vec<String>,
+    // Owner must be this close (centre to centre, pixels) to claim an object
+    pub owner_distance: f32,
+    pub unattended_secs: f64,
+    pub max_displacement: f32,
+}
+
+impl Default for AbandonedObjectConfig {
+    fn default() -> Self {
+        Self {
+            object_classes: vec![
+                "backpack".to_string(),
+                "suitcase".to_string(),
+                "handbag".to_string(),
+            ],
+            owner_classes: vec!["person".to_string()],
+            owner_distance: 150.0,
+            unattended_secs: 30.0,
+            max_displacement: 20.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Evidence {
+    pub frame_id: u64,
+    pub timestamp: DateTime<Utc>,
+    pub bbox: BBox,
+    // Base64 JPEG crop, when the frame was available
+    pub image: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BehaviorAnomaly {
+    Loitering {
+        track_id: u64,
+        class_name: String,
+        zone: Option<String>,
+        dwell_secs: f64,
+        evidence: Vec<Evidence>,
+    },
+    AbandonedObject {
+        object_track_id: u64,
+        class_name: String,
+        owner_track_id: Option<u64>,
+        unattended_secs: f64,
+        evidence: Vec<Evidence>,
+    },
+}
+
+impl From<&BehaviorAnomaly> for Anomaly {
+    fn from(anomaly: &BehaviorAnomaly) -> Self {
+        match anomaly {
+            BehaviorAnomaly::Loitering { track_id, class_name, zone, dwell_secs, .. } => Anomaly {
+                anomaly_type: "loitering".to_string(),
+                confidence: 1.0,
+                description: format!(
+                    "{} (track {}) stationary{} for {:.0}s",
+                    class_name,
+                    track_id,
+                    zone.as_ref().map(|z| format!(" in {}", z)).unwrap_or_default(),
+                    dwell_secs,
+                ),
+            },
+            BehaviorAnomaly::AbandonedObject { object_track_id, class_name, unattended_secs, .. } => Anomaly {
+                anomaly_type: "abandoned_object".to_string(),
+                confidence: 1.0,
+                description: format!(
+                    "{} (track {}) left unattended for {:.0}s",
+                    class_name, object_track_id, unattended_secs,
+                ),
+            },
+        }
+    }
+}
+
+// The frame a detector is looking at, for timing and evidence crops
+pub struct FrameRef<'a> {
+    pub frame_id: u64,
+    pub timestamp: DateTime<Utc>,
+    pub image: Option<&'a Mat>,
+}
+
+impl FrameRef<'_> {
+    fn evidence(&self, bbox: &BBox) -> Evidence {
+        Evidence {
+            frame_id: self.frame_id,
+            timestamp: self.timestamp,
+            bbox: bbox.clone(),
+            image: self.image.and_then(|image| encode_crop(image, bbox).ok()),
+        }
+    }
+}
+
+struct LoiterState {
+    zone: Option<String>,
+    anchor: (f32, f32),
+    since: DateTime<Utc>,
+    first: Evidence,
+    reported: bool,
+}
+
+pub struct LoiteringDetector {
+    config: LoiteringConfig,
+    states: HashMap<u64, LoiterState>,
+}
+
+impl LoiteringDetector {
+    pub fn new(config: LoiteringConfig) -> Self {
+        Self {
+            config,
+            states: HashMap::new(),
+        }
+    }
+
+    fn zone_for(&self, bbox: &BBox) -> Option<Option<String>> {
+        if self.config.zones.is_empty() {
+            return Some(None);
+        }
+        self.config.zones.iter()
+            .find(|zone| zone.contains_bbox(bbox))
+            .map(|zone| Some(zone.name.clone()))
+    }
+
+    pub fn update(&mut self, frame: &FrameRef, tracks: &[TrackSummary]) -> Vec<BehaviorAnomaly> {
+        let mut anomalies = Vec::new();
+        let mut seen = HashSet::new();
+
+        for track in tracks.iter().filter(|t| self.config.classes.contains(&t.class_name)) {
+            let zone = match self.zone_for(&track.bbox) {
+                Some(zone) => zone,
+                None => continue,
+            };
+            seen.insert(track.track_id);
+            let position = center(&track.bbox);
+
+            let restart = match self.states.get(&track.track_id) {
+                Some(state) => state.zone != zone || distance(state.anchor, position) > self.config.max_displacement,
+                None => true,
+            };
+            if restart {
+                self.states.insert(track.track_id, LoiterState {
+                    zone,
+                    anchor: position,
+                    since: frame.timestamp,
+                    first: frame.evidence(&track.bbox),
+                    reported: false,
+                });
+                continue;
+            }
+
+            let state = self.states.get_mut(&track.track_id).unwrap();
+            let dwell_secs = (frame.timestamp - state.since).num_milliseconds() as f64 / 1000.0;
+            if !state.reported && dwell_secs >= self.config.dwell_secs {
+                state.reported = true;
+                anomalies.push(BehaviorAnomaly::Loitering {
+                    track_id: track.track_id,
+                    class_name: track.class_name.clone(),
+                    zone: state.zone.clone(),
+                    dwell_secs,
+                    evidence: vec![state.first.clone(), frame.evidence(&track.bbox)],
+                });
+            }
+        }
+
+        // Leaving the zone (or the track dying) re-arms the detector
+        self.states.retain(|track_id, _| seen.contains(track_id));
+        anomalies
+    }
+}
+
+struct ObjectState {
+    anchor: (f32, f32),
+    owner: Option<u64>,
+    appeared: Evidence,
+    unattended_since: Option<DateTime<Utc>>,
+    reported: bool,
+}
+
+pub struct AbandonedObjectDetector {
+    config: AbandonedObjectConfig,
+    objects: HashMap<u64, ObjectState>,
+}
+
+impl AbandonedObjectDetector {
+    pub fn new(config: AbandonedObjectConfig) -> Self {
+        Self {
+            config,
+            objects: HashMap::new(),
+        }
+    }
+
+    pub fn update(&mut self, frame: &FrameRef, tracks: &[TrackSummary]) -> Vec<BehaviorAnomaly> {
+        let mut anomalies = Vec::new();
+        let owners: Vec<&TrackSummary> = tracks.iter()
+            .filter(|t| self.config.owner_classes.contains(&t.class_name))
+            .collect();
+        let nearest_owner = |position: (f32, f32)| {
+            owners.iter()
+                .map(|o| (o.track_id, distance(center(&o.bbox), position)))
+                .filter(|(_, d)| *d <= self.config.owner_distance)
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+                .map(|(id, _)| id)
+        };
+
+        let mut seen = HashSet::new();
+        for object in tracks.iter().filter(|t| self.config.object_classes.contains(&t.class_name)) {
+            seen.insert(object.track_id);
+            let position = center(&object.bbox);
+
+            let moved = self.objects.get(&object.track_id)
+                .map_or(true, |state| distance(state.anchor, position) > self.config.max_displacement);
+            if moved {
+                // A carried object is attended by definition; start over
+                // once it comes to rest, owned by whoever is next to it
+                self.objects.insert(object.track_id, ObjectState {
+                    anchor: position,
+                    owner: nearest_owner(position),
+                    appeared: frame.evidence(&object.bbox),
+                    unattended_since: None,
+                    reported: false,
+                });
+                continue;
+            }
+
+            let state = self.objects.get_mut(&object.track_id).unwrap();
+            if state.owner.is_none() {
+                state.owner = nearest_owner(position);
+            }
+
+            // Attended while the owner (or, for an ownerless object, anyone)
+            // is within reach
+            let attended = match state.owner {
+                Some(owner) => owners.iter()
+                    .any(|o| o.track_id == owner && distance(center(&o.bbox), position) <= self.config.owner_distance),
+                None => nearest_owner(position).is_some(),
+            };
+            if attended {
+                state.unattended_since = None;
+                continue;
+            }
+
+            let since = *state.unattended_since.get_or_insert(frame.timestamp);
+            let unattended_secs = (frame.timestamp - since).num_milliseconds() as f64 / 1000.0;
+            if !state.reported && unattended_secs >= self.config.unattended_secs {
+                state.reported = true;
+                anomalies.push(BehaviorAnomaly::AbandonedObject {
+                    object_track_id: object.track_id,
+                    class_name: object.class_name.clone(),
+                    owner_track_id: state.owner,
+                    unattended_secs,
+                    evidence: vec![state.appeared.clone(), frame.evidence(&object.bbox)],
+                });
+            }
+        }
+
+        self.objects.retain(|track_id, _| seen.contains(track_id));
+        anomalies
+    }
+}
+
+pub struct BehaviorDetectors {
+    loitering: Option<LoiteringDetector>,
+    abandoned_object: Option<AbandonedObjectDetector>,
+}
+
+impl BehaviorDetectors {
+    pub fn new(config: &BehaviorConfig) -> Self {
+        Self {
+            loitering: config.loitering.clone().map(LoiteringDetector::new),
+            abandoned_object: config.abandoned_object.clone().map(AbandonedObjectDetector::new),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.loitering.is_none() && self.abandoned_object.is_none()
+    }
+
+    pub fn update(&mut self, frame: &FrameRef, tracks: &[TrackSummary]) -> Vec<BehaviorAnomaly> {
+        let mut anomalies = Vec::new();
+        if let Some(detector) = self.loitering.as_mut() {
+            anomalies.extend(detector.update(frame, tracks));
+        }
+        if let Some(detector) = self.abandoned_object.as_mut() {
+            anomalies.extend(detector.update(frame, tracks));
+        }
+        anomalies
+    }
+}
@@ -0,0 +1,520 @@
+#![cfg(feature = "vision")]
+
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use lru::LruCache;
+use serde::{Serialize, Deserialize};
+use opencv::{
+    prelude::*,
+    core::{self, Mat, Point, Rect, Size, Vector, CV_32F},
+    imgproc,
+};
+
+use crate::vision::{
+    detector::{BBox, Detection, Detector, DetectorConfig},
+    ensemble::{merge, EnsembleStrategy, MemberOutput},
+    geometry,
+    processor::{Frame, FrameMetadata},
+    shadow::DetectionModel,
+};
+
+// Cascade detection: a cheap gate decides whether the heavy model runs on
+// a frame at all, and if so whether on the whole frame or only on the
+// regions where something is happening. On a mostly static camera the
+// heavy model then runs on a small share of the pixels.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CascadeGate {
+    // Frame differencing against a running background
+    Motion(MotionGateConfig),
+    // A small detector; its boxes become the regions. `classes` limits
+    // which of them count, empty meaning all.
+    Detector {
+        detector: DetectorConfig,
+        #[serde(default)]
+        classes: Vec<String>,
+    },
+}
+
+impl Default for CascadeGate {
+    fn default() -> Self {
+        CascadeGate::Motion(MotionGateConfig::default())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MotionGateConfig {
+    // Differencing runs on a copy scaled down to this width
+    pub downscale_width: i32,
+    // Grey-level change that counts as motion
+    pub pixel_threshold: f64,
+    // Blobs smaller than this share of the frame are noise
+    pub min_area: f32,
+    // How fast the background absorbs change; higher forgets parked cars
+    // sooner but also people standing still
+    pub learning_rate: f64,
+}
+
+impl Default for MotionGateConfig {
+    fn default() -> Self {
+        Self {
+            downscale_width: 320,
+            pixel_threshold: 25.0,
+            min_area: 0.001,
+            learning_rate: 0.05,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CascadeMode {
+    // Any activity runs the heavy model on the whole frame
+    FullFrame,
+    // The heavy model runs on crops around the active regions
+    Regions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CascadeConfig {
+    pub gate: CascadeGate,
+    pub mode: CascadeMode,
+    // Pixels added around each region, so an object half in it is seen whole
+    pub region_padding: f32,
+    // Smaller regions are grown to this size around their centre; models
+    // need some context to recognise anything
+    pub min_region_size: f32,
+    // More regions than this, or regions covering more than
+    // `full_frame_ratio` of the frame, and one full pass is cheaper
+    pub max_regions: usize,
+    pub full_frame_ratio: f32,
+    // Frames the last regions keep being checked after the gate goes quiet,
+    // so someone who stops moving isn't dropped at once
+    pub hold_frames: u32,
+    // Runs a full frame at least this often regardless, to pick up objects
+    // that were already still when the stream started
+    pub keyframe_interval: Option<u64>,
+    pub nms_threshold: f32,
+    // Cameras with gate state kept at once. Past this the least recently
+    // seen is forgotten, and starts over as if new when it comes back.
+    pub max_sources: usize,
+}
+
+impl Default for CascadeConfig {
+    fn default() -> Self {
+        Self {
+            gate: CascadeGate::default(),
+            mode: CascadeMode::Regions,
+            region_padding: 32.0,
+            min_region_size: 160.0,
+            max_regions: 4,
+            full_frame_ratio: 0.5,
+            hold_frames: 5,
+            keyframe_interval: Some(150),
+            nms_threshold: 0.45,
+            max_sources: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CascadePlan {
+    Skip,
+    FullFrame,
+    Regions(Vec<BBox>),
+}
+
+fn touches(a: &BBox, b: &BBox) -> bool {
+    a.x <= b.x + b.width && b.x <= a.x + a.width && a.y <= b.y + b.height && b.y <= a.y + a.height
+}
+
+fn union(a: &BBox, b: &BBox) -> BBox {
+    let x = a.x.min(b.x);
+    let y = a.y.min(b.y);
+    BBox {
+        x,
+        y,
+        width: (a.x + a.width).max(b.x + b.width) - x,
+        height: (a.y + a.height).max(b.y + b.height) - y,
+    }
+}
+
+// Grows a box to at least `size` on each side around its centre, shifted
+// back inside the frame where it would stick out
+fn grow(bbox: &BBox, size: f32, width: f32, height: f32) -> BBox {
+    let (cx, cy) = geometry::center(bbox);
+    let w = bbox.width.max(size).min(width);
+    let h = bbox.height.max(size).min(height);
+    BBox {
+        x: (cx - w / 2.0).clamp(0.0, width - w),
+        y: (cy - h / 2.0).clamp(0.0, height - h),
+        width: w,
+        height: h,
+    }
+}
+
+// Turns the gate's regions into crops for the heavy model: padded, grown
+// to the minimum size and merged where they touch, so an object on the
+// border of two regions is seen in one crop
+pub fn plan_regions(regions: &[BBox], frame_size: (f32, f32), config: &CascadeConfig) -> CascadePlan {
+    let (width, height) = frame_size;
+    if regions.is_empty() {
+        return CascadePlan::Skip;
+    }
+    if config.mode == CascadeMode::FullFrame || width <= 0.0 || height <= 0.0 {
+        return CascadePlan::FullFrame;
+    }
+
+    let padding = config.region_padding.max(0.0);
+    let mut merged: Vec<BBox> = regions.iter()
+        .filter_map(|r| geometry::clip(
+            &BBox { x: r.x - padding, y: r.y - padding, width: r.width + 2.0 * padding, height: r.height + 2.0 * padding },
+            width,
+            height,
+        ))
+        .map(|r| grow(&r, config.min_region_size, width, height))
+        .collect();
+    if merged.is_empty() {
+        return CascadePlan::Skip;
+    }
+
+    // Growing a merged box can make it touch another, so repeat until
+    // nothing changes
+    loop {
+        let mut changed = false;
+        let mut i = 0;
+        while i < merged.len() {
+            let mut j = i + 1;
+            while j < merged.len() {
+                if touches(&merged[i], &merged[j]) {
+                    let other = merged.swap_remove(j);
+                    merged[i] = grow(&union(&merged[i], &other), config.min_region_size, width, height);
+                    changed = true;
+                } else {
+                    j += 1;
+                }
+            }
+            i += 1;
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let covered: f32 = merged.iter().map(geometry::area).sum();
+    if merged.len() > config.max_regions || covered > config.full_frame_ratio * width * height {
+        return CascadePlan::FullFrame;
+    }
+    merged.sort_by(|a, b| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+    CascadePlan::Regions(merged)
+}
+
+// Running-average background per source. The first frame of a source has
+// nothing to compare with and counts as motion everywhere.
+struct MotionGate {
+    config: MotionGateConfig,
+    background: Option<Mat>,
+}
+
+impl MotionGate {
+    fn new(config: MotionGateConfig) -> Self {
+        Self { config, background: None }
+    }
+
+    fn regions(&mut self, image: &Mat) -> Result<Vec<BBox>> {
+        let (cols, rows) = (image.cols(), image.rows());
+        if cols <= 0 || rows <= 0 {
+            return Err(anyhow::anyhow!("Motion gate got an empty frame"));
+        }
+        let scale = (self.config.downscale_width.max(16) as f32 / cols as f32).min(1.0);
+        let size = Size::new(((cols as f32 * scale) as i32).max(1), ((rows as f32 * scale) as i32).max(1));
+
+        let mut small = Mat::default();
+        imgproc::resize(image, &mut small, size, 0.0, 0.0, imgproc::INTER_AREA)?;
+        let mut gray = Mat::default();
+        match small.channels() {
+            1 => gray = small,
+            4 => imgproc::cvt_color(&small, &mut gray, imgproc::COLOR_BGRA2GRAY, 0)?,
+            _ => imgproc::cvt_color(&small, &mut gray, imgproc::COLOR_BGR2GRAY, 0)?,
+        }
+        let mut blurred = Mat::default();
+        imgproc::gaussian_blur(&gray, &mut blurred, Size::new(5, 5), 0.0, 0.0, core::BORDER_DEFAULT)?;
+        let mut current = Mat::default();
+        blurred.convert_to(&mut current, CV_32F, 1.0, 0.0)?;
+
+        // A resolution change restarts the background
+        let background = match self.background.take() {
+            Some(background) if background.size()? == current.size()? => background,
+            _ => {
+                self.background = Some(current);
+                return Ok(vec![BBox { x: 0.0, y: 0.0, width: cols as f32, height: rows as f32 }]);
+            }
+        };
+
+        let mut diff = Mat::default();
+        core::absdiff(&current, &background, &mut diff)?;
+        let mut mask = Mat::default();
+        imgproc::threshold(&diff, &mut mask, self.config.pixel_threshold, 255.0, imgproc::THRESH_BINARY)?;
+        let mut mask_u8 = Mat::default();
+        mask.convert_to(&mut mask_u8, core::CV_8U, 1.0, 0.0)?;
+        // Joins the pieces of one moving object into one blob
+        let kernel = imgproc::get_structuring_element(imgproc::MORPH_RECT, Size::new(5, 5), Point::new(-1, -1))?;
+        let mut dilated = Mat::default();
+        imgproc::dilate(
+            &mask_u8, &mut dilated, &kernel, Point::new(-1, -1), 2,
+            core::BORDER_CONSTANT, imgproc::morphology_default_border_value()?,
+        )?;
+        let mut contours: Vector<Vector<Point>> = Vector::new();
+        imgproc::find_contours(
+            &dilated, &mut contours, imgproc::RETR_EXTERNAL, imgproc::CHAIN_APPROX_SIMPLE, Point::new(0, 0),
+        )?;
+
+        let mut background = background;
+        imgproc::accumulate_weighted(&current, &mut background, self.config.learning_rate, &core::no_array())?;
+        self.background = Some(background);
+
+        let min_area = self.config.min_area.max(0.0) * (size.width * size.height) as f32;
+        let mut regions = Vec::new();
+        for contour in contours.iter() {
+            let rect: Rect = imgproc::bounding_rect(&contour)?;
+            if (rect.width * rect.height) as f32 >= min_area {
+                regions.push(BBox {
+                    x: rect.x as f32 / scale,
+                    y: rect.y as f32 / scale,
+                    width: rect.width as f32 / scale,
+                    height: rect.height as f32 / scale,
+                });
+            }
+        }
+        Ok(regions)
+    }
+}
+
+enum Gate {
+    Motion(MotionGateConfig),
+    Model { model: Arc<dyn DetectionModel>, classes: Vec<String> },
+}
+
+#[derive(Default)]
+struct SourceState {
+    motion: Option<MotionGate>,
+    frames_since_full: u64,
+    held: Option<(CascadePlan, u32)>,
+}
+
+impl SourceState {
+    // Holds the last active plan through quiet frames and forces the
+    // periodic full frame
+    fn plan(&mut self, regions: &[BBox], frame_size: (f32, f32), config: &CascadeConfig) -> CascadePlan {
+        let mut plan = plan_regions(regions, frame_size, config);
+        match &plan {
+            CascadePlan::Skip => {
+                if let Some((held, remaining)) = self.held.as_mut() {
+                    if *remaining > 0 {
+                        *remaining -= 1;
+                        plan = held.clone();
+                    } else {
+                        self.held = None;
+                    }
+                }
+            }
+            active => self.held = Some((active.clone(), config.hold_frames)),
+        }
+
+        self.frames_since_full += 1;
+        if config.keyframe_interval.is_some_and(|n| self.frames_since_full >= n.max(1)) {
+            plan = CascadePlan::FullFrame;
+        }
+        if matches!(plan, CascadePlan::FullFrame) {
+            self.frames_since_full = 0;
+        }
+        plan
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CascadeStats {
+    pub frames: u64,
+    pub skipped: u64,
+    pub full_frame: u64,
+    pub regions: u64,
+    pub crops: u64,
+    // Share of frame pixels the heavy model never saw
+    pub pixels_saved: f64,
+    // Frames' worth of pixels it did see
+    #[serde(skip)]
+    seen: f64,
+}
+
+pub struct Cascade {
+    config: Arc<CascadeConfig>,
+    gate: Gate,
+    heavy: Arc<dyn DetectionModel>,
+    // Keyed by frame source, so one cascade can serve several cameras. The
+    // map is only held to find a source; its gate runs under its own lock.
+    sources: Mutex<LruCache<String, Arc<std::sync::Mutex<SourceState>>>>,
+    stats: Mutex<CascadeStats>,
+}
+
+impl Cascade {
+    pub async fn new(config: CascadeConfig, heavy: Arc<dyn DetectionModel>) -> Result<Self> {
+        let gate_model = match &config.gate {
+            CascadeGate::Detector { detector, .. } => Some(Arc::new(
+                Detector::new(detector.clone()).await.context("Failed to load cascade gate detector")?,
+            ) as Arc<dyn DetectionModel>),
+            CascadeGate::Motion(_) => None,
+        };
+        Self::with_models(config, gate_model, heavy)
+    }
+
+    // For callers that already hold the models; `gate_model` is required
+    // when the gate is a detector
+    pub fn with_models(
+        config: CascadeConfig,
+        gate_model: Option<Arc<dyn DetectionModel>>,
+        heavy: Arc<dyn DetectionModel>,
+    ) -> Result<Self> {
+        let gate = match (&config.gate, gate_model) {
+            (CascadeGate::Motion(motion), _) => Gate::Motion(motion.clone()),
+            (CascadeGate::Detector { classes, .. }, Some(model)) => Gate::Model { model, classes: classes.clone() },
+            (CascadeGate::Detector { .. }, None) => {
+                return Err(anyhow::anyhow!("A detector-gated cascade needs a gate model"));
+            }
+        };
+        let max_sources = NonZeroUsize::new(config.max_sources).unwrap_or(NonZeroUsize::MIN);
+        Ok(Self {
+            config: Arc::new(config),
+            gate,
+            heavy,
+            sources: Mutex::new(LruCache::new(max_sources)),
+            stats: Mutex::new(CascadeStats::default()),
+        })
+    }
+
+    pub async fn stats(&self) -> CascadeStats {
+        let mut stats = self.stats.lock().await.clone();
+        stats.pixels_saved = if stats.frames == 0 { 0.0 } else { 1.0 - stats.seen / stats.frames as f64 };
+        stats
+    }
+
+    async fn plan(&self, frame: &Frame) -> Result<CascadePlan> {
+        // A gate model runs before taking the lock, so cameras sharing the
+        // cascade don't wait on each other
+        let model_regions = match &self.gate {
+            Gate::Model { model, classes } => Some(model.detect(frame).await?
+                .into_iter()
+                .filter(|d| classes.is_empty() || classes.contains(&d.class_name))
+                .map(|d| d.bbox)
+                .collect::<Vec<_>>()),
+            Gate::Motion(_) => None,
+        };
+
+        let state = self.sources.lock().await
+            .get_or_insert(frame.metadata.source.clone(), Default::default)
+            .clone();
+        let motion = match &self.gate {
+            Gate::Motion(config) => Some(config.clone()),
+            Gate::Model { .. } => None,
+        };
+        let (config, image) = (self.config.clone(), frame.data.clone());
+        let frame_size = (frame.metadata.width as f32, frame.metadata.height as f32);
+
+        // Differencing is OpenCV work, so it goes to the blocking pool
+        tokio::task::spawn_blocking(move || {
+            let mut state = state.lock().unwrap();
+            let regions = match (model_regions, motion) {
+                (Some(regions), _) => regions,
+                (None, Some(motion)) => state.motion
+                    .get_or_insert_with(|| MotionGate::new(motion))
+                    .regions(&image)?,
+                (None, None) => Vec::new(),
+            };
+            Ok(state.plan(&regions, frame_size, &config))
+        })
+        .await
+        .context("Cascade gate panicked")?
+    }
+
+    async fn detect_regions(&self, frame: &Frame, regions: &[BBox]) -> Result<Vec<Detection>> {
+        let mut detections = Vec::new();
+        for region in regions {
+            let Some(region) = geometry::clip(region, frame.data.cols() as f32, frame.data.rows() as f32) else {
+                continue;
+            };
+            let x = region.x as i32;
+            let y = region.y as i32;
+            let width = (region.width as i32).min(frame.data.cols() - x);
+            let height = (region.height as i32).min(frame.data.rows() - y);
+            if width <= 0 || height <= 0 {
+                continue;
+            }
+            let crop = Mat::roi(frame.data.as_ref(), Rect::new(x, y, width, height))?.try_clone()?;
+            let tile = Frame {
+                id: frame.id,
+                timestamp: frame.timestamp,
+                data: Arc::new(crop),
+                metadata: FrameMetadata {
+                    width: width as u32,
+                    height: height as u32,
+                    ..frame.metadata.clone()
+                },
+            };
+            for mut detection in self.heavy.detect(&tile).await? {
+                detection.bbox.x += x as f32;
+                detection.bbox.y += y as f32;
+                detections.push(detection);
+            }
+        }
+        // Regions don't overlap, but a box cut by a region edge can still
+        // be found twice
+        Ok(merge(
+            EnsembleStrategy::UnionNms,
+            &[MemberOutput { weight: 1.0, detections }],
+            self.config.nms_threshold,
+            None,
+        ))
+    }
+}
+
+#[async_trait]
+impl DetectionModel for Cascade {
+    async fn detect(&self, frame: &Frame) -> Result<Vec<Detection>> {
+        let plan = match self.plan(frame).await {
+            Ok(plan) => plan,
+            Err(e) => {
+                // A gate that can't decide must not hide anything
+                log::warn!("Cascade gate failed on frame {}, running full frame: {}", frame.id, e);
+                CascadePlan::FullFrame
+            }
+        };
+
+        let frame_area = (frame.metadata.width as f64 * frame.metadata.height as f64).max(1.0);
+        let (detections, seen) = match &plan {
+            CascadePlan::Skip => (Vec::new(), 0.0),
+            CascadePlan::FullFrame => (self.heavy.detect(frame).await?, 1.0),
+            CascadePlan::Regions(regions) => {
+                let area: f64 = regions.iter().map(|r| geometry::area(r) as f64).sum();
+                (self.detect_regions(frame, regions).await?, (area / frame_area).min(1.0))
+            }
+        };
+
+        let mut stats = self.stats.lock().await;
+        stats.frames += 1;
+        stats.seen += seen;
+        match &plan {
+            CascadePlan::Skip => stats.skipped += 1,
+            CascadePlan::FullFrame => stats.full_frame += 1,
+            CascadePlan::Regions(regions) => {
+                stats.regions += 1;
+                stats.crops += regions.len() as u64;
+            }
+        }
+        Ok(detections)
+    }
+}
@@ -0,0 +1,180 @@
+#![cfg(feature = "vision")]
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use opencv::{
+    prelude::*,
+    core::{self, Mat, Scalar},
+    imgproc,
+};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SceneCondition {
+    Day,
+    Night,
+    Infrared,
+    Fog,
+    Rain,
+}
+
+impl std::fmt::Display for SceneCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SceneCondition::Day => "day",
+            SceneCondition::Night => "night",
+            SceneCondition::Infrared => "infrared",
+            SceneCondition::Fog => "fog",
+            SceneCondition::Rain => "rain",
+        })
+    }
+}
+
+impl std::str::FromStr for SceneCondition {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "day" => Ok(SceneCondition::Day),
+            "night" => Ok(SceneCondition::Night),
+            "infrared" => Ok(SceneCondition::Infrared),
+            "fog" => Ok(SceneCondition::Fog),
+            "rain" => Ok(SceneCondition::Rain),
+            _ => Err(anyhow::anyhow!("Unknown scene condition: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConditionConfig {
+    // Mean luma (0-255) below which the scene is considered night
+    pub night_brightness: f32,
+    // Mean saturation (0-255) below which a lit scene is assumed to be IR
+    pub infrared_saturation: f32,
+    // Luma standard deviation below which a bright scene is considered foggy
+    pub fog_contrast: f32,
+    // Laplacian variance above which a scene is considered rainy
+    pub rain_noise: f32,
+    // Consecutive frames a new condition must persist before switching
+    pub hysteresis_frames: u32,
+}
+
+impl Default for ConditionConfig {
+    fn default() -> Self {
+        Self {
+            night_brightness: 50.0,
+            infrared_saturation: 12.0,
+            fog_contrast: 25.0,
+            rain_noise: 1500.0,
+            hysteresis_frames: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct FrameStatistics {
+    pub brightness: f32,
+    pub saturation: f32,
+    pub contrast: f32,
+    pub noise: f32,
+}
+
+pub struct ConditionDetector {
+    config: ConditionConfig,
+    current: SceneCondition,
+    candidate: Option<(SceneCondition, u32)>,
+}
+
+impl ConditionDetector {
+    pub fn new(config: ConditionConfig) -> Self {
+        Self {
+            config,
+            current: SceneCondition::Day,
+            candidate: None,
+        }
+    }
+
+    pub fn current(&self) -> SceneCondition {
+        self.current
+    }
+
+    pub fn update(&mut self, frame: &Mat) -> Result<SceneCondition> {
+        let stats = compute_statistics(frame)?;
+        let observed = self.classify(&stats);
+
+        if observed == self.current {
+            self.candidate = None;
+            return Ok(self.current);
+        }
+
+        let count = match self.candidate {
+            Some((condition, count)) if condition == observed => count + 1,
+            _ => 1,
+        };
+
+        if count >= self.config.hysteresis_frames {
+            log::info!("Scene condition changed from {} to {}", self.current, observed);
+            self.current = observed;
+            self.candidate = None;
+        } else {
+            self.candidate = Some((observed, count));
+        }
+
+        Ok(self.current)
+    }
+
+    pub fn classify(&self, stats: &FrameStatistics) -> SceneCondition {
+        if stats.saturation < self.config.infrared_saturation
+            && stats.brightness >= self.config.night_brightness
+        {
+            return SceneCondition::Infrared;
+        }
+        if stats.brightness < self.config.night_brightness {
+            return SceneCondition::Night;
+        }
+        if stats.contrast < self.config.fog_contrast {
+            return SceneCondition::Fog;
+        }
+        if stats.noise > self.config.rain_noise {
+            return SceneCondition::Rain;
+        }
+        SceneCondition::Day
+    }
+}
+
+pub fn compute_statistics(frame: &Mat) -> Result<FrameStatistics> {
+    let mut gray = Mat::default();
+    let mut hsv = Mat::default();
+    let mut laplacian = Mat::default();
+
+    // Single-channel sources carry no color, which is indistinguishable
+    // from IR, so their hsv stays empty
+    if frame.channels() == 1 {
+        gray = frame.clone();
+    } else {
+        imgproc::cvt_color(frame, &mut gray, imgproc::COLOR_BGR2GRAY, 0)?;
+        imgproc::cvt_color(frame, &mut hsv, imgproc::COLOR_BGR2HSV, 0)?;
+    }
+
+    let mut mean = Scalar::default();
+    let mut stddev = Scalar::default();
+    core::mean_std_dev(&gray, &mut mean, &mut stddev, &core::no_array())?;
+
+    let saturation = if hsv.empty() {
+        0.0
+    } else {
+        core::mean(&hsv, &core::no_array())?[1] as f32
+    };
+
+    imgproc::laplacian(&gray, &mut laplacian, core::CV_64F, 3, 1.0, 0.0, core::BORDER_DEFAULT)?;
+    let mut lap_mean = Scalar::default();
+    let mut lap_stddev = Scalar::default();
+    core::mean_std_dev(&laplacian, &mut lap_mean, &mut lap_stddev, &core::no_array())?;
+
+    Ok(FrameStatistics {
+        brightness: mean[0] as f32,
+        saturation,
+        contrast: stddev[0] as f32,
+        noise: (lap_stddev[0] * lap_stddev[0]) as f32,
+    })
+}
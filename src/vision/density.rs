@@ -0,0 +1,167 @@
+#![cfg(feature = "vision")]
+
+use std::sync::Mutex;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use opencv::{
+    prelude::*,
+    core::{Mat, Scalar, Size, CV_32F},
+    dnn,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DensityConfig {
+    // CSRNet-style ONNX model mapping an image to a per-pixel density map
+    pub model_path: String,
+    #[serde(default = "default_input_size")]
+    pub input_size: (i32, i32),
+    // Rows and columns of the coarse grid reported alongside the count
+    #[serde(default = "default_grid")]
+    pub grid: (usize, usize),
+    // ImageNet statistics, which is what CSRNet backbones are trained on
+    #[serde(default = "default_mean")]
+    pub mean: [f64; 3],
+    #[serde(default = "default_std")]
+    pub std: [f64; 3],
+}
+
+fn default_input_size() -> (i32, i32) {
+    (640, 480)
+}
+
+fn default_grid() -> (usize, usize) {
+    (8, 8)
+}
+
+fn default_mean() -> [f64; 3] {
+    [0.485, 0.456, 0.406]
+}
+
+fn default_std() -> [f64; 3] {
+    [0.229, 0.224, 0.225]
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DensityInfo {
+    // Sum of the density map; fractional by nature
+    pub count: f32,
+    pub rows: usize,
+    pub cols: usize,
+    // Row-major estimated people per cell
+    pub cells: Vec<f32>,
+}
+
+impl DensityInfo {
+    pub fn cell(&self, row: usize, col: usize) -> Option<f32> {
+        if row >= self.rows || col >= self.cols {
+            return None;
+        }
+        self.cells.get(row * self.cols + col).copied()
+    }
+
+    pub fn peak(&self) -> Option<(usize, usize, f32)> {
+        self.cells.iter()
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(i, value)| (i / self.cols, i % self.cols, *value))
+    }
+}
+
+// Sums a density map into a rows x cols grid. Cells split the map as evenly
+// as integer division allows, so the grid always sums to the full count.
+pub fn pool_density(map: &[f32], width: usize, height: usize, rows: usize, cols: usize) -> Result<DensityInfo> {
+    if width.checked_mul(height) != Some(map.len()) {
+        return Err(anyhow::anyhow!("Density map is {} values, expected {}x{}", map.len(), width, height));
+    }
+    if rows == 0 || cols == 0 {
+        return Err(anyhow::anyhow!("Density grid must have at least one row and column"));
+    }
+    // A grid finer than the map has empty cells at best
+    if rows > height.max(1) || cols > width.max(1) {
+        return Err(anyhow::anyhow!("Density grid {}x{} is finer than the {}x{} map", rows, cols, width, height));
+    }
+
+    let mut cells = vec![0.0f32; rows * cols];
+    for y in 0..height {
+        let row = (y * rows / height.max(1)).min(rows - 1);
+        for x in 0..width {
+            let col = (x * cols / width.max(1)).min(cols - 1);
+            // Models occasionally emit tiny negatives; they aren't people
+            cells[row * cols + col] += map[y * width + x].max(0.0);
+        }
+    }
+
+    Ok(DensityInfo {
+        count: cells.iter().sum(),
+        rows,
+        cols,
+        cells,
+    })
+}
+
+pub struct DensityEstimator {
+    config: DensityConfig,
+    // dnn::Net isn't Sync and forward() needs &mut
+    net: Mutex<dnn::Net>,
+}
+
+impl DensityEstimator {
+    pub fn new(config: DensityConfig) -> Result<Self> {
+        let net = dnn::read_net_from_onnx(&config.model_path)
+            .with_context(|| format!("Failed to load density model {}", config.model_path))?;
+
+        Ok(Self {
+            config,
+            net: Mutex::new(net),
+        })
+    }
+
+    pub fn config(&self) -> &DensityConfig {
+        &self.config
+    }
+
+    pub fn estimate(&self, image: &Mat) -> Result<DensityInfo> {
+        let (width, height) = self.config.input_size;
+        let mean = self.config.mean;
+        let std = self.config.std;
+
+        // blob_from_image only takes a scalar scale, so normalise per channel
+        // by folding the mean in and dividing the std out afterwards
+        let mut blob = dnn::blob_from_image(
+            image,
+            1.0 / 255.0,
+            Size::new(width, height),
+            Scalar::new(mean[0] * 255.0, mean[1] * 255.0, mean[2] * 255.0, 0.0),
+            true,
+            false,
+            CV_32F,
+        )
+        .context("Failed to prepare density input")?;
+        let plane = (width * height) as usize;
+        {
+            let values = blob.data_typed_mut::<f32>()?;
+            for (channel, chunk) in values.chunks_mut(plane).enumerate().take(3) {
+                let divisor = std[channel] as f32;
+                chunk.iter_mut().for_each(|v| *v /= divisor);
+            }
+        }
+
+        let output = {
+            let mut net = self.net.lock().unwrap();
+            net.set_input(&blob, "", 1.0, Scalar::default())?;
+            net.forward_single("").context("Density model inference failed")?
+        };
+
+        // Output is NCHW with a single channel at reduced resolution
+        let sizes = output.mat_size();
+        let dims: Vec<i32> = (0..sizes.dims()).map(|i| sizes[i]).collect();
+        let (map_height, map_width) = match dims.as_slice() {
+            [_, _, h, w] | [_, h, w] => (*h as usize, *w as usize),
+            [h, w] => (*h as usize, *w as usize),
+            _ => return Err(anyhow::anyhow!("Unexpected density output shape {:?}", dims)),
+        };
+
+        let (rows, cols) = self.config.grid;
+        pool_density(output.data_typed::<f32>()?, map_width, map_height, rows, cols)
+    }
+}
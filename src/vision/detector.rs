@@ -1,3 +1,5 @@
+#![cfg(feature = "vision")]
+
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use anyhow::{Result, Context};
@@ -10,8 +12,15 @@ use opencv::{
     types,
 };
 
+use crate::vision::geometry;
 use crate::vision::processor::Frame;
-use crate::models::inference::Model;
+use crate::models::{
+    edge,
+    inference::Model,
+    onnx::OnnxModel,
+    server::{BatchModel, ModelServerConfig, ModelServerRegistry, Sequential},
+};
+use crate::runtime::backend::{BackendConfig, GpuBackend};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DetectorConfig {
@@ -21,9 +30,27 @@ pub struct DetectorConfig {
     pub batch_size: usize,
     pub enabled_detectors: Vec<DetectorType>,
     pub model_configs: Vec<ModelConfig>,
+    // Serve models through the shared per-model queue, so detectors with
+    // the same model and device reuse one loaded copy
+    #[serde(default)]
+    pub model_server: Option<ModelServerConfig>,
+    // ONNX Runtime providers to try for ONNX models
+    #[serde(default)]
+    pub backend: BackendConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl DetectorConfig {
+    // Without an explicit `backend.preferred`, the detector device decides
+    pub fn backends(&self) -> BackendConfig {
+        let mut backends = self.backend.clone();
+        if backends.preferred.is_empty() {
+            backends.preferred = GpuBackend::for_device(&self.device);
+        }
+        backends
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DetectorType {
     Object,
     Face,
@@ -36,6 +63,12 @@ pub enum DetectionDevice {
     CPU,
     CUDA,
     OpenCL,
+    // Apple GPUs and the Neural Engine, through Core ML on Metal
+    CoreML,
+    // DirectX 12 GPUs on Windows
+    DirectML,
+    // AMD GPUs through ROCm or MIGraphX
+    ROCm,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,6 +78,94 @@ pub struct ModelConfig {
     pub framework: ModelFramework,
     pub input_size: (i32, i32),
     pub class_names: Vec<String>,
+    // Runs this model on an edge NPU instead of the detector device, so
+    // one pipeline config can mix accelerators
+    #[serde(default)]
+    pub accelerator: Accelerator,
+    // Which unit when there are several: a Coral path such as "usb:0" or
+    // "pci:1", a Hailo device id, or an RKNN core mask such as "0,1"
+    #[serde(default)]
+    pub accelerator_device: Option<String>,
+    // Device and cache settings for ModelFramework::OpenVINO models
+    #[serde(default)]
+    pub openvino: Option<OpenVinoConfig>,
+}
+
+// Models for an NPU are compiled for it ahead of time: `path` points at an
+// Edge TPU .tflite, a Hailo .hef or an .rknn file respectively
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Accelerator {
+    // Framework and detector device decide
+    #[default]
+    Auto,
+    // Google Coral USB, M.2 and PCIe modules
+    EdgeTpu,
+    // Hailo-8 and Hailo-8L
+    Hailo,
+    // Rockchip RK3588, RK3568 and RK3566 NPUs
+    Rknn,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OpenVinoDevice {
+    // OpenVINO's AUTO plugin picks the best device present
+    #[default]
+    Auto,
+    Cpu,
+    // Intel integrated or Arc GPUs
+    Gpu,
+    // The NPU on Core Ultra chips. Myriad VPUs are no longer supported by
+    // OpenVINO, so "vpu" is taken to mean the NPU.
+    #[serde(alias = "vpu")]
+    Npu,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OpenVinoConfig {
+    pub device: OpenVinoDevice,
+    // Picks one of several GPUs, e.g. 1 for a discrete card next to the iGPU
+    pub device_index: Option<u32>,
+    // Falls back to the CPU when the device is missing or can't run a layer
+    pub fallback_to_cpu: bool,
+    // Compiled models are cached here, which cuts GPU and NPU load times
+    // from tens of seconds to about one after the first start
+    pub cache_dir: Option<String>,
+}
+
+impl Default for OpenVinoConfig {
+    fn default() -> Self {
+        Self {
+            device: OpenVinoDevice::Auto,
+            device_index: None,
+            fallback_to_cpu: true,
+            cache_dir: Some(String::from("cache/openvino")),
+        }
+    }
+}
+
+impl OpenVinoConfig {
+    // The device string OpenVINO compiles for, e.g. "GPU.1" or
+    // "AUTO:NPU,CPU" when falling back
+    pub fn device_name(&self) -> String {
+        let device = match self.device {
+            OpenVinoDevice::Auto => return "AUTO".to_string(),
+            OpenVinoDevice::Cpu => return "CPU".to_string(),
+            OpenVinoDevice::Gpu => "GPU",
+            OpenVinoDevice::Npu => "NPU",
+        };
+        let device = match self.device_index {
+            Some(index) => format!("{}.{}", device, index),
+            None => device.to_string(),
+        };
+        if self.fallback_to_cpu {
+            format!("AUTO:{},CPU", device)
+        } else {
+            device
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,7 +176,7 @@ pub enum ModelFramework {
     Custom(String),
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Detection {
     pub bbox: BBox,
     pub class_id: usize,
@@ -65,7 +186,7 @@ pub struct Detection {
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BBox {
     pub x: f32,
     pub y: f32,
@@ -73,19 +194,155 @@ pub struct BBox {
     pub height: f32,
 }
 
+impl BBox {
+    pub fn iou(&self, other: &BBox) -> f32 {
+        geometry::iou(self, other)
+    }
+}
+
 pub struct Detector {
     config: DetectorConfig,
     models: Vec<Arc<dyn Model>>,
     detection_count: Arc<Mutex<u64>>,
 }
 
+#[derive(Debug, Clone)]
+pub struct DetectorBuilder {
+    config: DetectorConfig,
+}
+
+impl Default for DetectorBuilder {
+    fn default() -> Self {
+        Self {
+            config: DetectorConfig {
+                confidence_threshold: 0.5,
+                nms_threshold: 0.45,
+                device: DetectionDevice::CPU,
+                batch_size: 1,
+                // Filled in by into_config when no detector was chosen
+                enabled_detectors: Vec::new(),
+                model_configs: Vec::new(),
+                model_server: None,
+                backend: BackendConfig::default(),
+            },
+        }
+    }
+}
+
+impl DetectorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn confidence(mut self, threshold: f32) -> Self {
+        self.config.confidence_threshold = threshold;
+        self
+    }
+
+    pub fn nms(mut self, threshold: f32) -> Self {
+        self.config.nms_threshold = threshold;
+        self
+    }
+
+    pub fn device(mut self, device: DetectionDevice) -> Self {
+        self.config.device = device;
+        self
+    }
+
+    pub fn batch_size(mut self, size: usize) -> Self {
+        self.config.batch_size = size;
+        self
+    }
+
+    // Replaces the default object detector; call again to add more
+    pub fn detector(mut self, detector: DetectorType) -> Self {
+        if !self.config.enabled_detectors.contains(&detector) {
+            self.config.enabled_detectors.push(detector);
+        }
+        self
+    }
+
+    // Shorthand for the common single ONNX model case
+    pub fn onnx_model(self, name: &str, path: &str, input_size: (i32, i32), class_names: Vec<String>) -> Self {
+        self.model(ModelConfig {
+            name: name.to_string(),
+            path: path.to_string(),
+            framework: ModelFramework::ONNX,
+            input_size,
+            class_names,
+            accelerator: Accelerator::Auto,
+            accelerator_device: None,
+            openvino: None,
+        })
+    }
+
+    pub fn model_server(mut self, config: ModelServerConfig) -> Self {
+        self.config.model_server = Some(config);
+        self
+    }
+
+    pub fn backend(mut self, backend: BackendConfig) -> Self {
+        self.config.backend = backend;
+        self
+    }
+
+    pub fn model(mut self, model: ModelConfig) -> Self {
+        self.config.model_configs.push(model);
+        self
+    }
+
+    pub fn into_config(mut self) -> Result<DetectorConfig> {
+        if self.config.enabled_detectors.is_empty() {
+            self.config.enabled_detectors.push(DetectorType::Object);
+        }
+        if self.config.model_configs.is_empty() {
+            return Err(anyhow::anyhow!("Detector needs at least one model"));
+        }
+        for (name, value) in [
+            ("confidence", self.config.confidence_threshold),
+            ("nms", self.config.nms_threshold),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(anyhow::anyhow!("{} threshold must be between 0 and 1", name));
+            }
+        }
+        Ok(self.config)
+    }
+
+    pub async fn build(self) -> Result<Detector> {
+        Detector::new(self.into_config()?).await
+    }
+}
+
 impl Detector {
+    pub fn builder() -> DetectorBuilder {
+        DetectorBuilder::new()
+    }
+
     pub async fn new(config: DetectorConfig) -> Result<Self> {
         let mut models = Vec::new();
-        
+        let backends = config.backends();
+
         for model_config in &config.model_configs {
-            let model = Self::load_model(model_config).await?;
-            models.push(Arc::new(model));
+            let model: Arc<dyn Model> = match &config.model_server {
+                Some(server_config) => {
+                    let device = match model_config.accelerator {
+                        Accelerator::Auto => format!("{:?}", config.device),
+                        accelerator => format!("{:?}", accelerator),
+                    };
+                    let key = format!("{}:{}@{}", model_config.name, model_config.path, device);
+                    ModelServerRegistry::global()
+                        .get_or_start(&key, &device, server_config, Self::load_batch_model(model_config, &backends))
+                        .await?
+                }
+                None => Self::load_model(model_config, &backends).await?,
+            };
+            #[cfg(feature = "chaos")]
+            let model: Arc<dyn Model> = match crate::core::chaos::FaultInjector::global() {
+                Some(injector) => Arc::new(crate::core::chaos::ChaosModel::new(model, injector, &model_config.name)),
+                None => model,
+            };
+            models.push(model);
         }
 
         Ok(Self {
@@ -188,43 +445,16 @@ impl Detector {
     }
 
     fn apply_nms(&self, detections: Vec<Detection>) -> Result<Vec<Detection>> {
-        if detections.is_empty() {
-            return Ok(detections);
-        }
-
-        let mut boxes = Mat::default();
-        let mut scores = Mat::default();
-        let mut indices = Mat::default();
-
-        // Prepare boxes and scores for NMS
-        for detection in &detections {
-            boxes.push_row(&Mat::from_slice(&[
-                detection.bbox.x,
-                detection.bbox.y,
-                detection.bbox.width,
-                detection.bbox.height,
-            ])?)?;
-            scores.push_row(&Mat::from_slice(&[detection.confidence])?)?;
-        }
-
-        // Apply NMS
-        dnn::nms_boxes(
-            &boxes,
-            &scores,
-            self.config.confidence_threshold,
-            self.config.nms_threshold,
-            &mut indices,
-            1.0,
-            0,
-        )?;
-
-        // Filter detections based on NMS results
-        let filtered_detections: Vec<Detection> = indices
-            .iter::<i32>()?
-            .map(|i| detections[i as usize].clone())
+        let detections: Vec<Detection> = detections.into_iter()
+            .filter(|d| d.confidence >= self.config.confidence_threshold)
             .collect();
+        let boxes: Vec<BBox> = detections.iter().map(|d| d.bbox.clone()).collect();
+        let scores: Vec<f32> = detections.iter().map(|d| d.confidence).collect();
 
-        Ok(filtered_detections)
+        Ok(geometry::nms(&boxes, &scores, self.config.nms_threshold)
+            .into_iter()
+            .map(|i| detections[i].clone())
+            .collect())
     }
 
     fn get_class_name(&self, class_id: usize) -> Result<String> {
@@ -235,8 +465,41 @@ impl Detector {
             .ok_or_else(|| anyhow::anyhow!("Class name not found for id: {}", class_id))
     }
 
-    async fn load_model(config: &ModelConfig) -> Result<impl Model> {
+    async fn load_model(config: &ModelConfig, backend: &BackendConfig) -> Result<Arc<dyn Model>> {
+        // Opening an NPU and loading a compiled model blocks for seconds
+        if config.accelerator != Accelerator::Auto {
+            let config = config.clone();
+            return tokio::task::spawn_blocking(move || edge::load(&config))
+                .await
+                .context("Edge model loader panicked")?;
+        }
+        if matches!(config.framework, ModelFramework::OpenVINO) {
+            #[cfg(feature = "openvino")]
+            return Ok(Arc::new(crate::models::openvino::OpenVinoModel::load(config)?));
+            #[cfg(not(feature = "openvino"))]
+            return Err(anyhow::anyhow!("Model {} needs the openvino Cargo feature", config.name));
+        }
+        if matches!(config.framework, ModelFramework::ONNX) {
+            return Ok(Arc::new(Self::load_onnx(config, backend).await?));
+        }
         // Model loading implementation based on framework
         todo!("Implement model loading")
     }
+
+    // ONNX Runtime models batch natively; everything else runs a batch
+    // one input at a time
+    async fn load_batch_model(config: &ModelConfig, backend: &BackendConfig) -> Result<Arc<dyn BatchModel>> {
+        if config.accelerator == Accelerator::Auto && matches!(config.framework, ModelFramework::ONNX) {
+            return Ok(Arc::new(Self::load_onnx(config, backend).await?));
+        }
+        Ok(Arc::new(Sequential(Self::load_model(config, backend).await?)))
+    }
+
+    async fn load_onnx(config: &ModelConfig, backend: &BackendConfig) -> Result<OnnxModel> {
+        // Building the session compiles the graph for the provider
+        let (config, backend) = (config.clone(), backend.clone());
+        tokio::task::spawn_blocking(move || OnnxModel::load(&config, &backend))
+            .await
+            .context("ONNX model loader panicked")?
+    }
 }
\ No newline at end of file
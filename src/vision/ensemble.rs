@@ -0,0 +1,221 @@
+#![cfg(feature = "vision")]
+
+use std::sync::Arc;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+
+use crate::vision::{
+    detector::{BBox, Detection, Detector, DetectorConfig},
+    geometry,
+    processor::Frame,
+    shadow::DetectionModel,
+};
+
+// Runs several detectors on the same frame and merges what they find.
+// Boxes of the same class that overlap by at least `iou_threshold` are
+// taken to be the same object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnsembleStrategy {
+    // Everything any member found, with duplicates removed by NMS. Best
+    // recall, most false positives.
+    UnionNms,
+    // Only objects enough members agree on
+    MajorityVote,
+    // Weighted box fusion: overlapping boxes are averaged by weight times
+    // confidence, and objects only some members saw score lower
+    WeightedFusion,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnsembleMember {
+    pub name: String,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    pub detector: DetectorConfig,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnsembleConfig {
+    pub strategy: EnsembleStrategy,
+    #[serde(default = "default_iou")]
+    pub iou_threshold: f32,
+    // Votes needed under MajorityVote; more than half the members if unset
+    #[serde(default)]
+    pub min_votes: Option<usize>,
+    pub members: Vec<EnsembleMember>,
+}
+
+fn default_iou() -> f32 {
+    0.55
+}
+
+// One member's detections for a frame
+pub struct MemberOutput {
+    pub weight: f32,
+    pub detections: Vec<Detection>,
+}
+
+struct Cluster {
+    // (member, weight, detection), best first
+    entries: Vec<(usize, f32, Detection)>,
+}
+
+impl Cluster {
+    fn best(&self) -> &Detection {
+        &self.entries[0].2
+    }
+
+    fn has_member(&self, member: usize) -> bool {
+        self.entries.iter().any(|(m, _, _)| *m == member)
+    }
+}
+
+// Groups detections into objects, highest confidence first. A member votes
+// at most once per object, so two overlapping boxes from one model don't
+// count as agreement.
+fn cluster(outputs: &[MemberOutput], iou_threshold: f32) -> Vec<Cluster> {
+    let mut all: Vec<(usize, f32, Detection)> = outputs.iter().enumerate()
+        .flat_map(|(member, output)| output.detections.iter().map(move |d| (member, output.weight, d.clone())))
+        .collect();
+    all.sort_by(|a, b| b.2.confidence.total_cmp(&a.2.confidence));
+
+    let mut clusters: Vec<Cluster> = Vec::new();
+    for entry in all {
+        let existing = clusters.iter_mut().find(|c| {
+            c.best().class_name == entry.2.class_name
+                && !c.has_member(entry.0)
+                && geometry::iou(&c.best().bbox, &entry.2.bbox) >= iou_threshold
+        });
+        match existing {
+            Some(cluster) => cluster.entries.push(entry),
+            None => clusters.push(Cluster { entries: vec![entry] }),
+        }
+    }
+    clusters
+}
+
+pub fn merge(
+    strategy: EnsembleStrategy,
+    outputs: &[MemberOutput],
+    iou_threshold: f32,
+    min_votes: Option<usize>,
+) -> Vec<Detection> {
+    match strategy {
+        EnsembleStrategy::UnionNms => {
+            let all: Vec<Detection> = outputs.iter().flat_map(|o| o.detections.iter().cloned()).collect();
+            // Per class, so a person and the bag they carry both survive
+            let mut classes: Vec<&str> = all.iter().map(|d| d.class_name.as_str()).collect();
+            classes.sort_unstable();
+            classes.dedup();
+            let mut kept = Vec::new();
+            for class in classes {
+                let same: Vec<&Detection> = all.iter().filter(|d| d.class_name == class).collect();
+                let boxes: Vec<BBox> = same.iter().map(|d| d.bbox.clone()).collect();
+                let scores: Vec<f32> = same.iter().map(|d| d.confidence).collect();
+                kept.extend(geometry::nms(&boxes, &scores, iou_threshold).into_iter().map(|i| same[i].clone()));
+            }
+            kept.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+            kept
+        }
+        EnsembleStrategy::MajorityVote => {
+            let needed = min_votes.unwrap_or(outputs.len() / 2 + 1).max(1);
+            cluster(outputs, iou_threshold).into_iter()
+                .filter(|c| c.entries.len() >= needed)
+                .map(|c| {
+                    let mut detection = c.best().clone();
+                    detection.confidence = c.entries.iter().map(|(_, _, d)| d.confidence).sum::<f32>() / c.entries.len() as f32;
+                    detection
+                })
+                .collect()
+        }
+        EnsembleStrategy::WeightedFusion => {
+            let total_weight: f32 = outputs.iter().map(|o| o.weight).sum();
+            cluster(outputs, iou_threshold).into_iter()
+                .filter_map(|c| {
+                    let score: f32 = c.entries.iter().map(|(_, w, d)| w * d.confidence).sum();
+                    if score <= 0.0 || total_weight <= 0.0 {
+                        return None;
+                    }
+                    let fuse = |f: fn(&BBox) -> f32| {
+                        c.entries.iter().map(|(_, w, d)| w * d.confidence * f(&d.bbox)).sum::<f32>() / score
+                    };
+                    let mut detection = c.best().clone();
+                    detection.bbox = BBox {
+                        x: fuse(|b| b.x),
+                        y: fuse(|b| b.y),
+                        width: fuse(|b| b.width),
+                        height: fuse(|b| b.height),
+                    };
+                    detection.confidence = (score / total_weight).min(1.0);
+                    Some(detection)
+                })
+                .collect()
+        }
+    }
+}
+
+// A composite model: anything that takes a DetectionModel, such as a shadow
+// deployment, can run an ensemble in place of a single detector
+pub struct Ensemble {
+    config: EnsembleConfig,
+    members: Vec<(String, f32, Arc<dyn DetectionModel>)>,
+}
+
+impl Ensemble {
+    pub async fn new(config: EnsembleConfig) -> Result<Self> {
+        let mut members = Vec::with_capacity(config.members.len());
+        for member in &config.members {
+            let detector = Detector::new(member.detector.clone()).await
+                .map_err(|e| anyhow::anyhow!("Failed to load ensemble member {}: {}", member.name, e))?;
+            members.push((member.name.clone(), member.weight, Arc::new(detector) as Arc<dyn DetectionModel>));
+        }
+        Self::with_members(config, members)
+    }
+
+    pub fn with_members(config: EnsembleConfig, members: Vec<(String, f32, Arc<dyn DetectionModel>)>) -> Result<Self> {
+        if members.is_empty() {
+            return Err(anyhow::anyhow!("An ensemble needs at least one member"));
+        }
+        if let Some(votes) = config.min_votes {
+            if votes > members.len() {
+                return Err(anyhow::anyhow!("min_votes is {} but the ensemble has {} members", votes, members.len()));
+            }
+        }
+        Ok(Self { config, members })
+    }
+}
+
+#[async_trait]
+impl DetectionModel for Ensemble {
+    async fn detect(&self, frame: &Frame) -> Result<Vec<Detection>> {
+        let results = futures::future::join_all(
+            self.members.iter().map(|(_, _, model)| model.detect(frame))
+        ).await;
+
+        // A failing member is left out of this frame rather than failing
+        // it; it still counts towards the vote and fusion totals, so its
+        // absence lowers confidence instead of raising it
+        let mut outputs = Vec::with_capacity(results.len());
+        let mut failures = 0;
+        for ((name, weight, _), result) in self.members.iter().zip(results) {
+            match result {
+                Ok(detections) => outputs.push(MemberOutput { weight: *weight, detections }),
+                Err(e) => {
+                    log::warn!("Ensemble member {} failed on frame {}: {}", name, frame.id, e);
+                    failures += 1;
+                    outputs.push(MemberOutput { weight: *weight, detections: Vec::new() });
+                }
+            }
+        }
+        if failures == self.members.len() {
+            return Err(anyhow::anyhow!("Every ensemble member failed on frame {}", frame.id));
+        }
+        Ok(merge(self.config.strategy, &outputs, self.config.iou_threshold, self.config.min_votes))
+    }
+}
@@ -0,0 +1,126 @@
+#![cfg(feature = "vision")]
+
+use crate::vision::detector::BBox;
+
+// Box math shared by the detector, tracker, zones and behaviour detectors.
+// Boxes are top-left corner plus size in pixels; boxes with a non-positive
+// width or height are empty.
+
+pub fn area(bbox: &BBox) -> f32 {
+    bbox.width.max(0.0) * bbox.height.max(0.0)
+}
+
+pub fn center(bbox: &BBox) -> (f32, f32) {
+    (bbox.x + bbox.width / 2.0, bbox.y + bbox.height / 2.0)
+}
+
+// Bottom-centre of the box, where an object touches the ground
+pub fn ground_point(bbox: &BBox) -> (f32, f32) {
+    (bbox.x + bbox.width / 2.0, bbox.y + bbox.height)
+}
+
+pub fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+pub fn intersection(a: &BBox, b: &BBox) -> Option<BBox> {
+    let x1 = a.x.max(b.x);
+    let y1 = a.y.max(b.y);
+    let x2 = (a.x + a.width).min(b.x + b.width);
+    let y2 = (a.y + a.height).min(b.y + b.height);
+    if x2 <= x1 || y2 <= y1 {
+        return None;
+    }
+    Some(BBox { x: x1, y: y1, width: x2 - x1, height: y2 - y1 })
+}
+
+pub fn iou(a: &BBox, b: &BBox) -> f32 {
+    let overlap = intersection(a, b).map_or(0.0, |i| area(&i));
+    let union = area(a) + area(b) - overlap;
+    if union <= 0.0 { 0.0 } else { (overlap / union).clamp(0.0, 1.0) }
+}
+
+// The part of the box inside a width x height frame; None when nothing of
+// it is
+pub fn clip(bbox: &BBox, width: f32, height: f32) -> Option<BBox> {
+    intersection(bbox, &BBox { x: 0.0, y: 0.0, width, height })
+}
+
+// Pixel boxes to 0..1 coordinates relative to the frame, and back
+pub fn normalize(bbox: &BBox, width: f32, height: f32) -> BBox {
+    BBox {
+        x: bbox.x / width,
+        y: bbox.y / height,
+        width: bbox.width / width,
+        height: bbox.height / height,
+    }
+}
+
+pub fn denormalize(bbox: &BBox, width: f32, height: f32) -> BBox {
+    BBox {
+        x: bbox.x * width,
+        y: bbox.y * height,
+        width: bbox.width * width,
+        height: bbox.height * height,
+    }
+}
+
+// Converts centre-based boxes (cx, cy, w, h), as most detection heads emit
+// them, to corner-based ones
+pub fn from_center(cx: f32, cy: f32, width: f32, height: f32) -> BBox {
+    BBox { x: cx - width / 2.0, y: cy - height / 2.0, width, height }
+}
+
+// Aspect-preserving resize into a model input with padding on the short
+// side. Maps boxes between frame and model coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Letterbox {
+    pub scale: f32,
+    pub pad_x: f32,
+    pub pad_y: f32,
+}
+
+impl Letterbox {
+    pub fn fit(frame: (f32, f32), input: (f32, f32)) -> Self {
+        let scale = (input.0 / frame.0).min(input.1 / frame.1);
+        Self {
+            scale,
+            pad_x: (input.0 - frame.0 * scale) / 2.0,
+            pad_y: (input.1 - frame.1 * scale) / 2.0,
+        }
+    }
+
+    pub fn to_input(&self, bbox: &BBox) -> BBox {
+        BBox {
+            x: bbox.x * self.scale + self.pad_x,
+            y: bbox.y * self.scale + self.pad_y,
+            width: bbox.width * self.scale,
+            height: bbox.height * self.scale,
+        }
+    }
+
+    pub fn to_frame(&self, bbox: &BBox) -> BBox {
+        BBox {
+            x: (bbox.x - self.pad_x) / self.scale,
+            y: (bbox.y - self.pad_y) / self.scale,
+            width: bbox.width / self.scale,
+            height: bbox.height / self.scale,
+        }
+    }
+}
+
+// Greedy non-maximum suppression. Returns the indices of the boxes to keep,
+// highest score first; a box is dropped when it overlaps a kept one by more
+// than `iou_threshold`.
+pub fn nms(boxes: &[BBox], scores: &[f32], iou_threshold: f32) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..boxes.len().min(scores.len())).collect();
+    order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]).then(a.cmp(&b)));
+
+    let mut keep: Vec<usize> = Vec::new();
+    for candidate in order {
+        if keep.iter().all(|&kept| iou(&boxes[kept], &boxes[candidate]) <= iou_threshold) {
+            keep.push(candidate);
+        }
+    }
+    keep
+}
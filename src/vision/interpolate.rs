@@ -0,0 +1,278 @@
+#![cfg(feature = "vision")]
+
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use opencv::{
+    prelude::*,
+    core::{self, Mat, Point2f, Rect, Scalar, Size, TermCriteria, Vector, CV_8UC1},
+    imgproc,
+    video,
+};
+
+use crate::vision::detector::{BBox, Detection};
+use crate::vision::geometry;
+use crate::vision::processor::Frame;
+
+// Fills in detections for the frames inference skips, so overlays and zone
+// logic run at the capture rate while the model runs at a fraction of it.
+// Boxes are carried forward from the last inference frame, either along
+// the velocity measured between the last two inference frames or by the
+// optical flow measured inside each box.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterpolationMethod {
+    // Constant velocity; free, but overshoots when objects stop or turn
+    Velocity,
+    // Median sparse flow inside each box, falling back to velocity where
+    // there is too little texture to track
+    OpticalFlow,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InterpolationConfig {
+    pub enabled: bool,
+    // Inference runs on every Nth frame of a source; the rest are
+    // interpolated
+    pub inference_interval: u64,
+    pub method: InterpolationMethod,
+    // Overlap, after prediction, for a box to continue an earlier one
+    pub match_iou: f32,
+    // Frames past the last inference a box is carried for
+    pub max_gap: u64,
+    // Confidence is multiplied by this for every interpolated frame
+    pub confidence_decay: f32,
+    // Flow is measured on a copy scaled down to this width
+    pub flow_width: i32,
+}
+
+impl Default for InterpolationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            inference_interval: 3,
+            method: InterpolationMethod::OpticalFlow,
+            match_iou: 0.3,
+            max_gap: 15,
+            confidence_decay: 0.98,
+            flow_width: 320,
+        }
+    }
+}
+
+// Box moved by `frames` frames at `velocity` (x, y, width, height per frame)
+pub fn extrapolate(bbox: &BBox, velocity: [f32; 4], frames: f32) -> BBox {
+    BBox {
+        x: bbox.x + velocity[0] * frames,
+        y: bbox.y + velocity[1] * frames,
+        width: (bbox.width + velocity[2] * frames).max(1.0),
+        height: (bbox.height + velocity[3] * frames).max(1.0),
+    }
+}
+
+struct TrackedBox {
+    detection: Detection,
+    keyframe_id: u64,
+    velocity: [f32; 4],
+    // Where flow has carried the box since the keyframe, and as of which
+    // frame
+    current: BBox,
+    current_id: u64,
+}
+
+pub struct DetectionInterpolator {
+    config: InterpolationConfig,
+    boxes: Vec<TrackedBox>,
+    // Grey, downscaled copy of the last frame seen, for optical flow
+    previous: Option<(u64, Mat)>,
+    // Frames of this source seen since the last one sent to inference
+    since_keyframe: Option<u64>,
+}
+
+impl DetectionInterpolator {
+    pub fn new(config: InterpolationConfig) -> Self {
+        Self {
+            config,
+            boxes: Vec::new(),
+            previous: None,
+            since_keyframe: None,
+        }
+    }
+
+    // Whether the source's next frame goes to inference. Counted per source
+    // rather than from frame ids, which can skip or come from the camera.
+    pub fn next_is_keyframe(&mut self) -> bool {
+        if !self.config.enabled || self.config.inference_interval <= 1 {
+            return true;
+        }
+        let seen = self.since_keyframe.map_or(self.config.inference_interval, |seen| seen + 1);
+        if seen >= self.config.inference_interval {
+            self.since_keyframe = Some(0);
+            true
+        } else {
+            self.since_keyframe = Some(seen);
+            false
+        }
+    }
+
+    // Records what inference found on a frame. Each detection continues the
+    // earlier box of its class that best overlaps it once that box is moved
+    // to this frame, and takes its velocity from the two.
+    pub fn keyframe(&mut self, frame: &Frame, detections: &[Detection]) -> Result<()> {
+        let mut previous: Vec<Option<TrackedBox>> = std::mem::take(&mut self.boxes).into_iter().map(Some).collect();
+        for detection in detections {
+            let best = previous.iter()
+                .enumerate()
+                .filter_map(|(i, b)| b.as_ref().map(|b| (i, b)))
+                .filter(|(_, b)| b.detection.class_name == detection.class_name && b.keyframe_id != frame.id)
+                .map(|(i, b)| {
+                    let gap = frame.id as f32 - b.keyframe_id as f32;
+                    (i, geometry::iou(&extrapolate(&b.detection.bbox, b.velocity, gap), &detection.bbox))
+                })
+                .filter(|(_, iou)| *iou >= self.config.match_iou)
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+
+            let velocity = match best.and_then(|(i, _)| previous[i].take()) {
+                Some(earlier) => {
+                    let gap = frame.id as f32 - earlier.keyframe_id as f32;
+                    let (a, b) = (&earlier.detection.bbox, &detection.bbox);
+                    [(b.x - a.x) / gap, (b.y - a.y) / gap, (b.width - a.width) / gap, (b.height - a.height) / gap]
+                }
+                None => [0.0; 4],
+            };
+            self.boxes.push(TrackedBox {
+                detection: detection.clone(),
+                keyframe_id: frame.id,
+                velocity,
+                current: detection.bbox.clone(),
+                current_id: frame.id,
+            });
+        }
+
+        if self.config.method == InterpolationMethod::OpticalFlow {
+            self.previous = Some((frame.id, self.gray(&frame.data)?));
+        }
+        Ok(())
+    }
+
+    // Detections for a frame inference skipped
+    pub fn predict(&mut self, frame: &Frame) -> Result<Vec<Detection>> {
+        let (width, height) = (frame.metadata.width as f32, frame.metadata.height as f32);
+
+        let flow = match (self.config.method, self.previous.take()) {
+            (InterpolationMethod::OpticalFlow, Some((previous_id, previous))) if previous_id < frame.id => {
+                let current = self.gray(&frame.data)?;
+                let scale = current.cols() as f32 / width.max(1.0);
+                let flow = Some((previous_id, previous, current.try_clone()?, scale));
+                self.previous = Some((frame.id, current));
+                flow
+            }
+            (_, previous) => {
+                self.previous = previous;
+                None
+            }
+        };
+
+        let mut detections = Vec::new();
+        for tracked in &mut self.boxes {
+            let gap = frame.id as i64 - tracked.keyframe_id as i64;
+            if gap.unsigned_abs() > self.config.max_gap {
+                continue;
+            }
+
+            let moved = match &flow {
+                Some((previous_id, previous, current, scale)) if tracked.current_id == *previous_id => {
+                    median_flow(previous, current, &tracked.current, *scale)?
+                        .map(|(dx, dy)| BBox { x: tracked.current.x + dx, y: tracked.current.y + dy, ..tracked.current.clone() })
+                }
+                _ => None,
+            };
+            let bbox = moved.unwrap_or_else(|| extrapolate(&tracked.detection.bbox, tracked.velocity, gap as f32));
+            if frame.id > tracked.current_id {
+                tracked.current = bbox.clone();
+                tracked.current_id = frame.id;
+            }
+
+            let Some(bbox) = geometry::clip(&bbox, width, height) else {
+                continue;
+            };
+            detections.push(Detection {
+                bbox,
+                confidence: tracked.detection.confidence * self.config.confidence_decay.powi(gap.unsigned_abs() as i32),
+                frame_id: frame.id,
+                timestamp: frame.timestamp,
+                ..tracked.detection.clone()
+            });
+        }
+        Ok(detections)
+    }
+
+    fn gray(&self, image: &Mat) -> Result<Mat> {
+        let mut gray = Mat::default();
+        match image.channels() {
+            1 => gray = image.try_clone()?,
+            4 => imgproc::cvt_color(image, &mut gray, imgproc::COLOR_BGRA2GRAY, 0)?,
+            _ => imgproc::cvt_color(image, &mut gray, imgproc::COLOR_BGR2GRAY, 0)?,
+        }
+        let scale = (self.config.flow_width.max(16) as f32 / gray.cols().max(1) as f32).min(1.0);
+        if scale >= 1.0 {
+            return Ok(gray);
+        }
+        let size = Size::new(
+            ((gray.cols() as f32 * scale) as i32).max(1),
+            ((gray.rows() as f32 * scale) as i32).max(1),
+        );
+        let mut small = Mat::default();
+        imgproc::resize(&gray, &mut small, size, 0.0, 0.0, imgproc::INTER_AREA)?;
+        Ok(small)
+    }
+}
+
+// Median Lucas-Kanade displacement of corners inside `bbox`, in frame
+// pixels; None when too few points could be followed
+fn median_flow(previous: &Mat, current: &Mat, bbox: &BBox, scale: f32) -> Result<Option<(f32, f32)>> {
+    let x = ((bbox.x * scale) as i32).clamp(0, previous.cols());
+    let y = ((bbox.y * scale) as i32).clamp(0, previous.rows());
+    let width = ((bbox.width * scale) as i32).min(previous.cols() - x);
+    let height = ((bbox.height * scale) as i32).min(previous.rows() - y);
+    if width < 4 || height < 4 {
+        return Ok(None);
+    }
+
+    let mut mask = Mat::new_size_with_default(previous.size()?, CV_8UC1, Scalar::all(0.0))?;
+    Mat::roi_mut(&mut mask, Rect::new(x, y, width, height))?.set_to(&Scalar::all(255.0), &core::no_array())?;
+    let mut corners: Vector<Point2f> = Vector::new();
+    imgproc::good_features_to_track(previous, &mut corners, 30, 0.01, 3.0, &mask, 3, false, 0.04)
+        .context("Failed to find features to track")?;
+    if corners.len() < 3 {
+        return Ok(None);
+    }
+
+    let mut tracked: Vector<Point2f> = Vector::new();
+    let mut status: Vector<u8> = Vector::new();
+    let mut errors: Vector<f32> = Vector::new();
+    let criteria = TermCriteria::new(
+        core::TermCriteria_Type::COUNT as i32 + core::TermCriteria_Type::EPS as i32,
+        20,
+        0.03,
+    )?;
+    video::calc_optical_flow_pyr_lk(
+        previous, current, &corners, &mut tracked, &mut status, &mut errors,
+        Size::new(15, 15), 2, criteria, 0, 1e-4,
+    )
+    .context("Optical flow failed")?;
+
+    let (mut dx, mut dy): (Vec<f32>, Vec<f32>) = corners.iter()
+        .zip(tracked.iter())
+        .zip(status.iter())
+        .filter(|(_, ok)| *ok == 1)
+        .map(|((from, to), _)| (to.x - from.x, to.y - from.y))
+        .unzip();
+    if dx.len() < 3 {
+        return Ok(None);
+    }
+    dx.sort_by(f32::total_cmp);
+    dy.sort_by(f32::total_cmp);
+    Ok(Some((dx[dx.len() / 2] / scale, dy[dy.len() / 2] / scale)))
+}
@@ -0,0 +1,555 @@
+#![cfg(feature = "vision")]
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::RwLock;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use chrono::{DateTime, Utc};
+use opencv::{
+    prelude::*,
+    core::{Mat, Rect, Scalar, Size, CV_32F},
+    dnn,
+};
+
+use crate::core::lifecycle::{DataCategory, DataStore, DataSubject};
+use crate::vision::{
+    processor::Frame,
+    detector::{BBox, Detection},
+    geometry,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VehicleAttributeConfig {
+    pub model_path: String,
+    #[serde(default = "default_attribute_input")]
+    pub input_size: (i32, i32),
+    // Label order must match the model's two output heads
+    pub types: Vec<String>,
+    pub colors: Vec<String>,
+}
+
+fn default_attribute_input() -> (i32, i32) {
+    (224, 224)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlateFormat {
+    pub region: String,
+    // Matched against the normalised plate text (uppercase, no separators)
+    pub pattern: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlateConfig {
+    pub detector_model_path: String,
+    pub ocr_model_path: String,
+    #[serde(default = "default_plate_input")]
+    pub detector_input_size: (i32, i32),
+    #[serde(default = "default_ocr_input")]
+    pub ocr_input_size: (i32, i32),
+    // CTC alphabet; index 0 is reserved for the blank
+    #[serde(default = "default_alphabet")]
+    pub alphabet: String,
+    #[serde(default = "default_plate_confidence")]
+    pub min_confidence: f32,
+    // Reads matching none of these are dropped; empty accepts anything
+    #[serde(default)]
+    pub formats: Vec<PlateFormat>,
+}
+
+fn default_plate_input() -> (i32, i32) {
+    (320, 320)
+}
+
+fn default_ocr_input() -> (i32, i32) {
+    (96, 32)
+}
+
+fn default_alphabet() -> String {
+    "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ".to_string()
+}
+
+fn default_plate_confidence() -> f32 {
+    0.5
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LprConfig {
+    // Plates are stored and looked up by an HMAC under this key; every LPR
+    // stage in a pipeline shares one plate index and so one key
+    pub hash_key: String,
+    #[serde(default = "default_vehicle_classes")]
+    pub vehicle_classes: Vec<String>,
+    #[serde(default)]
+    pub attributes: Option<VehicleAttributeConfig>,
+    #[serde(default)]
+    pub plates: Option<PlateConfig>,
+    #[serde(default = "default_history")]
+    pub history: usize,
+}
+
+fn default_vehicle_classes() -> Vec<String> {
+    vec![
+        "car".to_string(),
+        "truck".to_string(),
+        "bus".to_string(),
+        "motorcycle".to_string(),
+    ]
+}
+
+fn default_history() -> usize {
+    10_000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VehicleAttributes {
+    pub vehicle_type: String,
+    pub type_confidence: f32,
+    pub color: String,
+    pub color_confidence: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VehicleObservation {
+    pub bbox: BBox,
+    pub attributes: Option<VehicleAttributes>,
+    pub plate: Option<PlateRead>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlateRead {
+    pub source: String,
+    pub frame_id: u64,
+    pub timestamp: DateTime<Utc>,
+    // None once redacted; the hash still lets callers look a plate up
+    pub text: Option<String>,
+    pub text_hash: String,
+    pub region: Option<String>,
+    pub confidence: f32,
+    pub bbox: BBox,
+    pub vehicle_bbox: BBox,
+}
+
+impl PlateRead {
+    pub fn redact(&mut self) {
+        self.text = None;
+    }
+
+    // Plate detections go back into the detection list so privacy masking
+    // (which masks `license_plate` by default) covers them
+    pub fn to_detection(&self) -> Detection {
+        Detection {
+            bbox: self.bbox.clone(),
+            class_id: 0,
+            class_name: "license_plate".to_string(),
+            confidence: self.confidence,
+            frame_id: self.frame_id,
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+pub fn normalize_plate(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+// Keyed because the plate space is small enough to hash exhaustively: a
+// bare digest that leaked through an export or sink would give the plate
+// back
+pub fn hash_plate(key: &str, text: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(key.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(normalize_plate(text).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// Greedy CTC decoding over a (steps x classes) score matrix: take the best
+// class per step, collapse repeats, drop blanks. Confidence is the mean of
+// the kept steps' probabilities.
+pub fn ctc_greedy_decode(scores: &[f32], classes: usize, alphabet: &str) -> (String, f32) {
+    let symbols: Vec<char> = alphabet.chars().collect();
+    let mut text = String::new();
+    let mut kept = Vec::new();
+    let mut previous = 0usize;
+
+    for step in scores.chunks(classes) {
+        let best = step.iter()
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+
+        if best != 0 && best != previous {
+            if let Some(symbol) = symbols.get(best - 1) {
+                text.push(*symbol);
+                kept.push(softmax_at(step, best));
+            }
+        }
+        previous = best;
+    }
+
+    let confidence = if kept.is_empty() { 0.0 } else { kept.iter().sum::<f32>() / kept.len() as f32 };
+    (text, confidence)
+}
+
+fn softmax_at(values: &[f32], index: usize) -> f32 {
+    let max = values.iter().cloned().fold(f32::MIN, f32::max);
+    let total: f32 = values.iter().map(|v| (v - max).exp()).sum();
+    (values[index] - max).exp() / total
+}
+
+fn argmax_softmax(values: &[f32]) -> (usize, f32) {
+    let best = values.iter()
+        .enumerate()
+        .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    (best, softmax_at(values, best))
+}
+
+pub struct PlateValidator {
+    formats: Vec<(String, Regex)>,
+}
+
+impl PlateValidator {
+    pub fn new(formats: &[PlateFormat]) -> Result<Self> {
+        let formats = formats.iter()
+            .map(|f| {
+                let anchored = format!("^(?:{})$", f.pattern);
+                Regex::new(&anchored)
+                    .map(|re| (f.region.clone(), re))
+                    .with_context(|| format!("Invalid plate pattern for region {}", f.region))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { formats })
+    }
+
+    // None rejects the read; Some(None) accepts it with no known region,
+    // which is what happens when no formats are configured
+    pub fn validate(&self, text: &str) -> Option<Option<String>> {
+        if self.formats.is_empty() {
+            return Some(None);
+        }
+        self.formats.iter()
+            .find(|(_, re)| re.is_match(text))
+            .map(|(region, _)| Some(region.clone()))
+    }
+}
+
+fn crop(image: &Mat, bbox: &BBox) -> Result<Mat> {
+    let region = geometry::clip(bbox, image.cols() as f32, image.rows() as f32)
+        .ok_or_else(|| anyhow::anyhow!("Crop region is outside the frame"))?;
+    let x = region.x as i32;
+    let y = region.y as i32;
+    let width = (region.width as i32).min(image.cols() - x);
+    let height = (region.height as i32).min(image.rows() - y);
+    if width <= 0 || height <= 0 {
+        return Err(anyhow::anyhow!("Crop region is outside the frame"));
+    }
+    Ok(Mat::roi(image, Rect::new(x, y, width, height))?.try_clone()?)
+}
+
+fn run_net(net: &Mutex<dnn::Net>, image: &Mat, size: (i32, i32)) -> Result<Vec<Mat>> {
+    let blob = dnn::blob_from_image(
+        image,
+        1.0 / 255.0,
+        Size::new(size.0, size.1),
+        Scalar::default(),
+        true,
+        false,
+        CV_32F,
+    )?;
+
+    let mut net = net.lock().unwrap();
+    net.set_input(&blob, "", 1.0, Scalar::default())?;
+    let names = net.get_unconnected_out_layers_names()?;
+    let mut outputs = opencv::core::Vector::<Mat>::new();
+    net.forward(&mut outputs, &names)?;
+    Ok(outputs.to_vec())
+}
+
+struct AttributeModel {
+    config: VehicleAttributeConfig,
+    net: Mutex<dnn::Net>,
+}
+
+impl AttributeModel {
+    fn classify(&self, vehicle: &Mat) -> Result<VehicleAttributes> {
+        let outputs = run_net(&self.net, vehicle, self.config.input_size)?;
+        let (types, colors) = match outputs.as_slice() {
+            [types, colors] => (types.data_typed::<f32>()?, colors.data_typed::<f32>()?),
+            _ => return Err(anyhow::anyhow!("Vehicle attribute model must have two outputs")),
+        };
+
+        let (type_index, type_confidence) = argmax_softmax(types);
+        let (color_index, color_confidence) = argmax_softmax(colors);
+        Ok(VehicleAttributes {
+            vehicle_type: self.config.types.get(type_index).cloned().unwrap_or_else(|| "unknown".to_string()),
+            type_confidence,
+            color: self.config.colors.get(color_index).cloned().unwrap_or_else(|| "unknown".to_string()),
+            color_confidence,
+        })
+    }
+}
+
+struct PlateModels {
+    config: PlateConfig,
+    detector: Mutex<dnn::Net>,
+    ocr: Mutex<dnn::Net>,
+    validator: PlateValidator,
+}
+
+impl PlateModels {
+    // Detector output rows are [x, y, w, h, score] normalised to the crop
+    fn find_plate(&self, vehicle: &Mat) -> Result<Option<(BBox, f32)>> {
+        let outputs = run_net(&self.detector, vehicle, self.config.detector_input_size)?;
+        let rows = match outputs.first() {
+            Some(output) => output.data_typed::<f32>()?.to_vec(),
+            None => return Ok(None),
+        };
+
+        let best = rows.chunks(5)
+            .filter(|row| row.len() == 5 && row[4] >= self.config.min_confidence)
+            .max_by(|a, b| a[4].partial_cmp(&b[4]).unwrap_or(std::cmp::Ordering::Equal));
+
+        Ok(best.map(|row| {
+            let (w, h) = (vehicle.cols() as f32, vehicle.rows() as f32);
+            (BBox { x: row[0] * w, y: row[1] * h, width: row[2] * w, height: row[3] * h }, row[4])
+        }))
+    }
+
+    fn read(&self, plate: &Mat) -> Result<(String, f32)> {
+        let outputs = run_net(&self.ocr, plate, self.config.ocr_input_size)?;
+        let output = outputs.first().ok_or_else(|| anyhow::anyhow!("OCR model produced no output"))?;
+        let classes = self.config.alphabet.chars().count() + 1;
+        let (text, confidence) = ctc_greedy_decode(output.data_typed::<f32>()?, classes, &self.config.alphabet);
+        Ok((normalize_plate(&text), confidence))
+    }
+}
+
+// Bounded in-memory history of plate reads, queried by the detections API
+pub struct PlateIndex {
+    reads: RwLock<VecDeque<PlateRead>>,
+    capacity: usize,
+    hash_key: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PlateQuery {
+    pub plate: Option<String>,
+    pub source: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+impl PlateIndex {
+    pub fn new(capacity: usize, hash_key: &str) -> Self {
+        Self {
+            reads: RwLock::new(VecDeque::new()),
+            capacity: capacity.max(1),
+            hash_key: hash_key.to_string(),
+        }
+    }
+
+    pub fn hash(&self, text: &str) -> String {
+        hash_plate(&self.hash_key, text)
+    }
+
+    pub async fn record(&self, read: PlateRead) {
+        let mut reads = self.reads.write().await;
+        if reads.len() >= self.capacity {
+            reads.pop_front();
+        }
+        reads.push_back(read);
+    }
+
+    // Newest first. Plates are matched by hash so redacted reads are
+    // still found by someone who already knows the plate.
+    pub async fn query(&self, query: &PlateQuery) -> Vec<PlateRead> {
+        let hash = query.plate.as_deref().map(|p| self.hash(p));
+        self.reads.read().await
+            .iter()
+            .rev()
+            .filter(|r| hash.as_ref().map_or(true, |h| &r.text_hash == h))
+            .filter(|r| query.source.as_ref().map_or(true, |s| &r.source == s))
+            .filter(|r| query.since.map_or(true, |t| r.timestamp >= t))
+            .filter(|r| query.until.map_or(true, |t| r.timestamp <= t))
+            .take(query.limit.unwrap_or(100))
+            .cloned()
+            .collect()
+    }
+}
+
+// For plate reads the data subject is the vehicle: an identity request
+// names the plate, which is matched by hash like query() does. Sessions
+// and tenants have no plates.
+fn subject_hash(index: &PlateIndex, subject: &DataSubject) -> Option<String> {
+    match subject {
+        DataSubject::Identity(plate) => Some(index.hash(plate)),
+        DataSubject::Session(_) | DataSubject::Tenant(_) => None,
+    }
+}
+
+#[async_trait]
+impl DataStore for PlateIndex {
+    fn category(&self) -> DataCategory {
+        DataCategory::Detections
+    }
+
+    async fn export(&self, subject: &DataSubject) -> Result<serde_json::Value> {
+        let Some(hash) = subject_hash(self, subject) else {
+            return Ok(serde_json::Value::Array(Vec::new()));
+        };
+        let reads: Vec<PlateRead> = self.reads.read().await
+            .iter()
+            .filter(|r| r.text_hash == hash)
+            .cloned()
+            .collect();
+        Ok(serde_json::to_value(reads)?)
+    }
+
+    async fn delete(&self, subject: &DataSubject) -> Result<u64> {
+        let Some(hash) = subject_hash(self, subject) else {
+            return Ok(0);
+        };
+        let mut reads = self.reads.write().await;
+        let before = reads.len();
+        reads.retain(|r| r.text_hash != hash);
+        Ok((before - reads.len()) as u64)
+    }
+}
+
+pub struct LprPipeline {
+    config: LprConfig,
+    attributes: Option<AttributeModel>,
+    plates: Option<PlateModels>,
+    redact_text: bool,
+    index: Arc<PlateIndex>,
+}
+
+impl LprPipeline {
+    pub fn new(config: LprConfig, redact_text: bool, index: Arc<PlateIndex>) -> Result<Self> {
+        let attributes = match &config.attributes {
+            Some(cfg) => Some(AttributeModel {
+                net: Mutex::new(dnn::read_net_from_onnx(&cfg.model_path)
+                    .with_context(|| format!("Failed to load vehicle attribute model {}", cfg.model_path))?),
+                config: cfg.clone(),
+            }),
+            None => None,
+        };
+
+        let plates = match &config.plates {
+            Some(cfg) => Some(PlateModels {
+                detector: Mutex::new(dnn::read_net_from_onnx(&cfg.detector_model_path)
+                    .with_context(|| format!("Failed to load plate detector {}", cfg.detector_model_path))?),
+                ocr: Mutex::new(dnn::read_net_from_onnx(&cfg.ocr_model_path)
+                    .with_context(|| format!("Failed to load plate OCR model {}", cfg.ocr_model_path))?),
+                validator: PlateValidator::new(&cfg.formats)?,
+                config: cfg.clone(),
+            }),
+            None => None,
+        };
+
+        Ok(Self { config, attributes, plates, redact_text, index })
+    }
+
+    pub fn index(&self) -> Arc<PlateIndex> {
+        self.index.clone()
+    }
+
+    // Callers append each plate's `to_detection()` to the frame's
+    // detections ahead of privacy masking
+    pub async fn process(&self, frame: &Frame, detections: &[Detection]) -> Result<Vec<VehicleObservation>> {
+        let mut vehicles = Vec::new();
+
+        for vehicle in detections.iter().filter(|d| self.config.vehicle_classes.contains(&d.class_name)) {
+            let vehicle_image = match crop(&frame.data, &vehicle.bbox) {
+                Ok(image) => image,
+                Err(_) => continue,
+            };
+
+            let attributes = match &self.attributes {
+                Some(model) => match model.classify(&vehicle_image) {
+                    Ok(attributes) => Some(attributes),
+                    Err(e) => {
+                        log::warn!("Vehicle attribute classification failed: {}", e);
+                        None
+                    }
+                },
+                None => None,
+            };
+
+            let plate = match &self.plates {
+                Some(plates) => self.read_plate(plates, frame, vehicle, &vehicle_image).await?,
+                None => None,
+            };
+            vehicles.push(VehicleObservation {
+                bbox: vehicle.bbox.clone(),
+                attributes,
+                plate,
+            });
+        }
+
+        Ok(vehicles)
+    }
+
+    async fn read_plate(
+        &self,
+        plates: &PlateModels,
+        frame: &Frame,
+        vehicle: &Detection,
+        vehicle_image: &Mat,
+    ) -> Result<Option<PlateRead>> {
+        let Some((plate_box, detect_confidence)) = plates.find_plate(vehicle_image)? else {
+            return Ok(None);
+        };
+        let plate_image = match crop(vehicle_image, &plate_box) {
+            Ok(image) => image,
+            Err(_) => return Ok(None),
+        };
+
+        let (text, ocr_confidence) = plates.read(&plate_image)?;
+        if text.is_empty() {
+            return Ok(None);
+        }
+        let Some(region) = plates.validator.validate(&text) else {
+            log::debug!("Discarding plate read that matches no configured format");
+            return Ok(None);
+        };
+
+        let mut read = PlateRead {
+            source: frame.metadata.source.clone(),
+            frame_id: frame.id,
+            timestamp: frame.timestamp,
+            text_hash: self.index.hash(&text),
+            text: Some(text),
+            region,
+            confidence: detect_confidence.min(ocr_confidence),
+            // Back into frame coordinates
+            bbox: BBox {
+                x: vehicle.bbox.x.max(0.0) + plate_box.x,
+                y: vehicle.bbox.y.max(0.0) + plate_box.y,
+                width: plate_box.width,
+                height: plate_box.height,
+            },
+            vehicle_bbox: vehicle.bbox.clone(),
+        };
+        if self.redact_text {
+            read.redact();
+        }
+
+        self.index.record(read.clone()).await;
+        Ok(Some(read))
+    }
+}
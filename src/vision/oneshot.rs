@@ -0,0 +1,272 @@
+#![cfg(feature = "vision")]
+
+use std::sync::Arc;
+use std::time::Instant;
+use anyhow::{Result, Context};
+use futures::{stream, StreamExt};
+use opencv::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::vision::{
+    analyzer::{Analysis, Analyzer, AnalyzerConfig},
+    detector::{Detection, Detector},
+    processor::{Frame, Processor},
+    sources::{decode_frame, frame_dimensions, FrameEncoding},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OneShotConfig {
+    pub max_image_bytes: usize,
+    // Largest accepted width or height, in pixels
+    pub max_dimension: u32,
+    pub max_batch_images: usize,
+    // Images per detector call within a batch request
+    pub inference_batch_size: usize,
+    // Detector calls in flight at once for a batch request
+    pub batch_concurrency: usize,
+}
+
+impl Default for OneShotConfig {
+    fn default() -> Self {
+        Self {
+            max_image_bytes: 20 * 1024 * 1024,
+            max_dimension: 8192,
+            max_batch_images: 64,
+            inference_batch_size: 8,
+            batch_concurrency: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AnalysisTiming {
+    pub decode_ms: f64,
+    pub detect_ms: f64,
+    pub analyze_ms: f64,
+    pub total_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageAnalysis {
+    pub width: u32,
+    pub height: u32,
+    pub detections: Vec<Detection>,
+    pub analysis: Option<Analysis>,
+    pub timing: AnalysisTiming,
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchImage {
+    pub name: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+    // Set when the image couldn't be obtained at all, e.g. a failed fetch
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchItem {
+    pub index: usize,
+    pub name: String,
+    pub result: Option<ImageAnalysis>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchAnalysis {
+    pub results: Vec<BatchItem>,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub timing: AnalysisTiming,
+    pub images_per_second: f64,
+}
+
+// Request/response analysis of standalone images. Each image gets a fresh
+// Analyzer so motion and behavior history never leak between requests.
+pub struct ImageAnalysisService {
+    config: OneShotConfig,
+    processor: Arc<Processor>,
+    detector: Arc<Detector>,
+    analyzer_config: AnalyzerConfig,
+}
+
+impl ImageAnalysisService {
+    pub fn new(
+        config: OneShotConfig,
+        processor: Arc<Processor>,
+        detector: Arc<Detector>,
+        analyzer_config: AnalyzerConfig,
+    ) -> Self {
+        Self {
+            config,
+            processor,
+            detector,
+            analyzer_config,
+        }
+    }
+
+    pub fn config(&self) -> &OneShotConfig {
+        &self.config
+    }
+
+    async fn decode(&self, content_type: &str, data: &[u8]) -> Result<(Frame, u32, u32)> {
+        if data.len() > self.config.max_image_bytes {
+            return Err(anyhow::anyhow!(
+                "Image is {} bytes, limit is {}", data.len(), self.config.max_image_bytes
+            ));
+        }
+
+        // A small file can still decode to a huge bitmap, so the size
+        // comes from the header before any pixels are allocated
+        let encoding = FrameEncoding::from_content_type(content_type, None, None)?;
+        let (width, height) = frame_dimensions(encoding, data)?;
+        if width > self.config.max_dimension || height > self.config.max_dimension {
+            return Err(anyhow::anyhow!(
+                "Image is {}x{}, limit is {} pixels per side", width, height, self.config.max_dimension
+            ));
+        }
+        let image = decode_frame(encoding, data)?;
+        let frame = self.processor.process_frame(image).await
+            .context("Failed to preprocess image")?;
+        Ok((frame, width, height))
+    }
+
+    async fn run_analysis(&self, frame: &Frame, detections: &[Detection]) -> Result<Analysis> {
+        let mut analyzer = Analyzer::new(self.analyzer_config.clone())?;
+        analyzer.analyze(frame, detections).await
+    }
+
+    pub async fn analyze(&self, content_type: &str, data: &[u8], with_analysis: bool) -> Result<ImageAnalysis> {
+        let started = Instant::now();
+        let (frame, width, height) = self.decode(content_type, data).await?;
+        let decode_ms = elapsed_ms(started);
+
+        let detect_started = Instant::now();
+        let detections = self.detector.detect(&frame).await?;
+        let detect_ms = elapsed_ms(detect_started);
+
+        let analyze_started = Instant::now();
+        let analysis = if with_analysis {
+            Some(self.run_analysis(&frame, &detections).await?)
+        } else {
+            None
+        };
+        let analyze_ms = elapsed_ms(analyze_started);
+
+        Ok(ImageAnalysis {
+            width,
+            height,
+            detections,
+            analysis,
+            timing: AnalysisTiming {
+                decode_ms,
+                detect_ms,
+                analyze_ms,
+                total_ms: elapsed_ms(started),
+            },
+        })
+    }
+
+    // Images are decoded concurrently, then detected in chunks of
+    // `inference_batch_size` with up to `batch_concurrency` chunks in flight.
+    // A bad image fails on its own; the rest of the batch still completes.
+    pub async fn analyze_batch(&self, images: Vec<BatchImage>, with_analysis: bool) -> Result<BatchAnalysis> {
+        if images.is_empty() {
+            return Err(anyhow::anyhow!("Batch contains no images"));
+        }
+        if images.len() > self.config.max_batch_images {
+            return Err(anyhow::anyhow!(
+                "Batch has {} images, limit is {}", images.len(), self.config.max_batch_images
+            ));
+        }
+
+        let started = Instant::now();
+        let mut results: Vec<BatchItem> = images.iter()
+            .enumerate()
+            .map(|(index, image)| BatchItem { index, name: image.name.clone(), result: None, error: None })
+            .collect();
+
+        let decoded = futures::future::join_all(images.iter().map(|image| async move {
+            match &image.error {
+                Some(error) => Err(anyhow::anyhow!("{}", error)),
+                None => self.decode(&image.content_type, &image.data).await,
+            }
+        })).await;
+        let decode_ms = elapsed_ms(started);
+
+        let mut ready = Vec::new();
+        for (index, outcome) in decoded.into_iter().enumerate() {
+            match outcome {
+                Ok(decoded) => ready.push((index, decoded)),
+                Err(e) => results[index].error = Some(e.to_string()),
+            }
+        }
+
+        let detect_started = Instant::now();
+        let chunks: Vec<Vec<(usize, (Frame, u32, u32))>> = ready
+            .chunks(self.config.inference_batch_size.max(1))
+            .map(|chunk| chunk.to_vec())
+            .collect();
+        let detected: Vec<(Vec<(usize, (Frame, u32, u32))>, Result<Vec<Vec<Detection>>>)> = stream::iter(chunks)
+            .map(|chunk| async move {
+                let frames: Vec<Frame> = chunk.iter().map(|(_, (frame, _, _))| frame.clone()).collect();
+                let detections = self.detector.detect_batch(&frames).await;
+                (chunk, detections)
+            })
+            .buffer_unordered(self.config.batch_concurrency.max(1))
+            .collect()
+            .await;
+        let detect_ms = elapsed_ms(detect_started);
+
+        let analyze_started = Instant::now();
+        for (chunk, outcome) in detected {
+            let detections = match outcome {
+                Ok(detections) => detections,
+                Err(e) => {
+                    for (index, _) in &chunk {
+                        results[*index].error = Some(format!("Detection failed: {}", e));
+                    }
+                    continue;
+                }
+            };
+
+            for ((index, (frame, width, height)), detections) in chunk.into_iter().zip(detections) {
+                let analysis = if with_analysis {
+                    match self.run_analysis(&frame, &detections).await {
+                        Ok(analysis) => Some(analysis),
+                        Err(e) => {
+                            results[index].error = Some(format!("Analysis failed: {}", e));
+                            continue;
+                        }
+                    }
+                } else {
+                    None
+                };
+
+                results[index].result = Some(ImageAnalysis {
+                    width,
+                    height,
+                    detections,
+                    analysis,
+                    // Per-image timing isn't meaningful once work is batched
+                    timing: AnalysisTiming::default(),
+                });
+            }
+        }
+        let analyze_ms = elapsed_ms(analyze_started);
+
+        let total_ms = elapsed_ms(started);
+        let succeeded = results.iter().filter(|r| r.result.is_some()).count();
+        Ok(BatchAnalysis {
+            failed: results.len() - succeeded,
+            succeeded,
+            results,
+            timing: AnalysisTiming { decode_ms, detect_ms, analyze_ms, total_ms },
+            images_per_second: if total_ms > 0.0 { succeeded as f64 * 1000.0 / total_ms } else { 0.0 },
+        })
+    }
+}
+
+fn elapsed_ms(since: Instant) -> f64 {
+    since.elapsed().as_secs_f64() * 1000.0
+}
@@ -0,0 +1,212 @@
+#![cfg(feature = "vision")]
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use opencv::{
+    prelude::*,
+    core::{self, Mat, Rect, Size, Scalar, Vector},
+    imgproc,
+    objdetect,
+};
+
+use crate::vision::{
+    processor::Frame,
+    detector::{Detection, BBox},
+    zones::Zone,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrivacyConfig {
+    pub enabled: bool,
+    pub method: MaskMethod,
+    // Detection classes whose boxes are always masked
+    pub mask_classes: Vec<String>,
+    pub static_regions: Vec<Zone>,
+    // Extra margin around masked boxes, as a fraction of box size
+    pub padding: f32,
+    // Optional Haar cascade used when no upstream detector emits faces
+    pub face_cascade_path: Option<String>,
+    // Drop recognised plate text, keeping only its hash for lookups
+    pub redact_plate_text: bool,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            method: MaskMethod::Pixelate { block_size: 16 },
+            mask_classes: vec!["face".to_string(), "license_plate".to_string()],
+            static_regions: Vec::new(),
+            padding: 0.15,
+            face_cascade_path: None,
+            redact_plate_text: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MaskMethod {
+    Blur { kernel_size: i32 },
+    Pixelate { block_size: i32 },
+    Fill,
+}
+
+pub struct PrivacyMasker {
+    config: PrivacyConfig,
+    face_cascade: Option<Mutex<objdetect::CascadeClassifier>>,
+}
+
+impl PrivacyMasker {
+    pub fn new(config: PrivacyConfig) -> Result<Self> {
+        let face_cascade = match &config.face_cascade_path {
+            Some(path) => {
+                let classifier = objdetect::CascadeClassifier::new(path)
+                    .with_context(|| format!("Failed to load face cascade from {}", path))?;
+                Some(Mutex::new(classifier))
+            }
+            None => None,
+        };
+
+        Ok(Self { config, face_cascade })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    // Returns a copy of the frame with all configured regions masked; the
+    // input frame is left untouched since other stages may still need it
+    pub async fn mask(&self, frame: &Frame, detections: &[Detection]) -> Result<Frame> {
+        if !self.config.enabled || frame.metadata.privacy_masked {
+            return Ok(frame.clone());
+        }
+
+        let mut image = frame.data.as_ref().clone();
+        let bounds = Rect::new(0, 0, image.cols(), image.rows());
+        let mut regions = Vec::new();
+
+        for detection in detections {
+            if self.config.mask_classes.contains(&detection.class_name) {
+                regions.push(self.padded_rect(&detection.bbox));
+            }
+        }
+
+        let has_faces = detections.iter().any(|d| d.class_name == "face");
+        if !has_faces {
+            regions.extend(self.detect_faces(&image).await?);
+        }
+
+        for zone in &self.config.static_regions {
+            regions.push(polygon_bounds(zone));
+        }
+
+        for region in regions {
+            let region = region & bounds;
+            if region.width <= 0 || region.height <= 0 {
+                continue;
+            }
+            self.mask_region(&mut image, region)?;
+        }
+
+        let mut masked = frame.clone();
+        masked.data = Arc::new(image);
+        masked.metadata.privacy_masked = true;
+        Ok(masked)
+    }
+
+    // Egress points call this before letting a frame out of the pipeline
+    pub fn ensure_masked(&self, frame: &Frame) -> Result<()> {
+        if self.config.enabled && !frame.metadata.privacy_masked {
+            return Err(anyhow::anyhow!(
+                "Frame {} has not been privacy masked and cannot leave the pipeline",
+                frame.id
+            ));
+        }
+        Ok(())
+    }
+
+    fn padded_rect(&self, bbox: &BBox) -> Rect {
+        let pad_x = bbox.width * self.config.padding;
+        let pad_y = bbox.height * self.config.padding;
+        Rect::new(
+            (bbox.x - pad_x).floor() as i32,
+            (bbox.y - pad_y).floor() as i32,
+            (bbox.width + 2.0 * pad_x).ceil() as i32,
+            (bbox.height + 2.0 * pad_y).ceil() as i32,
+        )
+    }
+
+    async fn detect_faces(&self, image: &Mat) -> Result<Vec<Rect>> {
+        let Some(cascade) = &self.face_cascade else {
+            return Ok(Vec::new());
+        };
+
+        let mut gray = Mat::default();
+        if image.channels() == 1 {
+            gray = image.clone();
+        } else {
+            imgproc::cvt_color(image, &mut gray, imgproc::COLOR_BGR2GRAY, 0)?;
+        }
+
+        let mut faces = Vector::<Rect>::new();
+        cascade.lock().await.detect_multi_scale(
+            &gray,
+            &mut faces,
+            1.1,
+            3,
+            0,
+            Size::new(24, 24),
+            Size::default(),
+        )?;
+
+        Ok(faces.iter().collect())
+    }
+
+    fn mask_region(&self, image: &mut Mat, region: Rect) -> Result<()> {
+        let mut roi = Mat::roi_mut(image, region)?;
+
+        match &self.config.method {
+            MaskMethod::Blur { kernel_size } => {
+                // Kernel must be odd
+                let k = (*kernel_size).max(3) | 1;
+                let source = roi.try_clone()?;
+                imgproc::gaussian_blur(
+                    &source, &mut roi, Size::new(k, k), 0.0, 0.0, core::BORDER_DEFAULT,
+                )?;
+            }
+            MaskMethod::Pixelate { block_size } => {
+                let block = (*block_size).max(2);
+                let small_size = Size::new(
+                    (region.width / block).max(1),
+                    (region.height / block).max(1),
+                );
+                let mut small = Mat::default();
+                imgproc::resize(&roi, &mut small, small_size, 0.0, 0.0, imgproc::INTER_LINEAR)?;
+                imgproc::resize(&small, &mut roi, region.size(), 0.0, 0.0, imgproc::INTER_NEAREST)?;
+            }
+            MaskMethod::Fill => {
+                roi.set_to(&Scalar::all(0.0), &core::no_array())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn polygon_bounds(zone: &Zone) -> Rect {
+    let min_x = zone.polygon.iter().map(|p| p.x).fold(f32::MAX, f32::min);
+    let min_y = zone.polygon.iter().map(|p| p.y).fold(f32::MAX, f32::min);
+    let max_x = zone.polygon.iter().map(|p| p.x).fold(f32::MIN, f32::max);
+    let max_y = zone.polygon.iter().map(|p| p.y).fold(f32::MIN, f32::max);
+
+    Rect::new(
+        min_x.floor() as i32,
+        min_y.floor() as i32,
+        (max_x - min_x).ceil() as i32,
+        (max_y - min_y).ceil() as i32,
+    )
+}
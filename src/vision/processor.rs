@@ -1,3 +1,5 @@
+#![cfg(feature = "vision")]
+
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use anyhow::{Result, Context};
@@ -6,10 +8,13 @@ use opencv::{
     prelude::*,
     core::*,
     imgproc,
-    videoio,
 };
 use serde::{Serialize, Deserialize};
 
+use crate::core::stream_stats::StreamStats;
+use crate::vision::sources::{FrameSource, VideoSource};
+use crate::vision::transcode::TranscodeConfig;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessorConfig {
     pub input_size: (u32, u32),
@@ -18,6 +23,8 @@ pub struct ProcessorConfig {
     pub preprocessing: Vec<PreprocessingStep>,
     pub batch_size: usize,
     pub device: ProcessingDevice,
+    #[serde(default)]
+    pub transcode: TranscodeConfig,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -59,13 +66,22 @@ pub struct FrameMetadata {
     pub channels: u8,
     pub format: String,
     pub source: String,
+    #[serde(default)]
+    pub privacy_masked: bool,
+    // The configured stream the frame came from. `source` describes the
+    // device and may carry credentials, so anything keyed per stream uses
+    // this instead.
+    #[serde(default)]
+    pub stream_id: Option<String>,
 }
 
 pub struct Processor {
     config: ProcessorConfig,
     frame_counter: Arc<Mutex<u64>>,
-    capture: Option<videoio::VideoCapture>,
+    source: Option<Box<dyn FrameSource>>,
     preprocessing_pipeline: Vec<Box<dyn PreprocessingOperation>>,
+    stats: Option<Arc<StreamStats>>,
+    stream_id: Option<String>,
 }
 
 #[async_trait::async_trait]
@@ -80,8 +96,10 @@ impl Processor {
         Ok(Self {
             config,
             frame_counter: Arc::new(Mutex::new(0)),
-            capture: None,
+            source: None,
             preprocessing_pipeline,
+            stats: None,
+            stream_id: None,
         })
     }
 
@@ -118,6 +136,8 @@ impl Processor {
             channels: frame.channels() as u8,
             format: self.config.color_space.to_string(),
             source: "processor".to_string(),
+            privacy_masked: false,
+            stream_id: None,
         };
 
         // Increment frame counter
@@ -172,24 +192,46 @@ impl Processor {
     }
 
     pub async fn start_capture(&mut self, source: &str) -> Result<()> {
-        let mut cap = videoio::VideoCapture::from_file(source, videoio::CAP_ANY)?;
-        if !cap.is_opened()? {
-            return Err(anyhow::anyhow!("Failed to open video capture"));
-        }
-        self.capture = Some(cap);
+        let video = VideoSource::open(source, false, &self.config.transcode).await?;
+        self.attach_source(Box::new(video));
         Ok(())
     }
 
+    pub fn attach_source(&mut self, source: Box<dyn FrameSource>) {
+        self.source = Some(source);
+    }
+
+    // Stamped on every frame read from the attached source
+    pub fn set_stream_id(&mut self, stream_id: &str) {
+        self.stream_id = Some(stream_id.to_string());
+    }
+
+    pub fn attach_stream_stats(&mut self, stats: Arc<StreamStats>) {
+        self.stats = Some(stats);
+    }
+
     pub async fn read_frame(&mut self) -> Result<Option<Frame>> {
-        if let Some(cap) = &mut self.capture {
-            let mut frame = Mat::default();
-            if cap.read(&mut frame)? {
-                Ok(Some(self.process_frame(frame).await?))
-            } else {
-                Ok(None)
+        let source = self.source.as_mut()
+            .ok_or_else(|| anyhow::anyhow!("No capture device initialized"))?;
+        let source_name = source.name();
+
+        match source.next_tagged().await? {
+            Some((frame, external_id)) => {
+                // Timed from when the source hands the frame over, so the
+                // wait for the camera's next frame isn't counted
+                let started = std::time::Instant::now();
+                let mut frame = self.process_frame(frame).await?;
+                if let (Some(stats), Some(stream_id)) = (&self.stats, &self.stream_id) {
+                    stats.record_decode(stream_id, started.elapsed().as_secs_f64() * 1000.0).await;
+                }
+                frame.metadata.source = source_name;
+                frame.metadata.stream_id = self.stream_id.clone();
+                if let Some(id) = external_id {
+                    frame.id = id;
+                }
+                Ok(Some(frame))
             }
-        } else {
-            Err(anyhow::anyhow!("No capture device initialized"))
+            None => Ok(None),
         }
     }
 }
@@ -219,13 +261,13 @@ impl PreprocessingOperation for ResizeOperation {
 
 // Similar implementations for other preprocessing operations...
 
-impl ToString for ColorSpace {
-    fn to_string(&self) -> String {
-        match self {
-            ColorSpace::RGB => "RGB".to_string(),
-            ColorSpace::BGR => "BGR".to_string(),
-            ColorSpace::GRAY => "GRAY".to_string(),
-            ColorSpace::HSV => "HSV".to_string(),
-        }
+impl std::fmt::Display for ColorSpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ColorSpace::RGB => "RGB",
+            ColorSpace::BGR => "BGR",
+            ColorSpace::GRAY => "GRAY",
+            ColorSpace::HSV => "HSV",
+        })
     }
 }
\ No newline at end of file
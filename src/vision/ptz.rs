@@ -0,0 +1,385 @@
+#![cfg(feature = "vision")]
+
+use std::sync::Arc;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use sha1::{Digest, Sha1};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+
+use crate::utils::egress::{EgressClient, EgressConfig};
+use crate::vision::detector::{Detection, BBox};
+use crate::vision::geometry::{center, distance};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Auto-tracking only re-sends a move once either axis has changed by more
+// than this, so a steady target doesn't turn into a request per frame
+const VELOCITY_STEP: f32 = 0.05;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PtzConfig {
+    pub onvif_url: String,
+    pub username: String,
+    pub password: String,
+    pub profile_token: String,
+    pub auto_tracking: AutoTrackConfig,
+}
+
+impl PtzConfig {
+    // For responses; the password is only ever sent to the camera
+    pub fn redacted(&self) -> Self {
+        Self { password: "***".to_string(), ..self.clone() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoTrackConfig {
+    // Fraction of the frame around the center where no correction is issued
+    pub deadzone: f32,
+    pub gain: f32,
+    pub max_speed: f32,
+    pub min_confidence: f32,
+    // Stop the camera after this many frames without the target
+    pub lost_after_frames: u32,
+}
+
+impl Default for AutoTrackConfig {
+    fn default() -> Self {
+        Self {
+            deadzone: 0.1,
+            gain: 1.2,
+            max_speed: 0.8,
+            min_confidence: 0.5,
+            lost_after_frames: 15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct PtzVector {
+    pub pan: f32,
+    pub tilt: f32,
+    pub zoom: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PtzCommand {
+    ContinuousMove { velocity: PtzVector },
+    RelativeMove { translation: PtzVector },
+    AbsoluteMove { position: PtzVector },
+    GotoPreset { preset_token: String },
+    Stop,
+}
+
+pub struct OnvifPtzClient {
+    config: PtzConfig,
+    client: EgressClient,
+}
+
+impl OnvifPtzClient {
+    pub fn new(config: PtzConfig, client: EgressClient) -> Self {
+        Self { config, client }
+    }
+
+    pub async fn execute(&self, command: &PtzCommand) -> Result<()> {
+        let token = xml_escape(&self.config.profile_token);
+        let body = match command {
+            PtzCommand::ContinuousMove { velocity } => format!(
+                r#"<tptz:ContinuousMove><tptz:ProfileToken>{}</tptz:ProfileToken><tptz:Velocity>{}{}</tptz:Velocity></tptz:ContinuousMove>"#,
+                token, pan_tilt_xml(velocity), zoom_xml(velocity)
+            ),
+            PtzCommand::RelativeMove { translation } => format!(
+                r#"<tptz:RelativeMove><tptz:ProfileToken>{}</tptz:ProfileToken><tptz:Translation>{}{}</tptz:Translation></tptz:RelativeMove>"#,
+                token, pan_tilt_xml(translation), zoom_xml(translation)
+            ),
+            PtzCommand::AbsoluteMove { position } => format!(
+                r#"<tptz:AbsoluteMove><tptz:ProfileToken>{}</tptz:ProfileToken><tptz:Position>{}{}</tptz:Position></tptz:AbsoluteMove>"#,
+                token, pan_tilt_xml(position), zoom_xml(position)
+            ),
+            PtzCommand::GotoPreset { preset_token } => format!(
+                r#"<tptz:GotoPreset><tptz:ProfileToken>{}</tptz:ProfileToken><tptz:PresetToken>{}</tptz:PresetToken></tptz:GotoPreset>"#,
+                token, xml_escape(preset_token)
+            ),
+            PtzCommand::Stop => format!(
+                r#"<tptz:Stop><tptz:ProfileToken>{}</tptz:ProfileToken><tptz:PanTilt>true</tptz:PanTilt><tptz:Zoom>true</tptz:Zoom></tptz:Stop>"#,
+                token
+            ),
+        };
+
+        let envelope = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope" xmlns:tptz="http://www.onvif.org/ver20/ptz/wsdl" xmlns:tt="http://www.onvif.org/ver10/schema"><s:Header>{}</s:Header><s:Body>{}</s:Body></s:Envelope>"#,
+            self.security_header(),
+            body
+        );
+
+        let response = self.client.post(&self.config.onvif_url)?
+            .header("Content-Type", "application/soap+xml; charset=utf-8")
+            .body(envelope)
+            .send()
+            .await
+            .context("Failed to send ONVIF PTZ request")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("ONVIF PTZ request failed ({}): {}", status, text));
+        }
+
+        Ok(())
+    }
+
+    // WS-Security UsernameToken with password digest, as required by ONVIF
+    fn security_header(&self) -> String {
+        let nonce: [u8; 16] = rand::random();
+        let created = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+
+        let mut hasher = Sha1::new();
+        hasher.update(nonce);
+        hasher.update(created.as_bytes());
+        hasher.update(self.config.password.as_bytes());
+        let digest = BASE64.encode(hasher.finalize());
+
+        format!(
+            r#"<wsse:Security xmlns:wsse="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-secext-1.0.xsd" xmlns:wsu="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-utility-1.0.xsd"><wsse:UsernameToken><wsse:Username>{}</wsse:Username><wsse:Password Type="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-username-token-profile-1.0#PasswordDigest">{}</wsse:Password><wsse:Nonce EncodingType="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-soap-message-security-1.0#Base64Binary">{}</wsse:Nonce><wsu:Created>{}</wsu:Created></wsse:UsernameToken></wsse:Security>"#,
+            xml_escape(&self.config.username),
+            digest,
+            BASE64.encode(nonce),
+            created
+        )
+    }
+}
+
+fn pan_tilt_xml(vector: &PtzVector) -> String {
+    format!(r#"<tt:PanTilt x="{:.4}" y="{:.4}"/>"#, vector.pan, vector.tilt)
+}
+
+fn zoom_xml(vector: &PtzVector) -> String {
+    format!(r#"<tt:Zoom x="{:.4}"/>"#, vector.zoom)
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoTrackState {
+    pub enabled: bool,
+    pub target_class: Option<String>,
+    pub target: Option<BBox>,
+    pub frames_without_target: u32,
+}
+
+pub struct AutoTracker {
+    config: AutoTrackConfig,
+    state: AutoTrackState,
+    // What the camera was last told, so unchanged commands aren't repeated
+    issued: Option<PtzCommand>,
+}
+
+impl AutoTracker {
+    pub fn new(config: AutoTrackConfig) -> Self {
+        Self {
+            config,
+            state: AutoTrackState {
+                enabled: false,
+                target_class: None,
+                target: None,
+                frames_without_target: 0,
+            },
+            issued: None,
+        }
+    }
+
+    pub fn state(&self) -> &AutoTrackState {
+        &self.state
+    }
+
+    pub fn enable(&mut self, target_class: Option<String>) {
+        self.state.enabled = true;
+        self.state.target_class = target_class;
+        self.state.target = None;
+        self.state.frames_without_target = 0;
+        self.issued = None;
+    }
+
+    pub fn disable(&mut self) {
+        self.state.enabled = false;
+        self.state.target = None;
+        self.issued = None;
+    }
+
+    // Returns the command to issue for this frame, if any. While the target
+    // stays put nothing is returned; the camera keeps its last move.
+    pub fn update(&mut self, detections: &[Detection], frame_width: f32, frame_height: f32) -> Option<PtzCommand> {
+        if !self.state.enabled || frame_width <= 0.0 || frame_height <= 0.0 {
+            return None;
+        }
+
+        let Some(target) = self.select_target(detections) else {
+            self.state.frames_without_target += 1;
+            if self.state.frames_without_target == self.config.lost_after_frames {
+                self.state.target = None;
+                self.issued = Some(PtzCommand::Stop);
+                return Some(PtzCommand::Stop);
+            }
+            return None;
+        };
+
+        self.state.frames_without_target = 0;
+        self.state.target = Some(target.clone());
+
+        // Normalized offset of the target center from the frame center, in [-1, 1]
+        let dx = ((target.x + target.width / 2.0) / frame_width - 0.5) * 2.0;
+        let dy = ((target.y + target.height / 2.0) / frame_height - 0.5) * 2.0;
+
+        let pan = self.axis_velocity(dx);
+        // ONVIF tilt is positive upwards while image y grows downwards
+        let tilt = -self.axis_velocity(dy);
+
+        let command = if pan == 0.0 && tilt == 0.0 {
+            PtzCommand::Stop
+        } else {
+            PtzCommand::ContinuousMove { velocity: PtzVector { pan, tilt, zoom: 0.0 } }
+        };
+        self.issue(command)
+    }
+
+    fn issue(&mut self, command: PtzCommand) -> Option<PtzCommand> {
+        let unchanged = match (&self.issued, &command) {
+            (Some(PtzCommand::Stop), PtzCommand::Stop) => true,
+            (Some(PtzCommand::ContinuousMove { velocity: last }), PtzCommand::ContinuousMove { velocity }) => {
+                (velocity.pan - last.pan).abs() < VELOCITY_STEP && (velocity.tilt - last.tilt).abs() < VELOCITY_STEP
+            }
+            _ => false,
+        };
+        if unchanged {
+            return None;
+        }
+        self.issued = Some(command.clone());
+        Some(command)
+    }
+
+    fn axis_velocity(&self, offset: f32) -> f32 {
+        if offset.abs() <= self.config.deadzone {
+            return 0.0;
+        }
+        (offset * self.config.gain).clamp(-self.config.max_speed, self.config.max_speed)
+    }
+
+    // Prefer the candidate nearest the previous target so the camera doesn't
+    // jump between objects of the same class
+    fn select_target(&self, detections: &[Detection]) -> Option<BBox> {
+        let candidates = detections.iter()
+            .filter(|d| d.confidence >= self.config.min_confidence)
+            .filter(|d| match &self.state.target_class {
+                Some(class) => &d.class_name == class,
+                None => true,
+            });
+
+        match &self.state.target {
+            Some(previous) => {
+                let (px, py) = center(previous);
+                candidates
+                    .min_by(|a, b| {
+                        let da = distance(center(&a.bbox), (px, py));
+                        let db = distance(center(&b.bbox), (px, py));
+                        da.total_cmp(&db)
+                    })
+                    .map(|d| d.bbox.clone())
+            }
+            None => candidates
+                .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+                .map(|d| d.bbox.clone()),
+        }
+    }
+}
+
+pub struct PtzController {
+    client: OnvifPtzClient,
+    tracker: Mutex<AutoTracker>,
+}
+
+impl PtzController {
+    pub fn new(config: PtzConfig, client: EgressClient) -> Self {
+        let tracker = AutoTracker::new(config.auto_tracking.clone());
+        Self {
+            client: OnvifPtzClient::new(config, client),
+            tracker: Mutex::new(tracker),
+        }
+    }
+
+    pub async fn execute(&self, command: &PtzCommand) -> Result<()> {
+        // Manual control always wins over auto-tracking
+        self.tracker.lock().await.disable();
+        self.client.execute(command).await
+    }
+
+    pub async fn set_auto_tracking(&self, enabled: bool, target_class: Option<String>) -> Result<()> {
+        let mut tracker = self.tracker.lock().await;
+        if enabled {
+            tracker.enable(target_class);
+            Ok(())
+        } else {
+            tracker.disable();
+            drop(tracker);
+            self.client.execute(&PtzCommand::Stop).await
+        }
+    }
+
+    pub async fn auto_track_state(&self) -> AutoTrackState {
+        self.tracker.lock().await.state().clone()
+    }
+
+    pub async fn on_detections(&self, detections: &[Detection], frame_width: f32, frame_height: f32) -> Result<()> {
+        let command = self.tracker.lock().await.update(detections, frame_width, frame_height);
+        if let Some(command) = command {
+            self.client.execute(&command).await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct PtzManager {
+    controllers: RwLock<HashMap<String, Arc<PtzController>>>,
+    egress: EgressConfig,
+}
+
+impl PtzManager {
+    pub fn new(egress: EgressConfig) -> Self {
+        Self { controllers: RwLock::new(HashMap::new()), egress }
+    }
+
+    // Registers a controller for the stream's PTZ config, replacing any
+    // earlier one, or removes it when the stream has none
+    pub async fn configure(&self, stream_id: &str, config: Option<&PtzConfig>) -> Result<()> {
+        match config {
+            Some(config) => {
+                let client = EgressClient::new(&self.egress, REQUEST_TIMEOUT)?;
+                self.register(stream_id, Arc::new(PtzController::new(config.clone(), client))).await;
+            }
+            None => self.unregister(stream_id).await,
+        }
+        Ok(())
+    }
+
+    pub async fn register(&self, stream_id: &str, controller: Arc<PtzController>) {
+        self.controllers.write().await.insert(stream_id.to_string(), controller);
+    }
+
+    pub async fn unregister(&self, stream_id: &str) {
+        self.controllers.write().await.remove(stream_id);
+    }
+
+    pub async fn get(&self, stream_id: &str) -> Option<Arc<PtzController>> {
+        self.controllers.read().await.get(stream_id).cloned()
+    }
+}
@@ -0,0 +1,213 @@
+#![cfg(feature = "vision")]
+
+use std::sync::Mutex;
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use opencv::{
+    prelude::*,
+    core::{self, Mat, Scalar, Size, Vector, CV_32F},
+    dnn,
+    imgproc,
+};
+
+use crate::vision::detector::Detection;
+use crate::vision::geometry;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SceneModelConfig {
+    pub model_path: String,
+    // One label per model output, in output order
+    pub labels: Vec<String>,
+    #[serde(default = "default_scene_input")]
+    pub input_size: (i32, i32),
+    // Outputs are independent sigmoids; every label above this is reported
+    #[serde(default = "default_label_threshold")]
+    pub threshold: f32,
+    #[serde(default = "default_max_labels")]
+    pub max_labels: usize,
+}
+
+fn default_scene_input() -> (i32, i32) {
+    (224, 224)
+}
+
+fn default_label_threshold() -> f32 {
+    0.5
+}
+
+fn default_max_labels() -> usize {
+    5
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneLabel {
+    pub label: String,
+    pub confidence: f32,
+}
+
+pub struct SceneClassifier {
+    config: SceneModelConfig,
+    // dnn::Net isn't Sync and forward() needs &mut
+    net: Mutex<dnn::Net>,
+}
+
+impl SceneClassifier {
+    pub fn new(config: SceneModelConfig) -> Result<Self> {
+        let net = dnn::read_net_from_onnx(&config.model_path)
+            .with_context(|| format!("Failed to load scene model {}", config.model_path))?;
+
+        Ok(Self {
+            config,
+            net: Mutex::new(net),
+        })
+    }
+
+    pub fn classify(&self, image: &Mat) -> Result<Vec<SceneLabel>> {
+        let (width, height) = self.config.input_size;
+        let blob = dnn::blob_from_image(
+            image,
+            1.0 / 255.0,
+            Size::new(width, height),
+            Scalar::default(),
+            true,
+            false,
+            CV_32F,
+        )
+        .context("Failed to prepare scene input")?;
+
+        let output = {
+            let mut net = self.net.lock().unwrap();
+            net.set_input(&blob, "", 1.0, Scalar::default())?;
+            net.forward_single("").context("Scene model inference failed")?
+        };
+
+        let logits = output.data_typed::<f32>()?;
+        if logits.len() != self.config.labels.len() {
+            return Err(anyhow::anyhow!(
+                "Scene model produced {} outputs for {} labels", logits.len(), self.config.labels.len()
+            ));
+        }
+
+        Ok(select_labels(logits, &self.config.labels, self.config.threshold, self.config.max_labels))
+    }
+}
+
+// Multi-label: each logit is squashed on its own, so several labels can be
+// confident at once ("outdoor", "parking_lot", "night")
+pub fn select_labels(logits: &[f32], labels: &[String], threshold: f32, max_labels: usize) -> Vec<SceneLabel> {
+    let mut selected: Vec<SceneLabel> = logits.iter()
+        .zip(labels)
+        .map(|(logit, label)| SceneLabel {
+            label: label.clone(),
+            confidence: 1.0 / (1.0 + (-logit).exp()),
+        })
+        .filter(|l| l.confidence >= threshold)
+        .collect();
+
+    selected.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(std::cmp::Ordering::Equal));
+    selected.truncate(max_labels);
+    selected
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LightingStats {
+    // Luma statistics on a 0-255 scale
+    pub mean: f32,
+    pub std_dev: f32,
+    // Share of pixels in the bottom and top 5% of the range
+    pub shadows: f32,
+    pub highlights: f32,
+}
+
+pub fn lighting_stats(image: &Mat) -> Result<LightingStats> {
+    let mut gray = Mat::default();
+    if image.channels() == 1 {
+        gray = image.clone();
+    } else {
+        imgproc::cvt_color(image, &mut gray, imgproc::COLOR_BGR2GRAY, 0)?;
+    }
+
+    let mut hist = Mat::default();
+    let images: Vector<Mat> = Vector::from_iter([gray]);
+    imgproc::calc_hist(
+        &images,
+        &Vector::from_slice(&[0]),
+        &core::no_array(),
+        &mut hist,
+        &Vector::from_slice(&[256]),
+        &Vector::from_slice(&[0.0f32, 256.0]),
+        false,
+    )?;
+
+    let bins: Vec<f32> = (0..256).map(|i| *hist.at::<f32>(i).unwrap_or(&0.0)).collect();
+    Ok(stats_from_histogram(&bins))
+}
+
+pub fn stats_from_histogram(bins: &[f32]) -> LightingStats {
+    let total: f32 = bins.iter().sum();
+    if total <= 0.0 {
+        return LightingStats { mean: 0.0, std_dev: 0.0, shadows: 0.0, highlights: 0.0 };
+    }
+
+    let mean = bins.iter().enumerate().map(|(i, n)| i as f32 * n).sum::<f32>() / total;
+    let variance = bins.iter().enumerate().map(|(i, n)| (i as f32 - mean).powi(2) * n).sum::<f32>() / total;
+    let edge = (bins.len() as f32 * 0.05).ceil() as usize;
+
+    LightingStats {
+        mean,
+        std_dev: variance.sqrt(),
+        shadows: bins.iter().take(edge).sum::<f32>() / total,
+        highlights: bins.iter().rev().take(edge).sum::<f32>() / total,
+    }
+}
+
+pub fn classify_lighting(stats: &LightingStats) -> &'static str {
+    if stats.shadows > 0.25 && stats.highlights > 0.25 {
+        "high_contrast"
+    } else if stats.mean < 50.0 {
+        "dark"
+    } else if stats.mean < 100.0 {
+        "dim"
+    } else if stats.mean > 200.0 || stats.highlights > 0.4 {
+        "overexposed"
+    } else if stats.std_dev < 20.0 {
+        "flat"
+    } else {
+        "bright"
+    }
+}
+
+// Rough framing description from where detections sit in the frame
+pub fn composition(detections: &[Detection], width: f32, height: f32) -> &'static str {
+    if detections.is_empty() || width <= 0.0 || height <= 0.0 {
+        return "empty";
+    }
+
+    let frame_area = width * height;
+    let covered: f32 = detections.iter().map(|d| d.bbox.width * d.bbox.height).sum::<f32>() / frame_area;
+    if detections.len() > 15 || covered > 0.6 {
+        return "crowded";
+    }
+
+    // Area-weighted centre of mass, normalised to 0..1
+    let weight: f32 = detections.iter().map(|d| (d.bbox.width * d.bbox.height).max(1.0)).sum();
+    let (cx, cy) = detections.iter().fold((0.0, 0.0), |(x, y), d| {
+        let w = geometry::area(&d.bbox).max(1.0);
+        let (bx, by) = geometry::center(&d.bbox);
+        (x + bx / width * w, y + by / height * w)
+    });
+    let (cx, cy) = (cx / weight, cy / weight);
+
+    let near = |value: f32, target: f32| (value - target).abs() < 0.08;
+    if near(cx, 0.5) && near(cy, 0.5) {
+        "centered"
+    } else if (near(cx, 1.0 / 3.0) || near(cx, 2.0 / 3.0)) && (near(cy, 1.0 / 3.0) || near(cy, 2.0 / 3.0)) {
+        "rule_of_thirds"
+    } else if (cx - 0.5).abs() < 0.15 {
+        "balanced"
+    } else if cx < 0.5 {
+        "left_weighted"
+    } else {
+        "right_weighted"
+    }
+}
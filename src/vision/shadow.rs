@@ -0,0 +1,343 @@
+#![cfg(feature = "vision")]
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use tokio::sync::{RwLock, Semaphore};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+use crate::core::llm::latency::percentile;
+use crate::vision::{
+    processor::Frame,
+    cascade::{Cascade, CascadeConfig},
+    detector::{Detection, Detector, DetectorConfig},
+    ensemble::{Ensemble, EnsembleConfig},
+};
+
+// What the rollout swaps between; Detector in production, stubs in tests
+#[async_trait]
+pub trait DetectionModel: Send + Sync {
+    async fn detect(&self, frame: &Frame) -> Result<Vec<Detection>>;
+}
+
+#[async_trait]
+impl DetectionModel for Detector {
+    async fn detect(&self, frame: &Frame) -> Result<Vec<Detection>> {
+        Detector::detect(self, frame).await
+    }
+}
+
+// A detection model as configured for production or sent as a shadow
+// candidate: one detector or an ensemble, optionally behind a cascade gate
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DetectionModelConfig {
+    #[serde(default)]
+    pub detector: Option<DetectorConfig>,
+    #[serde(default)]
+    pub ensemble: Option<EnsembleConfig>,
+    #[serde(default)]
+    pub cascade: Option<CascadeConfig>,
+}
+
+pub async fn build_detection_model(config: DetectionModelConfig) -> Result<Arc<dyn DetectionModel>> {
+    let model: Arc<dyn DetectionModel> = match (config.detector, config.ensemble) {
+        (Some(detector), None) => Arc::new(Detector::new(detector).await?),
+        (None, Some(ensemble)) => Arc::new(Ensemble::new(ensemble).await?),
+        _ => return Err(anyhow::anyhow!("Give exactly one of detector or ensemble")),
+    };
+    match config.cascade {
+        Some(cascade) => Ok(Arc::new(Cascade::new(cascade, model).await?)),
+        None => Ok(model),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShadowConfig {
+    // Share of frames also sent to the candidate; below 1.0 when the
+    // device can't afford running both models on everything
+    pub sample_rate: f32,
+    // Boxes of the same class overlapping at least this much agree
+    pub match_iou: f32,
+    pub latency_window: usize,
+    // Candidate runs allowed at once; sampled frames beyond this are
+    // skipped rather than queued behind a slow candidate
+    pub max_in_flight: usize,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 1.0,
+            match_iou: 0.5,
+            latency_window: 1000,
+            max_in_flight: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameComparison {
+    pub matched: usize,
+    pub production_only: usize,
+    pub candidate_only: usize,
+    // Matched boxes over all boxes (F1 between the two outputs); two empty
+    // outputs agree fully
+    pub agreement: f32,
+}
+
+// Greedy one-to-one matching, best overlap first
+pub fn compare_detections(production: &[Detection], candidate: &[Detection], match_iou: f32) -> FrameComparison {
+    let mut pairs: Vec<(f32, usize, usize)> = Vec::new();
+    for (i, p) in production.iter().enumerate() {
+        for (j, c) in candidate.iter().enumerate() {
+            if p.class_name != c.class_name {
+                continue;
+            }
+            let iou = p.bbox.iou(&c.bbox);
+            if iou >= match_iou {
+                pairs.push((iou, i, j));
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut used_production = vec![false; production.len()];
+    let mut used_candidate = vec![false; candidate.len()];
+    let mut matched = 0;
+    for (_, i, j) in pairs {
+        if !used_production[i] && !used_candidate[j] {
+            used_production[i] = true;
+            used_candidate[j] = true;
+            matched += 1;
+        }
+    }
+
+    let total = production.len() + candidate.len();
+    FrameComparison {
+        matched,
+        production_only: production.len() - matched,
+        candidate_only: candidate.len() - matched,
+        agreement: if total == 0 { 1.0 } else { 2.0 * matched as f32 / total as f32 },
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencySummary {
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShadowReport {
+    pub production: String,
+    pub candidate: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub frames_compared: u64,
+    // Sampled while max_in_flight candidate runs were already going
+    pub frames_skipped: u64,
+    pub candidate_errors: u64,
+    pub agreement_rate: Option<f32>,
+    pub production_detections: u64,
+    pub candidate_detections: u64,
+    pub production_latency: LatencySummary,
+    pub candidate_latency: LatencySummary,
+    pub previous: Option<String>,
+}
+
+#[derive(Default)]
+struct ShadowStats {
+    since: Option<DateTime<Utc>>,
+    frames_compared: u64,
+    frames_skipped: u64,
+    candidate_errors: u64,
+    agreement_sum: f64,
+    production_detections: u64,
+    candidate_detections: u64,
+    production_latency: VecDeque<f64>,
+    candidate_latency: VecDeque<f64>,
+}
+
+struct Slot {
+    name: String,
+    model: Arc<dyn DetectionModel>,
+}
+
+struct Slots {
+    production: Slot,
+    candidate: Option<Slot>,
+    // Kept after a promotion so it can be rolled back without reloading
+    previous: Option<Slot>,
+}
+
+// Sits on the engine's detection path in place of the configured model, so
+// a promoted candidate serves production frames and the shadow sees them too
+pub struct ShadowDeployment {
+    config: ShadowConfig,
+    slots: RwLock<Slots>,
+    stats: Arc<RwLock<ShadowStats>>,
+    sampled: AtomicU64,
+    in_flight: Arc<Semaphore>,
+}
+
+fn push_sample(samples: &mut VecDeque<f64>, value: f64, window: usize) {
+    samples.push_back(value);
+    while samples.len() > window.max(1) {
+        samples.pop_front();
+    }
+}
+
+fn summarize_latency(samples: &VecDeque<f64>) -> LatencySummary {
+    let mut sorted: Vec<f64> = samples.iter().copied().collect();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    LatencySummary {
+        p50_ms: percentile(&sorted, 0.5),
+        p95_ms: percentile(&sorted, 0.95),
+    }
+}
+
+impl ShadowDeployment {
+    pub fn new(config: ShadowConfig, name: &str, production: Arc<dyn DetectionModel>) -> Self {
+        Self {
+            slots: RwLock::new(Slots {
+                production: Slot { name: name.to_string(), model: production },
+                candidate: None,
+                previous: None,
+            }),
+            stats: Arc::new(RwLock::new(ShadowStats::default())),
+            sampled: AtomicU64::new(0),
+            in_flight: Arc::new(Semaphore::new(config.max_in_flight.max(1))),
+            config,
+        }
+    }
+
+    fn should_sample(&self) -> bool {
+        let rate = self.config.sample_rate.clamp(0.0, 1.0);
+        if rate >= 1.0 {
+            return true;
+        }
+        // Deterministic spacing rather than random sampling, so short runs
+        // still see an even spread of frames
+        let n = self.sampled.fetch_add(1, Ordering::Relaxed);
+        ((n + 1) as f32 * rate).floor() > (n as f32 * rate).floor()
+    }
+
+    // Results always come from production. The candidate runs on its own
+    // task afterwards, so neither its latency nor its failures reach callers.
+    pub async fn detect(&self, frame: &Frame) -> Result<Vec<Detection>> {
+        let (production, candidate) = {
+            let slots = self.slots.read().await;
+            let candidate = slots.candidate.as_ref()
+                .filter(|_| self.should_sample())
+                .map(|c| c.model.clone());
+            (slots.production.model.clone(), candidate)
+        };
+
+        let started = Instant::now();
+        let detections = production.detect(frame).await?;
+        let production_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        let Some(candidate) = candidate else {
+            return Ok(detections);
+        };
+        let Ok(permit) = self.in_flight.clone().try_acquire_owned() else {
+            self.stats.write().await.frames_skipped += 1;
+            return Ok(detections);
+        };
+        let frame = frame.clone();
+        let baseline = detections.clone();
+        let stats = self.stats.clone();
+        let config = self.config.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let started = Instant::now();
+            let result = candidate.detect(&frame).await;
+            let candidate_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+            let mut stats = stats.write().await;
+            push_sample(&mut stats.production_latency, production_ms, config.latency_window);
+            match result {
+                Ok(shadow) => {
+                    let comparison = compare_detections(&baseline, &shadow, config.match_iou);
+                    stats.frames_compared += 1;
+                    stats.agreement_sum += comparison.agreement as f64;
+                    stats.production_detections += baseline.len() as u64;
+                    stats.candidate_detections += shadow.len() as u64;
+                    push_sample(&mut stats.candidate_latency, candidate_ms, config.latency_window);
+                }
+                Err(e) => {
+                    stats.candidate_errors += 1;
+                    log::warn!("Shadow model failed on frame {}: {}", frame.id, e);
+                }
+            }
+        });
+
+        Ok(detections)
+    }
+
+    pub async fn start_shadow(&self, name: &str, candidate: Arc<dyn DetectionModel>) {
+        self.slots.write().await.candidate = Some(Slot { name: name.to_string(), model: candidate });
+        *self.stats.write().await = ShadowStats {
+            since: Some(Utc::now()),
+            ..ShadowStats::default()
+        };
+    }
+
+    pub async fn stop_shadow(&self) -> Option<String> {
+        self.slots.write().await.candidate.take().map(|c| c.name)
+    }
+
+    // Candidate becomes production; the old production model is kept as the
+    // rollback target
+    pub async fn promote(&self) -> Result<String> {
+        let mut slots = self.slots.write().await;
+        let candidate = slots.candidate.take()
+            .ok_or_else(|| anyhow::anyhow!("No candidate model in shadow"))?;
+        let name = candidate.name.clone();
+        let previous = std::mem::replace(&mut slots.production, candidate);
+        log::warn!("Promoted model {} over {}", name, previous.name);
+        slots.previous = Some(previous);
+        Ok(name)
+    }
+
+    pub async fn rollback(&self) -> Result<String> {
+        let mut slots = self.slots.write().await;
+        let previous = slots.previous.take()
+            .ok_or_else(|| anyhow::anyhow!("No previous model to roll back to"))?;
+        let name = previous.name.clone();
+        let demoted = std::mem::replace(&mut slots.production, previous);
+        log::warn!("Rolled back model {} to {}", demoted.name, name);
+        Ok(name)
+    }
+
+    pub async fn report(&self) -> ShadowReport {
+        let slots = self.slots.read().await;
+        let stats = self.stats.read().await;
+        ShadowReport {
+            production: slots.production.name.clone(),
+            candidate: slots.candidate.as_ref().map(|c| c.name.clone()),
+            since: stats.since,
+            frames_compared: stats.frames_compared,
+            frames_skipped: stats.frames_skipped,
+            candidate_errors: stats.candidate_errors,
+            agreement_rate: (stats.frames_compared > 0)
+                .then(|| (stats.agreement_sum / stats.frames_compared as f64) as f32),
+            production_detections: stats.production_detections,
+            candidate_detections: stats.candidate_detections,
+            production_latency: summarize_latency(&stats.production_latency),
+            candidate_latency: summarize_latency(&stats.candidate_latency),
+            previous: slots.previous.as_ref().map(|p| p.name.clone()),
+        }
+    }
+}
+
+#[async_trait]
+impl DetectionModel for ShadowDeployment {
+    async fn detect(&self, frame: &Frame) -> Result<Vec<Detection>> {
+        ShadowDeployment::detect(self, frame).await
+    }
+}
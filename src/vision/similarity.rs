@@ -0,0 +1,332 @@
+#![cfg(feature = "vision")]
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::{RwLock, Semaphore};
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use opencv::{
+    prelude::*,
+    core::{Mat, Rect, Vector},
+    imgcodecs,
+};
+
+use crate::core::rag::store::cosine_similarity;
+use crate::utils::egress::{EgressClient, EgressConfig};
+use crate::vision::{
+    processor::Frame,
+    detector::{BBox, Detection},
+    geometry,
+    tracker::encode_crop,
+};
+
+// Embeds images and text into one space (CLIP-style), so a text query can
+// be compared directly against stored image embeddings
+#[async_trait]
+pub trait ImageEmbedder: Send + Sync {
+    fn model_id(&self) -> String;
+    async fn embed_image(&self, image: &Mat) -> Result<Vec<f32>>;
+    async fn embed_text(&self, text: &str) -> Result<Vec<f32>>;
+}
+
+// Talks to an embedding service taking `{"image": <base64 jpeg>}` or
+// `{"text": ...}` and returning `{"embedding": [...]}`
+pub struct RemoteClipEmbedder {
+    url: String,
+    model: String,
+    client: EgressClient,
+}
+
+impl RemoteClipEmbedder {
+    pub fn new(url: &str, model: &str, client: EgressClient) -> Self {
+        Self {
+            url: url.to_string(),
+            model: model.to_string(),
+            client,
+        }
+    }
+
+    async fn request(&self, body: serde_json::Value) -> Result<Vec<f32>> {
+        let response: EmbeddingResponse = self.client.post(&self.url)?
+            .json(&body)
+            .send()
+            .await
+            .context("Failed to call embedding service")?
+            .error_for_status()
+            .context("Embedding service rejected request")?
+            .json()
+            .await
+            .context("Failed to parse embedding response")?;
+        Ok(response.embedding)
+    }
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    embedding: Vec<f32>,
+}
+
+#[async_trait]
+impl ImageEmbedder for RemoteClipEmbedder {
+    fn model_id(&self) -> String {
+        self.model.clone()
+    }
+
+    async fn embed_image(&self, image: &Mat) -> Result<Vec<f32>> {
+        let mut buffer = Vector::<u8>::new();
+        imgcodecs::imencode(".jpg", image, &mut buffer, &Vector::new())
+            .context("Failed to encode image for embedding")?;
+        self.request(serde_json::json!({ "model": self.model, "image": BASE64.encode(buffer.as_slice()) })).await
+    }
+
+    async fn embed_text(&self, text: &str) -> Result<Vec<f32>> {
+        self.request(serde_json::json!({ "model": self.model, "text": text })).await
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimilarityConfig {
+    pub capacity: usize,
+    // Detection classes worth indexing as individual crops, in addition to
+    // the whole frame
+    pub crop_classes: Vec<String>,
+    pub min_crop_confidence: f32,
+    // Only every Nth frame per source is embedded; embedding is expensive
+    // and consecutive frames are near-duplicates anyway
+    pub frame_interval: u64,
+    // Frames being embedded at once when indexing from the pipeline; more
+    // than that are skipped rather than queued behind a slow embedder
+    pub max_in_flight: usize,
+}
+
+impl Default for SimilarityConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 50_000,
+            crop_classes: vec!["person".to_string(), "car".to_string()],
+            min_crop_confidence: 0.6,
+            frame_interval: 30,
+            max_in_flight: 4,
+        }
+    }
+}
+
+// The index plus the embedding service it uses, as they appear in the
+// pipeline config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilaritySettings {
+    #[serde(flatten)]
+    pub index: SimilarityConfig,
+    pub embedder_url: String,
+    pub model: String,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    #[serde(default)]
+    pub egress: EgressConfig,
+}
+
+fn default_timeout_ms() -> u64 {
+    10000
+}
+
+pub fn build_index(settings: &SimilaritySettings) -> Result<Arc<SimilarityIndex>> {
+    let client = EgressClient::new(&settings.egress, std::time::Duration::from_millis(settings.timeout_ms))?;
+    let embedder = RemoteClipEmbedder::new(&settings.embedder_url, &settings.model, client);
+    Ok(Arc::new(SimilarityIndex::new(settings.index.clone(), Arc::new(embedder))))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageEntry {
+    pub source: String,
+    pub frame_id: u64,
+    pub timestamp: DateTime<Utc>,
+    // None for whole-frame entries
+    pub bbox: Option<BBox>,
+    pub class_name: Option<String>,
+    // Base64 JPEG of crops, so results can be shown without the recording
+    pub thumbnail: Option<String>,
+    #[serde(skip)]
+    pub embedding: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimilarityHit {
+    pub score: f32,
+    #[serde(flatten)]
+    pub entry: ImageEntry,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SearchFilter {
+    pub source: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub crops_only: bool,
+}
+
+pub enum SearchQuery {
+    Image(Mat),
+    Text(String),
+}
+
+pub struct SimilarityIndex {
+    config: SimilarityConfig,
+    embedder: Arc<dyn ImageEmbedder>,
+    entries: RwLock<VecDeque<ImageEntry>>,
+    in_flight: Arc<Semaphore>,
+}
+
+impl SimilarityIndex {
+    pub fn new(config: SimilarityConfig, embedder: Arc<dyn ImageEmbedder>) -> Self {
+        Self {
+            in_flight: Arc::new(Semaphore::new(config.max_in_flight.max(1))),
+            config,
+            embedder,
+            entries: RwLock::new(VecDeque::new()),
+        }
+    }
+
+    fn is_indexed(&self, frame: &Frame) -> bool {
+        self.config.frame_interval <= 1 || frame.id % self.config.frame_interval == 0
+    }
+
+    // Indexes the frame in the background so embedding never holds up the
+    // caller. Returns false when the frame was skipped, either by
+    // frame_interval or because max_in_flight frames are already embedding.
+    pub fn submit(self: &Arc<Self>, frame: &Frame, detections: &[Detection]) -> bool {
+        if !self.is_indexed(frame) {
+            return false;
+        }
+        let Ok(permit) = self.in_flight.clone().try_acquire_owned() else {
+            log::debug!("Embedder is busy, not indexing frame {}", frame.id);
+            return false;
+        };
+        let index = self.clone();
+        let frame = frame.clone();
+        let detections = detections.to_vec();
+        tokio::spawn(async move {
+            if let Err(e) = index.index_frame(&frame, &detections).await {
+                log::warn!("Failed to index frame {}: {}", frame.id, e);
+            }
+            drop(permit);
+        });
+        true
+    }
+
+    pub async fn len(&self) -> usize {
+        self.entries.read().await.len()
+    }
+
+    pub async fn insert(&self, entry: ImageEntry) {
+        let mut entries = self.entries.write().await;
+        // Oldest first out; the index is a rolling window over recent footage
+        if entries.len() >= self.config.capacity.max(1) {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    // Indexes the frame and any qualifying detection crops. Returns how many
+    // entries were added.
+    pub async fn index_frame(&self, frame: &Frame, detections: &[Detection]) -> Result<usize> {
+        if !self.is_indexed(frame) {
+            return Ok(0);
+        }
+
+        let mut added = 0;
+        let embedding = self.embedder.embed_image(&frame.data).await?;
+        self.insert(ImageEntry {
+            source: frame.metadata.source.clone(),
+            frame_id: frame.id,
+            timestamp: frame.timestamp,
+            bbox: None,
+            class_name: None,
+            thumbnail: None,
+            embedding,
+        }).await;
+        added += 1;
+
+        let crops = detections.iter().filter(|d| {
+            d.confidence >= self.config.min_crop_confidence && self.config.crop_classes.contains(&d.class_name)
+        });
+        for detection in crops {
+            let crop = match crop_region(&frame.data, &detection.bbox) {
+                Some(crop) => crop,
+                None => continue,
+            };
+            let embedding = match self.embedder.embed_image(&crop).await {
+                Ok(embedding) => embedding,
+                Err(e) => {
+                    log::warn!("Failed to embed crop on frame {}: {}", frame.id, e);
+                    continue;
+                }
+            };
+            self.insert(ImageEntry {
+                source: frame.metadata.source.clone(),
+                frame_id: frame.id,
+                timestamp: frame.timestamp,
+                bbox: Some(detection.bbox.clone()),
+                class_name: Some(detection.class_name.clone()),
+                thumbnail: encode_crop(&frame.data, &detection.bbox).ok(),
+                embedding,
+            }).await;
+            added += 1;
+        }
+
+        Ok(added)
+    }
+
+    pub async fn search(
+        &self,
+        query: SearchQuery,
+        filter: &SearchFilter,
+        limit: usize,
+        min_score: f32,
+    ) -> Result<Vec<SimilarityHit>> {
+        let embedding = match &query {
+            SearchQuery::Image(image) => self.embedder.embed_image(image).await?,
+            SearchQuery::Text(text) => self.embedder.embed_text(text).await?,
+        };
+        Ok(self.search_embedding(&embedding, filter, limit, min_score).await)
+    }
+
+    pub async fn search_embedding(
+        &self,
+        embedding: &[f32],
+        filter: &SearchFilter,
+        limit: usize,
+        min_score: f32,
+    ) -> Vec<SimilarityHit> {
+        let entries = self.entries.read().await;
+        let mut hits: Vec<SimilarityHit> = entries.iter()
+            .filter(|e| filter.source.as_ref().map_or(true, |s| &e.source == s))
+            .filter(|e| filter.since.map_or(true, |t| e.timestamp >= t))
+            .filter(|e| filter.until.map_or(true, |t| e.timestamp <= t))
+            .filter(|e| !filter.crops_only || e.bbox.is_some())
+            .map(|e| (cosine_similarity(embedding, &e.embedding), e))
+            .filter(|(score, _)| *score >= min_score)
+            .map(|(score, e)| SimilarityHit { score, entry: e.clone() })
+            .collect();
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(limit);
+        hits
+    }
+}
+
+fn crop_region(image: &Mat, bbox: &BBox) -> Option<Mat> {
+    let region = geometry::clip(bbox, image.cols() as f32, image.rows() as f32)?;
+    let x = region.x as i32;
+    let y = region.y as i32;
+    let width = (region.width as i32).min(image.cols() - x);
+    let height = (region.height as i32).min(image.rows() - y);
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+    Mat::roi(image, Rect::new(x, y, width, height)).ok()?.try_clone().ok()
+}
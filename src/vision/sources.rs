@@ -0,0 +1,604 @@
+#![cfg(feature = "vision")]
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::{mpsc, RwLock};
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use opencv::{
+    prelude::*,
+    core::{Mat, Point, Rect, Scalar, CV_8UC3},
+    imgcodecs,
+    imgproc,
+    videoio,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::core::stream_stats::StreamStats;
+use crate::vision::transcode::{TranscodeConfig, TranscodedSource};
+
+// Produces raw frames for a Processor; `None` means the source is exhausted
+#[async_trait]
+pub trait FrameSource: Send {
+    fn name(&self) -> String;
+    async fn next_frame(&mut self) -> Result<Option<Mat>>;
+
+    // Sources whose producers need to correlate results supply their own
+    // frame id alongside the pixels
+    async fn next_tagged(&mut self) -> Result<Option<(Mat, Option<u64>)>> {
+        Ok(self.next_frame().await?.map(|frame| (frame, None)))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SourceConfig {
+    File {
+        path: String,
+        #[serde(default)]
+        looped: bool,
+    },
+    Rtsp {
+        url: String,
+    },
+    Camera {
+        index: i32,
+    },
+    ImageDirectory {
+        path: String,
+        #[serde(default = "default_directory_fps")]
+        fps: f64,
+        #[serde(default)]
+        looped: bool,
+    },
+    Synthetic {
+        width: u32,
+        height: u32,
+        #[serde(default)]
+        frames: Option<u64>,
+        #[serde(default)]
+        fps: f64,
+    },
+    // Frames arrive from outside, e.g. POST /v1/frames or gRPC
+    Push {
+        #[serde(default = "default_push_buffer")]
+        buffer: usize,
+    },
+}
+
+fn default_directory_fps() -> f64 {
+    1.0
+}
+
+fn default_push_buffer() -> usize {
+    32
+}
+
+impl SourceConfig {
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            SourceConfig::File { path, .. } if path.is_empty() => {
+                Err(anyhow::anyhow!("File source needs a path"))
+            }
+            SourceConfig::Rtsp { url } if !url.starts_with("rtsp://") && !url.starts_with("rtsps://") => {
+                Err(anyhow::anyhow!("RTSP source url must start with rtsp:// or rtsps://"))
+            }
+            SourceConfig::Camera { index } if *index < 0 => {
+                Err(anyhow::anyhow!("Camera index must not be negative"))
+            }
+            SourceConfig::ImageDirectory { fps, .. } | SourceConfig::Synthetic { fps, .. } if *fps < 0.0 => {
+                Err(anyhow::anyhow!("Source fps must not be negative"))
+            }
+            SourceConfig::Synthetic { width, height, .. } if *width == 0 || *height == 0 => {
+                Err(anyhow::anyhow!("Synthetic source dimensions must be non-zero"))
+            }
+            SourceConfig::Push { buffer } if *buffer == 0 => {
+                Err(anyhow::anyhow!("Push source buffer must be at least 1"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Older stream configs used a bare string for the source
+    pub fn parse_uri(uri: &str) -> Self {
+        if uri.starts_with("rtsp://") || uri.starts_with("rtsps://") {
+            SourceConfig::Rtsp { url: uri.to_string() }
+        } else if let Some(index) = uri.strip_prefix("camera:").and_then(|i| i.parse().ok()) {
+            SourceConfig::Camera { index }
+        } else {
+            SourceConfig::File { path: uri.to_string(), looped: false }
+        }
+    }
+}
+
+pub fn deserialize_source<'de, D>(deserializer: D) -> std::result::Result<SourceConfig, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum SourceSpec {
+        Uri(String),
+        Typed(SourceConfig),
+    }
+
+    Ok(match SourceSpec::deserialize(deserializer)? {
+        SourceSpec::Uri(uri) => SourceConfig::parse_uri(&uri),
+        SourceSpec::Typed(config) => config,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameEncoding {
+    Jpeg,
+    Png,
+    // Packed 8-bit BGR
+    RawBgr { width: u32, height: u32 },
+}
+
+impl FrameEncoding {
+    pub fn from_content_type(content_type: &str, width: Option<u32>, height: Option<u32>) -> Result<Self> {
+        match content_type.split(';').next().unwrap_or("").trim() {
+            "image/jpeg" | "image/jpg" => Ok(FrameEncoding::Jpeg),
+            "image/png" => Ok(FrameEncoding::Png),
+            "application/octet-stream" => match (width, height) {
+                (Some(width), Some(height)) => Ok(FrameEncoding::RawBgr { width, height }),
+                _ => Err(anyhow::anyhow!("Raw frames need width and height")),
+            },
+            other => Err(anyhow::anyhow!("Unsupported frame content type: {}", other)),
+        }
+    }
+}
+
+// Width and height from the image header, without decoding the pixels.
+// Sniffs the format the way imdecode does, so it doesn't matter which of
+// JPEG or PNG the caller claimed; raw frames carry their size already.
+pub fn frame_dimensions(encoding: FrameEncoding, data: &[u8]) -> Result<(u32, u32)> {
+    if let FrameEncoding::RawBgr { width, height } = encoding {
+        return Ok((width, height));
+    }
+    png_dimensions(data)
+        .or_else(|| jpeg_dimensions(data))
+        .ok_or_else(|| anyhow::anyhow!("Could not read image dimensions"))
+}
+
+fn png_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if !data.starts_with(b"\x89PNG\r\n\x1a\n") || data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+    let height = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+    Some((width, height))
+}
+
+// Walks the marker segments up to the first start-of-frame
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut i = 2;
+    loop {
+        while *data.get(i)? != 0xFF {
+            i += 1;
+        }
+        while *data.get(i)? == 0xFF {
+            i += 1;
+        }
+        let marker = *data.get(i)?;
+        i += 1;
+        // Standalone markers have no length
+        if marker == 0x01 || (0xD0..=0xD7).contains(&marker) {
+            continue;
+        }
+        let length = u16::from_be_bytes([*data.get(i)?, *data.get(i + 1)?]) as usize;
+        // SOF0-SOF15, less DHT, JPG and DAC which share the range
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let height = u16::from_be_bytes([*data.get(i + 3)?, *data.get(i + 4)?]);
+            let width = u16::from_be_bytes([*data.get(i + 5)?, *data.get(i + 6)?]);
+            return Some((width as u32, height as u32));
+        }
+        if marker == 0xDA || length < 2 {
+            return None;
+        }
+        i += length;
+    }
+}
+
+pub fn decode_frame(encoding: FrameEncoding, data: &[u8]) -> Result<Mat> {
+    match encoding {
+        FrameEncoding::Jpeg | FrameEncoding::Png => {
+            let buffer = opencv::core::Vector::<u8>::from_slice(data);
+            let frame = imgcodecs::imdecode(&buffer, imgcodecs::IMREAD_COLOR)?;
+            if frame.empty() {
+                return Err(anyhow::anyhow!("Failed to decode image"));
+            }
+            Ok(frame)
+        }
+        FrameEncoding::RawBgr { width, height } => {
+            let expected = width as usize * height as usize * 3;
+            if width == 0 || height == 0 || data.len() != expected {
+                return Err(anyhow::anyhow!(
+                    "Raw frame is {} bytes, expected {} for {}x{} BGR", data.len(), expected, width, height
+                ));
+            }
+            let mut frame = Mat::new_rows_cols_with_default(
+                height as i32, width as i32, CV_8UC3, Scalar::all(0.0),
+            )?;
+            frame.data_bytes_mut()?.copy_from_slice(data);
+            Ok(frame)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PushError {
+    UnknownStream,
+    // Pipeline isn't keeping up; the producer should back off
+    Backlogged,
+}
+
+impl std::fmt::Display for PushError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PushError::UnknownStream => write!(f, "No push source registered for stream"),
+            PushError::Backlogged => write!(f, "Stream is backlogged, retry later"),
+        }
+    }
+}
+
+struct PushChannel {
+    sender: mpsc::Sender<(Mat, u64)>,
+    next_id: Arc<AtomicU64>,
+}
+
+// Push sources register their sender here so ingestion endpoints can find
+// them by stream id
+#[derive(Default)]
+pub struct PushRegistry {
+    channels: RwLock<HashMap<String, PushChannel>>,
+    stats: Option<Arc<StreamStats>>,
+}
+
+impl PushRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Frames refused because a stream's buffer is full count as drops
+    pub fn with_stream_stats(mut self, stats: Arc<StreamStats>) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    pub async fn register(&self, stream_id: &str, buffer: usize) -> PushSource {
+        let (tx, rx) = mpsc::channel(buffer.max(1));
+        self.channels.write().await.insert(stream_id.to_string(), PushChannel {
+            sender: tx,
+            next_id: Arc::new(AtomicU64::new(1)),
+        });
+        PushSource { stream_id: stream_id.to_string(), frames: rx }
+    }
+
+    // Returns the frame id the pipeline will report results under
+    pub async fn push(&self, stream_id: &str, frame: Mat) -> std::result::Result<u64, PushError> {
+        let channels = self.channels.read().await;
+        let channel = channels.get(stream_id).ok_or(PushError::UnknownStream)?;
+        let frame_id = channel.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let error = match channel.sender.try_send((frame, frame_id)) {
+            Ok(()) => return Ok(frame_id),
+            Err(mpsc::error::TrySendError::Full(_)) => PushError::Backlogged,
+            Err(mpsc::error::TrySendError::Closed(_)) => PushError::UnknownStream,
+        };
+        if let (PushError::Backlogged, Some(stats)) = (&error, &self.stats) {
+            stats.record_drop(stream_id, "backlogged").await;
+        }
+        Err(error)
+    }
+
+    pub async fn is_registered(&self, stream_id: &str) -> bool {
+        self.channels.read().await.contains_key(stream_id)
+    }
+
+    pub async fn unregister(&self, stream_id: &str) {
+        self.channels.write().await.remove(stream_id);
+    }
+}
+
+pub async fn open_source(
+    stream_id: &str,
+    config: &SourceConfig,
+    transcode: &TranscodeConfig,
+    push: &Arc<PushRegistry>,
+) -> Result<Box<dyn FrameSource>> {
+    let source: Box<dyn FrameSource> = match config {
+        SourceConfig::File { path, looped } => Box::new(VideoSource::open(path, *looped, transcode).await?),
+        SourceConfig::Rtsp { url } => Box::new(VideoSource::open(url, false, transcode).await?),
+        SourceConfig::Camera { index } => Box::new(CameraSource::open(*index)?),
+        SourceConfig::ImageDirectory { path, fps, looped } => {
+            Box::new(ImageDirectorySource::open(path, *fps, *looped)?)
+        }
+        SourceConfig::Synthetic { width, height, frames, fps } => {
+            Box::new(SyntheticSource::new(*width, *height, *frames, *fps))
+        }
+        SourceConfig::Push { buffer } => Box::new(push.register(stream_id, *buffer).await),
+    };
+    Ok(source)
+}
+
+enum VideoBackend {
+    OpenCv(videoio::VideoCapture),
+    Transcoded(TranscodedSource),
+}
+
+pub struct VideoSource {
+    uri: String,
+    looped: bool,
+    transcode: TranscodeConfig,
+    backend: VideoBackend,
+}
+
+impl VideoSource {
+    pub async fn open(uri: &str, looped: bool, transcode: &TranscodeConfig) -> Result<Self> {
+        Ok(Self {
+            uri: uri.to_string(),
+            looped,
+            transcode: transcode.clone(),
+            backend: Self::open_backend(uri, transcode).await?,
+        })
+    }
+
+    // Opening an RTSP URL waits on the camera, so it stays off the runtime
+    async fn open_backend(uri: &str, transcode: &TranscodeConfig) -> Result<VideoBackend> {
+        let path = uri.to_string();
+        let opened = tokio::task::spawn_blocking(move || {
+            videoio::VideoCapture::from_file(&path, videoio::CAP_ANY)
+                .ok()
+                .filter(|cap| cap.is_opened().unwrap_or(false))
+        }).await.context("OpenCV open task panicked")?;
+        if let Some(cap) = opened {
+            return Ok(VideoBackend::OpenCv(cap));
+        }
+
+        if !transcode.enabled {
+            return Err(anyhow::anyhow!("Failed to open video capture"));
+        }
+
+        let transcoded = TranscodedSource::open(uri, transcode)
+            .await
+            .context("Failed to open video capture, including via ffmpeg")?;
+        let info = transcoded.info();
+        log::warn!(
+            "OpenCV cannot read {} ({} in {}), transcoding via ffmpeg; expect higher CPU usage",
+            uri, info.codec, info.container
+        );
+        Ok(VideoBackend::Transcoded(transcoded))
+    }
+
+    async fn read(&mut self) -> Result<Option<Mat>> {
+        match &mut self.backend {
+            VideoBackend::OpenCv(cap) => {
+                let mut frame = Mat::default();
+                if cap.read(&mut frame)? {
+                    Ok(Some(frame))
+                } else {
+                    Ok(None)
+                }
+            }
+            VideoBackend::Transcoded(transcoded) => transcoded.read().await,
+        }
+    }
+}
+
+#[async_trait]
+impl FrameSource for VideoSource {
+    fn name(&self) -> String {
+        match self.backend {
+            VideoBackend::OpenCv(_) => format!("video:{}", self.uri),
+            VideoBackend::Transcoded(_) => format!("video+ffmpeg:{}", self.uri),
+        }
+    }
+
+    async fn next_frame(&mut self) -> Result<Option<Mat>> {
+        match self.read().await? {
+            Some(frame) => Ok(Some(frame)),
+            None if self.looped => {
+                self.backend = Self::open_backend(&self.uri, &self.transcode).await?;
+                self.read().await
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+pub struct CameraSource {
+    index: i32,
+    capture: videoio::VideoCapture,
+}
+
+impl CameraSource {
+    pub fn open(index: i32) -> Result<Self> {
+        let capture = videoio::VideoCapture::new(index, videoio::CAP_ANY)?;
+        if !capture.is_opened()? {
+            return Err(anyhow::anyhow!("Failed to open camera {}", index));
+        }
+        Ok(Self { index, capture })
+    }
+}
+
+#[async_trait]
+impl FrameSource for CameraSource {
+    fn name(&self) -> String {
+        format!("camera:{}", self.index)
+    }
+
+    async fn next_frame(&mut self) -> Result<Option<Mat>> {
+        let mut frame = Mat::default();
+        if self.capture.read(&mut frame)? && !frame.empty() {
+            Ok(Some(frame))
+        } else {
+            Err(anyhow::anyhow!("Camera {} returned no frame", self.index))
+        }
+    }
+}
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "bmp", "tif", "tiff", "webp"];
+
+pub struct ImageDirectorySource {
+    path: String,
+    files: Vec<PathBuf>,
+    position: usize,
+    looped: bool,
+    interval: Option<tokio::time::Interval>,
+}
+
+impl ImageDirectorySource {
+    pub fn open(path: &str, fps: f64, looped: bool) -> Result<Self> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(path)
+            .with_context(|| format!("Failed to read image directory {}", path))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+            })
+            .collect();
+        files.sort();
+
+        if files.is_empty() {
+            return Err(anyhow::anyhow!("No images found in {}", path));
+        }
+
+        Ok(Self {
+            path: path.to_string(),
+            files,
+            position: 0,
+            looped,
+            interval: pacing(fps),
+        })
+    }
+}
+
+#[async_trait]
+impl FrameSource for ImageDirectorySource {
+    fn name(&self) -> String {
+        format!("images:{}", self.path)
+    }
+
+    async fn next_frame(&mut self) -> Result<Option<Mat>> {
+        if self.position >= self.files.len() {
+            if !self.looped {
+                return Ok(None);
+            }
+            self.position = 0;
+        }
+        if let Some(interval) = &mut self.interval {
+            interval.tick().await;
+        }
+
+        let file = &self.files[self.position];
+        self.position += 1;
+        let frame = imgcodecs::imread(&file.to_string_lossy(), imgcodecs::IMREAD_COLOR)?;
+        if frame.empty() {
+            return Err(anyhow::anyhow!("Failed to decode image {}", file.display()));
+        }
+        Ok(Some(frame))
+    }
+}
+
+// Deterministic moving box on a gray background, for tests and benchmarks
+pub struct SyntheticSource {
+    width: u32,
+    height: u32,
+    limit: Option<u64>,
+    produced: u64,
+    interval: Option<tokio::time::Interval>,
+}
+
+impl SyntheticSource {
+    pub fn new(width: u32, height: u32, limit: Option<u64>, fps: f64) -> Self {
+        Self {
+            width,
+            height,
+            limit,
+            produced: 0,
+            interval: pacing(fps),
+        }
+    }
+}
+
+#[async_trait]
+impl FrameSource for SyntheticSource {
+    fn name(&self) -> String {
+        format!("synthetic:{}x{}", self.width, self.height)
+    }
+
+    async fn next_frame(&mut self) -> Result<Option<Mat>> {
+        if self.limit.is_some_and(|limit| self.produced >= limit) {
+            return Ok(None);
+        }
+        if let Some(interval) = &mut self.interval {
+            interval.tick().await;
+        }
+
+        let mut frame = Mat::new_rows_cols_with_default(
+            self.height as i32, self.width as i32, CV_8UC3, Scalar::all(96.0),
+        )?;
+        let size = (self.width.min(self.height) / 8).max(1) as i32;
+        let travel = (self.width as i32 - size).max(1);
+        let x = (self.produced as i32 * 4) % travel;
+        let y = (self.height as i32 - size) / 2;
+        imgproc::rectangle(
+            &mut frame,
+            Rect::new(x, y, size, size),
+            Scalar::new(0.0, 0.0, 255.0, 0.0),
+            imgproc::FILLED,
+            imgproc::LINE_8,
+            0,
+        )?;
+        imgproc::put_text(
+            &mut frame,
+            &self.produced.to_string(),
+            Point::new(8, 24),
+            imgproc::FONT_HERSHEY_SIMPLEX,
+            0.6,
+            Scalar::all(255.0),
+            1,
+            imgproc::LINE_8,
+            false,
+        )?;
+
+        self.produced += 1;
+        Ok(Some(frame))
+    }
+}
+
+pub struct PushSource {
+    stream_id: String,
+    frames: mpsc::Receiver<(Mat, u64)>,
+}
+
+#[async_trait]
+impl FrameSource for PushSource {
+    fn name(&self) -> String {
+        format!("push:{}", self.stream_id)
+    }
+
+    async fn next_frame(&mut self) -> Result<Option<Mat>> {
+        Ok(self.frames.recv().await.map(|(frame, _)| frame))
+    }
+
+    async fn next_tagged(&mut self) -> Result<Option<(Mat, Option<u64>)>> {
+        Ok(self.frames.recv().await.map(|(frame, id)| (frame, Some(id))))
+    }
+}
+
+fn pacing(fps: f64) -> Option<tokio::time::Interval> {
+    if fps > 0.0 {
+        Some(tokio::time::interval(tokio::time::Duration::from_secs_f64(1.0 / fps)))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,360 @@
+#![cfg(feature = "vision")]
+
+use anyhow::{Result, Context};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use opencv::{
+    prelude::*,
+    core::{Mat, Rect, Vector},
+    imgcodecs,
+};
+
+use crate::vision::detector::{BBox, Detection};
+use crate::vision::geometry;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrackerConfig {
+    pub enabled: bool,
+    pub min_confidence: f32,
+    // Overlap needed to associate a detection with an existing track
+    pub match_iou: f32,
+    // Two live tracks of the same class overlapping this much are one object
+    pub merge_iou: f32,
+    // Frames a track may go unmatched before it dies
+    pub max_age: u32,
+    // Matches needed before a track is announced
+    pub min_hits: u32,
+    // Emit an update every N matched frames rather than on every frame
+    pub update_interval: u32,
+    pub max_path_points: usize,
+    pub snapshots: bool,
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_confidence: 0.3,
+            match_iou: 0.3,
+            merge_iou: 0.7,
+            max_age: 30,
+            min_hits: 3,
+            update_interval: 30,
+            max_path_points: 16,
+            snapshots: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PathPoint {
+    pub x: f32,
+    pub y: f32,
+    pub frame_id: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PathSummary {
+    pub start: PathPoint,
+    pub end: PathPoint,
+    pub distance: f32,
+    // Evenly thinned so long-lived tracks stay small on the wire
+    pub waypoints: Vec<PathPoint>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackSnapshot {
+    pub frame_id: u64,
+    pub bbox: BBox,
+    pub confidence: f32,
+    // Base64 JPEG crop of the best-confidence sighting
+    pub image: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackSummary {
+    pub track_id: u64,
+    pub source: String,
+    pub class_name: String,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub duration_secs: f64,
+    pub hits: u32,
+    pub bbox: BBox,
+    pub path: PathSummary,
+    pub snapshot: Option<TrackSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TrackEvent {
+    Birth { track: TrackSummary },
+    Update { track: TrackSummary },
+    Merge { into: TrackSummary, merged_id: u64 },
+    Death { track: TrackSummary },
+}
+
+struct Track {
+    id: u64,
+    class_name: String,
+    bbox: BBox,
+    first_seen: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+    hits: u32,
+    missed: u32,
+    confirmed: bool,
+    distance: f32,
+    path: Vec<PathPoint>,
+    best: Option<TrackSnapshot>,
+}
+
+impl Track {
+    fn observe(&mut self, detection: &Detection, max_path_points: usize) {
+        let (x, y) = geometry::center(&detection.bbox);
+        if let Some(last) = self.path.last() {
+            self.distance += geometry::distance((last.x, last.y), (x, y));
+        }
+        self.path.push(PathPoint { x, y, frame_id: detection.frame_id });
+        // Keep every other point once full; the path stays representative
+        // of the whole lifetime at a fixed size
+        if self.path.len() > max_path_points * 2 {
+            let last = self.path.pop();
+            self.path = self.path.iter().step_by(2).copied().collect();
+            self.path.extend(last);
+        }
+
+        self.bbox = detection.bbox.clone();
+        self.last_seen = detection.timestamp;
+        self.hits += 1;
+        self.missed = 0;
+    }
+
+    fn summary(&self, source: &str, max_path_points: usize) -> TrackSummary {
+        let start = self.path.first().copied().unwrap_or(PathPoint { x: 0.0, y: 0.0, frame_id: 0 });
+        let end = self.path.last().copied().unwrap_or(start);
+        let step = (self.path.len() / max_path_points.max(1)).max(1);
+
+        TrackSummary {
+            track_id: self.id,
+            source: source.to_string(),
+            class_name: self.class_name.clone(),
+            first_seen: self.first_seen,
+            last_seen: self.last_seen,
+            duration_secs: (self.last_seen - self.first_seen).num_milliseconds() as f64 / 1000.0,
+            hits: self.hits,
+            bbox: self.bbox.clone(),
+            path: PathSummary {
+                start,
+                end,
+                distance: self.distance,
+                waypoints: self.path.iter().step_by(step).copied().collect(),
+            },
+            snapshot: self.best.clone(),
+        }
+    }
+}
+
+// IoU tracker for one source. Detections are associated greedily by
+// overlap within a class; lifecycle transitions come back as events.
+pub struct Tracker {
+    config: TrackerConfig,
+    source: String,
+    tracks: Vec<Track>,
+    next_id: u64,
+    last_frame_id: Option<u64>,
+}
+
+impl Tracker {
+    pub fn new(source: &str, config: TrackerConfig) -> Self {
+        Self {
+            config,
+            source: source.to_string(),
+            tracks: Vec::new(),
+            next_id: 1,
+            last_frame_id: None,
+        }
+    }
+
+    pub fn active_tracks(&self) -> Vec<TrackSummary> {
+        self.tracks.iter()
+            .filter(|t| t.confirmed)
+            .map(|t| t.summary(&self.source, self.config.max_path_points))
+            .collect()
+    }
+
+    pub fn update(&mut self, frame_id: u64, image: Option<&Mat>, detections: &[Detection]) -> Vec<TrackEvent> {
+        // Workers can finish frames out of order; a stale frame would
+        // resurrect positions the tracks have already moved past
+        if self.last_frame_id.is_some_and(|last| frame_id <= last) {
+            return Vec::new();
+        }
+        self.last_frame_id = Some(frame_id);
+
+        let mut events = Vec::new();
+        let mut matched = vec![false; self.tracks.len()];
+
+        let mut candidates: Vec<&Detection> = detections.iter()
+            .filter(|d| d.confidence >= self.config.min_confidence)
+            .collect();
+        candidates.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(std::cmp::Ordering::Equal));
+
+        for detection in candidates {
+            let best = self.tracks.iter()
+                .enumerate()
+                .filter(|(i, t)| !matched[*i] && t.class_name == detection.class_name)
+                .map(|(i, t)| (i, t.bbox.iou(&detection.bbox)))
+                .filter(|(_, iou)| *iou >= self.config.match_iou)
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+
+            let index = match best {
+                Some((i, _)) => {
+                    matched[i] = true;
+                    self.tracks[i].observe(detection, self.config.max_path_points);
+                    i
+                }
+                None => {
+                    let mut track = Track {
+                        id: self.next_id,
+                        class_name: detection.class_name.clone(),
+                        bbox: detection.bbox.clone(),
+                        first_seen: detection.timestamp,
+                        last_seen: detection.timestamp,
+                        hits: 0,
+                        missed: 0,
+                        confirmed: false,
+                        distance: 0.0,
+                        path: Vec::new(),
+                        best: None,
+                    };
+                    self.next_id += 1;
+                    track.observe(detection, self.config.max_path_points);
+                    self.tracks.push(track);
+                    matched.push(true);
+                    self.tracks.len() - 1
+                }
+            };
+
+            let is_best = self.tracks[index].best.as_ref()
+                .map_or(true, |best| detection.confidence > best.confidence);
+            if is_best {
+                self.tracks[index].best = Some(TrackSnapshot {
+                    frame_id,
+                    bbox: detection.bbox.clone(),
+                    confidence: detection.confidence,
+                    image: if self.config.snapshots {
+                        image.and_then(|image| encode_crop(image, &detection.bbox).ok())
+                    } else {
+                        None
+                    },
+                });
+            }
+
+            let track = &mut self.tracks[index];
+            if !track.confirmed && track.hits >= self.config.min_hits {
+                track.confirmed = true;
+                events.push(TrackEvent::Birth { track: track.summary(&self.source, self.config.max_path_points) });
+            } else if track.confirmed
+                && self.config.update_interval > 0
+                && track.hits % self.config.update_interval == 0
+            {
+                events.push(TrackEvent::Update { track: track.summary(&self.source, self.config.max_path_points) });
+            }
+        }
+
+        for (i, track) in self.tracks.iter_mut().enumerate() {
+            if !matched[i] {
+                track.missed += 1;
+            }
+        }
+
+        events.extend(self.merge_overlapping());
+
+        let max_age = self.config.max_age;
+        let (dead, alive): (Vec<Track>, Vec<Track>) = self.tracks.drain(..)
+            .partition(|t| t.missed > max_age);
+        self.tracks = alive;
+        for track in dead.into_iter().filter(|t| t.confirmed) {
+            events.push(TrackEvent::Death { track: track.summary(&self.source, self.config.max_path_points) });
+        }
+
+        events
+    }
+
+    // Ends every live track, e.g. when the stream stops
+    pub fn flush(&mut self) -> Vec<TrackEvent> {
+        self.tracks.drain(..)
+            .filter(|t| t.confirmed)
+            .map(|t| TrackEvent::Death { track: t.summary(&self.source, self.config.max_path_points) })
+            .collect()
+    }
+
+    // The older track survives; it keeps the better snapshot and the
+    // earlier birth time
+    fn merge_overlapping(&mut self) -> Vec<TrackEvent> {
+        let mut events = Vec::new();
+        let mut i = 0;
+        while i < self.tracks.len() {
+            let mut j = i + 1;
+            while j < self.tracks.len() {
+                let same_object = self.tracks[i].class_name == self.tracks[j].class_name
+                    && self.tracks[i].bbox.iou(&self.tracks[j].bbox) >= self.config.merge_iou;
+                if !same_object {
+                    j += 1;
+                    continue;
+                }
+
+                let (keep, drop) = if self.tracks[i].id < self.tracks[j].id { (i, j) } else { (j, i) };
+                let merged = self.tracks.remove(drop);
+                let survivor = &mut self.tracks[if drop < keep { keep - 1 } else { keep }];
+                survivor.first_seen = survivor.first_seen.min(merged.first_seen);
+                survivor.hits += merged.hits;
+                let better = match (&survivor.best, &merged.best) {
+                    (Some(a), Some(b)) => b.confidence > a.confidence,
+                    (None, Some(_)) => true,
+                    _ => false,
+                };
+                if better {
+                    survivor.best = merged.best.clone();
+                }
+                if merged.confirmed || survivor.confirmed {
+                    // A tentative survivor inherits the confirmation, so
+                    // subscribers hear of its birth before the merge
+                    if !survivor.confirmed {
+                        survivor.confirmed = true;
+                        events.push(TrackEvent::Birth { track: survivor.summary(&self.source, self.config.max_path_points) });
+                    }
+                    events.push(TrackEvent::Merge {
+                        into: survivor.summary(&self.source, self.config.max_path_points),
+                        merged_id: merged.id,
+                    });
+                }
+                // Restart the scan: indices shifted under us
+                i = 0;
+                j = 1;
+            }
+            i += 1;
+        }
+        events
+    }
+}
+
+pub fn encode_crop(image: &Mat, bbox: &BBox) -> Result<String> {
+    let region = geometry::clip(bbox, image.cols() as f32, image.rows() as f32)
+        .ok_or_else(|| anyhow::anyhow!("Snapshot region is outside the frame"))?;
+    let x = region.x as i32;
+    let y = region.y as i32;
+    let width = (region.width as i32).min(image.cols() - x);
+    let height = (region.height as i32).min(image.rows() - y);
+    if width <= 0 || height <= 0 {
+        return Err(anyhow::anyhow!("Snapshot region is outside the frame"));
+    }
+
+    let crop = Mat::roi(image, Rect::new(x, y, width, height))
+        .context("Failed to crop snapshot")?;
+    let mut buffer = Vector::<u8>::new();
+    imgcodecs::imencode(".jpg", &crop, &mut buffer, &Vector::new())
+        .context("Failed to encode snapshot")?;
+    Ok(BASE64.encode(buffer.as_slice()))
+}
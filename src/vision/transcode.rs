@@ -0,0 +1,205 @@
+#![cfg(feature = "vision")]
+
+use std::thread;
+use anyhow::{Result, Context};
+use ffmpeg_next as ffmpeg;
+use opencv::{core::{Mat, Scalar, CV_8UC3}, prelude::*};
+use serde::{Serialize, Deserialize};
+use tokio::sync::{mpsc, oneshot};
+
+// Unreadable packets in a row before the source counts as broken; a
+// dropped connection fails every read and would otherwise never end
+const MAX_PACKET_ERRORS: u32 = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscodeConfig {
+    // Fall back to ffmpeg when OpenCV can't open a source
+    pub enabled: bool,
+    // Frames decoded ahead of the pipeline
+    pub buffer_frames: usize,
+    // Optional downscale applied during conversion, (width, height)
+    #[serde(default)]
+    pub output_size: Option<(u32, u32)>,
+}
+
+impl Default for TranscodeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            buffer_frames: 8,
+            output_size: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceInfo {
+    pub codec: String,
+    pub container: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Decodes on a dedicated thread since ffmpeg calls block and its contexts
+// aren't safe to move between runtime workers
+pub struct TranscodedSource {
+    info: SourceInfo,
+    frames: mpsc::Receiver<Result<Mat>>,
+}
+
+impl TranscodedSource {
+    // Opening probes the source, which for a network stream can take as
+    // long as its connect timeout; that happens on the decode thread
+    pub async fn open(source: &str, config: &TranscodeConfig) -> Result<Self> {
+        ffmpeg::init().context("Failed to initialize ffmpeg")?;
+
+        let (info_tx, info_rx) = oneshot::channel();
+        let (tx, rx) = mpsc::channel(config.buffer_frames.max(1));
+        let source = source.to_string();
+        let output_size = config.output_size;
+
+        thread::Builder::new()
+            .name("vae-transcode".to_string())
+            .spawn(move || {
+                let mut decoder = match FfmpegDecoder::open(&source, output_size) {
+                    Ok(decoder) => {
+                        let _ = info_tx.send(Ok(decoder.info.clone()));
+                        decoder
+                    }
+                    Err(e) => {
+                        let _ = info_tx.send(Err(e));
+                        return;
+                    }
+                };
+
+                loop {
+                    match decoder.next_frame() {
+                        Ok(Some(frame)) => {
+                            if tx.blocking_send(Ok(frame)).is_err() {
+                                break;
+                            }
+                        }
+                        Ok(None) => break,
+                        Err(e) => {
+                            let _ = tx.blocking_send(Err(e));
+                            break;
+                        }
+                    }
+                }
+            })
+            .context("Failed to spawn transcode thread")?;
+
+        let info = info_rx.await
+            .context("Transcode thread exited before opening source")??;
+
+        Ok(Self { info, frames: rx })
+    }
+
+    pub fn info(&self) -> &SourceInfo {
+        &self.info
+    }
+
+    pub async fn read(&mut self) -> Result<Option<Mat>> {
+        self.frames.recv().await.transpose()
+    }
+}
+
+struct FfmpegDecoder {
+    input: ffmpeg::format::context::Input,
+    decoder: ffmpeg::decoder::Video,
+    scaler: ffmpeg::software::scaling::Context,
+    stream_index: usize,
+    info: SourceInfo,
+    flushed: bool,
+    packet_errors: u32,
+}
+
+impl FfmpegDecoder {
+    fn open(source: &str, output_size: Option<(u32, u32)>) -> Result<Self> {
+        let input = ffmpeg::format::input(&source)
+            .with_context(|| format!("Failed to open source with ffmpeg: {}", source))?;
+        let stream = input.streams()
+            .best(ffmpeg::media::Type::Video)
+            .ok_or_else(|| anyhow::anyhow!("No video stream in source: {}", source))?;
+        let stream_index = stream.index();
+
+        let context = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?;
+        let decoder = context.decoder().video()
+            .context("Failed to create ffmpeg video decoder")?;
+
+        let (width, height) = output_size.unwrap_or((decoder.width(), decoder.height()));
+        let scaler = ffmpeg::software::scaling::Context::get(
+            decoder.format(),
+            decoder.width(),
+            decoder.height(),
+            ffmpeg::format::Pixel::BGR24,
+            width,
+            height,
+            ffmpeg::software::scaling::Flags::BILINEAR,
+        )?;
+
+        let info = SourceInfo {
+            codec: decoder.codec().map(|c| c.name().to_string()).unwrap_or_default(),
+            container: input.format().name().to_string(),
+            width,
+            height,
+        };
+
+        Ok(Self { input, decoder, scaler, stream_index, info, flushed: false, packet_errors: 0 })
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Mat>> {
+        let mut decoded = ffmpeg::util::frame::video::Video::empty();
+
+        loop {
+            if self.decoder.receive_frame(&mut decoded).is_ok() {
+                return self.convert(&decoded).map(Some);
+            }
+            if self.flushed {
+                return Ok(None);
+            }
+
+            let mut packet = ffmpeg::Packet::empty();
+            match packet.read(&mut self.input) {
+                Ok(()) => {
+                    self.packet_errors = 0;
+                    if packet.stream() == self.stream_index {
+                        self.decoder.send_packet(&packet)?;
+                    }
+                }
+                Err(ffmpeg::Error::Eof) => {
+                    self.decoder.send_eof()?;
+                    self.flushed = true;
+                }
+                // Corrupt packets are common on camera feeds; skip them
+                Err(e) if self.packet_errors < MAX_PACKET_ERRORS => {
+                    self.packet_errors += 1;
+                    log::warn!("Skipping unreadable packet: {}", e);
+                }
+                Err(e) => {
+                    return Err(anyhow::anyhow!("Source failed {} reads in a row: {}", MAX_PACKET_ERRORS, e));
+                }
+            }
+        }
+    }
+
+    fn convert(&mut self, decoded: &ffmpeg::util::frame::video::Video) -> Result<Mat> {
+        let mut bgr = ffmpeg::util::frame::video::Video::empty();
+        self.scaler.run(decoded, &mut bgr)?;
+
+        let (width, height) = (bgr.width() as usize, bgr.height() as usize);
+        let stride = bgr.stride(0);
+        let src = bgr.data(0);
+
+        let mut mat = Mat::new_rows_cols_with_default(
+            height as i32, width as i32, CV_8UC3, Scalar::all(0.0),
+        )?;
+        let dst = mat.data_bytes_mut()?;
+        let row_bytes = width * 3;
+        for row in 0..height {
+            dst[row * row_bytes..(row + 1) * row_bytes]
+                .copy_from_slice(&src[row * stride..row * stride + row_bytes]);
+        }
+        Ok(mat)
+    }
+}
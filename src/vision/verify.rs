@@ -0,0 +1,414 @@
+#![cfg(feature = "vision")]
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Duration, Utc};
+
+use opencv::core::Mat;
+
+use crate::core::llm::structured::parse_reply;
+use crate::core::llm::multimodal::{openai_message, ImagePart, MultimodalMessage};
+use crate::utils::egress::{EgressClient, EgressConfig};
+use crate::vision::{
+    detector::{BBox, Detection},
+    tracker::encode_crop,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VerificationConfig {
+    pub enabled: bool,
+    // Detections with confidence in [min, max) are ambiguous and get verified
+    pub min_confidence: f32,
+    pub max_confidence: f32,
+    // Empty means every class is eligible
+    pub classes: Vec<String>,
+    pub budget_per_minute: u32,
+    // What to do with an ambiguous detection once the budget is spent
+    pub over_budget: OverBudgetPolicy,
+    // Context around the box helps the model tell what it's looking at
+    pub crop_padding: f32,
+    // A verdict carries over to a same-class box on the same source that
+    // overlaps it by at least reuse_iou, for reuse_secs, so an object that
+    // stays in view is checked once rather than on every frame
+    pub reuse_secs: u64,
+    pub reuse_iou: f32,
+}
+
+impl Default for VerificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_confidence: 0.3,
+            max_confidence: 0.6,
+            classes: Vec::new(),
+            budget_per_minute: 20,
+            over_budget: OverBudgetPolicy::Pass,
+            crop_padding: 0.2,
+            reuse_secs: 30,
+            reuse_iou: 0.5,
+        }
+    }
+}
+
+// Verification settings plus the model that does it, as they appear in
+// the engine config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifierSettings {
+    #[serde(flatten)]
+    pub verification: VerificationConfig,
+    pub api_base: String,
+    pub model: String,
+    // Falls back to OPENAI_API_KEY
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    #[serde(default)]
+    pub egress: EgressConfig,
+}
+
+fn default_timeout_ms() -> u64 {
+    10000
+}
+
+pub fn build_verifier(settings: &VerifierSettings) -> Result<Arc<DetectionVerifier>> {
+    let api_key = match &settings.api_key {
+        Some(key) => key.clone(),
+        None => std::env::var("OPENAI_API_KEY")
+            .context("Verification model needs api_key or OPENAI_API_KEY")?,
+    };
+    let client = EgressClient::new(&settings.egress, std::time::Duration::from_millis(settings.timeout_ms))?;
+    let model = OpenAiVisionVerifier::new(&settings.api_base, &api_key, &settings.model, client);
+    Ok(Arc::new(DetectionVerifier::new(settings.verification.clone(), Arc::new(model))))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverBudgetPolicy {
+    // Alert on the unverified detection, as if verification were off
+    Pass,
+    // Suppress it; only sensible where false positives cost more than misses
+    Drop,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Verdict {
+    pub confirmed: bool,
+    pub confidence: f32,
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum VerificationOutcome {
+    // Confident enough (or not eligible) to skip verification
+    NotRequired,
+    Confirmed { verdict: Verdict },
+    Rejected { verdict: Verdict },
+    OverBudget,
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifiedDetection {
+    pub detection: Detection,
+    pub outcome: VerificationOutcome,
+}
+
+#[async_trait]
+pub trait VisionVerifier: Send + Sync {
+    // `image` is a base64 JPEG crop around the detection
+    async fn verify(&self, image: &str, class_name: &str, context: &str) -> Result<Verdict>;
+}
+
+pub fn parse_verdict(text: &str) -> Result<Verdict> {
+    let mut verdict: Verdict = parse_reply(text, "verifier verdict")?;
+    verdict.confidence = verdict.confidence.clamp(0.0, 1.0);
+    Ok(verdict)
+}
+
+fn verification_prompt(class_name: &str, context: &str) -> String {
+    format!(
+        "An object detector flagged this image crop as a \"{}\" with low confidence{}. \
+         Does the crop actually show a {}? Reply with JSON only: \
+         {{\"confirmed\": true|false, \"confidence\": 0.0-1.0, \"reason\": \"<one sentence>\"}}",
+        class_name,
+        if context.is_empty() { String::new() } else { format!(" ({})", context) },
+        class_name,
+    )
+}
+
+// Calls an OpenAI-compatible chat endpoint with the crop as an image part.
+// Goes through the EgressClient rather than a shared provider so the
+// destination allowlist applies.
+pub struct OpenAiVisionVerifier {
+    api_base: String,
+    api_key: String,
+    model: String,
+    client: EgressClient,
+}
+
+impl OpenAiVisionVerifier {
+    pub fn new(api_base: &str, api_key: &str, model: &str, client: EgressClient) -> Self {
+        Self {
+            api_base: api_base.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+            client,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    content: Option<String>,
+}
+
+#[async_trait]
+impl VisionVerifier for OpenAiVisionVerifier {
+    async fn verify(&self, image: &str, class_name: &str, context: &str) -> Result<Verdict> {
+        let body = serde_json::json!({
+            "model": self.model,
+            "temperature": 0.0,
+            "max_tokens": 150,
+            "messages": [openai_message(
+                &MultimodalMessage::new("user", &verification_prompt(class_name, context))
+                    .with_image(ImagePart::base64("image/jpeg", image)),
+            )],
+        });
+
+        let response: ChatResponse = self.client.post(&format!("{}/chat/completions", self.api_base))?
+            .bearer_auth(&self.api_key)
+            .json(&body)
+            .send()
+            .await
+            .context("Failed to call verification model")?
+            .error_for_status()
+            .context("Verification model rejected request")?
+            .json()
+            .await
+            .context("Failed to parse verification response")?;
+
+        let content = response.choices.into_iter()
+            .next()
+            .and_then(|c| c.message.content)
+            .ok_or_else(|| anyhow::anyhow!("Verification model returned no content"))?;
+        parse_verdict(&content)
+    }
+}
+
+// Sliding one-minute window; cheaper to reason about than a token bucket
+// when the budget is "N calls per minute"
+pub struct VerificationBudget {
+    per_minute: u32,
+    calls: VecDeque<DateTime<Utc>>,
+}
+
+impl VerificationBudget {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            calls: VecDeque::new(),
+        }
+    }
+
+    pub fn try_acquire(&mut self, now: DateTime<Utc>) -> bool {
+        while self.calls.front().is_some_and(|t| now - *t >= Duration::minutes(1)) {
+            self.calls.pop_front();
+        }
+        if self.calls.len() as u32 >= self.per_minute {
+            return false;
+        }
+        self.calls.push_back(now);
+        true
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VerificationStats {
+    pub verified: u64,
+    pub confirmed: u64,
+    pub rejected: u64,
+    pub over_budget: u64,
+    pub failed: u64,
+    // Answered from an earlier verdict without calling the model
+    pub reused: u64,
+}
+
+struct RecentVerdict {
+    class_name: String,
+    bbox: BBox,
+    verdict: Verdict,
+    at: DateTime<Utc>,
+}
+
+pub struct DetectionVerifier {
+    config: VerificationConfig,
+    verifier: Arc<dyn VisionVerifier>,
+    budget: Mutex<VerificationBudget>,
+    stats: Mutex<VerificationStats>,
+    // Per source
+    recent: Mutex<HashMap<String, Vec<RecentVerdict>>>,
+}
+
+impl DetectionVerifier {
+    pub fn new(config: VerificationConfig, verifier: Arc<dyn VisionVerifier>) -> Self {
+        Self {
+            budget: Mutex::new(VerificationBudget::new(config.budget_per_minute)),
+            config,
+            verifier,
+            stats: Mutex::new(VerificationStats::default()),
+            recent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn stats(&self) -> VerificationStats {
+        self.stats.lock().await.clone()
+    }
+
+    // Rule-triggered detections are verified regardless of confidence:
+    // those are the ones about to page someone
+    pub fn needs_verification(&self, detection: &Detection, rule_triggered: bool) -> bool {
+        if !self.config.enabled {
+            return false;
+        }
+        if !self.config.classes.is_empty() && !self.config.classes.contains(&detection.class_name) {
+            return false;
+        }
+        rule_triggered
+            || (detection.confidence >= self.config.min_confidence
+                && detection.confidence < self.config.max_confidence)
+    }
+
+    pub async fn verify(
+        &self,
+        image: &Mat,
+        source: &str,
+        frame_id: u64,
+        detections: &[Detection],
+        rule_triggered: bool,
+    ) -> Vec<VerifiedDetection> {
+        let context = format!("camera {}", source);
+        let mut results = Vec::with_capacity(detections.len());
+
+        for detection in detections {
+            let outcome = if !self.needs_verification(detection, rule_triggered) {
+                VerificationOutcome::NotRequired
+            } else if let Some(verdict) = self.reuse(source, detection).await {
+                self.stats.lock().await.reused += 1;
+                verdict_outcome(verdict)
+            } else if !self.budget.lock().await.try_acquire(Utc::now()) {
+                self.stats.lock().await.over_budget += 1;
+                VerificationOutcome::OverBudget
+            } else {
+                let outcome = self.verify_one(image, frame_id, detection, &context).await;
+                if let VerificationOutcome::Confirmed { verdict } | VerificationOutcome::Rejected { verdict } = &outcome {
+                    self.remember(source, detection, verdict.clone()).await;
+                }
+                outcome
+            };
+            results.push(VerifiedDetection { detection: detection.clone(), outcome });
+        }
+
+        results
+    }
+
+    async fn reuse(&self, source: &str, detection: &Detection) -> Option<Verdict> {
+        let now = Utc::now();
+        let mut recent = self.recent.lock().await;
+        let verdicts = recent.get_mut(source)?;
+        verdicts.retain(|r| now - r.at < Duration::seconds(self.config.reuse_secs as i64));
+        verdicts.iter()
+            .filter(|r| r.class_name == detection.class_name)
+            .map(|r| (r.bbox.iou(&detection.bbox), r))
+            .filter(|(iou, _)| *iou >= self.config.reuse_iou)
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, r)| r.verdict.clone())
+    }
+
+    async fn remember(&self, source: &str, detection: &Detection, verdict: Verdict) {
+        if self.config.reuse_secs == 0 {
+            return;
+        }
+        self.recent.lock().await
+            .entry(source.to_string())
+            .or_default()
+            .push(RecentVerdict {
+                class_name: detection.class_name.clone(),
+                bbox: detection.bbox.clone(),
+                verdict,
+                at: Utc::now(),
+            });
+    }
+
+    async fn verify_one(&self, image: &Mat, frame_id: u64, detection: &Detection, context: &str) -> VerificationOutcome {
+        let pad_x = detection.bbox.width * self.config.crop_padding;
+        let pad_y = detection.bbox.height * self.config.crop_padding;
+        let padded = BBox {
+            x: (detection.bbox.x - pad_x).max(0.0),
+            y: (detection.bbox.y - pad_y).max(0.0),
+            width: detection.bbox.width + 2.0 * pad_x,
+            height: detection.bbox.height + 2.0 * pad_y,
+        };
+
+        let outcome = match encode_crop(image, &padded) {
+            Ok(crop) => match self.verifier.verify(&crop, &detection.class_name, context).await {
+                Ok(verdict) => verdict_outcome(verdict),
+                Err(e) => VerificationOutcome::Failed { error: e.to_string() },
+            },
+            Err(e) => VerificationOutcome::Failed { error: e.to_string() },
+        };
+
+        let mut stats = self.stats.lock().await;
+        stats.verified += 1;
+        match &outcome {
+            VerificationOutcome::Confirmed { .. } => stats.confirmed += 1,
+            VerificationOutcome::Rejected { .. } => stats.rejected += 1,
+            VerificationOutcome::Failed { error } => {
+                stats.failed += 1;
+                log::warn!("Detection verification failed on frame {}: {}", frame_id, error);
+            }
+            _ => {}
+        }
+        outcome
+    }
+
+    // Which detections may go on to raise alerts. A failed verification
+    // fails open: the detector's own judgement stands.
+    pub fn alertable(&self, results: &[VerifiedDetection]) -> Vec<Detection> {
+        results.iter()
+            .filter(|r| match &r.outcome {
+                VerificationOutcome::NotRequired
+                | VerificationOutcome::Confirmed { .. }
+                | VerificationOutcome::Failed { .. } => true,
+                VerificationOutcome::Rejected { .. } => false,
+                VerificationOutcome::OverBudget => self.config.over_budget == OverBudgetPolicy::Pass,
+            })
+            .map(|r| r.detection.clone())
+            .collect()
+    }
+}
+
+fn verdict_outcome(verdict: Verdict) -> VerificationOutcome {
+    if verdict.confirmed {
+        VerificationOutcome::Confirmed { verdict }
+    } else {
+        VerificationOutcome::Rejected { verdict }
+    }
+}
@@ -0,0 +1,573 @@
+#![cfg(feature = "vision")]
+
+use std::sync::Arc;
+use tokio::sync::{broadcast, RwLock};
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::core::engine::EngineEvent;
+use crate::core::jobs::{Job, JobManager};
+use crate::core::profiles::StreamProfile;
+use crate::core::streams::StreamRegistry;
+use crate::vision::tracker::{TrackEvent, TrackSummary};
+use crate::vision::zones::{Zone, ZoneLine, ZonePoint};
+
+pub const ZONE_SUGGEST_JOB: &str = "zones.suggest";
+
+// Proposes zones from where tracks actually go, so a new camera doesn't
+// need someone to draw polygons before rules work. Finished tracks are
+// binned into a coarse grid over the image:
+//
+//   entrance  cells where many tracks start or end, with a counting line
+//             across the direction of travel
+//   pathway   cells many tracks pass through
+//   parking   cells where vehicles sit still for a long time
+//
+// Neighbouring qualifying cells are merged and each group becomes the
+// convex hull of its cells. Nothing is applied until an operator accepts
+// a suggestion.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ZoneSuggestConfig {
+    // How long an observation runs unless the request says otherwise
+    pub duration_secs: u64,
+    // Grid cell edge, in image pixels
+    pub cell_size: f32,
+    // Fewer finished tracks than this and no suggestions are made
+    pub min_tracks: u32,
+    // Tracks seen in fewer frames are mostly false positives
+    pub min_hits: u32,
+    // Only these classes are observed; empty observes everything
+    pub classes: Vec<String>,
+    // Share of tracks that must start or end in a cell for it to count
+    // towards an entrance
+    pub entrance_min_share: f32,
+    // Share of tracks that must pass through a cell for it to count towards
+    // a pathway, and the smallest pathway in cells
+    pub pathway_min_share: f32,
+    pub pathway_min_cells: usize,
+    pub parking_classes: Vec<String>,
+    // A vehicle that moves less than a cell over this long is parked
+    pub parking_min_dwell_secs: f64,
+    // Parked vehicles needed in a cell for it to count towards parking
+    pub parking_min_tracks: u32,
+    pub max_per_kind: usize,
+}
+
+impl Default for ZoneSuggestConfig {
+    fn default() -> Self {
+        Self {
+            duration_secs: 3600,
+            cell_size: 40.0,
+            min_tracks: 20,
+            min_hits: 5,
+            classes: Vec::new(),
+            entrance_min_share: 0.05,
+            pathway_min_share: 0.15,
+            pathway_min_cells: 3,
+            parking_classes: vec![
+                "car".to_string(), "truck".to_string(), "bus".to_string(), "motorcycle".to_string(),
+            ],
+            parking_min_dwell_secs: 300.0,
+            parking_min_tracks: 2,
+            max_per_kind: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionKind {
+    Entrance,
+    Pathway,
+    Parking,
+}
+
+impl SuggestionKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SuggestionKind::Entrance => "entrance",
+            SuggestionKind::Pathway => "pathway",
+            SuggestionKind::Parking => "parking",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneSuggestion {
+    pub id: String,
+    pub kind: SuggestionKind,
+    pub zone: Zone,
+    // Entrances only, when tracks there share a direction of travel
+    pub line: Option<ZoneLine>,
+    // Share of the observed tracks behind the suggestion
+    pub confidence: f32,
+    // Tracks behind the suggestion: endpoints for entrances, tracks through
+    // the busiest cell for pathways, parked vehicles for parking
+    pub support: u32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Cell {
+    endpoints: u32,
+    traffic: u32,
+    parked: u32,
+    // Sum of (cos 2a, sin 2a) over endpoint directions. Doubling the angle
+    // makes entering and leaving along the same axis add up instead of
+    // cancelling.
+    axis: (f32, f32),
+}
+
+pub struct TrafficObserver {
+    config: ZoneSuggestConfig,
+    cells: HashMap<(i32, i32), Cell>,
+    tracks: u32,
+    parked: u32,
+}
+
+impl TrafficObserver {
+    pub fn new(config: ZoneSuggestConfig) -> Self {
+        Self {
+            config,
+            cells: HashMap::new(),
+            tracks: 0,
+            parked: 0,
+        }
+    }
+
+    pub fn tracks(&self) -> u32 {
+        self.tracks
+    }
+
+    fn cell(&self, (x, y): (f32, f32)) -> (i32, i32) {
+        let size = self.config.cell_size.max(1.0);
+        ((x / size).floor() as i32, (y / size).floor() as i32)
+    }
+
+    // Feeds one finished track. Returns false when it was filtered out.
+    pub fn observe(&mut self, track: &TrackSummary) -> bool {
+        if !self.config.classes.is_empty() && !self.config.classes.contains(&track.class_name) {
+            return false;
+        }
+        if track.hits < self.config.min_hits {
+            return false;
+        }
+        self.tracks += 1;
+
+        // Paths follow box centres but zones test the ground point; the
+        // last box height is close enough for the whole track
+        let offset = track.bbox.height / 2.0;
+        let mut points: Vec<(f32, f32)> = std::iter::once(&track.path.start)
+            .chain(track.path.waypoints.iter())
+            .chain(std::iter::once(&track.path.end))
+            .map(|p| (p.x, p.y + offset))
+            .collect();
+        points.dedup();
+
+        if track.path.distance < self.config.cell_size {
+            if self.config.parking_classes.contains(&track.class_name)
+                && track.duration_secs >= self.config.parking_min_dwell_secs
+            {
+                let cell = self.cell(points[points.len() - 1]);
+                self.cells.entry(cell).or_default().parked += 1;
+                self.parked += 1;
+            }
+            // Anything else that barely moved says nothing about traffic
+            return true;
+        }
+
+        let mut visited = HashSet::new();
+        let step = (self.config.cell_size / 2.0).max(1.0);
+        for pair in points.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            let steps = ((x1 - x0).hypot(y1 - y0) / step).ceil().max(1.0) as usize;
+            for i in 0..=steps {
+                let t = i as f32 / steps as f32;
+                visited.insert(self.cell((x0 + (x1 - x0) * t, y0 + (y1 - y0) * t)));
+            }
+        }
+        for cell in visited {
+            self.cells.entry(cell).or_default().traffic += 1;
+        }
+
+        if points.len() >= 2 {
+            let last = points.len() - 1;
+            for (point, direction) in [
+                (points[0], (points[1].0 - points[0].0, points[1].1 - points[0].1)),
+                (points[last], (points[last].0 - points[last - 1].0, points[last].1 - points[last - 1].1)),
+            ] {
+                let angle = direction.1.atan2(direction.0);
+                let key = self.cell(point);
+                let cell = self.cells.entry(key).or_default();
+                cell.endpoints += 1;
+                cell.axis.0 += (2.0 * angle).cos();
+                cell.axis.1 += (2.0 * angle).sin();
+            }
+        }
+        true
+    }
+
+    // Empty until at least `min_tracks` tracks have been observed
+    pub fn suggest(&self) -> Vec<ZoneSuggestion> {
+        if self.tracks < self.config.min_tracks.max(1) {
+            return Vec::new();
+        }
+        let tracks = self.tracks as f32;
+        let mut suggestions = Vec::new();
+
+        let threshold = ((self.config.entrance_min_share * tracks).ceil() as u32).max(2);
+        let groups = self.groups(|c| c.endpoints >= threshold);
+        suggestions.extend(self.build(SuggestionKind::Entrance, groups, |cells| {
+            let support = cells.iter().map(|(_, c)| c.endpoints).sum::<u32>();
+            (support, support as f32 / tracks)
+        }));
+
+        let threshold = ((self.config.pathway_min_share * tracks).ceil() as u32).max(2);
+        let groups = self.groups(|c| c.traffic >= threshold)
+            .into_iter()
+            .filter(|g| g.len() >= self.config.pathway_min_cells.max(1))
+            .collect();
+        suggestions.extend(self.build(SuggestionKind::Pathway, groups, |cells| {
+            let support = cells.iter().map(|(_, c)| c.traffic).max().unwrap_or(0);
+            (support, support as f32 / tracks)
+        }));
+
+        let threshold = self.config.parking_min_tracks.max(1);
+        let groups = self.groups(|c| c.parked >= threshold);
+        let parked = self.parked.max(1) as f32;
+        suggestions.extend(self.build(SuggestionKind::Parking, groups, |cells| {
+            let support = cells.iter().map(|(_, c)| c.parked).sum::<u32>();
+            (support, support as f32 / parked)
+        }));
+
+        suggestions
+    }
+
+    // 4-connected groups of qualifying cells
+    fn groups(&self, qualifies: impl Fn(&Cell) -> bool) -> Vec<Vec<(i32, i32)>> {
+        let mut remaining: HashSet<(i32, i32)> = self.cells.iter()
+            .filter(|(_, c)| qualifies(c))
+            .map(|(k, _)| *k)
+            .collect();
+        let mut seeds: Vec<(i32, i32)> = remaining.iter().copied().collect();
+        // Keeps the output stable between runs over the same tracks
+        seeds.sort();
+
+        let mut groups = Vec::new();
+        for seed in seeds {
+            if !remaining.remove(&seed) {
+                continue;
+            }
+            let mut group = vec![seed];
+            let mut index = 0;
+            while index < group.len() {
+                let (x, y) = group[index];
+                for neighbour in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                    if remaining.remove(&neighbour) {
+                        group.push(neighbour);
+                    }
+                }
+                index += 1;
+            }
+            groups.push(group);
+        }
+        groups
+    }
+
+    fn build(
+        &self,
+        kind: SuggestionKind,
+        groups: Vec<Vec<(i32, i32)>>,
+        score: impl Fn(&[((i32, i32), &Cell)]) -> (u32, f32),
+    ) -> Vec<ZoneSuggestion> {
+        let mut scored: Vec<_> = groups.into_iter()
+            .map(|group| {
+                let cells: Vec<_> = group.iter().map(|k| (*k, &self.cells[k])).collect();
+                let (support, confidence) = score(&cells);
+                (cells, support, confidence)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.cmp(&a.1));
+        scored.truncate(self.config.max_per_kind);
+
+        scored.into_iter()
+            .enumerate()
+            .map(|(index, (cells, support, confidence))| {
+                let name = format!("{}-{}", kind.as_str(), index + 1);
+                let mut zone = Zone::new(&name, self.hull(&cells));
+                zone.labels.push(kind.as_str().to_string());
+                let line = match kind {
+                    SuggestionKind::Entrance => self.entrance_line(&name, &cells),
+                    _ => None,
+                };
+                ZoneSuggestion {
+                    id: Uuid::new_v4().to_string(),
+                    kind,
+                    zone,
+                    line,
+                    confidence: confidence.min(1.0),
+                    support,
+                    created_at: Utc::now(),
+                }
+            })
+            .collect()
+    }
+
+    fn corners(&self, (x, y): (i32, i32)) -> [(f32, f32); 4] {
+        let size = self.config.cell_size.max(1.0);
+        let (left, top) = (x as f32 * size, y as f32 * size);
+        [(left, top), (left + size, top), (left + size, top + size), (left, top + size)]
+    }
+
+    fn hull(&self, cells: &[((i32, i32), &Cell)]) -> Vec<ZonePoint> {
+        let points: Vec<(f32, f32)> = cells.iter()
+            .flat_map(|(k, _)| self.corners(*k))
+            .map(|(x, y)| (x.max(0.0), y.max(0.0)))
+            .collect();
+        convex_hull(points).into_iter().map(|(x, y)| ZonePoint { x, y }).collect()
+    }
+
+    // Across the dominant direction of travel, through the endpoint-weighted
+    // centre and spanning the group. None when directions are too mixed to
+    // say which way is across.
+    fn entrance_line(&self, name: &str, cells: &[((i32, i32), &Cell)]) -> Option<ZoneLine> {
+        let endpoints: u32 = cells.iter().map(|(_, c)| c.endpoints).sum();
+        let axis = cells.iter().fold((0.0f32, 0.0f32), |a, (_, c)| (a.0 + c.axis.0, a.1 + c.axis.1));
+        if endpoints == 0 || axis.0.hypot(axis.1) < 0.5 * endpoints as f32 {
+            return None;
+        }
+
+        let angle = axis.1.atan2(axis.0) / 2.0;
+        let across = (-angle.sin(), angle.cos());
+        let size = self.config.cell_size.max(1.0);
+        let centre = cells.iter().fold((0.0f32, 0.0f32), |a, ((x, y), c)| {
+            let weight = c.endpoints as f32 / endpoints as f32;
+            (a.0 + (*x as f32 + 0.5) * size * weight, a.1 + (*y as f32 + 0.5) * size * weight)
+        });
+
+        let (mut low, mut high) = (f32::MAX, f32::MIN);
+        for (x, y) in cells.iter().flat_map(|(k, _)| self.corners(*k)) {
+            let t = (x - centre.0) * across.0 + (y - centre.1) * across.1;
+            low = low.min(t);
+            high = high.max(t);
+        }
+        let point = |t: f32| ZonePoint {
+            x: (centre.0 + across.0 * t).max(0.0),
+            y: (centre.1 + across.1 * t).max(0.0),
+        };
+        let mut line = ZoneLine::new(&format!("{}-line", name), point(low), point(high));
+        line.labels.push(SuggestionKind::Entrance.as_str().to_string());
+        Some(line)
+    }
+}
+
+// Andrew's monotone chain, counter-clockwise without collinear points
+fn convex_hull(mut points: Vec<(f32, f32)>) -> Vec<(f32, f32)> {
+    points.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    let cross = |o: (f32, f32), a: (f32, f32), b: (f32, f32)| {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    };
+    let mut hull: Vec<(f32, f32)> = Vec::with_capacity(points.len() * 2);
+    for pass in [points.clone(), points.into_iter().rev().collect()] {
+        let base = hull.len();
+        for p in pass {
+            while hull.len() >= base + 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        // The last point of each pass starts the next one
+        hull.pop();
+    }
+    hull
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ObserveRequest {
+    pub duration_secs: Option<u64>,
+}
+
+// Runs observations as jobs and keeps their suggestions until an operator
+// accepts or rejects them. Suggestions live in memory; a restart means
+// observing again.
+pub struct ZoneSuggester {
+    config: ZoneSuggestConfig,
+    jobs: Arc<JobManager>,
+    registry: Arc<StreamRegistry>,
+    // Observations in progress, by stream
+    observers: Arc<RwLock<HashMap<String, TrafficObserver>>>,
+    suggestions: Arc<RwLock<HashMap<String, Vec<ZoneSuggestion>>>>,
+}
+
+impl ZoneSuggester {
+    pub fn new(config: ZoneSuggestConfig, jobs: Arc<JobManager>, registry: Arc<StreamRegistry>) -> Self {
+        Self {
+            config,
+            jobs,
+            registry,
+            observers: Arc::new(RwLock::new(HashMap::new())),
+            suggestions: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    // Builds the suggester and feeds it the engine's finished tracks
+    pub fn spawn(
+        config: ZoneSuggestConfig,
+        jobs: Arc<JobManager>,
+        registry: Arc<StreamRegistry>,
+        events: broadcast::Receiver<EngineEvent>,
+    ) -> Arc<Self> {
+        let suggester = Arc::new(Self::new(config, jobs, registry));
+        suggester.start_listening(events);
+        suggester
+    }
+
+    pub async fn record(&self, track: &TrackSummary) {
+        if let Some(observer) = self.observers.write().await.get_mut(&track.source) {
+            observer.observe(track);
+        }
+    }
+
+    // Feeds finished tracks from the engine to running observations
+    pub fn start_listening(self: &Arc<Self>, mut events: broadcast::Receiver<EngineEvent>) {
+        let suggester = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(EngineEvent::Track { event: TrackEvent::Death { track } }) => {
+                        suggester.record(&track).await;
+                    }
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::warn!("Zone suggestion missed {} engine events", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    pub async fn observe(self: &Arc<Self>, stream_id: &str, request: ObserveRequest) -> Result<Job> {
+        if self.registry.get(stream_id).await.is_none() {
+            return Err(anyhow::anyhow!("Stream not found: {}", stream_id));
+        }
+        {
+            let mut observers = self.observers.write().await;
+            if observers.contains_key(stream_id) {
+                return Err(anyhow::anyhow!("Stream {} is already being observed", stream_id));
+            }
+            observers.insert(stream_id.to_string(), TrafficObserver::new(self.config.clone()));
+        }
+
+        let duration = request.duration_secs.unwrap_or(self.config.duration_secs).max(1);
+        let stream_id = stream_id.to_string();
+        let suggester = self.clone();
+        let job = self.jobs.spawn(ZONE_SUGGEST_JOB, move |handle| async move {
+            handle.set_total(duration).await;
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
+            interval.tick().await;
+            for elapsed in 1..=duration {
+                interval.tick().await;
+                if handle.is_cancelled() {
+                    suggester.observers.write().await.remove(&stream_id);
+                    return Ok(());
+                }
+                handle.advance(1).await;
+                if elapsed % 10 == 0 {
+                    let tracks = suggester.observers.read().await.get(&stream_id).map_or(0, |o| o.tracks());
+                    handle.set_message(&format!("{} tracks observed", tracks)).await;
+                }
+            }
+
+            let observer = suggester.observers.write().await.remove(&stream_id)
+                .ok_or_else(|| anyhow::anyhow!("Observation of {} was lost", stream_id))?;
+            if observer.tracks() < suggester.config.min_tracks {
+                return Err(anyhow::anyhow!(
+                    "Only {} tracks observed on {}, at least {} are needed; observe for longer",
+                    observer.tracks(), stream_id, suggester.config.min_tracks,
+                ));
+            }
+            let suggestions = observer.suggest();
+            handle.set_message(&format!(
+                "{} suggestions from {} tracks", suggestions.len(), observer.tracks()
+            )).await;
+            // A new observation replaces whatever was still pending
+            suggester.suggestions.write().await.insert(stream_id, suggestions);
+            Ok(())
+        }).await;
+
+        Ok(job)
+    }
+
+    pub async fn list(&self, stream_id: &str) -> Vec<ZoneSuggestion> {
+        self.suggestions.read().await.get(stream_id).cloned().unwrap_or_default()
+    }
+
+    // Adds the suggestion's zone (and line) to the stream's profile, which
+    // every stream sharing the profile will pick up. The suggestion is taken
+    // out first so two accepts of it can't both apply, and put back if the
+    // profile refuses it.
+    pub async fn accept(&self, stream_id: &str, id: &str, name: Option<String>) -> Result<StreamProfile> {
+        let stream = self.registry.get(stream_id).await
+            .ok_or_else(|| anyhow::anyhow!("Stream not found: {}", stream_id))?;
+        let original = self.take(stream_id, id).await
+            .ok_or_else(|| anyhow::anyhow!("Suggestion not found: {}", id))?;
+
+        let mut suggestion = original.clone();
+        if let Some(name) = name {
+            if let Some(line) = &mut suggestion.line {
+                line.name = format!("{}-line", name);
+            }
+            suggestion.zone.name = name;
+        }
+
+        let result = self.registry.profiles().modify(&stream.profile, |profile| {
+            if profile.zones.iter().any(|z| z.name == suggestion.zone.name) {
+                return Err(anyhow::anyhow!(
+                    "Profile {} already has a zone named {}", profile.name, suggestion.zone.name
+                ));
+            }
+            if let Some(line) = &suggestion.line {
+                if profile.lines.iter().any(|l| l.name == line.name) {
+                    return Err(anyhow::anyhow!("Profile {} already has a line named {}", profile.name, line.name));
+                }
+                profile.lines.push(line.clone());
+            }
+            profile.zones.push(suggestion.zone.clone());
+            Ok(())
+        }).await;
+
+        if result.is_err() {
+            self.suggestions.write().await
+                .entry(stream_id.to_string())
+                .or_default()
+                .push(original);
+        }
+        result
+    }
+
+    pub async fn reject(&self, stream_id: &str, id: &str) -> Result<()> {
+        if self.take(stream_id, id).await.is_none() {
+            return Err(anyhow::anyhow!("Suggestion not found: {}", id));
+        }
+        Ok(())
+    }
+
+    async fn take(&self, stream_id: &str, id: &str) -> Option<ZoneSuggestion> {
+        let mut suggestions = self.suggestions.write().await;
+        let pending = suggestions.get_mut(stream_id)?;
+        let index = pending.iter().position(|s| s.id == id)?;
+        Some(pending.remove(index))
+    }
+}
@@ -0,0 +1,288 @@
+#![cfg(feature = "vision")]
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use tokio::sync::broadcast;
+
+use crate::core::engine::EngineEvent;
+use crate::core::streams::StreamRegistry;
+use crate::vision::detector::BBox;
+use crate::vision::geometry;
+use crate::vision::tracker::{TrackEvent, TrackSummary};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ZonePoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Zone {
+    pub name: String,
+    pub polygon: Vec<ZonePoint>,
+    pub labels: Vec<String>,
+}
+
+impl Zone {
+    pub fn new(name: &str, polygon: Vec<ZonePoint>) -> Self {
+        Self {
+            name: name.to_string(),
+            polygon,
+            labels: Vec::new(),
+        }
+    }
+
+    // Even-odd ray casting; points on the edge may fall either way
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        if self.polygon.len() < 3 {
+            return false;
+        }
+
+        let mut inside = false;
+        let mut j = self.polygon.len() - 1;
+        for i in 0..self.polygon.len() {
+            let (pi, pj) = (self.polygon[i], self.polygon[j]);
+            if (pi.y > y) != (pj.y > y)
+                && x < (pj.x - pi.x) * (y - pi.y) / (pj.y - pi.y) + pi.x
+            {
+                inside = !inside;
+            }
+            j = i;
+        }
+
+        inside
+    }
+
+    // Objects are considered inside a zone when their ground point
+    // (bottom-center of the box) is
+    pub fn contains_bbox(&self, bbox: &BBox) -> bool {
+        let (x, y) = geometry::ground_point(bbox);
+        self.contains(x, y)
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.name.is_empty() {
+            return Err(anyhow::anyhow!("Zone name must not be empty"));
+        }
+        if self.polygon.len() < 3 {
+            return Err(anyhow::anyhow!("Zone {} needs at least 3 points", self.name));
+        }
+        Ok(())
+    }
+}
+
+// A counting line (tripwire) between two points. Which side an object is on
+// is the sign of `side`, so a crossing is a sign change between two frames.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneLine {
+    pub name: String,
+    pub start: ZonePoint,
+    pub end: ZonePoint,
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl ZoneLine {
+    pub fn new(name: &str, start: ZonePoint, end: ZonePoint) -> Self {
+        Self {
+            name: name.to_string(),
+            start,
+            end,
+            labels: Vec::new(),
+        }
+    }
+
+    // Positive on the left of start -> end, negative on the right
+    pub fn side(&self, x: f32, y: f32) -> f32 {
+        (self.end.x - self.start.x) * (y - self.start.y) - (self.end.y - self.start.y) * (x - self.start.x)
+    }
+
+    // Whether moving from `from` to `to` crosses the segment itself, not
+    // just the infinite line through it
+    pub fn crosses(&self, from: (f32, f32), to: (f32, f32)) -> bool {
+        let (a, b) = (self.side(from.0, from.1), self.side(to.0, to.1));
+        if (a > 0.0) == (b > 0.0) || a == 0.0 && b == 0.0 {
+            return false;
+        }
+        let mover = ZoneLine::new("", ZonePoint { x: from.0, y: from.1 }, ZonePoint { x: to.0, y: to.1 });
+        (mover.side(self.start.x, self.start.y) > 0.0) != (mover.side(self.end.x, self.end.y) > 0.0)
+    }
+
+    // Which side a crossing ended on; None when the segment wasn't crossed
+    pub fn crossing(&self, from: (f32, f32), to: (f32, f32)) -> Option<CrossingDirection> {
+        if !self.crosses(from, to) {
+            return None;
+        }
+        Some(if self.side(to.0, to.1) > 0.0 { CrossingDirection::ToLeft } else { CrossingDirection::ToRight })
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.name.is_empty() {
+            return Err(anyhow::anyhow!("Line name must not be empty"));
+        }
+        if self.start == self.end {
+            return Err(anyhow::anyhow!("Line {} needs two distinct points", self.name));
+        }
+        Ok(())
+    }
+}
+
+// Left and right as seen looking from a line's start to its end
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum CrossingDirection {
+    ToLeft,
+    ToRight,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LineCrossing {
+    pub source: String,
+    pub line: String,
+    pub track_id: u64,
+    pub class_name: String,
+    pub direction: CrossingDirection,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LineCount {
+    pub line: String,
+    pub to_left: u64,
+    pub to_right: u64,
+}
+
+// Last ground point of each track on one source. Track updates only come
+// every few frames, so a crossing is any line between the previous point
+// and the current one.
+#[derive(Debug, Default)]
+pub struct LineCounter {
+    last: HashMap<u64, (f32, f32)>,
+}
+
+impl LineCounter {
+    pub fn update<'a>(&mut self, track_id: u64, bbox: &BBox, lines: &'a [ZoneLine]) -> Vec<(&'a ZoneLine, CrossingDirection)> {
+        let point = geometry::ground_point(bbox);
+        let Some(previous) = self.last.insert(track_id, point) else {
+            return Vec::new();
+        };
+        lines.iter()
+            .filter_map(|line| line.crossing(previous, point).map(|direction| (line, direction)))
+            .collect()
+    }
+
+    pub fn forget(&mut self, track_id: u64) {
+        self.last.remove(&track_id);
+    }
+}
+
+// Counts tracks over the lines in each stream's profile, by direction.
+// Lines are read from the profile on every update, so lines added or
+// accepted later count from then on.
+pub struct LineCrossings {
+    registry: Arc<StreamRegistry>,
+    counters: Mutex<HashMap<String, LineCounter>>,
+    // By stream, then line name
+    counts: Mutex<HashMap<String, HashMap<String, LineCount>>>,
+}
+
+impl LineCrossings {
+    pub fn new(registry: Arc<StreamRegistry>) -> Self {
+        Self {
+            registry,
+            counters: Mutex::new(HashMap::new()),
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn record(&self, event: &TrackEvent) -> Vec<LineCrossing> {
+        let (track, finished) = match event {
+            TrackEvent::Birth { track } | TrackEvent::Update { track } => (track, false),
+            TrackEvent::Death { track } => (track, true),
+            TrackEvent::Merge { into, merged_id } => {
+                if let Some(counter) = self.counters.lock().unwrap().get_mut(&into.source) {
+                    counter.forget(*merged_id);
+                }
+                return Vec::new();
+            }
+        };
+        // Frames from sources that aren't registered streams have no lines
+        let lines = match self.registry.resolve(&track.source).await {
+            Ok((_, profile)) => profile.lines,
+            Err(_) => return Vec::new(),
+        };
+
+        let crossings: Vec<LineCrossing> = {
+            let mut counters = self.counters.lock().unwrap();
+            let counter = counters.entry(track.source.clone()).or_default();
+            let crossed = counter.update(track.track_id, &track.bbox, &lines)
+                .into_iter()
+                .map(|(line, direction)| crossing(track, line, direction))
+                .collect();
+            if finished {
+                counter.forget(track.track_id);
+            }
+            crossed
+        };
+
+        if !crossings.is_empty() {
+            let mut counts = self.counts.lock().unwrap();
+            let stream = counts.entry(track.source.clone()).or_default();
+            for crossing in &crossings {
+                let count = stream.entry(crossing.line.clone())
+                    .or_insert_with(|| LineCount { line: crossing.line.clone(), ..Default::default() });
+                match crossing.direction {
+                    CrossingDirection::ToLeft => count.to_left += 1,
+                    CrossingDirection::ToRight => count.to_right += 1,
+                }
+            }
+        }
+        crossings
+    }
+
+    pub fn counts(&self, stream_id: &str) -> Vec<LineCount> {
+        let mut counts: Vec<LineCount> = self.counts.lock().unwrap()
+            .get(stream_id)
+            .map(|lines| lines.values().cloned().collect())
+            .unwrap_or_default();
+        counts.sort_by(|a, b| a.line.cmp(&b.line));
+        counts
+    }
+
+    pub fn start_listening(self: &Arc<Self>, mut events: broadcast::Receiver<EngineEvent>) {
+        let crossings = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(EngineEvent::Track { event }) => {
+                        for crossing in crossings.record(&event).await {
+                            log::debug!(
+                                "{} {} crossed {} on {} ({:?})",
+                                crossing.class_name, crossing.track_id, crossing.line, crossing.source, crossing.direction,
+                            );
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::warn!("Line counting missed {} engine events", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
+
+fn crossing(track: &TrackSummary, line: &ZoneLine, direction: CrossingDirection) -> LineCrossing {
+    LineCrossing {
+        source: track.source.clone(),
+        line: line.name.clone(),
+        track_id: track.track_id,
+        class_name: track.class_name.clone(),
+        direction,
+        timestamp: track.last_seen,
+    }
+}
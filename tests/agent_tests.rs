@@ -1,5 +1,7 @@
+mod common;
+
 use vae::core::agent::{Lilith, Memory, AgentTrait};
-use vae::core::llm::types::{Message, Response};
+use vae::core::llm::types::Message;
 use vae::utils::{logger, config};
 use tokio;
 use std::error::Error;
@@ -143,4 +145,907 @@ async fn test_metrics_collection() -> Result<(), Box<dyn Error>> {
     assert!(metrics.memory_usage > 0);
     
     Ok(())
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_few_shot_injection_respects_budget() -> Result<(), Box<dyn Error>> {
+    use vae::core::agent::fewshot::{ExampleTurn, FewShotConfig, FewShotExample, FewShotStore};
+
+    let store = FewShotStore::new(FewShotConfig {
+        persist: false,
+        token_budget: 20,
+        ..Default::default()
+    }).await?;
+
+    let example = |question: &str, answer: &str, priority: i32| FewShotExample {
+        id: String::new(),
+        agent: "lilith".to_string(),
+        turns: vec![
+            ExampleTurn { role: "user".to_string(), content: question.to_string() },
+            ExampleTurn { role: "assistant".to_string(), content: answer.to_string() },
+        ],
+        priority,
+        tags: Vec::new(),
+        created_at: chrono::Utc::now(),
+    };
+
+    store.add(example("How many cameras?", "Four are online.", 10)).await?;
+    store.add(example(&"long question ".repeat(20), "long answer", 5)).await?;
+
+    let messages = vec![
+        Message::new("system", "You are Lilith."),
+        Message::new("user", "Status?"),
+    ];
+    let injected = store.inject("lilith", messages).await;
+
+    // Only the high priority example fits, placed between system and user
+    assert_eq!(injected.len(), 4);
+    assert_eq!(injected[0].role, "system");
+    assert_eq!(injected[1].content, "How many cameras?");
+    assert_eq!(injected[3].content, "Status?");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_context_injection_skips_failing_providers() -> Result<(), Box<dyn Error>> {
+    use async_trait::async_trait;
+    use std::sync::Arc;
+    use vae::core::agent::context::{ContextConfig, ContextInjector, ContextProvider};
+
+    struct Fixed(&'static str, Option<&'static str>);
+
+    #[async_trait]
+    impl ContextProvider for Fixed {
+        fn name(&self) -> String {
+            self.0.to_string()
+        }
+
+        async fn provide(&self) -> anyhow::Result<Option<String>> {
+            match self.1 {
+                Some(text) => Ok(Some(text.to_string())),
+                None => Err(anyhow::anyhow!("unavailable")),
+            }
+        }
+    }
+
+    let mut injector = ContextInjector::new(ContextConfig::default());
+    injector.add_provider(Arc::new(Fixed("alerts", Some("No active alerts."))));
+    injector.add_provider(Arc::new(Fixed("pipeline", None)));
+
+    let messages = vec![
+        Message::new("system", "You are Lilith."),
+        Message::new("user", "Anything wrong?"),
+    ];
+    let injected = injector.inject(messages).await;
+
+    assert_eq!(injected.len(), 3);
+    assert_eq!(injected[1].role, "system");
+    assert!(injected[1].content.contains("No active alerts."));
+    assert!(!injected[1].content.contains("pipeline"));
+
+    Ok(())
+}
+
+#[test]
+fn test_importance_weighted_retention() {
+    use vae::core::agent::retention::{apply_retention, RetainedMessage, RetentionConfig};
+
+    let now = chrono::Utc::now();
+    let entry = |content: &str, age_secs: i64, importance: f32| RetainedMessage {
+        message: Message::new("user", content),
+        stored_at: now - chrono::Duration::seconds(age_secs),
+        ttl_secs: None,
+        importance,
+        pinned: false,
+    };
+
+    let mut entries = vec![
+        entry("my name is Ada", 500, 0.9),
+        entry("ok", 400, 0.1),
+        entry("thanks", 300, 0.1),
+        entry("expired", 200, 0.9),
+        entry("latest", 10, 0.1),
+    ];
+    entries[3].ttl_secs = Some(60);
+
+    let config = RetentionConfig {
+        max_messages: 2,
+        keep_recent: 1,
+        ..Default::default()
+    };
+    apply_retention(&mut entries, &config, now);
+
+    let kept: Vec<_> = entries.iter().map(|e| e.message.content.as_str()).collect();
+    assert_eq!(kept, vec!["my name is Ada", "latest"]);
+}
+
+#[tokio::test]
+async fn test_entity_memory_upsert_and_recall() -> Result<(), Box<dyn Error>> {
+    use vae::core::agent::entities::{EntityKind, EntityMemory, EntityMemoryConfig, FactSource};
+
+    let memory = EntityMemory::new(EntityMemoryConfig { persist: false, ..Default::default() }).await?;
+
+    memory.upsert_fact("Loading Dock", EntityKind::Location, "camera", "cam-3", FactSource::Manual).await?;
+    memory.upsert_fact("Ada", EntityKind::Person, "role", "night guard", FactSource::Manual).await?;
+    memory.upsert_fact("Ada", EntityKind::Person, "role", "shift lead", FactSource::Manual).await?;
+
+    let recalled = memory.recall("Is Ada at the loading dock?").await;
+    assert_eq!(recalled.len(), 2);
+
+    let ada = memory.get("ada").await.unwrap();
+    assert_eq!(ada.facts["role"].value, "shift lead");
+
+    // Partial words don't count as mentions
+    assert!(memory.recall("Adams is here").await.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_graph_tool_returns_latest_sighting() -> Result<(), Box<dyn Error>> {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use vae::core::agent::graph::{GraphConfig, GraphStore, QueryGraphTool};
+    use vae::core::agent::tools::ToolRegistry;
+
+    let graph = Arc::new(GraphStore::new(GraphConfig::default()));
+    let earlier = chrono::Utc::now() - chrono::Duration::hours(2);
+    let later = chrono::Utc::now() - chrono::Duration::minutes(5);
+
+    // The newer sighting arrives first, as a replayed or delayed event would
+    for at in [later, earlier] {
+        graph.relate(("red truck", "object"), "seen near", ("Gate 3", "location"), at, "vision:cam-1", HashMap::new()).await;
+    }
+    graph.relate(("red truck", "object"), "seen_near", ("Gate 1", "location"), chrono::Utc::now(), "vision:cam-2", HashMap::new()).await;
+
+    let mut tools = ToolRegistry::new();
+    tools.register(Arc::new(QueryGraphTool::new(graph)));
+    assert_eq!(tools.definitions()[0].name, "query_graph");
+
+    let result = tools.call("query_graph", serde_json::json!({
+        "subject": "red truck",
+        "relation": "seen_near",
+        "object": "gate 3",
+        "limit": 1
+    })).await?;
+
+    let facts = result["facts"].as_array().unwrap();
+    assert_eq!(facts.len(), 1);
+    assert_eq!(facts[0]["object"], "Gate 3");
+    assert_eq!(facts[0]["timestamp"], serde_json::to_value(later)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_metrics_summary_finds_largest_drop() {
+    use vae::core::agent::diagnostics::{downsample, summarize, MetricPoint};
+
+    let start = chrono::Utc::now();
+    let points: Vec<MetricPoint> = [30.0, 29.5, 30.0, 12.0, 14.0, 29.0]
+        .iter()
+        .enumerate()
+        .map(|(i, value)| MetricPoint { timestamp: start + chrono::Duration::minutes(i as i64), value: *value })
+        .collect();
+
+    let summary = summarize("fps", &points).unwrap();
+    assert_eq!(summary.samples, 6);
+    assert_eq!(summary.min, 12.0);
+    let drop = summary.largest_drop.unwrap();
+    assert_eq!(drop.delta, -18.0);
+    assert_eq!(drop.to.timestamp, start + chrono::Duration::minutes(3));
+    assert_eq!(summary.largest_rise.unwrap().delta, 15.0);
+
+    let sampled = downsample(points.clone(), 3);
+    let values: Vec<f32> = sampled.iter().map(|p| p.value).collect();
+    assert_eq!(values, vec![30.0, 12.0, 29.0]);
+    for max_points in 1..=5 {
+        let sampled = downsample(points.clone(), max_points);
+        assert!(sampled.len() <= max_points);
+        assert_eq!(sampled.last().unwrap().timestamp, start + chrono::Duration::minutes(5));
+    }
+    assert!(summarize("fps", &[]).is_none());
+}
+
+#[tokio::test]
+async fn test_canary_routes_sticky_and_rolls_back() -> Result<(), Box<dyn Error>> {
+    use vae::core::agent::canary::{
+        bucket, AgentVariant, Arm, CanaryConfig, CanaryGuardrails, CanaryRouter, CanaryStatus,
+        CanaryVerdict, SessionObservation,
+    };
+    use vae::core::prompts::PromptRef;
+
+    let variant = |model: &str, version: u32| AgentVariant {
+        model: model.to_string(),
+        prompt: PromptRef { name: "lilith-system".to_string(), version: Some(version) },
+        prompt_price: 0.001,
+        completion_price: 0.002,
+    };
+
+    let router = CanaryRouter::new(variant("gpt-4o", 3));
+    router.start(CanaryConfig {
+        baseline: variant("gpt-4o", 3),
+        candidate: variant("gpt-4o-mini", 4),
+        percent: 30,
+        guardrails: CanaryGuardrails { min_sessions: 5, ..CanaryGuardrails::default() },
+    }).await?;
+
+    // Assignment follows the session's bucket and never changes
+    let sessions: Vec<String> = (0..200).map(|i| format!("session-{}", i)).collect();
+    let mut candidates = 0;
+    for session in &sessions {
+        let (arm, _) = router.route(session).await;
+        assert_eq!(arm == Arm::Candidate, bucket(session) < 30);
+        assert_eq!(router.route(session).await.0, arm);
+        candidates += (arm == Arm::Candidate) as usize;
+    }
+    assert!(candidates > 30 && candidates < 90);
+
+    let observation = |quality: f32| SessionObservation {
+        latency_ms: 800.0,
+        prompt_tokens: 500,
+        completion_tokens: 200,
+        quality: Some(quality),
+        error: false,
+    };
+    for _ in 0..5 {
+        assert!(router.record(Arm::Baseline, observation(0.9)).await.is_none());
+    }
+    for _ in 0..4 {
+        assert!(router.record(Arm::Candidate, observation(0.7)).await.is_none());
+    }
+    // Fifth candidate session gives both arms enough data to compare
+    let verdict = router.record(Arm::Candidate, observation(0.7)).await;
+    assert!(matches!(verdict, Some(CanaryVerdict::Regressed { .. })));
+
+    let report = router.report().await.unwrap();
+    assert_eq!(report.status, CanaryStatus::RolledBack);
+    for session in &sessions {
+        assert_eq!(router.route(session).await.0, Arm::Baseline);
+    }
+    assert_eq!(router.stable().await.model, "gpt-4o");
+
+    // An unchanged candidate is refused; a promoted one becomes the stable variant
+    assert!(router.start(CanaryConfig {
+        baseline: variant("gpt-4o", 3),
+        candidate: variant("gpt-4o", 3),
+        percent: 10,
+        guardrails: CanaryGuardrails::default(),
+    }).await.is_err());
+    router.start(CanaryConfig {
+        baseline: variant("gpt-4o", 3),
+        candidate: variant("gpt-4o", 5),
+        percent: 0,
+        guardrails: CanaryGuardrails::default(),
+    }).await?;
+    assert_eq!(router.route("session-0").await.0, Arm::Baseline);
+    router.promote().await?;
+    assert_eq!(router.stable().await.prompt.version, Some(5));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_session_turns_run_on_their_canary_arm() -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+    use vae::core::agent::canary::{AgentVariant, CanaryConfig, CanaryRouter};
+    use vae::core::agent::sessions::{CreateSession, SessionConfig, SessionManager};
+    use vae::core::llm::LLMTrait;
+    use vae::core::llm::overrides::{ModelOverrideConfig, ModelOverrides};
+    use vae::core::prompts::{NewPromptVersion, PromptKind, PromptLibrary, PromptLibraryConfig, PromptRef};
+    use common::{CallLog, StubLLM};
+
+    let prompts = Arc::new(PromptLibrary::new(PromptLibraryConfig { persist: false, ..Default::default() }).await?);
+    for content in ["You are Lilith.", "You are Lilith. Be brief."] {
+        prompts.publish("lilith-system", NewPromptVersion {
+            kind: PromptKind::System,
+            content: content.to_string(),
+            description: None,
+            author: None,
+            note: None,
+            activate: true,
+        }).await?;
+    }
+    let variant = |version: u32| AgentVariant {
+        model: "gpt-4o".to_string(),
+        prompt: PromptRef { name: "lilith-system".to_string(), version: Some(version) },
+        prompt_price: 0.0,
+        completion_price: 0.0,
+    };
+
+    let calls = CallLog::default();
+    let llm: Arc<dyn LLMTrait> = Arc::new(StubLLM::new("gpt-4o").recording(calls.clone()));
+    let overrides = Arc::new(ModelOverrides::new(ModelOverrideConfig::default(), "openai", llm.clone()));
+    let router = Arc::new(CanaryRouter::new(variant(1)));
+    router.start(CanaryConfig { baseline: variant(1), candidate: variant(2), percent: 100, guardrails: Default::default() }).await?;
+    let sessions = SessionManager::new(SessionConfig::default()).await?
+        .with_llm(llm)
+        .with_canary(router.clone(), overrides, prompts);
+
+    // Every session is in the candidate arm, which answers with its prompt
+    // and is observed per turn
+    let session = sessions.create("alice", None, CreateSession::default()).await?;
+    sessions.chat(&session.id, "Any trucks at gate 2?").await?;
+    assert_eq!(calls.lock().unwrap()[0].messages[0].content, "You are Lilith. Be brief.");
+    let report = router.report().await.unwrap();
+    assert_eq!(report.candidate.sessions, 1);
+    assert_eq!(report.baseline.sessions, 0);
+
+    // Back on the baseline prompt once the canary is rolled back
+    router.rollback("manual").await?;
+    sessions.chat(&session.id, "And now?").await?;
+    assert_eq!(calls.lock().unwrap()[1].messages[0].content, "You are Lilith.");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_session_titles_generated_after_min_turns() -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+    use vae::core::agent::titles::{SessionTitler, TitleConfig};
+    use common::StubLLM;
+
+    // Answers with a fixed title, or garbage when `fail` is set
+    let titles = |fail: bool| StubLLM::new("stub").replying(move |_, _| Ok(if fail {
+        "Sorry, I can't help with that.".to_string()
+    } else {
+        "```json\n{\"title\": \"Lobby camera offline.\", \"summary\": \"The lobby camera stopped streaming; a PoE reset fixed it.\"}\n```".to_string()
+    }));
+
+    let llm = Arc::new(titles(false));
+    let config = TitleConfig { min_turns: 2, refresh_every_turns: Some(2), persist: false, ..Default::default() };
+    let titler = Arc::new(SessionTitler::new(config.clone(), llm.clone()).await?);
+
+    let mut history = vec![
+        Message::new("system", "You are Lilith."),
+        Message::new("user", "The lobby camera shows a black screen"),
+        Message::new("assistant", "Checking the stream."),
+    ];
+    titler.record_turn("s1", &history).await;
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert!(titler.get("s1").await.is_none());
+
+    history.push(Message::new("user", "Still black"));
+    titler.record_turn("s1", &history).await;
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let digest = titler.get("s1").await.expect("titled after two turns");
+    assert_eq!(digest.title, "Lobby camera offline");
+    assert_eq!(digest.turns, 2);
+    assert!(digest.llm_generated);
+
+    // One more turn isn't enough for a refresh
+    history.push(Message::new("user", "Thanks, it works"));
+    titler.record_turn("s1", &history).await;
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert_eq!(llm.call_count(), 1);
+
+    // A session deleted while its title is generated gets no digest
+    titler.record_turn("s3", &history).await;
+    titler.remove("s3").await;
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert!(titler.get("s3").await.is_none());
+
+    // Unparseable output falls back to the first question
+    let broken = SessionTitler::new(config, Arc::new(titles(true))).await?;
+    let digest = broken.generate("s2", &history).await;
+    assert_eq!(digest.title, "The lobby camera shows a black screen");
+    assert!(!digest.llm_generated);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_vector_memory_recalls_semantically_related_messages() -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+    use vae::core::agent::hnsw::{HnswConfig, HnswIndex};
+    use vae::core::agent::vector_memory::{MemoryIndex, MemoryPoint, VectorMemory, VectorMemoryConfig};
+    use vae::core::rag::store::Embedder;
+
+    // One dimension per topic word, so related messages share directions
+    struct TopicEmbedder;
+
+    #[async_trait::async_trait]
+    impl Embedder for TopicEmbedder {
+        fn model_id(&self) -> String { "topics".to_string() }
+        fn dimensions(&self) -> usize { 4 }
+
+        async fn embed(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+            Ok(texts.iter().map(|text| {
+                let text = text.to_lowercase();
+                ["camera", "invoice", "weather", "zone"].iter()
+                    .map(|word| text.matches(word).count() as f32 + 0.01)
+                    .collect()
+            }).collect())
+        }
+    }
+
+    let index: Arc<dyn MemoryIndex> = Arc::new(HnswIndex::new(HnswConfig::default()));
+    let config = VectorMemoryConfig { skip_recent: 1, max_indexed: 3, ..Default::default() };
+    let memory = VectorMemory::new(config.clone(), "s1", Arc::new(TopicEmbedder), index.clone());
+    let other = VectorMemory::new(config, "s2", Arc::new(TopicEmbedder), index.clone());
+
+    memory.store(Message::new("user", "The invoice for March looks wrong")).await;
+    memory.store(Message::new("user", "Camera 3 keeps dropping its camera feed")).await;
+    memory.store(Message::new("assistant", "ok")).await;
+    memory.store(Message::new("user", "What's the weather doing to the weather station?")).await;
+    other.store(Message::new("user", "Another session asking about a camera")).await;
+
+    assert_eq!(memory.get_recent(2).await[0].content, "ok");
+    // "ok" is too short to index
+    assert_eq!(index.count("s1").await?, 3);
+    assert_eq!(index.count("s2").await?, 1);
+
+    let found = memory.search_similar("is the camera back online?", 1).await?;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].message.content, "Camera 3 keeps dropping its camera feed");
+
+    // The newest message is already in the prompt and is skipped
+    let found = memory.search_similar("weather", 3).await?;
+    assert!(found.iter().all(|m| !m.message.content.contains("weather")));
+
+    memory.store(Message::new("user", "Add a zone by the loading dock zone")).await;
+    memory.cleanup().await?;
+    assert!(memory.is_within_limits().await);
+    let found = memory.search_similar("invoice", 3).await?;
+    assert!(found.iter().all(|m| !m.message.content.contains("invoice")));
+
+    // A memory opened after a restart still clears what the last one indexed
+    let reopened = VectorMemory::new(VectorMemoryConfig::default(), "s1", Arc::new(TopicEmbedder), index.clone());
+    reopened.clear().await?;
+    assert_eq!(index.count("s1").await?, 0);
+    assert_eq!(index.count("s2").await?, 1);
+
+    // The graph itself, past the exact-scan threshold, finds the true
+    // nearest neighbour
+    let graph = HnswIndex::new(HnswConfig { exact_below: 0, m: 8, ..Default::default() });
+    let mut seed = 42u64;
+    let mut next = || {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((seed >> 33) as f32 / (1u64 << 31) as f32) - 0.5
+    };
+    let points: Vec<MemoryPoint> = (0..300)
+        .map(|i| MemoryPoint {
+            id: format!("p{}", i),
+            scope: "bulk".to_string(),
+            message: Message::new("user", &format!("point {}", i)),
+            embedding: (0..8).map(|_| next()).collect(),
+            stored_at: chrono::Utc::now(),
+        })
+        .collect();
+    graph.upsert(points.clone()).await?;
+    for target in [&points[7], &points[150], &points[299]] {
+        let found = graph.search(&target.embedding, 1, "bulk").await?;
+        assert_eq!(found[0].id, target.id);
+    }
+    graph.delete(&["p7".to_string()]).await?;
+    assert_ne!(graph.search(&points[7].embedding, 1, "bulk").await?[0].id, "p7");
+    assert_eq!(graph.count("bulk").await?, 299);
+
+    // A small scope is not crowded out by a large one
+    let small = MemoryPoint { id: "tiny".to_string(), scope: "small".to_string(), ..points[0].clone() };
+    graph.upsert(vec![small]).await?;
+    let found = graph.search(&points[150].embedding, 3, "small").await?;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, "tiny");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sqlite_memory_survives_reopen() -> Result<(), Box<dyn Error>> {
+    use vae::core::agent::sqlite_memory::{MemoryBackend, MemoryConfig, SqliteMemory};
+
+    let dir = std::env::temp_dir().join(format!("vae-memory-{}", uuid::Uuid::new_v4()));
+    let config = MemoryConfig {
+        backend: MemoryBackend::Sqlite,
+        path: dir.join("memory.db").to_string_lossy().into_owned(),
+        max_messages: 3,
+        ..Default::default()
+    };
+
+    {
+        let memory = SqliteMemory::open(&config, "s1")?;
+        for i in 0..5 {
+            memory.store(Message::new("user", &format!("message {}", i))).await?;
+        }
+        memory.store(Message::new("assistant", "reply")).await?;
+        // A second scope shares the first one's pool
+        memory.with_scope("s2").store(Message::new("user", "other session")).await?;
+        assert!(!memory.is_within_limits().await);
+        memory.cleanup().await?;
+        assert!(memory.is_within_limits().await);
+    }
+
+    // Migrations are already applied; reopening keeps the data
+    let memory = SqliteMemory::open(&config, "s1")?;
+    let recent = memory.get_recent(10).await?;
+    let contents: Vec<&str> = recent.iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(contents, vec!["message 3", "message 4", "reply"]);
+    assert_eq!(recent[2].role, "assistant");
+    assert_eq!(SqliteMemory::open(&config, "s2")?.count().await?, 1);
+
+    drop(memory);
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_compaction_summarizes_oldest_messages() -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+    use vae::core::agent::compaction::{is_summary, CompactionConfig, Compactor};
+    use vae::core::agent::sessions::{CreateSession, SessionConfig, SessionManager};
+    use vae::core::agent::sqlite_memory::{MemoryConfig, SqliteMemory};
+    use vae::core::llm::tokenizer::count_tokens;
+    use common::StubLLM;
+
+    // Numbers its summaries; the transcripts it was given are in its calls
+    let summarizer = |fail: bool| StubLLM::new("stub").replying(move |_, n| {
+        if fail {
+            return Err(anyhow::anyhow!("provider down"));
+        }
+        Ok(format!("Pass {}: the user watches camera 4 at the north gate.", n + 1))
+    });
+
+    let mut history = vec![Message::new("system", "You are Lilith.")];
+    for i in 0..40 {
+        let role = if i % 2 == 0 { "user" } else { "assistant" };
+        history.push(Message::new(role, &format!("Message {} about the loading dock camera and its night-time false alarms", i)));
+    }
+    let config = CompactionConfig { max_history_tokens: 400, compact_messages: 10, keep_recent: 6, ..Default::default() };
+
+    // A failed summary leaves the history as it was
+    let broken = Compactor::new(config.clone(), Arc::new(summarizer(true)));
+    let session_config = SessionConfig {
+        memory: MemoryConfig { compaction: config.clone(), ..Default::default() },
+        ..Default::default()
+    };
+    let mut untouched = history.clone();
+    assert!(broken.compact(&mut untouched).await.is_err());
+    assert_eq!(untouched.len(), history.len());
+
+    let llm = Arc::new(summarizer(false));
+    let compactor = Compactor::new(config, llm.clone());
+    assert!(compactor.needs_compaction(&history));
+    let report = compactor.compact(&mut history).await?.expect("over the threshold");
+
+    assert!(report.tokens_after <= 400 && report.tokens_after < report.tokens_before);
+    assert_eq!(report.start, 1);
+    assert_eq!(history.len(), 41 - (report.end - report.start) + 1);
+    assert_eq!(count_tokens(&history), report.tokens_after);
+    assert_eq!(history[0].content, "You are Lilith.");
+    assert!(is_summary(&history[1]));
+    assert!(history[1].content.contains(&format!("Pass {}", report.passes)));
+    assert_eq!(history.iter().filter(|m| is_summary(m)).count(), 1);
+    // The newest turns are never summarized
+    assert!(history.last().unwrap().content.starts_with("Message 39 "));
+    assert!(history.iter().any(|m| m.content.starts_with("Message 34 ")));
+
+    // Later passes fold the previous summary in
+    let transcripts: Vec<String> = llm.calls().into_iter().map(|c| c.messages[1].content.clone()).collect();
+    assert!(report.passes >= 2);
+    assert!(transcripts[0].starts_with("user: Message 0 "));
+    assert!(transcripts[1].starts_with("Earlier summary: Pass 1"));
+
+    assert!(compactor.compact(&mut history).await?.is_none());
+
+    // A stored history is rewritten in place: the stored system prompt
+    // keeps its row and only the summarized span is replaced
+    let memory = SqliteMemory::open_in_memory(&MemoryConfig::default(), "s1")?;
+    memory.store(Message::new("system", "You are Lilith.")).await?;
+    for i in 0..40 {
+        let role = if i % 2 == 0 { "user" } else { "assistant" };
+        memory.store(Message::new(role, &format!("Message {} about the loading dock camera and its night-time false alarms", i))).await?;
+    }
+    let report = compactor.compact_memory(&memory).await?.expect("over the threshold");
+    assert_eq!(report.start, 1);
+    let stored = memory.get_recent(100).await?;
+    assert_eq!(stored.len(), 41 - (report.end - report.start) + 1);
+    assert_eq!(stored[0].content, "You are Lilith.");
+    assert_eq!(stored[1].content, report.summary.content);
+    assert!(stored.last().unwrap().content.starts_with("Message 39 "));
+
+    // Sessions compact with their chat model as turns come in
+    let sessions = SessionManager::new(session_config).await?.with_llm(Arc::new(summarizer(false)));
+    let session = sessions.create("alice", None, CreateSession::default()).await?;
+    let turn = |role: &str, n: usize| Message::new(role, &format!("Message {} about the loading dock camera and its night-time false alarms", n));
+    for i in 0..20 {
+        sessions.record_turn(&session.id, turn("user", 2 * i), turn("assistant", 2 * i + 1)).await?;
+    }
+    let history = sessions.history(&session.id).await?.unwrap();
+    assert!(history.iter().any(is_summary));
+    assert!(count_tokens(&history) <= 400);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sessions_keep_separate_memory_and_resume() -> Result<(), Box<dyn Error>> {
+    use std::collections::HashMap;
+    use vae::core::agent::sessions::{CreateSession, SessionConfig, SessionManager};
+    use vae::core::agent::sqlite_memory::{MemoryBackend, MemoryConfig};
+
+    let dir = std::env::temp_dir().join(format!("vae-sessions-{}", uuid::Uuid::new_v4()));
+    let config = SessionConfig {
+        max_sessions: 3,
+        idle_ttl_secs: None,
+        memory: MemoryConfig {
+            backend: MemoryBackend::Sqlite,
+            path: dir.join("memory.db").to_string_lossy().into_owned(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let (first, second, empty) = {
+        let manager = SessionManager::new(config.clone()).await?;
+        let first = manager.create("alice", Some("acme"), CreateSession {
+            title: Some("Dock cameras".into()),
+            metadata: HashMap::from([("site".to_string(), "north".to_string())]),
+        }).await?;
+        let second = manager.create("bob", None, CreateSession::default()).await?;
+        let empty = manager.create("alice", None, CreateSession::default()).await?;
+        assert!(manager.create("alice", None, CreateSession::default()).await.is_err());
+
+        manager.record_turn(&first.id, Message::new("user", "watch dock 3"), Message::new("assistant", "watching")).await?;
+        manager.record_turn(&second.id, Message::new("user", "count people"), Message::new("assistant", "12")).await?;
+        manager.record_turn(&first.id, Message::new("user", "any trucks?"), Message::new("assistant", "two")).await?;
+
+        let latest = manager.messages(&first.id, 2).await?.unwrap();
+        let contents: Vec<&str> = latest.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["any trucks?", "two"]);
+
+        // Listings are per owner unless unfiltered
+        let alice: Vec<String> = manager.list(Some("alice")).await.into_iter().map(|s| s.id).collect();
+        assert_eq!(alice, vec![first.id.clone(), empty.id.clone()]);
+        assert_eq!(manager.list(None).await.len(), 3);
+        (first, second, empty)
+    };
+
+    // A new manager over the same file picks every conversation back up,
+    // including one that never got a message, with its owner and metadata
+    let manager = SessionManager::new(config.clone()).await?;
+    let resumed = manager.info(&first.id).await.expect("session resumed");
+    assert_eq!(resumed.message_count, 4);
+    assert_eq!(resumed.owner, "alice");
+    assert_eq!(resumed.tenant.as_deref(), Some("acme"));
+    assert_eq!(resumed.title.as_deref(), Some("Dock cameras"));
+    assert_eq!(resumed.metadata["site"], "north");
+    assert_eq!(manager.info(&empty.id).await.expect("empty session resumed").message_count, 0);
+    assert_eq!(manager.messages(&second.id, 10).await?.unwrap()[0].content, "count people");
+    assert_eq!(manager.history(&first.id).await?.unwrap().len(), 4);
+
+    assert!(manager.delete(&second.id).await?);
+    assert!(!manager.delete(&second.id).await?);
+    assert!(manager.messages(&second.id, 10).await?.is_none());
+    assert_eq!(manager.list(None).await.len(), 2);
+    drop(manager);
+
+    // Resuming respects max_sessions: only the most recently active load
+    let manager = SessionManager::new(SessionConfig { max_sessions: 1, ..config }).await?;
+    assert_eq!(manager.list(None).await.len(), 1);
+    assert!(manager.info(&first.id).await.is_some());
+
+    drop(manager);
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_streamed_session_turn_is_recorded_once_finished() -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+    use futures::StreamExt;
+    use tokio_util::sync::CancellationToken;
+    use vae::core::agent::sessions::{CreateSession, SessionConfig, SessionManager};
+    use vae::core::llm::types::StreamChunk;
+    use common::StubLLM;
+
+    let llm = StubLLM::new("chat").streaming(|| {
+        Box::pin(futures::stream::iter(["Two ", "trucks"].map(|s| Ok(StreamChunk { content: s.to_string() }))))
+    });
+    let sessions = Arc::new(SessionManager::new(SessionConfig::default()).await?.with_llm(Arc::new(llm)));
+    let session = sessions.create("alice", None, CreateSession::default()).await?;
+
+    let deltas: Vec<String> = sessions.chat_stream(&session.id, "any trucks?", CancellationToken::new())
+        .map(|delta| delta.unwrap())
+        .collect()
+        .await;
+    assert_eq!(deltas, vec!["Two ", "trucks"]);
+    let contents: Vec<String> = sessions.messages(&session.id, 10).await?.unwrap()
+        .into_iter().map(|m| m.content).collect();
+    assert_eq!(contents, vec!["any trucks?", "Two trucks"]);
+
+    // A reader that goes away part way leaves the history alone
+    let cancel = CancellationToken::new();
+    let stream = sessions.chat_stream(&session.id, "and vans?", cancel.clone());
+    futures::pin_mut!(stream);
+    assert_eq!(stream.next().await.unwrap()?, "Two ");
+    cancel.cancel();
+    assert!(stream.next().await.is_none());
+    assert_eq!(sessions.messages(&session.id, 10).await?.unwrap().len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_session_turns_answer_in_the_resolved_language() -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+    use vae::core::agent::sessions::{CreateSession, SessionConfig, SessionManager};
+    use vae::core::i18n::{LanguageConfig, Localizer};
+    use common::StubLLM;
+
+    let llm = Arc::new(StubLLM::new("chat"));
+    let config = LanguageConfig {
+        tenants: [("acme".to_string(), "de".to_string())].into_iter().collect(),
+        ..Default::default()
+    };
+    let localizer = Arc::new(Localizer::new(config).await?);
+    let sessions = SessionManager::new(SessionConfig::default()).await?
+        .with_llm(llm.clone())
+        .with_localizer(localizer.clone());
+    let session = sessions.create("alice", Some("acme"), CreateSession::default()).await?;
+
+    // Too short to detect, so the tenant's language is enforced
+    sessions.chat(&session.id, "ok").await?;
+    let system = llm.calls()[0].messages[0].clone();
+    assert_eq!(system.role, "system");
+    assert!(system.content.ends_with("Keep identifiers, stream names and quoted log lines as they are."));
+    assert!(system.content.contains("German (de)"));
+
+    // The session's own preference wins, and goes with the session
+    localizer.set_session(&session.id, Some("fr")).await?;
+    sessions.chat(&session.id, "ok").await?;
+    assert!(llm.calls()[1].messages[0].content.contains("French (fr)"));
+    sessions.delete(&session.id).await?;
+    assert!(localizer.session_language(&session.id).await.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_session_system_prompt_comes_from_the_template() -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+    use vae::core::agent::sessions::{CreateSession, SessionConfig, SessionManager};
+    use vae::core::prompt_template::{PromptTemplateConfig, PromptTemplates};
+    use common::StubLLM;
+
+    let dir = std::env::temp_dir().join(format!("vae-session-prompts-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join("system.j2"), "You are {{ persona }}, watching the cameras.")?;
+    let templates = Arc::new(PromptTemplates::new(PromptTemplateConfig {
+        templates_dir: dir.to_string_lossy().into_owned(),
+        reload_interval_secs: None,
+        ..Default::default()
+    }).await?);
+
+    let llm = Arc::new(StubLLM::new("chat"));
+    let sessions = SessionManager::new(SessionConfig { persona: "Iris".to_string(), ..Default::default() }).await?
+        .with_llm(llm.clone())
+        .with_templates(templates);
+    let session = sessions.create("alice", None, CreateSession::default()).await?;
+    sessions.chat(&session.id, "anything new?").await?;
+    assert_eq!(llm.calls()[0].messages[0].content, "You are Iris, watching the cameras.");
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_data_subject_erasure_of_sessions() -> Result<(), Box<dyn Error>> {
+    use vae::core::agent::sessions::{CreateSession, SessionConfig, SessionManager};
+    use vae::core::lifecycle::{DataCategory, DataSubject, LifecycleConfig, LifecycleError, LifecycleManager};
+    use std::sync::Arc;
+
+    let dir = std::env::temp_dir().join(format!("vae-lifecycle-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir)?;
+    let audit_file = dir.join("audit.jsonl").to_string_lossy().into_owned();
+
+    // Nothing is acknowledged while a required store is missing
+    let lifecycle = LifecycleManager::new(LifecycleConfig { audit_file: audit_file.clone(), ..Default::default() }).await?;
+    let subject = DataSubject::Identity("alice".into());
+    let err = lifecycle.delete_subject(&subject, "dpo", None).await.unwrap_err();
+    assert!(matches!(err.downcast_ref::<LifecycleError>(), Some(LifecycleError::MissingStores(m)) if m.len() == 5));
+    assert!(lifecycle.audit_trail(None).await.is_empty());
+
+    let sessions = Arc::new(SessionManager::new(SessionConfig::default()).await?);
+    let mine = sessions.create("alice", Some("acme"), CreateSession::default()).await?;
+    let theirs = sessions.create("bob", Some("acme"), CreateSession::default()).await?;
+    sessions.record_turn(&mine.id, Message::new("user", "hi"), Message::new("assistant", "hello")).await?;
+
+    let mut lifecycle = LifecycleManager::new(LifecycleConfig {
+        audit_file,
+        required: vec![DataCategory::Conversations],
+    }).await?;
+    lifecycle.register_store(sessions.clone());
+
+    let bundle = lifecycle.export_subject(&subject, "dpo").await?;
+    assert_eq!(bundle.data[&DataCategory::Conversations].len(), 1);
+
+    let entry = lifecycle.delete_subject(&subject, "dpo", None).await?;
+    assert!(entry.is_complete());
+    // The session and its two messages
+    assert_eq!(entry.outcomes[0].records, 3);
+    assert!(sessions.info(&mine.id).await.is_none());
+    assert!(sessions.info(&theirs.id).await.is_some());
+
+    // A tenant request reaches everyone in it
+    lifecycle.delete_subject(&DataSubject::Tenant("acme".into()), "dpo", None).await?;
+    assert!(sessions.list(None).await.is_empty());
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[cfg(feature = "vision")]
+#[tokio::test]
+async fn test_look_at_camera_sends_the_latest_frame() -> Result<(), Box<dyn Error>> {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use opencv::core::{Mat, Scalar, CV_8UC3};
+    use serde_json::json;
+    use vae::core::agent::look::{LatestFrames, LookAtCameraTool};
+    use vae::core::agent::tools::Tool;
+    use vae::core::pipeline::PipelineData;
+    use vae::core::sinks::ResultSink;
+    use vae::vision::privacy::{PrivacyConfig, PrivacyMasker};
+    use vae::vision::processor::{Frame, FrameMetadata};
+    use common::StubLLM;
+
+    let data = |id: u64, masked: bool| -> Result<PipelineData, Box<dyn Error>> {
+        Ok(PipelineData {
+            frame: Frame {
+                id,
+                timestamp: chrono::Utc::now(),
+                data: Arc::new(Mat::new_rows_cols_with_default(480, 640, CV_8UC3, Scalar::all(64.0))?),
+                metadata: FrameMetadata {
+                    width: 640,
+                    height: 480,
+                    channels: 3,
+                    format: "bgr".to_string(),
+                    source: "rtsp://dock".to_string(),
+                    privacy_masked: masked,
+                    stream_id: Some("dock".to_string()),
+                },
+            },
+            detections: Vec::new(),
+            analysis: None,
+            metadata: HashMap::new(),
+            timestamp: chrono::Utc::now(),
+        })
+    };
+
+    let frames = Arc::new(LatestFrames::new());
+    frames.write(&data(1, true)?).await?;
+    frames.write(&data(2, true)?).await?;
+
+    let llm = Arc::new(StubLLM::new("gpt-4o").replying(|_, _| Ok("The door is open.".to_string())));
+    let masker = Arc::new(PrivacyMasker::new(PrivacyConfig { enabled: true, ..Default::default() })?);
+    let tool = LookAtCameraTool::new(frames.clone(), llm.clone(), masker.clone());
+
+    let answer = tool.call(json!({ "stream_id": "dock", "question": "Is the door open?" })).await?;
+    assert_eq!(answer["frame_id"], 2);
+    assert_eq!(answer["answer"], "The door is open.");
+    let calls = llm.calls();
+    assert_eq!(calls.len(), 1);
+    assert!(calls[0].messages[0].content.contains("\"dock\""));
+    assert!(calls[0].messages[0].content.ends_with("Is the door open?"));
+
+    // Unknown streams and unmasked frames never reach the model
+    assert!(tool.call(json!({ "stream_id": "gate", "question": "Anyone there?" })).await.is_err());
+    frames.write(&data(3, false)?).await?;
+    assert!(tool.call(json!({ "stream_id": "dock", "question": "Is the door open?" })).await.is_err());
+    assert_eq!(llm.call_count(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sessions_supply_fine_tune_conversations_to_their_owner() -> Result<(), Box<dyn Error>> {
+    use vae::core::agent::sessions::{CreateSession, SessionConfig, SessionManager};
+    use vae::core::llm::finetune::ConversationSource;
+
+    let sessions = SessionManager::new(SessionConfig::default()).await?;
+    let session = sessions.create("alice", None, CreateSession::default()).await?;
+    sessions.record_turn(&session.id, Message::new("user", "Gate 2?"), Message::new("assistant", "Clear.")).await?;
+
+    assert_eq!(sessions.conversation(&session.id, None).await?.len(), 2);
+    assert_eq!(sessions.conversation(&session.id, Some("alice")).await?[1].content, "Clear.");
+    // Another owner's session is indistinguishable from a missing one
+    let err = sessions.conversation(&session.id, Some("mallory")).await.unwrap_err();
+    assert!(err.to_string().contains("not found"));
+
+    Ok(())
+}
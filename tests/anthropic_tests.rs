@@ -0,0 +1,88 @@
+#![cfg(feature = "llm-anthropic")]
+
+use vae::core::llm::anthropic::{build_multimodal_request, build_request, parse_stream_event};
+use vae::core::llm::multimodal::{openai_message, ImagePart, MultimodalMessage};
+use vae::core::llm::types::{Message, ModelConfig};
+use std::error::Error;
+
+#[test]
+fn test_request_mapping() -> Result<(), Box<dyn Error>> {
+    let config = ModelConfig {
+        temperature: 1.4,
+        max_tokens: 256,
+        top_p: 1.0,
+        frequency_penalty: 0.5,
+        presence_penalty: 0.0,
+    };
+    let messages = vec![
+        Message::new("system", "You are Lilith."),
+        Message::new("user", "Hi"),
+        Message::new("user", "Are you there?"),
+        Message::new("assistant", "Yes."),
+        Message::new("system", "Answer briefly."),
+    ];
+
+    let body = build_request("claude-sonnet-4-5", &config, &messages, true)?;
+    assert_eq!(body["system"], "You are Lilith.\n\nAnswer briefly.");
+    assert_eq!(body["messages"].as_array().unwrap().len(), 2);
+    assert_eq!(body["messages"][0]["content"], "Hi\n\nAre you there?");
+    assert_eq!(body["messages"][1]["role"], "assistant");
+    assert_eq!(body["max_tokens"], 256);
+    assert_eq!(body["temperature"], 1.0);
+    assert!(body.get("top_p").is_none());
+    assert!(body.get("frequency_penalty").is_none());
+
+    // Only system messages is not a valid request
+    assert!(build_request("claude-sonnet-4-5", &config, &messages[..1], false).is_err());
+
+    // A trimmed history that opens with the assistant still starts with a user turn
+    let trimmed = vec![messages[0].clone(), messages[3].clone(), Message::new("user", "And now?")];
+    let body = build_request("claude-sonnet-4-5", &config, &trimmed, false)?;
+    assert_eq!(body["messages"].as_array().unwrap().len(), 3);
+    assert_eq!(body["messages"][0]["role"], "user");
+    assert_eq!(body["messages"][1]["content"], "Yes.");
+
+    Ok(())
+}
+
+#[test]
+fn test_stream_event_parsing() {
+    let delta = r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello"}}"#;
+    assert_eq!(parse_stream_event(delta).unwrap(), Some("Hello".to_string()));
+    assert_eq!(parse_stream_event(r#"{"type":"ping"}"#).unwrap(), None);
+    assert!(parse_stream_event(r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#).is_err());
+}
+
+#[test]
+fn test_image_parts_mapping() -> Result<(), Box<dyn Error>> {
+    let config = ModelConfig::default();
+    let messages = vec![
+        MultimodalMessage::new("system", "Describe what the camera sees."),
+        MultimodalMessage::new("user", "Front door, 14:02")
+            .with_image(ImagePart::jpeg(&[0xff, 0xd8, 0xff]))
+            .with_image(ImagePart::url("https://example.com/frame.jpg")),
+    ];
+
+    let body = build_multimodal_request("claude-sonnet-4-5", &config, &messages, false)?;
+    let blocks = body["messages"][0]["content"].as_array().unwrap();
+    assert_eq!(blocks.len(), 3);
+    assert_eq!(blocks[0]["type"], "text");
+    assert_eq!(blocks[1]["type"], "image");
+    assert_eq!(blocks[1]["source"]["type"], "base64");
+    assert_eq!(blocks[1]["source"]["media_type"], "image/jpeg");
+    assert_eq!(blocks[1]["source"]["data"], "/9j/");
+    assert_eq!(blocks[2]["source"]["url"], "https://example.com/frame.jpg");
+
+    // Claude rejects images outside user turns
+    let assistant = vec![MultimodalMessage::new("assistant", "").with_image(ImagePart::url("https://example.com/a.jpg"))];
+    assert!(build_multimodal_request("claude-sonnet-4-5", &config, &assistant, false).is_err());
+
+    let openai = openai_message(&messages[1]);
+    assert_eq!(openai["content"][1]["type"], "image_url");
+    assert_eq!(openai["content"][1]["image_url"]["url"], "data:image/jpeg;base64,/9j/");
+    assert_eq!(openai["content"][1]["image_url"]["detail"], "auto");
+    // Text-only messages keep a plain string
+    assert_eq!(openai_message(&messages[0])["content"], "Describe what the camera sees.");
+
+    Ok(())
+}
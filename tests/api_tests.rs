@@ -1,3 +1,5 @@
+#![cfg(feature = "api")]
+
 use vae::api::{Router, handlers};
 use vae::core::agent::Lilith;
 use vae::utils::{logger, config};
@@ -242,4 +244,403 @@ fn generate_test_token(config: &config::Config) -> Result<String, Box<dyn Error>
     )?;
     
     Ok(token)
-}
\ No newline at end of file
+}
+#[actix_web::test]
+async fn test_stream_shaping_coalesces_chunks() -> Result<(), Box<dyn Error>> {
+    use futures::StreamExt;
+    use vae::api::shaping::{shape_stream, StreamShapingConfig};
+
+    let deltas = ["He", "ll", "o,", " wo", "rld", "!"]
+        .iter()
+        .map(|s| Ok::<_, anyhow::Error>(s.to_string()));
+    let config = StreamShapingConfig {
+        min_event_chars: 5,
+        max_event_chars: 4,
+        ..StreamShapingConfig::default()
+    };
+
+    let events: Vec<String> = shape_stream(futures::stream::iter(deltas), config)
+        .map(|e| e.unwrap())
+        .collect()
+        .await;
+
+    assert_eq!(events.concat(), "Hello, world!");
+    assert!(events.iter().all(|e| e.chars().count() <= 4));
+    assert!(events.len() < 6 + 2);
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn test_rate_limits_per_route_and_tenant() -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+    use vae::api::middleware::ratelimit::{
+        LimitAlgorithm, LimitSpec, RateLimit, RateLimitConfig, RateLimitRule, RateLimiter,
+    };
+    use vae::api::principal::Principal;
+
+    let rule = |name: &str, tenant: Option<&str>, requests: u32| RateLimitRule {
+        name: name.to_string(),
+        path: Some("/health".to_string()),
+        method: Some("GET".to_string()),
+        tenant: tenant.map(|t| t.to_string()),
+        api_key: None,
+        limit: LimitSpec { algorithm: LimitAlgorithm::FixedWindow, requests, window_secs: 60 },
+    };
+    let limiter = Arc::new(RateLimiter::new(RateLimitConfig {
+        default_limit: None,
+        rules: vec![rule("health", None, 2), rule("health-acme", Some("acme"), 4)],
+        ..RateLimitConfig::default()
+    })?);
+
+    let app = test::init_service(
+        App::new()
+            .wrap(RateLimit::from_limiter(limiter.clone()))
+            .service(handlers::health::health_check)
+    ).await;
+
+    let statuses = |tenant: &'static str, n: usize| {
+        let app = &app;
+        async move {
+            let mut statuses = Vec::new();
+            for _ in 0..n {
+                // A tenant header is ignored; only the authenticated one counts
+                let req = test::TestRequest::get()
+                    .uri("/health")
+                    .insert_header(("X-Tenant-Id", "acme"))
+                    .to_request();
+                req.extensions_mut().insert(Principal {
+                    subject: format!("{}-user", tenant),
+                    tenant: Some(tenant.to_string()),
+                    admin: false,
+                });
+                statuses.push(test::call_service(app, req).await.status().as_u16());
+            }
+            statuses
+        }
+    };
+
+    assert_eq!(statuses("other", 3).await, vec![200, 200, 429]);
+    // The tenant rule is more specific and has its own budget
+    assert_eq!(statuses("acme", 5).await, vec![200, 200, 200, 200, 429]);
+
+    // Raising the limit at runtime takes effect on the next request
+    limiter.upsert_rule(rule("health", None, 10)).await?;
+    assert_eq!(statuses("other", 1).await, vec![200]);
+
+    // Keys are only ever handed back hashed
+    limiter.upsert_rule(RateLimitRule { api_key: Some("secret-key".to_string()), ..rule("keyed", None, 1) }).await?;
+    let config = limiter.config().await;
+    let keyed = config.rules.iter().find(|r| r.name == "keyed").unwrap();
+    assert!(keyed.api_key.as_deref().unwrap().starts_with("sha256:"));
+    assert!(!serde_json::to_string(&config)?.contains("secret-key"));
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn test_sliding_window_and_concurrency_limits() -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+    use vae::api::middleware::ratelimit::{
+        LimitAlgorithm, LimitSpec, RateLimit, RateLimitConfig, RateLimitRule, RateLimiter, RequestInfo,
+    };
+
+    let rule = |name: &str, path: &str, algorithm, requests| RateLimitRule {
+        name: name.to_string(),
+        path: Some(path.to_string()),
+        method: None,
+        tenant: None,
+        api_key: None,
+        limit: LimitSpec { algorithm, requests, window_secs: 60 },
+    };
+    let limiter = RateLimiter::new(RateLimitConfig {
+        default_limit: None,
+        rules: vec![
+            rule("search", "/v1/vision/search", LimitAlgorithm::SlidingWindow, 2),
+            rule("analyze", "/v1/vision/analyze", LimitAlgorithm::Concurrency, 2),
+        ],
+        ..RateLimitConfig::default()
+    })?;
+    let request = |path: &str| RequestInfo {
+        path: path.to_string(),
+        method: "POST".to_string(),
+        subject: Some("client-1".to_string()),
+        tenant: None,
+        api_key: Some("key-1".to_string()),
+        peer: None,
+    };
+
+    let search = request("/v1/vision/search");
+    assert!(limiter.check(&search).await.allowed);
+    assert!(limiter.check(&search).await.allowed);
+    let limited = limiter.check(&search).await;
+    assert!(!limited.allowed);
+    assert!(limited.retry_after_secs >= 1 && limited.retry_after_secs <= 60);
+
+    // Slots are held until the permit is dropped, not for a time window
+    let analyze = request("/v1/vision/analyze");
+    let first = limiter.check(&analyze).await;
+    let second = limiter.check(&analyze).await;
+    assert!(first.allowed && second.allowed);
+    assert!(!limiter.check(&analyze).await.allowed);
+    drop(first);
+    let third = limiter.check(&analyze).await;
+    assert!(third.allowed);
+    assert_eq!(third.remaining, 0);
+    drop((second, third));
+
+    // Behind the middleware a streamed response keeps its slot until the
+    // body is done, not just until the handler returns
+    let app = test::init_service(
+        App::new()
+            .wrap(RateLimit::from_limiter(Arc::new(limiter)))
+            .route("/v1/vision/analyze", web::post().to(|| async {
+                actix_web::HttpResponse::Ok().streaming(futures::stream::pending::<Result<web::Bytes, actix_web::Error>>())
+            }))
+    ).await;
+    let analyze = || test::TestRequest::post().uri("/v1/vision/analyze").to_request();
+    let first = test::call_service(&app, analyze()).await;
+    let second = test::call_service(&app, analyze()).await;
+    assert!(first.status().is_success() && second.status().is_success());
+    assert_eq!(test::call_service(&app, analyze()).await.status(), 429);
+    drop(first);
+    assert!(test::call_service(&app, analyze()).await.status().is_success());
+    drop(second);
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn test_quota_blocks_metered_routes_when_spent() -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+    use futures::StreamExt;
+    use vae::api::middleware::quota::{MeteredUsage, Quota, StreamedUsage};
+    use vae::api::principal::Principal;
+    use vae::core::usage::{Meter, MeteredPath, QuotaLimit, QuotaPeriod, QuotaPlan, UsageConfig, UsageLedger};
+
+    let ledger = Arc::new(UsageLedger::new(UsageConfig {
+        default_plan: QuotaPlan {
+            name: "trial".to_string(),
+            limits: vec![
+                QuotaLimit { meter: Meter::Tokens, period: QuotaPeriod::Daily, limit: 100 },
+                QuotaLimit { meter: Meter::Tokens, period: QuotaPeriod::Monthly, limit: 1000 },
+            ],
+        },
+        metered_paths: vec![MeteredPath { path: "/v1/agent/*".to_string(), meter: Meter::Tokens }],
+        ..UsageConfig::default()
+    }).await?);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(ledger.clone()))
+            .wrap(Quota::new(ledger.clone()))
+            .service(handlers::usage::get_quota)
+            .route("/v1/agent/echo", web::post().to(|| async {
+                let mut response = actix_web::HttpResponse::Ok().finish();
+                response.extensions_mut().insert(MeteredUsage { tokens: 60, frames: 0 });
+                response
+            }))
+            .route("/v1/agent/stream", web::post().to(|| async {
+                let usage = StreamedUsage::default();
+                let counter = usage.counter();
+                let body = futures::stream::iter(["a", "b"]).map(move |delta| {
+                    counter.fetch_add(15, std::sync::atomic::Ordering::Relaxed);
+                    Ok::<_, actix_web::Error>(web::Bytes::from(delta))
+                });
+                usage.attach(actix_web::HttpResponse::Ok().streaming(body))
+            }))
+    ).await;
+
+    let acme = || Principal {
+        subject: "svc-1".to_string(),
+        tenant: Some("acme".to_string()),
+        admin: false,
+    };
+    let call = || {
+        let req = test::TestRequest::post().uri("/v1/agent/echo").to_request();
+        req.extensions_mut().insert(acme());
+        req
+    };
+
+    let resp = test::call_service(&app, call()).await;
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get("x-quota-remaining").unwrap(), "40");
+    assert_eq!(resp.headers().get("x-quota-period").unwrap(), "daily");
+
+    // Second call overshoots the daily budget, the third is refused
+    assert!(test::call_service(&app, call()).await.status().is_success());
+    let resp = test::call_service(&app, call()).await;
+    assert_eq!(resp.status(), 429);
+    assert_eq!(resp.headers().get("x-quota-remaining").unwrap(), "0");
+
+    // The tenant comes from the principal; a header can't switch budgets
+    let spoofed = test::TestRequest::post()
+        .uri("/v1/agent/echo")
+        .insert_header(("X-Tenant-Id", "other"))
+        .to_request();
+    spoofed.extensions_mut().insert(acme());
+    assert_eq!(test::call_service(&app, spoofed).await.status(), 429);
+
+    let req = test::TestRequest::get().uri("/v1/usage/quota").to_request();
+    req.extensions_mut().insert(acme());
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["tenant"], "acme");
+    assert_eq!(body["plan"], "trial");
+    assert_eq!(body["quotas"][1]["used"], 120);
+    assert_eq!(body["quotas"][1]["remaining"], 880);
+
+    // A reservation holds the rest of the budget until it is settled, so a
+    // concurrent request is refused rather than let through
+    let held = ledger.reserve("beta", Meter::Tokens).await.map_err(|_| "beta refused")?;
+    assert_eq!(held, 100);
+    assert!(ledger.reserve("beta", Meter::Tokens).await.is_err());
+    ledger.settle("beta", Meter::Tokens, held, 30).await;
+    assert_eq!(ledger.reserve("beta", Meter::Tokens).await.map_err(|_| "beta refused")?, 70);
+
+    // A streamed reply keeps its reservation until the body is done, then
+    // settles with the tokens it counted
+    let req = test::TestRequest::post().uri("/v1/agent/stream").to_request();
+    req.extensions_mut().insert(Principal { subject: "svc-2".to_string(), tenant: Some("gamma".to_string()), admin: false });
+    let resp = test::call_service(&app, req).await;
+    assert!(ledger.reserve("gamma", Meter::Tokens).await.is_err());
+    assert_eq!(test::read_body(resp).await, "ab");
+    tokio::task::yield_now().await;
+    assert_eq!(ledger.reserve("gamma", Meter::Tokens).await.map_err(|_| "gamma refused")?, 70);
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn test_maintenance_mode_blocks_writes() -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use vae::api::middleware::maintenance::{Maintenance, MaintenanceConfig, MaintenanceMode};
+
+    let mode = Arc::new(MaintenanceMode::new(MaintenanceConfig::default()));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(mode.clone()))
+            .wrap(Maintenance::new(mode.clone()))
+            .service(handlers::health::health_check)
+            .service(handlers::maintenance::set_maintenance)
+            .route("/v1/rules", web::get().to(|| async { actix_web::HttpResponse::Ok().finish() }))
+            .route("/v1/rules", web::post().to(|| async { actix_web::HttpResponse::Created().finish() }))
+            .route("/v1/export", web::post().to(|| async {
+                actix_web::HttpResponse::Ok().streaming(futures::stream::pending::<Result<web::Bytes, actix_web::Error>>())
+            }))
+    ).await;
+
+    let post = || test::TestRequest::post().uri("/v1/rules").to_request();
+    assert_eq!(test::call_service(&app, post()).await.status(), 201);
+
+    let req = test::TestRequest::put()
+        .uri("/v1/admin/maintenance")
+        .set_json(json!({ "enabled": true, "message": "Upgrading to 2.4", "drain_timeout_secs": 1 }))
+        .to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["drained"], true);
+    assert_eq!(body["status"]["enabled"], true);
+
+    let resp = test::call_service(&app, post()).await;
+    assert_eq!(resp.status(), 503);
+    assert!(resp.headers().contains_key("retry-after"));
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Upgrading to 2.4");
+
+    // Reads and probes keep working
+    let req = test::TestRequest::get().uri("/v1/rules").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+    let req = test::TestRequest::get().uri("/health").to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    mode.disable().await;
+    assert_eq!(test::call_service(&app, post()).await.status(), 201);
+    assert!(mode.drain(Duration::from_millis(10)).await);
+
+    // A streamed response counts until its body is done, not when the handler returns
+    let req = test::TestRequest::post().uri("/v1/export").to_request();
+    let streaming = test::call_service(&app, req).await;
+    assert_eq!(mode.status().await.in_flight, 1);
+    assert!(!mode.drain(Duration::from_millis(10)).await);
+    drop(streaming);
+    assert!(mode.drain(Duration::from_millis(10)).await);
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn test_sse_disconnect_cancels_the_completion() -> Result<(), Box<dyn Error>> {
+    use actix_web::body::MessageBody;
+    use tokio_util::sync::CancellationToken;
+    use vae::api::sse::{event, stream_response};
+
+    assert_eq!(&event(None, "a\nb")[..], b"data: a\ndata: b\n\n");
+    assert_eq!(&event(Some("error"), "x")[..], b"event: error\ndata: x\n\n");
+
+    // The client goes away mid-stream: actix drops the body
+    let cancel = CancellationToken::new();
+    let endless = futures::stream::repeat_with(|| Ok::<_, anyhow::Error>("token".to_string()));
+    let response = stream_response(endless, cancel.clone());
+    let mut body = Box::pin(response.into_body());
+    let first = futures::future::poll_fn(|cx| body.as_mut().poll_next(cx)).await;
+    assert_eq!(&first.unwrap().unwrap()[..], b"data: token\n\n");
+    assert!(!cancel.is_cancelled());
+    drop(body);
+    assert!(cancel.is_cancelled());
+
+    // A stream that finishes leaves the token alone
+    let cancel = CancellationToken::new();
+    let finite = futures::stream::iter(vec![Ok::<_, anyhow::Error>("done".to_string())]);
+    let response = stream_response(finite, cancel.clone());
+    let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+    assert_eq!(&body[..], b"data: done\n\n");
+    assert!(!cancel.is_cancelled());
+    Ok(())
+}
+
+#[actix_web::test]
+async fn test_files_are_scoped_to_their_owner() -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+    use vae::api::principal::Principal;
+    use vae::core::storage::{FileStore, FileStoreConfig, LocalStorage};
+
+    let root = std::env::temp_dir().join(format!("vae-files-{}", uuid::Uuid::new_v4()));
+    let backend = Arc::new(LocalStorage::new(root.to_str().unwrap()).await?);
+    let store = Arc::new(FileStore::new(FileStoreConfig::default(), backend).await?);
+    let page = store.save("page.html", "text/html", b"<script>alert(1)</script>", "alice", None).await?;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .service(handlers::files::get_file)
+            .service(handlers::files::get_file_content)
+            .service(handlers::files::delete_file)
+    ).await;
+    let as_user = |req: test::TestRequest, subject: &str| {
+        let req = req.to_request();
+        req.extensions_mut().insert(Principal { subject: subject.to_string(), tenant: None, admin: false });
+        req
+    };
+
+    // Someone else's file looks missing, for every operation
+    let uri = format!("/v1/files/{}", page.id);
+    let req = as_user(test::TestRequest::get().uri(&uri), "mallory");
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+    let req = as_user(test::TestRequest::get().uri(&format!("{}/content", uri)), "mallory");
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+    let req = as_user(test::TestRequest::delete().uri(&uri), "mallory");
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+    // The owner gets content as a download, never rendered inline
+    let req = as_user(test::TestRequest::get().uri(&format!("{}/content", uri)), "alice");
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert!(resp.headers().get("content-disposition").unwrap().to_str()?.starts_with("attachment"));
+    assert_eq!(resp.headers().get("x-content-type-options").unwrap(), "nosniff");
+
+    let req = as_user(test::TestRequest::delete().uri(&uri), "alice");
+    assert_eq!(test::call_service(&app, req).await.status(), 204);
+
+    std::fs::remove_dir_all(root)?;
+    Ok(())
+}
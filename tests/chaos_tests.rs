@@ -0,0 +1,158 @@
+#![cfg(feature = "chaos")]
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use futures::Stream;
+use std::error::Error;
+
+use vae::core::chaos::{chaos_channel, ChaosConfig, ChaosLLM, FaultInjector, FaultRule};
+use vae::core::llm::LLMTrait;
+use vae::core::llm::router::{LLMRouter, RouterConfig};
+use vae::core::llm::types::{Message, ModelConfig, Response, StreamChunk, Usage};
+
+struct Echo(String);
+
+#[async_trait]
+impl LLMTrait for Echo {
+    async fn complete(&self, messages: Vec<Message>) -> anyhow::Result<Response> {
+        Ok(Response {
+            content: messages.last().map(|m| m.content.clone()).unwrap_or_default(),
+            role: "assistant".to_string(),
+            model: self.0.clone(),
+            usage: Usage { prompt_tokens: 1, completion_tokens: 1, total_tokens: 2 },
+        })
+    }
+
+    async fn complete_stream(
+        &self,
+        _messages: Vec<Message>,
+        _cancel: tokio_util::sync::CancellationToken,
+    ) -> anyhow::Result<Pin<Box<dyn Stream<Item = anyhow::Result<StreamChunk>> + Send>>> {
+        Ok(Box::pin(futures::stream::iter(vec![Ok(StreamChunk { content: self.0.clone() })])))
+    }
+
+    fn is_initialized(&self) -> bool { true }
+    fn get_model(&self) -> &str { &self.0 }
+    fn set_model_config(&mut self, _config: ModelConfig) {}
+    fn get_model_config(&self) -> ModelConfig { ModelConfig::default() }
+}
+
+fn injector(rules: Vec<FaultRule>) -> Arc<FaultInjector> {
+    Arc::new(FaultInjector::new(ChaosConfig { enabled: true, seed: Some(7), rules }))
+}
+
+#[tokio::test]
+async fn test_injected_llm_outage_fails_over() -> Result<(), Box<dyn Error>> {
+    let faults = injector(vec![FaultRule {
+        target: "llm.primary".to_string(),
+        error_rate: 1.0,
+        max_faults: Some(1),
+        ..FaultRule::default()
+    }]);
+    let router = LLMRouter::new(RouterConfig { cooldown_secs: 0, ..RouterConfig::default() })
+        .with_provider("primary", Box::new(ChaosLLM::new(Box::new(Echo("primary".to_string())), faults.clone(), "primary")))
+        .with_provider("backup", Box::new(ChaosLLM::new(Box::new(Echo("backup".to_string())), faults.clone(), "backup")));
+
+    let response = router.complete(vec![Message::new("user", "ping")]).await?;
+    assert_eq!(response.model, "backup");
+
+    // The outage was limited to one fault, so the primary recovers
+    let response = router.complete(vec![Message::new("user", "ping")]).await?;
+    assert_eq!(response.model, "primary");
+
+    let stats = faults.stats();
+    assert_eq!(stats["llm.primary"].failed, 1);
+    assert_eq!(stats["llm.primary"].calls, 2);
+    assert!(!stats.contains_key("llm.backup"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_channel_latency_and_skip_first() -> Result<(), Box<dyn Error>> {
+    let faults = injector(vec![FaultRule {
+        target: "channel.*".to_string(),
+        latency_ms: 30,
+        skip_first: 1,
+        ..FaultRule::default()
+    }]);
+    let (tx, mut rx) = chaos_channel::<u32>(4, faults.clone(), "frames");
+
+    let started = Instant::now();
+    tx.send(1).await?;
+    assert!(started.elapsed() < Duration::from_millis(30));
+
+    let started = Instant::now();
+    tx.send(2).await?;
+    assert!(started.elapsed() >= Duration::from_millis(30));
+
+    assert_eq!(rx.recv().await, Some(1));
+    assert_eq!(rx.recv().await, Some(2));
+    assert_eq!(faults.stats()["channel.frames"].delayed, 1);
+
+    // Disabled config injects nothing
+    let disabled = FaultInjector::new(ChaosConfig {
+        enabled: false,
+        seed: None,
+        rules: vec![FaultRule { error_rate: 1.0, ..FaultRule::default() }],
+    });
+    assert!(disabled.inject("gpu.yolo").await.is_ok());
+
+    Ok(())
+}
+
+// The only test that installs the process-wide injector
+#[cfg(feature = "vision")]
+#[tokio::test]
+async fn test_installed_injector_reaches_pipeline_input() -> Result<(), Box<dyn Error>> {
+    use opencv::core::Mat;
+    use vae::core::pipeline::{Pipeline, PipelineConfig, PipelineData, PipelineStage, StageType};
+    use vae::vision::processor::{Frame, FrameMetadata};
+
+    struct PassThrough;
+
+    #[async_trait]
+    impl PipelineStage for PassThrough {
+        async fn process(&self, input: PipelineData) -> anyhow::Result<PipelineData> {
+            Ok(input)
+        }
+
+        fn stage_type(&self) -> StageType { StageType::PostProcess }
+        fn name(&self) -> String { "pass".to_string() }
+    }
+
+    let installed = FaultInjector::install(ChaosConfig {
+        enabled: true,
+        seed: Some(7),
+        rules: vec![FaultRule { target: "channel.pipeline.input".to_string(), error_rate: 1.0, ..FaultRule::default() }],
+    });
+    // A second install keeps the first injector
+    let again = FaultInjector::install(ChaosConfig::default());
+    assert!(Arc::ptr_eq(&installed, &again));
+    assert!(FaultInjector::global().is_some_and(|global| Arc::ptr_eq(&global, &installed)));
+
+    let config = PipelineConfig::from_json(
+        r#"{"stages": [], "max_parallel_stages": 1, "buffer_size": 4, "timeout_ms": 1000, "retry_count": 0}"#,
+    )?;
+    let pipeline = Pipeline::with_stages(config, vec![Arc::new(PassThrough) as Arc<dyn PipelineStage>]).await?;
+    let frame = Frame {
+        id: 1,
+        timestamp: chrono::Utc::now(),
+        data: Arc::new(Mat::default()),
+        metadata: FrameMetadata {
+            width: 640,
+            height: 480,
+            channels: 3,
+            format: "bgr".to_string(),
+            source: "chaos".to_string(),
+            privacy_masked: false,
+            stream_id: None,
+        },
+    };
+    assert!(pipeline.process(frame).await.is_err());
+    assert_eq!(installed.stats()["channel.pipeline.input"].failed, 1);
+
+    Ok(())
+}
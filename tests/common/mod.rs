@@ -0,0 +1,142 @@
+// Helpers shared by the integration tests; `mod common;` pulls them in.
+// Not every test file uses every helper.
+#![allow(dead_code)]
+
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use futures::Stream;
+use tokio_util::sync::CancellationToken;
+use vae::core::llm::LLMTrait;
+use vae::core::llm::cancel::until_cancelled;
+use vae::core::llm::multimodal::{MultimodalLLM, MultimodalMessage};
+use vae::core::llm::overrides::for_call;
+use vae::core::llm::types::{Message, ModelConfig, Response, StreamChunk, Usage};
+
+pub type ChunkStream = Pin<Box<dyn Stream<Item = anyhow::Result<StreamChunk>> + Send>>;
+type Reply = Box<dyn Fn(&[Message], usize) -> anyhow::Result<String> + Send + Sync>;
+type Streaming = Box<dyn Fn() -> ChunkStream + Send + Sync>;
+
+// One request as the stub saw it, with any per-call override applied
+#[derive(Debug, Clone)]
+pub struct Call {
+    pub model: String,
+    pub config: ModelConfig,
+    pub messages: Vec<Message>,
+}
+
+// Can be shared between stubs that should count as one provider
+pub type CallLog = Arc<Mutex<Vec<Call>>>;
+
+// A model that answers from a closure instead of a provider. The reply
+// gets the messages and how many calls the log held before this one; an
+// Err fails the call. Streams send the reply as a single chunk, unless
+// `streaming` replaces them, and honour cancellation like a provider.
+pub struct StubLLM {
+    model: String,
+    config: ModelConfig,
+    // Prompt and completion tokens reported per call
+    usage: (u32, u32),
+    reply: Reply,
+    streaming: Option<Streaming>,
+    log: CallLog,
+}
+
+impl StubLLM {
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            config: ModelConfig::default(),
+            usage: (1, 1),
+            reply: Box::new(|_, _| Ok("ok".to_string())),
+            streaming: None,
+            log: CallLog::default(),
+        }
+    }
+
+    pub fn replying(mut self, reply: impl Fn(&[Message], usize) -> anyhow::Result<String> + Send + Sync + 'static) -> Self {
+        self.reply = Box::new(reply);
+        self
+    }
+
+    pub fn streaming(mut self, stream: impl Fn() -> ChunkStream + Send + Sync + 'static) -> Self {
+        self.streaming = Some(Box::new(stream));
+        self
+    }
+
+    pub fn with_usage(mut self, prompt_tokens: u32, completion_tokens: u32) -> Self {
+        self.usage = (prompt_tokens, completion_tokens);
+        self
+    }
+
+    pub fn with_config(mut self, config: ModelConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn recording(mut self, log: CallLog) -> Self {
+        self.log = log;
+        self
+    }
+
+    pub fn calls(&self) -> Vec<Call> {
+        self.log.lock().unwrap().clone()
+    }
+
+    pub fn call_count(&self) -> usize {
+        self.log.lock().unwrap().len()
+    }
+
+    fn record(&self, messages: &[Message]) -> (String, anyhow::Result<String>) {
+        let (model, config) = for_call(&self.model, &self.config);
+        let previous = {
+            let mut log = self.log.lock().unwrap();
+            log.push(Call { model: model.clone(), config, messages: messages.to_vec() });
+            log.len() - 1
+        };
+        (model, (self.reply)(messages, previous))
+    }
+}
+
+#[async_trait]
+impl LLMTrait for StubLLM {
+    async fn complete(&self, messages: Vec<Message>) -> anyhow::Result<Response> {
+        let (model, reply) = self.record(&messages);
+        Ok(Response {
+            content: reply?,
+            role: "assistant".to_string(),
+            model,
+            usage: Usage {
+                prompt_tokens: self.usage.0,
+                completion_tokens: self.usage.1,
+                total_tokens: self.usage.0 + self.usage.1,
+            },
+        })
+    }
+
+    async fn complete_stream(&self, messages: Vec<Message>, cancel: CancellationToken) -> anyhow::Result<ChunkStream> {
+        if cancel.is_cancelled() {
+            anyhow::bail!("Completion cancelled");
+        }
+        let (_, reply) = self.record(&messages);
+        if let Some(stream) = &self.streaming {
+            return Ok(until_cancelled(stream(), cancel));
+        }
+        let chunk = reply.map(|content| StreamChunk { content });
+        Ok(until_cancelled(Box::pin(futures::stream::iter(vec![chunk])), cancel))
+    }
+
+    fn is_initialized(&self) -> bool { true }
+    fn get_model(&self) -> &str { &self.model }
+    fn set_model_config(&mut self, config: ModelConfig) { self.config = config; }
+    fn get_model_config(&self) -> ModelConfig { self.config.clone() }
+}
+
+// Images are dropped; the reply sees the text parts as plain messages
+#[async_trait]
+impl MultimodalLLM for StubLLM {
+    async fn complete_multimodal(&self, messages: Vec<MultimodalMessage>) -> anyhow::Result<Response> {
+        let messages = messages.iter().map(|m| Message::new(&m.role, &m.text())).collect();
+        self.complete(messages).await
+    }
+}
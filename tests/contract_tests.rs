@@ -0,0 +1,526 @@
+#![cfg(feature = "api")]
+
+// Response contracts. Each case snapshots the shape of a handler's JSON
+// (field paths and value types, not values) into tests/snapshots, so a
+// renamed, removed or retyped field shows up as a snapshot diff in review.
+// After an intentional change, run `cargo insta review` and commit the
+// updated .snap files. New handlers should add a case here.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use actix_web::{test, web, App};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::error::Error;
+
+use vae::api::handlers;
+use vae::api::principal::Principal;
+use vae::api::middleware::maintenance::{MaintenanceConfig, MaintenanceMode};
+use vae::api::middleware::ratelimit::{
+    LimitAlgorithm, LimitSpec, RateLimitConfig, RateLimitRule, RateLimiter,
+};
+use vae::core::agent::canary::{AgentVariant, CanaryRouter};
+use vae::core::agent::sessions::{CreateSession, SessionConfig, SessionManager};
+use vae::core::alerts::{Alert, AlertConfig, AlertManager};
+use vae::core::i18n::{LanguageConfig, Localizer};
+use vae::core::incidents::{EvidenceClip, IncidentConfig, IncidentManager};
+use vae::core::jobs::JobManager;
+use vae::core::lifecycle::{DataCategory, DataSubject, LifecycleConfig, LifecycleManager};
+use vae::core::llm::latency::{LatencyTracker, LatencyTrackerConfig, SloConfig};
+use vae::core::llm::types::Message;
+use vae::core::prompts::{NewPromptVersion, PromptKind, PromptLibrary, PromptLibraryConfig, PromptRef};
+use vae::core::remediation::{
+    RemediationAction, RemediationConfig, RemediationExecutor, RemediationPolicy, Remediator,
+};
+use vae::core::notify::Severity;
+use vae::core::usage::{UsageConfig, UsageLedger};
+
+// One line per leaf: `path: type`. Object keys are sorted and arrays are
+// described by their first element, so the output only changes when the
+// schema does. A null says nothing about the type a field has when set,
+// so optional fields are declared in `nullable` with that type and come
+// out as `path: type?` whether this response filled them or not. An
+// undeclared null, or a declared field that never shows up, fails the case.
+fn schema(value: &Value, nullable: &[(&str, &str)]) -> String {
+    fn type_name(value: &Value) -> &'static str {
+        match value {
+            Value::Object(_) => "object",
+            Value::Array(_) => "array",
+            Value::String(_) => "string",
+            Value::Number(_) => "number",
+            Value::Bool(_) => "bool",
+            Value::Null => "null",
+        }
+    }
+
+    fn walk(value: &Value, path: &str, nullable: &[(&str, &str)], seen: &mut Vec<String>, lines: &mut Vec<String>) {
+        if let Some((_, declared)) = nullable.iter().find(|(p, _)| *p == path) {
+            seen.push(path.to_string());
+            match value {
+                Value::Null => {
+                    lines.push(format!("{}: {}?", path, declared));
+                    return;
+                }
+                Value::Object(_) | Value::Array(_) => {}
+                _ => {
+                    assert_eq!(type_name(value), *declared, "{} is declared as {}", path, declared);
+                    lines.push(format!("{}: {}?", path, declared));
+                    return;
+                }
+            }
+        }
+
+        match value {
+            Value::Object(map) => {
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                for key in keys {
+                    let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                    walk(&map[key], &child, nullable, seen, lines);
+                }
+            }
+            Value::Array(items) => match items.first() {
+                Some(first) => walk(first, &format!("{}[]", path), nullable, seen, lines),
+                None => lines.push(format!("{}: empty array", path)),
+            },
+            Value::Null => panic!("{} is null; fill it in the fixture or declare its type in nullable", path),
+            _ => lines.push(format!("{}: {}", path, type_name(value))),
+        }
+    }
+
+    let mut seen = Vec::new();
+    let mut lines = Vec::new();
+    walk(value, "", nullable, &mut seen, &mut lines);
+    for (path, _) in nullable {
+        assert!(seen.iter().any(|s| s == path), "{} is declared nullable but not in the response", path);
+    }
+    lines.join("\n")
+}
+
+fn variant(model: &str, version: u32) -> AgentVariant {
+    AgentVariant {
+        model: model.to_string(),
+        prompt: PromptRef { name: "lilith-system".to_string(), version: Some(version) },
+        prompt_price: 0.0025,
+        completion_price: 0.01,
+    }
+}
+
+struct NoopExecutor;
+
+#[async_trait]
+impl RemediationExecutor for NoopExecutor {
+    async fn execute(&self, _action: &RemediationAction) -> anyhow::Result<String> {
+        Ok("noop".to_string())
+    }
+}
+
+#[actix_web::test]
+async fn test_canary_contracts() -> Result<(), Box<dyn Error>> {
+    let router = Arc::new(CanaryRouter::new(variant("gpt-4o", 3)));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(router.clone()))
+            .service(handlers::canary::get_canary)
+            .service(handlers::canary::start_canary)
+            .service(handlers::canary::promote_canary)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/v1/admin/canary").to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    insta::assert_snapshot!("canary_idle", schema(&body, &[]));
+
+    // Errors share one shape across handlers
+    let req = test::TestRequest::post().uri("/v1/admin/canary/promote").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 409);
+    let body: Value = test::read_body_json(resp).await;
+    insta::assert_snapshot!("error_response", schema(&body, &[]));
+
+    let req = test::TestRequest::post()
+        .uri("/v1/admin/canary")
+        .set_json(json!({
+            "baseline": variant("gpt-4o", 3),
+            "candidate": variant("gpt-4o-mini", 4),
+            "percent": 10,
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let body: Value = test::read_body_json(resp).await;
+    // Neither arm has scored sessions yet, and the canary is still running
+    let nullable = [
+        ("baseline.cost_per_session", "number"),
+        ("baseline.mean_quality", "number"),
+        ("baseline.p50_latency_ms", "number"),
+        ("baseline.p95_latency_ms", "number"),
+        ("candidate.cost_per_session", "number"),
+        ("candidate.mean_quality", "number"),
+        ("candidate.p50_latency_ms", "number"),
+        ("candidate.p95_latency_ms", "number"),
+        ("end_reason", "string"),
+        ("ended_at", "string"),
+    ];
+    insta::assert_snapshot!("canary_report", schema(&body, &nullable));
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn test_admin_contracts() -> Result<(), Box<dyn Error>> {
+    let limiter = Arc::new(RateLimiter::new(RateLimitConfig {
+        rules: vec![RateLimitRule {
+            name: "acme-vision".to_string(),
+            path: Some("/v1/vision/*".to_string()),
+            method: None,
+            tenant: Some("acme".to_string()),
+            api_key: None,
+            limit: LimitSpec { algorithm: LimitAlgorithm::Concurrency, requests: 4, window_secs: 0 },
+        }],
+        ..RateLimitConfig::default()
+    })?);
+    let mode = Arc::new(MaintenanceMode::new(MaintenanceConfig::default()));
+    let remediator = Arc::new(Remediator::new(RemediationConfig {
+        policies: vec![RemediationPolicy {
+            name: "restart-stalled".to_string(),
+            alert_name: Some("stream_stalled".to_string()),
+            rule_id: None,
+            min_severity: Severity::Warning,
+            actions: vec![RemediationAction::RestartStream { stream_id: "{stream_id}".to_string() }],
+            cooldown_secs: 600,
+        }],
+        ..RemediationConfig::default()
+    }, Arc::new(NoopExecutor)).await?);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(limiter))
+            .app_data(web::Data::new(mode))
+            .app_data(web::Data::new(remediator))
+            .service(handlers::ratelimits::get_rate_limits)
+            .service(handlers::maintenance::get_maintenance)
+            .service(handlers::remediation::get_remediation)
+    ).await;
+
+    let cases: [(&str, &str, &[(&str, &str)]); 3] = [
+        ("rate_limits", "/v1/admin/rate-limits", &[("rules[].api_key", "string"), ("rules[].method", "string")]),
+        ("maintenance", "/v1/admin/maintenance", &[("since", "string")]),
+        ("remediation", "/v1/remediation", &[("policies[].rule_id", "string")]),
+    ];
+    for (name, uri, nullable) in cases {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        insta::assert_snapshot!(name, schema(&body, nullable));
+    }
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn test_usage_contracts() -> Result<(), Box<dyn Error>> {
+    let ledger = Arc::new(UsageLedger::new(UsageConfig::default()).await?);
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(ledger))
+            .service(handlers::usage::get_quota)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/v1/usage/quota").to_request();
+    req.extensions_mut().insert(Principal {
+        subject: "svc-1".to_string(),
+        tenant: Some("acme".to_string()),
+        admin: false,
+    });
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    insta::assert_snapshot!("usage_quota", schema(&body, &[]));
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn test_incident_contracts() -> Result<(), Box<dyn Error>> {
+    let incidents = Arc::new(IncidentManager::new(IncidentConfig::default()));
+    let now = chrono::Utc::now();
+    let incident = incidents.ingest_alert(&Alert {
+        key: "stream_stalled:dock".to_string(),
+        name: "stream_stalled".to_string(),
+        severity: Severity::Warning,
+        message: "No frames from dock for 60s".to_string(),
+        labels: HashMap::from([("stream_id".to_string(), "dock".to_string())]),
+        raised_at: now,
+        last_seen: now,
+        resolved_at: None,
+        occurrences: 1,
+    }).await;
+    incidents.acknowledge(&incident.id, "alice").await?;
+    incidents.assign(&incident.id, Some("alice".to_string())).await?;
+    incidents.add_note(&incident.id, "alice", "Camera power cycled").await?;
+    incidents.attach_evidence(&incident.id, EvidenceClip {
+        stream_id: "dock".to_string(),
+        uri: "s3://recordings/dock/0001.mp4".to_string(),
+        start: now,
+        end: now,
+        description: Some("Last frames before the stall".to_string()),
+    }).await?;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(incidents))
+            .service(handlers::incidents::get_incident)
+    ).await;
+
+    let req = test::TestRequest::get().uri(&format!("/v1/incidents/{}", incident.id)).to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    insta::assert_snapshot!("incident", schema(&body, &[("resolved_at", "string")]));
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn test_job_contracts() -> Result<(), Box<dyn Error>> {
+    let jobs = Arc::new(JobManager::new());
+    let job = jobs.spawn("reembed", |handle| async move {
+        handle.set_total(10).await;
+        handle.advance(10).await;
+        handle.set_message("Re-embedded 10 documents").await;
+        Ok(())
+    }).await;
+    for _ in 0..100 {
+        if jobs.get(&job.id).await.is_some_and(|j| j.finished_at.is_some()) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(jobs))
+            .service(handlers::jobs::get_job)
+    ).await;
+
+    let req = test::TestRequest::get().uri(&format!("/v1/jobs/{}", job.id)).to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    insta::assert_snapshot!("job", schema(&body, &[("error", "string")]));
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn test_prompt_contracts() -> Result<(), Box<dyn Error>> {
+    let library = Arc::new(PromptLibrary::new(PromptLibraryConfig { persist: false, ..Default::default() }).await?);
+    library.publish("lilith-system", NewPromptVersion {
+        kind: PromptKind::System,
+        content: "You are {{ persona }}.".to_string(),
+        description: Some("Agent system prompt".to_string()),
+        author: Some("alice".to_string()),
+        note: Some("Initial version".to_string()),
+        activate: true,
+    }).await?;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(library))
+            .service(handlers::prompts::get_prompt)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/v1/prompts/lilith-system").to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    insta::assert_snapshot!("prompt", schema(&body, &[]));
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn test_session_contracts() -> Result<(), Box<dyn Error>> {
+    let sessions = Arc::new(SessionManager::new(SessionConfig::default()).await?);
+    let session = sessions.create("svc-1", Some("acme"), CreateSession {
+        title: Some("Dock camera".to_string()),
+        metadata: HashMap::from([("camera".to_string(), "dock".to_string())]),
+    }).await?;
+    sessions.record_turn(&session.id, Message::new("user", "hi"), Message::new("assistant", "hello")).await?;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(sessions))
+            .service(handlers::sessions::list_sessions)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/v1/sessions").to_request();
+    req.extensions_mut().insert(Principal {
+        subject: "svc-1".to_string(),
+        tenant: Some("acme".to_string()),
+        admin: false,
+    });
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    insta::assert_snapshot!("sessions", schema(&body, &[("[].summary", "string")]));
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn test_slo_contracts() -> Result<(), Box<dyn Error>> {
+    let tracker = Arc::new(LatencyTracker::new(LatencyTrackerConfig {
+        slos: vec![SloConfig {
+            provider: "openai".to_string(),
+            model: "gpt-4o".to_string(),
+            p95_ttft_ms: 800.0,
+            p95_completion_ms: 4000.0,
+            objective: 0.95,
+        }],
+        ..LatencyTrackerConfig::default()
+    }));
+    tracker.record("openai", "gpt-4o", Some(420.0), 1800.0).await;
+    let alerts = Arc::new(AlertManager::new(AlertConfig::default(), None));
+    alerts.raise(
+        "llm_latency:openai",
+        "llm_latency_slo",
+        Severity::Warning,
+        "gpt-4o is burning its latency budget",
+        HashMap::from([("provider".to_string(), "openai".to_string())]),
+    ).await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(tracker))
+            .app_data(web::Data::new(alerts))
+            .service(handlers::slo::llm_latency)
+            .service(handlers::slo::alerts)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/v1/metrics/llm/latency").to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    insta::assert_snapshot!("llm_latency", schema(&body, &[]));
+
+    let req = test::TestRequest::get().uri("/v1/alerts").to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    insta::assert_snapshot!("alerts", schema(&body, &[("active[].resolved_at", "string")]));
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn test_language_contracts() -> Result<(), Box<dyn Error>> {
+    let localizer = Arc::new(Localizer::new(LanguageConfig {
+        supported: vec!["en".to_string(), "de".to_string()],
+        ..LanguageConfig::default()
+    }).await?);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(localizer))
+            .service(handlers::language::get_languages)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/v1/languages").to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    insta::assert_snapshot!("languages", schema(&body, &[]));
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn test_data_contracts() -> Result<(), Box<dyn Error>> {
+    let dir = std::env::temp_dir().join(format!("vae-contracts-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir)?;
+    let sessions = Arc::new(SessionManager::new(SessionConfig::default()).await?);
+    let session = sessions.create("svc-1", None, CreateSession::default()).await?;
+    let mut lifecycle = LifecycleManager::new(LifecycleConfig {
+        audit_file: dir.join("audit.jsonl").to_string_lossy().into_owned(),
+        required: vec![DataCategory::Conversations],
+    }).await?;
+    lifecycle.register_store(sessions);
+    let subject = DataSubject::Session(session.id.clone());
+    lifecycle.delete_subject(&subject, "dpo", Some("Erasure request".to_string())).await?;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(Arc::new(lifecycle)))
+            .service(handlers::data::subject_audit)
+    ).await;
+
+    let uri = format!("/v1/data/session/{}/audit", session.id);
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    req.extensions_mut().insert(Principal { subject: "svc-1".to_string(), tenant: None, admin: false });
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    req.extensions_mut().insert(Principal { subject: "dpo".to_string(), tenant: None, admin: true });
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    insta::assert_snapshot!("data_audit", schema(&body, &[("[].outcomes[].error", "string")]));
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[cfg(feature = "vision")]
+#[actix_web::test]
+async fn test_vision_contracts() -> Result<(), Box<dyn Error>> {
+    use opencv::core::{Mat, Scalar, Vector, CV_8UC3};
+    use opencv::imgcodecs;
+    use vae::core::stream_stats::{StreamStats, StreamStatsConfig};
+    use vae::vision::detector::{BBox, Detection};
+    use vae::vision::lpr::{PlateIndex, PlateRead};
+    use vae::vision::sources::PushRegistry;
+
+    let bbox = BBox { x: 10.0, y: 20.0, width: 40.0, height: 80.0 };
+    let plates = Arc::new(PlateIndex::new(16, "0123456789abcdef0123"));
+    plates.record(PlateRead {
+        source: "gate".to_string(),
+        frame_id: 1,
+        timestamp: chrono::Utc::now(),
+        text: Some("AB12CDE".to_string()),
+        text_hash: plates.hash("AB12CDE"),
+        region: Some("uk".to_string()),
+        confidence: 0.9,
+        bbox: bbox.clone(),
+        vehicle_bbox: bbox.clone(),
+    }).await;
+
+    let stats = Arc::new(StreamStats::new(StreamStatsConfig::default()));
+    stats.record_decode("dock", 4.0).await;
+    stats.record_frame("dock", 25.0, &[Detection {
+        bbox,
+        class_id: 0,
+        class_name: "person".to_string(),
+        confidence: 0.8,
+        frame_id: 1,
+        timestamp: chrono::Utc::now(),
+    }]).await;
+    stats.record_drop("dock", "backlogged").await;
+
+    let registry = Arc::new(PushRegistry::new());
+    let _source = registry.register("dock", 4).await;
+    let image = Mat::new_rows_cols_with_default(48, 64, CV_8UC3, Scalar::all(128.0))?;
+    let mut jpeg = Vector::<u8>::new();
+    imgcodecs::imencode(".jpg", &image, &mut jpeg, &Vector::new())?;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(plates))
+            .app_data(web::Data::new(stats))
+            .app_data(web::Data::new(registry))
+            .service(handlers::detections::plate_hits)
+            .service(handlers::stream_stats::stream_stats)
+            .service(handlers::frames::push_frame)
+    ).await;
+
+    // Redaction drops plate text, so the field is optional by design
+    let req = test::TestRequest::get().uri("/v1/detections/plates").to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    insta::assert_snapshot!("plate_hits", schema(&body, &[("plates[].text", "string")]));
+
+    let req = test::TestRequest::get().uri("/v1/streams/dock/stats?window=1m").to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    insta::assert_snapshot!("stream_stats", schema(&body, &[("windows[].uptime.online_since", "string")]));
+
+    let req = test::TestRequest::post()
+        .uri("/v1/frames?stream=dock")
+        .insert_header(("content-type", "image/jpeg"))
+        .set_payload(jpeg.to_vec())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 202);
+    let body: Value = test::read_body_json(resp).await;
+    insta::assert_snapshot!("push_frame", schema(&body, &[]));
+
+    Ok(())
+}
@@ -0,0 +1,1162 @@
+use vae::core::notify::{
+    render_template, ChannelConfig, ChannelType, Notification, NotificationDispatcher,
+    NotificationRateLimit, Notifier, Severity,
+};
+use vae::core::crypto::{EncryptionConfig, Encryptor, SecretsProvider};
+use vae::core::webhook::{signature_header, verify_signature, WebhookVerifier};
+use vae::utils::egress::{EgressClient, EgressConfig, EgressPolicy};
+use std::time::Duration;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+struct RecordingNotifier {
+    sent: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl Notifier for RecordingNotifier {
+    fn name(&self) -> String {
+        "recording".to_string()
+    }
+
+    async fn send(&self, _subject: &str, body: &str) -> anyhow::Result<()> {
+        self.sent.lock().unwrap().push(body.to_string());
+        Ok(())
+    }
+}
+
+fn channel_config(rate_limit: Option<NotificationRateLimit>) -> ChannelConfig {
+    ChannelConfig {
+        name: "recording".to_string(),
+        channel: ChannelType::Slack { webhook_url: "http://localhost".to_string() },
+        template: Some("{{severity}}: {{title}} on {{fields.camera}}".to_string()),
+        min_severity: Severity::Warning,
+        rate_limit,
+    }
+}
+
+#[test]
+fn test_notification_template() {
+    let notification = Notification::new("Intrusion", "Person in zone", Severity::Critical, "rules")
+        .with_field("camera", "gate-3");
+
+    let rendered = render_template("{{severity}}: {{title}} on {{fields.camera}}", &notification);
+    assert_eq!(rendered, "critical: Intrusion on gate-3");
+}
+
+#[tokio::test]
+async fn test_notification_rate_limit_and_severity() -> Result<(), Box<dyn Error>> {
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let client = EgressClient::new(&EgressConfig::default(), Duration::from_secs(5))?;
+    let mut dispatcher = NotificationDispatcher::new(&[], client)?;
+    dispatcher.add_channel(
+        Arc::new(RecordingNotifier { sent: sent.clone() }),
+        &channel_config(Some(NotificationRateLimit { max_per_window: 2, window_secs: 60 })),
+    );
+
+    let info = Notification::new("Heartbeat", "ok", Severity::Info, "engine");
+    dispatcher.dispatch(&info).await;
+    assert!(sent.lock().unwrap().is_empty());
+
+    let alert = Notification::new("Intrusion", "Person in zone", Severity::Critical, "rules")
+        .with_field("camera", "gate-3");
+    for _ in 0..5 {
+        dispatcher.dispatch(&alert).await;
+    }
+
+    assert_eq!(sent.lock().unwrap().len(), 2);
+    assert_eq!(dispatcher.stats().await[0].suppressed, 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_email_channels_are_checked_and_redacted() -> Result<(), Box<dyn Error>> {
+    use vae::core::notify::create_notifier;
+
+    let email = |to: &str| ChannelConfig {
+        name: "oncall".to_string(),
+        channel: ChannelType::Email {
+            smtp_host: "smtp.example.com".to_string(),
+            smtp_port: 587,
+            username: "alerts".to_string(),
+            password: "hunter2".to_string(),
+            from: "alerts@example.com".to_string(),
+            to: vec!["ops@example.com".to_string(), to.to_string()],
+        },
+        template: None,
+        min_severity: Severity::Warning,
+        rate_limit: None,
+    };
+    let client = EgressClient::new(&EgressConfig::default(), Duration::from_secs(5))?;
+
+    let err = create_notifier(&email("not an address"), client.clone()).err().expect("invalid recipient");
+    assert!(err.to_string().contains("not an address"));
+    assert!(create_notifier(&email("sec@example.com"), client).is_ok());
+
+    let telegram = ChannelType::Telegram { bot_token: "123:secret".to_string(), chat_id: "42".to_string() };
+    for debug in [format!("{:?}", email("sec@example.com")), format!("{:?}", telegram)] {
+        assert!(!debug.contains("hunter2") && !debug.contains("123:secret"), "{}", debug);
+    }
+    Ok(())
+}
+
+struct StaticSecrets {
+    keys: HashMap<String, Vec<u8>>,
+}
+
+#[async_trait]
+impl SecretsProvider for StaticSecrets {
+    async fn get_secret(&self, name: &str) -> anyhow::Result<Vec<u8>> {
+        self.keys.get(name).cloned().ok_or_else(|| anyhow::anyhow!("missing secret {}", name))
+    }
+}
+
+fn static_secrets() -> Arc<StaticSecrets> {
+    Arc::new(StaticSecrets {
+        keys: HashMap::from([
+            ("k1".to_string(), vec![1u8; 32]),
+            ("k2".to_string(), vec![2u8; 32]),
+        ]),
+    })
+}
+
+#[tokio::test]
+async fn test_encryption_roundtrip_and_rotation() -> Result<(), Box<dyn Error>> {
+    let config = EncryptionConfig {
+        enabled: true,
+        active_key: "k1".to_string(),
+        previous_keys: vec![],
+    };
+    let encryptor = Encryptor::new(&config, static_secrets()).await?;
+
+    let sealed = encryptor.encrypt(b"conversation memory").await?;
+    assert!(Encryptor::is_encrypted(&sealed));
+    assert_eq!(encryptor.decrypt(&sealed).await?, b"conversation memory");
+
+    // Data sealed with the old key stays readable after rotation
+    encryptor.rotate("k2").await?;
+    assert_eq!(encryptor.decrypt(&sealed).await?, b"conversation memory");
+
+    let resealed = encryptor.reencrypt(&sealed).await?;
+    assert_ne!(resealed, sealed);
+    assert_eq!(encryptor.decrypt(&resealed).await?, b"conversation memory");
+
+    // Plaintext written before encryption was enabled is passed through
+    assert_eq!(encryptor.decrypt_if_needed(b"{}".to_vec()).await?, b"{}");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_encryption_detects_tampering() -> Result<(), Box<dyn Error>> {
+    let config = EncryptionConfig {
+        enabled: true,
+        active_key: "k1".to_string(),
+        previous_keys: vec![],
+    };
+    let encryptor = Encryptor::new(&config, static_secrets()).await?;
+
+    let mut sealed = encryptor.encrypt(b"state").await?;
+    let last = sealed.len() - 1;
+    sealed[last] ^= 0xff;
+
+    assert!(encryptor.decrypt(&sealed).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_encryption_covers_memory_and_recordings() -> Result<(), Box<dyn Error>> {
+    use vae::core::agent::entities::{EntityKind, EntityMemory, EntityMemoryConfig, FactSource};
+    use vae::core::agent::sqlite_memory::{MemoryConfig, SqliteMemory};
+    use vae::core::llm::types::Message;
+    use vae::core::storage::{EncryptedStorage, FileStore, FileStoreConfig, LocalStorage, StorageBackend};
+
+    let config = EncryptionConfig {
+        enabled: true,
+        active_key: "k1".to_string(),
+        previous_keys: vec![],
+    };
+    let encryptor = Arc::new(Encryptor::new(&config, static_secrets()).await?);
+    let dir = std::env::temp_dir().join(format!("vae-sealed-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir)?;
+
+    // Conversation memory
+    let memory_config = MemoryConfig { path: dir.join("memory.db").to_string_lossy().into_owned(), ..Default::default() };
+    let memory = SqliteMemory::open(&memory_config, "s1")?.with_encryption(encryptor.clone());
+    memory.store(Message::new("user", "the gate code is 4711")).await?;
+    assert_eq!(memory.get_recent(1).await?[0].content, "the gate code is 4711");
+    memory.checkpoint().await?;
+    let raw = std::fs::read(dir.join("memory.db"))?;
+    assert!(!raw.windows(4).any(|window| window == b"4711"));
+
+    // Entity facts
+    let entities_file = dir.join("entities.json").to_string_lossy().into_owned();
+    let entity_config = EntityMemoryConfig { entities_file: entities_file.clone(), ..Default::default() };
+    let entities = EntityMemory::new_encrypted(entity_config.clone(), encryptor.clone()).await?;
+    entities.upsert_fact("Alice", EntityKind::Person, "badge", "B-17", FactSource::Manual).await?;
+    assert!(Encryptor::is_encrypted(&std::fs::read(&entities_file)?));
+    let reloaded = EntityMemory::new_encrypted(entity_config, encryptor.clone()).await?;
+    assert_eq!(reloaded.get("alice").await.unwrap().facts["badge"].value, "B-17");
+
+    // Recordings, readable after a key rotation and re-encryption
+    let local = Arc::new(LocalStorage::new(dir.join("files").to_str().unwrap()).await?);
+    let sealed = Arc::new(EncryptedStorage::new(local.clone(), encryptor.clone()));
+    let store = FileStore::new(FileStoreConfig::default(), sealed.clone()).await?;
+    let pdf = b"%PDF-1.7\nclip".to_vec();
+    let file = store.save("clip.pdf", "application/pdf", &pdf, "alice", None).await?;
+    assert!(Encryptor::is_encrypted(&local.get(&format!("data/{}", file.id)).await?));
+
+    encryptor.rotate("k2").await?;
+    assert_eq!(sealed.reencrypt("data").await?, 1);
+    let reopened = FileStore::new(FileStoreConfig::default(), sealed).await?;
+    assert_eq!(reopened.read(&file.id).await?.1, pdf);
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_webhook_signature_verification() {
+    let body = br#"{"type":"detection","data":{}}"#;
+    let now = chrono::Utc::now().timestamp();
+    let header = signature_header("secret", now, "evt-1", body);
+    let window = Duration::from_secs(300);
+
+    assert!(verify_signature("secret", &header, "evt-1", body, window, now).is_ok());
+    assert!(verify_signature("other", &header, "evt-1", body, window, now).is_err());
+    assert!(verify_signature("secret", &header, "evt-1", b"tampered", window, now).is_err());
+
+    // The event id is signed, so a captured body can't be resent as a new event
+    assert!(verify_signature("secret", &header, "evt-2", body, window, now).is_err());
+
+    // Stale deliveries are rejected even with a valid signature
+    assert!(verify_signature("secret", &header, "evt-1", body, window, now + 301).is_err());
+}
+
+#[test]
+fn test_webhook_verifier_rejects_replays() {
+    let verifier = WebhookVerifier::new("secret", Duration::from_secs(300));
+    let body = b"{}";
+    let header = signature_header("secret", chrono::Utc::now().timestamp(), "evt-1", body);
+
+    assert!(verifier.verify("evt-1", &header, body).is_ok());
+    assert!(verifier.verify("evt-1", &header, body).is_err());
+    assert!(verifier.verify("evt-2", &header, body).is_err());
+}
+
+#[test]
+fn test_egress_allow_list() {
+    let policy = EgressPolicy::new(&EgressConfig {
+        proxy: None,
+        enforce_allow_list: true,
+        allow_list: vec!["api.openai.com".to_string(), "*.slack.com".to_string()],
+    });
+
+    assert!(policy.is_allowed("api.openai.com"));
+    assert!(policy.is_allowed("hooks.slack.com"));
+    assert!(!policy.is_allowed("slack.com.evil.io"));
+    assert!(!policy.is_allowed("example.com"));
+
+    let open = EgressPolicy::new(&EgressConfig::default());
+    assert!(open.is_allowed("example.com"));
+}
+
+#[test]
+fn test_egress_client_blocks_disallowed_urls() -> Result<(), Box<dyn Error>> {
+    let client = EgressClient::new(&EgressConfig {
+        proxy: None,
+        enforce_allow_list: true,
+        allow_list: vec!["hooks.slack.com".to_string()],
+    }, Duration::from_secs(5))?;
+
+    assert!(client.post("https://hooks.slack.com/services/x").is_ok());
+    assert!(client.post("https://attacker.example/collect").is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_prompt_versioning_and_rollback() -> Result<(), Box<dyn Error>> {
+    use vae::core::prompts::{NewPromptVersion, PromptKind, PromptLibrary, PromptLibraryConfig, PromptRef};
+
+    let library = PromptLibrary::new(PromptLibraryConfig { persist: false, ..Default::default() }).await?;
+    let version = |content: &str, activate: bool| NewPromptVersion {
+        kind: PromptKind::System,
+        content: content.to_string(),
+        description: None,
+        author: None,
+        note: None,
+        activate,
+    };
+
+    library.publish("lilith", version("v1", true)).await?;
+    library.publish("lilith", version("v2", true)).await?;
+    let staged = library.publish("lilith", version("v3", false)).await?;
+    assert_eq!(staged.active_version, 2);
+
+    let active = PromptRef { name: "lilith".to_string(), version: None };
+    assert_eq!(library.resolve(&active).await?, "v2");
+
+    library.rollback("lilith").await?;
+    assert_eq!(library.resolve(&active).await?, "v1");
+    assert!(library.rollback("lilith").await.is_err());
+
+    let pinned = PromptRef { name: "lilith".to_string(), version: Some(3) };
+    assert_eq!(library.resolve(&pinned).await?, "v3");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_prompt_templates_render_and_reload() -> Result<(), Box<dyn Error>> {
+    use std::time::{Duration, SystemTime};
+    use vae::core::prompt_template::{PromptContext, PromptTemplate, PromptTemplateConfig, PromptTemplates};
+
+    let context = PromptContext::new("Lilith")
+        .with_memory("The loading dock camera was moved on Monday.")
+        .with_tool("search_detections", "Find detections by class & time")
+        .with_var("site", "Warehouse 4");
+    let template = PromptTemplate::new(
+        "system",
+        "You are {{ persona }} at {{ site }}.{% if memory_summary %} {{ memory_summary }}{% endif %}\n\
+         {% for tool in tools %}- {{ tool.name }}: {{ tool.description }}{% endfor %}{{ missing }}",
+    )?;
+    assert_eq!(
+        template.render(&context)?,
+        "You are Lilith at Warehouse 4. The loading dock camera was moved on Monday.\n\
+         - search_detections: Find detections by class & time",
+    );
+    assert!(PromptTemplate::new("broken", "{% if persona %}unclosed").is_err());
+
+    let dir = std::env::temp_dir().join(format!("vae-prompts-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("system.j2");
+    std::fs::write(&path, "Hello from {{ persona }}")?;
+    std::fs::write(dir.join("notes.md"), "not a template")?;
+    let templates = PromptTemplates::new(PromptTemplateConfig {
+        templates_dir: dir.to_string_lossy().to_string(),
+        reload_interval_secs: None,
+        ..Default::default()
+    }).await?;
+    assert_eq!(templates.names().await, vec!["system".to_string()]);
+    assert_eq!(templates.render_system(&context).await?, "Hello from Lilith");
+
+    // Edits are picked up; a broken edit keeps the last good version
+    let touch = |offset: u64| -> std::io::Result<()> {
+        std::fs::File::options().write(true).open(&path)?
+            .set_modified(SystemTime::now() + Duration::from_secs(offset))
+    };
+    std::fs::write(&path, "Goodbye from {{ persona }}")?;
+    touch(10)?;
+    assert!(templates.reload().await?.is_empty());
+    assert_eq!(templates.render_system(&context).await?, "Goodbye from Lilith");
+
+    std::fs::write(&path, "{% for %}")?;
+    touch(20)?;
+    assert_eq!(templates.reload().await?.len(), 1);
+    assert_eq!(templates.render_system(&context).await?, "Goodbye from Lilith");
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_file_store_validates_uploads() -> Result<(), Box<dyn Error>> {
+    use vae::core::storage::{FileKind, FileStore, FileStoreConfig, LocalStorage};
+
+    let root = std::env::temp_dir().join(format!("vae-files-{}", uuid::Uuid::new_v4()));
+    let backend = Arc::new(LocalStorage::new(root.to_str().unwrap()).await?);
+    let store = FileStore::new(FileStoreConfig::default(), backend.clone()).await?;
+
+    let pdf = b"%PDF-1.7\n...".to_vec();
+    let file = store.save("../../report.pdf", "application/pdf", &pdf, "alice", None).await?;
+    assert_eq!(file.kind, FileKind::Pdf);
+    assert_eq!(file.filename, "report.pdf");
+
+    // Declared type must match the bytes
+    assert!(store.save("photo.png", "image/png", b"MZ\x90\x00", "alice", None).await.is_err());
+    assert!(store.save("tool.exe", "application/x-msdownload", b"MZ", "alice", None).await.is_err());
+
+    // Metadata survives a restart
+    let reopened = FileStore::new(FileStoreConfig::default(), backend).await?;
+    let (_, data) = reopened.read(&file.id).await?;
+    assert_eq!(data, pdf);
+
+    std::fs::remove_dir_all(root)?;
+    Ok(())
+}
+
+#[cfg(feature = "vision")]
+#[test]
+fn test_geo_distance_and_geojson() {
+    use vae::core::geo::{to_geojson, GeoEvent, GeoPoint};
+
+    // Gates roughly 1.1 km apart along a meridian
+    let gate_1 = GeoPoint::new(52.5200, 13.4050);
+    let gate_3 = GeoPoint::new(52.5300, 13.4050);
+    let distance = gate_1.distance_to(&gate_3);
+    assert!((distance - 1112.0).abs() < 5.0);
+    assert!(GeoPoint::new(95.0, 0.0).validate().is_err());
+
+    let event = GeoEvent {
+        id: "e1".to_string(),
+        stream_id: "cam-1".to_string(),
+        kind: "detection".to_string(),
+        location: gate_1,
+        timestamp: chrono::Utc::now(),
+        properties: serde_json::Map::new(),
+    };
+    let geojson = to_geojson(&[event]);
+    assert_eq!(geojson["type"], "FeatureCollection");
+    assert_eq!(geojson["features"][0]["geometry"]["coordinates"][0], 13.4050);
+    assert_eq!(geojson["features"][0]["properties"]["stream_id"], "cam-1");
+}
+
+struct RecordingProcessor {
+    processed: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl vae::core::uploads::UploadProcessor for RecordingProcessor {
+    async fn process(
+        &self,
+        upload: vae::core::uploads::CompletedUpload,
+        _handle: vae::core::jobs::JobHandle,
+    ) -> anyhow::Result<()> {
+        self.processed.lock().unwrap().push(upload.session.filename);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_resumable_upload_starts_job() -> Result<(), Box<dyn Error>> {
+    use sha2::{Digest, Sha256};
+    use vae::core::jobs::JobManager;
+    use vae::core::uploads::{ChunkError, CreateUpload, UploadConfig, UploadManager, UploadStatus};
+
+    let root = std::env::temp_dir().join(format!("vae-uploads-{}", uuid::Uuid::new_v4()));
+    let config = UploadConfig {
+        upload_dir: root.to_string_lossy().to_string(),
+        ..UploadConfig::default()
+    };
+    let processed = Arc::new(Mutex::new(Vec::new()));
+    let jobs = Arc::new(JobManager::new());
+    let manager = UploadManager::new(
+        config.clone(),
+        jobs.clone(),
+        Arc::new(RecordingProcessor { processed: processed.clone() }),
+    ).await?;
+
+    let video = vec![7u8; 1000];
+    let session = manager.create(CreateUpload {
+        filename: "dock-3.mp4".to_string(),
+        size: video.len() as u64,
+        sha256: hex::encode(Sha256::digest(&video)),
+        stream_profile: None,
+    }, "ops").await?;
+    assert_eq!(session.owner, "ops");
+
+    let session = manager.append(&session.id, 0, &video[..400]).await.map_err(|e| e.to_string())?;
+    assert_eq!(session.offset, 400);
+
+    // Sessions survive a restart; data nobody owns any more is cleaned up
+    std::fs::write(root.join("orphan.part"), b"left over")?;
+    drop(manager);
+    let manager = UploadManager::new(
+        config,
+        jobs.clone(),
+        Arc::new(RecordingProcessor { processed: processed.clone() }),
+    ).await?;
+    let resumed = manager.get(&session.id).await.expect("session should be reloaded");
+    assert_eq!((resumed.offset, resumed.owner.as_str()), (400, "ops"));
+    assert!(!root.join("orphan.part").exists());
+
+    // A retried chunk at a stale offset reports where to resume
+    match manager.append(&session.id, 0, &video[..400]).await {
+        Err(ChunkError::OffsetMismatch(offset)) => assert_eq!(offset, 400),
+        other => panic!("expected offset mismatch, got {:?}", other.map(|s| s.offset)),
+    }
+
+    // A chunk that died part-way leaves bytes past the offset; the resumed
+    // chunk overwrites them rather than landing after them
+    {
+        use std::io::Write;
+        let mut part = std::fs::OpenOptions::new().append(true).open(root.join(format!("{}.part", session.id)))?;
+        part.write_all(&[0u8; 150])?;
+    }
+
+    let session = manager.append(&session.id, 400, &video[400..]).await.map_err(|e| e.to_string())?;
+    assert_eq!(session.status, UploadStatus::Complete);
+    let job_id = session.job_id.expect("job should start on completion");
+    assert!(jobs.get(&job_id).await.is_some());
+
+    // Corrupted content fails validation and never starts a job
+    let bad = manager.create(CreateUpload {
+        filename: "dock-4.mp4".to_string(),
+        size: 4,
+        sha256: hex::encode(Sha256::digest(b"good")),
+        stream_profile: None,
+    }, "ops").await?;
+    let bad = manager.append(&bad.id, 0, b"evil").await.map_err(|e| e.to_string())?;
+    assert_eq!(bad.status, UploadStatus::Failed);
+    assert!(bad.job_id.is_none());
+
+    std::fs::remove_dir_all(root)?;
+    Ok(())
+}
+
+#[test]
+fn test_watchdog_finds_stalled_workers() {
+    use chrono::{Duration, Utc};
+    use vae::core::watchdog::{describe_stall, find_stalled, WorkerActivity};
+
+    let now = Utc::now();
+    let mut activity = HashMap::new();
+    activity.insert(0, WorkerActivity {
+        stage: "detect".to_string(),
+        stage_index: 1,
+        frame_id: 42,
+        started_at: now - Duration::seconds(90),
+    });
+    activity.insert(1, WorkerActivity {
+        stage: "analyze".to_string(),
+        stage_index: 2,
+        frame_id: 43,
+        started_at: now - Duration::seconds(2),
+    });
+
+    let mut last_processed = HashMap::new();
+    last_processed.insert("detect".to_string(), now - Duration::seconds(120));
+    last_processed.insert("analyze".to_string(), now);
+
+    let stalled = find_stalled(&activity, &last_processed, Duration::seconds(30), now);
+    assert_eq!(stalled.len(), 1);
+    assert_eq!(stalled[0].worker_id, 0);
+    assert_eq!(stalled[0].frame_id, 42);
+
+    let names = vec!["pre".to_string(), "detect".to_string(), "analyze".to_string()];
+    let diagnostic = describe_stall(&stalled[0], &names);
+    assert!(diagnostic.contains("pre -> [detect] -> analyze"));
+
+    // Stage still completing frames on other workers isn't treated as stuck
+    last_processed.insert("detect".to_string(), now);
+    assert!(find_stalled(&activity, &last_processed, Duration::seconds(30), now).is_empty());
+}
+
+#[cfg(feature = "vision")]
+#[tokio::test]
+async fn test_pipeline_config_typed_stages() -> Result<(), Box<dyn Error>> {
+    use vae::core::pipeline::{Pipeline, PipelineConfig, StageSettings, StageType};
+
+    let raw = r#"{
+        "max_parallel_stages": 2,
+        "buffer_size": 16,
+        "timeout_ms": 1000,
+        "retry_count": 1,
+        "stages": [
+            { "name": "detect", "settings": { "detection": { "model": "yolov8n", "classes": ["person"] } } },
+            { "name": "mask", "params": { "privacy": "{}" }, "settings": { "privacy": {} } }
+        ]
+    }"#;
+    let config = PipelineConfig::from_json(raw)?;
+    assert_eq!(config.stages[0].stage_type(), StageType::Detection);
+    assert!(config.stages[1].enabled);
+    match &config.stages[0].settings {
+        StageSettings::Detection(settings) => assert_eq!(settings.confidence_threshold, 0.5),
+        other => panic!("unexpected settings: {:?}", other),
+    }
+
+    // Bad values fail at load with the stage named in the error
+    let invalid = raw.replace(r#""model": "yolov8n""#, r#""model": "yolov8n", "confidence_threshold": 1.5"#);
+    let err = PipelineConfig::from_json(&invalid).unwrap_err();
+    assert!(format!("{:#}", err).contains("stage 'detect'"));
+
+    // Wrong types are rejected too
+    let mistyped = raw.replace(r#""classes": ["person"]"#, r#""classes": "person""#);
+    assert!(PipelineConfig::from_json(&mistyped).is_err());
+
+    // Configs from before typed settings still load, through the same checks
+    let legacy = r#"{
+        "max_parallel_stages": 2,
+        "buffer_size": 16,
+        "timeout_ms": 1000,
+        "retry_count": 1,
+        "stages": [
+            { "name": "detect", "stage_type": "Detection", "enabled": true,
+              "params": { "model": "yolov8n", "confidence_threshold": "0.6", "classes": "[\"person\"]" } },
+            { "name": "mask", "stage_type": "Privacy", "enabled": false, "params": { "privacy": "{}" } }
+        ]
+    }"#;
+    let config = PipelineConfig::from_json(legacy)?;
+    match &config.stages[0].settings {
+        StageSettings::Detection(settings) => {
+            assert_eq!(settings.confidence_threshold, 0.6);
+            assert_eq!(settings.classes, vec!["person".to_string()]);
+        }
+        other => panic!("unexpected settings: {:?}", other),
+    }
+    assert_eq!(config.stages[1].stage_type(), StageType::Privacy);
+    assert!(!config.stages[1].enabled);
+    let err = PipelineConfig::from_json(&legacy.replace(r#""0.6""#, r#""1.6""#)).unwrap_err();
+    assert!(format!("{:#}", err).contains("stage 'detect'"));
+
+    // Configs that never went through from_json are checked on construction
+    assert!(Pipeline::new(PipelineConfig { buffer_size: 0, ..config }).await.is_err());
+
+    // LPR adds plate boxes for privacy to mask, so it has to sit between
+    // detection and privacy
+    let lpr = r#"{ "name": "plates", "settings": { "lpr": { "hash_key": "0123456789abcdef", "attributes": { "model_path": "attrs.onnx", "types": [], "colors": [] } } } }"#;
+    let with_lpr = |stages: &[&str]| format!(
+        r#"{{ "max_parallel_stages": 2, "buffer_size": 16, "timeout_ms": 1000, "retry_count": 1, "stages": [{}] }}"#,
+        stages.join(","),
+    );
+    let detect = r#"{ "name": "detect", "settings": { "detection": { "model": "yolov8n" } } }"#;
+    let mask = r#"{ "name": "mask", "settings": { "privacy": {} } }"#;
+    assert!(PipelineConfig::from_json(&with_lpr(&[detect, lpr, mask])).is_ok());
+    assert!(PipelineConfig::from_json(&with_lpr(&[detect, mask, lpr])).is_err());
+    assert!(PipelineConfig::from_json(&with_lpr(&[lpr, detect, mask])).is_err());
+    let short_key = lpr.replace("0123456789abcdef", "short");
+    assert!(PipelineConfig::from_json(&with_lpr(&[detect, &short_key])).is_err());
+
+    // A detection stage can name a configured ensemble, which needs exactly
+    // one of detector or ensemble
+    let ensemble = r#""models": { "yolov8n": { "ensemble": { "strategy": "weighted_fusion", "members": [] } } }, "stages""#;
+    let config = PipelineConfig::from_json(&raw.replacen(r#""stages""#, ensemble, 1))?;
+    assert!(config.models["yolov8n"].ensemble.is_some());
+    let empty = raw.replacen(r#""stages""#, r#""models": { "yolov8n": {} }, "stages""#, 1);
+    assert!(PipelineConfig::from_json(&empty).unwrap_err().to_string().contains("exactly one"));
+    Ok(())
+}
+
+#[cfg(feature = "vision")]
+#[test]
+fn test_builders_produce_validated_configs() -> Result<(), Box<dyn Error>> {
+    use vae::core::engine::Engine;
+    use vae::core::pipeline::{DetectionSettings, Pipeline, StageSettings};
+
+    let engine = Engine::builder().gpu(false).threads(8).into_config()?;
+    assert!(!engine.enable_gpu);
+    assert_eq!(engine.processing_threads, 8);
+    assert!(Engine::builder().threads(0).into_config().is_err());
+    let masked = Engine::builder()
+        .privacy(vae::vision::privacy::PrivacyConfig { enabled: true, ..Default::default() })
+        .into_config()?;
+    assert!(masked.privacy.enabled && !engine.privacy.enabled);
+
+    let pipeline = Pipeline::builder()
+        .stage("detect", StageSettings::Detection(DetectionSettings {
+            model: "yolov8n".to_string(),
+            ..DetectionSettings::default()
+        }))
+        .workers(2)
+        .into_config()?;
+    assert_eq!(pipeline.stages.len(), 1);
+    assert_eq!(pipeline.max_parallel_stages, 2);
+
+    // Stage validation still applies
+    assert!(Pipeline::builder()
+        .stage("detect", StageSettings::Detection(DetectionSettings::default()))
+        .into_config()
+        .is_err());
+    Ok(())
+}
+
+#[cfg(feature = "vision")]
+#[tokio::test]
+async fn test_engine_publishes_state_changes() -> Result<(), Box<dyn Error>> {
+    use vae::core::engine::{Engine, EngineEvent};
+
+    let mut engine = Engine::builder().gpu(false).threads(1).build().await?;
+    let mut events = engine.subscribe();
+
+    engine.start().await?;
+    engine.stop().await?;
+
+    match events.recv().await? {
+        EngineEvent::StateChanged { running } => assert!(running),
+        other => panic!("unexpected event: {:?}", other),
+    }
+    match events.recv().await? {
+        EngineEvent::StateChanged { running } => assert!(!running),
+        other => panic!("unexpected event: {:?}", other),
+    }
+
+    // stop() waits for the workers, so a restart starts from an empty pool
+    for _ in 0..3 {
+        engine.start().await?;
+        tokio::time::timeout(std::time::Duration::from_secs(2), engine.stop()).await??;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "vision")]
+#[test]
+fn test_sink_config_parsing() -> Result<(), Box<dyn Error>> {
+    use vae::core::sinks::{KafkaFormat, SinkConfig};
+
+    let sinks: Vec<SinkConfig> = serde_json::from_str(r#"[
+        { "type": "stdout" },
+        { "type": "file", "path": "/var/log/vae/results.jsonl" },
+        { "type": "kafka", "brokers": "kafka:9092", "topic": "vae.results" },
+        { "type": "kafka", "brokers": "kafka:9092", "topic": "vae.results.json", "format": "json" }
+    ]"#)?;
+    assert!(matches!(sinks[0], SinkConfig::Stdout));
+    assert!(matches!(&sinks[1], SinkConfig::File { path } if path.ends_with("results.jsonl")));
+    match &sinks[2] {
+        SinkConfig::Kafka { timeout_ms, format, .. } => {
+            assert_eq!(*timeout_ms, 5000);
+            assert_eq!(*format, KafkaFormat::Proto);
+        }
+        other => panic!("unexpected sink: {:?}", other),
+    }
+    assert!(matches!(&sinks[3], SinkConfig::Kafka { format: KafkaFormat::Json, .. }));
+    Ok(())
+}
+
+#[test]
+fn test_fetch_blocks_private_addresses() {
+    use std::net::IpAddr;
+    use vae::utils::fetch::is_blocked_ip;
+
+    for blocked in [
+        "127.0.0.1", "10.1.2.3", "172.16.0.9", "192.168.1.1", "169.254.169.254",
+        "100.64.0.1", "0.0.0.0", "::1", "fd00::1", "fe80::1", "::ffff:10.0.0.1", "64:ff9b::a9fe:a9fe",
+    ] {
+        let ip: IpAddr = blocked.parse().unwrap();
+        assert!(is_blocked_ip(&ip), "{} should be blocked", blocked);
+    }
+    for allowed in ["93.184.216.34", "2606:2800:220:1:248:1893:25c8:1946"] {
+        let ip: IpAddr = allowed.parse().unwrap();
+        assert!(!is_blocked_ip(&ip), "{} should be allowed", allowed);
+    }
+}
+
+#[tokio::test]
+async fn test_fetch_rejects_unsafe_urls() -> Result<(), Box<dyn Error>> {
+    use vae::utils::egress::{EgressConfig, ProxyConfig};
+    use vae::utils::fetch::{FetchConfig, ImageFetcher};
+
+    let fetcher = ImageFetcher::new(FetchConfig::default(), &EgressConfig::default())?;
+    assert!(fetcher.fetch("http://169.254.169.254/latest/meta-data").await.is_err());
+    assert!(fetcher.fetch("http://[::1]:8080/img.png").await.is_err());
+    assert!(fetcher.fetch("file:///etc/passwd").await.is_err());
+    assert!(fetcher.fetch("https://user:pw@example.com/a.png").await.is_err());
+
+    // The egress allow-list applies to fetches too
+    let egress = EgressConfig {
+        enforce_allow_list: true,
+        allow_list: vec!["images.example.com".to_string()],
+        ..EgressConfig::default()
+    };
+    let fetcher = ImageFetcher::new(FetchConfig::default(), &egress)?;
+    let err = fetcher.fetch("https://example.org/a.png").await.unwrap_err();
+    assert!(err.to_string().contains("allow-list"));
+
+    // A proxy would resolve past the address checks, so it's refused
+    let proxied = EgressConfig {
+        proxy: Some(ProxyConfig {
+            url: "http://proxy.internal:3128".to_string(),
+            username: None,
+            password: None,
+            no_proxy: None,
+        }),
+        ..EgressConfig::default()
+    };
+    assert!(ImageFetcher::new(FetchConfig::default(), &proxied).is_err());
+    Ok(())
+}
+
+#[cfg(feature = "vision")]
+#[test]
+fn test_rule_schedule_wraps_midnight() -> Result<(), Box<dyn Error>> {
+    use chrono::{NaiveTime, TimeZone, Utc, Weekday};
+    use vae::core::rules::{Rule, Schedule};
+
+    let rule: Rule = serde_json::from_value(serde_json::json!({
+        "name": "Loading dock after hours",
+        "stream_id": "dock-cam",
+        "zone": "loading dock",
+        "classes": ["person"],
+        "schedule": { "start": "22:00:00", "end": "06:00:00", "days": ["Fri"] },
+        "severity": "critical"
+    }))?;
+    assert_eq!(rule.min_count, 1);
+    assert!(rule.enabled);
+
+    let schedule: &Schedule = rule.schedule.as_ref().unwrap();
+    assert_eq!(schedule.start, NaiveTime::from_hms_opt(22, 0, 0).unwrap());
+    assert_eq!(schedule.days, vec![Weekday::Fri]);
+
+    // 2024-03-01 is a Friday; the window opened Friday night covers early Saturday
+    assert!(schedule.contains(Utc.with_ymd_and_hms(2024, 3, 1, 23, 30, 0).unwrap()));
+    assert!(schedule.contains(Utc.with_ymd_and_hms(2024, 3, 2, 5, 59, 0).unwrap()));
+    assert!(!schedule.contains(Utc.with_ymd_and_hms(2024, 3, 2, 23, 0, 0).unwrap()));
+    assert!(!schedule.contains(Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap()));
+
+    let shifted = Schedule { utc_offset_minutes: 120, ..schedule.clone() };
+    assert!(shifted.contains(Utc.with_ymd_and_hms(2024, 3, 1, 20, 30, 0).unwrap()));
+
+    let summary = rule.describe();
+    assert!(summary.starts_with("Raise a critical alert when person is detected in zone \"loading dock\""));
+    assert!(summary.contains("between 22:00 and 06:00"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_alerts_group_into_incidents() -> Result<(), Box<dyn Error>> {
+    use vae::core::alerts::{AlertConfig, AlertManager};
+    use vae::core::incidents::{IncidentConfig, IncidentEvent, IncidentFilter, IncidentManager, IncidentStatus};
+
+    let incidents = Arc::new(IncidentManager::new(IncidentConfig::default()));
+    let mut events = incidents.subscribe();
+    let alerts = AlertManager::new(AlertConfig::default(), None).with_incidents(incidents.clone());
+
+    let labels = |stream: &str| HashMap::from([("stream_id".to_string(), stream.to_string())]);
+    alerts.raise("rule:a:dock", "Person in dock", Severity::Warning, "1 person", labels("dock")).await;
+    alerts.raise("rule:b:dock", "Vehicle in dock", Severity::Critical, "1 truck", labels("dock")).await;
+    alerts.raise("rule:a:lobby", "Person in lobby", Severity::Info, "1 person", labels("lobby")).await;
+
+    let open = incidents.list(&IncidentFilter::default()).await;
+    assert_eq!(open.len(), 2);
+    // Most severe first; the dock incident escalated with its second alert
+    assert_eq!(open[0].title, "Person in dock");
+    assert_eq!(open[0].severity, Severity::Critical);
+    assert_eq!(open[0].alerts, vec!["rule:a:dock", "rule:b:dock"]);
+
+    let id = open[0].id.clone();
+    let acked = incidents.acknowledge(&id, "dana").await?;
+    assert_eq!(acked.status, IncidentStatus::Acknowledged);
+    assert_eq!(acked.assignee.as_deref(), Some("dana"));
+    incidents.add_note(&id, "dana", "Delivery driver, expected").await?;
+    incidents.resolve(&id, "dana").await?;
+    assert!(incidents.acknowledge(&id, "sam").await.is_err());
+
+    let resolved = IncidentFilter { status: Some(IncidentStatus::Resolved), ..IncidentFilter::default() };
+    assert_eq!(incidents.list(&resolved).await.len(), 1);
+
+    assert!(matches!(events.try_recv()?, IncidentEvent::Opened { .. }));
+    assert!(matches!(events.try_recv()?, IncidentEvent::AlertGrouped { .. }));
+    Ok(())
+}
+
+#[test]
+fn test_report_fallback_text() {
+    use std::collections::BTreeMap;
+    use vae::core::reports::{fallback_text, DailySummary};
+
+    let summary = DailySummary {
+        date: chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+        detections: 42,
+        detections_by_class: BTreeMap::from([
+            ("car".to_string(), 10),
+            ("person".to_string(), 30),
+            ("dog".to_string(), 2),
+        ]),
+        busiest_hour: Some(8),
+        incidents_opened: 3,
+        critical_incidents: 1,
+        incidents_resolved: 2,
+        incidents_unresolved: 1,
+        anomalies: BTreeMap::from([("Loitering".to_string(), 4)]),
+        uptime_ratio: 0.985,
+        ..DailySummary::default()
+    };
+
+    let text = fallback_text(&summary);
+    assert!(text.starts_with("2024-03-01: 42 detections (30 person, 10 car, 2 dog), 3 incidents opened (1 critical)"));
+    assert!(text.contains("Uptime 98.5%"));
+    assert!(text.contains("Busiest hour: 08:00."));
+    assert!(text.contains("Anomalies: Loitering x4."));
+}
+
+#[tokio::test]
+async fn test_remediation_cooldown_and_dry_run() -> Result<(), Box<dyn Error>> {
+    use vae::core::alerts::Alert;
+    use vae::core::llm::cache::{CacheConfig, ResponseCache};
+    use vae::core::remediation::{
+        ActionHandlers, RemediationAction, RemediationConfig, RemediationExecutor, RemediationOutcome,
+        RemediationPolicy, Remediator,
+    };
+
+    struct RecordingExecutor {
+        executed: Arc<Mutex<Vec<RemediationAction>>>,
+    }
+
+    #[async_trait]
+    impl RemediationExecutor for RecordingExecutor {
+        async fn execute(&self, action: &RemediationAction) -> anyhow::Result<String> {
+            self.executed.lock().unwrap().push(action.clone());
+            Ok("done".to_string())
+        }
+    }
+
+    let executed = Arc::new(Mutex::new(Vec::new()));
+    let remediator = Remediator::new(
+        RemediationConfig {
+            policies: vec![RemediationPolicy {
+                name: "restart stalled stream".to_string(),
+                alert_name: Some("Stream stalled".to_string()),
+                rule_id: None,
+                min_severity: Severity::Warning,
+                actions: vec![RemediationAction::RestartStream { stream_id: "{stream_id}".to_string() }],
+                cooldown_secs: 600,
+            }],
+            ..RemediationConfig::default()
+        },
+        Arc::new(RecordingExecutor { executed: executed.clone() }),
+    ).await?;
+
+    let now = chrono::Utc::now();
+    let alert = |name: &str, severity: Severity| Alert {
+        key: format!("{}:dock", name),
+        name: name.to_string(),
+        severity,
+        message: String::new(),
+        labels: HashMap::from([("stream_id".to_string(), "dock".to_string())]),
+        raised_at: now,
+        last_seen: now,
+        resolved_at: None,
+        occurrences: 1,
+    };
+
+    // Dry run by default: recorded, not executed
+    let records = remediator.on_alert(&alert("Stream stalled", Severity::Critical)).await;
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].action, RemediationAction::RestartStream { stream_id: "dock".to_string() });
+    assert!(matches!(records[0].outcome, RemediationOutcome::DryRun));
+
+    // The dry run didn't start the cool-down; the real run does
+    remediator.set_dry_run(false);
+    let records = remediator.on_alert(&alert("Stream stalled", Severity::Critical)).await;
+    assert!(matches!(records[0].outcome, RemediationOutcome::Executed { .. }));
+    assert_eq!(executed.lock().unwrap().len(), 1);
+    let records = remediator.on_alert(&alert("Stream stalled", Severity::Critical)).await;
+    assert!(matches!(records[0].outcome, RemediationOutcome::CoolingDown { .. }));
+    assert_eq!(executed.lock().unwrap().len(), 1);
+
+    assert!(remediator.on_alert(&alert("Stream stalled", Severity::Info)).await.is_empty());
+    assert!(remediator.on_alert(&alert("Disk full", Severity::Critical)).await.is_empty());
+    assert_eq!(remediator.audit_trail(10).await.len(), 3);
+
+    // Registered handlers and caches run; anything else fails visibly
+    let cache = Arc::new(ResponseCache::new(CacheConfig::default())?);
+    let handlers = ActionHandlers::new()
+        .with_cache("llm", cache.clone())
+        .with_handler("restart_stream", |action| async move { Ok(format!("restarted {:?}", action)) });
+    assert_eq!(handlers.execute(&RemediationAction::ClearCache { cache: "llm".to_string() }).await?, "Cleared cache llm");
+    assert!(handlers.execute(&RemediationAction::RestartStream { stream_id: "dock".to_string() }).await.is_ok());
+    assert!(handlers.execute(&RemediationAction::ReloadModel { model: "yolo".to_string() }).await.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_doctor_reports_actionable_failures() -> Result<(), Box<dyn Error>> {
+    use vae::core::llm::provider::api_key;
+    use vae::utils::doctor::{check_llm_provider, check_startup, check_writable, CheckStatus, DoctorConfig};
+
+    let dir = std::env::temp_dir().join(format!("vae-doctor-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let state_file = dir.join("state.json");
+    assert_eq!(check_writable(state_file.to_str().unwrap()).status, CheckStatus::Pass);
+
+    let missing = check_writable("/nonexistent-vae-dir/state.json");
+    assert_eq!(missing.status, CheckStatus::Fail);
+    assert!(missing.hint.is_some());
+
+    let config = DoctorConfig::from_yaml(
+        "llm_provider: local\nlocal_llm:\n  model_path: models/missing.gguf\n  gpu_layers: 20\nunrelated: true\n",
+    )?;
+    let llm = check_llm_provider(&config);
+    assert_eq!(llm.status, CheckStatus::Fail);
+    assert!(llm.detail.contains("models/missing.gguf"));
+
+    let config = DoctorConfig {
+        llm_provider: Some("openai".to_string()),
+        openai_key: Some("sk-test".to_string()),
+        ..DoctorConfig::default()
+    };
+    assert_eq!(check_llm_provider(&config).status, CheckStatus::Pass);
+
+    // Providers and the checks resolve keys the same way
+    assert_eq!(api_key(Some(" sk-test "), "VAE_TEST_UNSET_KEY").as_deref(), Some("sk-test"));
+    assert_eq!(api_key(Some("  "), "VAE_TEST_UNSET_KEY"), None);
+
+    // Startup refuses a config the checks fail
+    let config_file = dir.join("vae.yaml");
+    std::fs::write(&config_file, "engine:\n  enable_gpu: false\nllm_provider: local\nlocal_llm:\n  model_path: models/missing.gguf\n")?;
+    let error = check_startup(config_file.to_str().unwrap()).unwrap_err().to_string();
+    assert!(error.contains("llm"), "{}", error);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[cfg(feature = "vision")]
+#[test]
+fn test_config_bundle_template_variables() -> Result<(), Box<dyn Error>> {
+    use vae::core::bundle::{escape, substitute, ConfigBundle};
+
+    let vars: HashMap<String, String> = [("site".to_string(), "paris".to_string())].into_iter().collect();
+    assert_eq!(substitute("${site}-lobby", &vars)?, "paris-lobby");
+    assert_eq!(substitute("${zone:-entrance} $${site}", &vars)?, "entrance ${site}");
+    let error = substitute("${site} ${camera} ${camera}", &vars).unwrap_err().to_string();
+    assert!(error.ends_with("undefined variables: camera"), "{}", error);
+    assert!(substitute("${site", &vars).is_err());
+    assert!(substitute("${not a name}", &vars).is_err());
+
+    let raw = r#"
+variables:
+  site: hq
+  min_count: 2
+rules:
+  - id: "${site}-dock"
+    name: "${site} loading dock"
+    stream_id: "${site}-dock-cam"
+    zone: "${zone:-loading dock}"
+    min_count: ${min_count}
+    severity: warning
+"#;
+    // Declared values are defaults
+    let bundle = ConfigBundle::parse(raw, &HashMap::new())?;
+    assert_eq!(bundle.version, 1);
+    assert_eq!(bundle.rules[0].id, "hq-dock");
+    assert_eq!(bundle.rules[0].min_count, 2);
+    assert_eq!(bundle.rules[0].zone.as_deref(), Some("loading dock"));
+
+    // Values given at import time win
+    let bundle = ConfigBundle::parse(raw, &vars)?;
+    assert_eq!(bundle.rules[0].stream_id, "paris-dock-cam");
+    assert_eq!(bundle.variables.get("site").map(String::as_str), Some("hq"));
+
+    // Values land inside the string they replace, never in the structure
+    let hostile: HashMap<String, String> = [("site".to_string(), "x\"\nagent: {}\nrules: []".to_string())].into_iter().collect();
+    let bundle = ConfigBundle::parse(raw, &hostile)?;
+    assert_eq!(bundle.rules.len(), 1);
+    assert!(bundle.agent.is_none());
+    assert_eq!(bundle.rules[0].id, "x\"\nagent: {}\nrules: []-dock");
+
+    // Exported text that looks like a placeholder comes back literally
+    assert_eq!(substitute(&escape("cost ${price}"), &vars)?, "cost ${price}");
+
+    assert!(ConfigBundle::parse("version: 2\n", &HashMap::new()).is_err());
+    let exported = ConfigBundle::parse("rules: []\n", &HashMap::new())?.to_yaml()?;
+    assert!(exported.starts_with("version: 1"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_response_language_resolution_and_error_catalogs() -> Result<(), Box<dyn Error>> {
+    use vae::core::i18n::{enforce_language, parse_accept_language, Catalog, LanguageConfig, LanguageSource, Localizer};
+    use vae::core::llm::types::Message;
+
+    let config = LanguageConfig {
+        tenants: [("acme".to_string(), "de".to_string())].into_iter().collect(),
+        ..Default::default()
+    };
+    let localizer = Localizer::new(config).await?;
+
+    let french = "Bonjour, pouvez-vous me dire combien de personnes sont entrées dans le magasin aujourd'hui ?";
+    let resolved = localizer.resolve(None, Some("acme"), french).await;
+    assert_eq!((resolved.code.as_str(), resolved.source), ("fr", LanguageSource::Detected));
+
+    // Too short to detect, so the tenant's preference applies
+    let resolved = localizer.resolve(None, Some("acme"), "ok").await;
+    assert_eq!((resolved.code.as_str(), resolved.source), ("de", LanguageSource::Tenant));
+    assert_eq!(resolved.name, "German");
+
+    localizer.set_session("s1", Some("es-MX")).await?;
+    let resolved = localizer.resolve(Some("s1"), Some("acme"), french).await;
+    assert_eq!((resolved.code.as_str(), resolved.source), ("es", LanguageSource::Session));
+    assert!(localizer.set_session("s1", Some("klingon")).await.is_err());
+
+    // Preferences set through the API are capped; existing keys can change
+    let capped = Localizer::new(LanguageConfig { max_preferences: 1, ..Default::default() }).await?;
+    capped.set_session("a", Some("fr")).await?;
+    assert!(capped.set_session("b", Some("fr")).await.is_err());
+    capped.set_session("a", Some("de")).await?;
+    capped.forget_session("a").await;
+    capped.set_session("b", Some("fr")).await?;
+
+    let mut messages = vec![Message::new("user", "Hola")];
+    enforce_language(&mut messages, &resolved);
+    assert_eq!(messages[0].role, "system");
+    assert!(messages[0].content.contains("Spanish (es)"));
+
+    let entries = [
+        ("Incident not found: {}".to_string(), "Incidente no encontrado: {}".to_string()),
+        ("Quota exceeded".to_string(), "Cuota excedida".to_string()),
+    ].into_iter().collect();
+    localizer.set_catalog("es", Catalog::from_entries(&entries)).await?;
+    assert_eq!(localizer.localize("Incident not found: abc-1", "es").await, "Incidente no encontrado: abc-1");
+    assert_eq!(localizer.localize("Quota exceeded", "es").await, "Cuota excedida");
+    assert_eq!(localizer.localize("Quota exceeded for tenant", "es").await, "Quota exceeded for tenant");
+
+    assert_eq!(parse_accept_language("fr-CH, de;q=0.5, en;q=0.9, xx"), vec!["fr", "en", "de"]);
+    Ok(())
+}
+
+#[cfg(feature = "vision")]
+#[tokio::test]
+async fn test_stream_stats_windows_and_percentiles() -> Result<(), Box<dyn Error>> {
+    use chrono::{Duration, Utc};
+    use vae::core::stream_stats::{parse_window, StreamStats, StreamStatsConfig};
+    use vae::vision::detector::{BBox, Detection};
+
+    let detection = |class: &str| Detection {
+        bbox: BBox { x: 0.0, y: 0.0, width: 10.0, height: 10.0 },
+        class_id: 0,
+        class_name: class.to_string(),
+        confidence: 0.9,
+        frame_id: 1,
+        timestamp: Utc::now(),
+    };
+
+    let stats = StreamStats::new(StreamStatsConfig { retention_secs: 600, ..Default::default() });
+    let now = Utc::now();
+    let start = now - Duration::seconds(300);
+
+    // Ten frames a second for the first two minutes, then nothing for a
+    // minute, then ten a second again
+    for tick in 0..3000 {
+        let at = start + Duration::milliseconds(tick * 100);
+        if (1200..1800).contains(&tick) {
+            continue;
+        }
+        stats.record_decode_at("cam1", (tick % 100) as f64, at).await;
+        let detections = if tick % 10 == 0 { vec![detection("person"), detection("car")] } else { Vec::new() };
+        stats.record_frame_at("cam1", 20.0 + (tick % 100) as f64, &detections, at).await;
+    }
+    for _ in 0..10 {
+        stats.record_drop_at("cam1", "detect_error", now - Duration::seconds(30)).await;
+    }
+
+    let report = stats.report_at("cam1", "1m", now).await?.unwrap();
+    assert!((report.fps - 10.0).abs() < 0.1, "fps {}", report.fps);
+    assert_eq!(report.processed_frames, 600);
+    assert_eq!(report.decode_latency.p50_ms, Some(50.0));
+    assert_eq!(report.decode_latency.max_ms, Some(99.0));
+    assert_eq!(report.inference_latency.p50_ms, Some(70.0));
+    assert_eq!(report.inference_latency.max_ms, Some(119.0));
+    assert_eq!(report.detections["person"].detections, 60);
+    assert!((report.detections["car"].per_minute - 60.0).abs() < 1.0);
+    assert_eq!(report.drops.total, 10);
+    assert_eq!(report.drops.by_reason["detect_error"], 10);
+    assert!(report.uptime.ratio > 0.99);
+    assert!(report.uptime.online_since.is_some());
+
+    // The five-minute window spans the one-minute outage
+    let report = stats.report_at("cam1", "5m", now).await?.unwrap();
+    assert_eq!(report.processed_frames, 2400);
+    assert!(report.uptime.ratio > 0.78 && report.uptime.ratio < 0.82, "uptime {}", report.uptime.ratio);
+
+    assert!(stats.report_at("cam1", "1h", now).await.is_err());
+    assert!(stats.report_at("cam2", "1m", now).await?.is_none());
+    assert_eq!(parse_window("90s")?, Duration::seconds(90));
+    assert!(parse_window("5w").is_err());
+    assert!(parse_window("0m").is_err());
+    Ok(())
+}
@@ -1,9 +1,14 @@
+#![cfg(feature = "llm-openai")]
+
+mod common;
+
 use vae::core::llm::{OpenAI, LLMTrait};
 use vae::core::llm::types::{Message, Response, ModelConfig};
 use vae::utils::{logger, config};
 use tokio;
 use std::error::Error;
 use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 #[tokio::test]
 async fn test_openai_initialization() -> Result<(), Box<dyn Error>> {
@@ -47,7 +52,7 @@ async fn test_streaming_completion() -> Result<(), Box<dyn Error>> {
         Message::new("user", "Write a short story.")
     ];
     
-    let mut stream = llm.complete_stream(messages).await?;
+    let mut stream = llm.complete_stream(messages, CancellationToken::new()).await?;
     let mut chunks = Vec::new();
     
     while let Some(chunk) = stream.next().await {
@@ -187,4 +192,521 @@ async fn test_response_validation() -> Result<(), Box<dyn Error>> {
     assert!(!response.model.is_empty());
     
     Ok(())
-}
\ No newline at end of file
+}
+#[test]
+fn test_stream_recovery_strips_repeated_text() {
+    use vae::core::llm::recovery::strip_overlap;
+
+    let partial = "The quick brown fox jumps over";
+    assert_eq!(strip_overlap(partial, "jumps over the lazy dog"), " the lazy dog");
+    assert_eq!(strip_overlap(partial, " the lazy dog"), " the lazy dog");
+    // Short coincidental overlaps are kept
+    assert_eq!(strip_overlap("a b c", "c d"), "c d");
+}
+
+#[tokio::test]
+async fn test_latency_tracker_percentiles_and_burn_rate() -> Result<(), Box<dyn Error>> {
+    use vae::core::llm::latency::{LatencyTracker, LatencyTrackerConfig, SloConfig};
+
+    let tracker = LatencyTracker::new(LatencyTrackerConfig {
+        slos: vec![SloConfig {
+            provider: "openai".to_string(),
+            model: "gpt-4".to_string(),
+            p95_ttft_ms: 500.0,
+            p95_completion_ms: 5000.0,
+            objective: 0.9,
+        }],
+        ..Default::default()
+    });
+
+    for i in 0..10 {
+        let ttft = if i < 8 { 200.0 } else { 1200.0 };
+        tracker.record("openai", "gpt-4", Some(ttft), 2000.0).await;
+    }
+
+    let report = tracker.report().await;
+    assert_eq!(report.len(), 1);
+    assert_eq!(report[0].time_to_first_token.count, 10);
+    assert_eq!(report[0].time_to_first_token.p50_ms, Some(200.0));
+    assert_eq!(report[0].time_to_first_token.p95_ms, Some(1200.0));
+    // 20% slow against a 10% budget burns at twice the planned rate
+    let burn = report[0].burn_rate_short.unwrap();
+    assert!((burn - 2.0).abs() < 1e-6);
+
+    Ok(())
+}
+
+#[test]
+fn test_finetune_training_format() -> Result<(), Box<dyn Error>> {
+    use vae::core::llm::finetune::to_training_jsonl;
+
+    let conversations = vec![
+        vec![Message::new("user", "Hi"), Message::new("assistant", "Hello")],
+        vec![Message::new("user", "Bye"), Message::new("assistant", "Goodbye")],
+    ];
+    let jsonl = to_training_jsonl(&conversations)?;
+    let lines: Vec<_> = jsonl.lines().collect();
+    assert_eq!(lines.len(), 2);
+
+    let record: serde_json::Value = serde_json::from_str(lines[0])?;
+    assert_eq!(record["messages"][1]["role"], "assistant");
+    assert_eq!(record["messages"][1]["content"], "Hello");
+
+    Ok(())
+}
+
+#[test]
+fn test_judge_weighted_score() {
+    use vae::core::llm::judge::{weighted_score, CriterionScore, Rubric};
+
+    let rubric = Rubric::default();
+    let score = |name: &str, score: u32| CriterionScore {
+        name: name.to_string(),
+        score,
+        reasoning: String::new(),
+    };
+
+    let perfect = vec![score("correctness", 5), score("helpfulness", 5), score("clarity", 5)];
+    assert!((weighted_score(&rubric, &perfect) - 1.0).abs() < 1e-6);
+
+    let mixed = vec![score("correctness", 1), score("helpfulness", 3), score("clarity", 5)];
+    assert!((weighted_score(&rubric, &mixed) - 0.5).abs() < 1e-6);
+
+    // A zero-point scale would make clamping panic, so it never gets that far
+    assert!(rubric.validate().is_ok());
+    assert!(Rubric { scale_max: 0, ..Rubric::default() }.validate().is_err());
+    assert!(Rubric { criteria: Vec::new(), ..Rubric::default() }.validate().is_err());
+}
+
+#[tokio::test]
+async fn test_router_fails_over_on_transient_errors() -> Result<(), Box<dyn Error>> {
+    use futures::StreamExt;
+    use vae::core::llm::router::{is_retryable, ApiError, LLMRouter, RouterConfig};
+    use common::{CallLog, StubLLM};
+
+    // Answers with its own name, or fails with `error`
+    let stub = |model: &str, error: Option<&'static str>, calls: &CallLog| {
+        let name = model.to_string();
+        Box::new(StubLLM::new(model)
+            .replying(move |_, _| error.map_or_else(|| Ok(name.clone()), |e| Err(anyhow::anyhow!(e))))
+            .recording(calls.clone()))
+    };
+
+    let primary_calls = CallLog::default();
+    let backup_calls = CallLog::default();
+    let router = LLMRouter::new(RouterConfig::default())
+        .with_provider("primary", stub("primary", Some("API returned 503 Service Unavailable: busy"), &primary_calls))
+        .with_provider("backup", stub("backup", None, &backup_calls));
+
+    let response = router.complete(vec![Message::new("user", "Hi")]).await?;
+    assert_eq!(response.model, "backup");
+    // The failed provider cools down and is skipped on the next request
+    router.complete(vec![Message::new("user", "Hi")]).await?;
+    assert_eq!(primary_calls.lock().unwrap().len(), 1);
+    assert_eq!(backup_calls.lock().unwrap().len(), 2);
+
+    // Streams fail over when the first chunk is an error
+    let router = LLMRouter::new(RouterConfig::default())
+        .with_provider("primary", stub("primary", Some("stream error: Overloaded"), &primary_calls))
+        .with_provider("backup", stub("backup", None, &backup_calls));
+    let mut stream = router.complete_stream(vec![Message::new("user", "Hi")], CancellationToken::new()).await?;
+    assert_eq!(stream.next().await.unwrap()?.content, "backup");
+
+    // Client errors would fail everywhere, so they are returned directly
+    let rejected_calls = CallLog::default();
+    let router = LLMRouter::new(RouterConfig::default())
+        .with_provider("primary", stub("primary", Some("API returned 400 Bad Request: bad prompt"), &rejected_calls))
+        .with_provider("backup", stub("backup", None, &backup_calls));
+    assert!(router.complete(vec![Message::new("user", "Hi")]).await.is_err());
+    assert_eq!(backup_calls.lock().unwrap().len(), 3);
+
+    assert!(is_retryable(&anyhow::anyhow!("API returned 429 Too Many Requests: slow down")));
+    assert!(is_retryable(&anyhow::anyhow!("request timed out")));
+    assert!(!is_retryable(&anyhow::anyhow!("invalid api key")));
+
+    // The status decides, whatever the message says
+    assert!(is_retryable(&ApiError::new("Anthropic", 529, "Overloaded").into()));
+    assert!(!is_retryable(&ApiError::new("OpenAI", 400, "timeout must be positive").into()));
+    let wrapped = anyhow::Error::from(ApiError::new("OpenAI", 503, "busy")).context("Completion failed");
+    assert!(is_retryable(&wrapped));
+    assert!(!is_retryable(&anyhow::anyhow!("OpenAI error, status code 400: request timeout too long")));
+    assert!(is_retryable(&anyhow::anyhow!("upstream returned status: 502")));
+    assert!(!is_retryable(&anyhow::anyhow!("Embedder returned 5000 vectors for 2 texts")));
+
+    Ok(())
+}
+
+#[test]
+fn test_context_trimming_keeps_system_and_latest() -> Result<(), Box<dyn Error>> {
+    use vae::core::llm::tokenizer::{count_tokens, trim_to_budget, ContextConfig, Tokenizer};
+
+    let tokenizer = Tokenizer::for_model("gpt-4");
+    assert_eq!(tokenizer.count("hello world"), 2);
+    // Message framing and reply priming are counted too
+    let single = vec![Message::new("user", "hello world")];
+    assert_eq!(count_tokens(&single), 3 + 1 + 2 + 3);
+
+    let mut messages = vec![Message::new("system", "You are Lilith.")];
+    for i in 0..20 {
+        messages.push(Message::new("user", &format!("Question {} about camera {}", i, i)));
+        messages.push(Message::new("assistant", &format!("Answer {} with some detail", i)));
+    }
+    messages.push(Message::new("user", "What did I just ask?"));
+
+    let budget = tokenizer.count_tokens(&messages) / 3;
+    let trimmed = trim_to_budget(&tokenizer, &messages, budget)?;
+    assert!(tokenizer.count_tokens(&trimmed.messages) <= budget);
+    assert_eq!(trimmed.messages[0].role, "system");
+    assert_eq!(trimmed.messages.last().unwrap().content, "What did I just ask?");
+    // The oldest turns go first and the newest survive
+    assert_eq!(trimmed.dropped[0].content, "Question 0 about camera 0");
+    assert_eq!(trimmed.messages.len() + trimmed.dropped.len(), messages.len());
+    assert_eq!(trimmed.messages[trimmed.messages.len() - 2].content, "Answer 19 with some detail");
+
+    // Nothing can be cut from a prompt that is all system message and question
+    assert!(trim_to_budget(&tokenizer, &messages, 10).is_err());
+
+    let config = ContextConfig::default();
+    assert_eq!(config.window_for("gpt-4o-mini"), 128_000);
+    assert_eq!(config.window_for("my-local-model"), 8_192);
+    assert!(config.prompt_budget("gpt-4", 1000) < 8_192 - 1000);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_context_guard_fits_the_smallest_routed_window() -> Result<(), Box<dyn Error>> {
+    use vae::core::llm::tokenizer::{ContextConfig, ContextGuard, Tokenizer};
+    use common::{CallLog, StubLLM};
+
+    // About 6000 tokens: fits gpt-4o's window but not a 4096-token local model
+    let mut messages = vec![Message::new("system", "You are Lilith.")];
+    for i in 0..300 {
+        messages.push(Message::new("user", &format!("Question {} about camera {} and its zones", i, i)));
+    }
+    let tokenizer = Tokenizer::for_model("gpt-4o");
+    assert!(tokenizer.count_tokens(&messages) > 4_096);
+
+    let mut config = ContextConfig::default();
+    config.context_windows.insert("llama".to_string(), 4_096);
+
+    let calls = CallLog::default();
+    let alone = ContextGuard::new(Box::new(StubLLM::new("gpt-4o").recording(calls.clone())), config.clone());
+    alone.complete(messages.clone()).await?;
+    assert_eq!(calls.lock().unwrap()[0].messages.len(), messages.len());
+
+    // With a local fallback behind the router the smaller window decides
+    let calls = CallLog::default();
+    let routed = ContextGuard::new(Box::new(StubLLM::new("gpt-4o").recording(calls.clone())), config)
+        .with_models(vec!["gpt-4o".to_string(), "llama-3-8b".to_string()]);
+    routed.complete(messages.clone()).await?;
+    let sent = calls.lock().unwrap()[0].messages.clone();
+    assert!(sent.len() < messages.len());
+    assert!(tokenizer.count_tokens(&sent) <= 4_096);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_response_cache_short_circuits_identical_requests() -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+    use futures::StreamExt;
+    use vae::core::llm::cache::{cache_key, CacheConfig, CachedLLM, CachedMultimodal, ResponseCache};
+    use vae::core::llm::candidates::CompleteN;
+    use vae::core::llm::multimodal::{ImagePart, MultimodalLLM, MultimodalMessage};
+    use common::{CallLog, StubLLM};
+
+    // Numbers its captions, counting calls through every cache
+    let counting = |calls: &CallLog| Box::new(StubLLM::new("captioner")
+        .replying(|messages, n| Ok(format!("caption {} for {}", n + 1, messages.last().unwrap().content)))
+        .with_usage(10, 5)
+        .recording(calls.clone()));
+
+    let calls = CallLog::default();
+    let cache = Arc::new(ResponseCache::new(CacheConfig { max_entries: 2, ..Default::default() })?);
+    let llm = CachedLLM::new(counting(&calls), cache.clone());
+    let prompt = |text: &str| vec![Message::new("system", "Describe the frame."), Message::new("user", text)];
+
+    let first = llm.complete(prompt("frame 1")).await?;
+    let again = llm.complete(prompt("frame 1")).await?;
+    assert_eq!(first.content, "caption 1 for frame 1");
+    assert_eq!(again.content, first.content);
+    assert_eq!(again.usage.total_tokens, 15);
+    assert_eq!(calls.lock().unwrap().len(), 1);
+
+    // Hits replay as a single chunk; misses stream from the provider
+    let mut stream = llm.complete_stream(prompt("frame 1"), CancellationToken::new()).await?;
+    assert_eq!(stream.next().await.unwrap()?.content, first.content);
+    let mut stream = llm.complete_stream(prompt("frame 9"), CancellationToken::new()).await?;
+    assert_eq!(stream.next().await.unwrap()?.content, "caption 2 for frame 9");
+
+    // The least recently used entry goes first
+    llm.complete(prompt("frame 2")).await?;
+    llm.complete(prompt("frame 3")).await?;
+    llm.complete(prompt("frame 1")).await?;
+    assert_eq!(calls.lock().unwrap().len(), 5);
+
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses, stats.entries), (2, 5, 2));
+    assert!((stats.hit_rate - 2.0 / 7.0).abs() < 1e-9);
+
+    // Role and content boundaries both count
+    let split = cache_key("m", &ModelConfig::default(), &[Message::new("user", "ab"), Message::new("user", "c")]);
+    let joined = cache_key("m", &ModelConfig::default(), &[Message::new("user", "a"), Message::new("user", "bc")]);
+    assert_ne!(split, joined);
+
+    // Entries expire after the TTL
+    let expiring = Arc::new(ResponseCache::new(CacheConfig { ttl_secs: 0, ..Default::default() })?);
+    let llm = CachedLLM::new(counting(&calls), expiring);
+    llm.complete(prompt("frame 1")).await?;
+    llm.complete(prompt("frame 1")).await?;
+    assert_eq!(calls.lock().unwrap().len(), 7);
+
+    // Best-of-n candidates skip the cache, so each one is sampled
+    let calls = CallLog::default();
+    let llm = CachedLLM::new(counting(&calls), Arc::new(ResponseCache::new(CacheConfig::default())?));
+    let candidates = llm.complete_n(prompt("frame 1"), 3).await?;
+    assert_eq!(candidates.len(), 3);
+    assert_eq!(calls.lock().unwrap().len(), 3);
+    assert_ne!(candidates[0].content, candidates[1].content);
+
+    // Captions of the same image hit; another image with the same prompt misses
+    let calls = CallLog::default();
+    let vision = CachedMultimodal::new(*counting(&calls), Arc::new(ResponseCache::new(CacheConfig::default())?));
+    let caption = |jpeg: &[u8]| vec![MultimodalMessage::new("user", "Caption this frame.").with_image(ImagePart::jpeg(jpeg))];
+    let first = vision.complete_multimodal(caption(b"frame-a")).await?;
+    assert_eq!(vision.complete_multimodal(caption(b"frame-a")).await?.content, first.content);
+    vision.complete_multimodal(caption(b"frame-b")).await?;
+    assert_eq!(calls.lock().unwrap().len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_best_of_n_selects_among_concurrent_candidates() -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+    use futures::StreamExt;
+    use vae::core::llm::candidates::{BestOfN, CandidateSelector, CompleteN, ConsensusSelector, JudgeSelector};
+    use vae::core::llm::judge::{Judge, JudgeConfig};
+    use vae::core::llm::types::Usage;
+    use common::StubLLM;
+
+    // Answers in turn from a script; "fail" is an error
+    let scripted = |answers: Vec<&'static str>| StubLLM::new("scripted")
+        .replying(move |messages, n| match answers[n % answers.len()] {
+            "fail" => Err(anyhow::anyhow!("API returned 503 Service Unavailable")),
+            "judge" => {
+                // Judge stub: full marks for the detailed answer only
+                let score = if messages.last().unwrap().content.contains("Assistant response:\ndetailed") { 5 } else { 2 };
+                Ok(format!(
+                    r#"{{"scores": [{{"name": "correctness", "score": {s}, "reasoning": ""}}, {{"name": "helpfulness", "score": {s}, "reasoning": ""}}, {{"name": "clarity", "score": {s}, "reasoning": ""}}]}}"#,
+                    s = score,
+                ))
+            }
+            answer => Ok(answer.to_string()),
+        })
+        .with_usage(10, 5)
+        .with_config(ModelConfig { temperature: 0.8, ..ModelConfig::default() });
+    let question = || vec![Message::new("user", "How many people are in the lobby?")];
+
+    // Failed candidates are dropped, and only all of them failing is an error
+    let llm = scripted(vec!["4", "fail", "5"]);
+    assert_eq!(llm.complete_n(question(), 3).await?.len(), 2);
+    assert!(scripted(vec!["fail"]).complete_n(question(), 2).await.is_err());
+
+    // Majority answer wins; usage covers every candidate that came back
+    let best = BestOfN::new(Box::new(scripted(vec!["4", " 4 ", "5", "fail"])), 4, Arc::new(ConsensusSelector));
+    let response = best.complete(question()).await?;
+    assert_eq!(response.content, "4");
+    assert_eq!(response.usage.total_tokens, 45);
+    let mut stream = best.complete_stream(question(), CancellationToken::new()).await?;
+    assert_eq!(stream.next().await.unwrap()?.content, "4");
+
+    // The judge scores each candidate and the best one is kept
+    let judge = Arc::new(Judge::new(JudgeConfig::default(), Arc::new(scripted(vec!["judge"]))));
+    let candidates: Vec<Response> = ["brief", "detailed", "vague"].iter()
+        .map(|content| Response {
+            content: content.to_string(),
+            role: "assistant".to_string(),
+            model: "scripted".to_string(),
+            usage: Usage { prompt_tokens: 0, completion_tokens: 0, total_tokens: 0 },
+        })
+        .collect();
+    let selection = JudgeSelector::new(judge.clone()).select(&question(), &candidates).await?;
+    assert_eq!(selection.index, 1);
+    // Each judge call is metered
+    assert_eq!(selection.tokens_used, 45);
+
+    // A selector that fails leaves the first candidate
+    let unscoreable = Arc::new(Judge::new(JudgeConfig::default(), Arc::new(scripted(vec!["not json"]))));
+    let best = BestOfN::new(Box::new(scripted(vec!["a", "b"])), 2, Arc::new(JudgeSelector::new(unscoreable)));
+    assert_eq!(best.complete(question()).await?.content, "a");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancelled_stream_stops_reading_the_provider() -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use futures::StreamExt;
+    use vae::core::llm::recovery::{complete_stream_with_recovery, RecoveryConfig};
+    use vae::core::llm::types::StreamChunk;
+    use common::StubLLM;
+
+    // Never finishes on its own; counts the chunks pulled from it
+    let endless = |pulled: &Arc<AtomicU32>| {
+        let pulled = pulled.clone();
+        StubLLM::new("endless").streaming(move || {
+            let pulled = pulled.clone();
+            Box::pin(futures::stream::repeat_with(move || {
+                pulled.fetch_add(1, Ordering::SeqCst);
+                Ok(StreamChunk { content: "token ".to_string() })
+            }))
+        })
+    };
+
+    let pulled = Arc::new(AtomicU32::new(0));
+    let llm = endless(&pulled);
+    let cancel = CancellationToken::new();
+    let mut stream = llm.complete_stream(vec![Message::new("user", "Hi")], cancel.clone()).await?;
+    stream.next().await.unwrap()?;
+    stream.next().await.unwrap()?;
+    cancel.cancel();
+    assert!(stream.next().await.is_none());
+    assert_eq!(pulled.load(Ordering::SeqCst), 2);
+
+    // Already cancelled: the provider isn't called at all
+    assert!(llm.complete_stream(vec![Message::new("user", "Hi")], cancel.clone()).await.is_err());
+
+    // Recovery ends quietly instead of asking for a continuation
+    let cancel = CancellationToken::new();
+    let llm: Arc<dyn LLMTrait> = Arc::new(endless(&pulled));
+    let stream = complete_stream_with_recovery(llm, vec![Message::new("user", "Hi")], RecoveryConfig::default(), cancel.clone());
+    futures::pin_mut!(stream);
+    assert_eq!(stream.next().await.unwrap()?.content, "token ");
+    cancel.cancel();
+    assert!(stream.next().await.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_annotated_stream_tags_model_and_finish_reason() -> Result<(), Box<dyn Error>> {
+    use futures::StreamExt;
+    use vae::core::llm::annotated::{annotate, AnnotatedChunk, AnnotatedStream, StreamReport, TokenLogprob};
+    use vae::core::llm::router::{LLMRouter, RouterConfig};
+    use vae::core::llm::types::StreamChunk;
+    use common::StubLLM;
+
+    let chunks = futures::stream::iter(vec![
+        Ok(StreamChunk { content: "Hel".to_string() }),
+        Ok(StreamChunk { content: "lo".to_string() }),
+    ]);
+    let annotated: Vec<AnnotatedChunk> = annotate(chunks, "stub-1".to_string())
+        .map(|item| item.unwrap())
+        .collect()
+        .await;
+    assert_eq!(annotated.len(), 3);
+    assert!(annotated.iter().all(|c| c.metadata.model.as_deref() == Some("stub-1")));
+    assert_eq!(annotated[1].metadata.finish_reason, None);
+    assert_eq!(annotated[2].content, "");
+    assert_eq!(annotated[2].metadata.finish_reason.as_deref(), Some("stop"));
+
+    // Unset fields stay out of the SSE payload
+    let json = serde_json::to_value(&annotated[0])?;
+    assert_eq!(json, serde_json::json!({ "content": "Hel", "model": "stub-1" }));
+
+    // A failed stream has no finish chunk
+    let chunks = futures::stream::iter(vec![
+        Ok(StreamChunk { content: "Hel".to_string() }),
+        Err(anyhow::anyhow!("connection reset")),
+    ]);
+    let items: Vec<_> = annotate(chunks, "stub-1".to_string()).collect().await;
+    assert_eq!(items.len(), 2);
+    assert!(items[1].is_err());
+
+    // Through a stack, what the serving provider reports wins: the route
+    // that answered after a failover, its logprobs and a length cutoff
+    let truncated = StubLLM::new("backup").streaming(|| {
+        let report = StreamReport::current();
+        report.logprob(TokenLogprob { token: "Hel".to_string(), logprob: -0.25, top_logprobs: Vec::new() });
+        report.finish("length");
+        Box::pin(futures::stream::iter(vec![Ok(StreamChunk { content: "Hel".to_string() })]))
+    });
+    let router = LLMRouter::new(RouterConfig::default())
+        .with_provider("primary", Box::new(StubLLM::new("primary")
+            .replying(|_, _| Err(anyhow::anyhow!("API returned 503 Service Unavailable: busy")))))
+        .with_provider("backup", Box::new(truncated));
+    let annotated: Vec<AnnotatedChunk> = router
+        .complete_stream_annotated(vec![Message::new("user", "Hi")], CancellationToken::new())
+        .await?
+        .map(|item| item.unwrap())
+        .collect()
+        .await;
+    assert_eq!(annotated.len(), 2);
+    assert!(annotated.iter().all(|c| c.metadata.model.as_deref() == Some("backup")));
+    assert_eq!(annotated[0].metadata.logprobs.as_ref().map(Vec::len), Some(1));
+    assert_eq!(annotated[1].metadata.logprobs, None);
+    assert_eq!(annotated[1].metadata.finish_reason.as_deref(), Some("length"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_model_override_respects_allowlist() -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+    use vae::core::llm::overrides::{AllowedModel, ModelOverride, ModelOverrideConfig, ModelOverrides};
+    use common::{CallLog, StubLLM};
+
+    let calls = CallLog::default();
+    let config = ModelOverrideConfig {
+        allowed_models: vec![
+            AllowedModel { model: "claude-haiku-*".to_string(), provider: "anthropic".to_string() },
+            AllowedModel { model: "llama-*".to_string(), provider: "local".to_string() },
+        ],
+        max_tokens: 2048,
+        ..Default::default()
+    };
+    let default: Arc<dyn LLMTrait> = Arc::new(StubLLM::new("default-model").recording(calls.clone()));
+    let overrides = ModelOverrides::new(config, "openai", default.clone())
+        .with_fallbacks(&["anthropic".to_string()]);
+
+    // Nothing overridden: the shared instance itself
+    let llm = overrides.resolve(&ModelOverride::default())?;
+    assert!(Arc::ptr_eq(&llm, &default));
+
+    let request: ModelOverride = serde_json::from_value(serde_json::json!({
+        "model": "claude-haiku-4-5", "temperature": 0.2, "max_tokens": 512
+    }))?;
+    let llm = overrides.resolve(&request)?;
+    assert_eq!(llm.get_model(), "claude-haiku-4-5");
+    assert_eq!(llm.get_model_config().max_tokens, 512);
+
+    // The call goes through the shared instance with the override applied
+    // to that call only
+    let response = llm.complete(vec![Message::new("user", "hi")]).await?;
+    assert_eq!(response.model, "claude-haiku-4-5");
+    default.complete(vec![Message::new("user", "hi")]).await?;
+    {
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!((calls[0].model.as_str(), calls[0].config.temperature, calls[0].config.max_tokens), ("claude-haiku-4-5", 0.2, 512));
+        assert_eq!(calls[1].model, "default-model");
+        assert_eq!(calls[1].config.max_tokens, ModelConfig::default().max_tokens);
+    }
+
+    // Tuning the default model keeps it
+    let llm = overrides.resolve(&ModelOverride { temperature: Some(1.0), ..Default::default() })?;
+    llm.complete(vec![Message::new("user", "hi")]).await?;
+    assert_eq!(calls.lock().unwrap()[2].model, "default-model");
+
+    let rejected = [
+        ModelOverride { model: Some("gpt-4o".to_string()), ..Default::default() },
+        // Allowed, but its provider isn't behind the shared instance
+        ModelOverride { model: Some("llama-3".to_string()), ..Default::default() },
+        ModelOverride { temperature: Some(3.0), ..Default::default() },
+        ModelOverride { max_tokens: Some(100_000), ..Default::default() },
+    ];
+    for request in &rejected {
+        assert!(overrides.resolve(request).is_err());
+    }
+    assert!(overrides.validate(&rejected[0]).unwrap_err().to_string().starts_with("model:"));
+    Ok(())
+}
@@ -0,0 +1,60 @@
+#![cfg(feature = "llm-local")]
+
+use vae::core::llm::local::{LocalLLM, LocalLLMConfig};
+use vae::core::llm::LLMTrait;
+use vae::core::llm::types::Message;
+use futures::StreamExt;
+use std::error::Error;
+use tokio_util::sync::CancellationToken;
+
+#[test]
+fn test_missing_model_fails_fast() {
+    let config = LocalLLMConfig {
+        model_path: "models/does-not-exist.gguf".to_string(),
+        ..LocalLLMConfig::default()
+    };
+    let err = LocalLLM::new(config).err().expect("loading a missing model must fail");
+    assert!(err.to_string().contains("does-not-exist.gguf"));
+}
+
+// Needs a real model: VAE_TEST_GGUF=/path/to/model.gguf cargo test --features llm-local
+#[tokio::test]
+async fn test_local_streaming_completion() -> Result<(), Box<dyn Error>> {
+    let Ok(model_path) = std::env::var("VAE_TEST_GGUF") else {
+        return Ok(());
+    };
+    let llm = LocalLLM::new(LocalLLMConfig { model_path, ..LocalLLMConfig::default() })?;
+
+    let messages = vec![Message::new("user", "Say hello in one word.")];
+    let mut stream = llm.complete_stream(messages.clone(), CancellationToken::new()).await?;
+    let mut streamed = String::new();
+    while let Some(chunk) = stream.next().await {
+        streamed.push_str(&chunk?.content);
+    }
+    assert!(!streamed.is_empty());
+
+    let response = llm.complete(messages).await?;
+    assert!(response.usage.prompt_tokens > 0);
+    assert_eq!(response.usage.total_tokens, response.usage.prompt_tokens + response.usage.completion_tokens);
+
+    Ok(())
+}
+
+// Needs a real model, like the test above
+#[tokio::test]
+async fn test_local_models_share_the_backend_and_take_long_prompts() -> Result<(), Box<dyn Error>> {
+    let Ok(model_path) = std::env::var("VAE_TEST_GGUF") else {
+        return Ok(());
+    };
+    // A second model in the same process used to fail initializing llama.cpp
+    let config = LocalLLMConfig { model_path, context_size: 8192, ..LocalLLMConfig::default() };
+    let _first = LocalLLM::new(config.clone())?;
+    let llm = LocalLLM::new(config)?;
+
+    // Longer than one decode batch
+    let long = "The loading dock camera saw a truck. ".repeat(400);
+    let messages = vec![Message::new("user", &format!("{}How many trucks?", long))];
+    let response = llm.complete(messages).await?;
+    assert!(response.usage.prompt_tokens > 2048);
+    Ok(())
+}
@@ -0,0 +1,92 @@
+#![cfg(all(feature = "api", feature = "vision"))]
+
+use vae::api::proto::v1;
+use vae::vision::detector::{Detection, BBox};
+use prost::Message;
+use std::error::Error;
+
+fn sample_detection() -> Detection {
+    Detection {
+        bbox: BBox { x: 10.0, y: 20.0, width: 30.0, height: 40.0 },
+        class_id: 2,
+        class_name: "car".to_string(),
+        confidence: 0.87,
+        frame_id: 42,
+        timestamp: chrono::Utc::now(),
+    }
+}
+
+#[test]
+fn test_detection_roundtrip() -> Result<(), Box<dyn Error>> {
+    let detection = sample_detection();
+
+    let encoded = v1::Detection::from(&detection).encode_to_vec();
+    let decoded = Detection::try_from(v1::Detection::decode(encoded.as_slice())?)?;
+
+    assert_eq!(decoded.class_id, detection.class_id);
+    assert_eq!(decoded.class_name, detection.class_name);
+    assert_eq!(decoded.frame_id, detection.frame_id);
+    assert_eq!(decoded.timestamp, detection.timestamp);
+    assert_eq!(decoded.bbox.width, detection.bbox.width);
+
+    Ok(())
+}
+
+#[test]
+fn test_detection_requires_bbox() {
+    let mut message = v1::Detection::from(&sample_detection());
+    message.bbox = None;
+
+    assert!(Detection::try_from(message).is_err());
+}
+
+#[test]
+fn test_detection_event_payload() {
+    let detections = vec![sample_detection(), sample_detection()];
+    let event = v1::Event::detections("cam-1", 42, &detections);
+
+    assert_eq!(event.stream_id, "cam-1");
+    match event.payload {
+        Some(v1::event::Payload::Detections(batch)) => assert_eq!(batch.detections.len(), 2),
+        other => panic!("unexpected payload: {:?}", other),
+    }
+}
+
+#[test]
+fn test_density_grid_size_is_checked() {
+    use vae::vision::density::DensityInfo;
+
+    let grid = |rows: u32, cols: u32, cells: usize| v1::DensityInfo { count: 0.0, rows, cols, cells: vec![0.0; cells] };
+    assert!(DensityInfo::try_from(grid(2, 3, 6)).is_ok());
+    assert!(DensityInfo::try_from(grid(2, 3, 5)).is_err());
+    // 65536 * 65536 wraps to 0 in u32
+    assert!(DensityInfo::try_from(grid(65536, 65536, 0)).is_err());
+}
+
+#[test]
+fn test_kafka_records_encode_as_pipeline_results() -> Result<(), Box<dyn Error>> {
+    use vae::core::sinks::{encode_record, KafkaFormat};
+
+    let detection = sample_detection();
+    let record = serde_json::json!({
+        "delivery_id": "3f2a",
+        "frame_id": 42,
+        "source": "rtsp://dock-3",
+        "timestamp": detection.timestamp,
+        "detections": [detection],
+        "analysis": null,
+        "metadata": { "stream_id": "dock-3" }
+    });
+
+    let result = v1::PipelineResult::decode(encode_record(&record, KafkaFormat::Proto)?.as_slice())?;
+    assert_eq!(result.delivery_id, "3f2a");
+    assert_eq!(result.source, "rtsp://dock-3");
+    assert_eq!(result.detections.len(), 1);
+    assert_eq!(result.detections[0].class_name, "car");
+    assert_eq!(result.metadata.get("stream_id").map(String::as_str), Some("dock-3"));
+
+    let json: serde_json::Value = serde_json::from_slice(&encode_record(&record, KafkaFormat::Json)?)?;
+    assert_eq!(json, record);
+
+    Ok(())
+}
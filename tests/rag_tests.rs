@@ -0,0 +1,257 @@
+use vae::core::rag::ingest::{chunk_blocks, parse_html, parse_text, BlockKind, IngestConfig};
+use vae::core::rag::store::{Embedder, InMemoryVectorStore, Indexer, VectorStore};
+use async_trait::async_trait;
+use std::error::Error;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingEmbedder {
+    model: String,
+    calls: AtomicUsize,
+}
+
+#[async_trait]
+impl Embedder for CountingEmbedder {
+    fn model_id(&self) -> String {
+        self.model.clone()
+    }
+
+    fn dimensions(&self) -> usize {
+        2
+    }
+
+    async fn embed(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        self.calls.fetch_add(texts.len(), Ordering::SeqCst);
+        Ok(texts.iter().map(|t| vec![t.len() as f32, 1.0]).collect())
+    }
+}
+
+#[test]
+fn test_chunks_follow_sections() {
+    let text = "# Setup\n\nInstall the camera.\n\n## Wiring\n\nConnect power first.\n\n# Usage\n\nOpen the dashboard.";
+    let blocks = parse_text(text);
+    assert_eq!(blocks[0].kind, BlockKind::Heading);
+
+    let config = IngestConfig { min_chunk_chars: 0, ..Default::default() };
+    let chunks = chunk_blocks(&blocks, "doc", "file:doc", "manual.md", &config);
+
+    let sections: Vec<_> = chunks.iter()
+        .map(|c| c.metadata.section.clone().unwrap_or_default())
+        .collect();
+    assert_eq!(sections, vec!["Setup", "Setup > Wiring", "Usage"]);
+    assert_eq!(chunks[1].text, "Connect power first.");
+}
+
+#[test]
+fn test_long_sections_split_with_overlap() {
+    let paragraph = "Sentence number one is here. ".repeat(20);
+    let blocks = parse_text(&format!("{}\n\n{}", paragraph, paragraph));
+    let config = IngestConfig { max_chunk_chars: 700, overlap_chars: 50, min_chunk_chars: 0 };
+    let chunks = chunk_blocks(&blocks, "doc", "file:doc", "notes.txt", &config);
+
+    assert!(chunks.len() > 1);
+    assert!(chunks.iter().all(|c| c.text.len() <= 700 + 50));
+    assert_eq!(chunks[0].metadata.chunk_index, 0);
+}
+
+#[test]
+fn test_html_parsing() -> Result<(), Box<dyn Error>> {
+    let html = "<html><body><h2>Alerts</h2><p>Alerts  fire when\n thresholds are crossed.</p><ul><li>Slack</li></ul></body></html>";
+    let blocks = parse_html(html)?;
+
+    assert_eq!(blocks.len(), 3);
+    assert_eq!(blocks[0].level, Some(2));
+    assert_eq!(blocks[1].text, "Alerts fire when thresholds are crossed.");
+    assert_eq!(blocks[2].kind, BlockKind::ListItem);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_incremental_indexing_skips_unchanged_chunks() -> Result<(), Box<dyn Error>> {
+    let store = Arc::new(InMemoryVectorStore::new());
+    let embedder = Arc::new(CountingEmbedder { model: "embed-v1".to_string(), calls: AtomicUsize::new(0) });
+    let indexer = Indexer::new(store.clone(), embedder.clone());
+    let config = IngestConfig { min_chunk_chars: 0, ..Default::default() };
+
+    let v1 = parse_text("# A\n\nFirst.\n\n# B\n\nSecond.\n\n# C\n\nThird.");
+    let chunks = chunk_blocks(&v1, "doc", "file:doc", "doc.md", &config);
+    let stats = indexer.index_document("doc", chunks).await?;
+    assert_eq!(stats.embedded, 3);
+
+    // Second section edited, third removed
+    let v2 = parse_text("# A\n\nFirst.\n\n# B\n\nSecond, revised.");
+    let chunks = chunk_blocks(&v2, "doc", "file:doc", "doc.md", &config);
+    let stats = indexer.index_document("doc", chunks).await?;
+    assert_eq!((stats.embedded, stats.unchanged, stats.removed), (1, 1, 1));
+    assert_eq!(embedder.calls.load(Ordering::SeqCst), 4);
+    assert_eq!(store.count().await?, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_keyword_index_matches_exact_identifiers() {
+    use vae::core::rag::hybrid::{fuse, tokenize, Bm25Params, HybridConfig, KeywordIndex};
+
+    assert!(tokenize("Error ERR-1042 on cam_03.").contains(&"err-1042".to_string()));
+
+    let config = IngestConfig { min_chunk_chars: 0, ..Default::default() };
+    let blocks = parse_text("# Errors\n\nERR-1042 means the RTSP source timed out.\n\n# Other\n\nCameras reconnect automatically after errors.");
+    let chunks = chunk_blocks(&blocks, "doc", "file:doc", "errors.md", &config);
+
+    let index = KeywordIndex::new(Bm25Params::default());
+    index.upsert(&chunks).await;
+
+    let keyword = index.search("what does ERR-1042 mean", 5).await;
+    assert_eq!(keyword[0].chunk.id, chunks[0].id);
+
+    // A chunk ranked by both retrievers beats one ranked by only one
+    let vector = vec![keyword[0].clone()];
+    let fused = fuse(&vector, &keyword, &HybridConfig::default());
+    assert_eq!(fused[0].chunk.id, chunks[0].id);
+}
+
+#[test]
+fn test_citations_are_verified_against_chunks() {
+    use vae::core::rag::citations::{ground_answer, CitationConfig, UncitedPolicy};
+    use vae::core::rag::store::ScoredChunk;
+
+    let config = IngestConfig { min_chunk_chars: 0, ..Default::default() };
+    let blocks = parse_text("ERR-1042 means the RTSP source timed out.");
+    let chunk = chunk_blocks(&blocks, "doc", "file:doc", "errors.md", &config).remove(0);
+    let chunks = vec![ScoredChunk { chunk, score: 1.0 }];
+
+    let raw = "ERR-1042 is an RTSP timeout. [chunk:doc#0 \"the  RTSP source timed out\"] \
+               It also reboots the camera [chunk:doc#0 \"reboots the camera\"]. \
+               Upgrade to firmware 2.4.1 or later.";
+    let answer = ground_answer(raw, &chunks, &CitationConfig::default());
+
+    assert_eq!(answer.claims.len(), 3);
+    assert!(answer.claims[0].grounded);
+    assert!(!answer.claims[1].grounded);
+    assert!(!answer.claims[2].grounded);
+    assert_eq!(answer.claims[2].text, "Upgrade to firmware 2.4.1 or later.");
+    assert_eq!(answer.citations[0].start, Some(15));
+    assert_eq!(answer.citations[0].end, Some(40));
+    assert!(answer.low_confidence);
+
+    let strict = CitationConfig { uncited_policy: UncitedPolicy::Drop, ..Default::default() };
+    let answer = ground_answer(raw, &chunks, &strict);
+    assert_eq!(answer.answer, "ERR-1042 is an RTSP timeout.");
+}
+
+#[tokio::test]
+async fn test_history_search_links_prior_incidents() -> Result<(), Box<dyn Error>> {
+    use std::collections::HashMap;
+    use chrono::Utc;
+    use vae::core::alerts::Alert;
+    use vae::core::incidents::{IncidentConfig, IncidentManager};
+    use vae::core::notify::Severity;
+    use vae::core::rag::history::{HistoryConfig, HistoryIndexer, HistoryKind};
+    use vae::core::rag::hybrid::{Bm25Params, HybridConfig, KeywordIndex};
+
+    let incidents = Arc::new(IncidentManager::new(IncidentConfig::default()));
+    let alert = |key: &str, name: &str, stream: &str| Alert {
+        key: key.to_string(),
+        name: name.to_string(),
+        severity: Severity::Warning,
+        message: String::new(),
+        labels: HashMap::from([("stream_id".to_string(), stream.to_string())]),
+        raised_at: Utc::now(),
+        last_seen: Utc::now(),
+        resolved_at: None,
+        occurrences: 1,
+    };
+    let outage = incidents.ingest_alert(&alert("rtsp-lobby", "RTSP timeout on lobby camera", "lobby")).await;
+    incidents.add_note(&outage.id, "ops", "Switch PoE port rebooted, camera came back").await?;
+    incidents.resolve(&outage.id, "ops").await?;
+    let crowd = incidents.ingest_alert(&alert("crowd-gate", "Crowd density high at gate", "gate")).await;
+
+    let store = Arc::new(InMemoryVectorStore::new());
+    let embedder = Arc::new(CountingEmbedder { model: "embed-v1".to_string(), calls: AtomicUsize::new(0) });
+    let keywords = Arc::new(KeywordIndex::new(Bm25Params::default()));
+
+    // Records that aren't history are skipped even if they end up in the store
+    let config = IngestConfig { min_chunk_chars: 0, ..Default::default() };
+    let manual = chunk_blocks(&parse_text("RTSP timeout: check the camera PoE port."), "doc", "file:doc", "manual.md", &config);
+    Indexer::new(store.clone(), embedder.clone()).index_document("doc", manual.clone()).await?;
+    keywords.upsert(&manual).await;
+
+    let history = HistoryIndexer::new(HistoryConfig::default(), store.clone(), embedder.clone(), keywords.clone(), incidents.clone());
+    let stats = history.sync().await?;
+    assert_eq!((stats.incidents, stats.embedded), (2, 2));
+
+    let results = history.similar("RTSP timeout on the lobby camera again", &[HistoryKind::Incident], None, Some(&crowd.id)).await?;
+    assert_eq!(results[0].id, outage.id);
+    assert_eq!(results[0].link, format!("/v1/incidents/{}", outage.id));
+    assert!(results[0].excerpt.contains("PoE port rebooted"));
+    assert!(results.iter().all(|r| r.id != crowd.id && r.kind == HistoryKind::Incident));
+
+    // Filtered-out candidates widen the search instead of hiding matches
+    let narrow = HistoryConfig {
+        retrieval: HybridConfig { candidates: 1, top_k: 1, ..Default::default() },
+        ..Default::default()
+    };
+    let narrow = HistoryIndexer::new(narrow, store.clone(), embedder.clone(), keywords, incidents.clone());
+    let results = narrow.similar("RTSP timeout on the lobby camera again", &[HistoryKind::Incident], None, Some(&outage.id)).await?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].id, crowd.id);
+
+    // Nothing changed, so nothing is re-embedded
+    let before = embedder.calls.load(Ordering::SeqCst);
+    assert_eq!(history.sync().await?.embedded, 0);
+    assert_eq!(embedder.calls.load(Ordering::SeqCst), before);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reindex_jobs_are_exclusive_and_survive_panics() -> Result<(), Box<dyn Error>> {
+    use vae::core::jobs::{JobManager, JobStatus};
+    use vae::core::rag::reindex::{ReindexConfig, Reindexer, REEMBED_JOB};
+
+    let jobs = Arc::new(JobManager::new());
+    let reindexer = Reindexer::new(
+        ReindexConfig { model_check_interval_secs: None, ..ReindexConfig::default() },
+        Arc::new(InMemoryVectorStore::new()),
+        Arc::new(CountingEmbedder { model: "embed-v2".to_string(), calls: AtomicUsize::new(0) }),
+        jobs.clone(),
+    );
+
+    // Racing starts get exactly one job
+    let (release, released) = tokio::sync::oneshot::channel::<()>();
+    let held = jobs.spawn_exclusive(REEMBED_JOB, move |_| async move {
+        let _ = released.await;
+        Ok(())
+    }).await?;
+    let (a, b) = tokio::join!(reindexer.start_reembed(), reindexer.start_reembed());
+    assert!(a.is_err() && b.is_err());
+    release.send(()).ok();
+
+    // A panicking job is marked failed and frees its kind for the next run
+    let panicked = jobs.spawn_exclusive("rag.panics", |_| async move {
+        panic!("embedder exploded");
+    }).await?;
+    let mut status = JobStatus::Queued;
+    for _ in 0..100 {
+        status = jobs.get(&panicked.id).await.map_or(status, |j| j.status);
+        if status == JobStatus::Failed {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+    assert_eq!(status, JobStatus::Failed);
+    assert!(!jobs.is_running("rag.panics").await);
+    assert!(jobs.cancel(&panicked.id).await.is_err());
+
+    for _ in 0..100 {
+        if jobs.get(&held.id).await.is_some_and(|j| j.finished_at.is_some()) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+    reindexer.start_reembed().await?;
+
+    Ok(())
+}
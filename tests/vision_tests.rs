@@ -0,0 +1,1707 @@
+#![cfg(feature = "vision")]
+
+use vae::vision::detector::{Detection, BBox};
+use vae::vision::ptz::{AutoTrackConfig, AutoTracker, PtzCommand};
+
+fn detection(class_name: &str, x: f32, y: f32, confidence: f32) -> Detection {
+    Detection {
+        bbox: BBox { x, y, width: 40.0, height: 80.0 },
+        class_id: 0,
+        class_name: class_name.to_string(),
+        confidence,
+        frame_id: 1,
+        timestamp: chrono::Utc::now(),
+    }
+}
+
+#[test]
+fn test_auto_tracker_steers_towards_target() {
+    let mut tracker = AutoTracker::new(AutoTrackConfig::default());
+    tracker.enable(Some("person".to_string()));
+
+    // Target on the right edge, above center
+    let detections = vec![
+        detection("car", 0.0, 0.0, 0.9),
+        detection("person", 560.0, 40.0, 0.8),
+    ];
+
+    match tracker.update(&detections, 640.0, 480.0) {
+        Some(PtzCommand::ContinuousMove { velocity }) => {
+            assert!(velocity.pan > 0.0);
+            assert!(velocity.tilt > 0.0);
+            assert_eq!(velocity.zoom, 0.0);
+        }
+        other => panic!("expected continuous move, got {:?}", other),
+    }
+
+    // The camera is already moving that way, so the next frame sends nothing
+    assert!(tracker.update(&detections, 640.0, 480.0).is_none());
+    let centered = vec![detection("person", 300.0, 200.0, 0.8)];
+    assert!(matches!(tracker.update(&centered, 640.0, 480.0), Some(PtzCommand::Stop)));
+    assert!(tracker.update(&centered, 640.0, 480.0).is_none());
+}
+
+#[test]
+fn test_auto_tracker_deadzone_and_lost_target() {
+    let config = AutoTrackConfig { lost_after_frames: 2, ..AutoTrackConfig::default() };
+    let mut tracker = AutoTracker::new(config);
+    tracker.enable(None);
+
+    // Centered target stays inside the deadzone
+    let centered = vec![detection("person", 300.0, 200.0, 0.9)];
+    assert!(matches!(tracker.update(&centered, 640.0, 480.0), Some(PtzCommand::Stop)));
+
+    assert!(tracker.update(&[], 640.0, 480.0).is_none());
+    assert!(matches!(tracker.update(&[], 640.0, 480.0), Some(PtzCommand::Stop)));
+    assert!(tracker.state().target.is_none());
+}
+
+#[test]
+fn test_auto_tracker_disabled() {
+    let mut tracker = AutoTracker::new(AutoTrackConfig::default());
+    let detections = vec![detection("person", 600.0, 0.0, 0.9)];
+    assert!(tracker.update(&detections, 640.0, 480.0).is_none());
+}
+
+#[tokio::test]
+async fn test_frame_sources_are_injectable() -> Result<(), Box<dyn std::error::Error>> {
+    use opencv::prelude::*;
+    use std::sync::Arc;
+    use vae::core::stream_stats::{StreamStats, StreamStatsConfig};
+    use vae::vision::sources::{FrameSource, PushError, PushRegistry, SourceConfig, SyntheticSource};
+
+    let mut synthetic = SyntheticSource::new(64, 48, Some(2), 0.0);
+    let first = synthetic.next_frame().await?.expect("first frame");
+    assert_eq!((first.cols(), first.rows()), (64, 48));
+    assert!(synthetic.next_frame().await?.is_some());
+    assert!(synthetic.next_frame().await?.is_none());
+
+    let registry = Arc::new(PushRegistry::new());
+    let mut push = registry.register("dock-cam", 4).await;
+    let frame_id = registry.push("dock-cam", first).await.map_err(|e| e.to_string())?;
+    let (pushed, pushed_id) = push.next_tagged().await?.expect("pushed frame");
+    assert_eq!(pushed.cols(), 64);
+    assert_eq!(pushed_id, Some(frame_id));
+
+    // A full buffer refuses the frame and counts it as a drop for the stream
+    let stats = Arc::new(StreamStats::new(StreamStatsConfig::default()));
+    let registry = PushRegistry::new().with_stream_stats(stats.clone());
+    let _backlogged = registry.register("gate-cam", 1).await;
+    assert!(registry.push("gate-cam", pushed.try_clone()?).await.is_ok());
+    assert!(matches!(registry.push("gate-cam", pushed).await, Err(PushError::Backlogged)));
+    let report = stats.report("gate-cam", "1m").await?.expect("gate-cam has samples");
+    assert_eq!(report.drops.by_reason.get("backlogged"), Some(&1));
+
+    // Legacy string sources still map onto typed configs
+    assert_eq!(
+        SourceConfig::parse_uri("rtsp://10.0.0.5/live"),
+        SourceConfig::Rtsp { url: "rtsp://10.0.0.5/live".to_string() }
+    );
+    assert_eq!(SourceConfig::parse_uri("camera:1"), SourceConfig::Camera { index: 1 });
+    assert!(SourceConfig::Push { buffer: 0 }.validate().is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_transcoded_source_decodes_and_downscales() -> Result<(), Box<dyn std::error::Error>> {
+    use opencv::core::{Mat, Scalar, Size, CV_8UC3};
+    use opencv::prelude::*;
+    use opencv::videoio::VideoWriter;
+    use vae::vision::transcode::{TranscodeConfig, TranscodedSource};
+
+    let path = std::env::temp_dir().join(format!("vae-transcode-{}.avi", uuid::Uuid::new_v4()));
+    let path = path.to_string_lossy().to_string();
+    let mut writer = VideoWriter::new(&path, VideoWriter::fourcc('M', 'J', 'P', 'G')?, 10.0, Size::new(64, 48), true)?;
+    for shade in [0.0, 80.0, 160.0] {
+        writer.write(&Mat::new_rows_cols_with_default(48, 64, CV_8UC3, Scalar::all(shade))?)?;
+    }
+    writer.release()?;
+
+    let config = TranscodeConfig { output_size: Some((32, 24)), ..TranscodeConfig::default() };
+    let mut source = TranscodedSource::open(&path, &config).await?;
+    assert_eq!((source.info().width, source.info().height), (32, 24));
+    let mut frames = 0;
+    while let Some(frame) = source.read().await? {
+        assert_eq!((frame.cols(), frame.rows()), (32, 24));
+        frames += 1;
+    }
+    assert_eq!(frames, 3);
+    std::fs::remove_file(&path)?;
+
+    // A source ffmpeg can't open fails the open instead of hanging it
+    assert!(TranscodedSource::open("/nonexistent/dock-3.mkv", &config).await.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_image_dimensions_come_from_the_header() -> Result<(), Box<dyn std::error::Error>> {
+    use opencv::core::{Mat, Scalar, Vector, CV_8UC3};
+    use opencv::imgcodecs;
+    use vae::vision::sources::{frame_dimensions, FrameEncoding};
+
+    let image = Mat::new_rows_cols_with_default(30, 70, CV_8UC3, Scalar::all(128.0))?;
+    for (extension, encoding) in [(".png", FrameEncoding::Png), (".jpg", FrameEncoding::Jpeg)] {
+        let mut encoded = Vector::<u8>::new();
+        imgcodecs::imencode(extension, &image, &mut encoded, &Vector::new())?;
+        assert_eq!(frame_dimensions(encoding, encoded.as_slice())?, (70, 30));
+        // Mislabelled uploads are sniffed like imdecode does
+        let other = if encoding == FrameEncoding::Png { FrameEncoding::Jpeg } else { FrameEncoding::Png };
+        assert_eq!(frame_dimensions(other, encoded.as_slice())?, (70, 30));
+        // Cut off before the frame header
+        assert!(frame_dimensions(encoding, &encoded.as_slice()[..10]).is_err());
+    }
+    assert_eq!(frame_dimensions(FrameEncoding::RawBgr { width: 4, height: 2 }, &[])?, (4, 2));
+    Ok(())
+}
+
+#[test]
+fn test_differential_output_suppresses_unchanged_results() {
+    use vae::core::diff::{ChangeReason, DiffConfig, DiffFilter, OutputMode};
+
+    let mut filter = DiffFilter::new();
+    filter.set_mode("cam-1", OutputMode::Differential(DiffConfig {
+        heartbeat_secs: Some(60),
+        ..DiffConfig::default()
+    }));
+    let start = chrono::Utc::now();
+
+    let scene = vec![detection("person", 10.0, 10.0, 0.8), detection("car", 200.0, 50.0, 0.9)];
+    assert_eq!(filter.filter("cam-1", &scene, start), Some(vec![ChangeReason::Initial]));
+
+    // Small jitter in position and confidence is not material
+    let jittered = vec![detection("person", 12.0, 11.0, 0.75), detection("car", 201.0, 50.0, 0.88)];
+    assert_eq!(filter.filter("cam-1", &jittered, start + chrono::Duration::seconds(1)), None);
+
+    let arrival = vec![
+        detection("person", 12.0, 11.0, 0.75),
+        detection("car", 201.0, 50.0, 0.88),
+        detection("person", 400.0, 300.0, 0.7),
+    ];
+    let reasons = filter.filter("cam-1", &arrival, start + chrono::Duration::seconds(2)).unwrap();
+    assert!(reasons.contains(&ChangeReason::NewObject { class_name: "person".to_string() }));
+    assert!(reasons.contains(&ChangeReason::ClassCountChanged {
+        class_name: "person".to_string(), previous: 1, current: 2,
+    }));
+
+    let drop = vec![
+        detection("person", 12.0, 11.0, 0.3),
+        detection("car", 201.0, 50.0, 0.88),
+        detection("person", 400.0, 300.0, 0.7),
+    ];
+    let reasons = filter.filter("cam-1", &drop, start + chrono::Duration::seconds(3)).unwrap();
+    assert!(matches!(reasons[0], ChangeReason::ConfidenceJump { .. }));
+
+    assert_eq!(
+        filter.filter("cam-1", &drop, start + chrono::Duration::seconds(70)),
+        Some(vec![ChangeReason::Heartbeat])
+    );
+    assert_eq!(filter.suppressed(), 1);
+
+    // Streams without a differential mode pass everything through
+    assert_eq!(filter.filter("cam-2", &scene, start), Some(Vec::new()));
+    assert_eq!(filter.filter("cam-2", &scene, start), Some(Vec::new()));
+}
+
+#[test]
+fn test_tracker_lifecycle_events() {
+    use vae::vision::tracker::{TrackEvent, Tracker, TrackerConfig};
+
+    let mut tracker = Tracker::new("cam-1", TrackerConfig {
+        min_hits: 2,
+        max_age: 1,
+        update_interval: 3,
+        ..TrackerConfig::default()
+    });
+
+    let mut events = Vec::new();
+    for frame_id in 1..=3 {
+        let person = detection("person", 10.0 + frame_id as f32 * 2.0, 10.0, 0.6 + frame_id as f32 * 0.1);
+        events.extend(tracker.update(frame_id, None, &[person]));
+    }
+    assert_eq!(events.len(), 2);
+    match &events[0] {
+        TrackEvent::Birth { track } => {
+            assert_eq!(track.class_name, "person");
+            assert_eq!(track.hits, 2);
+        }
+        other => panic!("expected birth, got {:?}", other),
+    }
+    assert!(matches!(events[1], TrackEvent::Update { .. }));
+
+    // Stale frames from a slow worker are ignored
+    assert!(tracker.update(2, None, &[]).is_empty());
+
+    // Unmatched for longer than max_age ends the track
+    assert!(tracker.update(4, None, &[]).is_empty());
+    let events = tracker.update(5, None, &[]);
+    match &events[..] {
+        [TrackEvent::Death { track }] => {
+            assert_eq!(track.source, "cam-1");
+            assert!(track.path.distance > 0.0);
+            let snapshot = track.snapshot.as_ref().unwrap();
+            assert_eq!(snapshot.frame_id, 3);
+        }
+        other => panic!("expected death, got {:?}", other),
+    }
+    assert!(tracker.active_tracks().is_empty());
+
+    // An older tentative track that absorbs a confirmed one is born before
+    // the merge is announced
+    let mut tracker = Tracker::new("cam-2", TrackerConfig {
+        min_hits: 2,
+        max_age: 10,
+        update_interval: 0,
+        merge_iou: 0.45,
+        ..TrackerConfig::default()
+    });
+    assert!(tracker.update(1, None, &[detection("person", 0.0, 10.0, 0.9)]).is_empty());
+    assert!(tracker.update(2, None, &[detection("person", 24.0, 10.0, 0.9)]).is_empty());
+    let events = tracker.update(3, None, &[detection("person", 24.0, 10.0, 0.9)]);
+    assert!(matches!(&events[..], [TrackEvent::Birth { track }] if track.track_id == 2));
+    let events = tracker.update(4, None, &[detection("person", 14.0, 10.0, 0.9)]);
+    match &events[..] {
+        [TrackEvent::Birth { track }, TrackEvent::Merge { into, merged_id: 2 }] => {
+            assert_eq!(track.track_id, 1);
+            assert_eq!(into.track_id, 1);
+        }
+        other => panic!("expected birth then merge, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_loitering_and_abandoned_object_detectors() {
+    use vae::vision::behaviors::{
+        AbandonedObjectConfig, AbandonedObjectDetector, BehaviorAnomaly, FrameRef, LoiteringConfig,
+        LoiteringDetector,
+    };
+    use vae::vision::tracker::{Tracker, TrackerConfig};
+
+    let mut tracker = Tracker::new("lobby", TrackerConfig {
+        min_hits: 1,
+        max_age: 0,
+        snapshots: false,
+        ..TrackerConfig::default()
+    });
+    let mut loitering = LoiteringDetector::new(LoiteringConfig {
+        dwell_secs: 30.0,
+        ..LoiteringConfig::default()
+    });
+    let mut abandoned = AbandonedObjectDetector::new(AbandonedObjectConfig {
+        unattended_secs: 10.0,
+        ..AbandonedObjectConfig::default()
+    });
+
+    let start = chrono::Utc::now();
+    let mut loiter_alerts = Vec::new();
+    let mut abandoned_alerts = Vec::new();
+    for second in 0..60u64 {
+        // The owner drops the bag, lingers next to it, then walks off at 20s
+        let mut detections = vec![detection("backpack", 300.0, 200.0, 0.9)];
+        if second < 20 {
+            detections.push(detection("person", 320.0, 150.0, 0.9));
+        }
+        // Someone else stands still by the door the whole time
+        detections.push(detection("person", 10.0, 10.0, 0.9));
+
+        tracker.update(second + 1, None, &detections);
+        let frame = FrameRef {
+            frame_id: second + 1,
+            timestamp: start + chrono::Duration::seconds(second as i64),
+            image: None,
+        };
+        let tracks = tracker.active_tracks();
+        loiter_alerts.extend(loitering.update(&frame, &tracks));
+        abandoned_alerts.extend(abandoned.update(&frame, &tracks));
+    }
+
+    // The owner left before the dwell threshold; only the person by the door loitered
+    match &loiter_alerts[..] {
+        [BehaviorAnomaly::Loitering { dwell_secs, evidence, .. }] => {
+            assert!(*dwell_secs >= 30.0);
+            assert_eq!(evidence.len(), 2);
+        }
+        other => panic!("expected one loitering alert, got {:?}", other),
+    }
+
+    match &abandoned_alerts[..] {
+        [BehaviorAnomaly::AbandonedObject { class_name, owner_track_id, unattended_secs, evidence, .. }] => {
+            assert_eq!(class_name, "backpack");
+            assert!(owner_track_id.is_some());
+            assert!(*unattended_secs >= 10.0);
+            assert_eq!(evidence[0].frame_id, 1);
+        }
+        other => panic!("expected one abandoned object, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_density_map_pools_into_grid() {
+    use vae::vision::density::pool_density;
+
+    // 4x4 map with a dense top-left corner and one stray negative
+    let map = vec![
+        1.0, 1.0, 0.0, 0.0,
+        1.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 0.0, 0.5,
+        0.0, 0.0, -0.2, 0.5,
+    ];
+    let info = pool_density(&map, 4, 4, 2, 2).unwrap();
+
+    assert!((info.count - 5.0).abs() < 1e-6);
+    assert_eq!(info.cell(0, 0), Some(4.0));
+    assert_eq!(info.cell(1, 1), Some(1.0));
+    assert_eq!(info.cell(2, 0), None);
+    assert_eq!(info.peak(), Some((0, 0, 4.0)));
+
+    assert!(pool_density(&map, 3, 4, 2, 2).is_err());
+    assert!(pool_density(&map, 4, 4, 0, 2).is_err());
+    assert!(pool_density(&map, 4, 4, 8, 2).is_err());
+    assert!(pool_density(&map, usize::MAX, 2, 1, 1).is_err());
+}
+
+#[tokio::test]
+async fn test_plate_decoding_validation_and_lookup() -> Result<(), Box<dyn std::error::Error>> {
+    use vae::vision::lpr::{
+        ctc_greedy_decode, hash_plate, PlateFormat, PlateIndex, PlateQuery, PlateRead, PlateValidator,
+    };
+
+    // Alphabet "AB12": class 0 is blank, then A=1, B=2, 1=3, 2=4
+    let step = |class: usize| {
+        let mut scores = vec![0.0f32; 5];
+        scores[class] = 10.0;
+        scores
+    };
+    let scores: Vec<f32> = [1, 1, 0, 1, 2, 0, 3, 3, 4].iter().flat_map(|c| step(*c)).collect();
+    let (text, confidence) = ctc_greedy_decode(&scores, 5, "AB12");
+    assert_eq!(text, "AAB12");
+    assert!(confidence > 0.99);
+
+    let validator = PlateValidator::new(&[
+        PlateFormat { region: "uk".to_string(), pattern: "[A-Z]{2}[0-9]{2}[A-Z]{3}".to_string() },
+        PlateFormat { region: "de".to_string(), pattern: "[A-Z]{1,3}[A-Z]{1,2}[0-9]{1,4}".to_string() },
+    ])?;
+    assert_eq!(validator.validate("AB12CDE"), Some(Some("uk".to_string())));
+    assert_eq!(validator.validate("MAB1234"), Some(Some("de".to_string())));
+    assert_eq!(validator.validate("12"), None);
+    assert_eq!(PlateValidator::new(&[])?.validate("ANY"), Some(None));
+
+    // Keyed: the same plate hashes differently under another key
+    let key = "0123456789abcdef0123";
+    assert_eq!(hash_plate(key, "AB12 CDE"), hash_plate(key, "ab12cde"));
+    assert_ne!(hash_plate(key, "AB12CDE"), hash_plate("another key entirely", "AB12CDE"));
+
+    let index = PlateIndex::new(2, key);
+    for (frame_id, plate) in [(1u64, "AB12 CDE"), (2, "XY99ZZZ"), (3, "ab12cde")] {
+        let d = detection("license_plate", 0.0, 0.0, 0.9);
+        let mut read = PlateRead {
+            source: "gate".to_string(),
+            frame_id,
+            timestamp: chrono::Utc::now(),
+            text: Some(plate.to_string()),
+            text_hash: index.hash(plate),
+            region: Some("uk".to_string()),
+            confidence: 0.9,
+            bbox: d.bbox.clone(),
+            vehicle_bbox: d.bbox,
+        };
+        read.redact();
+        index.record(read).await;
+    }
+
+    // Capacity 2 evicted frame 1; redacted reads are still found by plate
+    let hits = index.query(&PlateQuery { plate: Some("AB-12-CDE".to_string()), ..PlateQuery::default() }).await;
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].frame_id, 3);
+    assert!(hits[0].text.is_none());
+    assert_eq!(index.query(&PlateQuery::default()).await.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_scene_labels_lighting_and_composition() {
+    use vae::vision::scene::{classify_lighting, composition, select_labels, stats_from_histogram};
+
+    let labels: Vec<String> = ["indoor", "outdoor", "parking_lot", "night"].iter().map(|s| s.to_string()).collect();
+    let selected = select_labels(&[-3.0, 2.5, 1.0, 4.0], &labels, 0.5, 2);
+    let names: Vec<&str> = selected.iter().map(|l| l.label.as_str()).collect();
+    assert_eq!(names, vec!["night", "outdoor"]);
+    assert!(selected[0].confidence > 0.98);
+
+    let mut dark = vec![0.0f32; 256];
+    dark[10..40].iter_mut().for_each(|b| *b = 100.0);
+    assert_eq!(classify_lighting(&stats_from_histogram(&dark)), "dark");
+
+    let mut spread = vec![0.0f32; 256];
+    spread[60..200].iter_mut().for_each(|b| *b = 10.0);
+    assert_eq!(classify_lighting(&stats_from_histogram(&spread)), "bright");
+
+    let mut split = vec![0.0f32; 256];
+    split[0..5].iter_mut().for_each(|b| *b = 100.0);
+    split[250..256].iter_mut().for_each(|b| *b = 100.0);
+    assert_eq!(classify_lighting(&stats_from_histogram(&split)), "high_contrast");
+
+    assert_eq!(composition(&[], 640.0, 480.0), "empty");
+    assert_eq!(composition(&[detection("person", 300.0, 200.0, 0.9)], 640.0, 480.0), "centered");
+    assert_eq!(composition(&[detection("person", 20.0, 200.0, 0.9)], 640.0, 480.0), "left_weighted");
+}
+
+#[tokio::test]
+async fn test_similarity_index_text_search() -> Result<(), Box<dyn std::error::Error>> {
+    use std::sync::Arc;
+    use async_trait::async_trait;
+    use opencv::core::Mat;
+    use vae::vision::similarity::{
+        ImageEmbedder, ImageEntry, SearchFilter, SearchQuery, SimilarityConfig, SimilarityIndex,
+    };
+
+    struct KeywordEmbedder;
+
+    #[async_trait]
+    impl ImageEmbedder for KeywordEmbedder {
+        fn model_id(&self) -> String {
+            "keyword".to_string()
+        }
+
+        async fn embed_image(&self, _image: &Mat) -> anyhow::Result<Vec<f32>> {
+            Ok(vec![0.0, 0.0, 1.0])
+        }
+
+        async fn embed_text(&self, text: &str) -> anyhow::Result<Vec<f32>> {
+            Ok(vec![
+                if text.contains("red") { 1.0 } else { 0.0 },
+                if text.contains("jacket") { 1.0 } else { 0.0 },
+                0.0,
+            ])
+        }
+    }
+
+    let index = SimilarityIndex::new(
+        SimilarityConfig { capacity: 3, ..SimilarityConfig::default() },
+        Arc::new(KeywordEmbedder),
+    );
+    let start = chrono::Utc::now();
+    let entry = |source: &str, frame_id: u64, embedding: Vec<f32>| ImageEntry {
+        source: source.to_string(),
+        frame_id,
+        timestamp: start + chrono::Duration::seconds(frame_id as i64),
+        bbox: Some(detection("person", 0.0, 0.0, 0.9).bbox),
+        class_name: Some("person".to_string()),
+        thumbnail: None,
+        embedding,
+    };
+
+    index.insert(entry("lobby", 1, vec![1.0, 1.0, 0.0])).await;
+    index.insert(entry("lobby", 2, vec![1.0, 1.0, 0.1])).await;
+    index.insert(entry("garage", 3, vec![1.0, 0.9, 0.0])).await;
+    index.insert(entry("lobby", 4, vec![0.0, 0.0, 1.0])).await;
+    assert_eq!(index.len().await, 3);
+
+    let hits = index.search(SearchQuery::Text("red jacket".to_string()), &SearchFilter::default(), 10, 0.5).await?;
+    let frames: Vec<u64> = hits.iter().map(|h| h.entry.frame_id).collect();
+    // Frame 1 was evicted; frame 4 is below min_score
+    assert_eq!(frames, vec![3, 2]);
+
+    let lobby = SearchFilter { source: Some("lobby".to_string()), ..SearchFilter::default() };
+    let hits = index.search(SearchQuery::Text("red jacket".to_string()), &lobby, 10, 0.5).await?;
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].entry.frame_id, 2);
+
+    // Frames submitted from the pipeline are embedded in the background,
+    // every frame_interval-th one only
+    use opencv::core::{Scalar, CV_8UC3};
+    use vae::vision::processor::{Frame, FrameMetadata};
+    let live = Arc::new(SimilarityIndex::new(
+        SimilarityConfig { frame_interval: 2, ..SimilarityConfig::default() },
+        Arc::new(KeywordEmbedder),
+    ));
+    let frame = |id: u64| -> Result<Frame, opencv::Error> {
+        Ok(Frame {
+            id,
+            timestamp: chrono::Utc::now(),
+            data: Arc::new(Mat::new_rows_cols_with_default(120, 160, CV_8UC3, Scalar::all(0.0))?),
+            metadata: FrameMetadata {
+                width: 160,
+                height: 120,
+                channels: 3,
+                format: "bgr".to_string(),
+                source: "lobby".to_string(),
+                privacy_masked: false,
+                stream_id: None,
+            },
+        })
+    };
+    assert!(!live.submit(&frame(1)?, &[]));
+    assert!(live.submit(&frame(2)?, &[detection("person", 10.0, 10.0, 0.9)]));
+    for _ in 0..100 {
+        if live.len().await == 2 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    // The frame and its person crop
+    assert_eq!(live.len().await, 2);
+    Ok(())
+}
+
+#[test]
+fn test_verification_budget_and_alert_gating() {
+    use std::sync::Arc;
+    use vae::vision::verify::{
+        parse_verdict, DetectionVerifier, OverBudgetPolicy, Verdict, VerificationBudget,
+        VerificationConfig, VerificationOutcome, VerifiedDetection, VisionVerifier,
+    };
+
+    struct NeverCalled;
+
+    #[async_trait::async_trait]
+    impl VisionVerifier for NeverCalled {
+        async fn verify(&self, _image: &str, _class_name: &str, _context: &str) -> anyhow::Result<Verdict> {
+            unreachable!("verifier should not be called")
+        }
+    }
+
+    let verdict = parse_verdict("```json\n{\"confirmed\": false, \"confidence\": 1.4, \"reason\": \"shadow\"}\n```").unwrap();
+    assert!(!verdict.confirmed);
+    assert_eq!(verdict.confidence, 1.0);
+    assert!(parse_verdict("probably a person").is_err());
+
+    let start = chrono::Utc::now();
+    let mut budget = VerificationBudget::new(2);
+    assert!(budget.try_acquire(start));
+    assert!(budget.try_acquire(start + chrono::Duration::seconds(10)));
+    assert!(!budget.try_acquire(start + chrono::Duration::seconds(20)));
+    assert!(budget.try_acquire(start + chrono::Duration::seconds(61)));
+
+    let verifier = DetectionVerifier::new(
+        VerificationConfig { over_budget: OverBudgetPolicy::Drop, ..VerificationConfig::default() },
+        Arc::new(NeverCalled),
+    );
+    assert!(verifier.needs_verification(&detection("person", 0.0, 0.0, 0.45), false));
+    assert!(!verifier.needs_verification(&detection("person", 0.0, 0.0, 0.9), false));
+    assert!(verifier.needs_verification(&detection("person", 0.0, 0.0, 0.9), true));
+
+    let rejected = Verdict { confirmed: false, confidence: 0.8, reason: "mannequin".to_string() };
+    let results = vec![
+        VerifiedDetection { detection: detection("person", 0.0, 0.0, 0.9), outcome: VerificationOutcome::NotRequired },
+        VerifiedDetection { detection: detection("person", 100.0, 0.0, 0.4), outcome: VerificationOutcome::Rejected { verdict: rejected } },
+        VerifiedDetection { detection: detection("person", 200.0, 0.0, 0.4), outcome: VerificationOutcome::OverBudget },
+        VerifiedDetection { detection: detection("person", 300.0, 0.0, 0.4), outcome: VerificationOutcome::Failed { error: "timeout".to_string() } },
+    ];
+    let xs: Vec<f32> = verifier.alertable(&results).iter().map(|d| d.bbox.x).collect();
+    assert_eq!(xs, vec![0.0, 300.0]);
+}
+
+#[tokio::test]
+async fn test_verdicts_are_reused_for_the_same_object() -> Result<(), Box<dyn std::error::Error>> {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use opencv::core::{Mat, Scalar, CV_8UC3};
+    use vae::vision::verify::{DetectionVerifier, Verdict, VerificationConfig, VerificationOutcome, VisionVerifier};
+
+    struct Rejecting(AtomicUsize);
+
+    #[async_trait::async_trait]
+    impl VisionVerifier for Rejecting {
+        async fn verify(&self, _image: &str, _class_name: &str, _context: &str) -> anyhow::Result<Verdict> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(Verdict { confirmed: false, confidence: 0.9, reason: "reflection".to_string() })
+        }
+    }
+
+    let model = Arc::new(Rejecting(AtomicUsize::new(0)));
+    let verifier = DetectionVerifier::new(VerificationConfig::default(), model.clone());
+    let image = Mat::new_rows_cols_with_default(240, 320, CV_8UC3, Scalar::all(0.0))?;
+
+    // The same object held across frames is only sent to the model once
+    for frame_id in 1..=3 {
+        let verified = verifier.verify(&image, "lobby", frame_id, &[detection("person", 10.0 + frame_id as f32, 10.0, 0.4)], true).await;
+        assert!(matches!(verified[0].outcome, VerificationOutcome::Rejected { .. }));
+        assert!(verifier.alertable(&verified).is_empty());
+    }
+    assert_eq!(model.0.load(Ordering::SeqCst), 1);
+
+    // Elsewhere in the frame, or on another camera, it's a new object
+    verifier.verify(&image, "lobby", 4, &[detection("person", 200.0, 10.0, 0.4)], true).await;
+    verifier.verify(&image, "garage", 5, &[detection("person", 11.0, 10.0, 0.4)], true).await;
+    assert_eq!(model.0.load(Ordering::SeqCst), 3);
+
+    let stats = verifier.stats().await;
+    assert_eq!(stats.reused, 2);
+    assert_eq!(stats.rejected, 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_shadow_model_compares_without_affecting_results() -> Result<(), Box<dyn std::error::Error>> {
+    use std::sync::Arc;
+    use async_trait::async_trait;
+    use opencv::core::Mat;
+    use vae::vision::processor::{Frame, FrameMetadata};
+    use vae::vision::shadow::{compare_detections, DetectionModel, ShadowConfig, ShadowDeployment};
+
+    struct Fixed(Vec<Detection>);
+
+    #[async_trait]
+    impl DetectionModel for Fixed {
+        async fn detect(&self, _frame: &Frame) -> anyhow::Result<Vec<Detection>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    let production = vec![detection("person", 0.0, 0.0, 0.9), detection("car", 200.0, 0.0, 0.8)];
+    let candidate = vec![detection("person", 2.0, 0.0, 0.95)];
+
+    let comparison = compare_detections(&production, &candidate, 0.5);
+    assert_eq!(comparison.matched, 1);
+    assert_eq!(comparison.production_only, 1);
+    assert!((comparison.agreement - 2.0 / 3.0).abs() < 1e-6);
+    assert_eq!(compare_detections(&[], &[], 0.5).agreement, 1.0);
+
+    let deployment = ShadowDeployment::new(ShadowConfig::default(), "yolo-v1", Arc::new(Fixed(production.clone())));
+    deployment.start_shadow("yolo-v2", Arc::new(Fixed(candidate))).await;
+
+    let frame = Frame {
+        id: 1,
+        timestamp: chrono::Utc::now(),
+        data: Arc::new(Mat::default()),
+        metadata: FrameMetadata {
+            width: 640,
+            height: 480,
+            channels: 3,
+            format: "bgr".to_string(),
+            source: "test".to_string(),
+            privacy_masked: false,
+            stream_id: None,
+        },
+    };
+    let detections = deployment.detect(&frame).await?;
+    assert_eq!(detections.len(), 2);
+
+    // The candidate runs on a background task
+    for _ in 0..100 {
+        if deployment.report().await.frames_compared > 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+    let report = deployment.report().await;
+    assert_eq!(report.frames_compared, 1);
+    assert!((report.agreement_rate.unwrap() - 2.0 / 3.0).abs() < 1e-6);
+
+    assert_eq!(deployment.promote().await?, "yolo-v2");
+    assert_eq!(deployment.detect(&frame).await?.len(), 1);
+    assert_eq!(deployment.rollback().await?, "yolo-v1");
+    assert_eq!(deployment.report().await.production, "yolo-v1");
+    assert!(deployment.promote().await.is_err());
+
+    // A candidate that never finishes holds its slot; frames sampled while
+    // all slots are taken are skipped instead of piling up tasks
+    struct Stuck;
+
+    #[async_trait]
+    impl DetectionModel for Stuck {
+        async fn detect(&self, _frame: &Frame) -> anyhow::Result<Vec<Detection>> {
+            futures::future::pending().await
+        }
+    }
+
+    let config = ShadowConfig { max_in_flight: 2, ..ShadowConfig::default() };
+    let deployment = ShadowDeployment::new(config, "yolo-v1", Arc::new(Fixed(production.clone())));
+    deployment.start_shadow("stuck", Arc::new(Stuck)).await;
+    for _ in 0..5 {
+        assert_eq!(deployment.detect(&frame).await?.len(), 2);
+    }
+    let report = deployment.report().await;
+    assert_eq!(report.frames_skipped, 3);
+    assert_eq!(report.frames_compared, 0);
+    Ok(())
+}
+
+mod geometry_properties {
+    use proptest::prelude::*;
+    use vae::vision::detector::BBox;
+    use vae::vision::geometry::{area, clip, iou, nms, Letterbox};
+
+    fn bbox() -> impl Strategy<Value = BBox> {
+        (-100.0f32..1000.0, -100.0f32..1000.0, 0.5f32..400.0, 0.5f32..400.0)
+            .prop_map(|(x, y, width, height)| BBox { x, y, width, height })
+    }
+
+    proptest! {
+        #[test]
+        fn iou_is_symmetric_and_bounded(a in bbox(), b in bbox()) {
+            let ab = iou(&a, &b);
+            prop_assert!((0.0..=1.0).contains(&ab));
+            prop_assert!((ab - iou(&b, &a)).abs() < 1e-6);
+            prop_assert!((iou(&a, &a) - 1.0).abs() < 1e-4);
+        }
+
+        #[test]
+        fn clip_stays_inside_the_frame(b in bbox(), width in 1.0f32..1920.0, height in 1.0f32..1080.0) {
+            if let Some(clipped) = clip(&b, width, height) {
+                prop_assert!(clipped.x >= 0.0 && clipped.y >= 0.0);
+                prop_assert!(clipped.x + clipped.width <= width + 1e-3);
+                prop_assert!(clipped.y + clipped.height <= height + 1e-3);
+                prop_assert!(area(&clipped) <= area(&b) + 1e-2);
+                let again = clip(&clipped, width, height).unwrap();
+                prop_assert!((area(&again) - area(&clipped)).abs() < 1e-2);
+            } else {
+                prop_assert!(b.x >= width || b.y >= height || b.x + b.width <= 0.0 || b.y + b.height <= 0.0);
+            }
+        }
+
+        #[test]
+        fn letterbox_round_trips(
+            b in bbox(),
+            frame in (64.0f32..3840.0, 64.0f32..2160.0),
+            input in (64.0f32..1280.0, 64.0f32..1280.0),
+        ) {
+            let letterbox = Letterbox::fit(frame, input);
+            let back = letterbox.to_frame(&letterbox.to_input(&b));
+            let tolerance = 1e-3 * (1.0 + b.x.abs().max(b.y.abs()));
+            prop_assert!((back.x - b.x).abs() < tolerance && (back.y - b.y).abs() < tolerance);
+            prop_assert!((back.width - b.width).abs() < tolerance && (back.height - b.height).abs() < tolerance);
+        }
+
+        #[test]
+        fn nms_keeps_best_and_separates_survivors(
+            boxes in prop::collection::vec((bbox(), 0.0f32..1.0), 0..40),
+            threshold in 0.1f32..0.9,
+        ) {
+            let (boxes, scores): (Vec<BBox>, Vec<f32>) = boxes.into_iter().unzip();
+            let keep = nms(&boxes, &scores, threshold);
+
+            if let Some(best) = (0..boxes.len()).max_by(|&a, &b| scores[a].total_cmp(&scores[b]).then(b.cmp(&a))) {
+                prop_assert_eq!(keep[0], best);
+            }
+            for pair in keep.windows(2) {
+                prop_assert!(scores[pair[0]] >= scores[pair[1]]);
+            }
+            for (i, &a) in keep.iter().enumerate() {
+                for &b in &keep[i + 1..] {
+                    prop_assert!(iou(&boxes[a], &boxes[b]) <= threshold);
+                }
+            }
+            // Everything dropped was suppressed by a kept box at least as confident
+            for dropped in (0..boxes.len()).filter(|i| !keep.contains(i)) {
+                prop_assert!(keep.iter().any(|&k| {
+                    scores[k] >= scores[dropped] && iou(&boxes[k], &boxes[dropped]) > threshold
+                }));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_gpu_backend_selection_falls_back_to_cpu() -> Result<(), Box<dyn std::error::Error>> {
+    use vae::runtime::backend::{candidates, discover, select, BackendConfig, GpuBackend};
+    use vae::vision::detector::DetectionDevice;
+
+    // The CPU is always the last resort and always works
+    let config = BackendConfig { preferred: vec![GpuBackend::CoreMl, GpuBackend::DirectMl], ..Default::default() };
+    assert_eq!(candidates(&config).last(), Some(&GpuBackend::Cpu));
+    let chosen = select(&config)?;
+    assert!(chosen.is_available());
+    assert!(discover().iter().any(|b| b.backend == GpuBackend::Cpu && b.available));
+
+    // Core ML only exists on macOS and DirectML only on Windows
+    if cfg!(target_os = "linux") {
+        assert_eq!(chosen, GpuBackend::Cpu);
+        assert!(select(&BackendConfig { require_gpu: true, ..config }).is_err());
+    }
+    assert_eq!(GpuBackend::for_device(&DetectionDevice::CoreML), vec![GpuBackend::CoreMl]);
+    // MIGraphX is preferred on AMD cards, and neither is usable without one
+    assert_eq!(GpuBackend::for_device(&DetectionDevice::ROCm), vec![GpuBackend::MiGraphX, GpuBackend::Rocm]);
+    if vae::runtime::backend::amd_devices().is_empty() {
+        assert!(!GpuBackend::Rocm.is_available());
+    }
+    // The probe is cached, so asking again gives the same answer
+    assert_eq!(GpuBackend::Cuda.is_available(), GpuBackend::Cuda.is_available());
+
+    // Without explicit backends the detector device picks them
+    use vae::vision::detector::DetectorBuilder;
+    let detector = DetectorBuilder::new()
+        .device(DetectionDevice::ROCm)
+        .onnx_model("yolo", "models/yolo.onnx", (640, 640), Vec::new())
+        .into_config()?;
+    assert_eq!(detector.backends().preferred, vec![GpuBackend::MiGraphX, GpuBackend::Rocm]);
+    let detector = DetectorBuilder::new()
+        .device(DetectionDevice::ROCm)
+        .backend(BackendConfig { preferred: vec![GpuBackend::Cpu], ..Default::default() })
+        .onnx_model("yolo", "models/yolo.onnx", (640, 640), Vec::new())
+        .into_config()?;
+    assert_eq!(detector.backends().preferred, vec![GpuBackend::Cpu]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_onnx_models_load_through_backend_selection() -> Result<(), Box<dyn std::error::Error>> {
+    use vae::runtime::backend::{BackendConfig, GpuBackend};
+    use vae::vision::detector::{Detector, DetectorBuilder, DetectorType};
+
+    let builder = DetectorBuilder::new()
+        .onnx_model("yolo", "models/yolo.onnx", (640, 640), vec!["person".to_string()])
+        .backend(BackendConfig { preferred: vec![GpuBackend::CoreMl], require_gpu: true, ..Default::default() });
+    let config = builder.into_config()?;
+    assert!(config.backend.require_gpu);
+    assert_eq!(config.enabled_detectors, vec![DetectorType::Object]);
+
+    // Choosing a detector replaces the object default rather than adding to it
+    let faces = DetectorBuilder::new()
+        .detector(DetectorType::Face)
+        .onnx_model("faces", "models/faces.onnx", (320, 320), vec!["face".to_string()])
+        .into_config()?;
+    assert_eq!(faces.enabled_detectors, vec![DetectorType::Face]);
+
+    // Selection runs before the file is opened, so a missing GPU is what fails
+    if cfg!(target_os = "linux") {
+        let error = Detector::new(config).await.err().expect("no GPU backend on Linux for Core ML");
+        assert!(error.to_string().contains("No GPU backend available"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_edge_accelerator_config() -> Result<(), Box<dyn std::error::Error>> {
+    use opencv::prelude::*;
+    use vae::models::edge;
+    use vae::vision::detector::{Accelerator, ModelConfig};
+
+    let config: ModelConfig = serde_json::from_str(r#"{
+        "name": "yolo", "path": "models/yolo_edgetpu.tflite", "framework": "ONNX",
+        "input_size": [320, 320], "class_names": ["person"],
+        "accelerator": "edgetpu", "accelerator_device": "usb:0"
+    }"#)?;
+    assert_eq!(config.accelerator, Accelerator::EdgeTpu);
+    assert_eq!(config.accelerator_device.as_deref(), Some("usb:0"));
+
+    // Existing configs keep running on the detector device
+    let plain: ModelConfig = serde_json::from_str(r#"{
+        "name": "yolo", "path": "models/yolo.onnx", "framework": "ONNX",
+        "input_size": [640, 640], "class_names": []
+    }"#)?;
+    assert_eq!(plain.accelerator, Accelerator::Auto);
+    assert!(edge::load(&plain).is_err());
+
+    assert_eq!(edge::dequantize(&[128, 138], false, 0.5, 128), vec![0.0, 5.0]);
+    assert_eq!(edge::dequantize(&[0xf6], true, 1.0, 0), vec![-10.0]);
+    let mat = edge::output_mat(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 3)?;
+    assert_eq!((mat.rows(), mat.cols()), (2, 3));
+    assert!(edge::output_mat(&[1.0, 2.0], 3).is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "hailo")]
+#[test]
+fn test_hailo_nms_output_becomes_detector_rows() -> Result<(), Box<dyn std::error::Error>> {
+    use vae::models::hailo::decode_nms;
+
+    // Two classes: one box for the first, none for the second
+    let values = [1.0, 0.25, 0.5, 0.75, 1.0, 0.9, 0.0];
+    let rows = decode_nms(&values, 2, (640, 320))?;
+    assert_eq!(rows, vec![320.0, 80.0, 320.0, 160.0, 0.9, 0.0]);
+
+    // A count that runs past the buffer is an error, not a panic
+    assert!(decode_nms(&[3.0, 0.1, 0.1, 0.2, 0.2, 0.5], 1, (640, 640)).is_err());
+    assert!(decode_nms(&[0.0], 2, (640, 640)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_openvino_device_names() -> Result<(), Box<dyn std::error::Error>> {
+    use vae::vision::detector::{OpenVinoConfig, OpenVinoDevice};
+
+    assert_eq!(OpenVinoConfig::default().device_name(), "AUTO");
+    let gpu = OpenVinoConfig { device: OpenVinoDevice::Gpu, device_index: Some(1), ..Default::default() };
+    assert_eq!(gpu.device_name(), "AUTO:GPU.1,CPU");
+    let strict = OpenVinoConfig { fallback_to_cpu: false, ..gpu };
+    assert_eq!(strict.device_name(), "GPU.1");
+
+    // Older configs still say "vpu"
+    let vpu: OpenVinoConfig = serde_json::from_str(r#"{"device": "vpu", "fallback_to_cpu": false}"#)?;
+    assert_eq!(vpu.device, OpenVinoDevice::Npu);
+    assert_eq!(vpu.device_name(), "NPU");
+    assert_eq!(vpu.cache_dir.as_deref(), Some("cache/openvino"));
+
+    Ok(())
+}
+
+struct RecordingBatchModel {
+    batch_sizes: std::sync::Mutex<Vec<usize>>,
+}
+
+#[async_trait::async_trait]
+impl vae::models::server::BatchModel for RecordingBatchModel {
+    async fn infer_batch(&self, inputs: &[opencv::core::Mat]) -> anyhow::Result<Vec<opencv::core::Mat>> {
+        use opencv::prelude::*;
+        self.batch_sizes.lock().unwrap().push(inputs.len());
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        inputs.iter().map(|m| Ok(m.try_clone()?)).collect()
+    }
+}
+
+#[tokio::test]
+async fn test_model_server_batches_concurrent_requests() -> Result<(), Box<dyn std::error::Error>> {
+    use std::sync::Arc;
+    use opencv::{core::{Mat, Scalar, CV_32F}, prelude::*};
+    use vae::models::inference::Model;
+    use vae::models::server::{ModelServer, ModelServerConfig};
+
+    let model = Arc::new(RecordingBatchModel { batch_sizes: std::sync::Mutex::new(Vec::new()) });
+    let config = ModelServerConfig { max_batch_size: 4, max_wait_ms: 50, queue_capacity: 16 };
+    let server = Arc::new(ModelServer::start("recording", "CPU", model.clone(), config)?);
+
+    let calls = (0..8).map(|i| {
+        let server = server.clone();
+        tokio::spawn(async move {
+            let input = Mat::new_rows_cols_with_default(1, 1, CV_32F, Scalar::all(i as f64))?;
+            let output = server.infer(&input).await?;
+            anyhow::Ok(*output.at_2d::<f32>(0, 0)? as i32)
+        })
+    });
+    let results = futures::future::join_all(calls).await;
+
+    // Every caller gets its own input back, in batches no larger than four
+    for (i, result) in results.into_iter().enumerate() {
+        assert_eq!(result??, i as i32);
+    }
+    let sizes = model.batch_sizes.lock().unwrap().clone();
+    assert_eq!(sizes.iter().sum::<usize>(), 8);
+    assert!(sizes.iter().all(|&n| n <= 4));
+    assert!(sizes.len() < 8, "requests were not batched: {:?}", sizes);
+
+    let stats = server.stats();
+    assert_eq!(stats.requests, 8);
+    assert_eq!(stats.batches as usize, sizes.len());
+    assert_eq!(stats.failed, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_model_server_registry_loads_once_per_key() -> Result<(), Box<dyn std::error::Error>> {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+    use vae::models::server::{BatchModel, ModelServerConfig, ModelServerRegistry};
+
+    let registry = ModelServerRegistry::default();
+    let config = ModelServerConfig::default();
+    let loads = AtomicUsize::new(0);
+    let load = |delay_ms: u64| {
+        let loads = &loads;
+        async move {
+            loads.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            Ok(Arc::new(RecordingBatchModel { batch_sizes: std::sync::Mutex::new(Vec::new()) }) as Arc<dyn BatchModel>)
+        }
+    };
+
+    // Two streams starting the same model share one load, and a different
+    // model doesn't wait behind it
+    let started = Instant::now();
+    let (first, second, other) = tokio::join!(
+        registry.get_or_start("yolo@CPU", "CPU", &config, load(300)),
+        registry.get_or_start("yolo@CPU", "CPU", &config, load(300)),
+        async {
+            let server = registry.get_or_start("face@CPU", "CPU", &config, load(0)).await;
+            (server, started.elapsed())
+        },
+    );
+    let (first, second) = (first?, second?);
+    assert!(Arc::ptr_eq(&first, &second));
+    let (other, other_elapsed) = other;
+    other?;
+    assert!(other_elapsed < Duration::from_millis(300));
+    assert_eq!(loads.load(Ordering::SeqCst), 2);
+    assert_eq!(registry.stats().len(), 1);
+
+    // Once no detector holds the server, the next one loads a fresh copy
+    drop((first, second));
+    let again = registry.get_or_start("yolo@CPU", "CPU", &config, load(0)).await?;
+    assert_eq!(loads.load(Ordering::SeqCst), 3);
+    assert_eq!(registry.stats().len(), 1);
+    drop(again);
+
+    Ok(())
+}
+
+#[test]
+fn test_ensemble_merge_strategies() {
+    use vae::vision::ensemble::{merge, EnsembleStrategy, MemberOutput};
+
+    // Two of three models see the person at roughly the same place; only
+    // one sees the car
+    let outputs = vec![
+        MemberOutput { weight: 2.0, detections: vec![detection("person", 100.0, 100.0, 0.9), detection("car", 300.0, 50.0, 0.6)] },
+        MemberOutput { weight: 1.0, detections: vec![detection("person", 104.0, 100.0, 0.7)] },
+        MemberOutput { weight: 1.0, detections: vec![] },
+    ];
+
+    let union = merge(EnsembleStrategy::UnionNms, &outputs, 0.5, None);
+    assert_eq!(union.len(), 2);
+    assert_eq!(union[0].class_name, "person");
+    assert_eq!(union[0].confidence, 0.9);
+
+    let voted = merge(EnsembleStrategy::MajorityVote, &outputs, 0.5, None);
+    assert_eq!(voted.len(), 1);
+    assert_eq!(voted[0].class_name, "person");
+    assert!((voted[0].confidence - 0.8).abs() < 1e-6);
+    assert_eq!(merge(EnsembleStrategy::MajorityVote, &outputs, 0.5, Some(1)).len(), 2);
+
+    let fused = merge(EnsembleStrategy::WeightedFusion, &outputs, 0.5, None);
+    let person = fused.iter().find(|d| d.class_name == "person").unwrap();
+    // (2*0.9*100 + 1*0.7*104) / (2*0.9 + 0.7)
+    assert!((person.bbox.x - 101.12).abs() < 0.01);
+    assert!((person.confidence - 2.5 / 4.0).abs() < 1e-6);
+    // Seen by one model of weight 2 out of 4
+    let car = fused.iter().find(|d| d.class_name == "car").unwrap();
+    assert!((car.confidence - 1.2 / 4.0).abs() < 1e-6);
+
+    // One model's overlapping boxes are not a majority on their own
+    let doubled = vec![
+        MemberOutput { weight: 1.0, detections: vec![detection("person", 0.0, 0.0, 0.9), detection("person", 2.0, 0.0, 0.8)] },
+        MemberOutput { weight: 1.0, detections: vec![] },
+    ];
+    assert!(merge(EnsembleStrategy::MajorityVote, &doubled, 0.5, None).is_empty());
+}
+
+#[tokio::test]
+async fn test_cascade_gates_heavy_model() -> Result<(), Box<dyn std::error::Error>> {
+    use std::sync::{Arc, Mutex};
+    use async_trait::async_trait;
+    use opencv::core::{Mat, Scalar, CV_8UC3};
+    use vae::vision::cascade::{plan_regions, Cascade, CascadeConfig, CascadeGate, CascadeMode, CascadePlan};
+    use vae::vision::detector::DetectorBuilder;
+    use vae::vision::processor::{Frame, FrameMetadata};
+    use vae::vision::shadow::DetectionModel;
+
+    let config = CascadeConfig {
+        gate: CascadeGate::Detector {
+            detector: DetectorBuilder::new().onnx_model("gate", "models/gate.onnx", (320, 320), vec!["person".to_string()]).into_config()?,
+            classes: vec!["person".to_string()],
+        },
+        hold_frames: 1,
+        keyframe_interval: None,
+        ..Default::default()
+    };
+    let frame_size = (640.0, 480.0);
+
+    // Small boxes are padded and grown; touching ones share a crop
+    let bbox = |x: f32, y: f32| BBox { x, y, width: 40.0, height: 80.0 };
+    assert!(matches!(plan_regions(&[], frame_size, &config), CascadePlan::Skip));
+    let CascadePlan::Regions(regions) = plan_regions(&[bbox(10.0, 10.0), bbox(60.0, 20.0), bbox(500.0, 300.0)], frame_size, &config) else {
+        panic!("expected regions");
+    };
+    assert_eq!(regions.len(), 2);
+    assert_eq!((regions[0].x, regions[0].y), (0.0, 0.0));
+    assert!(regions[0].width >= 160.0 && regions[0].height >= 160.0);
+    assert!(regions[1].x + regions[1].width <= 640.0);
+    let busy: Vec<BBox> = [0.0, 250.0, 480.0].iter()
+        .flat_map(|&x| [bbox(x, 0.0), bbox(x, 300.0)])
+        .collect();
+    assert!(matches!(plan_regions(&busy, frame_size, &config), CascadePlan::FullFrame));
+    let full = CascadeConfig { mode: CascadeMode::FullFrame, ..config.clone() };
+    assert!(matches!(plan_regions(&[bbox(10.0, 10.0)], frame_size, &full), CascadePlan::FullFrame));
+
+    struct Gate(Mutex<Vec<Vec<Detection>>>);
+
+    #[async_trait]
+    impl DetectionModel for Gate {
+        async fn detect(&self, _frame: &Frame) -> anyhow::Result<Vec<Detection>> {
+            Ok(self.0.lock().unwrap().pop().unwrap_or_default())
+        }
+    }
+
+    // Finds a car in the middle of whatever it is shown, and records the
+    // sizes it was shown
+    struct Heavy(Mutex<Vec<(u32, u32)>>);
+
+    #[async_trait]
+    impl DetectionModel for Heavy {
+        async fn detect(&self, frame: &Frame) -> anyhow::Result<Vec<Detection>> {
+            self.0.lock().unwrap().push((frame.metadata.width, frame.metadata.height));
+            let (w, h) = (frame.metadata.width as f32, frame.metadata.height as f32);
+            Ok(vec![detection("car", w / 2.0 - 20.0, h / 2.0 - 40.0, 0.9)])
+        }
+    }
+
+    // Popped from the end: a person, then nothing for three frames
+    let gate = Arc::new(Gate(Mutex::new(vec![vec![], vec![], vec![], vec![detection("person", 300.0, 200.0, 0.8)]])));
+    let heavy = Arc::new(Heavy(Mutex::new(Vec::new())));
+    let cascade = Cascade::with_models(config.clone(), Some(gate), heavy.clone())?;
+
+    let frame = Frame {
+        id: 1,
+        timestamp: chrono::Utc::now(),
+        data: Arc::new(Mat::new_rows_cols_with_default(480, 640, CV_8UC3, Scalar::all(0.0))?),
+        metadata: FrameMetadata {
+            width: 640,
+            height: 480,
+            channels: 3,
+            format: "bgr".to_string(),
+            source: "dock".to_string(),
+            privacy_masked: false,
+            stream_id: None,
+        },
+    };
+
+    // The crop is centred on the person, so the car comes back in frame
+    // coordinates there
+    let detections = cascade.detect(&frame).await?;
+    assert_eq!(detections.len(), 1);
+    assert!((detections[0].bbox.x - 300.0).abs() < 1.0);
+    assert!((detections[0].bbox.y - 200.0).abs() < 1.0);
+    // Held for one quiet frame, then skipped
+    assert_eq!(cascade.detect(&frame).await?.len(), 1);
+    assert!(cascade.detect(&frame).await?.is_empty());
+    assert!(cascade.detect(&frame).await?.is_empty());
+    assert_eq!(heavy.0.lock().unwrap().as_slice(), &[(160, 160), (160, 160)]);
+
+    let stats = cascade.stats().await;
+    assert_eq!((stats.frames, stats.regions, stats.skipped, stats.full_frame), (4, 2, 2, 0));
+    assert!(stats.pixels_saved > 0.9);
+
+    // Gate state is kept for max_sources cameras; one pushed out starts
+    // over, its first frame counting as motion everywhere
+    let motion = Cascade::with_models(
+        CascadeConfig { max_sources: 1, hold_frames: 0, keyframe_interval: None, ..CascadeConfig::default() },
+        None,
+        heavy.clone(),
+    )?;
+    let from = |source: &str| Frame {
+        metadata: FrameMetadata { source: source.to_string(), ..frame.metadata.clone() },
+        ..frame.clone()
+    };
+    motion.detect(&from("dock")).await?;
+    motion.detect(&from("dock")).await?;
+    motion.detect(&from("gate")).await?;
+    motion.detect(&from("dock")).await?;
+    let stats = motion.stats().await;
+    assert_eq!((stats.full_frame, stats.skipped), (3, 1));
+
+    // A detector gate needs its model
+    assert!(Cascade::with_models(config, None, heavy).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_clip_buffer_and_action_selection() -> Result<(), Box<dyn std::error::Error>> {
+    use std::sync::Arc;
+    use opencv::core::{Mat, Scalar, CV_8UC3};
+    use vae::vision::actions::{select_actions, to_activities, ActionLabel, ActionModelConfig, ActionTrack, Clip, ClipBuffer};
+    use vae::vision::processor::{Frame, FrameMetadata};
+
+    let config: ActionModelConfig = serde_json::from_value(serde_json::json!({
+        "model_path": "models/x3d_m.onnx",
+        "labels": ["walking", "running", "fighting"],
+        "input_size": [32, 32],
+        "clip_length": 4,
+        "frame_stride": 2,
+    }))?;
+    let mut buffer = ClipBuffer::new(&config);
+    let start = chrono::Utc::now();
+    let frame = |id: u64| -> opencv::Result<Frame> {
+        Ok(Frame {
+            id,
+            timestamp: start + chrono::Duration::milliseconds(40 * id as i64),
+            data: Arc::new(Mat::new_rows_cols_with_default(120, 160, CV_8UC3, Scalar::all(0.0))?),
+            metadata: FrameMetadata {
+                width: 160,
+                height: 120,
+                channels: 3,
+                format: "bgr".to_string(),
+                source: "yard".to_string(),
+                privacy_masked: false,
+                stream_id: None,
+            },
+        })
+    };
+
+    // Every other frame is kept; the first clip comes once four are in,
+    // then every two sampled frames after that
+    let mut clips = Vec::new();
+    for id in 0..12 {
+        if let Some(clip) = buffer.push(&frame(id)?)? {
+            clips.push((id, clip));
+        }
+    }
+    assert_eq!(clips.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![6, 10]);
+    let (_, clip) = &clips[0];
+    assert_eq!(clip.frames.len(), 4);
+    assert_eq!((clip.frames[0].cols(), clip.frames[0].rows()), (32, 32));
+    assert!((clip.duration() - 0.24).abs() < 1e-6);
+    assert_eq!(buffer.len(), 4);
+
+    let labels: Vec<String> = config.labels.clone();
+    let actions = select_actions(&[2.0, 0.5, -1.0], &labels, 0.1, 3);
+    assert_eq!(actions.iter().map(|a| a.label.as_str()).collect::<Vec<_>>(), vec!["walking", "running"]);
+    assert!((actions.iter().map(|a| a.confidence).sum::<f32>() - 0.96).abs() < 0.01);
+    assert_eq!(select_actions(&[2.0, 0.5, -1.0], &labels, 0.1, 1).len(), 1);
+
+    let activities = to_activities(&actions, clip, &[detection("person", 0.0, 0.0, 0.9), detection("person", 50.0, 0.0, 0.8)]);
+    assert_eq!(activities[0].action_type, "walking");
+    assert_eq!(activities[0].objects_involved, vec!["person".to_string()]);
+    assert!((activities[0].duration - 0.24).abs() < 1e-6);
+
+    // A track reports its last clip's activities until they are
+    // activity_ttl_secs old; a stall that long starts the clip over
+    let walking = |_: &Clip| -> anyhow::Result<Vec<ActionLabel>> {
+        Ok(vec![ActionLabel { label: "walking".to_string(), confidence: 0.9 }])
+    };
+    let mut track = ActionTrack::new(&config);
+    let reported = (0..8)
+        .map(|id| Ok(track.observe(&frame(id)?, &[], walking)?.len()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    assert_eq!(reported, vec![0, 0, 0, 0, 0, 0, 1, 1]);
+    assert!(track.observe(&frame(250)?, &[], walking)?.is_empty());
+    let resumed = (251..258)
+        .map(|id| Ok(track.observe(&frame(id)?, &[], walking)?.len()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    assert_eq!(resumed, vec![0, 0, 0, 0, 0, 1, 1]);
+    Ok(())
+}
+
+#[test]
+fn test_detection_interpolation_between_inference_frames() -> Result<(), Box<dyn std::error::Error>> {
+    use std::sync::Arc;
+    use opencv::core::Mat;
+    use vae::vision::interpolate::{DetectionInterpolator, InterpolationConfig, InterpolationMethod};
+    use vae::vision::processor::{Frame, FrameMetadata};
+
+    let config = InterpolationConfig {
+        enabled: true,
+        inference_interval: 3,
+        method: InterpolationMethod::Velocity,
+        max_gap: 4,
+        confidence_decay: 0.9,
+        ..Default::default()
+    };
+    // Every third frame of the source goes to inference, whatever its id
+    let mut schedule = DetectionInterpolator::new(config.clone());
+    let keyframes: Vec<bool> = (0..7).map(|_| schedule.next_is_keyframe()).collect();
+    assert_eq!(keyframes, vec![true, false, false, true, false, false, true]);
+    let mut disabled = DetectionInterpolator::new(InterpolationConfig::default());
+    assert!(disabled.next_is_keyframe() && disabled.next_is_keyframe());
+
+    let frame = |id: u64| Frame {
+        id,
+        timestamp: chrono::Utc::now(),
+        data: Arc::new(Mat::default()),
+        metadata: FrameMetadata {
+            width: 640,
+            height: 480,
+            channels: 3,
+            format: "bgr".to_string(),
+            source: "gate".to_string(),
+            privacy_masked: false,
+            stream_id: None,
+        },
+    };
+    let at = |x: f32, frame_id: u64| Detection { frame_id, ..detection("person", x, 100.0, 0.8) };
+
+    let mut interpolator = DetectionInterpolator::new(config);
+    interpolator.keyframe(&frame(0), &[at(100.0, 0)])?;
+    // Nothing to measure velocity from yet, so the box holds still
+    let held = interpolator.predict(&frame(1))?;
+    assert_eq!(held[0].bbox.x, 100.0);
+    assert_eq!(held[0].frame_id, 1);
+    assert!((held[0].confidence - 0.72).abs() < 1e-6);
+
+    // 12 px over three frames is 4 px a frame
+    interpolator.keyframe(&frame(3), &[at(112.0, 3)])?;
+    let moved = interpolator.predict(&frame(5))?;
+    assert_eq!(moved.len(), 1);
+    assert!((moved[0].bbox.x - 120.0).abs() < 1e-4);
+    assert_eq!(moved[0].class_name, "person");
+
+    // Carried no further than max_gap frames
+    assert!(interpolator.predict(&frame(8))?.is_empty());
+
+    // An object that disappears from inference disappears here too
+    interpolator.keyframe(&frame(6), &[])?;
+    assert!(interpolator.predict(&frame(7))?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_zone_suggestions_from_traffic() {
+    use vae::vision::tracker::{PathPoint, PathSummary, TrackSummary};
+    use vae::vision::zone_suggest::{SuggestionKind, TrafficObserver, ZoneSuggestConfig};
+    use vae::vision::zones::LineCounter;
+
+    let track = |id: u64, class_name: &str, from: (f32, f32), to: (f32, f32), secs: f64, hits: u32| {
+        let steps = 15;
+        let waypoints: Vec<PathPoint> = (0..=steps)
+            .map(|i| {
+                let t = i as f32 / steps as f32;
+                PathPoint { x: from.0 + (to.0 - from.0) * t, y: from.1 + (to.1 - from.1) * t, frame_id: i as u64 }
+            })
+            .collect();
+        let now = chrono::Utc::now();
+        TrackSummary {
+            track_id: id,
+            source: "lot".to_string(),
+            class_name: class_name.to_string(),
+            first_seen: now - chrono::Duration::seconds(secs as i64),
+            last_seen: now,
+            duration_secs: secs,
+            hits,
+            bbox: BBox { x: to.0 - 20.0, y: to.1 - 40.0, width: 40.0, height: 80.0 },
+            path: PathSummary {
+                start: waypoints[0],
+                end: waypoints[steps],
+                distance: (to.0 - from.0).hypot(to.1 - from.1),
+                waypoints,
+            },
+            snapshot: None,
+        }
+    };
+
+    let mut observer = TrafficObserver::new(ZoneSuggestConfig::default());
+    // Too short-lived to count
+    assert!(!observer.observe(&track(0, "person", (20.0, 200.0), (620.0, 200.0), 10.0, 2)));
+
+    // People crossing left to right and back along one walkway
+    for id in 1..=25 {
+        let (from, to) = if id % 2 == 0 { ((20.0, 200.0), (620.0, 200.0)) } else { ((620.0, 200.0), (20.0, 200.0)) };
+        assert!(observer.observe(&track(id, "person", from, to, 20.0, 30)));
+        if id == 10 {
+            assert!(observer.suggest().is_empty(), "below min_tracks");
+        }
+    }
+    // Cars standing in one spot for ten minutes
+    for id in 26..=28 {
+        assert!(observer.observe(&track(id, "car", (800.0, 400.0), (805.0, 400.0), 600.0, 500)));
+    }
+    assert_eq!(observer.tracks(), 28);
+
+    let suggestions = observer.suggest();
+    let of = |kind| suggestions.iter().filter(|s| s.kind == kind).collect::<Vec<_>>();
+
+    // Both ends of the walkway, ground points at y = 240
+    let entrances = of(SuggestionKind::Entrance);
+    assert_eq!(entrances.len(), 2);
+    assert!(entrances.iter().any(|s| s.zone.contains(20.0, 250.0)));
+    assert!(entrances.iter().any(|s| s.zone.contains(620.0, 250.0)));
+    for entrance in &entrances {
+        assert_eq!(entrance.support, 25);
+        entrance.zone.validate().unwrap();
+        // Travel is horizontal, so the counting line is vertical
+        let line = entrance.line.as_ref().expect("entrance line");
+        assert!((line.start.x - line.end.x).abs() < 1e-3);
+        assert!((line.start.y - line.end.y).abs() > 30.0);
+        assert!(line.crosses((line.start.x - 10.0, 260.0), (line.start.x + 10.0, 260.0)));
+    }
+
+    // Accepted lines count tracks over them, one way or the other
+    let line = entrances[0].line.clone().unwrap();
+    let lines = vec![line.clone()];
+    let at = |x: f32| BBox { x: x - 20.0, y: 180.0, width: 40.0, height: 80.0 };
+    let mut counter = LineCounter::default();
+    assert!(counter.update(1, &at(line.start.x - 30.0), &lines).is_empty());
+    let crossed = counter.update(1, &at(line.start.x + 30.0), &lines);
+    assert_eq!(crossed.len(), 1);
+    let back = counter.update(1, &at(line.start.x - 30.0), &lines);
+    assert_ne!(back[0].1, crossed[0].1);
+    assert!(counter.update(1, &at(line.start.x - 40.0), &lines).is_empty());
+    // A forgotten track starts over
+    counter.forget(1);
+    assert!(counter.update(1, &at(line.start.x + 30.0), &lines).is_empty());
+
+    let pathways = of(SuggestionKind::Pathway);
+    assert_eq!(pathways.len(), 1);
+    assert_eq!(pathways[0].support, 25);
+    assert!(pathways[0].zone.contains(320.0, 250.0));
+    assert!(!pathways[0].zone.contains(320.0, 100.0));
+    assert!(pathways[0].line.is_none());
+
+    let parking = of(SuggestionKind::Parking);
+    assert_eq!(parking.len(), 1);
+    assert_eq!(parking[0].support, 3);
+    assert!(parking[0].zone.contains(810.0, 450.0));
+    assert_eq!(parking[0].zone.labels, vec!["parking".to_string()]);
+}
+
+#[tokio::test]
+async fn test_failed_frames_dead_lettered_and_reinjected() -> Result<(), Box<dyn std::error::Error>> {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+    use opencv::core::Mat;
+    use vae::core::dead_letter::{DeadLetterConfig, DeadLetterFilter, DeadLetterQueue};
+    use vae::core::pipeline::{Pipeline, PipelineConfig, PipelineData, PipelineStage, StageType};
+    use vae::core::watchdog::WatchdogConfig;
+    use vae::vision::processor::{Frame, FrameMetadata};
+
+    struct FlakyModel {
+        failing: AtomicBool,
+        calls: AtomicU32,
+    }
+
+    #[async_trait::async_trait]
+    impl PipelineStage for FlakyModel {
+        async fn process(&self, mut input: PipelineData) -> anyhow::Result<PipelineData> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(anyhow::anyhow!("model server unavailable"));
+            }
+            input.detections.push(detection("person", 10.0, 10.0, 0.9));
+            Ok(input)
+        }
+        fn stage_type(&self) -> StageType { StageType::Detection }
+        fn name(&self) -> String { "detect".to_string() }
+    }
+
+    // Stands in for a tracker; counts its passes over the frame
+    struct Tagger;
+
+    #[async_trait::async_trait]
+    impl PipelineStage for Tagger {
+        async fn process(&self, mut input: PipelineData) -> anyhow::Result<PipelineData> {
+            let seen = input.metadata.get("tagged").map_or(0, |n| n.parse::<u32>().unwrap() + 1);
+            input.metadata.insert("tagged".to_string(), seen.to_string());
+            Ok(input)
+        }
+        fn stage_type(&self) -> StageType { StageType::PreProcess }
+        fn name(&self) -> String { "tag".to_string() }
+        fn is_stateful(&self) -> bool { true }
+    }
+
+    let model = Arc::new(FlakyModel { failing: AtomicBool::new(true), calls: AtomicU32::new(0) });
+    let mut pipeline = Pipeline::with_stages(PipelineConfig {
+        stages: Vec::new(),
+        max_parallel_stages: 1,
+        buffer_size: 8,
+        timeout_ms: 5000,
+        retry_count: 2,
+        retry_backoff_ms: 1,
+        retry_backoff_max_ms: 2,
+        watchdog: WatchdogConfig { enabled: false, ..Default::default() },
+        sinks: Vec::new(),
+        dead_letters: Some(DeadLetterConfig::default()),
+        outbox: None,
+        similarity: None,
+        models: Default::default(),
+    }, vec![Arc::new(Tagger) as Arc<dyn PipelineStage>, model.clone() as Arc<dyn PipelineStage>]).await?;
+    let queue = pipeline.dead_letters().await.unwrap();
+    pipeline.start().await?;
+
+    pipeline.process(Frame {
+        id: 7,
+        timestamp: chrono::Utc::now(),
+        data: Arc::new(Mat::default()),
+        metadata: FrameMetadata {
+            width: 640,
+            height: 480,
+            channels: 3,
+            format: "bgr".to_string(),
+            source: "dock".to_string(),
+            privacy_masked: false,
+            stream_id: None,
+        },
+    }).await?;
+
+    let mut entries = Vec::new();
+    for _ in 0..100 {
+        entries = queue.list(&DeadLetterFilter::default(), 10).await;
+        if !entries.is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(entries.len(), 1);
+    assert_eq!((entries[0].frame_id, entries[0].stream.as_str()), (7, "dock"));
+    assert_eq!((entries[0].stage.as_str(), entries[0].stage_index), ("detect", 1));
+    assert_eq!(entries[0].attempts, 3);
+    assert!(entries[0].error.contains("model server unavailable"));
+    assert_eq!(model.calls.load(Ordering::SeqCst), 3);
+
+    // Fails again: the same entry is updated
+    queue.reinject(&entries[0].id).await?;
+    while model.calls.load(Ordering::SeqCst) < 6 {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    let stats = queue.stats().await;
+    assert_eq!(stats.entries, 1);
+    assert_eq!(queue.get(&entries[0].id).await.unwrap().reinjections, 1);
+
+    model.failing.store(false, Ordering::SeqCst);
+    queue.reinject(&entries[0].id).await?;
+    let result = tokio::time::timeout(std::time::Duration::from_secs(2), pipeline.get_result()).await?.unwrap();
+    assert_eq!(result.frame.id, 7);
+    assert_eq!(result.detections.len(), 1);
+    // Resumed at the failing stage, without passing the stateful one again
+    assert_eq!(result.metadata["tagged"], "0");
+    assert!(!result.metadata.contains_key("dead_letter_id"));
+    assert!(!result.metadata.contains_key("dead_letter_stage"));
+    assert!(queue.get(&entries[0].id).await.is_none());
+    pipeline.stop().await?;
+
+    // Held frames are bounded by their pixels as well as their count
+    let small = DeadLetterQueue::new(DeadLetterConfig { max_bytes: 500, ..Default::default() });
+    let pixels = Mat::new_rows_cols_with_default(10, 10, opencv::core::CV_8UC3, opencv::core::Scalar::all(0.0))?;
+    for _ in 0..2 {
+        let mut data = result.clone();
+        data.frame.data = Arc::new(pixels.try_clone()?);
+        small.push(data, 1, "detect", "model server unavailable", 1).await;
+    }
+    let stats = small.stats().await;
+    assert_eq!((stats.entries, stats.bytes, stats.evicted), (1, 300, 1));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_outbox_retries_and_deduplicates_sink_deliveries() -> Result<(), Box<dyn std::error::Error>> {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use opencv::core::Mat;
+    use vae::core::outbox::{OutboxConfig, OutboxStatus, SinkOutbox};
+    use vae::core::pipeline::PipelineData;
+    use vae::core::sinks::{delivery_id, ResultSink};
+    use vae::vision::processor::{Frame, FrameMetadata};
+
+    struct Recorder {
+        name: String,
+        up: AtomicBool,
+        received: Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ResultSink for Recorder {
+        fn name(&self) -> String { self.name.clone() }
+
+        async fn write(&self, _data: &PipelineData) -> anyhow::Result<()> {
+            Err(anyhow::anyhow!("{} only takes outbox deliveries", self.name))
+        }
+
+        async fn deliver(&self, delivery_id: &str, record: &serde_json::Value) -> anyhow::Result<()> {
+            if !self.up.load(Ordering::SeqCst) {
+                return Err(anyhow::anyhow!("{} unreachable", self.name));
+            }
+            assert_eq!(record["delivery_id"], delivery_id);
+            self.received.lock().unwrap().push(delivery_id.to_string());
+            Ok(())
+        }
+    }
+
+    let recorder = |name: &str, up: bool| Arc::new(Recorder {
+        name: name.to_string(),
+        up: AtomicBool::new(up),
+        received: Mutex::new(Vec::new()),
+    });
+    let kafka = recorder("kafka:results", false);
+    let webhook = recorder("webhook", true);
+
+    let outbox = SinkOutbox::open_in_memory(OutboxConfig { initial_backoff_ms: 0, max_attempts: 3, ..Default::default() })?;
+    outbox.register(kafka.clone()).await;
+    outbox.register(webhook.clone()).await;
+
+    let data = PipelineData {
+        frame: Frame {
+            id: 42,
+            timestamp: chrono::Utc::now(),
+            data: Arc::new(Mat::default()),
+            metadata: FrameMetadata {
+                width: 640,
+                height: 480,
+                channels: 3,
+                format: "bgr".to_string(),
+                source: "gate".to_string(),
+                privacy_masked: false,
+                stream_id: None,
+            },
+        },
+        detections: vec![detection("car", 5.0, 5.0, 0.8)],
+        analysis: None,
+        metadata: HashMap::new(),
+        timestamp: chrono::Utc::now(),
+    };
+
+    // Kafka is down: the webhook gets it, Kafka's copy waits
+    let outcome = outbox.submit(&data).await?;
+    assert_eq!((outcome.enqueued, outcome.delivered, outcome.duplicates), (2, 1, 0));
+    let stats = outbox.stats()?;
+    assert_eq!((stats.pending, stats.delivered), (1, 1));
+    assert!(stats.oldest_pending_at.is_some());
+
+    // The same frame again is recognised and not sent anywhere
+    let outcome = outbox.submit(&data).await?;
+    assert_eq!((outcome.enqueued, outcome.duplicates), (0, 2));
+
+    assert_eq!(outbox.dispatch_due().await?, 0);
+    kafka.up.store(true, Ordering::SeqCst);
+    // Two dispatchers at once: the row is claimed by one of them
+    let (first, second) = tokio::join!(outbox.dispatch_due(), outbox.dispatch_due());
+    assert_eq!(first? + second?, 1);
+    assert_eq!(outbox.dispatch_due().await?, 0);
+
+    let id = delivery_id(&data);
+    assert_eq!(*kafka.received.lock().unwrap(), vec![id.clone()]);
+    assert_eq!(*webhook.received.lock().unwrap(), vec![id]);
+
+    // Out of attempts, then put back by hand
+    kafka.up.store(false, Ordering::SeqCst);
+    let mut later = data.clone();
+    later.frame.id = 43;
+    outbox.submit(&later).await?;
+    for _ in 0..5 {
+        outbox.dispatch_due().await?;
+    }
+    let failed = outbox.list(OutboxStatus::Failed, 10)?;
+    assert_eq!(failed.len(), 1);
+    assert_eq!((failed[0].sink.as_str(), failed[0].attempts), ("kafka:results", 3));
+    assert!(failed[0].last_error.as_deref().unwrap_or("").contains("unreachable"));
+
+    kafka.up.store(true, Ordering::SeqCst);
+    assert_eq!(outbox.retry_failed(Some("kafka:results"))?, 1);
+    assert_eq!(outbox.dispatch_due().await?, 1);
+    assert_eq!(outbox.stats()?.delivered, 4);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_engine_keeps_processing_without_a_result_reader() -> Result<(), Box<dyn std::error::Error>> {
+    use opencv::core::Mat;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use vae::core::engine::{Engine, EngineEvent};
+    use vae::vision::processor::{Frame, FrameMetadata};
+
+    let mut engine = Engine::builder().gpu(false).analytics(false).threads(1).batch_size(2).build().await?;
+    let mut events = engine.subscribe();
+    engine.start().await?;
+
+    // Nobody calls get_result(), so its channel fills after two frames
+    for id in 0..10 {
+        let frame = Frame {
+            id,
+            timestamp: chrono::Utc::now(),
+            data: Arc::new(Mat::default()),
+            metadata: FrameMetadata {
+                width: 64,
+                height: 48,
+                channels: 3,
+                format: "bgr".to_string(),
+                source: "test".to_string(),
+                privacy_masked: false,
+                stream_id: None,
+            },
+        };
+        tokio::time::timeout(Duration::from_secs(2), engine.process_frame(frame)).await??;
+    }
+
+    let mut processed = 0;
+    while processed < 10 {
+        if let EngineEvent::FrameProcessed { .. } = tokio::time::timeout(Duration::from_secs(2), events.recv()).await?? {
+            processed += 1;
+        }
+    }
+    engine.stop().await?;
+    Ok(())
+}